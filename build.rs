@@ -0,0 +1,63 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rustc-env=OWL_GIT_COMMIT={}", git_commit());
+    println!("cargo:rustc-env=OWL_BUILD_DATE={}", build_date());
+    for path in git_watch_paths() {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
+/// Files whose change means a new commit: HEAD, and when it names a branch, that
+/// branch's ref and packed-refs. Missing ones are left out, since cargo would rerun
+/// the script on every build for them.
+fn git_watch_paths() -> Vec<String> {
+    let mut paths = vec![".git/HEAD".to_string()];
+    if let Ok(head) = std::fs::read_to_string(".git/HEAD")
+        && let Some(reference) = head.strip_prefix("ref: ")
+    {
+        paths.push(format!(".git/{}", reference.trim()));
+        paths.push(".git/packed-refs".to_string());
+    }
+    paths.retain(|path| std::path::Path::new(path).exists());
+    paths
+}
+
+/// Short commit hash, or "unknown" when building from a tarball without git
+fn git_commit() -> String {
+    Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Build date as YYYY-MM-DD, honoring SOURCE_DATE_EPOCH for reproducible builds
+fn build_date() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
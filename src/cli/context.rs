@@ -0,0 +1,119 @@
+//! Resources shared by a command, loaded the first time it asks for them
+//!
+//! Arguments are parsed before any of this exists, so `owl --help` or a mistyped
+//! subcommand never reads the config, the state, or the package database. Each
+//! command declares what it [`Needs`]; debug builds catch undeclared loads.
+
+use anyhow::{Context as _, Result};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use crate::core::config::Config;
+use crate::core::state::PackageState;
+
+/// What a command may load through its [`Context`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Needs {
+    pub config: bool,
+    pub state: bool,
+    /// The names of the installed packages, from one database query
+    pub installed: bool,
+}
+
+impl Needs {
+    /// For commands that load what they need themselves
+    pub const NOTHING: Self = Self {
+        config: false,
+        state: false,
+        installed: false,
+    };
+}
+
+pub struct Context {
+    needs: Needs,
+    config: OnceLock<Config>,
+    state: OnceLock<PackageState>,
+    installed: OnceLock<HashSet<String>>,
+}
+
+fn get_or_load<'a, T>(
+    cell: &'a OnceLock<T>,
+    declared: bool,
+    what: &str,
+    load: impl FnOnce() -> Result<T>,
+) -> Result<&'a T> {
+    debug_assert!(declared, "command loaded {} without declaring it", what);
+    if let Some(value) = cell.get() {
+        return Ok(value);
+    }
+    let value = load()?;
+    Ok(cell.get_or_init(|| value))
+}
+
+impl Context {
+    pub fn new(needs: Needs) -> Self {
+        Self {
+            needs,
+            config: OnceLock::new(),
+            state: OnceLock::new(),
+            installed: OnceLock::new(),
+        }
+    }
+
+    /// The merged config for this host
+    pub fn config(&self) -> Result<&Config> {
+        get_or_load(&self.config, self.needs.config, "config", || {
+            Config::load_all_relevant_config_files().context("Failed to load config")
+        })
+    }
+
+    pub fn state(&self) -> Result<&PackageState> {
+        get_or_load(&self.state, self.needs.state, "state", || {
+            PackageState::load().context("Failed to load package state")
+        })
+    }
+
+    /// Copies of the config and state, for work handed to another thread, such as
+    /// the one a progress spinner runs
+    pub fn owned_config_and_state(&self) -> Result<(Config, PackageState)> {
+        Ok((self.config()?.clone(), self.state()?.clone()))
+    }
+
+    /// Installed package names; list and export need nothing else from the database,
+    /// so they skip the explicit and foreign queries of a full `SystemSnapshot`
+    pub fn installed(&self) -> Result<&HashSet<String>> {
+        get_or_load(
+            &self.installed,
+            self.needs.installed,
+            "the installed packages",
+            || {
+                crate::core::package::get_installed_packages()
+                    .context("Failed to query installed packages")
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_get_or_load_runs_the_loader_once() {
+        let cell = OnceLock::new();
+        let loads = Cell::new(0);
+        let load = || {
+            loads.set(loads.get() + 1);
+            Ok(42)
+        };
+        assert_eq!(*get_or_load(&cell, true, "answer", load).unwrap(), 42);
+        assert_eq!(*get_or_load(&cell, true, "answer", load).unwrap(), 42);
+        assert_eq!(loads.get(), 1);
+
+        // A failed load leaves the cell empty so a later call can try again
+        let failing = OnceLock::<u32>::new();
+        assert!(get_or_load(&failing, true, "answer", || Err(anyhow::anyhow!("no"))).is_err());
+        assert!(failing.get().is_none());
+    }
+}
@@ -1,17 +1,32 @@
-use crate::commands::{add, adopt, apply, dots, edit, find};
+use crate::cli::context::{Context, Needs};
+use crate::commands::{
+    add, adopt, apply, bootstrap, deps, diff, doctor, dots, edit, exec, export, find, fleet,
+    history, ignore, info, init, integrate, list, migrate, orphans, outdated, pin, prompt, remove,
+    schedule, search, snapshot, state, stats, status, sync, test_apply, ui, untrack, verify,
+    version, why,
+};
 use crate::error::exit_on_error;
 use crate::internal::color;
 use crate::internal::constants;
+use crate::internal::logging::LogCommand;
 use clap::{Parser, Subcommand};
 use std::process::{Command, Stdio};
 
 /// Global options for the CLI
 #[derive(Debug, Clone, Parser)]
-#[command(name = "owl", about = "Dotfile and package manager")]
+#[command(
+    name = "owl",
+    about = "Dotfile and package manager",
+    disable_version_flag = true
+)]
 pub struct Cli {
-    /// Enable verbose output
-    #[arg(short, long)]
-    pub verbose: bool,
+    /// Print version and build information
+    #[arg(short = 'V', long)]
+    pub version: bool,
+
+    /// Enable verbose output (-vv also prints debug logs; see OWL_LOG)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
 
     /// Perform a dry run without making changes
     #[arg(long)]
@@ -21,28 +36,104 @@ pub struct Cli {
     #[arg(short = 'y', long)]
     pub non_interactive: bool,
 
+    /// Do not page long output through $PAGER
+    #[arg(long)]
+    pub no_pager: bool,
+
+    /// Mark status lines with ASCII like [ok] and [!] instead of symbols
+    #[arg(long)]
+    pub ascii: bool,
+
+    /// Use this directory instead of ~/.owl for config, dotfiles, and settings
+    #[arg(long, value_name = "DIR")]
+    pub config_dir: Option<std::path::PathBuf>,
+
+    /// Keep package state in this JSON file instead of ~/.owl/.state
+    #[arg(long, value_name = "PATH")]
+    pub state_file: Option<std::path::PathBuf>,
+
+    /// Answer prompts from this file, one answer per line, instead of the terminal
+    #[arg(long, value_name = "PATH")]
+    pub answers_file: Option<std::path::PathBuf>,
+
+    /// Check the AUR for updates even when a recent result is cached
+    #[arg(long)]
+    pub refresh_aur: bool,
+
+    /// Write JSON events to stdout and human output to stderr
+    #[arg(long)]
+    pub porcelain: bool,
+
+    /// Do not commit config edits even when `git_autocommit` is set
+    #[arg(long)]
+    pub no_commit: bool,
+
+    /// Skip network checks that are not needed to apply: the Arch news, the AUR
+    /// update lookup, and the AUR check of install names
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Mark unread Arch news as read and let upgrades go ahead
+    #[arg(long)]
+    pub ack_news: bool,
+
+    /// Wait for another apply or adopt to finish instead of failing
+    #[arg(long)]
+    pub wait: bool,
+
+    /// Seconds to wait for another package manager to release pacman's database
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    pub db_lock_wait: u64,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
-/// Edit target types for better type safety
-#[derive(Debug, Clone, PartialEq, clap::ValueEnum)]
-pub enum EditTarget {
-    Dots,
-    Config,
-}
-
 /// Available commands for the CLI
 #[derive(Debug, Clone, Subcommand)]
 pub enum Commands {
     /// Apply configuration (default command)
-    Apply,
-    /// Edit dotfiles or config
+    Apply {
+        /// Only install, update, and remove packages
+        #[arg(long, conflicts_with = "dotfiles_only")]
+        packages_only: bool,
+        /// Only sync dotfiles, with no package work or network checks
+        #[arg(long)]
+        dotfiles_only: bool,
+        /// Show the whole plan and ask once before changing anything
+        #[arg(long)]
+        plan: bool,
+        /// Do not check with the AUR that install names the repos lack exist there
+        #[arg(long)]
+        no_network_check: bool,
+        /// Apply only these configured packages, without updating or removing others
+        #[arg(long, value_name = "NAME", num_args = 1..)]
+        only: Vec<String>,
+        /// Remove orphaned dependencies after the package changes
+        #[arg(long)]
+        clean_orphans: bool,
+        /// Stop at the first error instead of carrying on with the other phases
+        #[arg(long)]
+        fail_fast: bool,
+        /// Install missing packages without upgrading the system
+        #[arg(long)]
+        no_upgrade: bool,
+        /// Forget managed packages that are neither declared nor installed, without asking
+        #[arg(long)]
+        prune_state: bool,
+        /// Only build the AUR packages whose last build failed
+        #[arg(long, conflicts_with_all = ["only", "dotfiles_only", "plan"])]
+        retry_failed: bool,
+    },
+    /// Open the main config, a package's declaration, a config file, or a dotfile in $EDITOR
     Edit {
-        /// Type to edit (dots or config)
-        target: EditTarget,
-        /// Argument for edit
-        argument: String,
+        /// Package or config file to open, or `dots`/`config` followed by a name
+        target: Option<String>,
+        /// Name for the `dots` and `config` forms
+        argument: Option<String>,
+        /// Create groups/<NAME>.owl and open it
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["target", "argument"])]
+        new: Option<String>,
     },
     /// List dotfiles
     Dots,
@@ -58,9 +149,37 @@ pub enum Commands {
     Adopt {
         /// Packages to adopt
         items: Vec<String>,
+        /// Also adopt the packages listed in this file, one per line, or `-` for stdin
+        #[arg(long, value_name = "FILE")]
+        from: Option<String>,
         /// Discover explicitly installed unmanaged packages for adoption
         #[arg(long)]
         all: bool,
+        /// Adopt every candidate without prompting
+        #[arg(long)]
+        yes: bool,
+        /// Config file to add adopted packages to, relative to the owl directory
+        /// (default with --yes: main.owl)
+        #[arg(long, value_name = "PATH")]
+        to: Option<String>,
+        /// Also offer packages that configured or managed packages depend on
+        #[arg(long)]
+        include_deps: bool,
+        /// Write foreign (AUR) packages under an @aur section
+        #[arg(long)]
+        aur_section: bool,
+        /// Offer to copy each package's ~/.config directory into the dotfiles
+        #[arg(long)]
+        with_configs: bool,
+        /// Write the session's decisions to this file as JSON
+        #[arg(long, value_name = "PATH")]
+        report: Option<String>,
+        /// Then review orphaned dependencies: adopt, remove, or skip each
+        #[arg(long)]
+        orphans: bool,
+        /// Write to config files even when they do not parse
+        #[arg(long)]
+        force: bool,
     },
     /// Find packages or files
     Find {
@@ -74,10 +193,334 @@ pub enum Commands {
     },
     /// Show host configuration
     ConfigHost,
-    /// Clean up files
+    /// Tidy config files, or free disk space with --disk or a category flag
     Clean {
-        /// Specific filename to clean
+        /// Tidy only this config file
         filename: Option<String>,
+        /// Free disk space in every category instead of tidying config files
+        #[arg(long, conflicts_with = "filename")]
+        disk: bool,
+        /// Free disk space: old snapshots and state files moved aside by doctor
+        #[arg(long, conflicts_with = "filename")]
+        backups: bool,
+        /// Free disk space: run logs beyond the kept history
+        #[arg(long, conflicts_with = "filename")]
+        logs: bool,
+        /// Free disk space: cached package files beyond the kept versions
+        #[arg(long, conflicts_with = "filename")]
+        pkg_cache: bool,
+        /// Free disk space: AUR helper build dirs of packages that are no longer installed
+        #[arg(long, conflicts_with = "filename")]
+        aur_builds: bool,
+    },
+    /// Import packages and dotfiles from another tool into a new group file
+    MigrateFrom {
+        /// What kind of manifest PATH is
+        #[arg(value_enum)]
+        source: migrate::Source,
+        /// The list file, stow directory, or pacman hook file or directory
+        path: std::path::PathBuf,
+        /// Group to create, defaulting to the source name
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Provision a new machine: clone a config repository, prepare pacman and the AUR, then apply
+    Bootstrap {
+        /// Git URL of the config repository
+        url: String,
+        /// Answer yes to the package manager prompts
+        #[arg(long)]
+        assume_yes: bool,
+    },
+    /// Create the owl directory with a starter config
+    Init {
+        /// List explicitly installed packages in the starter config
+        #[arg(long)]
+        from_system: bool,
+        /// Run `git init` in the owl directory
+        #[arg(long)]
+        git: bool,
+    },
+    /// Install an integration with another tool, or remove them with --remove
+    Integrate {
+        #[arg(value_enum, required_unless_present = "remove")]
+        integration: Option<integrate::Integration>,
+        /// Remove every integration owl installed
+        #[arg(long, conflicts_with = "integration")]
+        remove: bool,
+    },
+    /// Check the environment, state, and config for problems (exit 1 if any check fails)
+    Doctor {
+        /// Apply the safe remediations for failed checks
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Show everything apply would change, as diffs, without changing anything
+    Diff {
+        /// Only changes that belong to this package
+        #[arg(long, value_name = "PKG")]
+        only: Option<String>,
+        /// Only dotfile changes
+        #[arg(long)]
+        dotfiles: bool,
+        /// Only package changes
+        #[arg(long)]
+        packages: bool,
+    },
+    /// Run a command with the configured environment variables applied
+    Exec {
+        /// Print the export lines for `eval` instead of running a command
+        #[arg(long, conflicts_with = "command")]
+        print: bool,
+        /// Command and its arguments, after `--`
+        #[arg(
+            required_unless_present = "print",
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<String>,
+    },
+    /// Show pacman or AUR metadata next to owl's view of a package and its pending changes
+    Info {
+        /// Package to describe
+        package: String,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Apply the config on the machines in `[fleet]` settings over SSH
+    Fleet {
+        #[command(subcommand)]
+        action: FleetAction,
+    },
+    /// Run apply in a throwaway container and report whether it converges
+    TestApply {
+        /// What starts the container (default: [test_apply] runtime, or docker)
+        #[arg(long, value_enum)]
+        runtime: Option<crate::core::settings::SandboxRuntime>,
+        /// Image to start, or the root filesystem directory for nspawn
+        #[arg(long)]
+        image: Option<String>,
+    },
+    /// Print the configured packages as a pkglist, JSON, or a list of AUR packages
+    Export {
+        #[arg(long, value_enum, default_value_t = export::Format::Pkglist)]
+        format: export::Format,
+        /// Use the config as this host sees it
+        #[arg(long, value_name = "NAME")]
+        host: Option<String>,
+        /// Only packages declared in this group (repeatable)
+        #[arg(long = "group", value_name = "GROUP")]
+        groups: Vec<String>,
+        /// Leave out packages declared in this group (repeatable)
+        #[arg(long = "exclude-group", value_name = "GROUP")]
+        exclude_groups: Vec<String>,
+        /// Only packages that are installed
+        #[arg(long)]
+        installed_only: bool,
+    },
+    /// List configured packages and their install status
+    List {
+        /// Only packages that are configured but not installed
+        #[arg(long, conflicts_with = "extra")]
+        missing: bool,
+        /// Only packages managed in state but gone from the config
+        #[arg(long)]
+        extra: bool,
+        /// Only packages that come from the AUR
+        #[arg(long)]
+        aur: bool,
+        /// Use the config as this host sees it
+        #[arg(long, value_name = "NAME")]
+        host: Option<String>,
+        /// Only packages with dotfiles attached
+        #[arg(long)]
+        with_dotfiles: bool,
+        /// Print the list as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Review orphaned dependencies: remove, keep, or adopt each into the config
+    Orphans,
+    /// Hold a package at its installed version so applies skip its updates
+    Pin {
+        /// Package to pin
+        #[arg(required_unless_present = "list")]
+        package: Option<String>,
+        /// List pinned packages and how far behind they are
+        #[arg(long, conflicts_with = "package")]
+        list: bool,
+    },
+    /// Release a pin so the next apply updates the package again
+    Unpin {
+        /// Package to unpin
+        package: String,
+    },
+    /// Add packages to the untracked list so adopt stops offering them
+    Ignore {
+        /// Packages to ignore, installed or not; `*` and `?` globs match installed packages
+        #[arg(required_unless_present = "list")]
+        packages: Vec<String>,
+        /// List ignored packages and whether they are installed
+        #[arg(long, conflicts_with_all = ["packages", "remove"])]
+        list: bool,
+        /// Take the packages off the untracked list instead
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Take packages off the untracked list (`ignore --remove`)
+    Unignore {
+        /// Ignored packages; `*` and `?` globs are allowed
+        #[arg(required_unless_present_any = ["all", "list"])]
+        packages: Vec<String>,
+        /// Take every package off the untracked list
+        #[arg(long, conflicts_with_all = ["packages", "list"])]
+        all: bool,
+        /// List ignored packages and whether they are installed
+        #[arg(long, conflicts_with = "packages")]
+        list: bool,
+    },
+    /// Forget that owl manages packages, without touching the config or the system
+    Untrack {
+        /// Managed packages to forget; `*` and `?` globs are allowed
+        #[arg(required_unless_present = "all_missing")]
+        packages: Vec<String>,
+        /// Also forget every managed package that is not installed
+        #[arg(long)]
+        all_missing: bool,
+        /// Add them to the untracked list so adopt stops offering them
+        #[arg(long)]
+        ignore_future: bool,
+    },
+    /// Stop managing a package and remove it from the config file that declares it
+    Remove {
+        /// Package to remove
+        package: String,
+        /// Leave the package installed without asking
+        #[arg(long, conflicts_with = "purge")]
+        keep_installed: bool,
+        /// Also uninstall the package and clean up its dotfiles and services
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Search repo packages, the AUR, and the config
+    Search {
+        /// Search terms; every term must match
+        #[arg(required = true)]
+        terms: Vec<String>,
+        /// Pick a result and add it to a config file
+        #[arg(long)]
+        add: bool,
+    },
+    /// Show what apply and adopt runs changed, newest first
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+        /// Only runs that touched this package
+        #[arg(long)]
+        package: Option<String>,
+        /// Show at most this many runs
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Run owl unattended from a systemd timer
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+    /// Capture, compare, and restore snapshots of packages, state, and dotfiles
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Maintain owl's package state
+    State {
+        #[command(subcommand)]
+        action: StateAction,
+    },
+    /// Pull the owl directory from its git remote, or commit and push local changes
+    Sync {
+        /// Run apply after a successful pull
+        #[arg(long, conflicts_with = "push")]
+        apply: bool,
+        /// Commit local config changes and push them instead of pulling
+        #[arg(long)]
+        push: bool,
+    },
+    /// List pending repo and AUR updates without applying them or needing sudo
+    Outdated {
+        /// Print the updates as JSON
+        #[arg(long, conflicts_with = "count")]
+        json: bool,
+        /// Print only the number of updates apply would install
+        #[arg(long)]
+        count: bool,
+    },
+    /// Check deployed dotfiles against what owl wrote (exit 1 on any failure)
+    Verify {
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+        /// Redeploy only the files that failed
+        #[arg(long)]
+        restore: bool,
+    },
+    /// Show the dependency tree of configured packages and the dependencies they share
+    Deps {
+        /// Configured packages to show; all of them by default
+        packages: Vec<String>,
+        /// Levels of dependencies to follow
+        #[arg(long)]
+        depth: Option<usize>,
+        /// Print Graphviz dot source instead of a tree
+        #[arg(long)]
+        dot: bool,
+        /// Also follow installed optional dependencies
+        #[arg(long)]
+        optional: bool,
+    },
+    /// Overview numbers about the configured packages, dotfiles, and state
+    Stats {
+        /// Print the numbers as JSON
+        #[arg(long, conflicts_with = "oneline")]
+        json: bool,
+        /// Print a single summary line for status bars
+        #[arg(long)]
+        oneline: bool,
+    },
+    /// Show whether the system matches the config (exit 0 only when in sync)
+    Status {
+        /// Print the status as JSON
+        #[arg(long)]
+        json: bool,
+        /// Skip the AUR update check
+        #[arg(long)]
+        fast: bool,
+    },
+    /// Print a short out-of-sync segment for a shell prompt, from the last status
+    Prompt {
+        /// Check the system and store the result for the next call, printing nothing
+        #[arg(long)]
+        refresh: bool,
+        /// How to write color codes into the segment
+        #[arg(long, value_enum, default_value = "never")]
+        color: prompt::PromptColor,
+    },
+    /// Explain where a package is declared and how it is installed
+    Why {
+        /// Package to explain
+        package: String,
+    },
+    /// Interactive dashboard of pending changes
+    Ui,
+    /// Show version information
+    Version {
+        /// Check whether a newer owl release exists
+        #[arg(long)]
+        check: bool,
+        /// Where to look for new releases
+        #[arg(long, value_enum, default_value = "aur")]
+        source: version::UpdateSource,
     },
     /// Alias for edit dots
     #[command(alias = "de")]
@@ -93,20 +536,227 @@ pub enum Commands {
     },
 }
 
+/// Subcommands of `owl history`
+#[derive(Debug, Clone, Subcommand)]
+pub enum HistoryAction {
+    /// Print the stored record of one run
+    Show {
+        /// Run id from `owl history`
+        id: String,
+    },
+}
+
+/// Which systemd manager `owl schedule` talks to
+#[derive(Debug, Clone, clap::Args)]
+pub struct ScheduleScope {
+    /// Use the per-user manager (the default)
+    #[arg(long, conflicts_with = "system")]
+    pub user: bool,
+    /// Use the system manager; needs root
+    #[arg(long)]
+    pub system: bool,
+}
+
+impl ScheduleScope {
+    fn scope(&self) -> crate::core::schedule::Scope {
+        if self.system {
+            crate::core::schedule::Scope::System
+        } else {
+            crate::core::schedule::Scope::User
+        }
+    }
+}
+
+/// Subcommands of `owl fleet`
+#[derive(Debug, Clone, Subcommand)]
+pub enum FleetAction {
+    /// Sync the config to each host and run apply there, then summarize
+    Apply {
+        /// Only this host (repeatable); every host when neither this nor --tag is given
+        #[arg(long = "host", value_name = "NAME")]
+        hosts: Vec<String>,
+        /// Also the hosts with this tag (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+}
+
+/// Subcommands of `owl schedule`
+#[derive(Debug, Clone, Subcommand)]
+pub enum ScheduleAction {
+    /// Write and start a timer that runs owl with `[schedule] args` from settings
+    Enable {
+        /// How often to run, e.g. 30m, 6h, 1d; defaults to `[schedule] interval`
+        #[arg(long)]
+        interval: Option<String>,
+        #[command(flatten)]
+        scope: ScheduleScope,
+    },
+    /// Stop the timer and remove its units
+    Disable {
+        #[command(flatten)]
+        scope: ScheduleScope,
+    },
+    /// Show the timer and the outcome of the last scheduled run
+    Status {
+        #[command(flatten)]
+        scope: ScheduleScope,
+    },
+}
+
+/// Subcommands of `owl snapshot`
+#[derive(Debug, Clone, Subcommand)]
+pub enum SnapshotAction {
+    /// Capture installed packages, package state, and deployed dotfiles
+    Create {
+        /// Name for the snapshot, e.g. pre-kde-switch
+        name: String,
+    },
+    /// List snapshots
+    List,
+    /// Compare the current system against a snapshot
+    Diff {
+        /// Snapshot to compare against
+        name: String,
+    },
+    /// Return to a snapshot after confirming the plan
+    Restore {
+        /// Snapshot to restore
+        name: String,
+    },
+}
+
+/// Subcommands of `owl state`
+#[derive(Debug, Clone, Subcommand)]
+pub enum StateAction {
+    /// Forget managed packages that are gone and move pins to the installed version
+    Refresh {
+        /// Read transaction targets from stdin, as the pacman hook passes them
+        #[arg(long)]
+        from_hook: bool,
+    },
+}
+
 /// Global flags extracted from CLI for passing to commands
 #[derive(Debug, Clone)]
 pub struct GlobalFlags {
     pub verbose: bool,
     pub dry_run: bool,
     pub non_interactive: bool,
+    pub no_pager: bool,
+    pub offline: bool,
+    pub answers_file: Option<std::path::PathBuf>,
 }
 
 impl From<&Cli> for GlobalFlags {
     fn from(cli: &Cli) -> Self {
         Self {
-            verbose: cli.verbose,
+            verbose: cli.verbose > 0,
             dry_run: cli.dry_run,
             non_interactive: cli.non_interactive,
+            no_pager: cli.no_pager,
+            offline: cli.offline,
+            answers_file: cli.answers_file.clone(),
+        }
+    }
+}
+
+impl Commands {
+    /// What the command loads through its [`Context`]. Every command is listed, so a
+    /// new one has to say; those that have not moved onto the context load for
+    /// themselves and declare nothing.
+    fn needs(&self) -> Needs {
+        match self {
+            Commands::List { .. } => Needs {
+                config: true,
+                state: true,
+                installed: true,
+            },
+            Commands::Export { .. } => Needs {
+                config: true,
+                state: false,
+                installed: true,
+            },
+            Commands::Status { .. }
+            | Commands::Why { .. }
+            | Commands::Info { .. }
+            | Commands::Stats { .. } => Needs {
+                config: true,
+                state: true,
+                installed: false,
+            },
+            Commands::Verify { .. } => Needs {
+                config: true,
+                state: false,
+                installed: false,
+            },
+            // Reads only the recorded runs
+            Commands::History { .. } => Needs::NOTHING,
+            Commands::Apply { .. }
+            | Commands::Edit { .. }
+            | Commands::Dots
+            | Commands::Add { .. }
+            | Commands::Adopt { .. }
+            | Commands::Find { .. }
+            | Commands::ConfigCheck { .. }
+            | Commands::ConfigHost
+            | Commands::Clean { .. }
+            | Commands::MigrateFrom { .. }
+            | Commands::Bootstrap { .. }
+            | Commands::Init { .. }
+            | Commands::Integrate { .. }
+            | Commands::Doctor { .. }
+            | Commands::Diff { .. }
+            | Commands::Exec { .. }
+            | Commands::Fleet { .. }
+            | Commands::TestApply { .. }
+            | Commands::Orphans
+            | Commands::Pin { .. }
+            | Commands::Unpin { .. }
+            | Commands::Ignore { .. }
+            | Commands::Unignore { .. }
+            | Commands::Untrack { .. }
+            | Commands::Remove { .. }
+            | Commands::Search { .. }
+            | Commands::Schedule { .. }
+            | Commands::Snapshot { .. }
+            | Commands::State { .. }
+            | Commands::Sync { .. }
+            | Commands::Outdated { .. }
+            | Commands::Deps { .. }
+            | Commands::Prompt { .. }
+            | Commands::Ui
+            | Commands::Version { .. }
+            | Commands::EditDots { .. }
+            | Commands::EditConfig { .. } => Needs::NOTHING,
+        }
+    }
+}
+
+/// Take the run lock for commands that change packages or state; dry runs go without.
+/// Exits with `EXIT_LOCKED` when another run holds it.
+fn lock_run(
+    command: Option<&Commands>,
+    dry_run: bool,
+    wait: bool,
+) -> Option<crate::core::lock::RunLock> {
+    let name = match command {
+        None | Some(Commands::Apply { .. }) => "apply",
+        Some(Commands::Adopt { .. }) => "adopt",
+        Some(_) => return None,
+    };
+    if dry_run {
+        return None;
+    }
+    match crate::core::lock::acquire(name, wait) {
+        Ok(lock) => Some(lock),
+        Err(err) => {
+            let code = if err.is::<crate::core::lock::LockHeld>() {
+                constants::EXIT_LOCKED
+            } else {
+                1
+            };
+            crate::error::exit_with_code(err, code)
         }
     }
 }
@@ -116,6 +766,7 @@ fn has_pacman() -> bool {
         .arg("--version")
         .stdout(Stdio::null())
         .stderr(Stdio::null())
+        .logged()
         .status()
         .map(|status| status.success())
         .unwrap_or(false)
@@ -123,7 +774,40 @@ fn has_pacman() -> bool {
 
 /// Execute the parsed command
 fn execute_command(cli: &Cli) {
+    if cli.version {
+        version::print_version();
+        return;
+    }
+
+    if let Some(dir) = &cli.config_dir {
+        exit_on_error(crate::internal::files::set_owl_dir(dir));
+    }
+    if let Some(path) = &cli.state_file {
+        exit_on_error(crate::core::state::set_state_file(path));
+    }
+
+    if cli.ascii {
+        color::force_ascii();
+    }
+    if cli.refresh_aur {
+        crate::core::aur_cache::bypass();
+    }
+    if cli.no_commit {
+        crate::core::autocommit::disable();
+    }
+    if cli.offline {
+        crate::core::news::go_offline();
+    }
+    if cli.ack_news {
+        crate::core::news::acknowledge_unread();
+    }
+    crate::core::pm::set_db_lock_wait(cli.db_lock_wait);
+    if cli.porcelain {
+        exit_on_error(crate::cli::porcelain::enable());
+    }
+
     let flags = GlobalFlags::from(cli);
+    exit_on_error(crate::cli::ui::configure_interaction(&flags));
 
     if flags.verbose {
         println!("{}", color::dim("[verbose] args parsed"));
@@ -132,18 +816,55 @@ fn execute_command(cli: &Cli) {
     // Normalize command aliases to their canonical form
     let command = match &cli.command {
         Some(Commands::EditDots { argument }) => Some(Commands::Edit {
-            target: EditTarget::Dots,
-            argument: argument.clone(),
+            target: Some(constants::EDIT_TYPE_DOTS.to_string()),
+            argument: Some(argument.clone()),
+            new: None,
         }),
         Some(Commands::EditConfig { argument }) => Some(Commands::Edit {
-            target: EditTarget::Config,
-            argument: argument.clone(),
+            target: Some(constants::EDIT_TYPE_CONFIG.to_string()),
+            argument: Some(argument.clone()),
+            new: None,
         }),
         other => other.clone(),
     };
 
+    let _lock = lock_run(command.as_ref(), flags.dry_run, cli.wait);
+    let ctx = Context::new(command.as_ref().map_or(Needs::NOTHING, Commands::needs));
     match command {
-        Some(Commands::Apply) => apply::run(&flags),
+        Some(Commands::Apply {
+            retry_failed: true, ..
+        }) => apply::retry_failed(&flags),
+        Some(Commands::Apply {
+            packages_only,
+            dotfiles_only,
+            plan,
+            no_network_check,
+            only,
+            clean_orphans,
+            fail_fast,
+            no_upgrade,
+            prune_state,
+            retry_failed: false,
+        }) => apply::run_with(
+            &flags,
+            apply::ApplyOptions {
+                scope: if packages_only {
+                    apply::Scope::PackagesOnly
+                } else if dotfiles_only {
+                    apply::Scope::DotfilesOnly
+                } else {
+                    apply::Scope::All
+                },
+                plan_first: plan,
+                no_network_check,
+                only,
+                clean_orphans,
+                fail_fast,
+                no_upgrade,
+                prune_state,
+                ..apply::ApplyOptions::new(&flags)
+            },
+        ),
         None => {
             if has_pacman() {
                 apply::run(&flags);
@@ -156,17 +877,40 @@ fn execute_command(cli: &Cli) {
                 dots::run(&flags);
             }
         }
-        Some(Commands::Edit { target, argument }) => {
-            let typ = match target {
-                EditTarget::Dots => constants::EDIT_TYPE_DOTS,
-                EditTarget::Config => constants::EDIT_TYPE_CONFIG,
-            };
-            exit_on_error(edit::run(typ, &argument));
-        }
+        Some(Commands::Edit {
+            target,
+            argument,
+            new,
+        }) => edit::run(target.as_deref(), argument.as_deref(), new.as_deref()),
         Some(Commands::Dots) => dots::run(&flags),
         Some(Commands::Add { items, search }) => add::run(&items, search),
-        Some(Commands::Adopt { items, all }) => adopt::run(&items, all),
-        Some(Commands::Find { query }) => find::run(&query),
+        Some(Commands::Adopt {
+            items,
+            from,
+            all,
+            yes,
+            to,
+            include_deps,
+            aur_section,
+            with_configs,
+            report,
+            orphans,
+            force,
+        }) => adopt::run(&adopt::AdoptOptions {
+            items: &items,
+            from: from.as_deref(),
+            all,
+            yes,
+            to: to.as_deref(),
+            dry_run: flags.dry_run,
+            include_deps,
+            aur_section,
+            with_configs,
+            report: report.as_deref(),
+            orphans,
+            force,
+        }),
+        Some(Commands::Find { query }) => find::run(&query, &flags),
         Some(Commands::ConfigCheck { file }) => {
             if let Some(f) = file {
                 exit_on_error(crate::core::config::validator::run_configcheck(&f));
@@ -177,13 +921,34 @@ fn execute_command(cli: &Cli) {
         Some(Commands::ConfigHost) => {
             exit_on_error(crate::core::config::validator::run_confighost());
         }
-        Some(Commands::Clean { filename }) => {
+        Some(Commands::Clean {
+            filename: None,
+            disk,
+            backups,
+            logs,
+            pkg_cache,
+            aur_builds,
+        }) if disk || backups || logs || pkg_cache || aur_builds => {
+            let categories: Vec<crate::core::clean::Category> = [
+                (backups, crate::core::clean::Category::Backups),
+                (logs, crate::core::clean::Category::Logs),
+                (pkg_cache, crate::core::clean::Category::PackageCache),
+                (aur_builds, crate::core::clean::Category::AurBuilds),
+            ]
+            .into_iter()
+            .filter(|(selected, _)| *selected)
+            .map(|(_, category)| category)
+            .collect();
+            crate::commands::clean::run(&categories, &flags);
+        }
+        Some(Commands::Clean { filename, .. }) => {
             let result = match filename {
                 Some(fname) => {
                     let result = crate::commands::clean::handle_clean(&fname);
                     if result.is_ok() {
                         println!("[{}]", color::blue("clean"));
                         println!("  {} {}", color::green("✓"), color::dim(&fname));
+                        crate::commands::clean::commit_cleaned(&[fname.into()]);
                     }
                     result
                 }
@@ -191,6 +956,134 @@ fn execute_command(cli: &Cli) {
             };
             exit_on_error(result);
         }
+        Some(Commands::MigrateFrom { source, path, name }) => {
+            migrate::run(source, &path, name.as_deref(), &flags)
+        }
+        Some(Commands::Bootstrap { url, assume_yes }) => bootstrap::run(&url, assume_yes, &flags),
+        Some(Commands::Init { from_system, git }) => init::run(from_system, git, &flags),
+        Some(Commands::Integrate {
+            integration,
+            remove,
+        }) => integrate::run(integration, remove, &flags),
+        Some(Commands::Diff {
+            only,
+            dotfiles,
+            packages,
+        }) => {
+            let filters = diff::DiffFilters {
+                only,
+                dotfiles,
+                packages,
+            };
+            diff::run(&filters, &flags)
+        }
+        Some(Commands::Doctor { fix }) => doctor::run(fix, &flags),
+        Some(Commands::Info { package, json }) => info::run(&package, json, &ctx),
+        Some(Commands::List {
+            missing,
+            extra,
+            aur,
+            host,
+            with_dotfiles,
+            json,
+        }) => {
+            let filters = list::ListFilters {
+                missing,
+                extra,
+                aur,
+                host,
+                with_dotfiles,
+            };
+            list::run(&filters, json, &ctx)
+        }
+        Some(Commands::Fleet { action }) => match action {
+            FleetAction::Apply { hosts, tags } => fleet::run_apply(&hosts, &tags, &flags),
+        },
+        Some(Commands::TestApply { runtime, image }) => test_apply::run(runtime, image.as_deref()),
+        Some(Commands::Export {
+            format,
+            host,
+            groups,
+            exclude_groups,
+            installed_only,
+        }) => {
+            let filters = export::ExportFilters {
+                host,
+                groups,
+                exclude_groups,
+                installed_only,
+            };
+            export::run(format, &filters, &ctx)
+        }
+        Some(Commands::Ignore {
+            packages,
+            list,
+            remove,
+        }) => ignore::run(&packages, list, remove, &flags),
+        Some(Commands::Unignore {
+            packages,
+            all,
+            list,
+        }) => ignore::run_unignore(&packages, all, list, &flags),
+        Some(Commands::Untrack {
+            packages,
+            all_missing,
+            ignore_future,
+        }) => untrack::run(&packages, all_missing, ignore_future, &flags),
+        Some(Commands::Remove {
+            package,
+            keep_installed,
+            purge,
+        }) => remove::run(&package, keep_installed, purge, &flags),
+        Some(Commands::Orphans) => orphans::run(&flags),
+        Some(Commands::Pin { package, list }) => pin::run(package.as_deref(), list, &flags),
+        Some(Commands::Unpin { package }) => pin::run_unpin(&package, &flags),
+        Some(Commands::Search { terms, add }) => search::run(&terms, add),
+        Some(Commands::History {
+            action,
+            package,
+            limit,
+        }) => match action {
+            Some(HistoryAction::Show { id }) => history::run_show(&id),
+            None => history::run(package.as_deref(), limit),
+        },
+        Some(Commands::Schedule { action }) => match action {
+            ScheduleAction::Enable { interval, scope } => {
+                schedule::run_enable(interval.as_deref(), scope.scope(), &flags)
+            }
+            ScheduleAction::Disable { scope } => schedule::run_disable(scope.scope(), &flags),
+            ScheduleAction::Status { scope } => schedule::run_status(scope.scope()),
+        },
+        Some(Commands::Snapshot { action }) => match action {
+            SnapshotAction::Create { name } => snapshot::run_create(&name),
+            SnapshotAction::List => snapshot::run_list(),
+            SnapshotAction::Diff { name } => snapshot::run_diff(&name),
+            SnapshotAction::Restore { name } => snapshot::run_restore(&name, &flags),
+        },
+        Some(Commands::State { action }) => match action {
+            StateAction::Refresh { from_hook } => state::run_refresh(from_hook, &flags),
+        },
+        Some(Commands::Outdated { json, count }) => outdated::run(json, count),
+        Some(Commands::Verify { json, restore }) => verify::run(json, restore, &flags, &ctx),
+        Some(Commands::Deps {
+            packages,
+            depth,
+            dot,
+            optional,
+        }) => deps::run(&deps::DepsOptions {
+            packages,
+            depth,
+            dot,
+            optional,
+        }),
+        Some(Commands::Stats { json, oneline }) => stats::run(json, oneline, &ctx),
+        Some(Commands::Status { json, fast }) => status::run(json, fast, &ctx),
+        Some(Commands::Prompt { refresh, color }) => prompt::run(refresh, color),
+        Some(Commands::Sync { apply, push }) => sync::run(apply, push, &flags),
+        Some(Commands::Exec { print, command }) => exec::run(&command, print),
+        Some(Commands::Why { package }) => why::run(&package, &ctx),
+        Some(Commands::Ui) => ui::run(&flags),
+        Some(Commands::Version { check, source }) => version::run(check, source),
         // These are normalized above, so they should never match here
         Some(Commands::EditDots { .. } | Commands::EditConfig { .. }) => unreachable!(),
     }
@@ -200,10 +1093,34 @@ fn execute_command(cli: &Cli) {
 pub fn parse_and_execute() {
     let cli = match Cli::try_parse() {
         Ok(cli) => cli,
+        // Help goes to stdout as clap renders it
+        Err(err) if !err.use_stderr() => err.exit(),
         Err(err) => {
-            eprintln!("{}", color::red(&err.to_string()));
+            // The themed colors would read settings.toml just to report a typo
+            eprintln!("{}", color::colorize(&err.to_string(), color::Color::Red));
             std::process::exit(1);
         }
     };
+    crate::internal::logging::init(cli.verbose);
     execute_command(&cli);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offline_before_or_after_apply_skips_news_and_aur() {
+        for args in [["owl", "--offline", "apply"], ["owl", "apply", "--offline"]] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            // `cli.offline` is what turns off the news check in `run`
+            assert!(cli.offline, "{:?}", args);
+            let options = apply::ApplyOptions::new(&GlobalFlags::from(&cli));
+            assert!(!options.check_aur_updates(), "{:?}", args);
+            assert!(!options.check_names(), "{:?}", args);
+        }
+        let online = Cli::try_parse_from(["owl", "apply"]).unwrap();
+        assert!(!online.offline);
+        assert!(apply::ApplyOptions::new(&GlobalFlags::from(&online)).check_aur_updates());
+    }
+}
@@ -1,2 +1,5 @@
+pub mod context;
 pub mod handler;
+pub mod notify;
+pub mod porcelain;
 pub mod ui;
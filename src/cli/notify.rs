@@ -0,0 +1,158 @@
+//! Desktop notifications for the result of an apply
+//!
+//! `notify` in settings picks which runs end with one: `on-change`, `on-error`, or
+//! `always`. They go through `notify-send`, so nothing happens when it is missing or
+//! no session bus is reachable, as in a system timer with nobody logged in.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cli::porcelain::Summary;
+use crate::core::settings::NotifyWhen;
+use crate::internal::logging::LogCommand;
+
+static ARMED: AtomicBool = AtomicBool::new(false);
+
+/// Mark this process as a real apply, so its end may notify
+pub fn arm() {
+    ARMED.store(true, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    fn as_str(self) -> &'static str {
+        match self {
+            Urgency::Low => "low",
+            Urgency::Normal => "normal",
+            Urgency::Critical => "critical",
+        }
+    }
+}
+
+fn should_notify(when: NotifyWhen, summary: &Summary, failed: bool) -> bool {
+    match when {
+        NotifyWhen::Off => false,
+        NotifyWhen::Always => true,
+        NotifyWhen::OnError => failed || summary.warnings > 0,
+        NotifyWhen::OnChange => failed || summary.changed(),
+    }
+}
+
+fn urgency(summary: &Summary, failed: bool) -> Urgency {
+    if failed {
+        Urgency::Critical
+    } else if summary.warnings > 0 {
+        Urgency::Normal
+    } else {
+        Urgency::Low
+    }
+}
+
+/// Title and body of the notification
+fn message(summary: &Summary, error: Option<&str>) -> (String, String) {
+    match error {
+        Some(error) => ("owl apply failed".to_string(), error.to_string()),
+        None => ("owl apply finished".to_string(), summary.headline()),
+    }
+}
+
+/// Whether a `notify-send` could reach a notification daemon
+fn session_available() -> bool {
+    if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some_and(|bus| !bus.is_empty()) {
+        return true;
+    }
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata("/proc/self")
+        .is_ok_and(|proc| Path::new(&format!("/run/user/{}/bus", proc.uid())).exists())
+}
+
+/// Notify about the run that just ended, when armed and the settings ask for it.
+/// `error` is set when the run stopped on one.
+pub fn finished(summary: &Summary, error: Option<&str>) {
+    if !ARMED.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    let failed = error.is_some();
+    if !should_notify(crate::core::settings::get().notify, summary, failed) || !session_available()
+    {
+        return;
+    }
+    let (title, body) = message(summary, error);
+    let sent = Command::new("notify-send")
+        .args([
+            "--app-name=owl",
+            "--urgency",
+            urgency(summary, failed).as_str(),
+        ])
+        .arg(&title)
+        .arg(&body)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .logged()
+        .status();
+    if let Err(err) = sent {
+        log::debug!("notify: could not run notify-send: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_notify_and_urgency() {
+        let quiet = Summary {
+            ok: true,
+            ..Default::default()
+        };
+        let changed = Summary {
+            ok: true,
+            upgraded: 3,
+            ..Default::default()
+        };
+        let warned = Summary {
+            warnings: 1,
+            ..Default::default()
+        };
+
+        assert!(!should_notify(NotifyWhen::Off, &warned, true));
+        assert!(should_notify(NotifyWhen::Always, &quiet, false));
+        assert!(!should_notify(NotifyWhen::OnChange, &quiet, false));
+        assert!(should_notify(NotifyWhen::OnChange, &changed, false));
+        assert!(should_notify(NotifyWhen::OnChange, &quiet, true));
+        assert!(!should_notify(NotifyWhen::OnError, &changed, false));
+        assert!(should_notify(NotifyWhen::OnError, &warned, false));
+
+        assert_eq!(urgency(&changed, false), Urgency::Low);
+        assert_eq!(urgency(&warned, false), Urgency::Normal);
+        assert_eq!(urgency(&quiet, true), Urgency::Critical);
+    }
+
+    #[test]
+    fn test_message_uses_the_summary_headline() {
+        let summary = Summary {
+            upgraded: 4,
+            warnings: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            message(&summary, None),
+            (
+                "owl apply finished".to_string(),
+                "4 packages updated, 1 warning".to_string()
+            )
+        );
+        assert_eq!(
+            message(&summary, Some("pacman failed")).0,
+            "owl apply failed"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! `--porcelain`: newline-delimited JSON events on stdout for wrappers
+//!
+//! Once enabled, stdout carries only events and everything meant for people, from
+//! owl and from pacman alike, goes to stderr. Each line is one object whose `event`
+//! field names its kind; the first line is always `start` with the schema version.
+//!
+//! Schema version 1 (`apply`, and `owl` without a subcommand, emit the stream):
+//!
+//! - `start` `{schema, command}`
+//! - `phase_start` `{phase}`: `analysis`, `packages`, `dotfiles`, then `system`
+//! - `pkg_install_start` `{source, packages}` and `pkg_install_done` `{source,
+//!   packages, ok}`, where `source` is `repo` or `aur`
+//! - `dotfile_written` `{destination, status}`, status `create` or `update`
+//! - `confirm_needed` `{prompt}`: answer with one line on stdin
+//! - `warning` `{message}`: something failed but the run carried on
+//! - `done` `{summary}`: the last line, also sent when the run stops on an error
+//!
+//! Fields are only ever added within a schema version; removing or changing one
+//! bumps [`SCHEMA_VERSION`].
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::AsFd;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Start {
+        schema: u32,
+        command: String,
+    },
+    PhaseStart {
+        phase: String,
+    },
+    PkgInstallStart {
+        source: String,
+        packages: Vec<String>,
+    },
+    PkgInstallDone {
+        source: String,
+        packages: Vec<String>,
+        ok: bool,
+    },
+    DotfileWritten {
+        destination: String,
+        status: String,
+    },
+    ConfirmNeeded {
+        prompt: String,
+    },
+    Warning {
+        message: String,
+    },
+    Done {
+        summary: Summary,
+    },
+}
+
+/// What an apply did, or would do on a dry run. Fields missing from an older owl's
+/// stream read as zero.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Summary {
+    pub ok: bool,
+    pub dry_run: bool,
+    /// Planned installs and removals
+    pub to_install: usize,
+    pub to_remove: usize,
+    /// Package changes the database shows afterwards; zero on a dry run
+    pub installed: usize,
+    pub removed: usize,
+    pub upgraded: usize,
+    pub dotfiles_written: usize,
+    pub warnings: usize,
+}
+
+impl Summary {
+    /// Whether the run changed anything on the system
+    pub fn changed(&self) -> bool {
+        self.installed + self.removed + self.upgraded + self.dotfiles_written > 0
+    }
+
+    /// One line for people, e.g. `4 packages updated, 1 warning`
+    pub fn headline(&self) -> String {
+        let count =
+            |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+        let mut parts = Vec::new();
+        for (n, one, many) in [
+            (self.installed, "package installed", "packages installed"),
+            (self.removed, "package removed", "packages removed"),
+            (self.upgraded, "package updated", "packages updated"),
+            (self.dotfiles_written, "dotfile written", "dotfiles written"),
+        ] {
+            if n > 0 {
+                parts.push(count(n, one, many));
+            }
+        }
+        if parts.is_empty() {
+            parts.push("nothing changed".to_string());
+        }
+        if self.warnings > 0 {
+            parts.push(count(self.warnings, "warning", "warnings"));
+        }
+        parts.join(", ")
+    }
+}
+
+/// The original stdout, kept for events after fd 1 is pointed at stderr
+static SINK: OnceLock<Mutex<File>> = OnceLock::new();
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Switch this process to porcelain output. Call before anything is printed.
+pub fn enable() -> Result<()> {
+    std::io::stdout().flush().ok();
+    let events = std::io::stdout()
+        .as_fd()
+        .try_clone_to_owned()
+        .context("Failed to duplicate stdout")?;
+    // SAFETY: dup2 on two descriptors that stay open for the life of the process
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to move output to stderr");
+    }
+    SINK.set(Mutex::new(File::from(events))).ok();
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    SINK.get().is_some()
+}
+
+/// Write `event` as one line, when porcelain output is on
+pub fn emit(event: Event) {
+    // Counted either way: notifications summarize warnings too
+    if matches!(event, Event::Warning { .. }) {
+        WARNINGS.fetch_add(1, Ordering::Relaxed);
+    }
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+    let line = match serde_json::to_string(&event) {
+        Ok(line) => line,
+        Err(err) => {
+            log::debug!("porcelain: could not serialize {:?}: {}", event, err);
+            return;
+        }
+    };
+    let mut sink = sink.lock().unwrap_or_else(|e| e.into_inner());
+    writeln!(sink, "{}", line).ok();
+    sink.flush().ok();
+}
+
+/// Start `phase`, which problems reported from here on are filed under
+pub fn phase(phase: &str) {
+    crate::error::enter_phase(phase);
+    emit(Event::PhaseStart {
+        phase: phase.to_string(),
+    });
+}
+
+pub fn warning(message: &str) {
+    emit(Event::Warning {
+        message: message.to_string(),
+    });
+}
+
+/// Warnings emitted so far, for the summary
+pub fn warnings() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_serialize_with_their_kind() {
+        let line = |event| serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            line(Event::PhaseStart {
+                phase: "packages".to_string()
+            }),
+            r#"{"event":"phase_start","phase":"packages"}"#
+        );
+        assert_eq!(
+            line(Event::PkgInstallDone {
+                source: "aur".to_string(),
+                packages: vec!["paru".to_string()],
+                ok: true
+            }),
+            r#"{"event":"pkg_install_done","source":"aur","packages":["paru"],"ok":true}"#
+        );
+    }
+
+    #[test]
+    fn test_summary_headline() {
+        let summary = Summary {
+            ok: true,
+            upgraded: 4,
+            warnings: 1,
+            ..Default::default()
+        };
+        assert_eq!(summary.headline(), "4 packages updated, 1 warning");
+        let summary = Summary {
+            installed: 1,
+            dotfiles_written: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            summary.headline(),
+            "1 package installed, 2 dotfiles written"
+        );
+        assert_eq!(Summary::default().headline(), "nothing changed");
+    }
+}
@@ -1,28 +1,258 @@
+use crate::core::pm::PackageInfo;
 use crate::internal::color;
-use std::io::Write;
+use crate::internal::logging::LogCommand;
+use anyhow::{Context, Result};
+use std::collections::{HashMap, VecDeque};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Why a prompt could not get an answer
+#[derive(Debug, Clone, PartialEq)]
+pub enum InteractionBlock {
+    /// `--non-interactive` was passed
+    NonInteractive,
+    /// stdin or stdout is not a terminal
+    NotATerminal,
+    /// Every line of the answers file has been used
+    AnswersExhausted(PathBuf),
+    /// stdin closed before an answer was given
+    EndOfInput,
+}
+
+/// A prompt needed an answer that owl was not allowed to ask for
+#[derive(Debug, Clone, PartialEq)]
+pub struct InteractionError {
+    pub prompt: String,
+    pub block: InteractionBlock,
+}
+
+impl std::fmt::Display for InteractionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match &self.block {
+            InteractionBlock::NonInteractive => "--non-interactive is set".to_string(),
+            InteractionBlock::NotATerminal => "stdin/stdout is not a terminal".to_string(),
+            InteractionBlock::AnswersExhausted(path) => {
+                format!("{} has no answers left", path.display())
+            }
+            InteractionBlock::EndOfInput => "stdin was closed".to_string(),
+        };
+        write!(f, "{} needs an answer, but {}", self.prompt, reason)
+    }
+}
+
+impl std::error::Error for InteractionError {}
+
+/// Canned responses read from `--answers-file`, one per line
+#[derive(Debug)]
+struct Answers {
+    path: PathBuf,
+    lines: VecDeque<String>,
+}
+
+impl Answers {
+    /// Lines starting with `#` are comments; blank lines answer with the prompt's default
+    fn parse(path: &Path, content: &str) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            lines: content
+                .lines()
+                .filter(|line| !line.trim_start().starts_with('#'))
+                .map(|line| line.trim().to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Interaction {
+    non_interactive: bool,
+    answers: Option<Mutex<Answers>>,
+}
+
+static INTERACTION: OnceLock<Interaction> = OnceLock::new();
+
+fn interaction() -> &'static Interaction {
+    INTERACTION.get_or_init(Interaction::default)
+}
+
+/// Record how prompts may be answered for this process. Call once, before any prompt.
+pub fn configure_interaction(flags: &crate::cli::handler::GlobalFlags) -> Result<()> {
+    let answers = match &flags.answers_file {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read answers file: {}", path.display()))?;
+            Some(Mutex::new(Answers::parse(path, &content)))
+        }
+        None => None,
+    };
+    INTERACTION
+        .set(Interaction {
+            non_interactive: flags.non_interactive,
+            answers,
+        })
+        .ok();
+    Ok(())
+}
+
+/// True when prompts may read from the terminal: both stdin and stdout are TTYs and
+/// `--non-interactive` was not passed. With `--porcelain` the wrapper answers on
+/// stdin instead, so no terminal is needed.
+pub fn interaction_allowed() -> bool {
+    !interaction().non_interactive
+        && (crate::cli::porcelain::enabled()
+            || (std::io::stdin().is_terminal() && std::io::stdout().is_terminal()))
+}
+
+/// True when a prompt can get an answer, from `--answers-file` or the terminal
+pub fn prompts_answerable() -> bool {
+    interaction().answers.is_some() || interaction_allowed()
+}
+
+/// Read one answer for the prompt named `prompt`, which has already been printed.
+/// Answers come from `--answers-file` when given, otherwise from the terminal.
+pub fn read_answer(prompt: &str) -> Result<String> {
+    let blocked = |block| {
+        // End the half-written prompt line so the error starts on its own line
+        println!();
+        InteractionError {
+            prompt: prompt.to_string(),
+            block,
+        }
+    };
+
+    if let Some(answers) = &interaction().answers {
+        let mut answers = answers.lock().unwrap_or_else(|e| e.into_inner());
+        let answer = answers
+            .lines
+            .pop_front()
+            .ok_or_else(|| blocked(InteractionBlock::AnswersExhausted(answers.path.clone())))?;
+        println!("{}", answer);
+        return Ok(answer);
+    }
+
+    if interaction().non_interactive {
+        return Err(blocked(InteractionBlock::NonInteractive).into());
+    }
+    if !interaction_allowed() {
+        return Err(blocked(InteractionBlock::NotATerminal).into());
+    }
+    crate::cli::porcelain::emit(crate::cli::porcelain::Event::ConfirmNeeded {
+        prompt: prompt.to_string(),
+    });
+
+    let mut input = String::new();
+    let read = std::io::stdin()
+        .read_line(&mut input)
+        .with_context(|| format!("Failed to read answer for {}", prompt))?;
+    if read == 0 {
+        return Err(blocked(InteractionBlock::EndOfInput).into());
+    }
+    Ok(input.trim().to_string())
+}
+
+/// Number of packages a confirmation lists before collapsing the rest
+const CONFIRM_SUMMARY_LIMIT: usize = 8;
+
+/// Look up package metadata for ranking; confirmations still work without it
+fn confirmation_info(packages: &[String]) -> HashMap<String, PackageInfo> {
+    crate::core::package::get_package_info(packages).unwrap_or_else(|err| {
+        log::debug!("package info unavailable for confirmation: {:#}", err);
+        HashMap::new()
+    })
+}
+
+fn print_summary(packages: &[String], info: &HashMap<String, PackageInfo>, limit: usize) {
+    for line in format_confirmation_summary(packages, info, limit) {
+        println!("{}", line);
+    }
+}
 
 fn confirm_operation(
+    name: &str,
     packages: &[String],
     header_icon: &str,
     header_text: &str,
-    detail_label: &str,
     prompt: &str,
-) -> bool {
+) -> Result<bool> {
     println!("\n  {} {}", color::red(header_icon), header_text);
-    println!(
-        "  {} {}: {}",
-        color::yellow(&packages.len().to_string()),
-        detail_label,
-        packages.join(", ")
+    let info = confirmation_info(packages);
+    print_summary(packages, &info, CONFIRM_SUMMARY_LIMIT);
+
+    loop {
+        print!("  -> {prompt} ");
+        std::io::stdout().flush().ok();
+
+        match read_answer(name)?.to_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "v" | "view" => print_summary(packages, &info, usize::MAX),
+            _ => return Ok(false),
+        }
+    }
+}
+
+/// Format the body of a confirmation: a count and size line, then the most impactful
+/// packages (most dependents first, then largest), then how many were left out.
+/// Packages without metadata keep their order after the ranked ones.
+pub fn format_confirmation_summary(
+    packages: &[String],
+    info: &HashMap<String, PackageInfo>,
+    limit: usize,
+) -> Vec<String> {
+    let known: Vec<&PackageInfo> = packages.iter().filter_map(|p| info.get(p)).collect();
+    let mut header = format!(
+        "  {} package{}",
+        packages.len(),
+        if packages.len() == 1 { "" } else { "s" }
     );
-    print!("  -> {prompt} ");
-    std::io::stdout().flush().ok();
+    if !known.is_empty() {
+        let total: u64 = known.iter().map(|p| p.installed_size).sum();
+        header.push_str(&format!(", {} installed", format_size(total)));
+    }
+    let mut lines = vec![header];
 
-    let mut input = String::new();
-    match std::io::stdin().read_line(&mut input) {
-        Ok(_) => matches!(input.trim().to_lowercase().as_str(), "y" | "yes"),
-        Err(_) => false,
+    let mut ranked: Vec<&String> = packages.iter().collect();
+    ranked.sort_by_key(|name| match info.get(*name) {
+        Some(p) => (
+            false,
+            std::cmp::Reverse(p.required_by.len()),
+            std::cmp::Reverse(p.installed_size),
+        ),
+        None => (true, std::cmp::Reverse(0), std::cmp::Reverse(0)),
+    });
+
+    let shown = &ranked[..ranked.len().min(limit)];
+    let width = shown.iter().map(|name| name.len()).max().unwrap_or(0);
+    for name in shown {
+        let mut line = format!("    {:<width$}", name, width = width);
+        if let Some(p) = info.get(*name) {
+            line.push_str(&format!("  {:>10}", format_size(p.installed_size)));
+            if !p.required_by.is_empty() {
+                line.push_str(&format!("  required by {}", p.required_by.len()));
+            }
+        }
+        lines.push(line.trim_end().to_string());
+    }
+
+    if ranked.len() > shown.len() {
+        lines.push(format!(
+            "    …and {} more (press v to view all)",
+            ranked.len() - shown.len()
+        ));
+    }
+    lines
+}
+
+/// Format a byte count the way pacman does (`412.50 KiB`)
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
     }
+    format!("{:.2} {}", value, UNITS[unit])
 }
 
 /// Generate the apply command output display with uninstalled package count
@@ -74,30 +304,669 @@ pub fn generate_apply_output_with_install(
     }
 }
 
+/// The AUR work a confirmation asks about; installs and updates are asked separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AurOperation {
+    Install,
+    Update,
+}
+
+impl AurOperation {
+    /// "install" or "update"
+    pub fn verb(self) -> &'static str {
+        match self {
+            AurOperation::Install => "install",
+            AurOperation::Update => "update",
+        }
+    }
+
+    /// "installing" or "updating", for the prompt's header
+    pub fn label(self) -> &'static str {
+        match self {
+            AurOperation::Install => "installing",
+            AurOperation::Update => "updating",
+        }
+    }
+}
+
 /// Prompt user for AUR package confirmation
-pub fn confirm_aur_operation(packages: &[String], operation: &str) -> bool {
-    let verb = match operation {
-        "installing" => "install",
-        "updating" => "update",
-        "installing/updating" => "install and/or update",
-        _ => operation.trim_end_matches("ing"),
-    };
+pub fn confirm_aur_operation(packages: &[String], operation: AurOperation) -> Result<bool> {
     confirm_operation(
+        "the AUR confirmation",
         packages,
         "‼",
-        "AUR packages require confirmation",
-        "AUR packages found",
-        &format!("Are you sure you wanna {} AUR packages? (y/N):", verb),
+        &format!(
+            "AUR packages require confirmation before {}",
+            operation.label()
+        ),
+        &format!(
+            "Are you sure you wanna {} these AUR packages? (y/N, v to view all):",
+            operation.verb()
+        ),
     )
 }
 
-/// Prompt user for removal confirmation
-pub fn confirm_remove_operation(packages: &[String]) -> bool {
-    confirm_operation(
-        packages,
-        "‼",
-        "Package removals require confirmation",
-        "packages to remove",
-        "Are you sure you want to remove these packages? (y/N):",
-    )
+/// Prompt user for removal confirmation, returning the packages they kept selected.
+/// Packages that something in `managed` requires are flagged and left out of a
+/// plain yes; `[s]elect` can still pick them.
+pub fn confirm_remove_operation(packages: &[String], managed: &[String]) -> Result<Vec<String>> {
+    println!(
+        "\n  {} Package removals require confirmation",
+        color::red("‼")
+    );
+    let info = confirmation_info(packages);
+    print_summary(packages, &info, CONFIRM_SUMMARY_LIMIT);
+    let reverse = crate::core::package::reverse_dependencies(&info);
+    let blocked = crate::core::package::required_by_managed(&reverse, packages, managed);
+    for line in format_dependents(&reverse, &blocked) {
+        println!("{}", line);
+    }
+    let unblocked: Vec<String> = packages
+        .iter()
+        .filter(|pkg| !blocked.contains_key(*pkg))
+        .cloned()
+        .collect();
+
+    loop {
+        print!(
+            "  -> Remove {} package(s)? [y]es / [N]o / [v]iew all / [s]elect: ",
+            unblocked.len()
+        );
+        std::io::stdout().flush().ok();
+
+        match read_answer("the removal confirmation")?
+            .to_lowercase()
+            .as_str()
+        {
+            "y" | "yes" => return Ok(unblocked),
+            "v" | "view" => print_summary(packages, &info, usize::MAX),
+            "s" | "select" => {
+                let selected = select_items(
+                    packages,
+                    "Select packages to remove (e.g. 1-3,5 / all / none)",
+                    SelectDefault::None,
+                )?;
+                return Ok(selected
+                    .into_iter()
+                    .map(|idx| packages[idx].clone())
+                    .collect());
+            }
+            _ => return Ok(Vec::new()),
+        }
+    }
+}
+
+/// The "required by" lines under a removal summary: every candidate that installed
+/// packages depend on, in red with a note when a managed package is among them
+pub fn format_dependents(
+    reverse: &std::collections::BTreeMap<String, Vec<String>>,
+    blocked: &std::collections::BTreeMap<String, Vec<String>>,
+) -> Vec<String> {
+    if reverse.is_empty() {
+        return Vec::new();
+    }
+    let mut lines = vec!["  Required by installed packages:".to_string()];
+    for (name, dependents) in reverse {
+        let line = format!("    {} <- {}", name, dependents.join(", "));
+        lines.push(match blocked.get(name) {
+            Some(managed) => color::red(&format!(
+                "{} (kept: managed {} need{} it; [s]elect to remove anyway)",
+                line,
+                managed.join(", "),
+                if managed.len() == 1 { "s" } else { "" }
+            )),
+            None => color::dim(&line),
+        });
+    }
+    lines
+}
+
+/// What an empty answer means in the multi-select prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectDefault {
+    All,
+    None,
+}
+
+/// Interactively pick a subset of `items`.
+///
+/// Accepts 1-based numbers and ranges (`1-5,8`), `all`, `none`, and `/term` to filter the
+/// displayed list (`/` alone clears the filter). While a filter is active `all` selects only
+/// the visible items. Returns the selected indices into `items`.
+pub fn select_items(items: &[String], prompt: &str, default: SelectDefault) -> Result<Vec<usize>> {
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut filter: Option<String> = None;
+    loop {
+        let visible = filter_items(items, filter.as_deref());
+        println!();
+        for idx in &visible {
+            println!(
+                "  {} {}",
+                color::dim(&format!("[{}]", idx + 1)),
+                color::highlight(&items[*idx])
+            );
+        }
+        if let Some(term) = &filter {
+            println!(
+                "  {} {} of {} shown (filter: {})",
+                color::blue("info:"),
+                visible.len(),
+                items.len(),
+                term
+            );
+        }
+
+        let default_label = match default {
+            SelectDefault::All => "all",
+            SelectDefault::None => "none",
+        };
+        print!("  -> {prompt} [{default_label}]: ");
+        std::io::stdout().flush().ok();
+
+        let input = read_answer("the package selection")?;
+        let input = input.as_str();
+
+        if let Some(term) = input.strip_prefix('/') {
+            let term = term.trim();
+            filter = (!term.is_empty()).then(|| term.to_lowercase());
+            continue;
+        }
+
+        let expression = if input.is_empty() {
+            default_label
+        } else {
+            input
+        };
+        if filter.is_some() && expression.eq_ignore_ascii_case("all") {
+            return Ok(visible);
+        }
+        match parse_selection(expression, items.len()) {
+            Ok(selected) => return Ok(selected),
+            Err(err) => println!("  {}", color::red(&err)),
+        }
+    }
+}
+
+fn filter_items(items: &[String], filter: Option<&str>) -> Vec<usize> {
+    items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| filter.is_none_or(|term| item.to_lowercase().contains(term)))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Parse a selection expression such as `1-5,8`, `all` or `none` into sorted, deduplicated
+/// 0-based indices for a list of `count` items. Numbers in the expression are 1-based.
+pub fn parse_selection(expression: &str, count: usize) -> Result<Vec<usize>, String> {
+    let expression = expression.trim();
+    if expression.eq_ignore_ascii_case("all") {
+        return Ok((0..count).collect());
+    }
+    if expression.eq_ignore_ascii_case("none") {
+        return Ok(Vec::new());
+    }
+
+    let parse_number = |token: &str| -> Result<usize, String> {
+        let number: usize = token
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid selection '{}'", token.trim()))?;
+        if number == 0 || number > count {
+            return Err(format!(
+                "Selection {} is out of range (1-{})",
+                number, count
+            ));
+        }
+        Ok(number - 1)
+    };
+
+    let mut selected = std::collections::BTreeSet::new();
+    for token in expression
+        .split([',', ' '])
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        if let Some((start, end)) = token.split_once('-') {
+            let start = parse_number(start)?;
+            let end = parse_number(end)?;
+            if start > end {
+                return Err(format!("Invalid range '{}'", token));
+            }
+            selected.extend(start..=end);
+        } else {
+            selected.insert(parse_number(token)?);
+        }
+    }
+
+    if selected.is_empty() {
+        return Err("Empty selection".to_string());
+    }
+    Ok(selected.into_iter().collect())
+}
+
+/// Writer for long output. When stdout is a terminal and the output grows past the
+/// terminal height, everything is sent through `$PAGER` (default `less -FRX`);
+/// otherwise it is written to stdout unchanged. A pager that quits early simply
+/// discards the rest of the output.
+pub struct Pager {
+    command: Vec<String>,
+    limit: Option<usize>,
+    buffer: Vec<u8>,
+    lines: usize,
+    child: Option<std::process::Child>,
+    closed: bool,
+}
+
+impl Pager {
+    /// Create a pager for this invocation, honoring `--no-pager` and the `pager` setting
+    pub fn new(flags: &crate::cli::handler::GlobalFlags) -> Self {
+        let enabled = !flags.no_pager
+            && crate::core::settings::get().pager
+            && std::io::stdout().is_terminal();
+        let command = pager_command();
+        let limit = if enabled && !command.is_empty() {
+            Some(terminal_height())
+        } else {
+            None
+        };
+        Self::with_command(command, limit)
+    }
+
+    fn with_command(command: Vec<String>, limit: Option<usize>) -> Self {
+        Self {
+            command,
+            limit,
+            buffer: Vec::new(),
+            lines: 0,
+            child: None,
+            closed: false,
+        }
+    }
+
+    /// Flush buffered output and wait for the pager to exit
+    pub fn finish(self) {
+        drop(self);
+    }
+
+    fn start_pager(&mut self) {
+        let mut command = std::process::Command::new(&self.command[0]);
+        command
+            .args(&self.command[1..])
+            .stdin(std::process::Stdio::piped());
+        if std::env::var_os("LESS").is_none() {
+            command.env("LESS", "FRX");
+        }
+
+        match command.logged().spawn() {
+            Ok(child) => {
+                self.child = Some(child);
+                let buffer = std::mem::take(&mut self.buffer);
+                self.write_to_pager(&buffer);
+            }
+            Err(_) => {
+                // No usable pager; fall back to plain output
+                self.limit = None;
+                let buffer = std::mem::take(&mut self.buffer);
+                self.write_to_stdout(&buffer);
+            }
+        }
+    }
+
+    fn write_to_pager(&mut self, buf: &[u8]) {
+        let stdin = self.child.as_mut().and_then(|child| child.stdin.as_mut());
+        if let Some(stdin) = stdin
+            && stdin.write_all(buf).is_err()
+        {
+            // The pager quit (EPIPE); drop whatever is left
+            self.closed = true;
+        }
+    }
+
+    fn write_to_stdout(&mut self, buf: &[u8]) {
+        if std::io::stdout().write_all(buf).is_err() {
+            self.closed = true;
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.closed {
+            return Ok(buf.len());
+        }
+        if self.child.is_some() {
+            self.write_to_pager(buf);
+            return Ok(buf.len());
+        }
+        let Some(limit) = self.limit else {
+            self.write_to_stdout(buf);
+            return Ok(buf.len());
+        };
+
+        self.buffer.extend_from_slice(buf);
+        self.lines += buf.iter().filter(|&&b| b == b'\n').count();
+        if self.lines >= limit {
+            self.start_pager();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.child.is_none() && self.limit.is_none() && !self.closed {
+            std::io::stdout().flush().ok();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            drop(child.stdin.take());
+            child.wait().ok();
+        } else if !self.closed {
+            let buffer = std::mem::take(&mut self.buffer);
+            self.write_to_stdout(&buffer);
+            std::io::stdout().flush().ok();
+        }
+    }
+}
+
+/// Split `$PAGER` (or the default) into program and arguments. An empty `$PAGER`
+/// or `cat` disables paging.
+fn pager_command() -> Vec<String> {
+    let pager = std::env::var("PAGER")
+        .unwrap_or_else(|_| crate::internal::constants::DEFAULT_PAGER.to_string());
+    let command: Vec<String> = pager.split_whitespace().map(str::to_string).collect();
+    if command.first().is_some_and(|program| program == "cat") {
+        return Vec::new();
+    }
+    command
+}
+
+/// Terminal height in lines, from `$LINES` or `stty size`, defaulting to 24
+fn terminal_height() -> usize {
+    terminal_dimension("LINES", 0).unwrap_or(24)
+}
+
+/// Terminal width in columns, from `$COLUMNS` or `stty size`, defaulting to 80
+pub fn terminal_width() -> usize {
+    terminal_dimension("COLUMNS", 1).unwrap_or(80)
+}
+
+/// One field of `stty size` (rows, then columns), unless `var` overrides it
+fn terminal_dimension(var: &str, field: usize) -> Option<usize> {
+    if let Some(value) = std::env::var(var)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|&value: &usize| value > 0)
+    {
+        return Some(value);
+    }
+
+    std::fs::File::open("/dev/tty")
+        .ok()
+        .and_then(|tty| {
+            std::process::Command::new("stty")
+                .arg("size")
+                .stdin(tty)
+                .stderr(std::process::Stdio::null())
+                .logged()
+                .output()
+                .ok()
+        })
+        .and_then(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .nth(field)
+                .and_then(|value| value.parse().ok())
+        })
+        .filter(|&value: &usize| value > 0)
+}
+
+/// Columns of plain text that shrink to fit the terminal. The widest columns give up
+/// space first, and cells that no longer fit are cut with `…`.
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(ToString::to_string).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    /// Render the header and rows, each indented by two spaces, within `width` columns
+    pub fn render(&self, width: usize) -> Vec<String> {
+        const MIN_COLUMN: usize = 4;
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (idx, cell) in row.iter().enumerate().take(widths.len()) {
+                widths[idx] = widths[idx].max(cell.chars().count());
+            }
+        }
+
+        let total = |widths: &[usize]| {
+            2 + widths.iter().sum::<usize>() + 2 * widths.len().saturating_sub(1)
+        };
+        while total(&widths) > width {
+            let Some((idx, _)) = widths
+                .iter()
+                .enumerate()
+                .filter(|&(_, &w)| w > MIN_COLUMN)
+                .max_by_key(|&(idx, &w)| (w, std::cmp::Reverse(idx)))
+            else {
+                break;
+            };
+            widths[idx] -= 1;
+        }
+
+        std::iter::once(&self.headers)
+            .chain(&self.rows)
+            .map(|row| {
+                let cells: Vec<String> = widths
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, &w)| {
+                        let cell = row.get(idx).map(String::as_str).unwrap_or("");
+                        format!("{:<w$}", truncate(cell, w), w = w)
+                    })
+                    .collect();
+                format!("  {}", cells.join("  ")).trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// Print the table at the terminal width, the header in bold
+    pub fn print(&self) {
+        for (idx, line) in self.render(terminal_width()).iter().enumerate() {
+            if idx == 0 {
+                println!("{}", color::bold(line));
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+fn truncate(cell: &str, width: usize) -> String {
+    if cell.chars().count() <= width {
+        return cell.to_string();
+    }
+    let mut out: String = cell.chars().take(width.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_selection_ranges_and_lists() {
+        assert_eq!(parse_selection("1-3,5", 6), Ok(vec![0, 1, 2, 4]));
+        assert_eq!(parse_selection("5, 1 2", 6), Ok(vec![0, 1, 4]));
+        assert_eq!(parse_selection("2-2,2", 3), Ok(vec![1]));
+    }
+
+    #[test]
+    fn test_parse_selection_keywords() {
+        assert_eq!(parse_selection("all", 3), Ok(vec![0, 1, 2]));
+        assert_eq!(parse_selection("ALL", 0), Ok(Vec::new()));
+        assert_eq!(parse_selection("none", 3), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_parse_selection_rejects_invalid_input() {
+        assert!(parse_selection("0", 3).is_err());
+        assert!(parse_selection("4", 3).is_err());
+        assert!(parse_selection("3-1", 3).is_err());
+        assert!(parse_selection("abc", 3).is_err());
+        assert!(parse_selection("1-x", 3).is_err());
+        assert!(parse_selection(",", 3).is_err());
+    }
+
+    #[test]
+    fn test_filter_items_matches_case_insensitively() {
+        let items = vec![
+            "Firefox".to_string(),
+            "htop".to_string(),
+            "firefox-ublock".to_string(),
+        ];
+        assert_eq!(filter_items(&items, Some("fire")), vec![0, 2]);
+        assert_eq!(filter_items(&items, None), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_pager_survives_early_exit() {
+        let mut pager = Pager::with_command(vec!["true".to_string()], Some(2));
+        for idx in 0..10_000 {
+            writeln!(pager, "line {}", idx).unwrap();
+        }
+        assert!(pager.child.is_some());
+        pager.finish();
+    }
+
+    #[test]
+    fn test_pager_buffers_short_output() {
+        let mut pager = Pager::with_command(vec!["true".to_string()], Some(50));
+        writeln!(pager, "short").unwrap();
+        assert!(pager.child.is_none());
+        assert_eq!(pager.buffer, b"short\n");
+        pager.buffer.clear();
+    }
+
+    #[test]
+    fn test_table_fits_width() {
+        let mut table = Table::new(&["name", "source"]);
+        table.add_row(vec!["htop".to_string(), "main.owl".to_string()]);
+        table.add_row(vec![
+            "visual-studio-code-bin".to_string(),
+            "groups/dev.owl".to_string(),
+        ]);
+
+        assert_eq!(
+            table.render(80),
+            vec![
+                "  name                    source",
+                "  htop                    main.owl",
+                "  visual-studio-code-bin  groups/dev.owl",
+            ]
+        );
+        assert_eq!(
+            table.render(30),
+            vec![
+                "  name           source",
+                "  htop           main.owl",
+                "  visual-studi…  groups/dev.o…",
+            ]
+        );
+    }
+
+    fn info(name: &str, size: u64, dependents: usize) -> (String, PackageInfo) {
+        (
+            name.to_string(),
+            PackageInfo {
+                name: name.to_string(),
+                installed_size: size,
+                required_by: (0..dependents).map(|i| format!("dep{}", i)).collect(),
+                ..PackageInfo::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_confirmation_summary_ranks_and_collapses() {
+        let packages: Vec<String> = ["tiny", "unknown", "huge", "core"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let info: HashMap<String, PackageInfo> = [
+            info("tiny", 1024, 0),
+            info("huge", 3 << 30, 0),
+            info("core", 2 << 20, 5),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            format_confirmation_summary(&packages, &info, 2),
+            vec![
+                "  4 packages, 3.00 GiB installed".to_string(),
+                "    core    2.00 MiB  required by 5".to_string(),
+                "    huge    3.00 GiB".to_string(),
+                "    …and 2 more (press v to view all)".to_string(),
+            ]
+        );
+
+        let all = format_confirmation_summary(&packages, &info, usize::MAX);
+        assert_eq!(all.len(), 5);
+        assert_eq!(all[3], "    tiny       1.00 KiB");
+        assert_eq!(all[4], "    unknown");
+    }
+
+    #[test]
+    fn test_confirmation_summary_without_info() {
+        let packages = vec!["htop".to_string()];
+        assert_eq!(
+            format_confirmation_summary(&packages, &HashMap::new(), 8),
+            vec!["  1 package".to_string(), "    htop".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_dependents_flag_packages_managed_ones_need() {
+        use std::collections::BTreeMap;
+        let reverse = BTreeMap::from([
+            ("libfoo".to_string(), vec!["foo-gui".to_string()]),
+            (
+                "python".to_string(),
+                vec!["meson".to_string(), "yt-dlp".to_string()],
+            ),
+        ]);
+        let blocked = BTreeMap::from([("libfoo".to_string(), vec!["foo-gui".to_string()])]);
+        let lines = format_dependents(&reverse, &blocked);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].contains("libfoo <- foo-gui (kept: managed foo-gui needs it;"));
+        assert!(lines[2].contains("python <- meson, yt-dlp"));
+        assert!(!lines[2].contains("kept"));
+        assert!(format_dependents(&BTreeMap::new(), &BTreeMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_answers_file_skips_comments_and_keeps_blanks() {
+        let answers = Answers::parse(Path::new("answers"), "# adopt run\ny\n\n  2-3 \n");
+        assert_eq!(answers.lines, vec!["y", "", "2-3"]);
+    }
 }
@@ -22,7 +22,11 @@ fn run_search_mode(terms: &[String]) {
             }
 
             display_search_results(&results);
-            let selection = prompt_package_selection(&results);
+            let names: Vec<String> = results.iter().map(|result| result.name.clone()).collect();
+            let selection = match prompt_package_selection(&names) {
+                Ok(selection) => selection,
+                Err(err) => crate::error::exit_with_error(err),
+            };
 
             match selection {
                 Some(package_name) => {
@@ -80,10 +84,11 @@ fn display_search_results(results: &[SearchResult]) {
     println!();
 }
 
-/// Prompt user to select a package from search results
-fn prompt_package_selection(results: &[SearchResult]) -> Option<String> {
+/// Prompt user to select a package from search results, listed with
+/// `[0]` on the last line
+pub fn prompt_package_selection(results: &[String]) -> anyhow::Result<Option<String>> {
     if results.is_empty() {
-        return None;
+        return Ok(None);
     }
 
     loop {
@@ -91,20 +96,19 @@ fn prompt_package_selection(results: &[SearchResult]) -> Option<String> {
             "Select package (0-{}, or 'c' to cancel): ",
             results.len() - 1
         );
-        std::io::Write::flush(&mut std::io::stdout()).ok()?;
+        std::io::Write::flush(&mut std::io::stdout()).ok();
 
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).ok()?;
-        let input = input.trim();
+        let input = crate::cli::ui::read_answer("the package selection")?;
+        let input = input.as_str();
 
         if input == "c" || input == "cancel" {
-            return None;
+            return Ok(None);
         }
 
         match input.parse::<usize>() {
             Ok(num) if num < results.len() => {
                 let index = results.len() - 1 - num;
-                return Some(results[index].name.clone());
+                return Ok(Some(results[index].clone()));
             }
             _ => {
                 println!(
@@ -116,14 +120,27 @@ fn prompt_package_selection(results: &[SearchResult]) -> Option<String> {
     }
 }
 
+fn commit_added(package_name: &str, file_path: &str) {
+    let path = std::path::Path::new(file_path);
+    crate::core::autocommit::commit_config_change(
+        &[path.to_path_buf()],
+        &format!(
+            "owl add: add {} to {}",
+            package_name,
+            crate::core::autocommit::describe(path)
+        ),
+    );
+}
+
 /// Format a number in brackets like [1], [2], etc.
 fn number_brackets(num: usize) -> String {
     format!("[{num}]")
 }
 
-/// Add a package to the appropriate configuration file
-fn add_package_to_config(package_name: &str) -> anyhow::Result<()> {
-    use crate::internal::files::{add_package_to_file, get_main_config_path, AddPackageResult};
+/// Add a package to the appropriate configuration file, asking which one when there
+/// are several
+pub fn add_package_to_config(package_name: &str) -> anyhow::Result<()> {
+    use crate::internal::files::{AddPackageResult, add_package_to_file, get_main_config_path};
 
     let mut config_files = get_relevant_config_files()?;
 
@@ -139,6 +156,7 @@ fn add_package_to_config(package_name: &str) -> anyhow::Result<()> {
                         package_name, main_config
                     ))
                 );
+                commit_added(package_name, &main_config);
             }
             AddPackageResult::AlreadyPresent => {
                 println!(
@@ -164,6 +182,7 @@ fn add_package_to_config(package_name: &str) -> anyhow::Result<()> {
                         package_name, file_path
                     ))
                 );
+                commit_added(package_name, file_path);
             }
             AddPackageResult::AlreadyPresent => {
                 println!(
@@ -199,7 +218,7 @@ fn add_package_to_config(package_name: &str) -> anyhow::Result<()> {
     }
     println!();
 
-    let selection = prompt_file_selection(config_files.len());
+    let selection = prompt_file_selection(config_files.len())?;
     match selection {
         Some(index) => {
             let file_path = &config_files[index];
@@ -212,6 +231,7 @@ fn add_package_to_config(package_name: &str) -> anyhow::Result<()> {
                             package_name, file_path
                         ))
                     );
+                    commit_added(package_name, file_path);
                 }
                 AddPackageResult::AlreadyPresent => {
                     println!(
@@ -241,27 +261,26 @@ fn get_relevant_config_files() -> anyhow::Result<Vec<String>> {
 }
 
 /// Prompt user to select a config file from search results
-fn prompt_file_selection(count: usize) -> Option<usize> {
+fn prompt_file_selection(count: usize) -> anyhow::Result<Option<usize>> {
     if count == 0 {
-        return None;
+        return Ok(None);
     }
 
     loop {
         print!("Select config file (0-{}, or 'c' to cancel): ", count - 1);
-        std::io::Write::flush(&mut std::io::stdout()).ok()?;
+        std::io::Write::flush(&mut std::io::stdout()).ok();
 
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).ok()?;
-        let input = input.trim();
+        let input = crate::cli::ui::read_answer("the config file selection")?;
+        let input = input.as_str();
 
         if input == "c" || input == "cancel" {
-            return None;
+            return Ok(None);
         }
 
         match input.parse::<usize>() {
             Ok(num) if num < count => {
                 let index = count - 1 - num;
-                return Some(index);
+                return Ok(Some(index));
             }
             _ => {
                 println!(
@@ -1,242 +1,909 @@
 use crate::core::config::Config;
+use crate::core::config::document::Document;
+use crate::core::pm::PackageInfo;
 use crate::core::state::PackageState;
 use crate::internal::color;
+use crate::internal::files::friendly_path;
 use anyhow::{Result, anyhow};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum PackageAction {
     Adopt,
     Ignore,
     Skip,
+    /// Adopt this package and every one after it without asking again
+    AdoptAll,
+    IgnoreAll,
+    SkipAll,
     Quit,
 }
 
+impl PackageAction {
+    /// The per-package action a bulk answer repeats; `None` for single answers
+    fn bulk(self) -> Option<PackageAction> {
+        match self {
+            PackageAction::AdoptAll => Some(PackageAction::Adopt),
+            PackageAction::IgnoreAll => Some(PackageAction::Ignore),
+            PackageAction::SkipAll => Some(PackageAction::Skip),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum AddResult {
     Added,
     AlreadyPresent,
 }
 
+/// What `owl adopt` adopts and how
+pub struct AdoptOptions<'a> {
+    /// Packages named on the command line
+    pub items: &'a [String],
+    /// A file with one package per line, or `-` for stdin, read on top of `items`
+    pub from: Option<&'a str>,
+    /// Discover candidates even though packages were named
+    pub all: bool,
+    /// Adopt every candidate without asking, into `to` or the main config
+    pub yes: bool,
+    pub to: Option<&'a str>,
+    /// Ask and write nothing; the summary lists what would happen
+    pub dry_run: bool,
+    /// Let discovery offer dependencies of managed packages too
+    pub include_deps: bool,
+    /// Write foreign packages under `@aur` instead of `@packages`
+    pub aur_section: bool,
+    /// Offer to copy `~/.config/<package>` into the dotfiles and declare it
+    pub with_configs: bool,
+    /// Where to write the session's decisions as JSON
+    pub report: Option<&'a str>,
+    /// Review orphaned dependencies after the explicit packages
+    pub orphans: bool,
+    /// Write to config files that do not parse
+    pub force: bool,
+}
 
-pub fn run(items: &[String], all: bool) {
-    let mut state = match PackageState::load() {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("{}", color::red(&format!("Failed to load state: {}", e)));
-            return;
-        }
-    };
+/// Run adopt; without `yes` each package is prompted for
+pub fn run(options: &AdoptOptions) {
+    adopt_explicit(options);
+    if options.orphans {
+        crate::error::report("review orphaned dependencies", adopt_orphans(options));
+    }
+}
 
-    let config = match Config::load_all_relevant_config_files() {
-        Ok(cfg) => cfg,
-        Err(e) => {
-            eprintln!("{}", color::red(&format!("Failed to load config: {}", e)));
-            return;
+fn adopt_explicit(options: &AdoptOptions) {
+    let AdoptOptions {
+        all,
+        yes,
+        to,
+        dry_run,
+        include_deps,
+        aur_section,
+        ..
+    } = *options;
+    let mut items = options.items.to_vec();
+    if let Some(from) = options.from {
+        match read_package_list(from) {
+            Ok(listed) => items.extend(listed),
+            Err(err) => {
+                eprintln!("{}", crate::error::render_error(&err));
+                return;
+            }
         }
+    }
+    if !yes && !dry_run && !crate::cli::ui::prompts_answerable() {
+        crate::error::exit_on_error(Err(anyhow!(
+            "adopt asks about each package but there is no terminal to answer on; pass --yes to adopt every candidate (and --to <file> to choose the config file)"
+        )));
+    }
+    // --to and --yes fix the file for every package; otherwise each adopt asks
+    let mut fixed_config: Option<String> = match to.map(resolve_target).transpose() {
+        Ok(path) => path,
+        Err(err) => crate::error::exit_with_error(err),
     };
+    if yes && fixed_config.is_none() {
+        match get_main_config_path() {
+            Ok(path) => fixed_config = Some(path),
+            Err(err) => {
+                eprintln!("{}", crate::error::render_error(&err));
+                return;
+            }
+        }
+    }
 
-    let installed = match crate::core::package::get_installed_packages() {
-        Ok(installed) => installed,
+    let mut state = match PackageState::load() {
+        Ok(s) => s,
         Err(e) => {
             eprintln!(
                 "{}",
-                color::red(&format!("Failed to list installed packages: {}", e))
+                crate::error::render_error(&e.context("Failed to load state"))
             );
             return;
         }
     };
-    let explicit_installed = match get_explicitly_installed_packages() {
-        Ok(explicit) => explicit,
+
+    let config = match Config::load_all_relevant_config_files() {
+        Ok(cfg) => cfg,
         Err(e) => {
             eprintln!(
                 "{}",
-                color::red(&format!("Failed to list explicit packages: {}", e))
+                crate::error::render_error(&e.context("Failed to load config"))
             );
             return;
         }
     };
 
-    let discover_mode = all || items.is_empty();
-    let targets = if discover_mode {
-        discover_candidates_from_explicit(&explicit_installed, &state, &config)
+    let system =
+        match crate::core::system::SystemSnapshot::capture(&crate::core::system::Backend::select())
+        {
+            Ok(system) => system,
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    color::err(&format!("Failed to list installed packages: {}", e))
+                );
+                return;
+            }
+        };
+    let installed = system.installed();
+    let explicit_installed = system.explicit();
+
+    let discover_mode = all || (items.is_empty() && options.from.is_none());
+    let mut targets = if discover_mode {
+        crate::core::analysis::unmanaged_explicit(explicit_installed, &state, &config)
     } else {
-        normalize_targets(items)
+        normalize_targets(&items)
     };
+    let mut dependencies = Vec::new();
+    if discover_mode && !include_deps && !targets.is_empty() {
+        (targets, dependencies) = split_dependencies(targets, &state, &config);
+    }
 
     if targets.is_empty() {
         println!(
             "{}",
-            color::yellow("No unmanaged installed packages available for adoption")
+            color::warn("No unmanaged installed packages available for adoption")
         );
+        if let Some(line) = dependencies_line(dependencies.len()) {
+            println!("{}", line);
+        }
         return;
     }
 
     println!(
-        "{} {} package(s) available for adoption",
-        color::blue("info:"),
-        targets.len()
+        "{}",
+        color::info(&format!(
+            "{} package(s) available for adoption",
+            targets.len()
+        ))
     );
 
-    let mut adopted = Vec::new();
-    let mut adopted_state_only = Vec::new();
-    let mut ignored = Vec::new();
     let mut skipped = Vec::new();
-    let mut skipped_not_installed = Vec::new();
-    let mut skipped_already_managed = Vec::new();
-    let mut state_changed = false;
-    let mut selected_config: Option<String> = None;
 
-    for pkg in targets {
-        if state.is_managed(&pkg) {
-            skipped_already_managed.push(pkg);
-            continue;
-        }
+    // In discovery mode let the user narrow the candidate list before deciding per package
+    let targets = if discover_mode && targets.len() > 1 && !yes && !dry_run {
+        let selected = match crate::cli::ui::select_items(
+            &targets,
+            "Select packages to review (e.g. 1-5,8 / all / none / /filter)",
+            crate::cli::ui::SelectDefault::All,
+        ) {
+            Ok(selected) => selected,
+            Err(err) => {
+                eprintln!("{}", crate::error::render_error(&err));
+                return;
+            }
+        };
+        let (chosen, unchosen) = partition_selected(targets, &selected);
+        skipped.extend(unchosen);
+        chosen
+    } else {
+        targets
+    };
 
-        if discover_mode && state.is_untracked(&pkg) {
-            skipped.push(pkg);
-            continue;
-        }
+    let classified = classify(targets, discover_mode, &state, &config, installed);
+    skipped.extend(classified.untracked);
+    let mut summary = Summary {
+        foreign: classified
+            .offered
+            .iter()
+            .filter(|pkg| system.foreign().contains_key(*pkg))
+            .cloned()
+            .collect(),
+        dependencies: dependencies.len(),
+        adopted_state_only: classified.in_config,
+        skipped_already_managed: classified.already_managed,
+        skipped_not_installed: classified.not_installed,
+        skipped,
+        ..Summary::default()
+    };
 
-        if !installed.contains(&pkg) {
-            skipped_not_installed.push(pkg);
-            continue;
+    if dry_run {
+        // Everything offered would be adopted; only a fixed file says where to
+        for pkg in classified.offered {
+            if let Some(path) = &fixed_config {
+                record_written(&mut summary.written, path, &pkg);
+            }
+            summary.adopted.push(pkg);
+        }
+        // `--to` was checked when it was resolved
+        if let Some(path) = &fixed_config
+            && !summary.adopted.is_empty()
+            && let Ok(group) = missing_include_for(path)
+        {
+            note_included(&mut summary.included, group);
         }
+        summary.print(true);
+        return;
+    }
 
-        if config.packages.contains_key(&pkg) {
-            state.add_managed(pkg.clone());
-            state_changed = true;
-            adopted_state_only.push(pkg);
-            continue;
+    let mut state_changed = false;
+    for pkg in &summary.adopted_state_only {
+        match config.provenance.get(pkg).and_then(|at| at.path.as_ref()) {
+            Some(path) => state.add_managed_from(pkg.clone(), &path.to_string_lossy()),
+            None => state.add_managed(pkg.clone()),
         }
+        state_changed = true;
+    }
+    let mut last_config: Option<String> = None;
+    // Set by --yes or a bulk answer; every remaining package gets it without a prompt
+    let mut sticky = yes.then_some(PackageAction::Adopt);
+    // One pacman call for every candidate; without it the prompt shows just the name
+    let details = if yes {
+        HashMap::new()
+    } else {
+        crate::core::package::get_package_info(&classified.offered).unwrap_or_else(|err| {
+            println!(
+                "{}",
+                color::warn(&format!("Could not read package details: {:#}", err))
+            );
+            HashMap::new()
+        })
+    };
+    // Sync db of each repo candidate, also in one call; only used to suggest a file
+    let repos: HashMap<String, String> = if yes {
+        HashMap::new()
+    } else {
+        let from_repos: Vec<String> = classified
+            .offered
+            .iter()
+            .filter(|pkg| !system.foreign().contains_key(*pkg))
+            .cloned()
+            .collect();
+        crate::core::pm::ParuPacman::new()
+            .repo_package_info(&from_repos)
+            .map(|info| {
+                info.into_iter()
+                    .map(|(name, info)| (name, info.repository))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
 
-        let action = match prompt_package_action(&pkg) {
-            Some(action) => action,
-            None => {
-                eprintln!("{}", color::red("Failed to read selection, stopping adopt"));
+    for pkg in classified.offered {
+        let foreign = system.foreign().contains_key(&pkg);
+        let ask = || {
+            for line in describe_candidate(details.get(&pkg), foreign) {
+                println!("  {}", color::dim(&line));
+            }
+            prompt_package_action(&pkg, foreign)
+        };
+        let action = match next_action(&mut sticky, ask) {
+            Ok(action) => action,
+            Err(err) => {
+                eprintln!("{}", crate::error::render_error(&err));
                 break;
             }
         };
 
         match action {
             PackageAction::Adopt => {
-                let config_path = if let Some(path) = &selected_config {
+                let config_path = if let Some(path) = &fixed_config {
                     path.clone()
                 } else {
-                    match prompt_config_file_selection() {
+                    let hints = placement_hints(
+                        details.get(&pkg),
+                        repos.get(&pkg).map(String::as_str),
+                        foreign,
+                    );
+                    match prompt_config_file_selection(last_config.as_deref(), &hints) {
                         Ok(Some(path)) => {
-                            selected_config = Some(path.clone());
+                            // After "adopt all" the file is asked for once
+                            if sticky.is_some() {
+                                fixed_config = Some(path.clone());
+                            }
+                            last_config = Some(path.clone());
                             path
                         }
                         Ok(None) => {
-                            println!("{}", color::yellow("Adopt cancelled by user"));
+                            println!("{}", color::warn("Adopt cancelled by user"));
                             break;
                         }
                         Err(err) => {
                             eprintln!(
                                 "{}",
-                                color::red(&format!("Failed to select config: {}", err))
+                                color::err(&format!("Failed to select config: {}", err))
                             );
+                            save_report(options.report, &summary);
                             return;
                         }
                     }
                 };
 
-                match add_package_to_file(&pkg, &config_path) {
+                let dotfile = if options.with_configs {
+                    offer_config_dir(&pkg, sticky.is_none())
+                } else {
+                    None
+                };
+                let entry = match &dotfile {
+                    Some(mapping) => Entry::WithConfig(mapping),
+                    None if aur_section && foreign => Entry::Aur,
+                    None => Entry::Listed,
+                };
+                let added = add_package_to_file(&pkg, &config_path, entry, options.force).and_then(
+                    |added| {
+                        note_included(&mut summary.included, include_target(&config_path)?);
+                        Ok(added)
+                    },
+                );
+                match added {
                     Ok(AddResult::Added) => {
+                        if dotfile.is_some() {
+                            summary.configs.push(pkg.clone());
+                        }
                         state.remove_untracked(&pkg);
-                        state.add_managed(pkg.clone());
+                        state.add_managed_from(pkg.clone(), &config_path);
                         state_changed = true;
-                        adopted.push(pkg);
+                        record_written(&mut summary.written, &config_path, &pkg);
+                        summary.adopted.push(pkg);
                     }
                     Ok(AddResult::AlreadyPresent) => {
                         state.remove_untracked(&pkg);
-                        state.add_managed(pkg.clone());
+                        state.add_managed_from(pkg.clone(), &config_path);
                         state_changed = true;
-                        adopted_state_only.push(pkg);
+                        summary.adopted_state_only.push(pkg);
                     }
                     Err(err) => {
                         eprintln!(
                             "{}",
-                            color::red(&format!("Failed to adopt {}: {}", pkg, err))
+                            color::err(&format!("Failed to adopt {}: {}", pkg, err))
                         );
                     }
                 }
             }
             PackageAction::Ignore => {
-                state.add_untracked(pkg.clone());
-                state.remove_managed(&pkg);
+                state.ignore(pkg.clone());
                 state_changed = true;
-                ignored.push(pkg);
+                summary.ignored.push(pkg);
             }
-            PackageAction::Skip => skipped.push(pkg),
+            PackageAction::Skip => summary.skipped.push(pkg),
             PackageAction::Quit => break,
+            PackageAction::AdoptAll | PackageAction::IgnoreAll | PackageAction::SkipAll => {
+                unreachable!("next_action resolves bulk answers")
+            }
         }
     }
 
+    // Quitting early still leaves a report of the decisions made so far
+    save_report(options.report, &summary);
+
+    if state_changed && let Err(e) = state.save() {
+        eprintln!("{}", color::err(&format!("Failed to save state: {}", e)));
+        return;
+    }
     if state_changed {
-        if let Err(e) = state.save() {
-            eprintln!("{}", color::red(&format!("Failed to save state: {}", e)));
-            return;
+        crate::error::report(
+            "record the run",
+            record_run(
+                &summary.adopted,
+                &summary.adopted_state_only,
+                &summary.ignored,
+            ),
+        );
+    }
+
+    commit_written(&summary.written, &summary.included);
+    summary.print(false);
+}
+
+/// Commit the config files adopt wrote to, and main.owl when it now pulls in
+/// `included` groups, when `git_autocommit` is on
+fn commit_written(written: &[(String, Vec<String>)], included: &[String]) {
+    if written.is_empty() {
+        return;
+    }
+    let mut paths: Vec<std::path::PathBuf> = written
+        .iter()
+        .map(|(file, _)| std::path::PathBuf::from(file))
+        .collect();
+    if !included.is_empty()
+        && let Ok(owl_dir) = crate::internal::files::owl_dir()
+    {
+        paths.push(owl_dir.join(crate::internal::constants::MAIN_CONFIG_FILE));
+    }
+    let additions: Vec<String> = written
+        .iter()
+        .map(|(file, packages)| {
+            format!(
+                "{} to {}",
+                packages.join(", "),
+                crate::core::autocommit::describe(Path::new(file))
+            )
+        })
+        .collect();
+    crate::core::autocommit::commit_config_change(
+        &paths,
+        &format!("owl adopt: add {}", additions.join("; ")),
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OrphanAction {
+    /// Mark it explicitly installed and add it to a config file
+    Adopt,
+    /// Queue it for removal, confirmed once at the end
+    Remove,
+    Skip,
+    Quit,
+}
+
+/// The outcome of the `--orphans` phase, kept apart from the explicit packages
+#[derive(Debug, Default)]
+struct OrphanSummary {
+    adopted: Vec<String>,
+    removed: Vec<String>,
+    skipped: Vec<String>,
+    written: Vec<(String, Vec<String>)>,
+    included: Vec<String>,
+}
+
+impl OrphanSummary {
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (file, packages) in &self.written {
+            lines.push(color::info(&format!(
+                "Wrote orphan(s) {} to {}",
+                packages.join(", "),
+                friendly_path(file)
+            )));
+        }
+        lines.extend(included_lines(&self.included, false));
+        if !self.adopted.is_empty() {
+            lines.push(color::ok(&format!(
+                "Adopted {} orphan(s), now explicitly installed: {}",
+                self.adopted.len(),
+                self.adopted.join(", ")
+            )));
         }
+        if !self.removed.is_empty() {
+            lines.push(color::ok(&format!(
+                "Removed {} orphan(s): {}",
+                self.removed.len(),
+                self.removed.join(", ")
+            )));
+        }
+        if !self.skipped.is_empty() {
+            lines.push(color::info(&format!(
+                "Orphans skipped: {}",
+                self.skipped.join(", ")
+            )));
+        }
+        lines
     }
+}
 
-    if let Some(file) = selected_config {
-        println!(
-            "{} Adopted packages were written to {}",
-            color::blue("info:"),
-            file
-        );
+/// The `--orphans` phase: each dependency that nothing requires any more is adopted,
+/// queued for removal, or skipped. Removals go through the usual confirmation.
+fn adopt_orphans(options: &AdoptOptions) -> Result<()> {
+    let orphans = crate::core::package::get_orphan_packages()?;
+    println!();
+    if orphans.is_empty() {
+        println!("{}", color::info("No orphaned dependencies"));
+        return Ok(());
     }
-    if !adopted.is_empty() {
+    println!(
+        "{}",
+        color::info(&format!(
+            "{} orphaned dependenc{} to review",
+            orphans.len(),
+            if orphans.len() == 1 { "y" } else { "ies" }
+        ))
+    );
+    if options.dry_run || options.yes {
+        // Removing or adopting an orphan is never a default; it takes an answer
         println!(
-            "{} Adopted {} package(s): {}",
-            color::green("✓"),
-            adopted.len(),
-            adopted.join(", ")
+            "{}",
+            color::info(&format!("Orphans (not reviewed): {}", orphans.join(", ")))
         );
+        return Ok(());
     }
-    if !adopted_state_only.is_empty() {
-        println!(
-            "{} Marked as managed (already in config): {}",
-            color::blue("info:"),
-            adopted_state_only.join(", ")
-        );
+
+    let mut state = PackageState::load()?;
+    let pm = crate::core::pm::ParuPacman::new();
+    let details = crate::core::package::get_package_info(&orphans).unwrap_or_default();
+    let fixed_config = options.to.map(resolve_target).transpose()?;
+    let mut last_config: Option<String> = None;
+    let mut summary = OrphanSummary::default();
+    let mut queued = Vec::new();
+    let mut pending = orphans.into_iter();
+
+    for pkg in pending.by_ref() {
+        for line in describe_candidate(details.get(&pkg), false) {
+            println!("  {}", color::dim(&line));
+        }
+        match prompt_orphan_action(&pkg)? {
+            OrphanAction::Adopt => {
+                let path = match &fixed_config {
+                    Some(path) => path.clone(),
+                    None => {
+                        let hints = placement_hints(details.get(&pkg), None, false);
+                        match prompt_config_file_selection(last_config.as_deref(), &hints)? {
+                            Some(path) => path,
+                            None => {
+                                summary.skipped.push(pkg);
+                                break;
+                            }
+                        }
+                    }
+                };
+                last_config = Some(path.clone());
+                pm.mark_explicit(std::slice::from_ref(&pkg))?;
+                if add_package_to_file(&pkg, &path, Entry::Listed, options.force)?
+                    == AddResult::Added
+                {
+                    record_written(&mut summary.written, &path, &pkg);
+                }
+                note_included(&mut summary.included, include_target(&path)?);
+                state.add_managed_from(pkg.clone(), &path);
+                summary.adopted.push(pkg);
+            }
+            OrphanAction::Remove => queued.push(pkg),
+            OrphanAction::Skip => summary.skipped.push(pkg),
+            OrphanAction::Quit => {
+                summary.skipped.push(pkg);
+                break;
+            }
+        }
     }
-    if !ignored.is_empty() {
-        println!(
-            "{} Ignored package(s): {}",
-            color::yellow("!"),
-            ignored.join(", ")
-        );
+    summary.skipped.extend(pending);
+
+    if !summary.adopted.is_empty() {
+        state.save()?;
+        commit_written(&summary.written, &summary.included);
     }
-    if !skipped_already_managed.is_empty() {
-        println!(
-            "{} Already managed: {}",
-            color::blue("info:"),
-            skipped_already_managed.join(", ")
-        );
+    if !queued.is_empty() {
+        let confirmed = crate::cli::ui::confirm_remove_operation(&queued, &state.managed)?;
+        pm.remove_packages(&confirmed, false)?;
+        summary
+            .skipped
+            .extend(queued.into_iter().filter(|pkg| !confirmed.contains(pkg)));
+        summary.removed = confirmed;
     }
-    if !skipped_not_installed.is_empty() {
-        println!(
-            "{} Not installed (skipped): {}",
-            color::yellow("!"),
-            skipped_not_installed.join(", ")
+    for line in summary.lines() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn parse_orphan_action(input: &str) -> Option<OrphanAction> {
+    match input.to_lowercase().as_str() {
+        "a" | "adopt" => Some(OrphanAction::Adopt),
+        "r" | "remove" => Some(OrphanAction::Remove),
+        "s" | "skip" => Some(OrphanAction::Skip),
+        "q" | "quit" => Some(OrphanAction::Quit),
+        _ => None,
+    }
+}
+
+fn prompt_orphan_action(package_name: &str) -> Result<OrphanAction> {
+    loop {
+        print!(
+            "Orphan '{}' -> [a]dopt / [r]emove / [s]kip / [q]uit: ",
+            package_name
         );
+        std::io::stdout().flush().ok();
+
+        let input = crate::cli::ui::read_answer("the orphan action prompt")?;
+        match parse_orphan_action(&input) {
+            Some(action) => return Ok(action),
+            None => println!("{}", color::err("Invalid choice, try again")),
+        }
+    }
+}
+
+/// Where each target stands before anyone is asked about it
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Classified {
+    /// Installed and unmanaged; these get the adopt prompt
+    offered: Vec<String>,
+    /// Already declared, so adopting only marks them managed
+    in_config: Vec<String>,
+    already_managed: Vec<String>,
+    /// On the untracked list; only skipped in discovery mode
+    untracked: Vec<String>,
+    not_installed: Vec<String>,
+}
+
+fn classify(
+    targets: Vec<String>,
+    discover_mode: bool,
+    state: &PackageState,
+    config: &Config,
+    installed: &HashSet<String>,
+) -> Classified {
+    let mut classified = Classified::default();
+    for pkg in targets {
+        if state.is_managed(&pkg) {
+            classified.already_managed.push(pkg);
+        } else if discover_mode && state.is_untracked(&pkg) {
+            classified.untracked.push(pkg);
+        } else if !installed.contains(&pkg) {
+            classified.not_installed.push(pkg);
+        } else if config.packages.contains_key(&pkg) {
+            classified.in_config.push(pkg);
+        } else {
+            classified.offered.push(pkg);
+        }
+    }
+    classified
+}
+
+/// The outcome of an adopt run, or with `--dry-run` what it would be
+#[derive(Debug, Default)]
+struct Summary {
+    adopted: Vec<String>,
+    adopted_state_only: Vec<String>,
+    ignored: Vec<String>,
+    skipped_already_managed: Vec<String>,
+    skipped_not_installed: Vec<String>,
+    skipped: Vec<String>,
+    /// Config files and the packages added to each, in first-use order
+    written: Vec<(String, Vec<String>)>,
+    /// Candidates left out as dependencies of managed packages
+    dependencies: usize,
+    /// Offered packages that are foreign, tagged "(AUR)" in the lists
+    foreign: HashSet<String>,
+    /// Adopted packages whose `~/.config` directory was copied in and declared
+    configs: Vec<String>,
+    /// Groups main.owl was made to pull in, so the files written to are read
+    included: Vec<String>,
+}
+
+impl Summary {
+    fn print(&self, dry_run: bool) {
+        for line in self.lines(dry_run) {
+            println!("{}", line);
+        }
     }
-    if !skipped.is_empty() {
-        println!("{} Skipped: {}", color::blue("info:"), skipped.join(", "));
+
+    /// `packages` joined for a summary line, foreign ones tagged
+    fn names(&self, packages: &[String]) -> String {
+        packages
+            .iter()
+            .map(|pkg| {
+                if self.foreign.contains(pkg) {
+                    format!("{} (AUR)", pkg)
+                } else {
+                    pkg.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
     }
+
+    fn lines(&self, dry_run: bool) -> Vec<String> {
+        let (wrote, adopted, marked) = if dry_run {
+            ("Would write", "Would adopt", "Would mark as managed")
+        } else {
+            ("Wrote", "Adopted", "Marked as managed")
+        };
+        let mut lines = Vec::new();
+        let owl_dir = crate::internal::files::owl_dir().ok();
+        for (file, packages) in &self.written {
+            let block = owl_dir
+                .as_deref()
+                .and_then(|owl_dir| block_label(file, owl_dir))
+                .map(|label| format!(" ({})", label))
+                .unwrap_or_default();
+            lines.push(color::info(&format!(
+                "{} {} to {}{}",
+                wrote,
+                self.names(packages),
+                friendly_path(file),
+                block
+            )));
+        }
+        lines.extend(included_lines(&self.included, dry_run));
+        if !self.adopted.is_empty() {
+            lines.push(color::ok(&format!(
+                "{} {} package(s): {}",
+                adopted,
+                self.adopted.len(),
+                self.names(&self.adopted)
+            )));
+        }
+        if !self.configs.is_empty() {
+            lines.push(color::info(&format!(
+                "Copied ~/.config directories into the owl dotfiles for: {}",
+                self.configs.join(", ")
+            )));
+        }
+        if !self.adopted_state_only.is_empty() {
+            lines.push(color::info(&format!(
+                "{} (already in config): {}",
+                marked,
+                self.adopted_state_only.join(", ")
+            )));
+        }
+        if !self.ignored.is_empty() {
+            lines.push(color::warn(&format!(
+                "Ignored package(s): {}",
+                self.ignored.join(", ")
+            )));
+        }
+        if !self.skipped_already_managed.is_empty() {
+            lines.push(color::info(&format!(
+                "Already managed: {}",
+                self.skipped_already_managed.join(", ")
+            )));
+        }
+        if !self.skipped_not_installed.is_empty() {
+            lines.push(color::warn(&format!(
+                "Not installed (skipped): {}",
+                self.skipped_not_installed.join(", ")
+            )));
+        }
+        lines.extend(dependencies_line(self.dependencies));
+        if !self.skipped.is_empty() {
+            lines.push(color::info(&format!(
+                "Skipped: {}",
+                self.skipped.join(", ")
+            )));
+        }
+        lines
+    }
+}
+
+/// What `owl adopt --report` writes: every decision of the session, for auditing
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AdoptReport {
+    /// Seconds since the Unix epoch when the session ended
+    pub timestamp: u64,
+    pub adopted: Vec<AdoptedPackage>,
+    #[serde(default)]
+    pub adopted_state_only: Vec<String>,
+    #[serde(default)]
+    pub ignored: Vec<String>,
+    #[serde(default)]
+    pub skipped: Vec<String>,
+    #[serde(default)]
+    pub skipped_not_installed: Vec<String>,
+    #[serde(default)]
+    pub skipped_already_managed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdoptedPackage {
+    pub name: String,
+    /// The config file it was added to
+    pub file: Option<String>,
+}
+
+impl AdoptReport {
+    fn from_summary(summary: &Summary, timestamp: u64) -> Self {
+        let file = |pkg: &str| {
+            summary
+                .written
+                .iter()
+                .find(|(_, packages)| packages.iter().any(|written| written == pkg))
+                .map(|(file, _)| file.clone())
+        };
+        Self {
+            timestamp,
+            adopted: summary
+                .adopted
+                .iter()
+                .map(|name| AdoptedPackage {
+                    name: name.clone(),
+                    file: file(name),
+                })
+                .collect(),
+            adopted_state_only: summary.adopted_state_only.clone(),
+            ignored: summary.ignored.clone(),
+            skipped: summary.skipped.clone(),
+            skipped_not_installed: summary.skipped_not_installed.clone(),
+            skipped_already_managed: summary.skipped_already_managed.clone(),
+        }
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// Write the `--report` file for `summary`, if one was asked for
+fn save_report(path: Option<&str>, summary: &Summary) {
+    if let Some(path) = path {
+        let report = AdoptReport::from_summary(summary, crate::core::history::now());
+        crate::error::report("write the adopt report", report.write(Path::new(path)));
+    }
+}
+
+/// Split candidates into those to offer and those that a configured or managed
+/// package pulls in. When the dependency query fails, everything is offered.
+fn split_dependencies(
+    targets: Vec<String>,
+    state: &PackageState,
+    config: &Config,
+) -> (Vec<String>, Vec<String>) {
+    let mut roots: Vec<String> = config.packages.keys().cloned().collect();
+    roots.extend(state.managed.iter().cloned());
+    roots.sort();
+    roots.dedup();
+    match crate::core::package::dependency_closure(&roots) {
+        Ok(closure) => targets.into_iter().partition(|pkg| !closure.contains(pkg)),
+        Err(err) => {
+            println!(
+                "{}",
+                color::warn(&format!(
+                    "Could not check dependencies of managed packages: {:#}",
+                    err
+                ))
+            );
+            (targets, Vec::new())
+        }
+    }
+}
+
+fn dependencies_line(count: usize) -> Option<String> {
+    (count > 0).then(|| {
+        color::info(&format!(
+            "Left out {} dependency package(s) of managed packages (--include-deps to review them)",
+            count
+        ))
+    })
+}
+
+fn record_run(adopted: &[String], adopted_state_only: &[String], ignored: &[String]) -> Result<()> {
+    let mut record = crate::core::history::RunRecord::start(crate::core::history::RunKind::Adopt);
+    record.adopted = adopted.iter().chain(adopted_state_only).cloned().collect();
+    record.ignored = ignored.to_vec();
+    record.finish();
+    crate::core::history::save_run(&record)?;
+    crate::core::history::prune_runs(crate::core::settings::get().history.keep_runs)
+}
+
+/// Note that `package` was written to `file`, keeping files in first-use order
+fn record_written(written: &mut Vec<(String, Vec<String>)>, file: &str, package: &str) {
+    match written.iter_mut().find(|(path, _)| path == file) {
+        Some((_, packages)) => packages.push(package.to_string()),
+        None => written.push((file.to_string(), vec![package.to_string()])),
+    }
+}
+
+/// The lines of `--from`: a file path, or `-` for stdin
+fn read_package_list(from: &str) -> Result<Vec<String>> {
+    let content = if from == "-" {
+        std::io::read_to_string(std::io::stdin())
+            .map_err(|e| anyhow!("Failed to read the package list from stdin: {}", e))?
+    } else {
+        std::fs::read_to_string(from)
+            .map_err(|e| anyhow!("Failed to read the package list '{}': {}", from, e))?
+    };
+    Ok(content.lines().map(ToString::to_string).collect())
 }
 
+/// Trimmed, deduplicated names in order; blank lines and `#` comments are dropped
 fn normalize_targets(items: &[String]) -> Vec<String> {
     let mut seen = HashSet::new();
     let mut targets = Vec::new();
     for item in items {
-        let name = item.trim();
+        let name = item.split('#').next().unwrap_or_default().trim();
         if name.is_empty() {
             continue;
         }
@@ -247,71 +914,141 @@ fn normalize_targets(items: &[String]) -> Vec<String> {
     targets
 }
 
-fn discover_candidates_from_explicit(
-    explicit_installed: &HashSet<String>,
-    state: &PackageState,
-    config: &Config,
-) -> Vec<String> {
-    let mut candidates: Vec<String> = explicit_installed
-        .iter()
-        .filter(|pkg| !state.is_managed(pkg))
-        .filter(|pkg| !state.is_untracked(pkg))
-        .filter(|pkg| !config.packages.contains_key(*pkg))
-        .cloned()
-        .collect();
-    candidates.sort();
-    candidates
-}
-
-fn get_explicitly_installed_packages() -> Result<HashSet<String>> {
-    let manager = "pacman";
-    let output = Command::new(manager)
-        .args(["-Qeq"])
-        .output()
-        .map_err(|e| anyhow!("Failed to query explicit packages via {}: {}", manager, e))?;
-
-    if !output.status.success() {
-        return Err(anyhow!(
-            "{} -Qeq failed: {}",
-            manager,
-            String::from_utf8_lossy(&output.stderr).trim()
-        ));
+/// Split `items` into the entries at `selected` indices and the rest, preserving order
+fn partition_selected(items: Vec<String>, selected: &[usize]) -> (Vec<String>, Vec<String>) {
+    let selected: HashSet<usize> = selected.iter().copied().collect();
+    let (chosen, unchosen): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .enumerate()
+        .partition(|(idx, _)| selected.contains(idx));
+    (
+        chosen.into_iter().map(|(_, item)| item).collect(),
+        unchosen.into_iter().map(|(_, item)| item).collect(),
+    )
+}
+
+/// Explicitly installed packages that adopt would offer for review
+pub fn discover_candidates(state: &PackageState, config: &Config) -> Result<Vec<String>> {
+    let explicit_installed = crate::core::pm::ParuPacman::new().explicit_packages()?;
+    Ok(crate::core::analysis::unmanaged_explicit(
+        &explicit_installed,
+        state,
+        config,
+    ))
+}
+
+/// The action for the next package: the sticky one when set, otherwise the answer
+/// `ask` gets. A bulk answer becomes sticky and is returned as its single action.
+fn next_action(
+    sticky: &mut Option<PackageAction>,
+    ask: impl FnOnce() -> Result<PackageAction>,
+) -> Result<PackageAction> {
+    if let Some(action) = *sticky {
+        return Ok(action);
     }
+    let answer = ask()?;
+    match answer.bulk() {
+        Some(action) => {
+            *sticky = Some(action);
+            Ok(action)
+        }
+        None => Ok(answer),
+    }
+}
+
+/// The lines shown above the prompt for a candidate: its description, then where it
+/// comes from, its size, and when it was installed. Empty when pacman had no info.
+fn describe_candidate(info: Option<&PackageInfo>, foreign: bool) -> Vec<String> {
+    let Some(info) = info else {
+        return Vec::new();
+    };
+    let mut lines = Vec::new();
+    if !info.description.is_empty() {
+        lines.push(info.description.clone());
+    }
+    let mut facts = vec![
+        if foreign { "foreign (AUR)" } else { "repo" }.to_string(),
+        crate::cli::ui::format_size(info.installed_size),
+    ];
+    if !info.install_date.is_empty() {
+        facts.push(format!("installed {}", info.install_date));
+    }
+    lines.push(facts.join(" · "));
+    lines
+}
 
-    Ok(String::from_utf8_lossy(&output.stdout)
-        .lines()
-        .map(str::trim)
-        .filter(|line| !line.is_empty())
-        .map(ToString::to_string)
-        .collect())
+/// Read an answer to the package prompt; upper-case letters cover all remaining
+fn parse_package_action(input: &str) -> Option<PackageAction> {
+    match input {
+        "A" => return Some(PackageAction::AdoptAll),
+        "I" => return Some(PackageAction::IgnoreAll),
+        "S" => return Some(PackageAction::SkipAll),
+        _ => {}
+    }
+    match input.to_lowercase().as_str() {
+        "a" | "adopt" => Some(PackageAction::Adopt),
+        "i" | "ignore" => Some(PackageAction::Ignore),
+        "s" | "skip" => Some(PackageAction::Skip),
+        "adopt all" => Some(PackageAction::AdoptAll),
+        "ignore all" => Some(PackageAction::IgnoreAll),
+        "skip all" => Some(PackageAction::SkipAll),
+        "q" | "quit" => Some(PackageAction::Quit),
+        _ => None,
+    }
 }
 
-fn prompt_package_action(package_name: &str) -> Option<PackageAction> {
+fn prompt_package_action(package_name: &str, foreign: bool) -> Result<PackageAction> {
     loop {
         print!(
-            "Package '{}' -> [a]dopt / [i]gnore / [s]kip / [q]uit: ",
-            package_name
+            "Package '{}'{} -> [a]dopt / [i]gnore / [s]kip / [q]uit, or A/I/S for all remaining: ",
+            package_name,
+            if foreign { " (AUR)" } else { "" }
         );
-        std::io::stdout().flush().ok()?;
+        std::io::stdout().flush().ok();
 
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).ok()?;
-        match input.trim().to_lowercase().as_str() {
-            "a" | "adopt" => return Some(PackageAction::Adopt),
-            "i" | "ignore" => return Some(PackageAction::Ignore),
-            "s" | "skip" => return Some(PackageAction::Skip),
-            "q" | "quit" => return Some(PackageAction::Quit),
-            _ => println!("{}", color::red("Invalid choice, try again")),
+        let input = crate::cli::ui::read_answer("the adopt action prompt")?;
+        match parse_package_action(&input) {
+            Some(action) => return Ok(action),
+            None => println!("{}", color::err("Invalid choice, try again")),
         }
     }
 }
 
-fn prompt_config_file_selection() -> Result<Option<String>> {
-    let mut config_files = crate::internal::files::get_all_config_files()?;
+/// What an answer to the config file prompt picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigChoice {
+    File(usize),
+    Cancel,
+    Invalid,
+}
 
-    if config_files.is_empty() {
-        config_files.push(get_main_config_path()?);
+/// Read an answer to the config file prompt; an empty answer takes `default`
+fn parse_config_choice(input: &str, count: usize, default: Option<usize>) -> ConfigChoice {
+    if input.is_empty() {
+        return default.map_or(ConfigChoice::Invalid, ConfigChoice::File);
+    }
+    if input.eq_ignore_ascii_case("c") || input.eq_ignore_ascii_case("cancel") {
+        return ConfigChoice::Cancel;
     }
+    match input.parse::<usize>() {
+        Ok(idx) if idx < count => ConfigChoice::File(idx),
+        _ => ConfigChoice::Invalid,
+    }
+}
+
+/// Ask which config file gets the package; `default` is the file picked last time.
+/// Offers only files the config loader reads, this host's file and the files of
+/// declared groups even before they exist.
+fn prompt_config_file_selection(default: Option<&str>, hints: &[String]) -> Result<Option<String>> {
+    let owl_dir = crate::internal::files::owl_dir()?;
+    let config_files = adopt_targets(
+        &crate::core::config::Config::loaded_files()?,
+        &owl_dir,
+        &crate::internal::constants::get_host_name()?,
+    );
+    let suggested = suggest_target(&config_files, hints);
+    let default = suggested
+        .or_else(|| default.and_then(|file| config_files.iter().position(|path| path == file)));
 
     println!();
     println!(
@@ -319,45 +1056,294 @@ fn prompt_config_file_selection() -> Result<Option<String>> {
         color::bold("Select config file to write adopted packages:")
     );
     for (idx, path) in config_files.iter().enumerate() {
-        let friendly = path.replace(&std::env::var("HOME").unwrap_or_default(), "~");
-        println!("  [{}] {}", idx, color::highlight(&friendly));
+        let marker = if Some(idx) == suggested {
+            " (suggested)"
+        } else if Some(idx) == default {
+            " (default)"
+        } else {
+            ""
+        };
+        let new = if Path::new(path).exists() {
+            ""
+        } else {
+            ", new"
+        };
+        let block = block_label(path, &owl_dir)
+            .map(|label| color::dim(&format!(" ({}{})", label, new)))
+            .unwrap_or_default();
+        println!(
+            "  [{}] {}{}{}",
+            idx,
+            color::highlight(&friendly_path(path)),
+            block,
+            marker
+        );
     }
 
     loop {
-        print!(
-            "Config index (0-{}, or 'c' to cancel): ",
-            config_files.len() - 1
-        );
+        match default {
+            Some(idx) => print!(
+                "Config index (0-{}, Enter for {}, or 'c' to cancel): ",
+                config_files.len() - 1,
+                idx
+            ),
+            None => print!(
+                "Config index (0-{}, or 'c' to cancel): ",
+                config_files.len() - 1
+            ),
+        }
         std::io::stdout().flush().ok();
 
-        let mut input = String::new();
-        std::io::stdin()
-            .read_line(&mut input)
-            .map_err(|e| anyhow!("Failed to read selection: {}", e))?;
-
-        let input = input.trim();
-        if input.eq_ignore_ascii_case("c") || input.eq_ignore_ascii_case("cancel") {
-            return Ok(None);
+        let input = crate::cli::ui::read_answer("the config file selection")?;
+        match parse_config_choice(&input, config_files.len(), default) {
+            ConfigChoice::File(idx) => return Ok(Some(config_files[idx].clone())),
+            ConfigChoice::Cancel => return Ok(None),
+            ConfigChoice::Invalid => println!("{}", color::err("Invalid selection, try again")),
         }
+    }
+}
 
-        if let Ok(idx) = input.parse::<usize>() {
-            if idx < config_files.len() {
-                return Ok(Some(config_files[idx].clone()));
-            }
+/// Names a config file could be called after to hold this package: its pacman
+/// groups, then its sync db, or `aur` for foreign packages
+fn placement_hints(info: Option<&PackageInfo>, repo: Option<&str>, foreign: bool) -> Vec<String> {
+    let mut hints: Vec<String> = info.map(|info| info.groups.clone()).unwrap_or_default();
+    if foreign {
+        hints.push("aur".to_string());
+    } else if let Some(repo) = repo.filter(|repo| !repo.is_empty()) {
+        hints.push(repo.to_string());
+    }
+    hints
+}
+
+/// The first of `files` whose name, without `.owl`, matches a hint, trying the hints
+/// in order
+fn suggest_target(files: &[String], hints: &[String]) -> Option<usize> {
+    hints.iter().find_map(|hint| {
+        files.iter().position(|path| {
+            Path::new(path)
+                .file_stem()
+                .is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case(hint))
+        })
+    })
+}
+
+/// The files adopt can write to: the ones the loader reads for this host, `loaded`,
+/// so an adopted package is always seen by the next apply. main.owl and this host's
+/// file lead, and declared groups are offered before their file exists.
+fn adopt_targets(loaded: &[PathBuf], owl_dir: &Path, hostname: &str) -> Vec<String> {
+    use crate::internal::constants::{HOSTS_DIR, MAIN_CONFIG_FILE, OWL_EXT};
+    let mut targets = vec![
+        owl_dir.join(MAIN_CONFIG_FILE),
+        owl_dir
+            .join(HOSTS_DIR)
+            .join(format!("{}{}", hostname, OWL_EXT)),
+    ];
+    for path in loaded {
+        if !targets.contains(path) {
+            targets.push(path.clone());
         }
-        println!("{}", color::red("Invalid selection, try again"));
     }
+    targets
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Which part of the config a file is: `main`, `host <name>`, or `group <name>`;
+/// `None` for other files in the owl directory
+fn block_label(path: &str, owl_dir: &Path) -> Option<String> {
+    use crate::internal::constants::{GROUPS_DIR, HOSTS_DIR, MAIN_CONFIG_FILE, OWL_EXT};
+    let relative = Path::new(path).strip_prefix(owl_dir).ok()?;
+    if relative == Path::new(MAIN_CONFIG_FILE) {
+        return Some("main".to_string());
+    }
+    let mut parts = relative.iter().map(|part| part.to_str());
+    let (Some(Some(dir)), Some(Some(file)), None) = (parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let name = file.strip_suffix(OWL_EXT)?;
+    match dir {
+        HOSTS_DIR => Some(format!("host {}", name)),
+        GROUPS_DIR => Some(format!("group {}", name)),
+        _ => None,
+    }
+}
+
+/// The `--to` file; relative paths are taken from the owl directory. Files the config
+/// loader would never read are refused, since their packages would end up managed
+/// but undeclared.
+fn resolve_target(to: &str) -> Result<String> {
+    let path = crate::internal::files::owl_dir()?
+        .join(to)
+        .to_string_lossy()
+        .into_owned();
+    missing_include_for(&path)?;
+    Ok(path)
+}
+
+/// How the config loader comes to read `target`, given the files it reads now: `None`
+/// when it already does, the group main.owl has to pull in when `target` is the file
+/// of a group nothing includes, and an error for any other file
+fn missing_include(target: &Path, owl_dir: &Path, loaded: &[PathBuf]) -> Result<Option<String>> {
+    use crate::internal::constants::{GROUPS_DIR, OWL_EXT};
+    if loaded.iter().any(|path| path == target) {
+        return Ok(None);
+    }
+    let group = target
+        .strip_prefix(owl_dir.join(GROUPS_DIR))
+        .ok()
+        .and_then(|rest| rest.to_str())
+        .and_then(|rest| rest.strip_suffix(OWL_EXT))
+        .filter(|name| !name.is_empty() && !name.contains('/'));
+    match group {
+        Some(name) => Ok(Some(name.to_string())),
+        None => Err(anyhow!(
+            "{} is not read by the config loader, so packages adopted into it would not be declared; use main.owl, this host's file in hosts/, or a file in groups/",
+            friendly_path(&target.to_string_lossy())
+        )),
+    }
+}
+
+/// `missing_include` against the files the config loader reads on this host
+fn missing_include_for(target: &str) -> Result<Option<String>> {
+    missing_include(
+        Path::new(target),
+        &crate::internal::files::owl_dir()?,
+        &Config::loaded_files()?,
+    )
+}
+
+/// Make sure the config loader reads `target`, adding an `@group` line to main.owl
+/// when it is the file of a group nothing includes yet; returns that group
+fn include_target(target: &str) -> Result<Option<String>> {
+    let Some(group) = missing_include_for(target)? else {
+        return Ok(None);
+    };
+    let main =
+        crate::internal::files::owl_dir()?.join(crate::internal::constants::MAIN_CONFIG_FILE);
+    let mut document = if main.exists() {
+        Document::load(&main)?
+    } else {
+        Document::parse("")
+    };
+    if document.add_group(&group) {
+        crate::internal::files::write_atomic(&main, &document.render())?;
+    }
+    Ok(Some(group))
+}
+
+fn note_included(included: &mut Vec<String>, group: Option<String>) {
+    if let Some(group) = group
+        && !included.contains(&group)
+    {
+        included.push(group);
+    }
+}
+
+/// Summary lines for the groups main.owl was made to pull in
+fn included_lines(included: &[String], dry_run: bool) -> Vec<String> {
+    let verb = if dry_run { "Would add" } else { "Added" };
+    included
+        .iter()
+        .map(|group| {
+            color::info(&format!(
+                "{} @group {} to main.owl so groups/{}.owl is read",
+                verb, group, group
+            ))
+        })
+        .collect()
 }
 
 fn get_main_config_path() -> Result<String> {
-    let home = std::env::var("HOME").map_err(|_| anyhow!("HOME environment variable not set"))?;
-    let path = PathBuf::from(home)
-        .join(crate::internal::constants::OWL_DIR)
-        .join(crate::internal::constants::MAIN_CONFIG_FILE);
+    let path =
+        crate::internal::files::owl_dir()?.join(crate::internal::constants::MAIN_CONFIG_FILE);
     Ok(path.to_string_lossy().into_owned())
 }
 
-fn add_package_to_file(package_name: &str, file_path: &str) -> Result<AddResult> {
+/// The dotfile mapping `owl adopt --with-configs` proposes for `package`:
+/// `~/.config/<package>` into `dotfiles/<package>`
+fn config_dir_mapping(package: &str) -> String {
+    format!("{} -> ~/.config/{}", package, package)
+}
+
+/// With `--with-configs`: when `~/.config/<package>` is a directory, copy it into the
+/// owl dotfiles tree and return the mapping to declare. `None` adopts the package
+/// alone: no such directory, already in the owl tree, declined, or the copy failed.
+/// Directories over `[adopt] confirm_config_mib` always need a yes, so `--yes` and
+/// bulk answers leave them out.
+fn offer_config_dir(package: &str, ask: bool) -> Option<String> {
+    let mapping = config_dir_mapping(package);
+    let (source, home) =
+        crate::core::dotfiles::resolve_mapping(&crate::core::dotfiles::parse_mapping(&mapping))
+            .ok()?;
+    if !home.is_dir() {
+        return None;
+    }
+    if source.exists() {
+        crate::error::warn(&format!(
+            "{} already exists in the owl directory; adopting {} without its config",
+            source.display(),
+            package
+        ));
+        return None;
+    }
+    let size = crate::core::dotfiles::tree_size(&home).unwrap_or(0);
+    let limit = crate::core::settings::get().adopt.confirm_config_mib * 1024 * 1024;
+    if size > limit && !ask {
+        crate::error::warn(&format!(
+            "{} is {}, over [adopt] confirm_config_mib; adopting {} without it",
+            home.display(),
+            crate::cli::ui::format_size(size),
+            package
+        ));
+        return None;
+    }
+    if ask {
+        print!(
+            "  Also copy {} ({}) into the owl dotfiles? (y/N): ",
+            friendly_path(&home.to_string_lossy()),
+            crate::cli::ui::format_size(size)
+        );
+        std::io::stdout().flush().ok();
+        let answer = crate::cli::ui::read_answer("the config directory prompt").ok()?;
+        if !matches!(answer.to_lowercase().as_str(), "y" | "yes") {
+            return None;
+        }
+    }
+    match crate::core::dotfiles::copy_dir_all(&home, &source) {
+        Ok(()) => Some(mapping),
+        Err(err) => {
+            crate::error::warn(&format!(
+                "Could not copy {}, adopting {} without it: {:#}",
+                home.display(),
+                package,
+                err
+            ));
+            None
+        }
+    }
+}
+
+/// How an adopted package is written into its config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry<'a> {
+    /// A line in the first `@packages` section
+    Listed,
+    /// A line in the first `@aur` section
+    Aur,
+    /// An `@package` block with a `:config` line for this mapping
+    WithConfig(&'a str),
+}
+
+/// Add `package_name` to the config file as `entry` says. A file that does not parse
+/// is left untouched unless `force` is set.
+fn add_package_to_file(
+    package_name: &str,
+    file_path: &str,
+    entry: Entry,
+    force: bool,
+) -> Result<AddResult> {
     use std::fs;
 
     let path = Path::new(file_path);
@@ -378,58 +1364,50 @@ fn add_package_to_file(package_name: &str, file_path: &str) -> Result<AddResult>
         String::new()
     };
 
-    if config_contains_package(package_name, &content) {
-        return Ok(AddResult::AlreadyPresent);
-    }
-
-    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
-    let mut inserted = false;
-
-    for i in 0..lines.len() {
-        let trimmed = lines[i].trim();
-        if trimmed == "@packages" || trimmed == "@pkgs" {
-            lines.insert(i + 1, package_name.to_string());
-            inserted = true;
-            break;
+    let declared = match Config::parse(&content) {
+        Ok(parsed) => parsed.packages.contains_key(package_name),
+        Err(err) if !force => {
+            return Err(anyhow!(
+                "'{}' does not parse, so it was left alone (--force writes to it anyway): {:#}",
+                file_path,
+                err
+            ));
         }
+        Err(_) => content.lines().any(|line| line.trim() == package_name),
+    };
+    if declared {
+        return Ok(AddResult::AlreadyPresent);
     }
 
-    if !inserted {
-        if !lines.is_empty() && !lines.last().map(|line| line.is_empty()).unwrap_or(false) {
-            lines.push(String::new());
+    let mut document = Document::parse(&content);
+    match entry {
+        Entry::Listed => document.insert_package(package_name),
+        Entry::Aur => document.insert_aur_package(package_name),
+        Entry::WithConfig(mapping) => {
+            document.append_package_block(package_name, &[format!(":config {}", mapping)])
         }
-        lines.push("@packages".to_string());
-        lines.push(package_name.to_string());
     }
-
-    let new_content = lines.join("\n") + "\n";
-    fs::write(path, new_content)
-        .map_err(|e| anyhow!("Failed to write config file '{}': {}", file_path, e))?;
+    crate::internal::files::write_atomic(path, &document.render())?;
 
     Ok(AddResult::Added)
 }
 
-fn config_contains_package(package_name: &str, content: &str) -> bool {
-    if let Ok(parsed) = Config::parse(content) {
-        return parsed.packages.contains_key(package_name);
-    }
-    content.lines().any(|line| line.trim() == package_name)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_discover_candidates_filters_state_and_config() {
+    fn test_classify_splits_targets_without_prompting() {
         let mut state = PackageState {
             untracked: Vec::new(),
             hidden: Vec::new(),
             managed: Vec::new(),
+            pinned: Default::default(),
+            sources: Default::default(),
+            remove_hooks: Default::default(),
         };
         state.add_managed("managed".to_string());
         state.add_untracked("ignored".to_string());
-
         let mut config = Config::new();
         config.packages.insert(
             "in-config".to_string(),
@@ -437,21 +1415,309 @@ mod tests {
                 config: Vec::new(),
                 service: None,
                 env_vars: std::collections::HashMap::new(),
+                exact: false,
+                hooks: Default::default(),
             },
         );
+        let installed: HashSet<String> = ["managed", "ignored", "in-config", "htop"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let targets: Vec<String> = ["managed", "ignored", "in-config", "htop", "gone"]
+            .into_iter()
+            .map(String::from)
+            .collect();
 
-        let explicit_installed = HashSet::from([
-            "managed".to_string(),
-            "ignored".to_string(),
-            "in-config".to_string(),
-            "candidate-a".to_string(),
-            "candidate-b".to_string(),
-        ]);
+        let classified = classify(targets.clone(), true, &state, &config, &installed);
+        assert_eq!(classified.offered, vec!["htop".to_string()]);
+        assert_eq!(classified.in_config, vec!["in-config".to_string()]);
+        assert_eq!(classified.already_managed, vec!["managed".to_string()]);
+        assert_eq!(classified.untracked, vec!["ignored".to_string()]);
+        assert_eq!(classified.not_installed, vec!["gone".to_string()]);
+
+        // Named explicitly, an untracked package is offered again
+        let classified = classify(targets, false, &state, &config, &installed);
+        assert_eq!(
+            classified.offered,
+            vec!["ignored".to_string(), "htop".to_string()]
+        );
+
+        let summary = Summary {
+            adopted: vec!["htop".to_string(), "paru-bin".to_string()],
+            adopted_state_only: vec!["in-config".to_string()],
+            dependencies: 3,
+            foreign: HashSet::from(["paru-bin".to_string()]),
+            ..Summary::default()
+        };
+        let dry = summary.lines(true);
+        assert!(dry[0].ends_with("Would adopt 2 package(s): htop, paru-bin (AUR)"));
+        assert!(dry[1].ends_with("Would mark as managed (already in config): in-config"));
+        assert!(summary.lines(false)[0].ends_with("Adopted 2 package(s): htop, paru-bin (AUR)"));
+        assert!(dry[2].contains("Left out 3 dependency package(s)"));
+    }
+
+    #[test]
+    fn test_report_round_trips_with_target_files() {
+        let mut summary = Summary {
+            adopted: vec!["htop".to_string(), "git".to_string()],
+            ignored: vec!["steam".to_string()],
+            skipped_not_installed: vec!["nope".to_string()],
+            ..Summary::default()
+        };
+        record_written(&mut summary.written, "/home/me/.owl/main.owl", "htop");
+        let report = AdoptReport::from_summary(&summary, 1_700_000_000);
+        assert_eq!(
+            report.adopted,
+            vec![
+                AdoptedPackage {
+                    name: "htop".to_string(),
+                    file: Some("/home/me/.owl/main.owl".to_string()),
+                },
+                AdoptedPackage {
+                    name: "git".to_string(),
+                    file: None,
+                },
+            ]
+        );
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("report.json");
+        report.write(&path).unwrap();
+        let read: AdoptReport =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(read, report);
+
+        let sparse: AdoptReport =
+            serde_json::from_str(r#"{"timestamp": 1, "adopted": []}"#).unwrap();
+        assert_eq!(
+            sparse,
+            AdoptReport {
+                timestamp: 1,
+                ..AdoptReport::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_orphan_actions_and_summary() {
+        assert_eq!(parse_orphan_action("R"), Some(OrphanAction::Remove));
+        assert_eq!(parse_orphan_action("adopt"), Some(OrphanAction::Adopt));
+        assert_eq!(parse_orphan_action("i"), None);
+
+        let summary = OrphanSummary {
+            adopted: vec!["m4".to_string()],
+            removed: vec!["libfoo".to_string(), "libbar".to_string()],
+            skipped: vec!["gettext".to_string()],
+            written: Vec::new(),
+            included: Vec::new(),
+        };
+        let lines = summary.lines();
+        assert!(lines[0].ends_with("Adopted 1 orphan(s), now explicitly installed: m4"));
+        assert!(lines[1].ends_with("Removed 2 orphan(s): libfoo, libbar"));
+        assert!(lines[2].ends_with("Orphans skipped: gettext"));
+    }
+
+    #[test]
+    fn test_bulk_answer_stops_the_prompts() {
+        assert_eq!(parse_package_action("a"), Some(PackageAction::Adopt));
+        assert_eq!(parse_package_action("A"), Some(PackageAction::AdoptAll));
+        assert_eq!(parse_package_action("I"), Some(PackageAction::IgnoreAll));
+        assert_eq!(
+            parse_package_action("Skip All"),
+            Some(PackageAction::SkipAll)
+        );
+        assert_eq!(parse_package_action("x"), None);
+
+        let mut answers = vec![
+            PackageAction::Adopt,
+            PackageAction::Skip,
+            PackageAction::IgnoreAll,
+        ]
+        .into_iter();
+        let mut asked = 0;
+        let mut sticky = None;
+        let actions: Vec<PackageAction> = (0..5)
+            .map(|_| {
+                next_action(&mut sticky, || {
+                    asked += 1;
+                    Ok(answers.next().unwrap())
+                })
+                .unwrap()
+            })
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                PackageAction::Adopt,
+                PackageAction::Skip,
+                PackageAction::Ignore,
+                PackageAction::Ignore,
+                PackageAction::Ignore,
+            ]
+        );
+        assert_eq!(asked, 3);
+
+        // --yes starts out sticky and never asks
+        let mut sticky = Some(PackageAction::Adopt);
+        let action = next_action(&mut sticky, || panic!("asked despite --yes")).unwrap();
+        assert_eq!(action, PackageAction::Adopt);
+    }
+
+    #[test]
+    fn test_describe_candidate_lists_pacman_details() {
+        let info = PackageInfo {
+            name: "perl-clone".to_string(),
+            description: "Recursively copy Perl datatypes".to_string(),
+            installed_size: 42_240,
+            install_date: "Tue 04 Jun 2024 10:12:03 AM CEST".to_string(),
+            ..PackageInfo::default()
+        };
+        assert_eq!(
+            describe_candidate(Some(&info), false),
+            [
+                "Recursively copy Perl datatypes",
+                "repo · 41.25 KiB · installed Tue 04 Jun 2024 10:12:03 AM CEST",
+            ]
+        );
+        let bare = PackageInfo {
+            installed_size: 1024,
+            ..PackageInfo::default()
+        };
+        assert_eq!(
+            describe_candidate(Some(&bare), true),
+            ["foreign (AUR) · 1.00 KiB"]
+        );
+        assert!(describe_candidate(None, false).is_empty());
+    }
+
+    #[test]
+    fn test_normalize_targets_reads_a_package_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("pkglist.txt");
+        std::fs::write(
+            &list,
+            "# from the laptop\nhtop\n\n  git  \nhtop\nripgrep # search\n",
+        )
+        .unwrap();
+        let lines = read_package_list(list.to_str().unwrap()).unwrap();
+        assert_eq!(normalize_targets(&lines), ["htop", "git", "ripgrep"]);
+        assert!(read_package_list("/nonexistent/pkglist.txt").is_err());
+    }
+
+    #[test]
+    fn test_partition_selected_preserves_order() {
+        let items = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let (chosen, unchosen) = partition_selected(items, &[2, 0]);
+        assert_eq!(chosen, vec!["a".to_string(), "c".to_string()]);
+        assert_eq!(unchosen, vec!["b".to_string()]);
+    }
 
-        let candidates = discover_candidates_from_explicit(&explicit_installed, &state, &config);
+    #[test]
+    fn test_missing_include_only_accepts_files_the_loader_can_read() {
+        let owl = Path::new("/home/me/.owl");
+        let loaded = vec![
+            owl.join("main.owl"),
+            owl.join("hosts/laptop.owl"),
+            owl.join("groups/dev.owl"),
+        ];
+        let reach = |path: &str| missing_include(&owl.join(path), owl, &loaded);
+        assert_eq!(reach("main.owl").unwrap(), None);
+        assert_eq!(reach("./groups/dev.owl").unwrap(), None);
+        assert_eq!(reach("groups/cli.owl").unwrap(), Some("cli".to_string()));
+        assert!(reach("hosts/desktop.owl").is_err());
+        assert!(reach("groups/nested/cli.owl").is_err());
+        assert!(reach("notes.owl").is_err());
+        assert!(reach("/etc/owl.owl").is_err());
+    }
+
+    #[test]
+    fn test_adopt_targets_offer_host_and_group_files() {
+        let owl = Path::new("/home/me/.owl");
+        // What the loader reads: groups/gaming.owl is declared but not written yet,
+        // and groups/unused.owl, which no @group names, is not among them
+        let loaded = [
+            "/home/me/.owl/main.owl",
+            "/home/me/.owl/hosts/laptop.owl",
+            "/home/me/.owl/groups/dev.owl",
+            "/home/me/.owl/groups/gaming.owl",
+        ]
+        .map(PathBuf::from);
+        let targets = adopt_targets(&loaded[2..], owl, "laptop");
         assert_eq!(
-            candidates,
-            vec!["candidate-a".to_string(), "candidate-b".to_string()]
+            targets,
+            vec![
+                "/home/me/.owl/main.owl",
+                "/home/me/.owl/hosts/laptop.owl",
+                "/home/me/.owl/groups/dev.owl",
+                "/home/me/.owl/groups/gaming.owl",
+            ]
+        );
+        assert_eq!(adopt_targets(&loaded, owl, "laptop"), targets);
+        let labels: Vec<Option<String>> =
+            targets.iter().map(|path| block_label(path, owl)).collect();
+        assert_eq!(
+            labels,
+            vec![
+                Some("main".to_string()),
+                Some("host laptop".to_string()),
+                Some("group dev".to_string()),
+                Some("group gaming".to_string()),
+            ]
+        );
+        assert_eq!(block_label("/home/me/.owl/notes/x.owl", owl), None);
+        assert_eq!(block_label("/elsewhere/main.owl", owl), None);
+    }
+
+    #[test]
+    fn test_suggest_target_matches_groups_then_repo() {
+        let files = vec![
+            "/home/me/.owl/main.owl".to_string(),
+            "/home/me/.owl/groups/Gnome.owl".to_string(),
+            "/home/me/.owl/groups/multilib.owl".to_string(),
+            "/home/me/.owl/aur.owl".to_string(),
+        ];
+        let info = PackageInfo {
+            groups: vec!["gnome".to_string()],
+            ..PackageInfo::default()
+        };
+        let hints = placement_hints(Some(&info), Some("multilib"), false);
+        assert_eq!(hints, ["gnome", "multilib"]);
+        assert_eq!(suggest_target(&files, &hints), Some(1));
+        assert_eq!(
+            suggest_target(&files, &placement_hints(None, Some("multilib"), false)),
+            Some(2)
+        );
+        assert_eq!(
+            suggest_target(&files, &placement_hints(None, None, true)),
+            Some(3)
+        );
+        assert_eq!(
+            suggest_target(&files, &placement_hints(None, Some("extra"), false)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_config_choice_defaults_to_the_last_pick() {
+        assert_eq!(parse_config_choice("", 3, Some(2)), ConfigChoice::File(2));
+        assert_eq!(parse_config_choice("", 3, None), ConfigChoice::Invalid);
+        assert_eq!(parse_config_choice("0", 3, Some(2)), ConfigChoice::File(0));
+        assert_eq!(parse_config_choice("3", 3, Some(2)), ConfigChoice::Invalid);
+        assert_eq!(parse_config_choice("C", 3, None), ConfigChoice::Cancel);
+
+        let mut written = Vec::new();
+        record_written(&mut written, "browser.owl", "firefox");
+        record_written(&mut written, "cli.owl", "htop");
+        record_written(&mut written, "browser.owl", "chromium");
+        assert_eq!(
+            written,
+            [
+                (
+                    "browser.owl".to_string(),
+                    vec!["firefox".to_string(), "chromium".to_string()]
+                ),
+                ("cli.owl".to_string(), vec!["htop".to_string()]),
+            ]
         );
     }
 
@@ -459,10 +1725,89 @@ mod tests {
     fn test_add_package_to_file_creates_packages_section() {
         let temp = tempfile::tempdir().expect("failed to create temp dir");
         let path = temp.path().join("main.owl");
-        let result = add_package_to_file("htop", path.to_str().expect("utf8 path"));
+        let result = add_package_to_file(
+            "htop",
+            path.to_str().expect("utf8 path"),
+            Entry::Listed,
+            false,
+        );
         assert!(matches!(result, Ok(AddResult::Added)));
 
-        let content = std::fs::read_to_string(path).expect("failed to read file");
+        let content = std::fs::read_to_string(&path).expect("failed to read file");
         assert!(content.contains("@packages\nhtop\n"));
+
+        std::fs::write(&path, "# cli\n@packages\nbat\n# editors\nvim\n").unwrap();
+        assert!(matches!(
+            add_package_to_file(
+                "htop",
+                path.to_str().expect("utf8 path"),
+                Entry::Listed,
+                false
+            ),
+            Ok(AddResult::Added)
+        ));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# cli\n@packages\nbat\nhtop\n# editors\nvim\n"
+        );
+        assert!(matches!(
+            add_package_to_file(
+                "paru-bin",
+                path.to_str().expect("utf8 path"),
+                Entry::Aur,
+                false
+            ),
+            Ok(AddResult::Added)
+        ));
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .ends_with("vim\n\n@aur\nparu-bin\n")
+        );
+    }
+
+    #[test]
+    fn test_add_package_with_config_writes_a_block() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        let path = temp.path().join("main.owl");
+        std::fs::write(&path, "@packages\nhtop\n").unwrap();
+        let mapping = config_dir_mapping("kitty");
+        assert_eq!(mapping, "kitty -> ~/.config/kitty");
+
+        let file = path.to_str().expect("utf8 path");
+        assert!(matches!(
+            add_package_to_file("kitty", file, Entry::WithConfig(&mapping), false),
+            Ok(AddResult::Added)
+        ));
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            "@packages\nhtop\n\n@package kitty\n:config kitty -> ~/.config/kitty\n"
+        );
+        let config = Config::parse(&content).unwrap();
+        assert_eq!(config.packages["kitty"].config, [mapping.as_str()]);
+    }
+
+    #[test]
+    fn test_add_package_to_file_leaves_unparsable_files_alone() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        let path = temp.path().join("main.owl");
+        let original = "# mine\n@packages\nhtop\n:exact\n";
+        std::fs::write(&path, original).unwrap();
+        let file = path.to_str().expect("utf8 path");
+
+        let err = add_package_to_file("git", file, Entry::Listed, false).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        assert_eq!(std::fs::read(&path).unwrap(), original.as_bytes());
+
+        assert!(matches!(
+            add_package_to_file("git", file, Entry::Listed, true),
+            Ok(AddResult::Added)
+        ));
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .starts_with("# mine\n@packages\ngit\nhtop\n")
+        );
     }
 }
@@ -1,9 +1,4 @@
-use anyhow::{Result, anyhow};
-
-/// Get list of AUR packages that can be updated
-pub fn get_aur_updates() -> Result<Vec<String>> {
-    crate::core::pm::ParuPacman::new().get_aur_updates()
-}
+use anyhow::{Context, anyhow};
 
 /// Count packages that have dotfile configurations
 pub fn count_dotfile_packages(config: &crate::core::config::Config) -> usize {
@@ -30,7 +25,15 @@ pub struct Analysis {
     pub package_count: usize,
     pub config: crate::core::config::Config,
     pub state: crate::core::state::PackageState,
+    /// The package database as analysis saw it, shared with the later phases
+    pub system: crate::core::system::SystemSnapshot,
     pub actions: Vec<crate::core::package::PackageAction>,
+    /// Configured packages an installed package provides, left uninstalled
+    pub satisfied: Vec<crate::core::package::Satisfied>,
+    /// Managed packages that would be removed but are protected
+    pub protected: Vec<String>,
+    /// Managed packages missing from the system, and stale state entries
+    pub drift: crate::core::analysis::Drift,
     pub dotfile_count: usize,
     pub service_count: usize,
     pub config_package_count: usize,
@@ -46,46 +49,54 @@ pub fn analyze_system() -> anyhow::Result<Analysis> {
     let config_handle = thread::spawn(crate::core::config::Config::load_all_relevant_config_files);
     // 3) Load package state from disk
     let state_handle = thread::spawn(crate::core::state::PackageState::load);
-    // 4) Prewarm installed package cache to avoid repeated -Q calls later
-    let installed_warm_handle = thread::spawn(|| {
-        let _ = crate::core::package::get_installed_packages();
-        Ok::<(), anyhow::Error>(())
+    // 4) Read the package database once for every phase of the run
+    let system_handle = thread::spawn(|| {
+        crate::core::system::SystemSnapshot::capture(&crate::core::system::Backend::select())
     });
 
     // Join results
     let package_count = count_handle
         .join()
         .map_err(|_| anyhow!("Failed to join package count thread"))?
-        .map_err(|e| anyhow!("Failed to get package count: {}", e))?;
+        .context("Failed to get package count")?;
 
     let mut state = state_handle
         .join()
         .map_err(|_| anyhow!("Failed to join state loader thread"))?
-        .map_err(|e| anyhow!("Failed to load package state: {}", e))?;
+        .context("Failed to load package state")?;
 
     let config = config_handle
         .join()
         .map_err(|_| anyhow!("Failed to join config loader thread"))?
-        .map_err(|e| anyhow!("Failed to load config: {}", e))?;
+        .context("Failed to load config")?;
 
-    // Ensure installed cache warm-up finished (best-effort)
-    let _ = installed_warm_handle.join();
+    let system = system_handle
+        .join()
+        .map_err(|_| anyhow!("Failed to join package database thread"))?
+        .context("Failed to query installed packages")?;
 
     // Seed managed state with currently installed packages that are present in config.
     // This ensures future removals are detected only for packages user explicitly managed via config.
-    if seed_managed_with_desired_installed(&config, &mut state)? {
+    let seeded = seed_managed_with_desired_installed(&config, &mut state, &system)?;
+    // Remove hooks are needed once a package has left the config, so state keeps them
+    if state.record_remove_hooks(&config) || seeded {
         // Best-effort save; don't fail analysis if saving state fails.
         if let Err(e) = state.save() {
             eprintln!(
                 "{}",
-                crate::internal::color::red(&format!("Failed to save seeded package state: {}", e))
+                crate::internal::color::red(&format!("Failed to save package state: {}", e))
             );
         }
     }
 
+    let drift = crate::core::analysis::drift(&state, &config, &system);
+
     // Plan package actions (installs and removals)
-    let actions = crate::core::package::plan_package_actions(&config, &state)
-        .map_err(|e| anyhow!("Failed to plan package actions: {}", e))?;
+    let crate::core::plan::PackageActions {
+        actions,
+        satisfied,
+        protected,
+    } = crate::core::plan::package_actions(&config, &state, &system)?;
 
     // Calculate dynamic values (these are fast)
     let dotfile_count = count_dotfile_packages(&config);
@@ -96,7 +107,11 @@ pub fn analyze_system() -> anyhow::Result<Analysis> {
         package_count,
         config,
         state,
+        system,
         actions,
+        satisfied,
+        protected,
+        drift,
         dotfile_count,
         service_count,
         config_package_count,
@@ -107,6 +122,7 @@ pub fn analyze_system() -> anyhow::Result<Analysis> {
 pub fn seed_managed_with_desired_installed(
     config: &crate::core::config::Config,
     state: &mut crate::core::state::PackageState,
+    system: &crate::core::system::SystemSnapshot,
 ) -> anyhow::Result<bool> {
     let mut changed = false;
 
@@ -124,7 +140,7 @@ pub fn seed_managed_with_desired_installed(
     // Group packages by whether they might be groups or regular packages
     // to minimize redundant group checks
     for pkg in packages_to_check {
-        match crate::core::package::is_package_or_group_installed(pkg) {
+        match system.has_package_or_group(pkg) {
             Ok(true) => {
                 state.add_managed(pkg.to_string());
                 changed = true;
@@ -1,5 +1,10 @@
-/// Apply dotfile synchronization
-pub fn apply_dotfiles_with_config(config: &crate::core::config::Config, dry_run: bool) {
+/// Apply dotfile synchronization, returning the destinations that were written. A dry
+/// run's listing goes through the pager.
+pub fn apply_dotfiles_with_config(
+    config: &crate::core::config::Config,
+    flags: &crate::cli::handler::GlobalFlags,
+) -> Vec<String> {
+    let dry_run = flags.dry_run;
     // Config is provided from earlier analysis
 
     // Get dotfile mappings from config
@@ -14,18 +19,15 @@ pub fn apply_dotfiles_with_config(config: &crate::core::config::Config, dry_run:
             "  {} No dotfiles configured",
             crate::internal::color::blue("info:")
         );
-        return;
+        return Vec::new();
     }
 
     // Check if any actions are needed
     let has_actions = match crate::core::dotfiles::has_actionable_dotfiles(&mappings) {
         Ok(has) => has,
         Err(err) => {
-            eprintln!(
-                "{}",
-                crate::internal::color::red(&format!("Failed to analyze dotfiles: {}", err))
-            );
-            return;
+            crate::error::report("analyze dotfiles", Err(err));
+            return Vec::new();
         }
     };
 
@@ -35,20 +37,42 @@ pub fn apply_dotfiles_with_config(config: &crate::core::config::Config, dry_run:
             crate::internal::color::green("➔"),
             mappings.len()
         );
-        return;
+        if !dry_run && let Err(err) = crate::core::dotfiles::record_unrecorded(&mappings) {
+            log::warn!("dotfiles: could not record deployed files: {:#}", err);
+        }
+        return Vec::new();
     }
 
     // Analyze and apply dotfiles
     let actions = match crate::core::dotfiles::apply_dotfiles(&mappings, dry_run) {
         Ok(actions) => actions,
         Err(err) => {
-            eprintln!(
-                "{}",
-                crate::internal::color::red(&format!("Failed to apply dotfiles: {}", err))
-            );
-            return;
+            crate::error::report("apply dotfiles", Err(err));
+            return Vec::new();
         }
     };
 
-    crate::core::dotfiles::print_actions(&actions, dry_run);
+    if dry_run {
+        let mut out = crate::cli::ui::Pager::new(flags);
+        crate::core::dotfiles::print_actions(&actions, dry_run, &mut out);
+        out.finish();
+    } else {
+        crate::core::dotfiles::print_actions(&actions, dry_run, &mut std::io::stdout());
+        for action in &actions {
+            let status = match action.status {
+                crate::core::dotfiles::DotfileStatus::Create => "create",
+                crate::core::dotfiles::DotfileStatus::Update => "update",
+                crate::core::dotfiles::DotfileStatus::UpToDate => continue,
+            };
+            crate::cli::porcelain::emit(crate::cli::porcelain::Event::DotfileWritten {
+                destination: action.mapping.destination.clone(),
+                status: status.to_string(),
+            });
+        }
+    }
+    actions
+        .into_iter()
+        .filter(|action| action.status != crate::core::dotfiles::DotfileStatus::UpToDate)
+        .map(|action| action.mapping.destination)
+        .collect()
 }
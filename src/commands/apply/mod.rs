@@ -1,23 +1,178 @@
 pub mod analysis;
 pub mod dotfiles;
 pub mod packages;
+pub mod plan;
+pub mod summary;
 pub mod system;
 
-use crate::error::handle_error_with_context;
+use crate::cli::porcelain;
+use crate::error::report;
+
+/// Which phases an apply runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scope {
+    #[default]
+    All,
+    /// Package removals, installs, and updates; no dotfiles or system section
+    PackagesOnly,
+    /// Dotfiles alone: no package work at all, so no AUR or news checks
+    DotfilesOnly,
+}
+
+impl Scope {
+    fn packages(self) -> bool {
+        self != Scope::DotfilesOnly
+    }
+
+    fn dotfiles(self) -> bool {
+        self != Scope::PackagesOnly
+    }
+
+    fn system(self) -> bool {
+        self == Scope::All
+    }
+
+    /// The header line for a partial apply
+    fn describe(self) -> Option<&'static str> {
+        match self {
+            Scope::All => None,
+            Scope::PackagesOnly => {
+                Some("Packages only - dotfiles and the system section are skipped")
+            }
+            Scope::DotfilesOnly => {
+                Some("Dotfiles only - packages and the system section are skipped")
+            }
+        }
+    }
+}
+
+/// How `owl apply` runs, beyond the global flags
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    pub scope: Scope,
+    /// Print the whole plan and ask once before anything runs; with `--dry-run`,
+    /// only print it
+    pub plan_first: bool,
+    /// Skip the AUR update lookup, the one network call a dry run makes, and the AUR
+    /// name check; from the global `--offline`
+    pub offline: bool,
+    /// Skip the AUR check of install names the repos do not have (`--no-network-check`)
+    pub no_network_check: bool,
+    /// Apply only these configured packages (`--only`): their installs, dotfiles,
+    /// and services, with no updates or removals
+    pub only: Vec<String>,
+    /// Remove orphaned dependencies after the package work (`--clean-orphans`)
+    pub clean_orphans: bool,
+    /// Skip everything after the first error (`--fail-fast`)
+    pub fail_fast: bool,
+    /// Install missing packages without upgrading installed ones (`--no-upgrade`)
+    pub no_upgrade: bool,
+    /// Drop stale managed entries from state without asking (`--prune-state`)
+    pub prune_state: bool,
+}
+
+impl ApplyOptions {
+    /// The defaults, with what the global flags decide
+    pub fn new(flags: &crate::cli::handler::GlobalFlags) -> Self {
+        Self {
+            offline: flags.offline,
+            ..Self::default()
+        }
+    }
+
+    /// Whether to update installed packages; neither `--no-upgrade` nor a targeted
+    /// apply does
+    fn updates(&self) -> bool {
+        self.only.is_empty() && !self.no_upgrade
+    }
+
+    /// Whether to ask the AUR helper for outdated AUR packages. Read-only, so a dry
+    /// run checks too.
+    pub(crate) fn check_aur_updates(&self) -> bool {
+        !self.offline && self.updates()
+    }
+
+    /// Whether to look up in the AUR the installs the repos do not have
+    pub(crate) fn check_names(&self) -> bool {
+        !self.offline && !self.no_network_check
+    }
+
+    /// Whether to run the orphan cleanup, from the flag or the `clean_orphans` setting;
+    /// never for a targeted apply, which removes nothing
+    fn cleans_orphans(&self) -> bool {
+        (self.clean_orphans || crate::core::settings::get().clean_orphans) && self.only.is_empty()
+    }
+
+    /// Whether the first error stops the run, from the flag or the `fail_fast` setting
+    fn fails_fast(&self) -> bool {
+        self.fail_fast || crate::core::settings::get().fail_fast
+    }
+
+    /// Names in `--only` that `config` does not declare
+    fn undeclared(&self, config: &crate::core::config::Config) -> Vec<String> {
+        self.only
+            .iter()
+            .filter(|name| !config.packages.contains_key(*name))
+            .cloned()
+            .collect()
+    }
+}
+
+/// `config` cut down to the `only` packages for a targeted apply. Environment
+/// variables are dropped: env.sh is written whole, so writing it from part of the
+/// config would lose the rest.
+fn targeted_config(
+    config: &crate::core::config::Config,
+    only: &[String],
+) -> crate::core::config::Config {
+    let mut config = config.clone();
+    config.packages.retain(|name, _| only.contains(name));
+    for package in config.packages.values_mut() {
+        package.env_vars.clear();
+    }
+    config.env_vars.clear();
+    config
+}
 
 /// Run the apply command to update packages and system
 pub fn run(flags: &crate::cli::handler::GlobalFlags) {
+    run_with(flags, ApplyOptions::new(flags));
+}
+
+/// Run apply, limited to the phases `options.scope` includes
+pub fn run_with(flags: &crate::cli::handler::GlobalFlags, options: ApplyOptions) {
     let dry_run = flags.dry_run;
-    let non_interactive = flags.non_interactive;
-    if dry_run {
-        println!(
-            "  {} Dry run mode - no changes will be made to the system",
-            crate::internal::color::blue("info:")
-        );
+    let scope = options.scope;
+    porcelain::emit(porcelain::Event::Start {
+        schema: porcelain::SCHEMA_VERSION,
+        command: "apply".to_string(),
+    });
+    let mut package_params = packages::PackageOperationParams::new(flags);
+    let header: Vec<String> = package_params
+        .modes()
+        .into_iter()
+        .chain(scope.describe().map(ToString::to_string))
+        .chain(
+            (!options.only.is_empty())
+                .then(|| format!("Only {} - no updates or removals", options.only.join(", "))),
+        )
+        .chain((options.no_upgrade && options.only.is_empty()).then(|| {
+            "No upgrade - missing packages are installed, nothing is upgraded".to_string()
+        }))
+        .collect();
+    for line in &header {
+        println!("  {}", crate::internal::color::info(line));
+    }
+    if !header.is_empty() {
         println!();
     }
 
+    if !dry_run {
+        crate::cli::notify::arm();
+    }
+
     // Perform analysis with spinner
+    porcelain::phase("analysis");
     let analysis_result = crate::internal::util::execute_with_progress(
         analysis::analyze_system,
         "Analyzing system configuration",
@@ -30,6 +185,33 @@ pub fn run(flags: &crate::cli::handler::GlobalFlags) {
         }
     };
 
+    if !options.only.is_empty() {
+        let undeclared = options.undeclared(&analysis.config);
+        if !undeclared.is_empty() {
+            crate::error::exit_with_error(anyhow::anyhow!(
+                "--only names packages the config does not declare: {}",
+                undeclared.join(", ")
+            ));
+        }
+        analysis.actions.retain(|action| match action {
+            crate::core::package::PackageAction::Install { name } => options.only.contains(name),
+            crate::core::package::PackageAction::Remove { .. } => false,
+        });
+        analysis
+            .satisfied
+            .retain(|satisfied| options.only.contains(&satisfied.name));
+        analysis.config = targeted_config(&analysis.config, &options.only);
+    }
+    if !scope.packages() {
+        analysis.actions.clear();
+        analysis.satisfied.clear();
+    } else if options.only.is_empty() && !analysis.protected.is_empty() {
+        crate::error::warn(&format!(
+            "protected, not removing: {} (built in or listed under @protected)",
+            analysis.protected.join(", ")
+        ));
+    }
+
     // Separate actions into installs and removals
     let to_install: Vec<String> = analysis
         .actions
@@ -49,6 +231,19 @@ pub fn run(flags: &crate::cli::handler::GlobalFlags) {
         })
         .collect();
 
+    package_params.had_uninstalled = !to_install.is_empty();
+    package_params.fail_fast = options.fails_fast();
+    package_params.check_names = options.check_names();
+    package_params.held = crate::core::package::held_packages(&analysis.state, &analysis.config);
+    package_params.aur_skip = analysis.config.aur_skip.clone();
+    package_params.hooks = crate::core::package::package_hooks(&analysis.config);
+    package_params.pinned_versions = analysis
+        .config
+        .pins
+        .iter()
+        .filter_map(|(name, version)| Some((name.clone(), version.clone()?)))
+        .collect();
+
     crate::cli::ui::generate_apply_output_with_install(
         analysis.package_count,
         to_install.len(),
@@ -57,39 +252,520 @@ pub fn run(flags: &crate::cli::handler::GlobalFlags) {
         to_remove.len(),
         analysis.config_package_count,
     );
+    for satisfied in &analysis.satisfied {
+        println!(
+            "  {}",
+            crate::internal::color::info(&format!(
+                "{} satisfied by {}",
+                satisfied.name, satisfied.by
+            ))
+        );
+    }
+
+    if scope.packages() && options.only.is_empty() {
+        report_drift(&analysis.drift, dry_run);
+        if !analysis.drift.stale.is_empty() {
+            prune_stale_state(&analysis.drift.stale, &mut analysis.state, &options, flags);
+        }
+    }
+
+    if options.plan_first {
+        porcelain::phase("plan");
+    } else if scope.packages() {
+        porcelain::phase("packages");
+    }
+    let mut plan = if scope.packages() {
+        let mut packages = crate::core::plan::Plan {
+            installs: to_install.clone(),
+            removals: to_remove.clone(),
+            ..crate::core::plan::Plan::default()
+        };
+        if options.updates() {
+            let check = crate::core::plan::PlanOptions {
+                check_aur_updates: options.check_aur_updates(),
+            };
+            let added = crate::core::plan::add_updates(
+                &mut packages,
+                &analysis.config,
+                &analysis.state,
+                &analysis.system,
+                analysis.package_count,
+                &check,
+            );
+            crate::error::report_warning("check for updates", added);
+        }
+        plan::ApplyPlan::from_plan(&packages, options.updates(), &package_params)
+    } else {
+        plan::ApplyPlan::default()
+    };
+    let mut proceed = true;
+    if options.plan_first {
+        if scope.dotfiles() {
+            plan.add_dotfiles(&analysis.config);
+        }
+        if scope.system() {
+            plan.add_services(&analysis.config);
+        }
+        let mut out = crate::cli::ui::Pager::new(flags);
+        plan.print(&mut out);
+        out.finish();
+        proceed = !dry_run && !plan.is_empty() && (flags.non_interactive || plan::confirm());
+        if !proceed && !dry_run && !plan.is_empty() {
+            println!("  {}", crate::internal::color::info("Apply cancelled"));
+        }
+        package_params.confirmed = true;
+    }
 
-    let had_uninstalled = !to_install.is_empty();
+    // Before any package changes, so a busy pacman stops the run with nothing half done
+    if proceed && !dry_run && !plan.is_empty() {
+        crate::error::exit_on_error(crate::core::pm::wait_for_database());
+    }
+    // Made before the hooks so they get its id, and saved once they let the run go on
+    let record = (proceed && !dry_run)
+        .then(|| crate::core::history::RunRecord::start(crate::core::history::RunKind::Apply));
+    let run_id = record.as_ref().map(|record| record.id.clone());
+    package_params.run_id = run_id.clone();
+    // A failing pre-apply hook stops the run before anything changes
+    let hook_env = crate::core::apply_hooks::HookEnv {
+        run_id,
+        ..plan.hook_env()
+    };
+    if proceed {
+        crate::error::exit_on_error(crate::core::apply_hooks::run(
+            crate::core::apply_hooks::Stage::PreApply,
+            &hook_env,
+            dry_run,
+        ));
+    }
 
-    // Handle removals first
-    packages::handle_removals(&to_remove, dry_run, &mut analysis.state);
+    let mut run = record.and_then(|record| start_run(record, &analysis.system));
 
-    // Handle all package operations (install + update) in one combined phase
-    let package_params = packages::PackageOperationParams {
+    let mut results = summary::ApplySummary {
         dry_run,
-        non_interactive,
-        had_uninstalled,
+        ..Default::default()
     };
-    packages::install_and_update_packages(&to_install, &package_params, &analysis.config);
+    if proceed && scope.packages() {
+        if options.plan_first {
+            porcelain::phase("packages");
+        }
+        // Handle removals first
+        results.removed =
+            packages::handle_removals(&plan.removals, &package_params, &mut analysis.state);
+
+        // Handle all package operations (install + update) in one combined phase
+        let outcome = packages::install_and_update_packages(&plan, &package_params);
+        results.repo_installed = outcome.repo_installed;
+        results.aur_installed = outcome.aur_installed;
+        results.aur_updated = outcome.aur_updated;
+        results.aur_failed = outcome.aur_failed;
+        results.skipped = outcome.skipped;
+
+        // After the removals, so the dependencies they left behind are included
+        if options.cleans_orphans() && !package_params.skip("orphan cleanup", &mut results.skipped)
+        {
+            results.orphans =
+                packages::clean_orphans(&package_params, &analysis.config, &analysis.state.managed);
+        }
+    }
+    if proceed && scope.packages() && options.no_upgrade {
+        println!(
+            "  {}",
+            crate::internal::color::yellow("system upgrade skipped (--no-upgrade)")
+        );
+    }
+
+    let dotfiles =
+        if proceed && scope.dotfiles() && !package_params.skip("dotfiles", &mut results.skipped) {
+            porcelain::phase("dotfiles");
+            dotfiles::apply_dotfiles_with_config(&analysis.config, flags)
+        } else {
+            Vec::new()
+        };
+
+    // Services and environment
+    if proceed && scope.system() && !package_params.skip("system", &mut results.skipped) {
+        porcelain::phase("system");
+        results.services = system::handle_system_section_with_config(&analysis.config, dry_run);
+    }
 
     // After operations, mark newly installed packages as managed (only if installed by our tool)
+    if !dry_run && proceed && scope.packages() {
+        report(
+            "re-read the package database",
+            analysis
+                .system
+                .refresh(&crate::core::system::Backend::select()),
+        );
+        mark_installed_as_managed(&to_install, &mut analysis.state, &analysis.system);
+    }
+    if !dry_run && proceed {
+        // A partial apply leaves the rest of the config unapplied, so it is not recorded
+        if scope == Scope::All && options.only.is_empty() && results.skipped.is_empty() {
+            report("record the apply", record_apply());
+        }
+        report(
+            "regenerate the schedule units",
+            crate::core::schedule::refresh_stale(),
+        );
+    }
+    let mut summary = porcelain::Summary {
+        dry_run,
+        to_install: to_install.len(),
+        to_remove: to_remove.len(),
+        dotfiles_written: if dry_run { 0 } else { dotfiles.len() },
+        ..Default::default()
+    };
+    if proceed {
+        let env = crate::core::apply_hooks::HookEnv {
+            failed: Some(crate::error::errors_reported()),
+            changed_files: dotfiles.clone(),
+            ..hook_env
+        };
+        crate::error::report_warning(
+            "run the post-apply hooks",
+            crate::core::apply_hooks::run(
+                crate::core::apply_hooks::Stage::PostApply,
+                &env,
+                dry_run,
+            ),
+        );
+    }
+    let mut issues = crate::error::drain();
+    let dotfiles_written = dotfiles.clone();
+    if let Some((record, before)) = run.as_mut() {
+        record.dotfiles = dotfiles;
+        record.issues = issues.clone();
+        report(
+            "record the run",
+            finish_run(record, before, &analysis.system),
+        );
+        issues.extend(crate::error::drain());
+        for change in &record.packages {
+            match (&change.from, &change.to) {
+                (None, _) => summary.installed += 1,
+                (_, None) => summary.removed += 1,
+                _ => summary.upgraded += 1,
+            }
+        }
+    }
+    let unmanaged = crate::core::analysis::unmanaged_explicit(
+        analysis.system.explicit(),
+        &analysis.state,
+        &analysis.config,
+    );
+    if let Some(notice) = crate::core::analysis::unmanaged_notice(unmanaged.len()) {
+        println!();
+        println!("  {} {}", crate::internal::color::blue("info:"), notice);
+    }
+    summary.warnings = issues.len();
+    summary.ok = issues.is_empty();
     if !dry_run {
-        let mut changed = false;
-        for pkg in &to_install {
-            match crate::core::package::is_package_or_group_installed(pkg) {
-                Ok(true) if !analysis.state.is_managed(pkg) => {
-                    analysis.state.add_managed(pkg.clone());
-                    changed = true;
-                }
-                Ok(true) => {}
-                Ok(false) => {}
-                Err(e) => {
-                    handle_error_with_context(&format!("verify installation of {}", pkg), Err(e));
+        results.dotfiles = dotfiles_written;
+    }
+    results.issues = issues;
+    results.print();
+    crate::cli::notify::finished(&summary, None);
+    porcelain::emit(porcelain::Event::Done { summary });
+    if results.failed() {
+        std::process::exit(1);
+    }
+}
+
+/// Build the AUR packages whose last build failed (`--retry-failed`) one at a time,
+/// without analyzing or touching anything else
+pub fn retry_failed(flags: &crate::cli::handler::GlobalFlags) {
+    let failed = crate::core::aur_failures::read();
+    if failed.is_empty() {
+        println!(
+            "  {}",
+            crate::internal::color::info("no failed AUR builds recorded")
+        );
+        return;
+    }
+    let mut results = summary::ApplySummary {
+        dry_run: flags.dry_run,
+        ..Default::default()
+    };
+    if flags.dry_run {
+        println!(
+            "  {}",
+            crate::internal::color::info(&format!("Would retry {} from AUR", failed.join(", ")))
+        );
+    } else {
+        crate::error::exit_on_error(crate::core::pm::wait_for_database());
+        let params = packages::PackageOperationParams::new(flags);
+        let (built, still_failed) = packages::install_aur_each(&failed, &params);
+        crate::error::report_warning(
+            "record the failed AUR builds",
+            crate::core::aur_failures::record(&failed, &still_failed),
+        );
+        results.aur_installed = built;
+        results.aur_failed = still_failed;
+    }
+    results.issues = crate::error::drain();
+    results.print();
+    if results.failed() {
+        std::process::exit(1);
+    }
+}
+
+/// Note the managed packages removed outside owl, which this apply reinstalls
+fn report_drift(drift: &crate::core::analysis::Drift, dry_run: bool) {
+    if !drift.missing.is_empty() {
+        println!(
+            "  {}",
+            crate::internal::color::info(&format!(
+                "managed but no longer installed, removed outside owl ({}): {}",
+                if dry_run {
+                    "would reinstall"
+                } else {
+                    "reinstalling"
+                },
+                drift.missing.join(", ")
+            ))
+        );
+    }
+}
+
+/// Offer to forget managed packages that are neither declared nor installed; always
+/// with `--prune-state`, never on a dry run or without an answer
+fn prune_stale_state(
+    stale: &[String],
+    state: &mut crate::core::state::PackageState,
+    options: &ApplyOptions,
+    flags: &crate::cli::handler::GlobalFlags,
+) {
+    crate::error::warn(&format!(
+        "managed in state but no longer declared or installed: {}",
+        stale.join(", ")
+    ));
+    if flags.dry_run {
+        println!(
+            "  {}",
+            crate::internal::color::info("would ask to prune them from state")
+        );
+        return;
+    }
+    let prune = options.prune_state
+        || (!flags.non_interactive && {
+            print!("  -> Prune them from state? (y/N): ");
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+            match crate::cli::ui::read_answer("the state prune prompt") {
+                Ok(answer) => matches!(answer.to_lowercase().as_str(), "y" | "yes"),
+                Err(err) => {
+                    crate::error::report_error(Err(err));
+                    false
                 }
             }
+        });
+    if !prune {
+        println!(
+            "  {}",
+            crate::internal::color::info(
+                "left in state; prune them with `owl apply --prune-state`"
+            )
+        );
+        return;
+    }
+    for name in stale {
+        state.remove_managed(name);
+    }
+    if !report("save package state", state.save()) {
+        println!(
+            "  {} pruned {} stale state entr{}",
+            crate::internal::color::green("✓"),
+            stale.len(),
+            if stale.len() == 1 { "y" } else { "ies" }
+        );
+    }
+}
+
+/// Write `record` as an incomplete run and remember the installed versions to diff
+/// against
+fn start_run(
+    record: crate::core::history::RunRecord,
+    system: &crate::core::system::SystemSnapshot,
+) -> Option<(
+    crate::core::history::RunRecord,
+    std::collections::BTreeMap<String, String>,
+)> {
+    let result = (|| {
+        let before = system.versions().clone();
+        crate::core::history::save_run(&record)?;
+        Ok((record, before))
+    })();
+    match result {
+        Ok(run) => Some(run),
+        Err(err) => {
+            report("start the run record", Err(err));
+            None
+        }
+    }
+}
+
+fn finish_run(
+    record: &mut crate::core::history::RunRecord,
+    before: &std::collections::BTreeMap<String, String>,
+    after: &crate::core::system::SystemSnapshot,
+) -> anyhow::Result<()> {
+    record.packages = crate::core::history::package_changes(before, after.versions());
+    record.finish();
+    crate::core::history::save_run(record)?;
+    crate::core::history::prune_runs(crate::core::settings::get().history.keep_runs)
+}
+
+/// Remember when this apply finished and which config it applied, for `owl status`
+fn record_apply() -> anyhow::Result<()> {
+    let files = crate::internal::files::get_all_config_files()?;
+    crate::core::history::save_last(&crate::core::history::ApplyRecord::capture(&files)?)
+}
+
+// Only the dashboard (`tui` feature) applies partial plans
+/// A subset of the plan picked by the user, e.g. from the dashboard
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    pub installs: Vec<String>,
+    pub removals: Vec<String>,
+    pub aur_updates: Vec<String>,
+    pub repo_updates: bool,
+}
+
+/// Apply only the selected package changes, leaving dotfiles and services alone
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub fn run_selection(
+    selection: &Selection,
+    flags: &crate::cli::handler::GlobalFlags,
+    state: &mut crate::core::state::PackageState,
+) {
+    let config =
+        crate::core::config::Config::load_all_relevant_config_files().unwrap_or_else(|err| {
+            log::debug!("not reading @pin and @aur-skip sections: {:#}", err);
+            crate::core::config::Config::new()
+        });
+    let params = packages::PackageOperationParams {
+        held: crate::core::package::held_packages(state, &config),
+        aur_skip: config.aur_skip.clone(),
+        hooks: crate::core::package::package_hooks(&config),
+        ..packages::PackageOperationParams::new(flags)
+    };
+    packages::handle_removals(&selection.removals, &params, state);
+
+    let (repo_to_install, aur_to_install, groups) =
+        packages::categorize_install_sets(&selection.installs, &params);
+    packages::install_repo_packages(&repo_to_install, &groups, &params);
+    let upgrades = (selection.repo_updates || !selection.aur_updates.is_empty())
+        && packages::news_allows_upgrades(flags.dry_run);
+    let aur_updates = if upgrades {
+        selection.aur_updates.as_slice()
+    } else {
+        &[]
+    };
+    if !aur_to_install.is_empty() || !aur_updates.is_empty() {
+        packages::handle_aur_operations(&aur_to_install, aur_updates, &params);
+    }
+    if selection.repo_updates && upgrades {
+        packages::update_repo_packages(&params);
+    }
+
+    if !flags.dry_run {
+        match crate::core::system::SystemSnapshot::capture(&crate::core::system::Backend::select())
+        {
+            Ok(system) => mark_installed_as_managed(&selection.installs, state, &system),
+            Err(err) => {
+                report("re-read the package database", Err(err));
+            }
         }
+    }
+}
 
-        if changed {
-            handle_error_with_context("save package state", analysis.state.save());
+/// Record packages from `installed` that are now present on the system as managed
+/// according to `system`, read after the operations ran
+fn mark_installed_as_managed(
+    installed: &[String],
+    state: &mut crate::core::state::PackageState,
+    system: &crate::core::system::SystemSnapshot,
+) {
+    let mut changed = false;
+    for pkg in installed {
+        match system.has_package_or_group(pkg) {
+            Ok(true) if !state.is_managed(pkg) => {
+                state.add_managed(pkg.clone());
+                changed = true;
+            }
+            Ok(true) => {}
+            Ok(false) => {}
+            Err(e) => {
+                report(&format!("verify installation of {}", pkg), Err(e));
+            }
         }
     }
+
+    if changed {
+        report("save package state", state.save());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aur_updates_are_checked_unless_offline() {
+        assert!(ApplyOptions::default().check_aur_updates());
+        let plan_first = ApplyOptions {
+            plan_first: true,
+            ..ApplyOptions::default()
+        };
+        assert!(plan_first.check_aur_updates());
+        let offline = ApplyOptions {
+            offline: true,
+            ..ApplyOptions::default()
+        };
+        assert!(!offline.check_aur_updates());
+        assert!(!offline.check_names());
+        assert!(ApplyOptions::default().check_names());
+        let no_network_check = ApplyOptions {
+            no_network_check: true,
+            ..ApplyOptions::default()
+        };
+        assert!(no_network_check.check_aur_updates() && !no_network_check.check_names());
+        let no_upgrade = ApplyOptions {
+            no_upgrade: true,
+            ..ApplyOptions::default()
+        };
+        assert!(!no_upgrade.updates() && !no_upgrade.check_aur_updates());
+        assert!(no_upgrade.cleans_orphans() == crate::core::settings::get().clean_orphans);
+        assert!(Scope::DotfilesOnly.dotfiles() && !Scope::DotfilesOnly.packages());
+    }
+
+    #[test]
+    fn test_only_targets_declared_packages_without_updates() {
+        let mut config = crate::core::config::Config::parse(
+            "@env EDITOR=nvim\n@package htop\n:config htoprc\n:env HTOP=1\n@package sshd-pkg\n:service sshd\n@packages\nripgrep\n",
+        )
+        .unwrap();
+        config.packages.get_mut("ripgrep").unwrap().service = Some("rg".to_string());
+        let options = ApplyOptions {
+            only: vec![
+                "htop".to_string(),
+                "ripgrep".to_string(),
+                "nope".to_string(),
+            ],
+            ..ApplyOptions::default()
+        };
+        assert_eq!(options.undeclared(&config), ["nope"]);
+        assert!(!options.updates() && !options.check_aur_updates());
+
+        let targeted = targeted_config(&config, &options.only);
+        let mut names: Vec<&String> = targeted.packages.keys().collect();
+        names.sort();
+        assert_eq!(names, ["htop", "ripgrep"]);
+        assert_eq!(targeted.packages["htop"].config, ["htoprc"]);
+        assert!(targeted.packages["htop"].env_vars.is_empty() && targeted.env_vars.is_empty());
+        assert_eq!(
+            crate::core::services::get_configured_services(&targeted),
+            ["rg"]
+        );
+    }
 }
@@ -1,109 +1,290 @@
-use crate::error::{handle_error, handle_error_with_context};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::core::hooks::{HookPoint, Hooks};
+use crate::core::package::GroupMembers;
+use crate::error::{report, report_error, report_warning};
+use crate::internal::color;
 
 /// Parameters for package operations
 #[derive(Debug)]
 pub struct PackageOperationParams {
     pub dry_run: bool,
     pub non_interactive: bool,
+    /// Hand the terminal to the package manager (`OWL_PM_PASSTHROUGH`)
+    pub passthrough: bool,
     pub had_uninstalled: bool,
+    /// Pinned packages that updates must skip
+    pub held: Vec<String>,
+    /// AUR packages the config keeps out of updates (`@aur-skip`, `:noupdate`)
+    pub aur_skip: Vec<String>,
+    /// Versions `@pin` asks for, by package: what a missing pinned package installs at
+    pub pinned_versions: BTreeMap<String, String>,
+    /// The hooks of declared packages that have any, by package; removals take theirs
+    /// from state instead
+    pub hooks: BTreeMap<String, Hooks>,
+    /// The whole plan was approved up front, so removals and AUR work do not ask again
+    pub confirmed: bool,
+    /// Stop at the first error instead of carrying on (`--fail-fast`)
+    pub fail_fast: bool,
+    /// Look up in the AUR the installs the repos do not have, so unknown names are
+    /// caught before the AUR helper; off with `--no-network-check` and `--offline`
+    pub check_names: bool,
+    /// The history id of the run, passed to package hooks as `OWL_RUN_ID`
+    pub run_id: Option<String>,
+}
+
+impl Default for PackageOperationParams {
+    /// A real, interactive run with nothing installed or held yet
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            non_interactive: false,
+            passthrough: false,
+            had_uninstalled: false,
+            held: Vec::new(),
+            aur_skip: Vec::new(),
+            pinned_versions: BTreeMap::new(),
+            hooks: BTreeMap::new(),
+            confirmed: false,
+            fail_fast: false,
+            check_names: true,
+            run_id: None,
+        }
+    }
+}
+
+impl PackageOperationParams {
+    /// The modes `flags` and the environment set, with nothing installed or held yet
+    pub fn new(flags: &crate::cli::handler::GlobalFlags) -> Self {
+        Self {
+            dry_run: flags.dry_run,
+            non_interactive: flags.non_interactive,
+            passthrough: use_pm_passthrough(flags.non_interactive),
+            ..Self::default()
+        }
+    }
+
+    /// Under fail-fast, once an error has been reported: note `step` in `skipped` and
+    /// return true so the caller leaves it out
+    pub fn skip(&self, step: &str, skipped: &mut Vec<String>) -> bool {
+        self.skip_after(crate::error::errors_reported(), step, skipped)
+    }
+
+    fn skip_after(&self, errors_reported: bool, step: &str, skipped: &mut Vec<String>) -> bool {
+        let stop = self.fail_fast && errors_reported;
+        if stop {
+            skipped.push(step.to_string());
+        }
+        stop
+    }
+
+    /// The modes this run is in, for the one line the apply header shows; `None` when
+    /// none are on
+    pub fn modes(&self) -> Option<String> {
+        let mut modes = Vec::new();
+        if self.dry_run {
+            modes.push("Dry run mode");
+        }
+        if self.non_interactive {
+            modes.push("non-interactive");
+        }
+        if self.passthrough {
+            modes.push("package manager passthrough");
+        }
+        if modes.is_empty() {
+            return None;
+        }
+        let line = modes.join(", ");
+        let mut line = line[..1].to_uppercase() + &line[1..];
+        if self.dry_run {
+            line.push_str(" - no changes will be made to the system");
+        }
+        Some(line)
+    }
+}
+
+/// The packages a package phase installed or updated
+#[derive(Debug, Default)]
+pub struct PackageOutcome {
+    pub repo_installed: Vec<String>,
+    pub aur_installed: Vec<String>,
+    pub aur_updated: Vec<String>,
+    /// AUR packages whose build failed, also after the retry if one was accepted
+    pub aur_failed: Vec<String>,
+    /// Steps fail-fast left out after an error
+    pub skipped: Vec<String>,
 }
 
+/// Remove `to_remove` after confirmation, returning the packages removed
 pub fn handle_removals(
     to_remove: &[String],
-    dry_run: bool,
+    params: &PackageOperationParams,
     state: &mut crate::core::state::PackageState,
-) {
+) -> Vec<String> {
     if to_remove.is_empty() {
-        return;
+        return Vec::new();
     }
 
-    if dry_run {
+    if params.dry_run {
         println!("Package cleanup (would remove conflicting packages):");
         for package in to_remove {
-            println!(
-                "  {} Would remove: {}",
-                crate::internal::color::red("remove"),
-                crate::internal::color::yellow(package)
-            );
+            println!("  {}", color::action("would remove", package));
         }
         println!(
-            "  {} Would remove {} package(s)",
-            crate::internal::color::blue("info:"),
-            to_remove.len()
+            "  {}",
+            color::info(&format!("Would remove {} package(s)", to_remove.len()))
         );
-        return;
+        run_hooks(to_remove, HookPoint::PreRemove, &state.remove_hooks, params);
+        run_hooks(
+            to_remove,
+            HookPoint::PostRemove,
+            &state.remove_hooks,
+            params,
+        );
+        return Vec::new();
     }
 
-    // Ask for explicit confirmation before removing packages
-    if !crate::cli::ui::confirm_remove_operation(to_remove) {
-        println!(
-            "  {}",
-            crate::internal::color::blue("Package removal cancelled")
-        );
-        return;
+    // Ask for explicit confirmation before removing packages; the user may deselect some
+    let selected = if params.confirmed {
+        to_remove.to_vec()
+    } else {
+        match crate::cli::ui::confirm_remove_operation(to_remove, &state.managed) {
+            Ok(selected) => selected,
+            Err(err) => {
+                report_error(Err(err));
+                return Vec::new();
+            }
+        }
+    };
+    if selected.is_empty() {
+        println!("  {}", color::info("Package removal cancelled"));
+        return Vec::new();
     }
 
-    if let Err(e) = crate::core::package::remove_unmanaged_packages(to_remove, true) {
-        eprintln!(
-            "{}",
-            crate::internal::color::red(&format!("Failed to remove packages: {}", e))
-        );
-        return;
+    run_hooks(&selected, HookPoint::PreRemove, &state.remove_hooks, params);
+    if params.fail_fast && crate::error::errors_reported() {
+        return Vec::new();
     }
+    if report(
+        "remove packages",
+        crate::core::package::remove_unmanaged_packages(&selected, true),
+    ) {
+        return Vec::new();
+    }
+    run_hooks(
+        &selected,
+        HookPoint::PostRemove,
+        &state.remove_hooks,
+        params,
+    );
 
     // Remove successfully removed packages from managed list
-    for package in to_remove {
+    for package in &selected {
         state.remove_managed(package);
     }
 
-    if let Err(e) = state.save() {
-        eprintln!(
-            "{}",
-            crate::internal::color::red(&format!("Failed to update package state: {}", e))
-        );
+    report("update package state", state.save());
+    selected
+}
+
+/// Remove the orphaned dependencies left after the other package work, asked like
+/// any removal unless non-interactive; a dry run lists them. Returns the packages
+/// removed.
+pub fn clean_orphans(
+    params: &PackageOperationParams,
+    config: &crate::core::config::Config,
+    managed: &[String],
+) -> Vec<String> {
+    let pm = crate::core::pm::ParuPacman::new();
+    let result = crate::core::package::remove_orphans_with(&pm, config, |orphans| {
+        println!("Orphan cleanup (dependencies nothing requires any more):");
+        if params.dry_run {
+            for package in orphans {
+                println!("  {}", color::action("would remove", package));
+            }
+            return Ok(Vec::new());
+        }
+        if params.non_interactive {
+            return Ok(orphans.to_vec());
+        }
+        crate::cli::ui::confirm_remove_operation(orphans, managed)
+    });
+    match result {
+        Ok(removed) => removed,
+        Err(err) => {
+            report("remove orphaned dependencies", Err(err));
+            Vec::new()
+        }
     }
 }
 
-/// Install missing packages and update all packages
+/// Install missing packages and update all packages, as `plan` lays out
 pub fn install_and_update_packages(
-    to_install: &[String],
+    plan: &super::plan::ApplyPlan,
     params: &PackageOperationParams,
-    config: &crate::core::config::Config,
-) {
-    // First, handle uninstalled packages
-    let (repo_to_install, aur_to_install) = categorize_install_sets(to_install);
+) -> PackageOutcome {
+    let aur_to_install = &plan.aur_installs;
+    let aur_to_update = &plan.aur_updates;
 
-    // Get AUR packages that need updates
-    let aur_to_update = compute_aur_updates(params.dry_run);
+    let mut skipped = Vec::new();
+    // Before any install, so a failing hook under fail-fast stops them all
+    run_hooks(
+        &plan.repo_installs,
+        HookPoint::PreInstall,
+        &params.hooks,
+        params,
+    );
+    run_hooks(aur_to_install, HookPoint::PreInstall, &params.hooks, params);
 
     // Install repo packages first (no confirmation needed)
-    install_repo_packages(&repo_to_install, params.dry_run, params.non_interactive);
+    let repo_installed =
+        if plan.repo_installs.is_empty() || params.skip("repo installs", &mut skipped) {
+            Vec::new()
+        } else {
+            install_repo_packages(&plan.repo_installs, &plan.groups, params)
+        };
+    let installed = if params.dry_run {
+        &plan.repo_installs
+    } else {
+        &repo_installed
+    };
+    run_hooks(installed, HookPoint::PostInstall, &params.hooks, params);
+    let mut outcome = PackageOutcome::default();
 
+    // A printed plan already said so
+    if !params.confirmed {
+        note_aur_skipped(&plan.aur_skipped);
+    }
     // Handle all AUR packages together if there are any
-    if !aur_to_install.is_empty() || !aur_to_update.is_empty() {
+    if (!aur_to_install.is_empty() || !aur_to_update.is_empty())
+        && !params.skip("AUR packages", &mut skipped)
+    {
         // Show detailed breakdown of what will happen
         if !aur_to_install.is_empty() {
             println!(
                 "  {} AUR packages to install: {}",
-                crate::internal::color::yellow(&aur_to_install.len().to_string()),
+                color::yellow(&aur_to_install.len().to_string()),
                 aur_to_install.join(", ")
             );
         }
         if !aur_to_update.is_empty() {
             println!(
                 "  {} AUR packages to update: {}",
-                crate::internal::color::yellow(&aur_to_update.len().to_string()),
+                color::yellow(&aur_to_update.len().to_string()),
                 aur_to_update.join(", ")
             );
         }
 
-        handle_aur_operations(
-            &aur_to_install,
-            &aur_to_update,
-            params.dry_run,
-            params.non_interactive,
-        );
+        outcome = handle_aur_operations(aur_to_install, aur_to_update, params);
+        let installed = if params.dry_run {
+            aur_to_install
+        } else {
+            &outcome.aur_installed
+        };
+        run_hooks(installed, HookPoint::PostInstall, &params.hooks, params);
     }
+    outcome.repo_installed = repo_installed;
 
     // Add blank line if we installed packages before this
     if params.had_uninstalled {
@@ -111,158 +292,572 @@ pub fn install_and_update_packages(
     }
 
     // Update repo packages
-    update_repo_packages(params.dry_run, params.non_interactive);
-
-    // Apply dotfile synchronization
-    super::dotfiles::apply_dotfiles_with_config(config, params.dry_run);
-
-    // Handle system section (services + environment)
-    super::system::handle_system_section_with_config(config, params.dry_run);
+    if plan.upgrades && !params.skip("repo updates", &mut skipped) {
+        update_repo_packages(params);
+    }
+    outcome.skipped = skipped;
+    outcome
 }
 
-pub fn categorize_install_sets(to_install: &[String]) -> (Vec<String>, Vec<String>) {
-    if to_install.is_empty() {
-        return (Vec::new(), Vec::new());
-    }
-    match crate::core::package::categorize_packages(to_install) {
-        Ok(result) => result,
-        Err(e) => {
-            handle_error_with_context("categorize packages", Err(e));
-            (Vec::new(), Vec::new())
+/// Run the `point` hooks of each of `packages` that has any, reporting a failure with
+/// the package it belongs to. Under fail-fast the first failure ends the round.
+fn run_hooks(
+    packages: &[String],
+    point: HookPoint,
+    hooks: &BTreeMap<String, Hooks>,
+    params: &PackageOperationParams,
+) {
+    for package in packages {
+        let Some(hooks) = hooks.get(package) else {
+            continue;
+        };
+        let result = crate::core::hooks::run(
+            package,
+            point,
+            hooks,
+            params.dry_run,
+            params.run_id.as_deref(),
+        );
+        if report_error(result) && params.fail_fast {
+            return;
         }
     }
 }
 
-pub fn compute_aur_updates(dry_run: bool) -> Vec<String> {
+/// Whether upgrades may run given the Arch news: unread items are listed and need an
+/// acknowledgment, from `--ack-news` or the prompt. Installs do not wait for it.
+pub fn news_allows_upgrades(dry_run: bool) -> bool {
+    use crate::core::news::Verdict;
+    let unread = match crate::core::news::check(dry_run) {
+        Ok(Verdict::Clear) => return true,
+        Ok(Verdict::Acknowledged(items)) => {
+            println!(
+                "  {}",
+                color::ok(&format!(
+                    "acknowledged {} Arch news item{}",
+                    items.len(),
+                    if items.len() == 1 { "" } else { "s" }
+                ))
+            );
+            return true;
+        }
+        Ok(Verdict::Unread(items)) => items,
+        Err(err) => {
+            report_warning("check the Arch news", Err(err));
+            return true;
+        }
+    };
+    println!("  {}", color::warn("unread Arch news:"));
+    for item in &unread {
+        println!(
+            "    {} {}",
+            color::bold(&item.title),
+            color::dim(&item.link)
+        );
+    }
     if dry_run {
-        return Vec::new();
+        println!(
+            "  {}",
+            color::info("would hold upgrades until the news is acknowledged")
+        );
+        return false;
     }
-    match super::analysis::get_aur_updates() {
-        Ok(packages) => packages,
+
+    print!("  -> Read them first. Continue with the upgrade? (y/N): ");
+    std::io::Write::flush(&mut std::io::stdout()).ok();
+    let answer = crate::cli::ui::read_answer("the news acknowledgment");
+    if let Ok(answer) = &answer
+        && matches!(answer.to_lowercase().as_str(), "y" | "yes")
+    {
+        report("acknowledge the news", crate::core::news::acknowledge());
+        return true;
+    }
+    let hint = if answer.is_err() {
+        "; pass --ack-news once they are read"
+    } else {
+        ""
+    };
+    crate::error::warn(&format!(
+        "upgrades held back for unread Arch news{}; installs and dotfiles go ahead",
+        hint
+    ));
+    false
+}
+
+/// The repo and AUR install sets, with the members of the groups among the repo set.
+/// Names found neither in the repos nor in the AUR are reported as an error and left
+/// out, rather than failing deep in an AUR build.
+pub fn categorize_install_sets(
+    to_install: &[String],
+    params: &PackageOperationParams,
+) -> (Vec<String>, Vec<String>, GroupMembers) {
+    if to_install.is_empty() {
+        return (Vec::new(), Vec::new(), GroupMembers::new());
+    }
+    let categorized = if params.check_names {
+        crate::core::package::categorize_packages(to_install)
+    } else {
+        crate::core::package::categorize_packages_offline(to_install)
+    };
+    match categorized {
+        Ok(mut categorized) => {
+            let unknown = std::mem::take(&mut categorized.unknown);
+            if !unknown.is_empty() {
+                crate::error::report_error(Err(anyhow::anyhow!(
+                    "Not found in repos or AUR, not installing: {}",
+                    unknown.join(", ")
+                )));
+            }
+            // Already looked up while categorizing; without members a group goes to
+            // pacman by name
+            let groups = crate::core::pm::ParuPacman::new()
+                .group_members(&categorized.groups)
+                .unwrap_or_else(|err| {
+                    log::debug!("not expanding groups: {:#}", err);
+                    GroupMembers::new()
+                });
+            let (repo, aur) = categorized.install_sets();
+            (repo, aur, groups)
+        }
         Err(e) => {
-            handle_error_with_context("check AUR updates", Err(e));
-            Vec::new()
+            report("categorize packages", Err(e));
+            (Vec::new(), Vec::new(), GroupMembers::new())
         }
     }
 }
 
+/// Whether `OWL_PM_PASSTHROUGH` asks for passthrough; read once per process
+fn passthrough_requested() -> bool {
+    static REQUESTED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *REQUESTED.get_or_init(|| {
+        let value = std::env::var("OWL_PM_PASSTHROUGH").unwrap_or_default();
+        let requested = matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes" | "on"
+        );
+        log::debug!("OWL_PM_PASSTHROUGH={:?}: passthrough {}", value, requested);
+        requested
+    })
+}
+
 fn use_pm_passthrough(non_interactive: bool) -> bool {
-    if non_interactive {
-        return false;
-    }
+    !non_interactive && passthrough_requested()
+}
 
-    std::env::var("OWL_PM_PASSTHROUGH")
-        .map(|value| {
-            matches!(
-                value.trim().to_ascii_lowercase().as_str(),
-                "1" | "true" | "yes" | "on"
-            )
-        })
-        .unwrap_or(false)
+/// Run an install between its porcelain start and done events; true if it succeeded
+fn install_reported(
+    source: &str,
+    packages: &[String],
+    install: impl FnOnce() -> anyhow::Result<()>,
+) -> bool {
+    use crate::cli::porcelain::{Event, emit};
+    emit(Event::PkgInstallStart {
+        source: source.to_string(),
+        packages: packages.to_vec(),
+    });
+    let failed = report_error(install());
+    emit(Event::PkgInstallDone {
+        source: source.to_string(),
+        packages: packages.to_vec(),
+        ok: !failed,
+    });
+    !failed
 }
 
-pub fn install_repo_packages(repo_to_install: &[String], dry_run: bool, non_interactive: bool) {
+/// Install `repo_to_install` from the repos, returning them if the install succeeded
+pub fn install_repo_packages(
+    repo_to_install: &[String],
+    groups: &GroupMembers,
+    params: &PackageOperationParams,
+) -> Vec<String> {
     if repo_to_install.is_empty() {
-        return;
+        return Vec::new();
     }
     println!(
         "  {} repo packages found: {}",
-        crate::internal::color::yellow(&repo_to_install.len().to_string()),
-        repo_to_install.join(", ")
+        color::yellow(&repo_to_install.len().to_string()),
+        crate::core::package::describe_groups(repo_to_install, groups).join(", ")
     );
-    if dry_run {
-        println!(
-            "  {} Would install {} from official repositories",
-            crate::internal::color::blue("info:"),
-            repo_to_install.join(", ")
-        );
-    } else {
-        let pm = crate::core::pm::ParuPacman::new();
-        if use_pm_passthrough(non_interactive) {
+    let (from_cache, from_repos) = pinned_installs(repo_to_install, &params.pinned_versions);
+    if params.dry_run {
+        for (name, file) in &from_cache {
             println!(
-                "  {} Package manager passthrough enabled",
-                crate::internal::color::blue("info:")
+                "  {}",
+                color::info(&format!(
+                    "Would install pinned {} from {}",
+                    name,
+                    file.display()
+                ))
             );
-            handle_error(pm.install_repo_with_mode(repo_to_install, false));
-        } else {
-            handle_error(pm.install_repo(repo_to_install));
         }
+        if !from_repos.is_empty() {
+            println!(
+                "  {}",
+                color::info(&format!(
+                    "Would install {} from official repositories",
+                    crate::core::package::describe_groups(&from_repos, groups).join(", ")
+                ))
+            );
+        }
+        return Vec::new();
     }
+    let pm = crate::core::pm::ParuPacman::new();
+    let mut installed = Vec::new();
+    let targets = crate::core::package::expand_groups(&from_repos, groups);
+    if !from_repos.is_empty()
+        && install_reported("repo", &from_repos, || {
+            if params.passthrough {
+                pm.install_repo_with_mode(&targets, false)
+            } else {
+                pm.install_repo(&targets)
+            }
+        })
+    {
+        installed.extend(from_repos);
+    }
+    let (names, files): (Vec<String>, Vec<PathBuf>) = from_cache.into_iter().unzip();
+    if !files.is_empty()
+        && install_reported("repo", &names, || {
+            pm.install_files_with_mode(&files, !params.passthrough)
+        })
+    {
+        installed.extend(names);
+    }
+    installed
 }
 
-pub fn handle_aur_operations(
-    aur_to_install: &[String],
-    aur_to_update: &[String],
-    dry_run: bool,
-    non_interactive: bool,
-) {
-    // Create combined list only when needed for confirmation/display
-    let all_aur_packages: Vec<String> = aur_to_install
+/// Split repo installs into the pinned ones installed from a cached package file at
+/// their `@pin` version, and the rest, installed from the repos. A pin the repos no
+/// longer meet and the cache does not have is warned about and installed from the repos.
+fn pinned_installs(
+    repo_to_install: &[String],
+    pinned_versions: &BTreeMap<String, String>,
+) -> (Vec<(String, PathBuf)>, Vec<String>) {
+    let pinned: Vec<String> = repo_to_install
         .iter()
-        .chain(aur_to_update.iter())
+        .filter(|name| pinned_versions.contains_key(*name))
         .cloned()
         .collect();
+    if pinned.is_empty() {
+        return (Vec::new(), repo_to_install.to_vec());
+    }
+    let available = match crate::core::package::get_repo_package_info(&pinned) {
+        Ok(info) => info,
+        Err(err) => {
+            log::debug!("not checking pinned versions in the repos: {:#}", err);
+            Default::default()
+        }
+    };
 
-    if dry_run
-        || non_interactive
-        || crate::cli::ui::confirm_aur_operation(&all_aur_packages, "installing/updating")
-    {
-        if dry_run {
-            println!(
-                "  {} Would install/update {} from AUR",
-                crate::internal::color::blue("info:"),
-                all_aur_packages.join(", ")
-            );
-            return;
+    let mut from_cache = Vec::new();
+    let mut from_repos = Vec::new();
+    for name in repo_to_install {
+        let Some(pin) = pinned_versions.get(name) else {
+            from_repos.push(name.clone());
+            continue;
+        };
+        let repo_version = available.get(name).map(|info| info.version.as_str());
+        if repo_version.is_some_and(|version| crate::core::pm::pin_matches(pin, version)) {
+            from_repos.push(name.clone());
+        } else if let Some(file) = crate::core::pm::cached_pinned_package(name, pin) {
+            from_cache.push((name.clone(), file));
+        } else {
+            crate::error::warn(&format!(
+                "{} is pinned at {}, but the repos have {} and the package cache does not; installing that",
+                name,
+                pin,
+                repo_version.unwrap_or("another version")
+            ));
+            from_repos.push(name.clone());
         }
-        if !aur_to_install.is_empty() {
-            let pm = crate::core::pm::ParuPacman::new();
-            if use_pm_passthrough(non_interactive) {
+    }
+    (from_cache, from_repos)
+}
+
+/// Install and update AUR packages, each set after its own confirmation, returning
+/// what succeeded
+pub fn handle_aur_operations(
+    aur_to_install: &[String],
+    aur_to_update: &[String],
+    params: &PackageOperationParams,
+) -> PackageOutcome {
+    use crate::cli::ui::AurOperation;
+
+    let mut outcome = PackageOutcome::default();
+    let (aur_to_update, skipped) = split_aur_skipped(aur_to_update, &params.aur_skip);
+    note_aur_skipped(&skipped);
+    let aur_to_update = aur_to_update.as_slice();
+    // Both questions come first, so no answer waits on a build
+    let install = confirm_aur(aur_to_install, AurOperation::Install, params);
+    let update = confirm_aur(aur_to_update, AurOperation::Update, params);
+    if params.dry_run {
+        for (confirmed, packages, verb) in [
+            (install, aur_to_install, "install"),
+            (update, aur_to_update, "update"),
+        ] {
+            if confirmed {
                 println!(
-                    "  {} Package manager passthrough enabled",
-                    crate::internal::color::blue("info:")
+                    "  {}",
+                    color::info(&format!("Would {} {} from AUR", verb, packages.join(", ")))
                 );
-                handle_error(pm.install_aur_with_mode(aur_to_install, false));
+            }
+        }
+        return outcome;
+    }
+    let mut attempted = Vec::new();
+    if install {
+        let (built, failed) = install_aur_each(aur_to_install, params);
+        outcome.aur_installed = built;
+        outcome.aur_failed = failed;
+        attempted.extend_from_slice(aur_to_install);
+    }
+    if update {
+        let pm = crate::core::pm::ParuPacman::new();
+        let failed = if params.passthrough {
+            report_error(pm.update_aur_with_mode(aur_to_update, false, &params.held))
+        } else {
+            report_error(pm.update_aur(aur_to_update, &params.held))
+        };
+        if failed {
+            // One `-Syu` builds them all, so which one broke is not known
+            outcome.aur_failed.extend_from_slice(aur_to_update);
+        } else {
+            outcome.aur_updated = aur_to_update.to_vec();
+        }
+        attempted.extend_from_slice(aur_to_update);
+    }
+    if !outcome.aur_failed.is_empty() && confirm_retry(&outcome.aur_failed, params) {
+        let (built, failed) = install_aur_each(&outcome.aur_failed, params);
+        for name in built {
+            if aur_to_update.contains(&name) {
+                outcome.aur_updated.push(name);
             } else {
-                handle_error(pm.install_aur(aur_to_install));
+                outcome.aur_installed.push(name);
             }
         }
-        if !aur_to_update.is_empty() {
-            let pm = crate::core::pm::ParuPacman::new();
-            if use_pm_passthrough(non_interactive) {
-                println!(
-                    "  {} Package manager passthrough enabled",
-                    crate::internal::color::blue("info:")
-                );
-                handle_error(pm.update_aur_with_mode(aur_to_update, false));
+        outcome.aur_failed = failed;
+    }
+    report_warning(
+        "record the failed AUR builds",
+        crate::core::aur_failures::record(&attempted, &outcome.aur_failed),
+    );
+    outcome
+}
+
+/// Build `packages` from the AUR one at a time, so one failed build does not take the
+/// others down. Returns the ones that built and the ones that failed.
+pub fn install_aur_each(
+    packages: &[String],
+    params: &PackageOperationParams,
+) -> (Vec<String>, Vec<String>) {
+    let pm = crate::core::pm::ParuPacman::new();
+    let mut built = Vec::new();
+    let mut failed = Vec::new();
+    for package in packages {
+        let target = std::slice::from_ref(package);
+        let installed = install_reported("aur", target, || {
+            if params.passthrough {
+                pm.install_aur_with_mode(target, false)
             } else {
-                handle_error(pm.update_aur(aur_to_update));
+                pm.install_aur(target)
             }
+        });
+        if installed {
+            built.push(package.clone());
+        } else {
+            failed.push(package.clone());
         }
-    } else {
+    }
+    (built, failed)
+}
+
+/// Whether to build the `failed` AUR packages once more; only asked interactively
+fn confirm_retry(failed: &[String], params: &PackageOperationParams) -> bool {
+    if params.non_interactive {
+        return false;
+    }
+    print!(
+        "  -> {} AUR build{} failed ({}). Retry once? (y/N): ",
+        failed.len(),
+        if failed.len() == 1 { "" } else { "s" },
+        failed.join(", ")
+    );
+    std::io::Write::flush(&mut std::io::stdout()).ok();
+    match crate::cli::ui::read_answer("the AUR retry prompt") {
+        Ok(answer) => matches!(answer.to_lowercase().as_str(), "y" | "yes"),
+        Err(err) => {
+            report_error(Err(err));
+            false
+        }
+    }
+}
+
+/// Split AUR updates into those to run and those the config skips
+pub fn split_aur_skipped(updates: &[String], skip: &[String]) -> (Vec<String>, Vec<String>) {
+    updates
+        .iter()
+        .cloned()
+        .partition(|name| !skip.contains(name))
+}
+
+/// Say how many AUR updates the config suppressed, if any
+pub fn note_aur_skipped(skipped: &[String]) {
+    if let Some(line) = aur_skipped_line(skipped) {
+        println!("  {}", color::info(&line));
+    }
+}
+
+/// The note `note_aur_skipped` prints, or nothing when no update was skipped
+pub fn aur_skipped_line(skipped: &[String]) -> Option<String> {
+    (!skipped.is_empty()).then(|| {
+        format!(
+            "{} AUR update{} skipped by config (@aur-skip): {}",
+            skipped.len(),
+            if skipped.len() == 1 { "" } else { "s" },
+            skipped.join(", ")
+        )
+    })
+}
+
+/// Whether to go ahead with one AUR operation: asked unless the run is a dry run,
+/// non-interactive, or already confirmed; never for an empty set
+fn confirm_aur(
+    packages: &[String],
+    operation: crate::cli::ui::AurOperation,
+    params: &PackageOperationParams,
+) -> bool {
+    if packages.is_empty() {
+        return false;
+    }
+    if params.dry_run || params.non_interactive || params.confirmed {
+        return true;
+    }
+    match crate::cli::ui::confirm_aur_operation(packages, operation) {
+        Ok(true) => true,
+        Ok(false) => {
+            println!(
+                "  {}",
+                color::info(&format!("Not {} AUR packages", operation.label()))
+            );
+            false
+        }
+        Err(err) => {
+            report_error(Err(err));
+            false
+        }
+    }
+}
+
+pub fn update_repo_packages(params: &PackageOperationParams) {
+    let held = &params.held;
+    if !held.is_empty() {
         println!(
             "  {}",
-            crate::internal::color::blue("AUR package operations cancelled")
+            color::info(&format!("holding pinned packages: {}", held.join(", ")))
         );
     }
-}
-
-pub fn update_repo_packages(dry_run: bool, non_interactive: bool) {
-    if dry_run {
+    if params.dry_run {
         println!(
-            "  {} Would update official repository packages",
-            crate::internal::color::blue("info:")
+            "  {}",
+            color::info("Would update official repository packages")
         );
         return;
     }
     let pm = crate::core::pm::ParuPacman::new();
-    if use_pm_passthrough(non_interactive) {
-        println!(
-            "  {} Package manager passthrough enabled",
-            crate::internal::color::blue("info:")
+    if params.passthrough {
+        report(
+            "update repo packages",
+            pm.update_repo_with_mode(false, held),
         );
-        handle_error_with_context("update repo packages", pm.update_repo_with_mode(false));
     } else {
-        handle_error_with_context("update repo packages", pm.update_repo());
+        report("update repo packages", pm.update_repo(held));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modes_share_one_line() {
+        let params = |dry_run, non_interactive, passthrough| PackageOperationParams {
+            dry_run,
+            non_interactive,
+            passthrough,
+            ..PackageOperationParams::default()
+        };
+        assert_eq!(params(false, false, false).modes(), None);
+        assert_eq!(
+            params(true, false, false).modes().as_deref(),
+            Some("Dry run mode - no changes will be made to the system")
+        );
+        assert_eq!(
+            params(false, true, false).modes().as_deref(),
+            Some("Non-interactive")
+        );
+        assert_eq!(
+            params(true, false, true).modes().as_deref(),
+            Some(
+                "Dry run mode, package manager passthrough - no changes will be made to the system"
+            )
+        );
+    }
+
+    #[test]
+    fn test_aur_confirmation_skips_empty_sets_and_dry_runs() {
+        use crate::cli::ui::AurOperation;
+        let params = PackageOperationParams {
+            dry_run: true,
+            ..PackageOperationParams::default()
+        };
+        let chromium = ["chromium".to_string()];
+        assert!(!confirm_aur(&[], AurOperation::Install, &params));
+        assert!(confirm_aur(&chromium, AurOperation::Update, &params));
+        assert_eq!(AurOperation::Update.verb(), "update");
+        assert_eq!(AurOperation::Install.label(), "installing");
+    }
+
+    #[test]
+    fn test_split_aur_skipped_keeps_the_rest_in_order() {
+        let names =
+            |names: &[&str]| -> Vec<String> { names.iter().map(ToString::to_string).collect() };
+        let updates = names(&["chromium", "neovim-git", "paru-bin", "hyprland-git"]);
+        assert_eq!(
+            split_aur_skipped(&updates, &names(&["hyprland-git", "neovim-git", "absent"])),
+            (
+                names(&["chromium", "paru-bin"]),
+                names(&["neovim-git", "hyprland-git"])
+            )
+        );
+        assert_eq!(
+            split_aur_skipped(&updates, &[]),
+            (updates.clone(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_fail_fast_skips_steps_after_an_error() {
+        let mut params = PackageOperationParams {
+            non_interactive: true,
+            ..PackageOperationParams::default()
+        };
+        let mut reporter = crate::error::Reporter::default();
+        let mut skipped = Vec::new();
+        params.fail_fast = true;
+        assert!(!params.skip_after(reporter.errors_reported(), "AUR packages", &mut skipped));
+
+        reporter.result(
+            crate::error::Severity::Error,
+            Some("install htop"),
+            Err(anyhow::anyhow!("target not found")),
+        );
+        params.fail_fast = false;
+        assert!(!params.skip_after(reporter.errors_reported(), "AUR packages", &mut skipped));
+        assert!(skipped.is_empty());
+
+        params.fail_fast = true;
+        assert!(params.skip_after(reporter.errors_reported(), "AUR packages", &mut skipped));
+        assert!(params.skip_after(reporter.errors_reported(), "dotfiles", &mut skipped));
+        assert_eq!(skipped, ["AUR packages", "dotfiles"]);
     }
 }
@@ -0,0 +1,259 @@
+//! Everything an apply is about to do, worked out before any of it runs
+//!
+//! The package phases execute from an `ApplyPlan`, made from the package side of a
+//! `core::plan::Plan`; `owl apply --plan` also fills in the dotfiles and services,
+//! prints the whole plan (paged when it is long), and asks once.
+
+use std::io::Write;
+
+use crate::core::dotfiles::{DotfileAction, DotfileStatus};
+use crate::internal::color;
+
+use super::packages::{self, PackageOperationParams};
+
+#[derive(Debug, Clone, Default)]
+pub struct ApplyPlan {
+    pub repo_installs: Vec<String>,
+    /// Members of the groups among `repo_installs`
+    pub groups: crate::core::package::GroupMembers,
+    pub aur_installs: Vec<String>,
+    /// Outdated AUR packages, pinned ones left out
+    pub aur_updates: Vec<String>,
+    /// Whether repo packages get upgraded; false while unread Arch news holds them back
+    pub upgrades: bool,
+    /// Repo packages with an update available
+    pub repo_update_count: usize,
+    pub removals: Vec<String>,
+    /// Pinned packages with an update available that the run holds back
+    pub held: Vec<String>,
+    /// Outdated AUR packages the config keeps out of updates (`@aur-skip`)
+    pub aur_skipped: Vec<String>,
+    /// Only filled in for `--plan`
+    pub dotfiles: Vec<DotfileAction>,
+    /// Configured services that are not both enabled and running; only for `--plan`
+    pub services: Vec<String>,
+}
+
+impl ApplyPlan {
+    /// What apply runs for the package side of `plan`: the installs split between
+    /// the repos and the AUR, the removals, and the updates, held and skipped ones
+    /// included, only when `updates` is set and unread Arch news does not hold them
+    /// back
+    pub fn from_plan(
+        plan: &crate::core::plan::Plan,
+        updates: bool,
+        params: &PackageOperationParams,
+    ) -> Self {
+        let (repo_installs, aur_installs, groups) =
+            packages::categorize_install_sets(&plan.installs, params);
+        let upgrades = updates && packages::news_allows_upgrades(params.dry_run);
+        let (aur_updates, held, aur_skipped) = if upgrades {
+            (
+                plan.aur_updates.clone(),
+                plan.held.clone(),
+                plan.aur_skipped.clone(),
+            )
+        } else {
+            Default::default()
+        };
+        Self {
+            repo_installs,
+            groups,
+            aur_installs,
+            aur_updates,
+            upgrades,
+            repo_update_count: plan.repo_update_count,
+            removals: plan.removals.clone(),
+            held,
+            aur_skipped,
+            ..Self::default()
+        }
+    }
+
+    /// Add the dotfiles a sync would create or update
+    pub fn add_dotfiles(&mut self, config: &crate::core::config::Config) {
+        let mappings = crate::core::dotfiles::get_dotfile_mappings(config);
+        match crate::core::dotfiles::apply_dotfiles(&mappings, true) {
+            Ok(actions) => {
+                self.dotfiles = actions
+                    .into_iter()
+                    .filter(|action| action.status != DotfileStatus::UpToDate)
+                    .collect();
+            }
+            Err(err) => {
+                crate::error::report_warning("plan the dotfiles", Err(err));
+            }
+        }
+    }
+
+    /// Add the configured services that still need enabling or starting
+    pub fn add_services(&mut self, config: &crate::core::config::Config) {
+        self.services = crate::core::services::get_configured_services(config)
+            .iter()
+            .map(|service| crate::core::services::query_service_status(service))
+            .filter(|status| !(status.enabled && status.active))
+            .map(|status| status.name)
+            .collect();
+    }
+
+    /// The counts the pre- and post-apply hook scripts are given
+    pub fn hook_env(&self) -> crate::core::apply_hooks::HookEnv {
+        let repo_updates = if self.upgrades {
+            self.repo_update_count
+        } else {
+            0
+        };
+        crate::core::apply_hooks::HookEnv {
+            installs: self.repo_installs.len() + self.aur_installs.len(),
+            updates: repo_updates + self.aur_updates.len(),
+            removals: self.removals.len(),
+            failed: None,
+            run_id: None,
+            changed_files: Vec::new(),
+        }
+    }
+
+    /// True when the plan changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.lines().is_empty()
+    }
+
+    /// One line per kind of change, empty ones left out
+    pub fn lines(&self) -> Vec<String> {
+        let dotfiles: Vec<String> = self
+            .dotfiles
+            .iter()
+            .map(|action| action.mapping.destination.clone())
+            .collect();
+        let mut lines = Vec::new();
+        lines.extend(listed(
+            "install from the repos",
+            &crate::core::package::describe_groups(&self.repo_installs, &self.groups),
+        ));
+        lines.extend(listed("install from the AUR", &self.aur_installs));
+        if self.upgrades && self.repo_update_count > 0 {
+            lines.push(format!(
+                "update repo packages ({} pending)",
+                self.repo_update_count
+            ));
+        }
+        lines.extend(listed("update from the AUR", &self.aur_updates));
+        lines.extend(listed("remove", &self.removals));
+        lines.extend(listed("write dotfiles", &dotfiles));
+        lines.extend(listed("enable and start services", &self.services));
+        lines
+    }
+
+    /// Write the plan under a `[plan]` header, followed by the updates pins hold back
+    /// and those the config skips
+    pub fn print(&self, out: &mut impl Write) {
+        writeln!(out).ok();
+        writeln!(out, "[{}]", color::blue("plan")).ok();
+        if self.is_empty() {
+            writeln!(out, "  {} nothing to do", color::green("✓")).ok();
+        }
+        for line in self.lines() {
+            writeln!(out, "  {}", line).ok();
+        }
+        let notes = listed("held back by pins", &self.held)
+            .into_iter()
+            .chain(packages::aur_skipped_line(&self.aur_skipped));
+        for line in notes {
+            writeln!(out, "  {}", color::info(&line)).ok();
+        }
+    }
+}
+
+/// `label (count): names`, or nothing for an empty list
+fn listed(label: &str, names: &[String]) -> Option<String> {
+    (!names.is_empty()).then(|| format!("{} ({}): {}", label, names.len(), names.join(", ")))
+}
+
+/// Ask the single "Proceed?" for a printed plan
+pub fn confirm() -> bool {
+    print!("\n  -> Proceed? [y/N]: ");
+    std::io::Write::flush(&mut std::io::stdout()).ok();
+    match crate::cli::ui::read_answer("the plan confirmation") {
+        Ok(answer) => matches!(answer.to_lowercase().as_str(), "y" | "yes"),
+        Err(err) => {
+            crate::error::report_error(Err(err));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dotfiles::DotfileMapping;
+
+    #[test]
+    fn test_from_plan_leaves_updates_out_without_upgrades() {
+        let names =
+            |names: &[&str]| -> Vec<String> { names.iter().map(ToString::to_string).collect() };
+        let plan = crate::core::plan::Plan {
+            removals: names(&["nano"]),
+            repo_update_count: 4,
+            aur_updates: names(&["paru-bin"]),
+            held: names(&["linux"]),
+            aur_skipped: names(&["neovim-git"]),
+            ..crate::core::plan::Plan::default()
+        };
+        let params = PackageOperationParams {
+            dry_run: true,
+            non_interactive: true,
+            check_names: false,
+            ..PackageOperationParams::default()
+        };
+        let apply = ApplyPlan::from_plan(&plan, false, &params);
+        assert_eq!(apply.removals, names(&["nano"]));
+        assert!(!apply.upgrades);
+        assert!(apply.aur_updates.is_empty() && apply.held.is_empty());
+        assert!(apply.aur_skipped.is_empty());
+        assert_eq!(apply.lines(), ["remove (1): nano"]);
+    }
+
+    #[test]
+    fn test_plan_lines_list_each_kind_of_change() {
+        let names = |names: &[&str]| names.iter().map(ToString::to_string).collect();
+        let mut plan = ApplyPlan {
+            repo_installs: names(&["base-devel", "htop", "ripgrep"]),
+            groups: [("base-devel".to_string(), names(&["gcc", "make"]))].into(),
+            aur_updates: names(&["paru-bin"]),
+            upgrades: true,
+            repo_update_count: 12,
+            removals: names(&["nano"]),
+            services: names(&["sshd"]),
+            dotfiles: vec![DotfileAction {
+                mapping: DotfileMapping {
+                    source: "nvim".to_string(),
+                    destination: "~/.config/nvim".to_string(),
+                },
+                status: DotfileStatus::Update,
+            }],
+            ..ApplyPlan::default()
+        };
+        assert_eq!(
+            plan.lines(),
+            [
+                "install from the repos (3): base-devel (2 packages), htop, ripgrep",
+                "update repo packages (12 pending)",
+                "update from the AUR (1): paru-bin",
+                "remove (1): nano",
+                "write dotfiles (1): ~/.config/nvim",
+                "enable and start services (1): sshd",
+            ]
+        );
+
+        plan.upgrades = false;
+        assert!(!plan.lines().iter().any(|line| line.contains("pending")));
+        assert!(ApplyPlan::default().is_empty());
+        assert!(
+            ApplyPlan {
+                repo_update_count: 3,
+                ..ApplyPlan::default()
+            }
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! What an apply actually did, printed as a table when it ends
+//!
+//! Problems reported along the way are carried on past, so the table is where a
+//! failure in an early phase shows up again after the rest of the run's output.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Issue;
+use crate::internal::color;
+
+/// Per-phase results of one apply. Empty lists on a dry run, where nothing changes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApplySummary {
+    pub dry_run: bool,
+    pub repo_installed: Vec<String>,
+    pub aur_installed: Vec<String>,
+    pub aur_updated: Vec<String>,
+    /// AUR packages whose build failed; recorded for `owl apply --retry-failed`, and
+    /// the next apply tries them again
+    pub aur_failed: Vec<String>,
+    pub removed: Vec<String>,
+    /// Orphaned dependencies removed by the cleanup phase
+    pub orphans: Vec<String>,
+    /// Destinations of the dotfiles written
+    pub dotfiles: Vec<String>,
+    /// Services enabled or started
+    pub services: Vec<String>,
+    /// Everything reported during the run; errors are its failures
+    pub issues: Vec<Issue>,
+    /// Steps `--fail-fast` left out after the first error
+    pub skipped: Vec<String>,
+}
+
+impl ApplySummary {
+    /// Whether any phase recorded a failure, which makes the apply exit 1
+    pub fn failed(&self) -> bool {
+        crate::error::failed(&self.issues)
+    }
+
+    /// One row per kind of change that happened: label, count, and names
+    pub fn rows(&self) -> Vec<String> {
+        [
+            ("repo installed", &self.repo_installed),
+            ("AUR installed", &self.aur_installed),
+            ("AUR updated", &self.aur_updated),
+            ("removed", &self.removed),
+            ("orphans", &self.orphans),
+            ("dotfiles", &self.dotfiles),
+            ("services", &self.services),
+        ]
+        .into_iter()
+        .filter(|(_, names)| !names.is_empty())
+        .map(|(label, names)| format!("{:<15}{:>3}  {}", label, names.len(), names.join(", ")))
+        .collect()
+    }
+
+    /// Print the table under a `[summary]` header, failures in red. A dry run
+    /// without problems prints nothing; its plan was already shown.
+    pub fn print(&self) {
+        let mut lines = self.rows();
+        if !self.aur_failed.is_empty() {
+            lines.push(color::red(&format!(
+                "AUR failed {:>4}  {} (will retry on next run, or `owl apply --retry-failed`)",
+                self.aur_failed.len(),
+                self.aur_failed.join(", ")
+            )));
+        }
+        if !self.skipped.is_empty() {
+            lines.push(color::red(&format!(
+                "skipped after the first error (fail-fast): {}",
+                self.skipped.join(", ")
+            )));
+        }
+        lines.extend(crate::error::completion_lines(&self.issues));
+        if lines.is_empty() {
+            if self.dry_run {
+                return;
+            }
+            lines.push(format!("{} nothing changed", color::green("✓")));
+        }
+        println!();
+        println!("[{}]", color::blue("summary"));
+        for line in &lines {
+            println!("  {}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Severity;
+
+    #[test]
+    fn test_rows_skip_empty_phases_and_failures_fail_the_run() {
+        let names = |names: &[&str]| names.iter().map(ToString::to_string).collect();
+        let mut summary = ApplySummary {
+            repo_installed: names(&["htop", "ripgrep"]),
+            aur_updated: names(&["paru-bin"]),
+            services: names(&["sshd"]),
+            ..ApplySummary::default()
+        };
+        assert_eq!(
+            summary.rows(),
+            [
+                "repo installed   2  htop, ripgrep",
+                "AUR updated      1  paru-bin",
+                "services         1  sshd",
+            ]
+        );
+        assert!(!summary.failed());
+
+        summary.issues.push(Issue {
+            severity: Severity::Warning,
+            phase: Some("packages".to_string()),
+            context: None,
+            message: "could not resolve host".to_string(),
+        });
+        assert!(!summary.failed());
+        summary.issues.push(Issue {
+            severity: Severity::Error,
+            phase: Some("system".to_string()),
+            context: Some("enable sshd".to_string()),
+            message: "Failed to enable sshd".to_string(),
+        });
+        assert!(summary.failed());
+
+        let json = serde_json::to_string(&summary).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ApplySummary>(&json).unwrap(),
+            summary
+        );
+        assert!(ApplySummary::default().rows().is_empty());
+    }
+}
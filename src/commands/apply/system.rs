@@ -1,5 +1,10 @@
-/// Handle system section (services + environment variables)
-pub fn handle_system_section_with_config(config: &crate::core::config::Config, dry_run: bool) {
+/// Handle system section (services + environment variables), returning the services
+/// that were enabled or started
+pub fn handle_system_section_with_config(
+    config: &crate::core::config::Config,
+    dry_run: bool,
+) -> Vec<String> {
+    let mut changed = Vec::new();
     // no-op placeholder kept for potential future use
 
     // Check if we have services or environment variables
@@ -7,7 +12,7 @@ pub fn handle_system_section_with_config(config: &crate::core::config::Config, d
     let env_var_count = super::analysis::count_environment_variables(config);
 
     if services.is_empty() && env_var_count == 0 {
-        return;
+        return changed;
     }
 
     // Show section header
@@ -40,16 +45,25 @@ pub fn handle_system_section_with_config(config: &crate::core::config::Config, d
             ) {
                 Ok(result) => result,
                 Err(err) => {
-                    eprintln!(
-                        "{}",
-                        crate::internal::color::red(&format!(
-                            "Failed to configure services: {}",
-                            err
-                        ))
-                    );
-                    return;
+                    crate::error::report("configure services", Err(err));
+                    return changed;
                 }
             };
+            for service in &result.failed_services {
+                crate::error::report_error(Err(anyhow::anyhow!(
+                    "Failed to enable or start service {}",
+                    service
+                )));
+            }
+            for service in result
+                .enabled_services
+                .iter()
+                .chain(&result.started_services)
+            {
+                if !changed.contains(service) {
+                    changed.push(service.clone());
+                }
+            }
 
             if result.changed {
                 println!(
@@ -88,14 +102,10 @@ pub fn handle_system_section_with_config(config: &crate::core::config::Config, d
 
     // Handle environment variables
     if env_var_count > 0 {
-        match crate::core::env::apply_environment_variables(config, dry_run) {
-            Ok(()) => {}
-            Err(e) => {
-                eprintln!(
-                    "{}",
-                    crate::internal::color::red(&format!("Environment handling failed: {}", e))
-                );
-            }
-        }
+        crate::error::report(
+            "apply environment variables",
+            crate::core::env::apply_environment_variables(config, dry_run),
+        );
     }
+    changed
 }
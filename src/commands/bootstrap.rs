@@ -0,0 +1,308 @@
+//! Provision a fresh machine from a config repository: prepare pacman, clone the
+//! config, make the AUR usable, then run the first apply
+//!
+//! Each step checks whether it is already done before acting, so rerunning after a
+//! failure carries on from the step that failed.
+
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cli::handler::GlobalFlags;
+use crate::core::config::Config;
+use crate::internal::color;
+use crate::internal::constants;
+use crate::internal::logging::LogCommand;
+
+/// AUR helpers owl can drive; `-bin` and `-git` variants count too
+const AUR_HELPERS: [&str; 2] = ["paru", "yay"];
+
+/// Run the bootstrap command
+pub fn run(url: &str, assume_yes: bool, flags: &GlobalFlags) {
+    let mut flags = flags.clone();
+    flags.non_interactive |= assume_yes;
+
+    println!("[{}]", color::blue("bootstrap"));
+    let ready = match prepare(url, &flags) {
+        Ok(ready) => ready,
+        Err(err) => crate::error::exit_with_error(
+            err.context("Bootstrap stopped; rerun `owl bootstrap` to continue from here"),
+        ),
+    };
+    if !ready {
+        println!(
+            "  {} the first apply runs once the config is cloned",
+            color::blue("info:")
+        );
+        return;
+    }
+    println!();
+    crate::commands::apply::run(&flags);
+}
+
+/// Every step before the apply. False when a dry run stops before the clone exists.
+fn prepare(url: &str, flags: &GlobalFlags) -> Result<bool> {
+    ensure_keyring(flags.dry_run)?;
+    ensure_installed(&["git"], flags)?;
+
+    let owl = crate::internal::files::owl_dir()?;
+    if !clone_config(url, &owl, flags.dry_run)? {
+        return Ok(false);
+    }
+
+    let config =
+        Config::load_all_relevant_config_files().context("Failed to load the cloned config")?;
+    let aur = aur_packages(&config)?;
+    if aur.is_empty() {
+        println!("  {} no AUR packages configured", color::green("✓"));
+        return Ok(true);
+    }
+    ensure_installed(&["base-devel"], flags)?;
+    ensure_aur_helper(&aur, flags)?;
+    Ok(true)
+}
+
+fn shown(path: &Path) -> String {
+    crate::internal::files::friendly_path(&path.display().to_string())
+}
+
+fn ensure_keyring(dry_run: bool) -> Result<()> {
+    let dir = Path::new(constants::PACMAN_GNUPG_DIR);
+    if ["pubring.gpg", "pubring.kbx"]
+        .iter()
+        .any(|file| dir.join(file).exists())
+    {
+        println!("  {} pacman keyring is initialized", color::green("✓"));
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "  {} would initialize and populate the pacman keyring",
+            color::green("➔")
+        );
+        return Ok(());
+    }
+    crate::core::privilege::run(&["pacman-key", "--init"])?;
+    crate::core::privilege::run(&["pacman-key", "--populate", "archlinux"])?;
+    println!("  {} initialized the pacman keyring", color::green("✓"));
+    Ok(())
+}
+
+/// Install any of `packages` that are missing from the repos
+fn ensure_installed(packages: &[&str], flags: &GlobalFlags) -> Result<()> {
+    let mut missing = Vec::new();
+    for package in packages {
+        if !crate::core::package::is_package_installed(package)? {
+            missing.push(*package);
+        }
+    }
+    if missing.is_empty() {
+        println!("  {} {} installed", color::green("✓"), packages.join(", "));
+        return Ok(());
+    }
+    if flags.dry_run {
+        println!(
+            "  {} would install {}",
+            color::green("➔"),
+            missing.join(", ")
+        );
+        return Ok(());
+    }
+    let mut args = vec!["pacman", "-S", "--needed"];
+    if flags.non_interactive {
+        args.push("--noconfirm");
+    }
+    args.extend(&missing);
+    crate::core::privilege::run(&args)?;
+    crate::core::package::invalidate_package_caches();
+    println!("  {} installed {}", color::green("✓"), missing.join(", "));
+    Ok(())
+}
+
+/// Whether two remote URLs name the same repository, e.g. the https and ssh forms
+fn same_repository(a: &str, b: &str) -> bool {
+    fn normalize(url: &str) -> String {
+        let url = url.trim().trim_end_matches('/');
+        let url = url.strip_suffix(".git").unwrap_or(url);
+        let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let url = url.split_once('@').map_or(url, |(_, rest)| rest);
+        url.replacen(':', "/", 1).to_lowercase()
+    }
+    normalize(a) == normalize(b)
+}
+
+/// Clone the config into the owl directory. False on a dry run that would clone.
+fn clone_config(url: &str, owl: &Path, dry_run: bool) -> Result<bool> {
+    if owl.join(".git").exists() {
+        return match crate::core::git::origin_url(owl)? {
+            Some(origin) if same_repository(&origin, url) => {
+                println!("  {} {} is cloned", color::green("✓"), shown(owl));
+                Ok(true)
+            }
+            origin => Err(anyhow!(
+                "{} is already a clone of {}; move it aside or pass --config-dir",
+                shown(owl),
+                origin
+                    .as_deref()
+                    .unwrap_or("a repository without an origin")
+            )),
+        };
+    }
+    let occupied = owl.is_dir()
+        && std::fs::read_dir(owl)
+            .with_context(|| format!("Failed to read {}", owl.display()))?
+            .next()
+            .is_some();
+    if occupied || owl.is_file() {
+        return Err(anyhow!(
+            "{} already exists and is not a clone of {}; move it aside or pass --config-dir",
+            shown(owl),
+            url
+        ));
+    }
+    if dry_run {
+        println!(
+            "  {} would clone {} into {}",
+            color::green("➔"),
+            url,
+            shown(owl)
+        );
+        return Ok(false);
+    }
+
+    // Clone beside the target so an interrupted clone is never mistaken for a finished one
+    let name = owl
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let partial = owl.with_file_name(format!("{}.partial", name));
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)
+            .with_context(|| format!("Failed to remove {}", partial.display()))?;
+    }
+    crate::core::git::clone(url, &partial)?;
+    if !partial.join(constants::MAIN_CONFIG_FILE).exists() {
+        std::fs::remove_dir_all(&partial).ok();
+        return Err(anyhow!(
+            "{} has no {} at its top level",
+            url,
+            constants::MAIN_CONFIG_FILE
+        ));
+    }
+    if owl.exists() {
+        std::fs::remove_dir(owl).with_context(|| format!("Failed to replace {}", owl.display()))?;
+    }
+    std::fs::rename(&partial, owl)
+        .with_context(|| format!("Failed to move the clone to {}", owl.display()))?;
+    println!("  {} cloned {} into {}", color::green("✓"), url, shown(owl));
+    Ok(true)
+}
+
+/// Configured packages that are neither in the repos nor package groups
+fn aur_packages(config: &Config) -> Result<Vec<String>> {
+    let names: Vec<String> = config.packages.keys().cloned().collect();
+    let categorized = crate::core::package::categorize_packages(&names)?;
+    Ok(categorized.install_sets().1)
+}
+
+/// The AUR helper package the config asks for, e.g. `paru-bin`
+fn configured_helper(aur: &[String]) -> Option<&str> {
+    aur.iter().map(String::as_str).find(|name| {
+        AUR_HELPERS.iter().any(|helper| {
+            name == helper
+                || name
+                    .strip_prefix(helper)
+                    .is_some_and(|rest| rest == "-bin" || rest == "-git")
+        })
+    })
+}
+
+fn build_root() -> Result<PathBuf> {
+    let cache = match std::env::var("XDG_CACHE_HOME") {
+        Ok(cache) if !cache.is_empty() => PathBuf::from(cache),
+        _ => PathBuf::from(
+            std::env::var("HOME").map_err(|_| anyhow!("HOME environment variable not set"))?,
+        )
+        .join(".cache"),
+    };
+    Ok(cache.join("owl").join("bootstrap"))
+}
+
+/// Build the configured AUR helper with makepkg when none is installed
+fn ensure_aur_helper(aur: &[String], flags: &GlobalFlags) -> Result<()> {
+    if let Some(helper) = AUR_HELPERS
+        .iter()
+        .find(|helper| crate::core::pm::command_exists(helper))
+    {
+        println!("  {} {} is installed", color::green("✓"), helper);
+        return Ok(());
+    }
+    let Some(package) = configured_helper(aur) else {
+        return Err(anyhow!(
+            "the config has AUR packages ({}) but no AUR helper; add paru or yay to it",
+            aur.join(", ")
+        ));
+    };
+    if crate::core::privilege::is_root() {
+        return Err(anyhow!(
+            "makepkg cannot build {} as root; rerun `owl bootstrap` as a regular user with sudo",
+            package
+        ));
+    }
+    if flags.dry_run {
+        println!(
+            "  {} would build {} from the AUR",
+            color::green("➔"),
+            package
+        );
+        return Ok(());
+    }
+
+    let dir = build_root()?.join(package);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)
+            .with_context(|| format!("Failed to remove {}", dir.display()))?;
+    }
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    crate::core::git::clone(&format!("{}/{}.git", constants::AUR_GIT_URL, package), &dir)?;
+    let mut makepkg = Command::new("makepkg");
+    makepkg.arg("-si").current_dir(&dir);
+    if flags.non_interactive {
+        makepkg.arg("--noconfirm");
+    }
+    let status = makepkg.logged().status().context("Failed to run makepkg")?;
+    if !status.success() {
+        return Err(anyhow!("makepkg failed to build {}", package));
+    }
+    std::fs::remove_dir_all(&dir).ok();
+    println!("  {} built and installed {}", color::green("✓"), package);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_repository_across_url_forms() {
+        let https = "https://github.com/me/dotfiles";
+        assert!(same_repository(https, "https://github.com/me/dotfiles.git"));
+        assert!(same_repository(https, "git@github.com:me/dotfiles.git"));
+        assert!(same_repository(https, "ssh://git@github.com/me/dotfiles/"));
+        assert!(!same_repository(https, "https://github.com/me/other"));
+    }
+
+    #[test]
+    fn test_configured_helper() {
+        let names = |items: &[&str]| items.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            configured_helper(&names(&["spotify", "paru-bin"])),
+            Some("paru-bin")
+        );
+        assert_eq!(configured_helper(&names(&["yay"])), Some("yay"));
+        assert_eq!(configured_helper(&names(&["paruz", "yay-themes"])), None);
+    }
+}
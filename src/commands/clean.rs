@@ -1,13 +1,154 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashSet;
 use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
+use crate::cli::ui::format_size;
+use crate::core::clean::{Candidate, Category};
 use crate::core::config::Config;
 use crate::internal::color;
+use crate::internal::logging::LogCommand;
+
+/// Free disk space in the given categories, or in all of them when none is given
+pub fn run(categories: &[Category], flags: &crate::cli::handler::GlobalFlags) {
+    let categories = if categories.is_empty() {
+        Category::ALL.to_vec()
+    } else {
+        categories.to_vec()
+    };
+    crate::error::exit_on_error(clean_disk(
+        &categories,
+        flags.dry_run,
+        flags.non_interactive,
+    ));
+}
+
+fn clean_disk(categories: &[Category], dry_run: bool, non_interactive: bool) -> Result<()> {
+    let candidates = collect(categories)?;
+    println!("[{}]", color::blue("clean"));
+    for category in categories {
+        let found: Vec<&Candidate> = candidates
+            .iter()
+            .filter(|candidate| candidate.category == *category)
+            .collect();
+        let size: u64 = found.iter().map(|candidate| candidate.size).sum();
+        println!(
+            "  {} {:<14} {:>10}  {} item{}",
+            color::blue("info:"),
+            category.label(),
+            format_size(size),
+            found.len(),
+            if found.len() == 1 { "" } else { "s" }
+        );
+        for candidate in &found {
+            log::debug!("clean: {}", candidate.path.display());
+        }
+    }
+
+    let total: u64 = candidates.iter().map(|candidate| candidate.size).sum();
+    if candidates.is_empty() {
+        println!("  {} nothing to clean", color::green("✓"));
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "  {} would free {}",
+            color::blue("info:"),
+            format_size(total)
+        );
+        return Ok(());
+    }
+    if !non_interactive && !confirm(total)? {
+        println!("  {} nothing deleted", color::yellow("!"));
+        return Ok(());
+    }
+
+    let removal = crate::core::clean::remove(&candidates)?;
+    let mut freed = removal.freed;
+    if !removal.denied.is_empty() {
+        // The pacman cache belongs to root
+        let status = Command::new("sudo")
+            .args(["rm", "-rf", "--"])
+            .args(&removal.denied)
+            .logged()
+            .status()
+            .context("Failed to run sudo rm")?;
+        if !status.success() {
+            return Err(anyhow!(
+                "sudo rm failed after freeing {}",
+                format_size(freed)
+            ));
+        }
+        freed += candidates
+            .iter()
+            .filter(|candidate| removal.denied.contains(&candidate.path))
+            .map(|candidate| candidate.size)
+            .sum::<u64>();
+    }
+    println!("  {} freed {}", color::green("✓"), format_size(freed));
+    Ok(())
+}
+
+fn collect(categories: &[Category]) -> Result<Vec<Candidate>> {
+    let settings = &crate::core::settings::get().clean;
+    let mut candidates = Vec::new();
+    if categories.contains(&Category::Backups) {
+        candidates.extend(crate::core::clean::old_snapshots(
+            &crate::core::snapshot::snapshots_dir()?,
+            settings.keep_snapshots,
+        )?);
+        // `owl doctor --fix` leaves the state files it moved aside where they were
+        candidates.extend(crate::core::clean::corrupt_state_files(
+            &crate::core::state::PackageState::sibling_dir()?,
+        )?);
+    }
+    if categories.contains(&Category::Logs) {
+        candidates.extend(crate::core::clean::old_run_logs(
+            &crate::core::history::runs_dir()?,
+            crate::core::settings::get().history.keep_runs,
+        )?);
+    }
+    if categories.contains(&Category::PackageCache) {
+        candidates.extend(crate::core::clean::stale_package_files(
+            Path::new(crate::internal::constants::PACMAN_CACHE_DIR),
+            settings.keep_package_versions,
+            &crate::core::package::get_installed_versions()?,
+        )?);
+    }
+    if categories.contains(&Category::AurBuilds) {
+        let installed: HashSet<String> = crate::core::package::get_installed_packages()?;
+        for dir in aur_clone_dirs() {
+            candidates.extend(crate::core::clean::orphaned_build_dirs(&dir, &installed)?);
+        }
+    }
+    Ok(candidates)
+}
+
+/// Clone caches of the AUR helpers owl drives
+fn aur_clone_dirs() -> Vec<PathBuf> {
+    let cache = match std::env::var("XDG_CACHE_HOME") {
+        Ok(cache) if !cache.is_empty() => PathBuf::from(cache),
+        _ => match std::env::var("HOME") {
+            Ok(home) => PathBuf::from(home).join(".cache"),
+            Err(_) => return Vec::new(),
+        },
+    };
+    vec![cache.join("paru").join("clone"), cache.join("yay")]
+}
+
+fn confirm(total: u64) -> Result<bool> {
+    print!("  -> Delete and free {}? (y/N): ", format_size(total));
+    std::io::stdout().flush().ok();
+    let answer = crate::cli::ui::read_answer("the clean confirmation")?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
 
 pub fn handle_clean(filename: &str) -> Result<()> {
     // Read and parse the config file
     let config =
-        Config::parse_file(filename).map_err(|e| anyhow!("Failed to parse {}: {}", filename, e))?;
+        Config::parse_file(filename).with_context(|| format!("Failed to parse {}", filename))?;
 
     // Optimize the config
     let optimized_content = optimize_config(&config);
@@ -40,12 +181,11 @@ pub fn handle_clean_all() -> Result<()> {
     );
 
     let mut failed_count = 0;
+    let mut cleaned = Vec::new();
 
     for filename in config_files {
         match handle_clean(&filename) {
-            Ok(()) => {
-                // File cleaned successfully
-            }
+            Ok(()) => cleaned.push(PathBuf::from(filename)),
             Err(e) => {
                 failed_count += 1;
                 eprintln!("  {} {}: {}", color::red("✗"), color::dim(&filename), e);
@@ -53,6 +193,8 @@ pub fn handle_clean_all() -> Result<()> {
         }
     }
 
+    commit_cleaned(&cleaned);
+
     if failed_count > 0 {
         println!();
         println!(
@@ -65,11 +207,24 @@ pub fn handle_clean_all() -> Result<()> {
     Ok(())
 }
 
+/// Commit the tidied files; files the tidy left as they were drop out of the commit
+pub fn commit_cleaned(files: &[PathBuf]) {
+    let message = match files {
+        [file] => format!(
+            "owl clean: tidy {}",
+            crate::core::autocommit::describe(file)
+        ),
+        _ => format!("owl clean: tidy {} config files", files.len()),
+    };
+    crate::core::autocommit::commit_config_change(files, &message);
+}
+
 fn get_all_config_files() -> Result<Vec<String>> {
     crate::internal::files::get_all_config_files()
 }
 
-fn optimize_config(config: &Config) -> String {
+/// Render `config` as a tidy .owl file: groups, env vars, `@pkg` blocks, then `@pkgs`
+pub fn optimize_config(config: &Config) -> String {
     let mut sections: Vec<String> = Vec::new();
 
     // Collect packages without directives
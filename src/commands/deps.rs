@@ -0,0 +1,431 @@
+//! Dependency graph of the configured packages, as a tree or Graphviz output
+//!
+//! The graph is built from a plain name-to-dependencies map so the traversal can be
+//! tested without pacman; `dependency_map` fills that map from `pacman -Qi`, naming
+//! the installed provider for sonames and virtual packages.
+
+use anyhow::{Context, Result, anyhow};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::core::config::Config;
+use crate::core::pm::ParuPacman;
+use crate::internal::color;
+
+/// What `owl deps` shows
+pub struct DepsOptions {
+    /// Limit the tree to these configured packages
+    pub packages: Vec<String>,
+    /// Levels of dependencies below each configured package; unlimited when `None`
+    pub depth: Option<usize>,
+    pub dot: bool,
+    /// Follow installed optional dependencies too
+    pub optional: bool,
+}
+
+/// Configured packages and everything they pull in
+#[derive(Debug, PartialEq)]
+struct Graph {
+    roots: BTreeSet<String>,
+    /// Each reached package's dependencies
+    children: BTreeMap<String, Vec<String>>,
+    /// For each dependency, the configured packages that need it directly or indirectly
+    needed_by: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Run the deps command
+pub fn run(options: &DepsOptions) {
+    crate::core::state::forbid_writes();
+    crate::error::exit_on_error(deps(options));
+}
+
+fn deps(options: &DepsOptions) -> Result<()> {
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    let configured: Vec<String> = config.packages.keys().cloned().collect();
+    for package in &options.packages {
+        if !config.packages.contains_key(package) {
+            return Err(anyhow!("'{}' is not a configured package", package));
+        }
+    }
+
+    let map = dependency_map(&configured, options.depth, options.optional)?;
+    let graph = build_graph(&configured, &map, options.depth);
+    let shown: Vec<String> = if options.packages.is_empty() {
+        graph.roots.iter().cloned().collect()
+    } else {
+        options.packages.clone()
+    };
+
+    if options.dot {
+        print!("{}", render_dot(&graph, &shown));
+        return Ok(());
+    }
+    println!("[{}]", color::blue("deps"));
+    for (line, note) in render_tree(&graph, &shown, options.depth) {
+        match note {
+            Note::None => println!("  {}", line),
+            Note::Only(root) => {
+                println!("  {} {}", line, color::yellow(&format!("(only {})", root)))
+            }
+            Note::Configured => println!("  {} {}", line, color::blue("[configured]")),
+            Note::Cycle => println!("  {} {}", line, color::red("(cycle)")),
+            Note::Repeated => println!("  {} …", line),
+        }
+    }
+    let unique = graph
+        .needed_by
+        .values()
+        .filter(|roots| roots.len() == 1)
+        .count();
+    println!(
+        "\n  {} {} dependencies, {} needed by a single configured package",
+        color::blue("info:"),
+        graph.needed_by.len(),
+        unique
+    );
+    Ok(())
+}
+
+/// Dependencies of the installed packages reachable from `roots`, one `pacman -Qi`
+/// call per level. Sonames and virtual packages are replaced by the installed package
+/// providing them; names nothing installed provides (missing optional deps) have no
+/// entry.
+fn dependency_map(
+    roots: &[String],
+    depth: Option<usize>,
+    optional: bool,
+) -> Result<HashMap<String, Vec<String>>> {
+    let mut map: HashMap<String, Vec<String>> = HashMap::new();
+    let mut optional_deps: HashMap<String, Vec<String>> = HashMap::new();
+    let mut queried: HashSet<String> = roots.iter().cloned().collect();
+    let mut level: Vec<String> = roots.to_vec();
+    let installed = crate::core::package::get_installed_packages()?;
+    let provides = ParuPacman::new().installed_provides()?;
+    let providers = crate::core::package::providers(&provides);
+    let mut remaining = depth;
+    while !level.is_empty() && remaining != Some(0) {
+        remaining = remaining.map(|n| n - 1);
+        let info = crate::core::package::get_package_info(&level)?;
+        let mut next = Vec::new();
+        for (name, info) in info {
+            let resolve = |deps: &[String]| -> Vec<String> {
+                let mut resolved: Vec<String> = deps
+                    .iter()
+                    .map(|dep| resolve_dependency(dep, &installed, &providers))
+                    .collect();
+                resolved.sort();
+                resolved.dedup();
+                resolved
+            };
+            let deps = resolve(&info.depends_on);
+            let optional = if optional {
+                resolve(&info.optional_deps)
+            } else {
+                Vec::new()
+            };
+            next.extend(
+                deps.iter()
+                    .chain(&optional)
+                    .filter(|dep| queried.insert(dep.to_string()))
+                    .cloned(),
+            );
+            map.insert(name.clone(), deps);
+            optional_deps.insert(name, optional);
+        }
+        level = next;
+    }
+    // Optional deps only count when they are installed
+    for (name, optional) in optional_deps {
+        let installed: Vec<String> = optional
+            .into_iter()
+            .filter(|dep| map.contains_key(dep))
+            .collect();
+        if let Some(deps) = map.get_mut(&name) {
+            deps.extend(installed);
+            deps.sort();
+            deps.dedup();
+        }
+    }
+    Ok(map)
+}
+
+/// The package a dependency entry names: itself when installed under that name,
+/// otherwise its first installed provider, so `libssl.so=3-64` becomes `openssl`.
+/// Names nothing provides are kept as they are.
+fn resolve_dependency(
+    dependency: &str,
+    installed: &HashSet<String>,
+    providers: &HashMap<&str, Vec<&str>>,
+) -> String {
+    let name = crate::core::package::dependency_name(dependency);
+    if installed.contains(name) {
+        return name.to_string();
+    }
+    providers.get(name).map_or(name, |by| by[0]).to_string()
+}
+
+/// Walk the dependencies of every root up to `depth` levels. Each package is visited
+/// once per root, so cycles end the walk instead of looping.
+fn build_graph(
+    roots: &[String],
+    deps: &HashMap<String, Vec<String>>,
+    depth: Option<usize>,
+) -> Graph {
+    let roots: BTreeSet<String> = roots.iter().cloned().collect();
+    let mut children = BTreeMap::new();
+    let mut needed_by: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for root in &roots {
+        let mut seen = HashSet::from([root.clone()]);
+        let mut level = vec![root.clone()];
+        let mut remaining = depth;
+        while !level.is_empty() && remaining != Some(0) {
+            remaining = remaining.map(|n| n - 1);
+            let mut next = Vec::new();
+            for package in level {
+                let Some(package_deps) = deps.get(&package) else {
+                    continue;
+                };
+                children.insert(package.clone(), package_deps.clone());
+                for dep in package_deps {
+                    if !roots.contains(dep) {
+                        needed_by
+                            .entry(dep.clone())
+                            .or_default()
+                            .insert(root.clone());
+                    }
+                    if seen.insert(dep.clone()) {
+                        next.push(dep.clone());
+                    }
+                }
+            }
+            level = next;
+        }
+    }
+    Graph {
+        roots,
+        children,
+        needed_by,
+    }
+}
+
+/// Why a tree line is annotated
+#[derive(Debug, PartialEq)]
+enum Note {
+    None,
+    /// Only this configured package needs it
+    Only(String),
+    /// A configured package of its own, expanded at its own root
+    Configured,
+    /// Already on the path from the root
+    Cycle,
+    /// Expanded earlier in this root's tree
+    Repeated,
+}
+
+/// The tree under each of `shown`, as (line, annotation) pairs
+fn render_tree(graph: &Graph, shown: &[String], depth: Option<usize>) -> Vec<(String, Note)> {
+    struct Walk<'a> {
+        graph: &'a Graph,
+        depth: Option<usize>,
+        expanded: HashSet<String>,
+        path: Vec<String>,
+        lines: Vec<(String, Note)>,
+    }
+
+    impl Walk<'_> {
+        fn children(&mut self, package: &str, prefix: &str, level: usize) {
+            if self.depth.is_some_and(|depth| level >= depth) {
+                return;
+            }
+            let Some(deps) = self.graph.children.get(package) else {
+                return;
+            };
+            for (idx, dep) in deps.iter().enumerate() {
+                let last = idx + 1 == deps.len();
+                let branch = if last { "└── " } else { "├── " };
+                let line = format!("{}{}{}", prefix, branch, dep);
+                let note = if self.graph.roots.contains(dep) {
+                    Note::Configured
+                } else if self.path.contains(dep) {
+                    Note::Cycle
+                } else if self.graph.children.contains_key(dep)
+                    && !self.expanded.insert(dep.clone())
+                {
+                    Note::Repeated
+                } else {
+                    match self.graph.needed_by.get(dep) {
+                        Some(roots) if roots.len() == 1 => {
+                            Note::Only(roots.iter().next().cloned().unwrap_or_default())
+                        }
+                        _ => Note::None,
+                    }
+                };
+                let expand = matches!(note, Note::None | Note::Only(_));
+                self.lines.push((line, note));
+                if expand {
+                    let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                    self.path.push(dep.clone());
+                    self.children(dep, &prefix, level + 1);
+                    self.path.pop();
+                }
+            }
+        }
+    }
+
+    let mut walk = Walk {
+        graph,
+        depth,
+        expanded: HashSet::new(),
+        path: Vec::new(),
+        lines: Vec::new(),
+    };
+    for root in shown {
+        walk.lines.push((root.clone(), Note::None));
+        walk.expanded.clear();
+        walk.path = vec![root.clone()];
+        walk.children(root, "", 0);
+    }
+    walk.lines
+}
+
+/// Graphviz source for the packages reachable from `shown`. Configured packages are
+/// boxes; dependencies needed by one configured package are orange.
+fn render_dot(graph: &Graph, shown: &[String]) -> String {
+    let mut reached: BTreeSet<&String> = BTreeSet::new();
+    let mut stack: Vec<&String> = shown.iter().collect();
+    while let Some(package) = stack.pop() {
+        if !reached.insert(package) {
+            continue;
+        }
+        if let Some(deps) = graph.children.get(package) {
+            stack.extend(deps.iter().filter(|dep| !graph.roots.contains(*dep)));
+        }
+    }
+
+    let mut out = String::from("digraph deps {\n  rankdir=LR;\n");
+    for package in &reached {
+        let attrs = if graph.roots.contains(*package) {
+            " [shape=box, style=bold]"
+        } else if graph.needed_by.get(*package).is_some_and(|r| r.len() == 1) {
+            " [color=orange]"
+        } else {
+            ""
+        };
+        out.push_str(&format!("  \"{}\"{};\n", package, attrs));
+    }
+    for package in &reached {
+        for dep in graph.children.get(*package).into_iter().flatten() {
+            out.push_str(&format!("  \"{}\" -> \"{}\";\n", package, dep));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(name, deps)| {
+                (
+                    name.to_string(),
+                    deps.iter().map(ToString::to_string).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn names(items: &[&str]) -> Vec<String> {
+        items.iter().map(ToString::to_string).collect()
+    }
+
+    fn canned() -> HashMap<String, Vec<String>> {
+        map(&[
+            ("htop", &["glibc", "libnl", "ncurses"]),
+            ("tmux", &["glibc", "libevent", "ncurses"]),
+            ("libnl", &["glibc"]),
+            ("libevent", &["openssl"]),
+            ("openssl", &["glibc"]),
+            ("ncurses", &["glibc"]),
+            ("glibc", &["filesystem"]),
+            ("filesystem", &["glibc"]),
+        ])
+    }
+
+    #[test]
+    fn test_resolve_dependency_names_the_provider() {
+        let installed: HashSet<String> = names(&["openssl", "bash", "jdk21-openjdk"])
+            .into_iter()
+            .collect();
+        let provides = BTreeMap::from([
+            ("bash".to_string(), names(&["sh", "libreadline.so=8-64"])),
+            ("jdk21-openjdk".to_string(), names(&["java-runtime=21"])),
+            ("openssl".to_string(), names(&["libssl.so=3-64"])),
+            ("zsh".to_string(), names(&["sh"])),
+        ]);
+        let providers = crate::core::package::providers(&provides);
+        let resolve = |dep: &str| resolve_dependency(dep, &installed, &providers);
+        assert_eq!(resolve("openssl>=3"), "openssl");
+        assert_eq!(resolve("libssl.so=3-64"), "openssl");
+        assert_eq!(resolve("java-runtime>=17"), "jdk21-openjdk");
+        // Several providers: the first by name
+        assert_eq!(resolve("sh"), "bash");
+        assert_eq!(resolve("missing"), "missing");
+    }
+
+    #[test]
+    fn test_build_graph_attributes_shared_and_unique_dependencies() {
+        let graph = build_graph(&names(&["htop", "tmux"]), &canned(), None);
+        let owners =
+            |dep: &str| -> Vec<&str> { graph.needed_by[dep].iter().map(String::as_str).collect() };
+        assert_eq!(owners("libnl"), vec!["htop"]);
+        assert_eq!(owners("openssl"), vec!["tmux"]);
+        assert_eq!(owners("glibc"), vec!["htop", "tmux"]);
+        // glibc and filesystem depend on each other; the walk still ends
+        assert_eq!(owners("filesystem"), vec!["htop", "tmux"]);
+    }
+
+    #[test]
+    fn test_build_graph_respects_depth() {
+        let graph = build_graph(&names(&["tmux"]), &canned(), Some(1));
+        assert_eq!(
+            graph.needed_by.keys().collect::<Vec<_>>(),
+            vec!["glibc", "libevent", "ncurses"]
+        );
+    }
+
+    #[test]
+    fn test_render_tree_marks_cycles_repeats_and_configured() {
+        let mut deps = canned();
+        deps.get_mut("tmux").unwrap().push("htop".to_string());
+        let graph = build_graph(&names(&["htop", "tmux"]), &deps, None);
+        let lines = render_tree(&graph, &names(&["tmux"]), None);
+        let rendered: Vec<(&str, &Note)> = lines.iter().map(|(l, n)| (l.as_str(), n)).collect();
+        assert_eq!(
+            rendered,
+            vec![
+                ("tmux", &Note::None),
+                ("├── glibc", &Note::None),
+                ("│   └── filesystem", &Note::None),
+                ("│       └── glibc", &Note::Cycle),
+                ("├── libevent", &Note::Only("tmux".to_string())),
+                ("│   └── openssl", &Note::Only("tmux".to_string())),
+                ("│       └── glibc", &Note::Repeated),
+                ("├── ncurses", &Note::None),
+                ("│   └── glibc", &Note::Repeated),
+                ("└── htop", &Note::Configured),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_dot() {
+        let graph = build_graph(&names(&["htop"]), &map(&[("htop", &["libnl"])]), None);
+        assert_eq!(
+            render_dot(&graph, &names(&["htop"])),
+            "digraph deps {\n  rankdir=LR;\n  \"htop\" [shape=box, style=bold];\n  \"libnl\" [color=orange];\n  \"htop\" -> \"libnl\";\n}\n"
+        );
+    }
+}
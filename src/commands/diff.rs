@@ -0,0 +1,291 @@
+//! Everything an apply would change, rendered as diffs
+//!
+//! `owl diff` is strictly read-only: package state writes are forbidden for the whole
+//! run, and everything else comes from the read-only plan and direct file reads.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+use crate::core::config::Config;
+use crate::core::plan::PlanOptions;
+use crate::core::state::PackageState;
+use crate::internal::color;
+use crate::internal::diff::{colorize, unified_diff};
+use crate::internal::files::friendly_path;
+
+/// Which parts of the pending changes to show
+#[derive(Debug, Clone, Default)]
+pub struct DiffFilters {
+    /// Only changes that belong to this package
+    pub only: Option<String>,
+    pub dotfiles: bool,
+    pub packages: bool,
+}
+
+impl DiffFilters {
+    fn show_packages(&self) -> bool {
+        self.packages || !self.dotfiles
+    }
+
+    fn show_dotfiles(&self) -> bool {
+        self.dotfiles || !self.packages
+    }
+
+    /// Env files and services have no flag of their own, so either filter hides them
+    fn show_other(&self) -> bool {
+        !self.packages && !self.dotfiles
+    }
+
+    fn includes(&self, package: &str) -> bool {
+        self.only.as_deref().is_none_or(|only| only == package)
+    }
+}
+
+/// Run the diff command
+pub fn run(filters: &DiffFilters, flags: &crate::cli::handler::GlobalFlags) {
+    crate::core::state::forbid_writes();
+    let lines = match crate::internal::util::execute_with_progress(
+        {
+            let filters = filters.clone();
+            move || collect(&filters)
+        },
+        "Computing changes",
+    ) {
+        Ok(lines) => lines,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+
+    let mut out = crate::cli::ui::Pager::new(flags);
+    for line in &lines {
+        writeln!(out, "{}", line).ok();
+    }
+    out.finish();
+}
+
+fn collect(filters: &DiffFilters) -> Result<Vec<String>> {
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    let state = PackageState::load().context("Failed to load package state")?;
+    // AUR updates are queried below with versions, so the plan can skip them
+    let options = PlanOptions {
+        check_aur_updates: false,
+    };
+    let plan = crate::core::plan::build_plan(&config, &state, &options)?;
+
+    let mut out = Vec::new();
+    if filters.show_packages() {
+        section(&mut out, "packages", package_lines(&plan, &state, filters)?);
+    }
+
+    if filters.show_dotfiles() {
+        let wanted: Option<HashSet<String>> = filters.only.as_ref().map(|only| {
+            config
+                .packages
+                .get(only)
+                .map(|package| package.config.clone())
+                .unwrap_or_default()
+                .iter()
+                .map(|cfg| crate::core::dotfiles::parse_mapping(cfg).destination)
+                .collect()
+        });
+        let mut lines = Vec::new();
+        let manifest = crate::core::dotfiles::load_manifest();
+        for action in plan.dotfile_drift() {
+            if wanted
+                .as_ref()
+                .is_some_and(|wanted| !wanted.contains(&action.mapping.destination))
+            {
+                continue;
+            }
+            for (source, destination) in
+                crate::core::dotfiles::differing_files(&action.mapping, &manifest)?
+            {
+                lines.extend(file_diff(&destination, &source)?);
+            }
+        }
+        section(&mut out, "dotfiles", lines);
+    }
+
+    if filters.show_other() {
+        let env_relevant = filters.only.as_ref().is_none_or(|only| {
+            config
+                .packages
+                .get(only)
+                .is_some_and(|package| !package.env_vars.is_empty())
+        });
+        let mut lines = Vec::new();
+        if env_relevant {
+            for (path, content) in crate::core::env::render_env_files(&config)? {
+                let current = std::fs::read_to_string(&path).unwrap_or_default();
+                let label = friendly_path(&path.display().to_string());
+                lines.extend(
+                    unified_diff(&label, &label, &current, &content)
+                        .iter()
+                        .map(|line| format!("  {}", colorize(line))),
+                );
+            }
+        }
+        section(&mut out, "env", lines);
+
+        let only_service = filters
+            .only
+            .as_ref()
+            .map(|only| config.packages.get(only).and_then(|p| p.service.clone()));
+        let lines = plan
+            .services_out_of_state()
+            .filter(|service| {
+                only_service
+                    .as_ref()
+                    .is_none_or(|wanted| wanted.as_deref() == Some(service.name.as_str()))
+            })
+            .map(|service| {
+                format!(
+                    "  {}: {} -> {}",
+                    service.name,
+                    service.describe(),
+                    color::green("enabled, active")
+                )
+            })
+            .collect();
+        section(&mut out, "services", lines);
+    }
+
+    if out.is_empty() {
+        out.push(format!("  {} nothing to apply", color::green("✓")));
+    }
+    Ok(out)
+}
+
+fn section(out: &mut Vec<String>, name: &str, lines: Vec<String>) {
+    if lines.is_empty() {
+        return;
+    }
+    if !out.is_empty() {
+        out.push(String::new());
+    }
+    out.push(format!("[{}]", color::blue(name)));
+    out.extend(lines);
+}
+
+/// Added, removed, and updated packages, with versions where pacman knows them
+fn package_lines(
+    plan: &crate::core::plan::Plan,
+    state: &PackageState,
+    filters: &DiffFilters,
+) -> Result<Vec<String>> {
+    let pm = crate::core::pm::ParuPacman::new();
+    let mut updates = pm.repo_updates()?;
+    if crate::core::pm::aur_helper_command().is_some() {
+        updates.extend(pm.aur_updates()?);
+    }
+    let removals: Vec<String> = plan
+        .removals
+        .iter()
+        .filter(|name| filters.includes(name))
+        .cloned()
+        .collect();
+    let installed = crate::core::package::get_package_info(&removals)?;
+
+    let mut lines: Vec<String> = plan
+        .installs
+        .iter()
+        .filter(|name| filters.includes(name))
+        .map(|name| format!("  {}", color::green(&format!("+ {}", name))))
+        .collect();
+    for name in &removals {
+        let version = installed
+            .get(name)
+            .map(|info| format!("  {}", info.version))
+            .unwrap_or_default();
+        lines.push(format!(
+            "  {}{}",
+            color::red(&format!("- {}", name)),
+            version
+        ));
+    }
+    updates.sort_by(|a, b| a.name.cmp(&b.name));
+    for update in updates
+        .iter()
+        .filter(|update| filters.includes(&update.name))
+    {
+        if state.pinned_version(&update.name).is_some() {
+            lines.push(format!(
+                "  {}  {} (pinned, {} available)",
+                color::dim(&format!("= {}", update.name)),
+                update.from,
+                update.to
+            ));
+            continue;
+        }
+        lines.push(format!(
+            "  {}  {} -> {}",
+            color::yellow(&format!("~ {}", update.name)),
+            update.from,
+            update.to
+        ));
+    }
+    Ok(lines)
+}
+
+/// Diff of what an apply would do to `destination`, given the source it copies from.
+/// Either file may be missing.
+fn file_diff(destination: &Path, source: &Path) -> Result<Vec<String>> {
+    let read = |path: &Path| -> Result<Option<Vec<u8>>> {
+        if path.exists() {
+            std::fs::read(path)
+                .map(Some)
+                .with_context(|| format!("Failed to read {}", path.display()))
+        } else {
+            Ok(None)
+        }
+    };
+    let (old, new) = (read(destination)?, read(source)?);
+    let label = |path: &Path, exists: bool| {
+        if exists {
+            friendly_path(&path.display().to_string())
+        } else {
+            "/dev/null".to_string()
+        }
+    };
+    let (old_label, new_label) = (
+        label(destination, old.is_some()),
+        label(source, new.is_some()),
+    );
+
+    let old = String::from_utf8(old.unwrap_or_default());
+    let new = String::from_utf8(new.unwrap_or_default());
+    match (old, new) {
+        (Ok(old), Ok(new)) => Ok(unified_diff(&old_label, &new_label, &old, &new)
+            .iter()
+            .map(|line| format!("  {}", colorize(line)))
+            .collect()),
+        _ => Ok(vec![format!(
+            "  Binary files {} and {} differ",
+            old_label, new_label
+        )]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters_pick_sections() {
+        let all = DiffFilters::default();
+        assert!(all.show_packages() && all.show_dotfiles() && all.show_other());
+
+        let dotfiles = DiffFilters {
+            dotfiles: true,
+            ..DiffFilters::default()
+        };
+        assert!(!dotfiles.show_packages() && dotfiles.show_dotfiles() && !dotfiles.show_other());
+
+        let only = DiffFilters {
+            only: Some("htop".to_string()),
+            ..DiffFilters::default()
+        };
+        assert!(only.includes("htop") && !only.includes("git"));
+    }
+}
@@ -0,0 +1,665 @@
+//! Environment and consistency checks, with safe fixes behind `--fix`
+
+use anyhow::Result;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::core::config::Config;
+use crate::core::state::{PackageState, StateError};
+use crate::internal::color;
+use crate::internal::files::friendly_path;
+use crate::internal::logging::LogCommand;
+
+/// Free space below this is worth a warning
+const LOW_SPACE: u64 = 5 * 1024 * 1024 * 1024;
+/// Free space below this makes upgrades likely to fail
+const CRITICAL_SPACE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Level {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// A remediation that `--fix` may apply without risking user data
+#[derive(Debug, Clone, PartialEq)]
+enum Fix {
+    /// Move an unreadable state file aside so owl recreates it with defaults
+    MoveAside(PathBuf),
+    /// Drop managed entries for packages that are neither installed nor configured
+    ForgetManaged(Vec<String>),
+    /// Mark managed packages that pacman lists as dependencies as explicitly installed
+    MarkExplicit(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+struct Check {
+    name: &'static str,
+    level: Level,
+    detail: String,
+    hint: Option<String>,
+    fix: Option<Fix>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            level: Level::Pass,
+            detail: detail.into(),
+            hint: None,
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            level: Level::Warn,
+            hint: Some(hint.into()),
+            ..Self::pass(name, detail)
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            level: Level::Fail,
+            ..Self::warn(name, detail, hint)
+        }
+    }
+
+    fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+/// Run the doctor command; exits non-zero when a check fails and was not fixed
+pub fn run(fix: bool, flags: &crate::cli::handler::GlobalFlags) {
+    if !fix {
+        crate::core::state::forbid_writes();
+    }
+    let checks = match crate::internal::util::execute_with_progress(run_checks, "Running checks") {
+        Ok(checks) => checks,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+
+    println!("[{}]", color::blue("doctor"));
+    for check in &checks {
+        print_check(check, fix);
+    }
+
+    let count = |level: Level| checks.iter().filter(|check| check.level == level).count();
+    println!();
+    let warnings = count(Level::Warn);
+    println!(
+        "  {} passed, {} warning{}, {} failed",
+        count(Level::Pass),
+        warnings,
+        if warnings == 1 { "" } else { "s" },
+        count(Level::Fail)
+    );
+
+    let mut unresolved = count(Level::Fail);
+    if fix {
+        let fixes: Vec<&Check> = checks.iter().filter(|check| check.fix.is_some()).collect();
+        if !fixes.is_empty() {
+            println!();
+            println!("[{}]", color::blue("fix"));
+        }
+        for check in fixes {
+            let Some(remedy) = &check.fix else {
+                continue;
+            };
+            if flags.dry_run {
+                println!("  {} would {}", color::blue("info:"), describe_fix(remedy));
+                continue;
+            }
+            match apply_fix(remedy) {
+                Ok(()) => {
+                    println!("  {} {}", color::green("✓"), describe_fix(remedy));
+                    if check.level == Level::Fail {
+                        unresolved -= 1;
+                    }
+                }
+                Err(err) => println!("  {} {}: {:#}", color::red("✗"), describe_fix(remedy), err),
+            }
+        }
+    }
+
+    if unresolved > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn print_check(check: &Check, fix: bool) {
+    let symbol = match check.level {
+        Level::Pass => color::green("✓"),
+        Level::Warn => color::yellow("!"),
+        Level::Fail => color::red("✗"),
+    };
+    println!("  {} {:<16} {}", symbol, check.name, check.detail);
+    if let Some(hint) = &check.hint {
+        let fixable = if check.fix.is_some() && !fix {
+            " (`owl doctor --fix` can do this)"
+        } else {
+            ""
+        };
+        println!(
+            "      {}",
+            color::dim(&format!("hint: {}{}", hint, fixable))
+        );
+    }
+}
+
+fn run_checks() -> Result<Vec<Check>> {
+    let mut checks = Vec::new();
+
+    let pacman_ok = command_works("pacman", &["--version"]);
+    checks.push(if pacman_ok {
+        Check::pass("pacman", "installed and working")
+    } else {
+        Check::fail(
+            "pacman",
+            "not found or not working",
+            "owl drives pacman; run it on an Arch-based system with pacman on PATH",
+        )
+    });
+
+    checks.push(match crate::core::pm::aur_helper_command() {
+        Some(helper) => Check::pass("aur helper", helper),
+        None => Check::warn(
+            "aur helper",
+            "neither paru nor yay found",
+            "install paru or yay to manage AUR packages",
+        ),
+    });
+
+    checks.push(privilege_check());
+
+    let owl_root = crate::internal::files::owl_dir()?;
+    checks.push(if !owl_root.is_dir() {
+        Check::fail(
+            "owl directory",
+            format!(
+                "{} does not exist",
+                friendly_path(&owl_root.display().to_string())
+            ),
+            "run `owl init` to create it",
+        )
+    } else {
+        match probe_writable(&owl_root) {
+            Ok(()) => Check::pass(
+                "owl directory",
+                friendly_path(&owl_root.display().to_string()),
+            ),
+            Err(err) => Check::fail(
+                "owl directory",
+                format!("not writable: {}", err),
+                format!("check the ownership of {}", owl_root.display()),
+            ),
+        }
+    });
+
+    let state = match PackageState::load() {
+        Ok(state) => {
+            checks.push(state_file_check(&state));
+            Some(state)
+        }
+        Err(err) => {
+            let check = Check::fail(
+                "state file",
+                format!("{:#}", err),
+                "move the broken file aside; owl then recreates it with defaults",
+            );
+            checks.push(match err.downcast_ref::<StateError>() {
+                Some(state_error) => check.with_fix(Fix::MoveAside(state_error.path.clone())),
+                None => check,
+            });
+            None
+        }
+    };
+
+    let config = match Config::load_all_relevant_config_files() {
+        Ok(config) => {
+            checks.push(Check::pass(
+                "config",
+                format!(
+                    "{} packages, {} groups",
+                    config.packages.len(),
+                    config.groups.len()
+                ),
+            ));
+            Some(config)
+        }
+        Err(err) => {
+            checks.push(Check::fail(
+                "config",
+                format!("{:#}", err),
+                "`owl config-check` shows every file in the chain",
+            ));
+            None
+        }
+    };
+
+    if let Some(config) = &config {
+        let conflicts = conflicting_destinations(config);
+        checks.push(if conflicts.is_empty() {
+            Check::pass("dotfiles", "no conflicting destinations")
+        } else {
+            Check::fail(
+                "dotfiles",
+                conflicts
+                    .iter()
+                    .map(|(destination, packages)| {
+                        format!("{} (from {})", destination, packages.join(", "))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("; "),
+                "keep a single `:config` line per destination",
+            )
+        });
+    }
+
+    if pacman_ok {
+        let installed = crate::core::package::get_installed_packages()?;
+        if let (Some(config), Some(state)) = (&config, &state) {
+            checks.extend(consistency_checks(config, state, &installed)?);
+        }
+        if let Some(config) = &config {
+            checks.push(unknown_packages_check(config, &installed)?);
+        }
+    }
+
+    let has_services = config
+        .as_ref()
+        .is_some_and(|config| !crate::core::services::get_configured_services(config).is_empty());
+    checks.push(if command_works("systemctl", &["--version"]) {
+        Check::pass("systemd", "systemctl available")
+    } else if has_services {
+        Check::fail(
+            "systemd",
+            "systemctl not found, but the config declares services",
+            "`:service` entries need systemd",
+        )
+    } else {
+        Check::warn(
+            "systemd",
+            "systemctl not found",
+            "`:service` entries will not work without systemd",
+        )
+    });
+
+    checks.extend(disk_checks());
+    Ok(checks)
+}
+
+fn privilege_check() -> Check {
+    let sudo = Command::new("sudo")
+        .args(["-n", "true"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .logged()
+        .status();
+    match sudo {
+        Ok(status) if status.success() => Check::pass("privileges", "sudo works without a prompt"),
+        Ok(_) => Check::pass("privileges", "sudo available (asks for a password)"),
+        Err(_) if command_works("doas", &["true"]) || Path::new("/usr/bin/doas").exists() => {
+            Check::warn(
+                "privileges",
+                "only doas found",
+                "owl runs services and pacman -D through sudo; install sudo or provide a sudo wrapper",
+            )
+        }
+        Err(_) => Check::fail(
+            "privileges",
+            "neither sudo nor doas found",
+            "install sudo so owl can manage services",
+        ),
+    }
+}
+
+fn state_file_check(state: &PackageState) -> Check {
+    let detail = format!(
+        "{} managed, {} untracked, {} hidden",
+        state.managed.len(),
+        state.untracked.len(),
+        state.hidden.len()
+    );
+    let Ok(location) = PackageState::location() else {
+        return Check::pass("state file", detail);
+    };
+    let writable = if location.is_dir() {
+        probe_writable(&location)
+    } else if location.exists() {
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&location)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    } else {
+        Ok(())
+    };
+    match writable {
+        Ok(()) => Check::pass("state file", detail),
+        Err(err) => Check::fail(
+            "state file",
+            format!(
+                "{} is not writable: {}",
+                friendly_path(&location.display().to_string()),
+                err
+            ),
+            format!("check the ownership of {}", location.display()),
+        ),
+    }
+}
+
+/// Managed packages that pacman no longer has or installed as a dependency
+fn consistency_checks(
+    config: &Config,
+    state: &PackageState,
+    installed: &HashSet<String>,
+) -> Result<Vec<Check>> {
+    let stale: Vec<String> = state
+        .managed
+        .iter()
+        .filter(|name| !installed.contains(*name) && !config.packages.contains_key(*name))
+        .cloned()
+        .collect();
+    let managed_installed: Vec<String> = state
+        .managed
+        .iter()
+        .filter(|name| installed.contains(*name))
+        .cloned()
+        .collect();
+    let mut as_dependency: Vec<String> =
+        crate::core::package::get_package_info(&managed_installed)?
+            .into_values()
+            .filter(|info| !info.explicit)
+            .map(|info| info.name)
+            .collect();
+    as_dependency.sort();
+
+    let mut checks = Vec::new();
+    checks.push(if stale.is_empty() {
+        Check::pass("managed state", "matches the pacman database")
+    } else {
+        Check::warn(
+            "managed state",
+            format!(
+                "managed but gone from pacman and the config: {}",
+                stale.join(", ")
+            ),
+            "drop them from the managed list",
+        )
+        .with_fix(Fix::ForgetManaged(stale))
+    });
+    checks.push(if as_dependency.is_empty() {
+        Check::pass(
+            "install reasons",
+            "managed packages are explicitly installed",
+        )
+    } else {
+        Check::warn(
+            "install reasons",
+            format!(
+                "managed but installed as dependencies: {}",
+                as_dependency.join(", ")
+            ),
+            "mark them explicit so `pacman -Rns` of another package cannot take them along",
+        )
+        .with_fix(Fix::MarkExplicit(as_dependency))
+    });
+    Ok(checks)
+}
+
+/// Configured packages that are neither installed, in a sync db, a group, nor in the AUR
+fn unknown_packages_check(config: &Config, installed: &HashSet<String>) -> Result<Check> {
+    let mut candidates: Vec<String> = config
+        .packages
+        .keys()
+        .filter(|name| !installed.contains(*name))
+        .cloned()
+        .collect();
+    candidates.sort();
+    let pm = crate::core::pm::ParuPacman::new();
+    let in_repos = pm.batch_repo_available(&candidates)?;
+    let mut unknown = Vec::new();
+    for name in candidates
+        .into_iter()
+        .filter(|name| !in_repos.contains(name))
+    {
+        if !pm.is_package_group(&name)? {
+            unknown.push(name);
+        }
+    }
+    if unknown.is_empty() {
+        return Ok(Check::pass("packages", "every configured package exists"));
+    }
+
+    match crate::core::search::aur_info_batch(&unknown) {
+        Ok(found) => {
+            unknown.retain(|name| !found.contains_key(name));
+            Ok(if unknown.is_empty() {
+                Check::pass("packages", "every configured package exists")
+            } else {
+                Check::fail(
+                    "packages",
+                    format!("not in the repos or the AUR: {}", unknown.join(", ")),
+                    "check the spelling; `owl search` finds the right name",
+                )
+            })
+        }
+        Err(err) => Ok(Check::warn(
+            "packages",
+            format!(
+                "could not ask the AUR about {} ({:#})",
+                unknown.join(", "),
+                err
+            ),
+            "check the network connection and run doctor again",
+        )),
+    }
+}
+
+fn disk_checks() -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut seen_mounts = HashSet::new();
+    for (name, path) in [
+        ("disk /", "/"),
+        ("disk cache", crate::internal::constants::PACMAN_CACHE_DIR),
+    ] {
+        if !Path::new(path).exists() {
+            continue;
+        }
+        let Some(usage) = disk_usage(path) else {
+            checks.push(Check::warn(
+                name,
+                format!("could not read free space for {}", path),
+                "make sure `df` is installed",
+            ));
+            continue;
+        };
+        if !seen_mounts.insert(usage.mount.clone()) {
+            continue;
+        }
+        let detail = format!(
+            "{} free on {}",
+            crate::cli::ui::format_size(usage.available),
+            usage.mount
+        );
+        checks.push(if usage.available < CRITICAL_SPACE {
+            Check::fail(
+                name,
+                detail,
+                "free up space; `paccache -r` trims the package cache",
+            )
+        } else if usage.available < LOW_SPACE {
+            Check::warn(
+                name,
+                detail,
+                "upgrades may run out of space; `paccache -r` helps",
+            )
+        } else {
+            Check::pass(name, detail)
+        });
+    }
+    checks
+}
+
+#[derive(Debug, PartialEq)]
+struct DiskUsage {
+    available: u64,
+    mount: String,
+}
+
+fn disk_usage(path: &str) -> Option<DiskUsage> {
+    let output = Command::new("df")
+        .args(["-Pk", path])
+        .logged()
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    parse_df(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse `df -Pk` output: a header, then `fs blocks used available capacity mount`
+fn parse_df(output: &str) -> Option<DiskUsage> {
+    let line = output.lines().nth(1)?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 6 {
+        return None;
+    }
+    Some(DiskUsage {
+        available: fields[3].parse::<u64>().ok()? * 1024,
+        mount: fields[5..].join(" "),
+    })
+}
+
+/// Destinations written by more than one `:config` line, with the packages involved
+fn conflicting_destinations(config: &Config) -> Vec<(String, Vec<String>)> {
+    let mut owners: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, package) in &config.packages {
+        for cfg in &package.config {
+            let mapping = crate::core::dotfiles::parse_mapping(cfg);
+            owners
+                .entry(mapping.destination.trim_end_matches('/').to_string())
+                .or_default()
+                .push(name.clone());
+        }
+    }
+    owners
+        .into_iter()
+        .filter(|(_, packages)| packages.len() > 1)
+        .map(|(destination, mut packages)| {
+            packages.sort();
+            (destination, packages)
+        })
+        .collect()
+}
+
+fn command_works(command: &str, args: &[&str]) -> bool {
+    Command::new(command)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .logged()
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Create and remove a scratch file to see whether `dir` accepts writes
+fn probe_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(".owl-doctor-probe");
+    std::fs::write(&probe, b"").map_err(|e| e.to_string())?;
+    std::fs::remove_file(&probe).map_err(|e| e.to_string())
+}
+
+fn describe_fix(fix: &Fix) -> String {
+    match fix {
+        Fix::MoveAside(path) => format!(
+            "move {} to {}",
+            friendly_path(&path.display().to_string()),
+            friendly_path(&aside_path(path).display().to_string())
+        ),
+        Fix::ForgetManaged(packages) => {
+            format!("drop {} from the managed list", packages.join(", "))
+        }
+        Fix::MarkExplicit(packages) => {
+            format!("mark {} as explicitly installed", packages.join(", "))
+        }
+    }
+}
+
+fn apply_fix(fix: &Fix) -> Result<()> {
+    match fix {
+        Fix::MoveAside(path) => std::fs::rename(path, aside_path(path))
+            .map_err(|e| anyhow::anyhow!("Failed to move {}: {}", path.display(), e)),
+        Fix::ForgetManaged(packages) => {
+            let mut state = PackageState::load()?;
+            for package in packages {
+                state.remove_managed(package);
+            }
+            state.save()
+        }
+        Fix::MarkExplicit(packages) => crate::core::pm::ParuPacman::new().mark_explicit(packages),
+    }
+}
+
+fn aside_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".corrupt");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::Package;
+
+    #[test]
+    fn test_parse_df() {
+        let output = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+                      /dev/nvme0n1p2   479597248 312020764 143149332      69% /\n";
+        assert_eq!(
+            parse_df(output),
+            Some(DiskUsage {
+                available: 143_149_332 * 1024,
+                mount: "/".to_string(),
+            })
+        );
+        assert_eq!(parse_df("Filesystem 1024-blocks\n"), None);
+    }
+
+    #[test]
+    fn test_conflicting_destinations() {
+        let package = |configs: &[&str]| Package {
+            config: configs.iter().map(|cfg| cfg.to_string()).collect(),
+            service: None,
+            env_vars: Default::default(),
+            exact: false,
+            hooks: Default::default(),
+        };
+        let mut config = Config::new();
+        config
+            .packages
+            .insert("neovim".to_string(), package(&["nvim -> ~/.config/nvim"]));
+        config
+            .packages
+            .insert("lazyvim".to_string(), package(&["lazy -> ~/.config/nvim/"]));
+        config
+            .packages
+            .insert("kitty".to_string(), package(&["kitty -> ~/.config/kitty"]));
+
+        assert_eq!(
+            conflicting_destinations(&config),
+            vec![(
+                "~/.config/nvim".to_string(),
+                vec!["lazyvim".to_string(), "neovim".to_string()]
+            )]
+        );
+    }
+}
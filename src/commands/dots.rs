@@ -12,13 +12,7 @@ pub fn run(flags: &crate::cli::handler::GlobalFlags) {
     // Load configuration
     let config = match crate::core::config::Config::load_all_relevant_config_files() {
         Ok(config) => config,
-        Err(err) => {
-            eprintln!(
-                "{}",
-                crate::internal::color::red(&format!("Failed to load config: {}", err))
-            );
-            std::process::exit(1);
-        }
+        Err(err) => crate::error::exit_with_error(err.context("Failed to load config")),
     };
 
     // Get dotfile mappings from config
@@ -54,6 +48,9 @@ pub fn run(flags: &crate::cli::handler::GlobalFlags) {
             crate::internal::color::green("➔"),
             mappings.len()
         );
+        if !dry_run && let Err(err) = crate::core::dotfiles::record_unrecorded(&mappings) {
+            log::warn!("dotfiles: could not record deployed files: {:#}", err);
+        }
         return;
     }
 
@@ -69,5 +66,11 @@ pub fn run(flags: &crate::cli::handler::GlobalFlags) {
         }
     };
 
-    crate::core::dotfiles::print_actions(&actions, dry_run);
+    if dry_run {
+        let mut out = crate::cli::ui::Pager::new(flags);
+        crate::core::dotfiles::print_actions(&actions, dry_run, &mut out);
+        out.finish();
+    } else {
+        crate::core::dotfiles::print_actions(&actions, dry_run, &mut std::io::stdout());
+    }
 }
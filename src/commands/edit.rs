@@ -1,26 +1,133 @@
+//! Open config files and dotfiles in the user's editor
+
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+
+use crate::core::config::Config;
+use crate::internal::color;
+use crate::internal::constants;
 use crate::internal::files;
-use anyhow::{Result, anyhow};
 
-/// Run the edit command to open files in editor
-pub fn run(typ: &str, arg: &str) -> Result<()> {
-    if arg.is_empty() {
-        return Err(anyhow!("edit command requires a non-empty argument"));
+/// Run the edit command
+///
+/// - no target: the main config
+/// - `dots <file>` / `config <name>`: a dotfile or a config file by name
+/// - `<pkg>`: the file and line that declare the package, else a config file by that name
+/// - `--new <name>`: a new `groups/<name>.owl` fragment
+pub fn run(target: Option<&str>, argument: Option<&str>, new: Option<&str>) {
+    crate::error::exit_on_error(edit(target, argument, new));
+}
+
+fn edit(target: Option<&str>, argument: Option<&str>, new: Option<&str>) -> Result<()> {
+    if let Some(name) = new {
+        let path = create_fragment(name)?;
+        edit_config(&path, None)?;
+        println!(
+            "  {} add `@group {}` to a config file to use it",
+            color::blue("info:"),
+            name.trim_end_matches(constants::OWL_EXT)
+        );
+        return Ok(());
     }
 
-    match typ {
-        crate::internal::constants::EDIT_TYPE_DOTS => {
-            let path = files::get_dotfile_path(arg)?;
-            files::open_editor(&path)
+    match (target, argument) {
+        (None, _) => edit_config(Path::new(&files::get_main_config_path()?), None),
+        (Some(constants::EDIT_TYPE_DOTS), Some(file)) => {
+            files::open_editor(&files::get_dotfile_path(file)?)
         }
-        crate::internal::constants::EDIT_TYPE_CONFIG => {
-            let path = files::find_config_file(arg)?;
-            files::open_editor(&path)
+        (Some(constants::EDIT_TYPE_CONFIG), Some(name)) => {
+            edit_config(Path::new(&files::find_config_file(name)?), None)
         }
-        _ => Err(anyhow!(
-            "invalid edit type '{}'. Must be '{}' or '{}'",
-            typ,
-            crate::internal::constants::EDIT_TYPE_DOTS,
-            crate::internal::constants::EDIT_TYPE_CONFIG
+        (Some(target), Some(_)) => Err(anyhow!(
+            "unexpected second argument after '{}'; only `owl edit {} <file>` and `owl edit {} <name>` take one",
+            target,
+            constants::EDIT_TYPE_DOTS,
+            constants::EDIT_TYPE_CONFIG
         )),
+        (Some(target), None) => {
+            // A broken config should not stop `owl edit <file>` from opening the file to fix it
+            let declared = match Config::load_all_relevant_config_files() {
+                Ok(config) => config.provenance.get(target).cloned(),
+                Err(err) => {
+                    log::debug!("config does not load, not looking up {}: {:#}", target, err);
+                    None
+                }
+            };
+            if let Some(provenance) = declared
+                && let Some(path) = provenance.path
+            {
+                return edit_config(&path, Some(provenance.line));
+            }
+            match files::find_config_file(target) {
+                Ok(path) => edit_config(Path::new(&path), None),
+                Err(_) => Err(anyhow!(
+                    "'{}' is neither a configured package nor a config file",
+                    target
+                )),
+            }
+        }
+    }
+}
+
+/// Open a config file, then re-parse it so syntax errors show up before the next apply
+fn edit_config(path: &Path, line: Option<usize>) -> Result<()> {
+    let display = path.display().to_string();
+    files::open_editor_at(&display, line)?;
+    if !path.exists() {
+        return Ok(());
+    }
+    Config::parse_file(path)
+        .with_context(|| format!("{} has errors", files::friendly_path(&display)))?;
+    println!(
+        "  {} {} parses cleanly",
+        color::green("✓"),
+        files::friendly_path(&display)
+    );
+    Ok(())
+}
+
+/// Create `groups/<name>.owl` with a short header
+fn create_fragment(name: &str) -> Result<PathBuf> {
+    let name = name.trim_end_matches(constants::OWL_EXT);
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(anyhow!(
+            "invalid group name '{}'; use a plain file name such as `gaming`",
+            name
+        ));
+    }
+    let groups = files::owl_dir()?.join(constants::GROUPS_DIR);
+    let path = groups.join(format!("{}{}", name, constants::OWL_EXT));
+    if path.exists() {
+        return Err(anyhow!(
+            "{} already exists; open it with `owl edit {} {}`",
+            files::friendly_path(&path.display().to_string()),
+            constants::EDIT_TYPE_CONFIG,
+            name
+        ));
+    }
+    std::fs::create_dir_all(&groups)
+        .with_context(|| format!("Failed to create {}", groups.display()))?;
+    std::fs::write(&path, fragment_template(name))
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    println!(
+        "  {} created {}",
+        color::green("✓"),
+        files::friendly_path(&path.display().to_string())
+    );
+    Ok(path)
+}
+
+fn fragment_template(name: &str) -> String {
+    format!("# {name}.owl - pulled in by `@group {name}` in main.owl or a host file\n\n@packages\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragment_template_parses() {
+        let config = Config::parse(&fragment_template("gaming")).unwrap();
+        assert!(config.packages.is_empty());
     }
 }
@@ -0,0 +1,36 @@
+//! Run a command, or the current shell, with the configured environment applied
+
+use anyhow::{Context, Result, anyhow};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::core::config::Config;
+use crate::core::env;
+use crate::internal::logging::LogCommand;
+
+/// Run the exec command: replace owl with `command`, or print the export lines
+pub fn run(command: &[String], print: bool) {
+    crate::core::state::forbid_writes();
+    crate::error::exit_on_error(exec(command, print));
+}
+
+fn exec(command: &[String], print: bool) -> Result<()> {
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    let vars = env::collect_all_env_vars(&config);
+    if print {
+        // The same lines apply writes to the env file, so eval expands them the same way
+        print!("{}", env::render_bash(&vars));
+        return Ok(());
+    }
+
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("No command given; pass one after `--`"))?;
+    let resolved = env::resolve_env_vars(&vars, |name| std::env::var(name).ok());
+    let err = Command::new(program)
+        .args(args)
+        .envs(resolved)
+        .logged()
+        .exec();
+    Err(err).with_context(|| format!("Failed to run {}", program))
+}
@@ -0,0 +1,183 @@
+//! Write the configured package set in formats other tools read
+//!
+//! Output is sorted by name so exported files diff cleanly in git. `pkglist` is what
+//! `pacman -S --needed - < file` and `owl migrate-from pkglist` both read.
+
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::cli::context::Context;
+use crate::core::config::Config;
+use crate::internal::constants;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// One package name per line
+    Pkglist,
+    /// An array of objects with the name, declaring file, and group
+    Json,
+    /// Like pkglist, restricted to packages that come from the AUR
+    AurList,
+}
+
+/// Which packages `owl export` writes; every set filter must match
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilters {
+    pub host: Option<String>,
+    /// Only packages declared in these group files
+    pub groups: Vec<String>,
+    /// Leave out packages declared in these group files
+    pub exclude_groups: Vec<String>,
+    pub installed_only: bool,
+}
+
+/// One exported package
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Exported {
+    pub name: String,
+    /// Declaring file, relative to the owl directory
+    pub source: Option<String>,
+    /// The group file that declared it, e.g. `dev` for `groups/dev.owl`
+    pub group: Option<String>,
+}
+
+/// Run the export command
+pub fn run(format: Format, filters: &ExportFilters, ctx: &Context) {
+    crate::error::exit_on_error(export(format, filters, ctx));
+}
+
+fn export(format: Format, filters: &ExportFilters, ctx: &Context) -> Result<()> {
+    let owl_root = crate::internal::files::owl_dir()?;
+    let host_config;
+    let config = match &filters.host {
+        Some(host) => {
+            host_config =
+                Config::load_for_host(&owl_root, host).context("Failed to load config")?;
+            &host_config
+        }
+        None => ctx.config()?,
+    };
+
+    let mut packages = select(config, &owl_root, filters);
+    if filters.installed_only {
+        let installed = ctx.installed()?;
+        packages.retain(|package| installed.contains(&package.name));
+    }
+    if format == Format::AurList {
+        let names: Vec<String> = packages
+            .iter()
+            .map(|package| package.name.clone())
+            .collect();
+        let aur: HashSet<String> = crate::core::package::categorize_packages(&names)?
+            .aur
+            .into_iter()
+            .collect();
+        packages.retain(|package| aur.contains(&package.name));
+    }
+    print!("{}", render(format, &packages)?);
+    Ok(())
+}
+
+/// The group a package declared in `relative` belongs to, if it is a group file
+fn group_of(relative: &Path) -> Option<String> {
+    let name = relative
+        .strip_prefix(constants::GROUPS_DIR)
+        .ok()?
+        .to_str()?
+        .strip_suffix(constants::OWL_EXT)?;
+    Some(name.to_string())
+}
+
+/// Configured packages that pass the group filters, sorted by name
+fn select(config: &Config, owl_root: &Path, filters: &ExportFilters) -> Vec<Exported> {
+    let mut packages: Vec<Exported> = config
+        .packages
+        .keys()
+        .map(|name| {
+            let relative = config
+                .provenance
+                .get(name)
+                .and_then(|provenance| provenance.path.as_deref())
+                .map(|path| path.strip_prefix(owl_root).unwrap_or(path));
+            Exported {
+                name: name.clone(),
+                source: relative.map(|path| path.display().to_string()),
+                group: relative.and_then(group_of),
+            }
+        })
+        .filter(|package| {
+            let group = package.group.as_ref();
+            (filters.groups.is_empty() || group.is_some_and(|g| filters.groups.contains(g)))
+                && !group.is_some_and(|g| filters.exclude_groups.contains(g))
+        })
+        .collect();
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    packages
+}
+
+fn render(format: Format, packages: &[Exported]) -> Result<String> {
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(packages)? + "\n",
+        Format::Pkglist | Format::AurList => packages
+            .iter()
+            .map(|package| format!("{}\n", package.name))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn names(packages: &[Exported]) -> Vec<&str> {
+        packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_select_by_group_and_round_trip_through_migrate() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("groups")).unwrap();
+        fs::write(
+            root.join("main.owl"),
+            "@group dev\n@group games\n@packages\nhtop\n",
+        )
+        .unwrap();
+        fs::write(root.join("groups/dev.owl"), "@packages\nripgrep\nneovim\n").unwrap();
+        fs::write(root.join("groups/games.owl"), "@packages\nsteam\n").unwrap();
+        let config = Config::load_for_host(root, "export-test-host").unwrap();
+
+        let all = select(&config, root, &ExportFilters::default());
+        assert_eq!(names(&all), ["htop", "neovim", "ripgrep", "steam"]);
+        assert_eq!(all[0].source.as_deref(), Some("main.owl"));
+        assert_eq!(all[0].group, None);
+        assert_eq!(all[1].group.as_deref(), Some("dev"));
+
+        let dev = ExportFilters {
+            groups: vec!["dev".to_string()],
+            ..ExportFilters::default()
+        };
+        assert_eq!(names(&select(&config, root, &dev)), ["neovim", "ripgrep"]);
+        let no_games = ExportFilters {
+            exclude_groups: vec!["games".to_string()],
+            ..ExportFilters::default()
+        };
+        assert_eq!(
+            names(&select(&config, root, &no_games)),
+            ["htop", "neovim", "ripgrep"]
+        );
+
+        let pkglist = render(Format::Pkglist, &all).unwrap();
+        assert_eq!(pkglist, "htop\nneovim\nripgrep\nsteam\n");
+        assert_eq!(
+            crate::commands::migrate::parse_pkglist(&pkglist),
+            names(&all)
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use crate::error::exit_on_error;
 use anyhow::Result;
+use std::io::Write;
 
 /// Run the find command to find where packages are defined in config files
-pub fn run(query: &[String]) {
+pub fn run(query: &[String], flags: &crate::cli::handler::GlobalFlags) {
     if query.is_empty() {
         eprintln!(
             "{}",
@@ -29,7 +30,9 @@ pub fn run(query: &[String]) {
                     crate::internal::color::yellow("No matches found for the given query")
                 );
             } else {
-                display_locations(&locations);
+                let mut out = crate::cli::ui::Pager::new(flags);
+                display_locations(&mut out, &locations);
+                out.finish();
             }
         }
         Err(err) => {
@@ -255,7 +258,7 @@ fn find_config_syntax_in_file(
                 }
             }
             // Check for packages in sections
-            else if (directive == "@packages" || directive == "@pkgs")
+            else if (directive == "@packages" || directive == "@pkgs" || directive == "@aur")
                 && trimmed == *value
                 && is_in_packages_section(content, line_num)
             {
@@ -272,7 +275,7 @@ fn find_config_syntax_in_file(
     Ok(locations)
 }
 
-/// Check if a line is within a @packages or @pkgs section
+/// Check if a line is within a @packages, @pkgs, or @aur section
 fn is_in_packages_section(content: &str, line_num: usize) -> bool {
     let lines: Vec<&str> = content.lines().collect();
     let mut in_section = false;
@@ -283,9 +286,9 @@ fn is_in_packages_section(content: &str, line_num: usize) -> bool {
         }
 
         let trimmed = line.trim();
-        if trimmed == "@packages" || trimmed == "@pkgs" {
+        if matches!(trimmed, "@packages" | "@pkgs" | "@aur") {
             in_section = true;
-        } else if trimmed.starts_with('@') && trimmed != "@packages" && trimmed != "@pkgs" {
+        } else if trimmed.starts_with('@') {
             in_section = false;
         }
     }
@@ -299,7 +302,7 @@ fn get_all_config_files() -> Result<Vec<String>> {
 }
 
 /// Display the found locations in a formatted way
-fn display_locations(locations: &[Location]) {
+fn display_locations(out: &mut impl Write, locations: &[Location]) {
     if locations.is_empty() {
         return;
     }
@@ -314,15 +317,17 @@ fn display_locations(locations: &[Location]) {
             .push(location);
     }
 
-    println!(
+    writeln!(
+        out,
         "\n{} {} location(s):\n",
         crate::internal::color::bold("Found"),
         locations.len()
-    );
+    )
+    .ok();
 
     for (file_path, file_locations) in file_groups {
         let friendly_path = file_path.replace(&std::env::var("HOME").unwrap_or_default(), "~");
-        println!("{}", crate::internal::color::highlight(&friendly_path));
+        writeln!(out, "{}", crate::internal::color::highlight(&friendly_path)).ok();
 
         for location in file_locations {
             let context_indicator = match location.context {
@@ -336,14 +341,16 @@ fn display_locations(locations: &[Location]) {
                 LocationContext::GroupDeclaration => crate::internal::color::success("[group]"),
             };
 
-            println!(
+            writeln!(
+                out,
                 "  {} {}: {}",
                 context_indicator,
                 crate::internal::color::dim(&format!("line {}", location.line_number)),
                 crate::internal::color::description(&location.line_content)
-            );
+            )
+            .ok();
         }
-        println!();
+        writeln!(out).ok();
     }
 }
 
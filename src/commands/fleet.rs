@@ -0,0 +1,139 @@
+//! Apply the config on the machines in `[fleet]` and summarize them together
+
+use anyhow::{Result, anyhow};
+
+use crate::cli::handler::GlobalFlags;
+use crate::cli::porcelain::{Event, Summary};
+use crate::core::fleet::{self, Synced};
+use crate::internal::color;
+
+/// Run `owl fleet apply`
+pub fn run_apply(hosts: &[String], tags: &[String], flags: &GlobalFlags) {
+    crate::error::exit_on_error(apply(hosts, tags, flags.dry_run));
+}
+
+fn apply(names: &[String], tags: &[String], dry_run: bool) -> Result<()> {
+    let settings = crate::core::settings::get();
+    let hosts = fleet::select(&settings.fleet.hosts, names, tags)?;
+    let local = crate::internal::files::owl_dir()?;
+
+    let mut results = Vec::new();
+    for (name, host) in &hosts {
+        println!("[{}]", color::blue(name));
+        let result = fleet::sync_config(host, &local, dry_run).and_then(|synced| {
+            match (synced, dry_run) {
+                (Synced::Pulled, false) => {
+                    println!("  {} pulled the config", color::green("✓"))
+                }
+                (Synced::Copied, false) => {
+                    println!("  {} copied the config", color::green("✓"))
+                }
+                (synced, true) => println!(
+                    "  {}",
+                    color::info(&format!(
+                        "dry run, would {} the config; the host plans with the one it has",
+                        if synced == Synced::Pulled {
+                            "pull"
+                        } else {
+                            "copy"
+                        }
+                    ))
+                ),
+            }
+            fleet::apply(host, dry_run, render)
+        });
+        if let Err(err) = &result {
+            println!("  {} {:#}", color::red("✗"), err);
+        }
+        results.push((*name, result));
+        println!();
+    }
+
+    print_summary(&results);
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    if failed > 0 {
+        return Err(anyhow!("{} of {} hosts failed", failed, results.len()));
+    }
+    Ok(())
+}
+
+/// Print one event of a host's stream
+pub fn render(event: &Event) {
+    match event {
+        Event::Start { schema, .. } if *schema != crate::cli::porcelain::SCHEMA_VERSION => {
+            println!(
+                "  {} the host speaks porcelain schema {}, this owl {}",
+                color::yellow("!"),
+                schema,
+                crate::cli::porcelain::SCHEMA_VERSION
+            )
+        }
+        Event::PhaseStart { phase } => println!("  {} {}", color::blue("info:"), phase),
+        Event::PkgInstallStart { source, packages } => println!(
+            "  {} installing {} from {}",
+            color::green("➔"),
+            packages.join(", "),
+            source
+        ),
+        Event::PkgInstallDone {
+            source, ok: false, ..
+        } => println!("  {} the {} install failed", color::red("✗"), source),
+        Event::DotfileWritten {
+            destination,
+            status,
+        } => println!("  {} {} {}", color::green("✓"), status, destination),
+        Event::Warning { message } => println!("  {} {}", color::yellow("!"), message),
+        Event::ConfirmNeeded { prompt } => println!(
+            "  {} the host asked '{}' with nobody to answer",
+            color::yellow("!"),
+            prompt
+        ),
+        _ => {}
+    }
+}
+
+/// What the summary table says a host did
+fn changes(summary: &Summary) -> String {
+    if summary.dry_run {
+        format!(
+            "{} to install, {} to remove",
+            summary.to_install, summary.to_remove
+        )
+    } else {
+        summary.headline()
+    }
+}
+
+fn print_summary(results: &[(&str, Result<Summary>)]) {
+    let mut table = crate::cli::ui::Table::new(&["host", "result", "changes"]);
+    for (name, result) in results {
+        let (outcome, detail) = match result {
+            Ok(summary) if summary.warnings > 0 => ("warnings", changes(summary)),
+            Ok(summary) => ("ok", changes(summary)),
+            Err(err) => ("failed", format!("{:#}", err)),
+        };
+        table.add_row(vec![name.to_string(), outcome.to_string(), detail]);
+    }
+    table.print();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_describe_the_plan_on_a_dry_run() {
+        let planned = Summary {
+            dry_run: true,
+            to_install: 2,
+            to_remove: 1,
+            ..Default::default()
+        };
+        assert_eq!(changes(&planned), "2 to install, 1 to remove");
+        let applied = Summary {
+            upgraded: 3,
+            ..Default::default()
+        };
+        assert_eq!(changes(&applied), "3 packages updated");
+    }
+}
@@ -0,0 +1,163 @@
+//! Feed of the apply and adopt runs owl recorded
+
+use anyhow::{Result, anyhow};
+
+use crate::core::history::{Outcome, PackageChange, RunRecord};
+use crate::internal::color;
+
+/// Run the history command: the newest `limit` runs, or one package's timeline
+pub fn run(package: Option<&str>, limit: usize) {
+    crate::core::state::forbid_writes();
+    crate::error::exit_on_error(feed(package, limit));
+}
+
+/// Run `history show`: print the stored record of one run
+pub fn run_show(id: &str) {
+    crate::core::state::forbid_writes();
+    let result = crate::core::history::run_file(id).and_then(|content| {
+        let content =
+            content.ok_or_else(|| anyhow!("no run '{}'; see `owl history` for run ids", id))?;
+        print!("{}", content);
+        Ok(())
+    });
+    crate::error::exit_on_error(result);
+}
+
+fn feed(package: Option<&str>, limit: usize) -> Result<()> {
+    let runs: Vec<RunRecord> = crate::core::history::load_runs()?
+        .into_iter()
+        .filter(|run| package.is_none_or(|package| run.touches(package)))
+        .take(limit)
+        .collect();
+
+    println!("[{}]", color::blue("history"));
+    if runs.is_empty() {
+        let scope = package
+            .map(|package| format!(" touching {}", package))
+            .unwrap_or_default();
+        println!("  {} no recorded runs{}", color::blue("info:"), scope);
+        return Ok(());
+    }
+
+    let now = crate::core::history::now();
+    let mut table = crate::cli::ui::Table::new(&["when", "run", "outcome", "changes"]);
+    for run in &runs {
+        let changes = match package {
+            Some(package) => package_event(run, package),
+            None => summary(run),
+        };
+        table.add_row(vec![
+            crate::commands::status::format_age(now.saturating_sub(run.started_at)),
+            run.id.clone(),
+            match run.outcome {
+                Outcome::Success => "success".to_string(),
+                Outcome::Incomplete => "incomplete".to_string(),
+            },
+            changes,
+        ]);
+    }
+    table.print();
+    Ok(())
+}
+
+/// Counts for the feed: `+installed -removed ~upgraded`, dotfiles, and adopt results
+fn summary(run: &RunRecord) -> String {
+    let count = |pick: fn(&PackageChange) -> bool| run.packages.iter().filter(|c| pick(c)).count();
+    let mut parts = Vec::new();
+    if !run.packages.is_empty() {
+        parts.push(format!(
+            "+{} -{} ~{}",
+            count(|c| c.from.is_none()),
+            count(|c| c.to.is_none()),
+            count(|c| c.from.is_some() && c.to.is_some())
+        ));
+    }
+    if !run.dotfiles.is_empty() {
+        parts.push(format!(
+            "{} dotfile{}",
+            run.dotfiles.len(),
+            if run.dotfiles.len() == 1 { "" } else { "s" }
+        ));
+    }
+    if !run.adopted.is_empty() {
+        parts.push(format!("adopted {}", run.adopted.join(", ")));
+    }
+    if !run.ignored.is_empty() {
+        parts.push(format!("ignored {}", run.ignored.join(", ")));
+    }
+    if !run.issues.is_empty() {
+        parts.push(format!(
+            "{} warning{}",
+            run.issues.len(),
+            if run.issues.len() == 1 { "" } else { "s" }
+        ));
+    }
+    if parts.is_empty() {
+        "no changes".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// What one run did to `package`
+fn package_event(run: &RunRecord, package: &str) -> String {
+    let mut events = Vec::new();
+    if let Some(change) = run.packages.iter().find(|change| change.name == package) {
+        events.push(match (&change.from, &change.to) {
+            (None, Some(to)) => format!("installed {}", to),
+            (Some(from), None) => format!("removed {}", from),
+            (Some(from), Some(to)) => format!("{} -> {}", from, to),
+            (None, None) => String::new(),
+        });
+    }
+    if run.adopted.iter().any(|name| name == package) {
+        events.push("adopted".to_string());
+    }
+    if run.ignored.iter().any(|name| name == package) {
+        events.push("ignored".to_string());
+    }
+    events.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::history::RunKind;
+
+    fn change(name: &str, from: Option<&str>, to: Option<&str>) -> PackageChange {
+        PackageChange {
+            name: name.to_string(),
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_summary_and_package_event() {
+        let mut run = RunRecord::start(RunKind::Apply);
+        run.packages = vec![
+            change("htop", Some("3.3.0-1"), Some("3.3.0-2")),
+            change("tmux", None, Some("3.4-1")),
+            change("vim", Some("9.1-1"), None),
+            change("zsh", None, Some("5.9-5")),
+        ];
+        run.dotfiles = vec!["~/.tmux.conf".to_string()];
+        assert_eq!(summary(&run), "+2 -1 ~1, 1 dotfile");
+        assert_eq!(package_event(&run, "htop"), "3.3.0-1 -> 3.3.0-2");
+        assert_eq!(package_event(&run, "vim"), "removed 9.1-1");
+        assert_eq!(package_event(&run, "tmux"), "installed 3.4-1");
+
+        let mut adopt = RunRecord::start(RunKind::Adopt);
+        adopt.adopted = vec!["htop".to_string()];
+        assert_eq!(summary(&adopt), "adopted htop");
+        adopt.issues.push(crate::error::Issue {
+            severity: crate::error::Severity::Warning,
+            phase: None,
+            context: Some("record the run".to_string()),
+            message: "Failed to record the run: disk full".to_string(),
+        });
+        assert_eq!(summary(&adopt), "adopted htop, 1 warning");
+        assert_eq!(package_event(&adopt, "htop"), "adopted");
+        assert_eq!(summary(&RunRecord::start(RunKind::Apply)), "no changes");
+    }
+}
@@ -0,0 +1,195 @@
+//! Direct access to the untracked list that adopt's Ignore action writes to
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+
+use crate::core::state::PackageState;
+use crate::internal::color;
+use crate::internal::util::glob_match;
+
+/// Run the ignore command: add `patterns`, take them out with `remove`, or list
+pub fn run(
+    patterns: &[String],
+    list: bool,
+    remove: bool,
+    flags: &crate::cli::handler::GlobalFlags,
+) {
+    let result = if list {
+        crate::core::state::forbid_writes();
+        list_ignored()
+    } else if remove {
+        unignore(patterns, flags.dry_run)
+    } else {
+        ignore(patterns, flags.dry_run)
+    };
+    crate::error::exit_on_error(result);
+}
+
+fn ignore(patterns: &[String], dry_run: bool) -> Result<()> {
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    let installed: Vec<String> = crate::core::pm::ParuPacman::new()
+        .list_installed()?
+        .into_iter()
+        .collect();
+    let (selected, unmatched) = expand(patterns, &installed);
+
+    println!("[{}]", color::blue("ignore"));
+    for pattern in &unmatched {
+        println!(
+            "  {} '{}' matches nothing installed; check the spelling",
+            color::yellow("!"),
+            pattern
+        );
+    }
+    let verb = if dry_run { "would ignore" } else { "ignored" };
+    let mut changed = false;
+    for package in selected {
+        if state.is_untracked(&package) {
+            println!("  {} {} is already ignored", color::blue("info:"), package);
+            continue;
+        }
+        println!("  {} {} {}", color::green("✓"), verb, package);
+        state.ignore(package);
+        changed = true;
+    }
+    if changed && !dry_run {
+        state.save().context("Failed to save package state")?;
+    }
+    Ok(())
+}
+
+/// Run `owl unignore`: take `patterns`, or with `all` everything, off the untracked
+/// list; `list` prints it instead
+pub fn run_unignore(
+    patterns: &[String],
+    all: bool,
+    list: bool,
+    flags: &crate::cli::handler::GlobalFlags,
+) {
+    let result = if list {
+        crate::core::state::forbid_writes();
+        list_ignored()
+    } else if all {
+        unignore_all(flags.dry_run)
+    } else {
+        unignore(patterns, flags.dry_run)
+    };
+    crate::error::exit_on_error(result);
+}
+
+fn unignore(patterns: &[String], dry_run: bool) -> Result<()> {
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    let (selected, unmatched) = expand(patterns, &state.untracked);
+    let (removed, not_found) = state.unignore(&selected);
+
+    println!("[{}]", color::blue("unignore"));
+    for pattern in unmatched.iter().filter(|p| p.contains(['*', '?'])) {
+        println!(
+            "  {} '{}' matches nothing ignored",
+            color::yellow("!"),
+            pattern
+        );
+    }
+    for package in &not_found {
+        println!("  {} {} is not ignored", color::yellow("!"), package);
+    }
+    print_unignored(&removed, dry_run);
+    if !removed.is_empty() && !dry_run {
+        state.save().context("Failed to save package state")?;
+    }
+    Ok(())
+}
+
+fn unignore_all(dry_run: bool) -> Result<()> {
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    let removed = state.unignore_all();
+
+    println!("[{}]", color::blue("unignore"));
+    if removed.is_empty() {
+        println!("  {} nothing is ignored", color::blue("info:"));
+        return Ok(());
+    }
+    print_unignored(&removed, dry_run);
+    if !dry_run {
+        state.save().context("Failed to save package state")?;
+    }
+    Ok(())
+}
+
+fn print_unignored(removed: &[String], dry_run: bool) {
+    let verb = if dry_run {
+        "would stop ignoring"
+    } else {
+        "stopped ignoring"
+    };
+    for package in removed {
+        println!("  {} {} {}", color::green("✓"), verb, package);
+    }
+}
+
+fn list_ignored() -> Result<()> {
+    let state = PackageState::load().context("Failed to load package state")?;
+    let installed: HashSet<String> = crate::core::pm::ParuPacman::new().list_installed()?;
+
+    println!("[{}]", color::blue("ignored"));
+    if state.untracked.is_empty() {
+        println!("  {} nothing is ignored", color::blue("info:"));
+        return Ok(());
+    }
+    let mut table = crate::cli::ui::Table::new(&["name", "installed"]);
+    for package in &state.untracked {
+        let shown = if installed.contains(package) {
+            "yes"
+        } else {
+            "no"
+        };
+        table.add_row(vec![package.clone(), shown.to_string()]);
+    }
+    table.print();
+    Ok(())
+}
+
+/// Names for `patterns`: globs expand among `candidates`, plain names are kept as
+/// given. Also returns the patterns that matched no candidate.
+fn expand(patterns: &[String], candidates: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut selected = Vec::new();
+    let mut unmatched = Vec::new();
+    for pattern in patterns {
+        let matches: Vec<&String> = candidates
+            .iter()
+            .filter(|name| glob_match(pattern, name))
+            .collect();
+        if matches.is_empty() {
+            unmatched.push(pattern.clone());
+        }
+        if pattern.contains(['*', '?']) {
+            selected.extend(matches.into_iter().cloned());
+        } else {
+            selected.push(pattern.clone());
+        }
+    }
+    selected.sort();
+    selected.dedup();
+    (selected, unmatched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(items: &[&str]) -> Vec<String> {
+        items.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_expand_keeps_plain_names_and_reports_misses() {
+        let installed = names(&["htop", "python-numpy", "python-requests"]);
+        let (selected, unmatched) =
+            expand(&names(&["python-*", "hotp", "htop", "ruby-*"]), &installed);
+        assert_eq!(
+            selected,
+            names(&["hotp", "htop", "python-numpy", "python-requests"])
+        );
+        assert_eq!(unmatched, names(&["hotp", "ruby-*"]));
+    }
+}
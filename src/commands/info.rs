@@ -0,0 +1,376 @@
+//! Everything pacman, the AUR, and owl know about one package
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::cli::context::Context;
+use crate::core::config::Config;
+use crate::core::dotfiles::DotfileStatus;
+use crate::core::plan::{Plan, PlanOptions};
+use crate::core::pm::PendingUpdate;
+use crate::core::state::PackageState;
+use crate::internal::color;
+
+/// The combined view printed by `owl info`
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    name: String,
+    /// Sync db name, `aur`, `local` for installed packages found nowhere else, or `None`
+    source: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    installed_size: Option<u64>,
+    depends: Vec<String>,
+    /// AUR maintainer, or the packager for repo packages
+    maintainer: Option<String>,
+    /// Unix time the AUR package was flagged out of date
+    out_of_date: Option<u64>,
+    installed: bool,
+    install_date: Option<String>,
+    explicit: Option<bool>,
+    /// Declaring file and line, followed by the `@group` lines that pulled it in
+    declared: Vec<String>,
+    dotfiles: Vec<String>,
+    service: Option<String>,
+    env: Vec<String>,
+    state: Vec<&'static str>,
+    pending: Vec<String>,
+}
+
+/// Run the info command
+pub fn run(package: &str, json: bool, ctx: &Context) {
+    crate::core::state::forbid_writes();
+    let (config, state) = match ctx.owned_config_and_state() {
+        Ok(loaded) => loaded,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+    let report = match crate::internal::util::execute_with_progress(
+        {
+            let package = package.to_string();
+            move || collect(&package, &config, &state)
+        },
+        "Gathering package info",
+    ) {
+        Ok(report) => report,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(out) => println!("{}", out),
+            Err(err) => crate::error::exit_with_error(err.into()),
+        }
+    } else {
+        print_report(&report);
+    }
+}
+
+fn collect(package: &str, config: &Config, state: &PackageState) -> Result<Report> {
+    let owl_root = crate::internal::files::owl_dir()?;
+    let names = [package.to_string()];
+
+    let mut report = Report {
+        name: package.to_string(),
+        ..Report::default()
+    };
+
+    let installed = crate::core::package::get_package_info(&names)?.remove(package);
+    let repo = crate::core::package::get_repo_package_info(&names)?.remove(package);
+    if let Some(info) = installed.as_ref().or(repo.as_ref()) {
+        report.version = Some(info.version.clone());
+        report.description = Some(info.description.clone());
+        report.installed_size = Some(info.installed_size);
+        report.depends = info.depends_on.clone();
+    }
+    if let Some(info) = &installed {
+        report.installed = true;
+        report.install_date = Some(info.install_date.clone()).filter(|date| !date.is_empty());
+        report.explicit = Some(info.explicit);
+    }
+
+    if let Some(repo) = &repo {
+        report.source = Some(repo.repository.clone());
+        report.maintainer = Some(repo.packager.clone()).filter(|p| !p.is_empty());
+    } else {
+        // Being offline should not hide what pacman and owl already know
+        let aur = crate::core::search::aur_info(package).unwrap_or_else(|err| {
+            log::debug!("AUR info unavailable for {}: {:#}", package, err);
+            None
+        });
+        match aur {
+            Some(aur) => {
+                report.source = Some("aur".to_string());
+                report.maintainer = aur.maintainer;
+                report.out_of_date = aur.out_of_date;
+                if !report.installed {
+                    report.version = Some(aur.version);
+                    report.description = Some(aur.description);
+                    report.depends = aur.depends;
+                }
+            }
+            None if report.installed => report.source = Some("local".to_string()),
+            None => {}
+        }
+    }
+
+    report.declared = crate::commands::why::origin_chain(config, &owl_root, package);
+    if let Some(declared) = config.packages.get(package) {
+        report.dotfiles = declared
+            .config
+            .iter()
+            .map(|cfg| {
+                let mapping = crate::core::dotfiles::parse_mapping(cfg);
+                format!("{} -> {}", mapping.source, mapping.destination)
+            })
+            .collect();
+        report.service = declared.service.clone();
+        let mut env: Vec<String> = declared
+            .env_vars
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        env.sort();
+        report.env = env;
+    }
+
+    if state.is_managed(package) {
+        report.state.push("managed");
+    }
+    if state.is_untracked(package) {
+        report.state.push("untracked");
+    }
+    if state.is_hidden(package) {
+        report.state.push("hidden");
+    }
+    if state.pinned_version(package).is_some() {
+        report.state.push("pinned");
+    }
+
+    // The AUR helper is asked below, with versions, only when it matters
+    let options = PlanOptions {
+        check_aur_updates: false,
+    };
+    let plan = crate::core::plan::build_plan(config, state, &options)?;
+    let mut updates = Vec::new();
+    if report.installed {
+        let pm = crate::core::pm::ParuPacman::new();
+        updates = pm.repo_updates()?;
+        if report.source.as_deref() == Some("aur")
+            && crate::core::pm::aur_helper_command().is_some()
+        {
+            updates.extend(pm.aur_updates()?);
+        }
+    }
+    report.pending = pending_actions(
+        package,
+        &plan,
+        &updates,
+        config,
+        state.pinned_version(package),
+    );
+    Ok(report)
+}
+
+/// What an apply would do to `package`, in the order apply does it
+fn pending_actions(
+    package: &str,
+    plan: &Plan,
+    updates: &[PendingUpdate],
+    config: &Config,
+    pinned: Option<&str>,
+) -> Vec<String> {
+    let mut pending = Vec::new();
+    if plan.installs.iter().any(|name| name == package) {
+        pending.push("will be installed".to_string());
+    }
+    if plan.removals.iter().any(|name| name == package) {
+        pending.push("will be removed (managed, no longer configured)".to_string());
+    }
+    if let Some(update) = updates.iter().find(|update| update.name == package) {
+        match pinned {
+            Some(version) => pending.push(format!(
+                "held at {} by a pin ({} available)",
+                version, update.to
+            )),
+            None => pending.push(format!("will be updated {} → {}", update.from, update.to)),
+        }
+    }
+
+    let Some(declared) = config.packages.get(package) else {
+        return pending;
+    };
+    let destinations: Vec<String> = declared
+        .config
+        .iter()
+        .map(|cfg| crate::core::dotfiles::parse_mapping(cfg).destination)
+        .collect();
+    for action in plan
+        .dotfile_drift()
+        .filter(|action| destinations.contains(&action.mapping.destination))
+    {
+        let verb = if action.status == DotfileStatus::Create {
+            "created"
+        } else {
+            "updated"
+        };
+        pending.push(format!(
+            "dotfile {} will be {}",
+            action.mapping.destination, verb
+        ));
+    }
+    if let Some(service) = plan
+        .services_out_of_state()
+        .find(|service| declared.service.as_deref() == Some(service.name.as_str()))
+    {
+        pending.push(format!(
+            "service {} will be enabled and started ({} now)",
+            service.name,
+            service.describe()
+        ));
+    }
+    pending
+}
+
+fn print_report(report: &Report) {
+    println!("[{}]", color::blue("info"));
+    println!("  {}", color::highlight(&report.name));
+    if report.source.is_none() && report.declared.is_empty() {
+        println!(
+            "  {} not found in the repos, the AUR, or the config",
+            color::yellow("!")
+        );
+        return;
+    }
+
+    if let Some(version) = &report.version {
+        field("version:", &color::success(version));
+    }
+    if let Some(description) = report.description.as_ref().filter(|d| !d.is_empty()) {
+        field("about:", &color::description(description));
+    }
+    if let Some(source) = &report.source {
+        let source = match source.as_str() {
+            "aur" => "the AUR".to_string(),
+            "local" => "installed, not in any repo or the AUR".to_string(),
+            repo => repo.to_string(),
+        };
+        field("source:", &source);
+    }
+    if let Some(size) = report.installed_size.filter(|size| *size > 0) {
+        field("size:", &crate::cli::ui::format_size(size));
+    }
+    if !report.depends.is_empty() {
+        field("depends:", &report.depends.join(" "));
+    }
+    if let Some(maintainer) = &report.maintainer {
+        field("maintainer:", maintainer);
+    }
+    if report.out_of_date.is_some() {
+        field("flagged:", &color::yellow("out of date in the AUR"));
+    }
+
+    println!();
+    println!("[{}]", color::blue("owl"));
+    if report.declared.is_empty() {
+        println!("  {} not configured", color::yellow("!"));
+    }
+    for (idx, line) in report.declared.iter().enumerate() {
+        field(if idx == 0 { "declared:" } else { "via:" }, line);
+    }
+    for dotfile in &report.dotfiles {
+        field("dotfile:", dotfile);
+    }
+    if let Some(service) = &report.service {
+        field("service:", service);
+    }
+    for env in &report.env {
+        field("env:", env);
+    }
+    let state = if report.state.is_empty() {
+        "not in state".to_string()
+    } else {
+        report.state.join(", ")
+    };
+    field("state:", &state);
+    let installed = match (report.installed, report.explicit) {
+        (false, _) => "no".to_string(),
+        (true, explicit) => {
+            let reason = if explicit == Some(true) {
+                "explicitly"
+            } else {
+                "as a dependency"
+            };
+            match &report.install_date {
+                Some(date) => format!("{} on {}", reason, date),
+                None => reason.to_string(),
+            }
+        }
+    };
+    field("installed:", &installed);
+
+    println!();
+    println!("[{}]", color::blue("pending"));
+    if report.pending.is_empty() {
+        println!("  {} nothing to apply", color::green("✓"));
+    }
+    for action in &report.pending {
+        println!("  {} {}", color::yellow("~"), action);
+    }
+}
+
+/// Print one aligned `label value` line
+fn field(label: &str, value: &str) {
+    println!("  {} {}", color::blue(&format!("{:<12}", label)), value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::Package;
+    use crate::core::dotfiles::{DotfileAction, DotfileMapping};
+
+    #[test]
+    fn test_pending_actions_only_mention_the_package() {
+        let mut config = Config::new();
+        config.packages.insert(
+            "neovim".to_string(),
+            Package {
+                config: vec!["nvim -> ~/.config/nvim".to_string()],
+                service: None,
+                env_vars: Default::default(),
+                exact: false,
+                hooks: Default::default(),
+            },
+        );
+        let plan = Plan {
+            installs: vec!["neovim".to_string(), "htop".to_string()],
+            dotfiles: vec![DotfileAction {
+                mapping: DotfileMapping {
+                    source: "nvim".to_string(),
+                    destination: "~/.config/nvim".to_string(),
+                },
+                status: DotfileStatus::Create,
+            }],
+            ..Plan::default()
+        };
+        let updates = vec![PendingUpdate {
+            name: "neovim".to_string(),
+            from: "0.9.5-1".to_string(),
+            to: "0.10.0-1".to_string(),
+        }];
+
+        assert_eq!(
+            pending_actions("neovim", &plan, &updates, &config, None),
+            vec![
+                "will be installed",
+                "will be updated 0.9.5-1 → 0.10.0-1",
+                "dotfile ~/.config/nvim will be created",
+            ]
+        );
+        assert!(pending_actions("git", &plan, &updates, &config, None).is_empty());
+        assert_eq!(
+            pending_actions("neovim", &plan, &updates, &config, Some("0.9.5-1"))[1],
+            "held at 0.9.5-1 by a pin (0.10.0-1 available)"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! First-run setup of the owl directory
+
+use anyhow::{Context, Result, anyhow};
+use std::path::Path;
+use std::process::Command;
+
+use crate::core::state::PackageState;
+use crate::internal::color;
+use crate::internal::constants;
+use crate::internal::files::friendly_path;
+use crate::internal::logging::LogCommand;
+
+/// Starter main.owl; `{packages}` is replaced with the package list
+const STARTER_CONFIG: &str = "\
+# main.owl - applied on every host
+#
+# Packages are listed under @packages, one per line. Use @package <name> when a
+# package needs dotfiles or services:
+#
+#   @package neovim
+#   :config nvim -> ~/.config/nvim
+#
+#   @package openssh
+#   :service sshd
+#
+# Host specific config lives in hosts/<hostname>.owl, shared pieces in
+# groups/<name>.owl (pulled in with @group <name>). Dotfile sources are
+# relative to the dotfiles/ directory next to this file.
+
+@packages
+{packages}";
+
+/// Render the starter config, listing `packages` under @packages
+pub fn starter_config(packages: &[String]) -> String {
+    let listed: String = packages.iter().map(|p| format!("{}\n", p)).collect();
+    STARTER_CONFIG.replace("{packages}", &listed)
+}
+
+/// Run the init command
+pub fn run(from_system: bool, git: bool, flags: &crate::cli::handler::GlobalFlags) {
+    crate::error::exit_on_error(init(from_system, git, flags.dry_run));
+}
+
+fn init(from_system: bool, git: bool, dry_run: bool) -> Result<()> {
+    let owl = crate::internal::files::owl_dir()?;
+    let verb = if dry_run { "would create" } else { "created" };
+    println!("[{}]", color::blue("init"));
+
+    for dir in [
+        owl.clone(),
+        owl.join(constants::DOTFILES_DIR),
+        owl.join(constants::HOSTS_DIR),
+        owl.join(constants::GROUPS_DIR),
+    ] {
+        if dir.is_dir() {
+            report_existing(&dir);
+            continue;
+        }
+        if !dry_run {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        report_created(verb, &dir);
+    }
+
+    let state = PackageState::location()?;
+    if state.exists() {
+        report_existing(&state);
+    } else {
+        if !dry_run {
+            PackageState::load()
+                .and_then(|state| state.save())
+                .context("Failed to initialize package state")?;
+        }
+        report_created(verb, &state);
+    }
+
+    let main_config = owl.join(constants::MAIN_CONFIG_FILE);
+    if main_config.exists() {
+        report_existing(&main_config);
+    } else {
+        // Reading the state would create it, so a dry run only says what it would list
+        let packages = if from_system && !dry_run {
+            let state = PackageState::load().context("Failed to load package state")?;
+            let config = crate::core::config::Config::new();
+            crate::commands::adopt::discover_candidates(&state, &config)
+                .context("Failed to list explicitly installed packages")?
+        } else {
+            Vec::new()
+        };
+        if !dry_run {
+            std::fs::write(&main_config, starter_config(&packages))
+                .with_context(|| format!("Failed to write {}", main_config.display()))?;
+        }
+        report_created(verb, &main_config);
+        if from_system && !dry_run {
+            println!(
+                "  {} listed {} explicitly installed packages",
+                color::blue("info:"),
+                packages.len()
+            );
+        }
+    }
+
+    if git {
+        let repo = owl.join(".git");
+        if repo.exists() {
+            report_existing(&repo);
+        } else {
+            if !dry_run {
+                git_init(&owl)?;
+            }
+            report_created(verb, &repo);
+        }
+    }
+
+    println!("\n[{}]", color::blue("next"));
+    for (command, purpose) in [
+        ("owl adopt", "pick installed packages to manage"),
+        ("owl edit config main", "edit main.owl"),
+        ("owl --dry-run", "preview what apply would change"),
+        ("owl", "apply the configuration"),
+    ] {
+        println!(
+            "  {} {:<22} {}",
+            color::green("➔"),
+            command,
+            color::dim(purpose)
+        );
+    }
+    Ok(())
+}
+
+fn git_init(dir: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .arg("init")
+        .arg("--quiet")
+        .current_dir(dir)
+        .logged()
+        .output()
+        .context("Failed to run git init")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git init failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+fn report_created(verb: &str, path: &Path) {
+    println!(
+        "  {} {} {}",
+        color::green("✓"),
+        verb,
+        friendly_path(&path.display().to_string())
+    );
+}
+
+fn report_existing(path: &Path) {
+    println!(
+        "  {} {} already exists",
+        color::blue("info:"),
+        friendly_path(&path.display().to_string())
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starter_config_parses() {
+        let empty = crate::core::config::Config::parse(&starter_config(&[])).unwrap();
+        assert!(empty.packages.is_empty());
+
+        let packages = vec!["htop".to_string(), "git".to_string()];
+        let config = crate::core::config::Config::parse(&starter_config(&packages)).unwrap();
+        assert_eq!(config.packages.len(), 2);
+        assert!(config.packages.contains_key("htop"));
+    }
+}
@@ -0,0 +1,87 @@
+//! Hook owl into other tools on the system
+
+use anyhow::{Result, anyhow};
+use std::path::Path;
+
+use crate::core::pacman_hook;
+use crate::internal::color;
+
+/// Something `owl integrate` can install
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Integration {
+    /// Refresh owl's state after every pacman transaction
+    PacmanHook,
+}
+
+/// Run `owl integrate`: install `integration`, or remove every integration
+pub fn run(
+    integration: Option<Integration>,
+    remove: bool,
+    flags: &crate::cli::handler::GlobalFlags,
+) {
+    let result = match integration {
+        Some(Integration::PacmanHook) if !remove => install_pacman_hook(flags.dry_run),
+        _ => remove_all(flags.dry_run),
+    };
+    crate::error::exit_on_error(result);
+}
+
+fn install_pacman_hook(dry_run: bool) -> Result<()> {
+    let invocation = crate::core::schedule::Invocation::current()?;
+    let hook = pacman_hook::render(&invocation);
+    println!("[{}]", color::blue("integrate"));
+    if crate::core::privilege::writable_by_others(&invocation.exe) {
+        crate::error::warn(&format!(
+            "pacman runs the hook as root, but {} can be changed without root; install owl somewhere root owns, such as /usr/bin, before relying on it",
+            invocation.exe.display()
+        ));
+    }
+    let existing = std::fs::read_to_string(pacman_hook::HOOK_PATH).ok();
+    if existing.as_deref() == Some(hook.as_str()) {
+        println!(
+            "  {} the pacman hook is already installed",
+            color::green("✓")
+        );
+        return Ok(());
+    }
+    if existing.is_some() && pacman_hook::installed().is_none() {
+        return Err(anyhow!(
+            "{} exists and was not written by owl; move it aside first",
+            pacman_hook::HOOK_PATH
+        ));
+    }
+    if dry_run {
+        println!(
+            "  {} would write {}",
+            color::green("➔"),
+            pacman_hook::HOOK_PATH
+        );
+        return Ok(());
+    }
+    crate::core::privilege::write_file(Path::new(pacman_hook::HOOK_PATH), &hook)?;
+    println!("  {} wrote {}", color::green("✓"), pacman_hook::HOOK_PATH);
+    println!(
+        "  {} pacman now runs `owl state refresh --from-hook` after each transaction",
+        color::blue("info:")
+    );
+    Ok(())
+}
+
+fn remove_all(dry_run: bool) -> Result<()> {
+    println!("[{}]", color::blue("integrate"));
+    if pacman_hook::installed().is_none() {
+        println!("  {} no owl integration is installed", color::blue("info:"));
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "  {} would remove {}",
+            color::green("➔"),
+            pacman_hook::HOOK_PATH
+        );
+        return Ok(());
+    }
+    crate::core::privilege::remove_file(Path::new(pacman_hook::HOOK_PATH))?;
+    println!("  {} removed {}", color::green("✓"), pacman_hook::HOOK_PATH);
+    Ok(())
+}
@@ -0,0 +1,221 @@
+//! Enumerate configured packages and their install status
+
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::cli::context::Context;
+use crate::core::config::Config;
+use crate::core::state::PackageState;
+use crate::internal::color;
+
+/// Which packages `owl list` shows; every set filter must match
+#[derive(Debug, Clone, Default)]
+pub struct ListFilters {
+    pub missing: bool,
+    pub extra: bool,
+    pub aur: bool,
+    pub host: Option<String>,
+    pub with_dotfiles: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryStatus {
+    Installed,
+    Missing,
+    /// Managed in state but no longer configured
+    Extra,
+}
+
+impl EntryStatus {
+    fn label(self) -> &'static str {
+        match self {
+            EntryStatus::Installed => "installed",
+            EntryStatus::Missing => "missing",
+            EntryStatus::Extra => "extra",
+        }
+    }
+}
+
+/// One row of `owl list`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entry {
+    pub name: String,
+    /// Declaring file, relative to the owl directory
+    pub source: Option<String>,
+    pub version: Option<String>,
+    pub status: EntryStatus,
+    pub dotfiles: usize,
+}
+
+/// Run the list command
+pub fn run(filters: &ListFilters, json: bool, ctx: &Context) {
+    crate::error::exit_on_error(list(filters, json, ctx));
+}
+
+fn list(filters: &ListFilters, json: bool, ctx: &Context) -> Result<()> {
+    let owl_root = crate::internal::files::owl_dir()?;
+    let host_config;
+    let config = match &filters.host {
+        Some(host) => {
+            host_config =
+                Config::load_for_host(&owl_root, host).context("Failed to load config")?;
+            &host_config
+        }
+        None => ctx.config()?,
+    };
+    let installed = ctx.installed()?;
+
+    let mut entries = select(config, ctx.state()?, installed, &owl_root, filters);
+    if filters.aur {
+        let names: Vec<String> = entries.iter().map(|entry| entry.name.clone()).collect();
+        let aur: HashSet<String> = crate::core::package::categorize_packages(&names)?
+            .aur
+            .into_iter()
+            .collect();
+        entries.retain(|entry| aur.contains(&entry.name));
+    }
+
+    let installed_names: Vec<String> = entries
+        .iter()
+        .filter(|entry| entry.status != EntryStatus::Missing)
+        .map(|entry| entry.name.clone())
+        .collect();
+    let info = crate::core::package::get_package_info(&installed_names)?;
+    for entry in &mut entries {
+        entry.version = info.get(&entry.name).map(|info| info.version.clone());
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    let mut table = crate::cli::ui::Table::new(&["name", "source", "version", "status"]);
+    for entry in &entries {
+        table.add_row(vec![
+            entry.name.clone(),
+            entry.source.clone().unwrap_or_else(|| "-".to_string()),
+            entry.version.clone().unwrap_or_else(|| "-".to_string()),
+            entry.status.label().to_string(),
+        ]);
+    }
+    table.print();
+    println!(
+        "\n  {} {} package{}",
+        color::blue("info:"),
+        entries.len(),
+        if entries.len() == 1 { "" } else { "s" }
+    );
+    Ok(())
+}
+
+/// Configured packages (or, with `--extra`, leftover managed ones) that pass the filters
+/// answerable from config, state and the installed set, sorted by name
+fn select(
+    config: &Config,
+    state: &PackageState,
+    installed: &HashSet<String>,
+    owl_root: &Path,
+    filters: &ListFilters,
+) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = if filters.extra {
+        state
+            .managed
+            .iter()
+            .filter(|name| !config.packages.contains_key(*name))
+            .map(|name| Entry {
+                name: name.clone(),
+                source: None,
+                version: None,
+                status: EntryStatus::Extra,
+                dotfiles: 0,
+            })
+            .collect()
+    } else {
+        let sources: HashMap<&String, String> = config
+            .provenance
+            .iter()
+            .filter_map(|(name, provenance)| {
+                let path = provenance.path.as_deref()?;
+                let relative = path.strip_prefix(owl_root).unwrap_or(path);
+                Some((name, relative.display().to_string()))
+            })
+            .collect();
+        config
+            .packages
+            .iter()
+            .map(|(name, package)| Entry {
+                name: name.clone(),
+                source: sources.get(name).cloned(),
+                version: None,
+                status: if installed.contains(name) {
+                    EntryStatus::Installed
+                } else {
+                    EntryStatus::Missing
+                },
+                dotfiles: package.config.len(),
+            })
+            .collect()
+    };
+
+    entries.retain(|entry| {
+        (!filters.missing || entry.status == EntryStatus::Missing)
+            && (!filters.with_dotfiles || entry.dotfiles > 0)
+    });
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_select_filters() {
+        let config =
+            Config::parse("@packages\nhtop\nripgrep\n\n@package nvim\n:config nvim\n").unwrap();
+        let state = PackageState {
+            untracked: Vec::new(),
+            hidden: Vec::new(),
+            managed: vec!["htop".to_string(), "firefox".to_string()],
+            pinned: Default::default(),
+            sources: Default::default(),
+            remove_hooks: Default::default(),
+        };
+        let installed: HashSet<String> = ["htop", "nvim", "firefox"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let root = Path::new("/o");
+        let select = |filters: ListFilters| select(&config, &state, &installed, root, &filters);
+
+        let all = select(ListFilters::default());
+        assert_eq!(names(&all), vec!["htop", "nvim", "ripgrep"]);
+        assert_eq!(all[2].status, EntryStatus::Missing);
+
+        let missing = ListFilters {
+            missing: true,
+            ..ListFilters::default()
+        };
+        assert_eq!(names(&select(missing)), vec!["ripgrep"]);
+
+        let extra = ListFilters {
+            extra: true,
+            ..ListFilters::default()
+        };
+        assert_eq!(names(&select(extra)), vec!["firefox"]);
+
+        let with_dotfiles = ListFilters {
+            with_dotfiles: true,
+            ..ListFilters::default()
+        };
+        assert_eq!(names(&select(with_dotfiles)), vec!["nvim"]);
+    }
+}
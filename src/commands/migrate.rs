@@ -0,0 +1,735 @@
+//! Import package lists and stow dotfiles from other tools into a new group file
+
+use anyhow::{Context, Result, anyhow};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::document::Document;
+use crate::core::config::{Config, Package};
+use crate::core::state::PackageState;
+use crate::internal::color;
+use crate::internal::constants;
+
+/// Tools owl can import from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Source {
+    /// A text file with one package per line; `#` starts a comment
+    Pkglist,
+    /// A GNU stow directory; each package dir becomes a package with dotfiles
+    Stow,
+    /// Output of `aur repo --list`: name and version per line
+    Aurutils,
+    /// A pacman hook file, or a directory of them; package targets are imported
+    PacmanHooks,
+}
+
+impl Source {
+    fn name(self) -> &'static str {
+        match self {
+            Source::Pkglist => "pkglist",
+            Source::Stow => "stow",
+            Source::Aurutils => "aurutils",
+            Source::PacmanHooks => "pacman-hooks",
+        }
+    }
+}
+
+/// What a source contributes, before the names are checked
+#[derive(Debug, Default, PartialEq)]
+struct Import {
+    packages: Vec<String>,
+    /// `:config` mappings per package; only stow has these
+    dotfiles: HashMap<String, Vec<String>>,
+}
+
+/// Everything the migration wrote, so a declined review can undo it
+struct Written {
+    group_file: PathBuf,
+    main_config: PathBuf,
+    main_before: String,
+    dotfile_dirs: Vec<PathBuf>,
+}
+
+/// Run the migrate-from command
+pub fn run(
+    source: Source,
+    path: &Path,
+    name: Option<&str>,
+    flags: &crate::cli::handler::GlobalFlags,
+) {
+    crate::error::exit_on_error(migrate(source, path, name.unwrap_or(source.name()), flags));
+}
+
+fn migrate(
+    source: Source,
+    path: &Path,
+    group: &str,
+    flags: &crate::cli::handler::GlobalFlags,
+) -> Result<()> {
+    validate_group(group)?;
+    let import = read_source(source, path)?;
+    println!("[{}]", color::blue("migrate"));
+    if import.packages.is_empty() {
+        println!(
+            "  {} no packages found in {}",
+            color::yellow("!"),
+            path.display()
+        );
+        return Ok(());
+    }
+
+    let unknown = unknown_packages(&import.packages)?;
+    println!(
+        "  {} {} package{} from {}",
+        color::blue("info:"),
+        import.packages.len(),
+        if import.packages.len() == 1 { "" } else { "s" },
+        path.display()
+    );
+    if !unknown.is_empty() {
+        println!(
+            "  {} not in the repos or the AUR, left commented out: {}",
+            color::yellow("!"),
+            unknown.join(", ")
+        );
+    }
+
+    let owl = crate::internal::files::owl_dir()?;
+    let group_file =
+        owl.join(constants::GROUPS_DIR)
+            .join(format!("{}{}", group, constants::OWL_EXT));
+    if group_file.exists() {
+        return Err(anyhow!(
+            "{} already exists; pick another group with --name",
+            group_file.display()
+        ));
+    }
+    let main_config = owl.join(constants::MAIN_CONFIG_FILE);
+    if !main_config.exists() {
+        return Err(anyhow!(
+            "{} does not exist; run `owl init` first",
+            main_config.display()
+        ));
+    }
+    let dotfiles = owl.join(constants::DOTFILES_DIR);
+    for package in import.dotfiles.keys() {
+        if dotfiles.join(package).exists() {
+            return Err(anyhow!(
+                "{} already exists; move it out of the way first",
+                dotfiles.join(package).display()
+            ));
+        }
+    }
+
+    let content = render(source, path, &import, &unknown);
+    if flags.dry_run {
+        println!(
+            "  {} would write {} and add @group {} to {}:",
+            color::blue("info:"),
+            group_file.display(),
+            group,
+            constants::MAIN_CONFIG_FILE
+        );
+        for line in content.lines() {
+            println!("    {}", line);
+        }
+        return Ok(());
+    }
+
+    let written = write(path, group, &group_file, &main_config, &import, &content)?;
+    println!(
+        "  {} wrote {}",
+        color::green("✓"),
+        crate::internal::files::friendly_path(&group_file.display().to_string())
+    );
+
+    // Show what apply would do with the new group before owl takes ownership; a
+    // review that fails still rolls the migration back
+    let plan = match review() {
+        Ok(plan) => plan,
+        Err(err) => {
+            undo(&written)?;
+            return Err(err);
+        }
+    };
+    println!(
+        "  {} with the new group, `owl` would:",
+        color::blue("info:")
+    );
+    for line in review_lines(&plan) {
+        println!("    {}", line);
+    }
+
+    let known: Vec<String> = import
+        .packages
+        .iter()
+        .filter(|package| !unknown.contains(package))
+        .cloned()
+        .collect();
+    if !flags.non_interactive && !confirm(known.len())? {
+        undo(&written)?;
+        println!(
+            "  {} migration rolled back; nothing changed",
+            color::yellow("!")
+        );
+        return Ok(());
+    }
+
+    let mut files = vec![written.group_file.clone(), written.main_config.clone()];
+    files.extend(written.dotfile_dirs.iter().cloned());
+    crate::core::autocommit::commit_config_change(
+        &files,
+        &format!(
+            "owl migrate: import {} from {} as {}",
+            source.name(),
+            path.display(),
+            crate::core::autocommit::describe(&written.group_file)
+        ),
+    );
+
+    let installed = crate::core::pm::ParuPacman::new().list_installed()?;
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    let mut managed = 0;
+    for package in known.iter().filter(|package| installed.contains(*package)) {
+        state.add_managed(package.clone());
+        managed += 1;
+    }
+    state.save().context("Failed to save package state")?;
+    println!(
+        "  {} {} installed package{} now managed; run `owl` to apply the rest",
+        color::green("✓"),
+        managed,
+        if managed == 1 { "" } else { "s" }
+    );
+    Ok(())
+}
+
+/// A group name becomes `groups/<name>.owl`, so it must stay inside that directory
+fn validate_group(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "invalid group name '{}'; use letters, digits, '-', '_' and '.'",
+            name
+        ))
+    }
+}
+
+/// The plan of an apply against the config as migrated, without AUR update checks
+fn review() -> Result<crate::core::plan::Plan> {
+    let config = crate::core::config::Config::load_all_relevant_config_files()
+        .context("Failed to load config")?;
+    let state = PackageState::load().context("Failed to load package state")?;
+    crate::core::plan::build_plan(
+        &config,
+        &state,
+        &crate::core::plan::PlanOptions {
+            check_aur_updates: false,
+        },
+    )
+}
+
+/// The installs, removals, and dotfile writes of `plan`, one line each
+fn review_lines(plan: &crate::core::plan::Plan) -> Vec<String> {
+    let plural = |count: usize| if count == 1 { "" } else { "s" };
+    let mut lines = Vec::new();
+    if !plan.installs.is_empty() {
+        lines.push(format!(
+            "install {} package{}: {}",
+            plan.installs.len(),
+            plural(plan.installs.len()),
+            plan.installs.join(", ")
+        ));
+    }
+    if !plan.removals.is_empty() {
+        lines.push(format!(
+            "remove {} package{}: {}",
+            plan.removals.len(),
+            plural(plan.removals.len()),
+            plan.removals.join(", ")
+        ));
+    }
+    let dotfiles: Vec<String> = plan
+        .dotfile_drift()
+        .map(|action| crate::internal::files::friendly_path(&action.mapping.destination))
+        .collect();
+    if !dotfiles.is_empty() {
+        lines.push(format!(
+            "write {} dotfile{}: {}",
+            dotfiles.len(),
+            plural(dotfiles.len()),
+            dotfiles.join(", ")
+        ));
+    }
+    if lines.is_empty() {
+        lines.push("change nothing".to_string());
+    }
+    lines
+}
+
+fn read_source(source: Source, path: &Path) -> Result<Import> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+    };
+    match source {
+        Source::Pkglist | Source::Aurutils => Ok(Import {
+            packages: parse_pkglist(&read(path)?),
+            ..Import::default()
+        }),
+        Source::PacmanHooks => {
+            let files = if path.is_dir() {
+                let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|file| file.extension().is_some_and(|ext| ext == "hook"))
+                    .collect();
+                files.sort();
+                files
+            } else {
+                vec![path.to_path_buf()]
+            };
+            let mut packages = Vec::new();
+            for file in files {
+                packages.extend(parse_hook(&read(&file)?));
+            }
+            Ok(Import {
+                packages: dedup(packages),
+                ..Import::default()
+            })
+        }
+        Source::Stow => read_stow(path),
+    }
+}
+
+fn dedup(names: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    names
+        .into_iter()
+        .filter(|name| seen.insert(name.clone()))
+        .collect()
+}
+
+/// Package names from a list: the first word of each line, ignoring comments, so
+/// `pacman -Q` and `aur repo --list` output work as well
+pub fn parse_pkglist(content: &str) -> Vec<String> {
+    dedup(
+        content
+            .lines()
+            .filter_map(|line| {
+                let line = line.split('#').next().unwrap_or_default();
+                line.split_whitespace().next().map(ToString::to_string)
+            })
+            .collect(),
+    )
+}
+
+/// `Target` names of the `Type = Package` triggers in a pacman hook. Globs and negated
+/// targets cannot be turned into package entries and are skipped.
+fn parse_hook(content: &str) -> Vec<String> {
+    let mut packages = Vec::new();
+    let mut in_trigger = false;
+    let mut package_trigger = false;
+    let mut targets = Vec::new();
+    let mut flush = |package_trigger: bool, targets: &mut Vec<String>| {
+        if package_trigger {
+            packages.append(targets);
+        }
+        targets.clear();
+    };
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            flush(package_trigger, &mut targets);
+            in_trigger = line == "[Trigger]";
+            package_trigger = false;
+            continue;
+        }
+        if !in_trigger {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match (key.trim(), value.trim()) {
+            ("Type", kind) => package_trigger = kind == "Package",
+            ("Target", target)
+                if !target.is_empty()
+                    && !target.starts_with('!')
+                    && !target.contains(['*', '?', '[']) =>
+            {
+                targets.push(target.to_string())
+            }
+            _ => {}
+        }
+    }
+    flush(package_trigger, &mut targets);
+    packages
+}
+
+/// Directories shared by many programs; mappings go one level below them
+const SHARED_DIRS: &[&str] = &[
+    ".config",
+    ".local",
+    ".local/share",
+    ".local/bin",
+    ".local/state",
+];
+
+/// Each directory in the stow dir is a package; its top-level entries become mappings
+fn read_stow(dir: &Path) -> Result<Import> {
+    let mut import = Import::default();
+    let mut packages: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_dir()
+                && !path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    packages.sort();
+    for package in packages {
+        let name = package
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mappings = stow_mappings(&package, &name)?;
+        import.packages.push(name.clone());
+        if !mappings.is_empty() {
+            import.dotfiles.insert(name, mappings);
+        }
+    }
+    Ok(import)
+}
+
+/// `:config` values for one stow package, relative to the dotfiles dir it is copied to.
+/// Stow's `--dotfiles` spelling (`dot-config` for `.config`) is translated.
+fn stow_mappings(package_dir: &Path, name: &str) -> Result<Vec<String>> {
+    fn walk(dir: &Path, rel: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        entries.sort();
+        for entry in entries {
+            let Some(file) = entry.file_name() else {
+                continue;
+            };
+            if file == ".stow-local-ignore" {
+                continue;
+            }
+            let entry_rel = rel.join(file);
+            if entry.is_dir() && SHARED_DIRS.contains(&home_path(&entry_rel).as_str()) {
+                walk(&entry, &entry_rel, out)?;
+            } else {
+                out.push(entry_rel);
+            }
+        }
+        Ok(())
+    }
+
+    let mut rels = Vec::new();
+    walk(package_dir, Path::new(""), &mut rels)?;
+    Ok(rels
+        .iter()
+        .map(|rel| format!("{}/{} -> ~/{}", name, rel.display(), home_path(rel)))
+        .collect())
+}
+
+/// Where stow would link `rel`, relative to the home directory
+fn home_path(rel: &Path) -> String {
+    rel.iter()
+        .map(|part| {
+            let part = part.to_string_lossy();
+            match part.strip_prefix("dot-") {
+                Some(rest) => format!(".{}", rest),
+                None => part.into_owned(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Names that are neither installed, in a sync repo, nor in the AUR. When the AUR
+/// cannot be reached, names outside the repos are given the benefit of the doubt.
+fn unknown_packages(packages: &[String]) -> Result<Vec<String>> {
+    let pm = crate::core::pm::ParuPacman::new();
+    let installed = pm.list_installed()?;
+    let repo = pm.batch_repo_available(packages)?;
+    let rest: Vec<String> = packages
+        .iter()
+        .filter(|package| !installed.contains(*package) && !repo.contains(*package))
+        .cloned()
+        .collect();
+    match crate::core::search::aur_info_batch(&rest) {
+        Ok(aur) => Ok(rest
+            .into_iter()
+            .filter(|package| !aur.contains_key(package))
+            .collect()),
+        Err(err) => {
+            println!(
+                "  {} could not check the AUR ({:#}); assuming the other names exist",
+                color::yellow("!"),
+                err
+            );
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// The new group file: known packages through the config serializer, unknown ones
+/// commented out below so nothing from the source is lost
+fn render(source: Source, path: &Path, import: &Import, unknown: &[String]) -> String {
+    let mut config = Config::new();
+    for package in import
+        .packages
+        .iter()
+        .filter(|name| !unknown.contains(name))
+    {
+        config.packages.insert(
+            package.clone(),
+            Package {
+                config: import.dotfiles.get(package).cloned().unwrap_or_default(),
+                service: None,
+                env_vars: HashMap::new(),
+                exact: false,
+                hooks: Default::default(),
+            },
+        );
+    }
+
+    let mut content = format!(
+        "# Imported by `owl migrate-from {}` from {}\n\n",
+        source.name(),
+        path.display()
+    );
+    if !config.packages.is_empty() {
+        content.push_str(&crate::commands::clean::optimize_config(&config));
+        content.push('\n');
+    }
+    if !unknown.is_empty() {
+        content.push_str("\n# Not found in the repos or the AUR; fix the names and uncomment\n");
+        for package in unknown {
+            content.push_str(&format!("# @package {}\n", package));
+            for mapping in import.dotfiles.get(package).into_iter().flatten() {
+                content.push_str(&format!("# :config {}\n", mapping));
+            }
+        }
+    }
+    content
+}
+
+fn write(
+    source_dir: &Path,
+    group: &str,
+    group_file: &Path,
+    main_config: &Path,
+    import: &Import,
+    content: &str,
+) -> Result<Written> {
+    let main_before = std::fs::read_to_string(main_config)
+        .with_context(|| format!("Failed to read {}", main_config.display()))?;
+    let mut written = Written {
+        group_file: group_file.to_path_buf(),
+        main_config: main_config.to_path_buf(),
+        main_before: main_before.clone(),
+        dotfile_dirs: Vec::new(),
+    };
+
+    let result = (|| -> Result<()> {
+        let dotfiles = crate::internal::files::owl_dir()?.join(constants::DOTFILES_DIR);
+        let mut packages: Vec<&String> = import.dotfiles.keys().collect();
+        packages.sort();
+        for package in packages {
+            let target = dotfiles.join(package);
+            written.dotfile_dirs.push(target.clone());
+            crate::core::dotfiles::copy_dir_all(&source_dir.join(package), &target)?;
+        }
+
+        if let Some(parent) = group_file.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(group_file, content)
+            .with_context(|| format!("Failed to write {}", group_file.display()))?;
+
+        let mut doc = Document::parse(&main_before);
+        doc.add_group(group);
+        doc.save(main_config)
+    })();
+    if let Err(err) = result {
+        undo(&written)?;
+        return Err(err);
+    }
+    Ok(written)
+}
+
+fn undo(written: &Written) -> Result<()> {
+    std::fs::write(&written.main_config, &written.main_before)
+        .with_context(|| format!("Failed to restore {}", written.main_config.display()))?;
+    if written.group_file.exists() {
+        std::fs::remove_file(&written.group_file)
+            .with_context(|| format!("Failed to remove {}", written.group_file.display()))?;
+    }
+    for dir in &written.dotfile_dirs {
+        if dir.exists() {
+            std::fs::remove_dir_all(dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn confirm(count: usize) -> Result<bool> {
+    print!(
+        "  -> Keep the migration and mark {} package{} as managed? (y/N): ",
+        count,
+        if count == 1 { "" } else { "s" }
+    );
+    std::io::stdout().flush().ok();
+    let answer = crate::cli::ui::read_answer("the migration confirmation")?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_validate_group_keeps_the_file_in_groups() {
+        assert!(validate_group("pkglist").is_ok());
+        assert!(validate_group("dev-tools_2.0").is_ok());
+        for bad in ["", "../main", "dev/tools", ".hidden", "a b"] {
+            assert!(validate_group(bad).is_err(), "{:?} was accepted", bad);
+        }
+    }
+
+    #[test]
+    fn test_review_lines_summarize_the_plan() {
+        let plan = crate::core::plan::Plan {
+            installs: vec!["htop".to_string(), "neovim".to_string()],
+            removals: vec!["nano".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            review_lines(&plan),
+            ["install 2 packages: htop, neovim", "remove 1 package: nano"]
+        );
+        assert_eq!(
+            review_lines(&crate::core::plan::Plan::default()),
+            ["change nothing"]
+        );
+    }
+
+    #[test]
+    fn test_parse_pkglist_handles_comments_versions_and_duplicates() {
+        let content = "# base\nhtop\n\nneovim  # editor\nhtop\nyay-bin 12.3.5-1\n   \n";
+        assert_eq!(parse_pkglist(content), vec!["htop", "neovim", "yay-bin"]);
+    }
+
+    #[test]
+    fn test_parse_hook_keeps_plain_package_targets() {
+        let content = "\
+[Trigger]
+Operation = Upgrade
+Type = Package
+Target = linux
+Target = nvidia-*
+Target = !linux-lts
+
+[Trigger]
+Type = Path
+Target = usr/lib/modules/*
+
+[Action]
+Target = ignored
+Exec = /usr/bin/mkinitcpio -P
+";
+        assert_eq!(parse_hook(content), vec!["linux"]);
+    }
+
+    #[test]
+    fn test_stow_packages_map_below_shared_dirs() {
+        let dir = tempdir().unwrap();
+        for file in [
+            "nvim/.config/nvim/init.lua",
+            "zsh/.zshrc",
+            "zsh/dot-zprofile",
+            "zsh/.stow-local-ignore",
+            "scripts/.local/bin/backup",
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "x").unwrap();
+        }
+        std::fs::create_dir_all(dir.path().join(".git")).unwrap();
+
+        let import = read_stow(dir.path()).unwrap();
+        assert_eq!(import.packages, vec!["nvim", "scripts", "zsh"]);
+        assert_eq!(
+            import.dotfiles["nvim"],
+            vec!["nvim/.config/nvim -> ~/.config/nvim"]
+        );
+        assert_eq!(
+            import.dotfiles["scripts"],
+            vec!["scripts/.local/bin/backup -> ~/.local/bin/backup"]
+        );
+        assert_eq!(
+            import.dotfiles["zsh"],
+            vec!["zsh/.zshrc -> ~/.zshrc", "zsh/dot-zprofile -> ~/.zprofile"]
+        );
+    }
+
+    #[test]
+    fn test_render_comments_out_unknown_packages() {
+        let import = Import {
+            packages: vec![
+                "htop".to_string(),
+                "nvim".to_string(),
+                "scripts".to_string(),
+            ],
+            dotfiles: HashMap::from([
+                (
+                    "nvim".to_string(),
+                    vec!["nvim/.config/nvim -> ~/.config/nvim".to_string()],
+                ),
+                (
+                    "scripts".to_string(),
+                    vec!["scripts/.local/bin/backup -> ~/.local/bin/backup".to_string()],
+                ),
+            ]),
+        };
+        let content = render(
+            Source::Stow,
+            Path::new("/home/me/dotfiles"),
+            &import,
+            &["scripts".to_string()],
+        );
+        assert_eq!(
+            content,
+            "\
+# Imported by `owl migrate-from stow` from /home/me/dotfiles
+
+@pkg nvim
+:cfg nvim/.config/nvim -> ~/.config/nvim
+
+@pkgs
+htop
+
+# Not found in the repos or the AUR; fix the names and uncomment
+# @package scripts
+# :config scripts/.local/bin/backup -> ~/.local/bin/backup
+"
+        );
+        let config = Config::parse(&content).unwrap();
+        assert_eq!(config.packages.len(), 2);
+    }
+}
@@ -1,7 +1,39 @@
 pub mod add;
 pub mod adopt;
 pub mod apply;
+pub mod bootstrap;
 pub mod clean;
+pub mod deps;
+pub mod diff;
+pub mod doctor;
 pub mod dots;
 pub mod edit;
+pub mod exec;
+pub mod export;
 pub mod find;
+pub mod fleet;
+pub mod history;
+pub mod ignore;
+pub mod info;
+pub mod init;
+pub mod integrate;
+pub mod list;
+pub mod migrate;
+pub mod orphans;
+pub mod outdated;
+pub mod pin;
+pub mod prompt;
+pub mod remove;
+pub mod schedule;
+pub mod search;
+pub mod snapshot;
+pub mod state;
+pub mod stats;
+pub mod status;
+pub mod sync;
+pub mod test_apply;
+pub mod ui;
+pub mod untrack;
+pub mod verify;
+pub mod version;
+pub mod why;
@@ -0,0 +1,194 @@
+//! Review packages installed as dependencies that nothing needs any more
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+use crate::cli::ui::{SelectDefault, format_size, select_items};
+use crate::core::pacman_log::InstallOrigin;
+use crate::core::pm::{PackageInfo, ParuPacman};
+use crate::core::state::PackageState;
+use crate::internal::color;
+
+/// What the user decided for each orphan; anything undecided is listed again next time
+#[derive(Debug, Default)]
+struct Decisions {
+    remove: Vec<String>,
+    keep: Vec<String>,
+    adopt: Vec<String>,
+    undecided: Vec<String>,
+}
+
+/// Run the orphans command
+pub fn run(flags: &crate::cli::handler::GlobalFlags) {
+    crate::error::exit_on_error(review(flags.dry_run, flags.non_interactive));
+}
+
+fn review(dry_run: bool, non_interactive: bool) -> Result<()> {
+    let pm = ParuPacman::new();
+    let orphans = pm.orphans()?;
+    println!("[{}]", color::blue("orphans"));
+    if orphans.is_empty() {
+        println!("  {} no orphaned dependencies", color::green("✓"));
+        return Ok(());
+    }
+
+    let info = crate::core::package::get_package_info(&orphans)?;
+    let origins = crate::core::pacman_log::install_origins(&orphans).unwrap_or_else(|err| {
+        log::debug!("not showing install origins: {:#}", err);
+        HashMap::new()
+    });
+    let total: u64 = info.values().map(|info| info.installed_size).sum();
+    println!(
+        "  {} {} orphaned dependenc{}, {} installed",
+        color::blue("info:"),
+        orphans.len(),
+        if orphans.len() == 1 { "y" } else { "ies" },
+        format_size(total)
+    );
+    let lines = describe(&orphans, &info, &origins);
+
+    let decisions = decide(&orphans, &lines)?;
+    if dry_run {
+        for (verb, names) in [
+            ("remove", &decisions.remove),
+            ("keep", &decisions.keep),
+            ("adopt", &decisions.adopt),
+        ] {
+            if !names.is_empty() {
+                println!(
+                    "  {} would {} {}",
+                    color::blue("info:"),
+                    verb,
+                    names.join(", ")
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    for name in &decisions.adopt {
+        crate::commands::add::add_package_to_config(name)?;
+        state.add_managed(name.clone());
+    }
+    // Kept orphans become explicit so pacman stops listing them, and untracked so adopt
+    // does not offer them again
+    for name in &decisions.keep {
+        state.add_untracked(name.clone());
+    }
+    let explicit: Vec<String> = decisions
+        .keep
+        .iter()
+        .chain(&decisions.adopt)
+        .cloned()
+        .collect();
+    pm.mark_explicit(&explicit)?;
+    if !explicit.is_empty() {
+        state.save().context("Failed to save package state")?;
+    }
+
+    if !decisions.remove.is_empty() {
+        pm.remove_packages(&decisions.remove, non_interactive)?;
+    }
+
+    if !decisions.keep.is_empty() {
+        println!("  {} kept {}", color::green("✓"), decisions.keep.join(", "));
+    }
+    if !decisions.adopt.is_empty() {
+        println!(
+            "  {} adopted {}",
+            color::green("✓"),
+            decisions.adopt.join(", ")
+        );
+    }
+    if !decisions.undecided.is_empty() {
+        println!(
+            "  {} left for later: {}",
+            color::blue("info:"),
+            decisions.undecided.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Ask which orphans to remove, then which of the rest to keep, then which to adopt
+fn decide(orphans: &[String], lines: &[String]) -> Result<Decisions> {
+    let mut remaining: Vec<usize> = (0..orphans.len()).collect();
+    let mut pick = |prompt: &str| -> Result<Vec<String>> {
+        if remaining.is_empty() {
+            return Ok(Vec::new());
+        }
+        let shown: Vec<String> = remaining.iter().map(|idx| lines[*idx].clone()).collect();
+        let selected = select_items(&shown, prompt, SelectDefault::None)?;
+        let chosen: Vec<usize> = selected.iter().map(|idx| remaining[*idx]).collect();
+        remaining.retain(|idx| !chosen.contains(idx));
+        Ok(chosen.iter().map(|idx| orphans[*idx].clone()).collect())
+    };
+
+    let remove = pick("Select packages to remove (e.g. 1-3,5 / all / none / /filter)")?;
+    let keep = pick("Select packages to keep (marked explicit)")?;
+    let adopt = pick("Select packages to adopt into the config")?;
+    Ok(Decisions {
+        remove,
+        keep,
+        adopt,
+        undecided: remaining.iter().map(|idx| orphans[*idx].clone()).collect(),
+    })
+}
+
+/// One aligned line per orphan: name, size, and the transaction that installed it
+fn describe(
+    orphans: &[String],
+    info: &HashMap<String, PackageInfo>,
+    origins: &HashMap<String, InstallOrigin>,
+) -> Vec<String> {
+    let width = orphans.iter().map(String::len).max().unwrap_or(0);
+    orphans
+        .iter()
+        .map(|name| {
+            let mut line = format!("{:<width$}", name, width = width);
+            if let Some(info) = info.get(name) {
+                line.push_str(&format!("  {:>10}", format_size(info.installed_size)));
+            }
+            if let Some(origin) = origins.get(name) {
+                line.push_str(&format!(
+                    "  installed {} by `{}`",
+                    origin.date, origin.command
+                ));
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_aligns_and_tolerates_missing_details() {
+        let orphans = vec!["m4".to_string(), "libfoo".to_string()];
+        let info = HashMap::from([(
+            "m4".to_string(),
+            PackageInfo {
+                name: "m4".to_string(),
+                installed_size: 2048,
+                ..PackageInfo::default()
+            },
+        )]);
+        let origins = HashMap::from([(
+            "libfoo".to_string(),
+            InstallOrigin {
+                date: "2024-05-01".to_string(),
+                command: "pacman -S firefox".to_string(),
+            },
+        )]);
+        assert_eq!(
+            describe(&orphans, &info, &origins),
+            vec![
+                "m4        2.00 KiB",
+                "libfoo  installed 2024-05-01 by `pacman -S firefox`",
+            ]
+        );
+    }
+}
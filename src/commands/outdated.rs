@@ -0,0 +1,208 @@
+//! Read-only list of pending updates, without syncing the system databases or sudo
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::core::config::Config;
+use crate::core::pm::{ParuPacman, PendingUpdate};
+use crate::core::state::PackageState;
+use crate::internal::color;
+
+/// One pending update
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Outdated {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    /// "repo" or "aur"
+    pub source: &'static str,
+    /// Declared in the config, rather than pulled in as a dependency
+    pub configured: bool,
+    /// The version a pin holds the package at; apply skips these
+    pub pinned: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    updates: Vec<Outdated>,
+    /// False when neither checkupdates nor fakeroot was available, so the repo
+    /// updates come from the databases as of the last sync
+    repo_synced: bool,
+    /// False when the AUR could not be reached
+    aur_checked: bool,
+}
+
+/// Run the outdated command
+pub fn run(json: bool, count: bool) {
+    crate::core::state::forbid_writes();
+    let report = if json || count {
+        collect()
+    } else {
+        crate::internal::util::execute_with_progress(collect, "Checking for updates")
+    };
+    let report = match report {
+        Ok(report) => report,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+
+    if count {
+        let pending = report
+            .updates
+            .iter()
+            .filter(|update| update.pinned.is_none())
+            .count();
+        println!("{}", pending);
+    } else if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(out) => println!("{}", out),
+            Err(err) => crate::error::exit_with_error(err.into()),
+        }
+    } else {
+        print_report(&report);
+    }
+}
+
+fn collect() -> Result<Report> {
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    let state = PackageState::load().context("Failed to load package state")?;
+    let pm = ParuPacman::new();
+    let (repo, repo_synced) = pm.synced_repo_updates()?;
+
+    let foreign = crate::core::system::PackageQueries::foreign_versions(
+        &crate::core::system::Backend::select(),
+    )?;
+    let (aur, aur_checked) = match crate::core::aur_cache::updates(&foreign) {
+        Ok(updates) => (updates, true),
+        Err(err) => {
+            log::debug!("outdated: AUR check failed: {:#}", err);
+            (Vec::new(), false)
+        }
+    };
+
+    Ok(Report {
+        updates: annotate(repo, aur, &config, &state),
+        repo_synced,
+        aur_checked,
+    })
+}
+
+fn annotate(
+    repo: Vec<PendingUpdate>,
+    aur: Vec<PendingUpdate>,
+    config: &Config,
+    state: &PackageState,
+) -> Vec<Outdated> {
+    let mut updates: Vec<Outdated> = repo
+        .into_iter()
+        .map(|update| (update, "repo"))
+        .chain(aur.into_iter().map(|update| (update, "aur")))
+        .map(|(update, source)| Outdated {
+            configured: config.packages.contains_key(&update.name),
+            pinned: state.pinned_version(&update.name).map(str::to_string),
+            name: update.name,
+            from: update.from,
+            to: update.to,
+            source,
+        })
+        .collect();
+    updates.sort_by(|a, b| a.name.cmp(&b.name));
+    updates
+}
+
+fn print_report(report: &Report) {
+    println!("[{}]", color::blue("outdated"));
+    if !report.repo_synced {
+        println!(
+            "  {} install pacman-contrib or fakeroot for fresh results; repo updates are as of the last sync",
+            color::yellow("!")
+        );
+    }
+    if !report.aur_checked {
+        println!(
+            "  {} could not reach the AUR; AUR updates are not shown",
+            color::yellow("!")
+        );
+    }
+    if report.updates.is_empty() {
+        println!("  {} everything is up to date", color::green("✓"));
+        return;
+    }
+
+    let mut table = crate::cli::ui::Table::new(&["name", "version", "source", "note"]);
+    for update in &report.updates {
+        let note = match &update.pinned {
+            Some(version) => format!("pinned at {}, held", version),
+            None if update.configured => "configured".to_string(),
+            None => "dependency".to_string(),
+        };
+        table.add_row(vec![
+            update.name.clone(),
+            format!("{} -> {}", update.from, update.to),
+            update.source.to_string(),
+            note,
+        ]);
+    }
+    table.print();
+
+    let configured = report
+        .updates
+        .iter()
+        .filter(|update| update.configured)
+        .count();
+    println!(
+        "\n  {} {} update{}, {} to configured packages",
+        color::blue("info:"),
+        report.updates.len(),
+        if report.updates.len() == 1 { "" } else { "s" },
+        configured
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(name: &str, from: &str, to: &str) -> PendingUpdate {
+        PendingUpdate {
+            name: name.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_annotate_marks_configured_and_pinned() {
+        let config = Config::parse("@packages\nfirefox\nparu\n").unwrap();
+        let mut state = PackageState {
+            untracked: Vec::new(),
+            hidden: Vec::new(),
+            managed: Vec::new(),
+            pinned: Default::default(),
+            sources: Default::default(),
+            remove_hooks: Default::default(),
+        };
+        state.pin("firefox".to_string(), "126.0-1".to_string());
+
+        let updates = annotate(
+            vec![
+                update("firefox", "126.0-1", "127.0-1"),
+                update("glibc", "2.39-1", "2.39-2"),
+            ],
+            vec![update("paru", "2.0.3-1", "2.0.4-1")],
+            &config,
+            &state,
+        );
+        let summary: Vec<(&str, &str, bool, Option<&str>)> = updates
+            .iter()
+            .map(|u| (u.name.as_str(), u.source, u.configured, u.pinned.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("firefox", "repo", true, Some("126.0-1")),
+                ("glibc", "repo", false, None),
+                ("paru", "aur", true, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Hold packages at their installed version across applies
+//!
+//! These pins live in state: a pin made here describes this machine rather than the
+//! setup shared between hosts. Pins meant for every host go under `@pin` in the config.
+
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+
+use crate::core::config::Config;
+use crate::core::pm::PendingUpdate;
+use crate::core::state::PackageState;
+use crate::internal::color;
+
+/// Run the pin command: pin `package`, or list the pins
+pub fn run(package: Option<&str>, list: bool, flags: &crate::cli::handler::GlobalFlags) {
+    let result = match package {
+        Some(package) if !list => pin(package, flags.dry_run),
+        _ => list_pins(),
+    };
+    crate::error::exit_on_error(result);
+}
+
+/// Run the unpin command
+pub fn run_unpin(package: &str, flags: &crate::cli::handler::GlobalFlags) {
+    crate::error::exit_on_error(unpin(package, flags.dry_run));
+}
+
+fn pin(package: &str, dry_run: bool) -> Result<()> {
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    if !config.packages.contains_key(package) {
+        return Err(anyhow!(
+            "'{}' is not in the config; add it with `owl add {}` before pinning it",
+            package,
+            package
+        ));
+    }
+    let version = crate::core::package::get_package_info(&[package.to_string()])?
+        .remove(package)
+        .map(|info| info.version)
+        .ok_or_else(|| {
+            anyhow!(
+                "'{}' is not installed; run `owl` to install it, then pin the version you get",
+                package
+            )
+        })?;
+
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    println!("[{}]", color::blue("pin"));
+    if state.pinned_version(package) == Some(version.as_str()) {
+        println!(
+            "  {} {} is already pinned at {}",
+            color::green("✓"),
+            package,
+            version
+        );
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "  {} would pin {} at {}",
+            color::blue("info:"),
+            package,
+            version
+        );
+        return Ok(());
+    }
+    state.pin(package.to_string(), version.clone());
+    state.save().context("Failed to save package state")?;
+    println!("  {} pinned {} at {}", color::green("✓"), package, version);
+    println!(
+        "  {} apply holds back its updates until `owl unpin {}`",
+        color::blue("info:"),
+        package
+    );
+    Ok(())
+}
+
+fn unpin(package: &str, dry_run: bool) -> Result<()> {
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    let Some(version) = state.pinned_version(package).map(str::to_string) else {
+        return Err(anyhow!("'{}' is not pinned", package));
+    };
+    println!("[{}]", color::blue("unpin"));
+    if dry_run {
+        println!(
+            "  {} would unpin {} (pinned at {})",
+            color::blue("info:"),
+            package,
+            version
+        );
+        return Ok(());
+    }
+    state.unpin(package);
+    state.save().context("Failed to save package state")?;
+    println!(
+        "  {} unpinned {} (was pinned at {}); the next apply updates it",
+        color::green("✓"),
+        package,
+        version
+    );
+    Ok(())
+}
+
+fn list_pins() -> Result<()> {
+    crate::core::state::forbid_writes();
+    let state = PackageState::load().context("Failed to load package state")?;
+    println!("[{}]", color::blue("pins"));
+    if state.pinned.is_empty() {
+        println!(
+            "  {} no pinned packages; pin one with `owl pin <package>`",
+            color::blue("info:")
+        );
+        return Ok(());
+    }
+
+    let names = state.pinned_names();
+    let installed = crate::core::package::get_package_info(&names)?;
+    let pm = crate::core::pm::ParuPacman::new();
+    let mut updates = pm.repo_updates()?;
+    if crate::core::pm::aur_helper_command().is_some() {
+        match pm.aur_updates() {
+            Ok(aur) => updates.extend(aur),
+            Err(err) => log::debug!("not checking AUR updates for pins: {:#}", err),
+        }
+    }
+    let updates: HashMap<&str, &PendingUpdate> = updates
+        .iter()
+        .map(|update| (update.name.as_str(), update))
+        .collect();
+
+    let mut table =
+        crate::cli::ui::Table::new(&["name", "pinned", "installed", "available", "status"]);
+    for (name, pinned) in &state.pinned {
+        let installed = installed.get(name).map(|info| info.version.as_str());
+        let available = updates.get(name.as_str()).map(|update| update.to.as_str());
+        table.add_row(vec![
+            name.clone(),
+            pinned.clone(),
+            installed.unwrap_or("-").to_string(),
+            available.unwrap_or("-").to_string(),
+            pin_status(pinned, installed, available).to_string(),
+        ]);
+    }
+    table.print();
+    Ok(())
+}
+
+/// How a pin compares with what is installed and what the repos offer
+fn pin_status(pinned: &str, installed: Option<&str>, available: Option<&str>) -> &'static str {
+    match (installed, available) {
+        (None, _) => "not installed",
+        (Some(installed), _) if installed != pinned => "changed outside owl",
+        (_, Some(_)) => "behind",
+        _ => "current",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_status() {
+        assert_eq!(pin_status("1.0-1", Some("1.0-1"), None), "current");
+        assert_eq!(pin_status("1.0-1", Some("1.0-1"), Some("1.1-1")), "behind");
+        assert_eq!(
+            pin_status("1.0-1", Some("1.1-1"), None),
+            "changed outside owl"
+        );
+        assert_eq!(pin_status("1.0-1", None, Some("1.1-1")), "not installed");
+    }
+}
@@ -0,0 +1,88 @@
+//! A status segment for shell prompts
+//!
+//! `owl prompt` only reads the snapshot the last `owl status` or `owl prompt
+//! --refresh` stored, so it costs a file read. The refresh does the real check and
+//! belongs on a timer or in the background, e.g. `owl prompt --refresh &!` in zsh.
+
+use crate::core::status_cache::{self, StatusSnapshot};
+
+/// How color codes are written into the segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PromptColor {
+    /// Plain text
+    Never,
+    /// Raw ANSI codes
+    Ansi,
+    /// ANSI codes wrapped in `\[ \]` for bash's PS1
+    Bash,
+    /// ANSI codes wrapped in `%{ %}` for zsh's PROMPT
+    Zsh,
+}
+
+impl PromptColor {
+    fn paint(self, code: &str, text: &str) -> String {
+        let (open, close) = match self {
+            PromptColor::Never => return text.to_string(),
+            PromptColor::Ansi => ("", ""),
+            PromptColor::Bash => ("\\[", "\\]"),
+            PromptColor::Zsh => ("%{", "%}"),
+        };
+        format!("{open}\x1b[{code}m{close}{text}{open}\x1b[0m{close}")
+    }
+}
+
+/// Run `owl prompt`
+pub fn run(refresh: bool, color: PromptColor) {
+    if refresh {
+        // Errors would end up in the prompt; the next refresh tries again
+        if let Err(err) = crate::commands::status::collect(false) {
+            log::debug!("prompt refresh: {:#}", err);
+        }
+        return;
+    }
+    if let Some(snapshot) = status_cache::read() {
+        print!("{}", segment(&snapshot, color));
+    }
+}
+
+/// `owl:3↑ 1✎` style text for what is out of sync; empty when nothing is
+fn segment(snapshot: &StatusSnapshot, color: PromptColor) -> String {
+    if snapshot.in_sync() {
+        return String::new();
+    }
+    let parts: Vec<String> = [
+        (snapshot.installs, "+", "32"),
+        (snapshot.removals, "-", "31"),
+        (snapshot.updates, "↑", "33"),
+        (snapshot.dotfile_drift, "✎", "34"),
+    ]
+    .into_iter()
+    .filter(|(count, _, _)| *count > 0)
+    .map(|(count, mark, code)| color.paint(code, &format!("{}{}", count, mark)))
+    .collect();
+    format!("owl:{}", parts.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_lists_what_is_out_of_sync() {
+        let snapshot = StatusSnapshot {
+            updates: 3,
+            dotfile_drift: 1,
+            ..Default::default()
+        };
+        assert_eq!(segment(&snapshot, PromptColor::Never), "owl:3↑ 1✎");
+        assert_eq!(segment(&StatusSnapshot::default(), PromptColor::Zsh), "");
+        let installs = StatusSnapshot {
+            installs: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            segment(&installs, PromptColor::Bash),
+            "owl:\\[\x1b[32m\\]2+\\[\x1b[0m\\]"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Stop managing a package: the inverse of adopt
+
+use anyhow::{Context, Result, anyhow};
+use std::io::Write;
+
+use crate::core::config::Config;
+use crate::core::config::document::{Document, removal_diff};
+use crate::core::state::PackageState;
+use crate::internal::color;
+
+/// What to do with the package after it leaves the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FollowUp {
+    KeepInstalled,
+    Purge,
+    Ask,
+}
+
+/// Run the remove command
+pub fn run(
+    package: &str,
+    keep_installed: bool,
+    purge: bool,
+    flags: &crate::cli::handler::GlobalFlags,
+) {
+    let follow_up = if purge {
+        FollowUp::Purge
+    } else if keep_installed || flags.non_interactive {
+        FollowUp::KeepInstalled
+    } else {
+        FollowUp::Ask
+    };
+    crate::error::exit_on_error(remove(package, follow_up, flags.dry_run));
+}
+
+fn remove(package: &str, follow_up: FollowUp, dry_run: bool) -> Result<()> {
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    let path = config
+        .provenance
+        .get(package)
+        .and_then(|provenance| provenance.path.clone())
+        .ok_or_else(|| anyhow!("'{}' is not declared in any config file", package))?;
+    let friendly = crate::internal::files::friendly_path(&path.display().to_string());
+
+    let mut document = Document::load(&path)?;
+    let removed = document.remove_package(package);
+    if removed.is_empty() {
+        return Err(anyhow!("could not find '{}' in {}", package, friendly));
+    }
+
+    println!("[{}]", color::blue("remove"));
+    println!("  {} declared in {}", color::blue("info:"), friendly);
+    for (line_number, line) in &removed {
+        println!(
+            "    {} {}",
+            color::dim(&format!("{:>4} |", line_number)),
+            line
+        );
+    }
+
+    let declared = &config.packages[package];
+    let mappings: Vec<_> = declared
+        .config
+        .iter()
+        .map(|cfg| crate::core::dotfiles::parse_mapping(cfg))
+        .collect();
+
+    if dry_run {
+        println!();
+        for line in removal_diff(&friendly, &removed) {
+            println!("  {}", crate::internal::diff::colorize(&line));
+        }
+        println!(
+            "\n  {} would stop managing {} in the package state",
+            color::blue("info:"),
+            package
+        );
+        return Ok(());
+    }
+
+    // Load the state first so a broken state file stops us before the config is edited
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    document.save(&path)?;
+    println!(
+        "  {} removed {} from {}",
+        color::green("✓"),
+        package,
+        friendly
+    );
+    crate::core::autocommit::commit_config_change(
+        std::slice::from_ref(&path),
+        &format!(
+            "owl remove: drop {} from {}",
+            package,
+            crate::core::autocommit::describe(&path)
+        ),
+    );
+
+    state.remove_managed(package);
+    state.unpin(package);
+    state.save().context("Failed to save package state")?;
+
+    let purge = match follow_up {
+        FollowUp::Purge => true,
+        FollowUp::KeepInstalled => false,
+        FollowUp::Ask => {
+            print!(
+                "  -> Also uninstall {} and remove its dotfiles and services? (y/N): ",
+                package
+            );
+            std::io::stdout().flush().ok();
+            let answer = crate::cli::ui::read_answer("the uninstall prompt")?;
+            matches!(answer.to_lowercase().as_str(), "y" | "yes")
+        }
+    };
+    if !purge {
+        println!(
+            "  {} {} stays installed; `owl adopt {}` manages it again",
+            color::blue("info:"),
+            package,
+            package
+        );
+        return Ok(());
+    }
+
+    if let Some(service) = &declared.service {
+        crate::core::services::disable_service(service)?;
+        println!("  {} disabled {}", color::green("✓"), service);
+    }
+    for mapping in &mappings {
+        if crate::core::dotfiles::remove_deployed(mapping)? {
+            println!("  {} removed {}", color::green("✓"), mapping.destination);
+        }
+    }
+    if crate::core::package::is_package_installed(package)? {
+        crate::core::package::remove_unmanaged_packages(&[package.to_string()], false)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,154 @@
+//! Run owl unattended from a systemd timer
+
+use anyhow::{Result, anyhow};
+
+use crate::cli::handler::GlobalFlags;
+use crate::core::schedule::{self, Scope};
+use crate::internal::color;
+use crate::internal::logging::LogCommand;
+
+fn shown(scope: Scope) -> Result<String> {
+    Ok(crate::internal::files::friendly_path(
+        &scope.unit_dir()?.display().to_string(),
+    ))
+}
+
+fn require_root(scope: Scope) -> Result<()> {
+    if scope == Scope::System && !crate::core::privilege::is_root() {
+        return Err(anyhow!(
+            "system units live in /etc/systemd/system; rerun with sudo, or drop --system"
+        ));
+    }
+    Ok(())
+}
+
+/// Run `owl schedule enable`
+pub fn run_enable(interval: Option<&str>, scope: Scope, flags: &GlobalFlags) {
+    crate::error::exit_on_error(enable(interval, scope, flags.dry_run));
+}
+
+fn enable(interval: Option<&str>, scope: Scope, dry_run: bool) -> Result<()> {
+    let settings = &crate::core::settings::get().schedule;
+    let interval = schedule::parse_interval(interval.unwrap_or(&settings.interval))?;
+    let units = schedule::render(&schedule::Invocation::current()?, &interval);
+
+    println!("[{}]", color::blue("schedule"));
+    if dry_run {
+        println!(
+            "  {} would write {} and {} to {}, then enable the timer",
+            color::green("➔"),
+            schedule::SERVICE,
+            schedule::TIMER,
+            shown(scope)?
+        );
+        return Ok(());
+    }
+    require_root(scope)?;
+    schedule::write(scope, &units)?;
+    schedule::systemctl(scope, &["enable", "--now", schedule::TIMER])?;
+    println!(
+        "  {} owl runs every {} ({})",
+        color::green("✓"),
+        interval,
+        settings.args.join(" ")
+    );
+    println!(
+        "  {} units are in {}; `owl status` shows the last scheduled run",
+        color::blue("info:"),
+        shown(scope)?
+    );
+    Ok(())
+}
+
+/// Run `owl schedule disable`
+pub fn run_disable(scope: Scope, flags: &GlobalFlags) {
+    crate::error::exit_on_error(disable(scope, flags.dry_run));
+}
+
+fn disable(scope: Scope, dry_run: bool) -> Result<()> {
+    println!("[{}]", color::blue("schedule"));
+    if schedule::installed_interval(scope)?.is_none() {
+        println!("  {} no owl timer is installed", color::blue("info:"));
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "  {} would stop the timer and remove its units from {}",
+            color::green("➔"),
+            shown(scope)?
+        );
+        return Ok(());
+    }
+    require_root(scope)?;
+    schedule::systemctl(scope, &["disable", "--now", schedule::TIMER])?;
+    schedule::remove(scope)?;
+    println!("  {} removed the owl timer", color::green("✓"));
+    Ok(())
+}
+
+/// Run `owl schedule status`
+pub fn run_status(scope: Scope) {
+    crate::core::state::forbid_writes();
+    crate::error::exit_on_error(status(scope));
+}
+
+fn status(scope: Scope) -> Result<()> {
+    println!("[{}]", color::blue("schedule"));
+    let Some(interval) = schedule::installed_interval(scope)? else {
+        println!(
+            "  {} no owl timer is installed; `owl schedule enable` adds one",
+            color::blue("info:")
+        );
+        return Ok(());
+    };
+    let timer = scope
+        .systemctl()
+        .args(["is-enabled", "--quiet", schedule::TIMER])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .logged()
+        .status()
+        .is_ok_and(|status| status.success());
+    let mark = if timer {
+        color::green("✓")
+    } else {
+        color::yellow("!")
+    };
+    println!(
+        "  {} timer every {}, {}",
+        mark,
+        interval,
+        if timer { "enabled" } else { "disabled" }
+    );
+
+    let units = schedule::render(&schedule::Invocation::current()?, &interval);
+    if schedule::is_stale(scope, &units)? {
+        println!(
+            "  {} the units differ from the settings; the next apply regenerates them",
+            color::yellow("!")
+        );
+    }
+
+    let last = crate::core::history::load_runs()?
+        .into_iter()
+        .find(|run| run.scheduled);
+    match last {
+        Some(run) => println!(
+            "  {} last run: {}, {}",
+            color::blue("info:"),
+            crate::commands::status::format_age(
+                crate::core::history::now().saturating_sub(run.started_at)
+            ),
+            outcome_label(run.outcome)
+        ),
+        None => println!("  {} last run: none recorded yet", color::blue("info:")),
+    }
+    Ok(())
+}
+
+pub fn outcome_label(outcome: crate::core::history::Outcome) -> String {
+    match outcome {
+        crate::core::history::Outcome::Success => color::green("success"),
+        crate::core::history::Outcome::Incomplete => color::red("did not finish"),
+    }
+}
@@ -0,0 +1,221 @@
+//! Search repos, the AUR, and the config in one go
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+
+use crate::core::config::Config;
+use crate::core::pm::{PackageSource, SearchResult};
+use crate::core::search::{matches_terms, normalize_terms};
+use crate::internal::color;
+
+/// A search result merged with what owl knows about it
+#[derive(Debug, Clone, PartialEq)]
+struct Hit {
+    name: String,
+    version: String,
+    /// `None` for packages only found in the config
+    source: Option<PackageSource>,
+    /// Repo name, `aur`, or `config`
+    origin: String,
+    description: String,
+    installed: bool,
+    configured: bool,
+    votes: Option<u32>,
+    popularity: Option<f64>,
+}
+
+impl Hit {
+    fn from_result(result: SearchResult, configured: &HashSet<String>) -> Self {
+        Self {
+            configured: configured.contains(&result.name),
+            name: result.name,
+            version: result.ver,
+            source: Some(result.source),
+            origin: result.repo,
+            description: result.description,
+            installed: result.installed,
+            votes: result.votes,
+            popularity: result.popularity,
+        }
+    }
+
+    fn is_repo(&self) -> bool {
+        self.source == Some(PackageSource::Repo)
+    }
+}
+
+/// Run the search command; with `add`, offer to add a result to a config file
+pub fn run(terms: &[String], add: bool) {
+    crate::error::exit_on_error(search(terms, add));
+}
+
+fn search(terms: &[String], add: bool) -> Result<()> {
+    let hits = crate::internal::util::execute_with_progress(
+        {
+            let terms = terms.to_vec();
+            move || collect(&terms)
+        },
+        "Searching",
+    )?;
+    if hits.is_empty() {
+        println!(
+            "{}",
+            color::yellow("No packages found matching the search terms")
+        );
+        return Ok(());
+    }
+
+    // Best match last, right above the prompt
+    println!();
+    for (idx, hit) in hits.iter().enumerate().rev() {
+        println!("{}", format_hit(idx, hit));
+    }
+    println!();
+
+    if !add {
+        return Ok(());
+    }
+    let names: Vec<String> = hits.iter().rev().map(|hit| hit.name.clone()).collect();
+    match crate::commands::add::prompt_package_selection(&names)? {
+        Some(name) => crate::commands::add::add_package_to_config(&name),
+        None => {
+            println!("{}", color::yellow("No package selected"));
+            Ok(())
+        }
+    }
+}
+
+fn collect(terms: &[String]) -> Result<Vec<Hit>> {
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    let configured: HashSet<String> = config.packages.keys().cloned().collect();
+    let normalized = normalize_terms(terms);
+
+    let mut hits: Vec<Hit> = crate::core::package::search_packages(terms)
+        .context("Search failed")?
+        .into_iter()
+        .map(|result| Hit::from_result(result, &configured))
+        .collect();
+
+    let found: HashSet<String> = hits.iter().map(|hit| hit.name.clone()).collect();
+    let config_only: Vec<&String> = configured
+        .iter()
+        .filter(|name| !found.contains(*name) && matches_terms(name, "", &normalized))
+        .collect();
+    if !config_only.is_empty() {
+        let installed = crate::core::package::get_installed_packages()?;
+        hits.extend(config_only.into_iter().map(|name| Hit {
+            name: name.clone(),
+            version: String::new(),
+            source: None,
+            origin: "config".to_string(),
+            description: String::new(),
+            installed: installed.contains(name),
+            configured: true,
+            votes: None,
+            popularity: None,
+        }));
+    }
+
+    hits.sort_by(|a, b| {
+        relevance(b, &normalized)
+            .cmp(&relevance(a, &normalized))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(hits)
+}
+
+/// Rough ranking: how well the name matches, then official repos, installed packages,
+/// and AUR popularity as tie-breakers
+fn relevance(hit: &Hit, terms: &[String]) -> u32 {
+    let name = hit.name.to_ascii_lowercase();
+    let name_score = if terms.join("-") == name || terms.join("") == name {
+        100
+    } else if terms
+        .first()
+        .is_some_and(|term| name.starts_with(term.as_str()))
+    {
+        60
+    } else if terms.iter().all(|term| name.contains(term.as_str())) {
+        40
+    } else if terms.iter().any(|term| name.contains(term.as_str())) {
+        20
+    } else {
+        0
+    };
+    let popularity = hit
+        .popularity
+        .map(|popularity| (popularity * 2.0).clamp(0.0, 20.0) as u32)
+        .unwrap_or(0);
+    name_score + if hit.is_repo() { 10 } else { 0 } + if hit.installed { 5 } else { 0 } + popularity
+}
+
+fn format_hit(idx: usize, hit: &Hit) -> String {
+    let origin = format!("[{}]", hit.origin);
+    let origin = if hit.is_repo() {
+        color::repository(&origin)
+    } else {
+        color::warning(&origin)
+    };
+    let mut line = format!(
+        "[{}] {} {} {}",
+        idx,
+        color::highlight(&hit.name),
+        color::success(&hit.version),
+        origin
+    );
+    if hit.installed {
+        line.push_str(&format!(" {}", color::success("[installed]")));
+    }
+    if hit.configured {
+        line.push_str(&format!(" {}", color::blue("[configured]")));
+    }
+    if let (Some(votes), Some(popularity)) = (hit.votes, hit.popularity) {
+        line.push_str(&format!(
+            " {}",
+            color::dim(&format!("({} votes, {:.2} popularity)", votes, popularity))
+        ));
+    }
+    if !hit.description.is_empty() {
+        line.push_str(&format!(" - {}", color::description(&hit.description)));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(name: &str, origin: &str, popularity: Option<f64>) -> Hit {
+        Hit {
+            name: name.to_string(),
+            version: "1.0".to_string(),
+            source: Some(if origin == "aur" {
+                PackageSource::Aur
+            } else {
+                PackageSource::Repo
+            }),
+            origin: origin.to_string(),
+            description: String::new(),
+            installed: false,
+            configured: false,
+            votes: popularity.map(|_| 10),
+            popularity,
+        }
+    }
+
+    #[test]
+    fn test_relevance_prefers_name_matches_then_repos_and_popularity() {
+        let terms = vec!["terminal".to_string()];
+        let exact = hit("terminal", "aur", Some(0.1));
+        let prefix_repo = hit("terminal-emulator", "extra", None);
+        let prefix_aur = hit("terminal-emulator-git", "aur", Some(0.0));
+        let popular_aur = hit("terminal-x", "aur", Some(8.0));
+        let description_only = hit("kitty", "extra", None);
+
+        let score = |hit: &Hit| relevance(hit, &terms);
+        assert!(score(&exact) > score(&popular_aur));
+        assert!(score(&popular_aur) > score(&prefix_repo));
+        assert!(score(&prefix_repo) > score(&prefix_aur));
+        assert!(score(&prefix_aur) > score(&description_only));
+    }
+}
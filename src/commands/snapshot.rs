@@ -0,0 +1,378 @@
+//! Create, inspect, and restore snapshots of the managed surface
+
+use anyhow::{Context, Result, anyhow};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::Config;
+use crate::core::snapshot::{self, DotfileChange, Snapshot, SnapshotDiff};
+use crate::core::state::PackageState;
+use crate::internal::color;
+use crate::internal::files::friendly_path;
+
+/// What `owl snapshot restore` will do, decided before anything changes
+#[derive(Debug, Default, PartialEq)]
+struct RestorePlan {
+    remove: Vec<String>,
+    /// Exact versions found in the pacman cache: (name, version, package file)
+    from_cache: Vec<(String, String, PathBuf)>,
+    /// Missing packages without a cached file; they come back at the latest version
+    latest: Vec<(String, String)>,
+    /// Packages whose snapshot version is not cached: (name, then, now)
+    kept: Vec<(String, String, String)>,
+    /// Dotfiles to put back, with the hash of the stored content
+    dotfiles: Vec<(String, String)>,
+    /// Dotfiles deployed since the snapshot; restore leaves them alone
+    untouched: Vec<String>,
+    state: bool,
+}
+
+impl RestorePlan {
+    fn is_empty(&self) -> bool {
+        self.remove.is_empty()
+            && self.from_cache.is_empty()
+            && self.latest.is_empty()
+            && self.dotfiles.is_empty()
+            && !self.state
+    }
+}
+
+/// Run `owl snapshot create <name>`
+pub fn run_create(name: &str) {
+    crate::error::exit_on_error(create(name));
+}
+
+/// Run `owl snapshot list`
+pub fn run_list() {
+    crate::error::exit_on_error(list());
+}
+
+/// Run `owl snapshot diff <name>`
+pub fn run_diff(name: &str) {
+    crate::error::exit_on_error(diff(name));
+}
+
+/// Run `owl snapshot restore <name>`
+pub fn run_restore(name: &str, flags: &crate::cli::handler::GlobalFlags) {
+    crate::error::exit_on_error(restore(name, flags.dry_run, flags.non_interactive));
+}
+
+fn create(name: &str) -> Result<()> {
+    let owned = name.to_string();
+    let snapshot = crate::internal::util::execute_with_progress(
+        move || snapshot::create(&owned),
+        "Capturing snapshot",
+    )?;
+    println!("[{}]", color::blue("snapshot"));
+    println!(
+        "  {} created {}: {} packages, {} dotfiles",
+        color::green("✓"),
+        color::bold(&snapshot.name),
+        snapshot.packages.len(),
+        snapshot.dotfiles.len()
+    );
+    println!(
+        "  {} compare with `owl snapshot diff {}`, roll back with `owl snapshot restore {}`",
+        color::blue("info:"),
+        name,
+        name
+    );
+    Ok(())
+}
+
+fn list() -> Result<()> {
+    crate::core::state::forbid_writes();
+    let snapshots = snapshot::list()?;
+    println!("[{}]", color::blue("snapshots"));
+    if snapshots.is_empty() {
+        println!(
+            "  {} no snapshots; take one with `owl snapshot create <name>`",
+            color::blue("info:")
+        );
+        return Ok(());
+    }
+    let now = crate::core::history::now();
+    let mut table = crate::cli::ui::Table::new(&["name", "created", "packages", "dotfiles"]);
+    for snapshot in &snapshots {
+        table.add_row(vec![
+            snapshot.name.clone(),
+            crate::commands::status::format_age(now.saturating_sub(snapshot.created_at)),
+            snapshot.packages.len().to_string(),
+            snapshot.dotfiles.len().to_string(),
+        ]);
+    }
+    table.print();
+    Ok(())
+}
+
+/// The snapshot and how the system has moved away from it
+fn compare_with_current(name: &str) -> Result<(Snapshot, SnapshotDiff)> {
+    let snapshot = snapshot::load(name)?;
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    let recorded: Vec<String> = snapshot.dotfiles.keys().cloned().collect();
+    let packages = crate::core::pm::ParuPacman::new().installed_versions()?;
+    let dotfiles = snapshot::deployed_hashes(&config, &recorded)?;
+    let state = PackageState::load().context("Failed to load package state")?;
+    let diff = snapshot::compare(&snapshot, &packages, &dotfiles, &state);
+    Ok((snapshot, diff))
+}
+
+fn diff(name: &str) -> Result<()> {
+    crate::core::state::forbid_writes();
+    let owned = name.to_string();
+    let (_, diff) = crate::internal::util::execute_with_progress(
+        move || compare_with_current(&owned),
+        "Comparing with snapshot",
+    )?;
+    println!("[{}]", color::blue("snapshot"));
+    if diff.is_empty() {
+        println!("  {} the system matches {}", color::green("✓"), name);
+        return Ok(());
+    }
+
+    for (package, version) in &diff.added {
+        println!("  {}  {}", color::green(&format!("+ {}", package)), version);
+    }
+    for (package, version) in &diff.missing {
+        println!("  {}  {}", color::red(&format!("- {}", package)), version);
+    }
+    for (package, then, now) in &diff.changed {
+        println!(
+            "  {}  {} -> {}",
+            color::yellow(&format!("~ {}", package)),
+            then,
+            now
+        );
+    }
+    for (path, change) in &diff.dotfiles {
+        let (mark, label) = match change {
+            DotfileChange::Changed => (color::yellow("~"), "changed"),
+            DotfileChange::Missing => (color::red("-"), "missing"),
+            DotfileChange::Added => (color::green("+"), "new since the snapshot"),
+        };
+        println!("  {} {} ({})", mark, friendly_path(path), label);
+    }
+    if diff.state_changed {
+        println!("  {} package state differs", color::yellow("~"));
+    }
+    Ok(())
+}
+
+/// Decide the restore, looking up snapshot versions with `cached`
+fn plan_restore(
+    snapshot: &Snapshot,
+    diff: &SnapshotDiff,
+    cached: impl Fn(&str, &str) -> Option<PathBuf>,
+) -> RestorePlan {
+    let mut plan = RestorePlan {
+        remove: diff.added.iter().map(|(name, _)| name.clone()).collect(),
+        state: diff.state_changed,
+        ..RestorePlan::default()
+    };
+    for (name, version) in &diff.missing {
+        match cached(name, version) {
+            Some(file) => plan.from_cache.push((name.clone(), version.clone(), file)),
+            None => plan.latest.push((name.clone(), version.clone())),
+        }
+    }
+    for (name, then, now) in &diff.changed {
+        match cached(name, then) {
+            Some(file) => plan.from_cache.push((name.clone(), then.clone(), file)),
+            None => plan.kept.push((name.clone(), then.clone(), now.clone())),
+        }
+    }
+    for (path, change) in &diff.dotfiles {
+        match change {
+            DotfileChange::Added => plan.untouched.push(path.clone()),
+            _ => plan
+                .dotfiles
+                .push((path.clone(), snapshot.dotfiles[path].clone())),
+        }
+    }
+    plan
+}
+
+fn print_plan(plan: &RestorePlan) {
+    for name in &plan.remove {
+        println!("  {} remove {}", color::red("➔"), name);
+    }
+    for (name, version, _) in &plan.from_cache {
+        println!(
+            "  {} install {} {} (cached)",
+            color::green("➔"),
+            name,
+            version
+        );
+    }
+    for (name, version) in &plan.latest {
+        println!(
+            "  {} install {} at the latest version ({} is not cached)",
+            color::green("➔"),
+            name,
+            version
+        );
+    }
+    for (path, _) in &plan.dotfiles {
+        println!("  {} restore {}", color::yellow("➔"), friendly_path(path));
+    }
+    if plan.state {
+        println!("  {} restore package state", color::yellow("➔"));
+    }
+    for (name, then, now) in &plan.kept {
+        println!(
+            "  {} {} stays at {} ({} is not cached)",
+            color::yellow("!"),
+            name,
+            now,
+            then
+        );
+    }
+    for path in &plan.untouched {
+        println!(
+            "  {} {} is new since the snapshot and is left alone",
+            color::blue("info:"),
+            friendly_path(path)
+        );
+    }
+}
+
+fn restore(name: &str, dry_run: bool, non_interactive: bool) -> Result<()> {
+    let owned = name.to_string();
+    let (snapshot, diff) = crate::internal::util::execute_with_progress(
+        move || compare_with_current(&owned),
+        "Comparing with snapshot",
+    )?;
+    let plan = plan_restore(&snapshot, &diff, crate::core::pm::cached_package);
+
+    println!("[{}]", color::blue("restore"));
+    print_plan(&plan);
+    if plan.is_empty() {
+        println!("  {} nothing to restore", color::green("✓"));
+        return Ok(());
+    }
+    if dry_run {
+        println!("  {} dry run; nothing was changed", color::blue("info:"));
+        return Ok(());
+    }
+    if !non_interactive && !confirm(name)? {
+        println!("  {}", color::blue("Restore cancelled"));
+        return Ok(());
+    }
+
+    // The plan has been confirmed, so the package manager runs without asking again
+    let pm = crate::core::pm::ParuPacman::new();
+    pm.remove_packages(&plan.remove, true)?;
+    let files: Vec<PathBuf> = plan
+        .from_cache
+        .iter()
+        .map(|(_, _, file)| file.clone())
+        .collect();
+    pm.install_files_with_mode(&files, true)?;
+    let latest: Vec<String> = plan.latest.iter().map(|(name, _)| name.clone()).collect();
+    if !latest.is_empty() {
+        let (repo, aur) = crate::core::package::categorize_packages(&latest)?.install_sets();
+        pm.install_repo(&repo)?;
+        pm.install_aur(&aur)?;
+    }
+    crate::core::package::invalidate_package_caches();
+
+    for (path, hash) in &plan.dotfiles {
+        restore_dotfile(name, Path::new(path), hash)?;
+    }
+    if plan.state {
+        snapshot
+            .state
+            .save()
+            .context("Failed to restore package state")?;
+    }
+    println!("  {} restored {}", color::green("✓"), name);
+    Ok(())
+}
+
+fn restore_dotfile(name: &str, path: &Path, hash: &str) -> Result<()> {
+    let stored = snapshot::stored_file(name, hash)?;
+    if !stored.is_file() {
+        return Err(anyhow!(
+            "snapshot '{}' has no stored copy of {}",
+            name,
+            path.display()
+        ));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::copy(&stored, path)
+        .with_context(|| format!("Failed to restore {}", path.display()))?;
+    Ok(())
+}
+
+fn confirm(name: &str) -> Result<bool> {
+    print!("  -> Restore snapshot {}? (y/N): ", name);
+    std::io::stdout().flush().ok();
+    let answer = crate::cli::ui::read_answer("the restore confirmation")?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_plan_restore_prefers_cached_versions() {
+        let snapshot = Snapshot {
+            name: "before".to_string(),
+            created_at: 0,
+            packages: BTreeMap::new(),
+            state: PackageState {
+                untracked: Vec::new(),
+                hidden: Vec::new(),
+                managed: Vec::new(),
+                pinned: Default::default(),
+                sources: Default::default(),
+                remove_hooks: Default::default(),
+            },
+            dotfiles: BTreeMap::from([("/h/.vimrc".to_string(), "aa".to_string())]),
+        };
+        let diff = SnapshotDiff {
+            missing: vec![
+                ("vim".to_string(), "9.1-1".to_string()),
+                ("git".to_string(), "2.45-1".to_string()),
+            ],
+            added: vec![("plasma".to_string(), "6.0-1".to_string())],
+            changed: vec![(
+                "htop".to_string(),
+                "3.3.0-1".to_string(),
+                "3.3.0-2".to_string(),
+            )],
+            dotfiles: vec![
+                ("/h/.bashrc".to_string(), DotfileChange::Added),
+                ("/h/.vimrc".to_string(), DotfileChange::Missing),
+            ],
+            state_changed: false,
+        };
+        let cache = |name: &str, version: &str| {
+            (name == "vim")
+                .then(|| PathBuf::from(format!("/cache/{}-{}.pkg.tar.zst", name, version)))
+        };
+
+        let plan = plan_restore(&snapshot, &diff, cache);
+        assert_eq!(plan.remove, vec!["plasma"]);
+        assert_eq!(
+            plan.from_cache,
+            vec![(
+                "vim".to_string(),
+                "9.1-1".to_string(),
+                PathBuf::from("/cache/vim-9.1-1.pkg.tar.zst")
+            )]
+        );
+        assert_eq!(plan.latest, vec![("git".to_string(), "2.45-1".to_string())]);
+        assert_eq!(plan.kept.len(), 1, "htop 3.3.0-1 is not cached");
+        assert_eq!(
+            plan.dotfiles,
+            vec![("/h/.vimrc".to_string(), "aa".to_string())]
+        );
+        assert_eq!(plan.untouched, vec!["/h/.bashrc"]);
+    }
+}
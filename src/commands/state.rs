@@ -0,0 +1,120 @@
+//! Maintenance of owl's package state
+
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::Path;
+
+use crate::core::pacman_hook::{self, Refresh};
+use crate::core::state::PackageState;
+use crate::internal::color;
+use crate::internal::constants;
+
+/// Run `owl state refresh`
+pub fn run_refresh(from_hook: bool, flags: &crate::cli::handler::GlobalFlags) {
+    crate::error::exit_on_error(refresh(from_hook, flags.dry_run));
+}
+
+fn refresh(from_hook: bool, dry_run: bool) -> Result<()> {
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    let targets = if from_hook {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read the transaction targets")?;
+        pacman_hook::parse_targets(&input)
+    } else {
+        let mut names = state.managed.clone();
+        names.extend(state.pinned_names());
+        names.sort();
+        names.dedup();
+        names
+    };
+    let local = Path::new(constants::PACMAN_DB_DIR).join("local");
+    let installed = pacman_hook::installed_versions(&local, &targets)
+        .with_context(|| format!("Failed to read {}", local.display()))?;
+    let changes = pacman_hook::refresh(&mut state, &targets, &installed);
+
+    // pacman prints hook output verbatim, so the hook stays quiet unless state moved
+    if !from_hook {
+        println!("[{}]", color::blue("state"));
+    }
+    if changes.is_empty() {
+        if !from_hook {
+            println!(
+                "  {} state matches the installed packages",
+                color::green("✓")
+            );
+        }
+        return Ok(());
+    }
+    report(&changes, dry_run);
+    if dry_run {
+        return Ok(());
+    }
+    state.save().context("Failed to save package state")?;
+    if crate::core::privilege::is_root() {
+        return_to_owner()?;
+    }
+    Ok(())
+}
+
+fn report(changes: &Refresh, dry_run: bool) {
+    let (forgot, moved, dropped) = if dry_run {
+        (
+            "would forget",
+            "would move the pin of",
+            "would drop the pin of",
+        )
+    } else {
+        ("forgot", "moved the pin of", "dropped the pin of")
+    };
+    for package in &changes.forgotten {
+        println!(
+            "  {} {} {}, no longer installed",
+            color::green("✓"),
+            forgot,
+            package
+        );
+    }
+    for (package, old, new) in &changes.repinned {
+        println!(
+            "  {} {} {} from {} to {}, the installed version",
+            color::yellow("!"),
+            moved,
+            package,
+            old,
+            new
+        );
+    }
+    for package in &changes.unpinned {
+        println!(
+            "  {} {} {}, no longer installed",
+            color::green("✓"),
+            dropped,
+            package
+        );
+    }
+}
+
+/// The hook runs as root; hand state files it created back to the owner of the owl
+/// directory so the next unprivileged run can still write them
+fn return_to_owner() -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let owner = std::fs::metadata(crate::internal::files::owl_dir()?)
+        .context("Failed to read the owl directory")?;
+    if owner.uid() == 0 {
+        return Ok(());
+    }
+    let location = PackageState::location()?;
+    let mut paths = vec![location.clone()];
+    if location.is_dir() {
+        for entry in std::fs::read_dir(&location)? {
+            paths.push(entry?.path());
+        }
+    }
+    for path in paths {
+        std::os::unix::fs::chown(&path, Some(owner.uid()), Some(owner.gid()))
+            .with_context(|| format!("Failed to hand {} back", path.display()))?;
+    }
+    Ok(())
+}
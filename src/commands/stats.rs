@@ -0,0 +1,273 @@
+//! Overview numbers about the managed system
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::cli::context::Context;
+use crate::core::config::Config;
+use crate::core::pm::PackageInfo;
+use crate::core::state::PackageState;
+use crate::internal::color;
+
+/// How many configured packages come from where
+#[derive(Debug, Serialize)]
+struct Sources {
+    repo: usize,
+    aur: usize,
+    /// Package groups, which expand to repo packages
+    groups: usize,
+    /// Found neither in the repos nor in the AUR
+    unknown: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Sized {
+    name: String,
+    installed_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    configured: usize,
+    sources: Sources,
+    managed: usize,
+    untracked: usize,
+    /// Bytes installed by configured packages
+    installed_size: u64,
+    dotfiles: usize,
+    /// Bytes currently deployed at the dotfile destinations
+    dotfiles_size: u64,
+    config_files: usize,
+    config_lines: usize,
+    services: usize,
+    /// Seconds since the Unix epoch; absent before the first apply
+    last_apply: Option<u64>,
+    largest: Vec<Sized>,
+}
+
+/// Run the stats command
+pub fn run(json: bool, oneline: bool, ctx: &Context) {
+    crate::core::state::forbid_writes();
+    let (config, state) = match ctx.owned_config_and_state() {
+        Ok(loaded) => loaded,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+    let stats = if json || oneline {
+        collect(&config, &state)
+    } else {
+        crate::internal::util::execute_with_progress(
+            move || collect(&config, &state),
+            "Collecting stats",
+        )
+    };
+    let stats = match stats {
+        Ok(stats) => stats,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+
+    if oneline {
+        println!("{}", oneline_summary(&stats, crate::core::history::now()));
+    } else if json {
+        match serde_json::to_string_pretty(&stats) {
+            Ok(out) => println!("{}", out),
+            Err(err) => crate::error::exit_with_error(err.into()),
+        }
+    } else {
+        print_stats(&stats);
+    }
+}
+
+fn collect(config: &Config, state: &PackageState) -> Result<Stats> {
+    let names: Vec<String> = config.packages.keys().cloned().collect();
+    let categorized = crate::core::package::categorize_packages(&names)?;
+    let sources = Sources {
+        // Provided names install from the repos through a provider
+        repo: categorized.repo.len() + categorized.provided.len(),
+        aur: categorized.aur.len(),
+        groups: categorized.groups.len(),
+        unknown: categorized.unknown.len(),
+    };
+
+    let info = crate::core::package::get_package_info(&names)?;
+    let mappings = crate::core::dotfiles::get_dotfile_mappings(config);
+    let mut dotfiles_size = 0;
+    for mapping in &mappings {
+        for file in crate::core::dotfiles::deployed_files(mapping)? {
+            dotfiles_size += std::fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+        }
+    }
+
+    let files = crate::internal::files::get_all_config_files()?;
+    let config_lines = files
+        .iter()
+        .filter_map(|file| std::fs::read_to_string(file).ok())
+        .map(|content| content.lines().count())
+        .sum();
+
+    Ok(Stats {
+        configured: names.len(),
+        sources,
+        managed: state.managed.len(),
+        untracked: state.untracked.len(),
+        installed_size: info.values().map(|info| info.installed_size).sum(),
+        dotfiles: mappings.len(),
+        dotfiles_size,
+        config_files: files.len(),
+        config_lines,
+        services: config
+            .packages
+            .values()
+            .filter(|package| package.service.is_some())
+            .count(),
+        last_apply: crate::core::history::load_last()?.map(|record| record.finished_at),
+        largest: largest(&info, 10),
+    })
+}
+
+/// The `count` biggest installed packages, largest first
+fn largest(info: &HashMap<String, PackageInfo>, count: usize) -> Vec<Sized> {
+    let mut sized: Vec<Sized> = info
+        .values()
+        .map(|info| Sized {
+            name: info.name.clone(),
+            installed_size: info.installed_size,
+        })
+        .collect();
+    sized.sort_by(|a, b| {
+        b.installed_size
+            .cmp(&a.installed_size)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    sized.truncate(count);
+    sized
+}
+
+fn last_apply(stats: &Stats, now: u64) -> String {
+    match stats.last_apply {
+        Some(at) => format!(
+            "applied {}",
+            crate::commands::status::format_age(now.saturating_sub(at))
+        ),
+        None => "never applied".to_string(),
+    }
+}
+
+/// A status-bar line: `42 pkgs (5 aur), 3.10 GiB, 12 dotfiles, 3 services, applied 2h ago`
+fn oneline_summary(stats: &Stats, now: u64) -> String {
+    format!(
+        "{} pkgs ({} aur), {}, {} dotfiles, {} services, {}",
+        stats.configured,
+        stats.sources.aur,
+        crate::cli::ui::format_size(stats.installed_size),
+        stats.dotfiles,
+        stats.services,
+        last_apply(stats, now)
+    )
+}
+
+fn print_stats(stats: &Stats) {
+    let size = crate::cli::ui::format_size;
+    println!("[{}]", color::blue("stats"));
+    println!(
+        "  packages: {} configured ({} repo, {} AUR, {} groups{})",
+        color::bold(&stats.configured.to_string()),
+        stats.sources.repo,
+        stats.sources.aur,
+        stats.sources.groups,
+        if stats.sources.unknown > 0 {
+            format!(", {} unknown", stats.sources.unknown)
+        } else {
+            String::new()
+        }
+    );
+    println!(
+        "  state: {} managed, {} untracked",
+        color::bold(&stats.managed.to_string()),
+        stats.untracked
+    );
+    println!(
+        "  installed size: {}",
+        color::bold(&size(stats.installed_size))
+    );
+    println!(
+        "  dotfiles: {} mappings, {} on disk",
+        color::bold(&stats.dotfiles.to_string()),
+        size(stats.dotfiles_size)
+    );
+    println!(
+        "  config: {} files, {} lines",
+        color::bold(&stats.config_files.to_string()),
+        stats.config_lines
+    );
+    println!("  services: {}", color::bold(&stats.services.to_string()));
+    println!(
+        "  last apply: {}",
+        last_apply(stats, crate::core::history::now())
+    );
+
+    if stats.largest.is_empty() {
+        return;
+    }
+    println!();
+    let mut table = crate::cli::ui::Table::new(&["largest", "size"]);
+    for package in &stats.largest {
+        table.add_row(vec![package.name.clone(), size(package.installed_size)]);
+    }
+    table.print();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(name: &str, installed_size: u64) -> (String, PackageInfo) {
+        (
+            name.to_string(),
+            PackageInfo {
+                name: name.to_string(),
+                installed_size,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_largest_orders_by_size_then_name() {
+        let info = HashMap::from([
+            info("htop", 400),
+            info("firefox", 250_000),
+            info("vim", 4_000),
+            info("neovim", 4_000),
+        ]);
+        let names: Vec<String> = largest(&info, 3).into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["firefox", "neovim", "vim"]);
+    }
+
+    #[test]
+    fn test_oneline_summary() {
+        let stats = Stats {
+            configured: 42,
+            sources: Sources {
+                repo: 36,
+                aur: 5,
+                groups: 1,
+                unknown: 0,
+            },
+            managed: 40,
+            untracked: 28,
+            installed_size: 3 * 1024 * 1024 * 1024,
+            dotfiles: 12,
+            dotfiles_size: 0,
+            config_files: 3,
+            config_lines: 120,
+            services: 3,
+            last_apply: Some(1_000),
+            largest: Vec::new(),
+        };
+        assert_eq!(
+            oneline_summary(&stats, 1_000 + 7_200),
+            "42 pkgs (5 aur), 3.00 GiB, 12 dotfiles, 3 services, applied 2h ago"
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! One-screen overview of whether the system matches the config
+
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::cli::context::Context;
+use crate::core::config::Config;
+use crate::core::plan::{Plan, PlanOptions};
+use crate::core::state::PackageState;
+use crate::internal::color;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledRun {
+    /// Seconds since it started
+    pub age: u64,
+    pub outcome: crate::core::history::Outcome,
+}
+
+/// Everything `owl status` reports
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    pub installs: usize,
+    pub removals: usize,
+    pub repo_updates: usize,
+    pub aur_updates: usize,
+    /// False when `--fast` skipped the AUR check
+    pub aur_checked: bool,
+    /// Pinned packages with an update that apply holds back
+    pub held: Vec<String>,
+    pub dotfile_drift: usize,
+    pub services_out_of_state: Vec<String>,
+    pub changed_config_files: Vec<String>,
+    /// Seconds since the last apply finished, if one was recorded
+    pub last_apply_age: Option<u64>,
+    /// The newest run started by the `owl schedule` timer
+    pub last_scheduled: Option<ScheduledRun>,
+    pub inconsistencies: Vec<String>,
+    /// Explicitly installed packages the config does not manage
+    pub unmanaged: usize,
+    pub in_sync: bool,
+}
+
+impl Status {
+    fn from_plan(plan: &Plan, aur_checked: bool) -> Self {
+        Self {
+            installs: plan.installs.len(),
+            removals: plan.removals.len(),
+            repo_updates: plan.repo_update_count,
+            aur_updates: plan.aur_updates.len(),
+            aur_checked,
+            held: plan.held.clone(),
+            dotfile_drift: plan.dotfile_drift().count(),
+            services_out_of_state: plan
+                .services_out_of_state()
+                .map(|service| service.name.clone())
+                .collect(),
+            unmanaged: plan.unmanaged,
+            in_sync: plan.is_in_sync(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Run the status command, exiting 0 only when the system is fully in sync
+pub fn run(json: bool, fast: bool, ctx: &Context) {
+    let (config, state) = match ctx.owned_config_and_state() {
+        Ok(loaded) => loaded,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+    let status = if json {
+        collect_with(&config, &state, fast)
+    } else {
+        crate::internal::util::execute_with_progress(
+            move || collect_with(&config, &state, fast),
+            "Checking status",
+        )
+    };
+    let status = match status {
+        Ok(status) => status,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&status) {
+            Ok(out) => println!("{}", out),
+            Err(err) => crate::error::exit_with_error(err.into()),
+        }
+    } else {
+        print_status(&status);
+    }
+
+    if !status.in_sync {
+        std::process::exit(1);
+    }
+}
+
+/// Check everything `owl status` reports and store the counts for `owl prompt`
+pub fn collect(fast: bool) -> Result<Status> {
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    let state = PackageState::load().context("Failed to load package state")?;
+    collect_with(&config, &state, fast)
+}
+
+fn collect_with(config: &Config, state: &PackageState, fast: bool) -> Result<Status> {
+    let options = PlanOptions {
+        check_aur_updates: !fast,
+    };
+    let plan = crate::core::plan::build_plan(config, state, &options)?;
+    let mut status = Status::from_plan(&plan, !fast);
+
+    let last = crate::core::history::load_last()?;
+    let mut current = BTreeMap::new();
+    for entry in crate::internal::files::config_file_entries()? {
+        let file = entry.path.to_string_lossy().into_owned();
+        let hash = match last
+            .as_ref()
+            .and_then(|record| unchanged_hash(record, &entry))
+        {
+            Some(hash) => hash.clone(),
+            None => crate::core::dotfiles::sha256_file(&entry.path)?,
+        };
+        current.insert(file, hash);
+    }
+    if let Some(record) = last {
+        status.changed_config_files = record.changed_files(&current);
+        status.last_apply_age = Some(record.age());
+    }
+    status.last_scheduled = crate::core::history::load_runs()?
+        .into_iter()
+        .find(|run| run.scheduled)
+        .map(|run| ScheduledRun {
+            age: crate::core::history::now().saturating_sub(run.started_at),
+            outcome: run.outcome,
+        });
+
+    let installed = crate::core::package::get_installed_packages()?;
+    status.inconsistencies = find_inconsistencies(state, &installed, config);
+    status.in_sync &= status.inconsistencies.is_empty();
+
+    let snapshot = crate::core::status_cache::StatusSnapshot {
+        taken_at: crate::core::history::now(),
+        installs: status.installs,
+        removals: status.removals,
+        updates: status.repo_updates + status.aur_updates,
+        dotfile_drift: status.dotfile_drift,
+    };
+    if let Err(err) = crate::core::status_cache::write(&snapshot) {
+        log::debug!("status cache: {:#}", err);
+    }
+    Ok(status)
+}
+
+/// The hash the last apply recorded for a file not modified since, which saves
+/// reading it again
+fn unchanged_hash<'a>(
+    record: &'a crate::core::history::ApplyRecord,
+    entry: &crate::internal::files::ConfigFileEntry,
+) -> Option<&'a String> {
+    let modified = entry
+        .modified?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    // Same-second edits are hashed, since the seconds cannot tell them apart
+    if modified >= record.finished_at {
+        return None;
+    }
+    record.config_hashes.get(entry.path.to_str()?)
+}
+
+/// State entries that contradict each other or the system
+fn find_inconsistencies(
+    state: &PackageState,
+    installed: &HashSet<String>,
+    config: &Config,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    for pkg in &state.managed {
+        if state.is_untracked(pkg) {
+            problems.push(format!("{} is both managed and untracked", pkg));
+        }
+        if state.is_hidden(pkg) {
+            problems.push(format!("{} is both managed and hidden", pkg));
+        }
+        // Configured packages that are missing already show up as installs
+        if !installed.contains(pkg) && !config.packages.contains_key(pkg) {
+            problems.push(format!(
+                "{} is managed but neither installed nor configured",
+                pkg
+            ));
+        }
+    }
+    problems
+}
+
+/// Format a duration in seconds as a short "ago" string
+pub fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+fn print_status(status: &Status) {
+    println!("[{}]", color::blue("status"));
+
+    let mark = |count: usize| {
+        if count == 0 {
+            color::green("✓")
+        } else {
+            color::yellow("➔")
+        }
+    };
+    let aur = if status.aur_checked {
+        status.aur_updates.to_string()
+    } else {
+        "unchecked".to_string()
+    };
+    println!(
+        "  {} packages: {} to install, {} to remove, {} repo updates, {} AUR updates",
+        mark(status.installs + status.removals + status.repo_updates + status.aur_updates),
+        status.installs,
+        status.removals,
+        status.repo_updates,
+        aur
+    );
+    if !status.held.is_empty() {
+        println!(
+            "  {} held: {} (pinned)",
+            color::blue("info:"),
+            status.held.join(", ")
+        );
+    }
+    println!(
+        "  {} dotfiles: {} drifted",
+        mark(status.dotfile_drift),
+        status.dotfile_drift
+    );
+    println!(
+        "  {} services: {} out of state{}",
+        mark(status.services_out_of_state.len()),
+        status.services_out_of_state.len(),
+        if status.services_out_of_state.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", status.services_out_of_state.join(", "))
+        }
+    );
+
+    match status.last_apply_age {
+        Some(age) => println!(
+            "  {} last apply: {}, {} config files changed since",
+            color::blue("info:"),
+            format_age(age),
+            status.changed_config_files.len()
+        ),
+        None => println!("  {} last apply: never recorded", color::blue("info:")),
+    }
+    for file in &status.changed_config_files {
+        println!("      {}", color::dim(file));
+    }
+    if let Some(run) = &status.last_scheduled {
+        println!(
+            "  {} last scheduled run: {}, {}",
+            color::blue("info:"),
+            format_age(run.age),
+            crate::commands::schedule::outcome_label(run.outcome)
+        );
+    }
+
+    for problem in &status.inconsistencies {
+        println!("  {} {}", color::red("✗"), problem);
+    }
+    if let Some(notice) = crate::core::analysis::unmanaged_notice(status.unmanaged) {
+        println!("  {} {}", color::blue("info:"), notice);
+    }
+
+    if status.in_sync {
+        println!("\n  {} system is in sync", color::green("✓"));
+    } else {
+        println!(
+            "\n  {} run `owl` to apply, or `owl ui` to review",
+            color::yellow("!")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_inconsistencies() {
+        let state = PackageState {
+            untracked: vec!["base".to_string()],
+            hidden: Vec::new(),
+            managed: vec!["base".to_string(), "htop".to_string(), "gone".to_string()],
+            pinned: Default::default(),
+            sources: Default::default(),
+            remove_hooks: Default::default(),
+        };
+        let installed: HashSet<String> = ["base", "htop"].iter().map(|s| s.to_string()).collect();
+        let config = Config::parse("@packages\nhtop\n").unwrap();
+
+        assert_eq!(
+            find_inconsistencies(&state, &installed, &config),
+            vec![
+                "base is both managed and untracked",
+                "gone is managed but neither installed nor configured",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(5), "just now");
+        assert_eq!(format_age(125), "2m ago");
+        assert_eq!(format_age(7200), "2h ago");
+        assert_eq!(format_age(3 * 86400 + 5), "3d ago");
+    }
+
+    #[test]
+    fn test_unchanged_hash_only_for_files_older_than_the_apply() {
+        let record = crate::core::history::ApplyRecord {
+            finished_at: 1_000,
+            config_hashes: BTreeMap::from([("/owl/main.owl".to_string(), "abc".to_string())]),
+        };
+        let entry = |path: &str, secs: u64| crate::internal::files::ConfigFileEntry {
+            path: path.into(),
+            relative: "main.owl".into(),
+            modified: Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs)),
+        };
+        assert_eq!(
+            unchanged_hash(&record, &entry("/owl/main.owl", 999)).map(String::as_str),
+            Some("abc")
+        );
+        assert_eq!(
+            unchanged_hash(&record, &entry("/owl/main.owl", 1_000)),
+            None
+        );
+        assert_eq!(unchanged_hash(&record, &entry("/owl/new.owl", 10)), None);
+    }
+}
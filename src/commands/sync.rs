@@ -0,0 +1,290 @@
+//! Keep the owl directory in step with its git remote
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::Config;
+use crate::core::git::{self, GitError};
+use crate::internal::color;
+use crate::internal::constants;
+use crate::internal::files::friendly_path;
+
+/// Diffing against this shows every file of the first commit as added
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Names listed in a commit subject before it switches to a count
+const MAX_SUBJECT_NAMES: usize = 4;
+
+/// A work tree owl syncs: the owl directory, or a dotfiles directory with its own repo
+struct Repo {
+    path: PathBuf,
+    dotfiles: bool,
+}
+
+/// Run the sync command: pull (then optionally apply), or commit and push
+pub fn run(apply: bool, push: bool, flags: &crate::cli::handler::GlobalFlags) {
+    let result = if push {
+        push_changes(flags.dry_run)
+    } else {
+        pull_changes(flags.dry_run)
+    };
+    crate::error::exit_on_error(result);
+
+    if apply {
+        println!();
+        crate::commands::apply::run(flags);
+    }
+}
+
+fn repos() -> Result<Vec<Repo>> {
+    let owl = crate::internal::files::owl_dir()?;
+    let top = git::toplevel(&owl)?;
+    let mut repos = vec![Repo {
+        path: top.clone(),
+        dotfiles: false,
+    }];
+    let dotfiles = owl.join(constants::DOTFILES_DIR);
+    if dotfiles.is_dir()
+        && let Ok(dotfiles_top) = git::toplevel(&dotfiles)
+        && dotfiles_top != top
+    {
+        repos.push(Repo {
+            path: dotfiles_top,
+            dotfiles: true,
+        });
+    }
+    Ok(repos)
+}
+
+/// Refuse to touch any repo while one of them is mid-merge
+fn ensure_no_conflicts(repos: &[Repo]) -> Result<()> {
+    for repo in repos {
+        let conflicted: Vec<String> = git::status(&repo.path)?
+            .into_iter()
+            .filter(|entry| entry.is_conflict())
+            .map(|entry| entry.path)
+            .collect();
+        if !conflicted.is_empty() || git::merge_in_progress(&repo.path)? {
+            return Err(GitError::Conflicted {
+                repo: repo.path.clone(),
+                files: conflicted,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn pull_changes(dry_run: bool) -> Result<()> {
+    let repos = repos()?;
+    println!("[{}]", color::blue("sync"));
+    ensure_no_conflicts(&repos)?;
+    // Check every tree before pulling any, so a dirty dotfiles repo cannot leave the
+    // config updated and the dotfiles behind
+    for repo in &repos {
+        let dirty: Vec<String> = git::status(&repo.path)?
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        if !dirty.is_empty() {
+            return Err(GitError::Dirty {
+                repo: repo.path.clone(),
+                files: dirty,
+            }
+            .into());
+        }
+    }
+
+    for repo in &repos {
+        let name = shown(&repo.path);
+        if dry_run {
+            println!(
+                "  {} would run git pull --ff-only in {}",
+                color::blue("info:"),
+                name
+            );
+            continue;
+        }
+        let before = git::head(&repo.path)?;
+        git::pull_ff_only(&repo.path)?;
+        let after = git::head(&repo.path)?;
+        let Some(after) = after.filter(|after| Some(after) != before.as_ref()) else {
+            println!("  {} {} is up to date", color::green("✓"), name);
+            continue;
+        };
+
+        let changed =
+            git::changed_files(&repo.path, before.as_deref().unwrap_or(EMPTY_TREE), &after)?;
+        println!(
+            "  {} {} pulled, {} file{} changed",
+            color::green("✓"),
+            name,
+            changed.len(),
+            if changed.len() == 1 { "" } else { "s" }
+        );
+        for (status, path) in &changed {
+            let status = status.to_string();
+            let status = match status.as_str() {
+                "A" => color::green(&status),
+                "D" => color::red(&status),
+                _ => color::yellow(&status),
+            };
+            println!("    {} {}", status, path);
+        }
+    }
+    Ok(())
+}
+
+fn push_changes(dry_run: bool) -> Result<()> {
+    let repos = repos()?;
+    println!("[{}]", color::blue("sync"));
+    ensure_no_conflicts(&repos)?;
+
+    for repo in &repos {
+        let name = shown(&repo.path);
+        let entries = git::status(&repo.path)?;
+        if entries.is_empty() {
+            println!("  {} {} has nothing to commit", color::green("✓"), name);
+        } else {
+            let files: Vec<String> = entries.into_iter().map(|entry| entry.path).collect();
+            let message = if repo.dotfiles {
+                commit_message("owl: update dotfiles", &[], &[], &files)
+            } else {
+                let (added, removed) = package_changes(&repo.path, &files)?;
+                commit_message("owl: update config", &added, &removed, &files)
+            };
+            let subject = message.lines().next().unwrap_or_default();
+            if dry_run {
+                println!(
+                    "  {} would commit {} in {}",
+                    color::blue("info:"),
+                    color::bold(subject),
+                    name
+                );
+            } else {
+                git::commit_all(&repo.path, &message)?;
+                println!(
+                    "  {} committed {} in {}",
+                    color::green("✓"),
+                    color::bold(subject),
+                    name
+                );
+            }
+        }
+
+        if dry_run {
+            println!("  {} would push {}", color::blue("info:"), name);
+        } else {
+            git::push(&repo.path)?;
+            println!("  {} pushed {}", color::green("✓"), name);
+        }
+    }
+    Ok(())
+}
+
+/// Packages declared in the changed `.owl` files that were added or dropped since HEAD.
+/// A package that only moved between files counts as neither.
+fn package_changes(repo: &Path, files: &[String]) -> Result<(Vec<String>, Vec<String>)> {
+    let packages = |content: Option<String>| -> BTreeSet<String> {
+        content
+            .and_then(|content| Config::parse(&content).ok())
+            .map(|config| config.packages.into_keys().collect())
+            .unwrap_or_default()
+    };
+
+    let mut added = BTreeSet::new();
+    let mut removed = BTreeSet::new();
+    for file in files
+        .iter()
+        .filter(|file| file.ends_with(constants::OWL_EXT))
+    {
+        let old = packages(git::show_file(repo, "HEAD", file)?);
+        let new = packages(std::fs::read_to_string(repo.join(file)).ok());
+        added.extend(new.difference(&old).cloned());
+        removed.extend(old.difference(&new).cloned());
+    }
+    let moved: BTreeSet<String> = added.intersection(&removed).cloned().collect();
+    Ok((
+        added.difference(&moved).cloned().collect(),
+        removed.difference(&moved).cloned().collect(),
+    ))
+}
+
+/// Commit message naming the added and removed packages, with the changed files in the body
+fn commit_message(
+    fallback: &str,
+    added: &[String],
+    removed: &[String],
+    files: &[String],
+) -> String {
+    let describe = |verb: &str, names: &[String]| {
+        if names.len() > MAX_SUBJECT_NAMES {
+            format!("{} {} packages", verb, names.len())
+        } else {
+            format!("{} {}", verb, names.join(", "))
+        }
+    };
+    let mut parts = Vec::new();
+    if !added.is_empty() {
+        parts.push(describe("add", added));
+    }
+    if !removed.is_empty() {
+        parts.push(describe("remove", removed));
+    }
+    let mut message = if parts.is_empty() {
+        fallback.to_string()
+    } else {
+        format!("owl: {}", parts.join("; "))
+    };
+
+    message.push_str("\n\n");
+    if !added.is_empty() {
+        message.push_str(&format!("Added: {}\n", added.join(", ")));
+    }
+    if !removed.is_empty() {
+        message.push_str(&format!("Removed: {}\n", removed.join(", ")));
+    }
+    message.push_str("Changed files:\n");
+    for file in files {
+        message.push_str(&format!("  {}\n", file));
+    }
+    message
+}
+
+fn shown(path: &Path) -> String {
+    friendly_path(&path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_commit_message_lists_packages() {
+        let message = commit_message(
+            "owl: update config",
+            &names(&["htop", "neovim"]),
+            &names(&["vim"]),
+            &names(&["main.owl"]),
+        );
+        assert_eq!(
+            message,
+            "owl: add htop, neovim; remove vim\n\n\
+             Added: htop, neovim\n\
+             Removed: vim\n\
+             Changed files:\n  main.owl\n"
+        );
+
+        let many = names(&["a", "b", "c", "d", "e"]);
+        let message = commit_message("owl: update config", &many, &[], &names(&["main.owl"]));
+        assert!(message.starts_with("owl: add 5 packages\n"));
+
+        let message = commit_message("owl: update dotfiles", &[], &[], &names(&["nvim/init.lua"]));
+        assert!(message.starts_with("owl: update dotfiles\n\nChanged files:\n"));
+    }
+}
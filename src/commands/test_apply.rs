@@ -0,0 +1,112 @@
+//! Try the config in a throwaway container before applying it for real
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::cli::porcelain::Summary;
+use crate::core::sandbox::Sandbox;
+use crate::core::settings::SandboxRuntime;
+use crate::internal::color;
+
+/// Run `owl test-apply`; `runtime` and `image` override `[test_apply]`
+pub fn run(runtime: Option<SandboxRuntime>, image: Option<&str>) {
+    crate::error::exit_on_error(test_apply(runtime, image));
+}
+
+fn test_apply(runtime: Option<SandboxRuntime>, image: Option<&str>) -> Result<()> {
+    let settings = &crate::core::settings::get().test_apply;
+    let mut sandbox = Sandbox::new(
+        runtime.unwrap_or(settings.runtime),
+        image.unwrap_or(&settings.image),
+    );
+    let missing = sandbox.missing();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "test-apply cannot run on this host:\n  - {}",
+            missing.join("\n  - ")
+        ));
+    }
+    let owl_dir = crate::internal::files::owl_dir()?;
+    let binary = std::env::current_exe().context("Failed to find the owl binary")?;
+
+    println!(
+        "{} applying in {} with {}",
+        color::blue("info:"),
+        color::highlight(sandbox.image()),
+        sandbox.program()
+    );
+    let summaries = sandbox
+        .run(&owl_dir, &binary, crate::commands::fleet::render)
+        .context("The apply did not finish")?;
+    drop(sandbox);
+
+    let applied = converged(&summaries)?;
+    println!(
+        "{} converged cleanly: {}",
+        color::green("✓"),
+        applied.headline()
+    );
+    Ok(())
+}
+
+/// The apply's summary when the dry run after it found nothing left to do
+fn converged(summaries: &[Summary]) -> Result<&Summary> {
+    let [applied, recheck] = summaries else {
+        return Err(anyhow!(
+            "expected an apply and a recheck, got {} result(s)",
+            summaries.len()
+        ));
+    };
+    if !applied.ok {
+        return Err(anyhow!("the apply failed: {}", applied.headline()));
+    }
+    if recheck.to_install + recheck.to_remove > 0 {
+        return Err(anyhow!(
+            "did not converge: a second apply would still install {} and remove {} package(s)",
+            recheck.to_install,
+            recheck.to_remove
+        ));
+    }
+    if applied.warnings > 0 {
+        return Err(anyhow!(
+            "the apply finished with warnings: {}",
+            applied.headline()
+        ));
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_converged_needs_a_clean_recheck() {
+        let applied = Summary {
+            ok: true,
+            installed: 4,
+            ..Default::default()
+        };
+        let recheck = Summary {
+            ok: true,
+            dry_run: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            converged(&[applied.clone(), recheck.clone()]).unwrap(),
+            &applied
+        );
+
+        let pending = Summary {
+            to_install: 1,
+            ..recheck.clone()
+        };
+        let err = converged(&[applied.clone(), pending]).unwrap_err();
+        assert!(err.to_string().contains("install 1"));
+        let warned = Summary {
+            warnings: 2,
+            ..applied.clone()
+        };
+        assert!(converged(&[warned, recheck]).is_err());
+        assert!(converged(&[applied]).is_err());
+    }
+}
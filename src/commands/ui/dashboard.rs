@@ -0,0 +1,530 @@
+//! Terminal dashboard for `owl ui`
+//!
+//! All data comes from [`Snapshot`]; actions suspend the dashboard and hand over to
+//! the regular apply/adopt code paths, then re-plan.
+
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use super::Snapshot;
+use crate::cli::handler::GlobalFlags;
+use crate::commands::apply::Selection;
+use crate::core::dotfiles::DotfileMapping;
+use crate::internal::logging::LogCommand;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Plan,
+    Dotfiles,
+    Services,
+    Adopt,
+}
+
+const PANES: [Pane; 4] = [Pane::Plan, Pane::Dotfiles, Pane::Services, Pane::Adopt];
+
+#[derive(Debug, Clone, PartialEq)]
+enum PlanItem {
+    Install(String),
+    Remove(String),
+    AurUpdate(String),
+    RepoUpdates(usize),
+}
+
+/// What the user asked for with the last key press
+#[derive(Debug, Clone, PartialEq)]
+enum Request {
+    Nothing,
+    Quit,
+    Refresh,
+    Apply(Selection),
+    Adopt(String),
+    Diff(DotfileMapping),
+}
+
+struct App {
+    snapshot: Snapshot,
+    plan_items: Vec<PlanItem>,
+    focus: usize,
+    cursors: [usize; 4],
+    selected: BTreeSet<usize>,
+    message: Option<String>,
+}
+
+impl App {
+    fn new(snapshot: Snapshot) -> Self {
+        let mut app = Self {
+            snapshot,
+            plan_items: Vec::new(),
+            focus: 0,
+            cursors: [0; 4],
+            selected: BTreeSet::new(),
+            message: None,
+        };
+        app.rebuild_items();
+        app
+    }
+
+    fn replace_snapshot(&mut self, snapshot: Snapshot) {
+        self.snapshot = snapshot;
+        self.selected.clear();
+        self.rebuild_items();
+        for (idx, pane) in PANES.iter().enumerate() {
+            let len = self.pane_len(*pane);
+            self.cursors[idx] = self.cursors[idx].min(len.saturating_sub(1));
+        }
+    }
+
+    fn rebuild_items(&mut self) {
+        let plan = &self.snapshot.plan;
+        let mut items: Vec<PlanItem> = plan
+            .installs
+            .iter()
+            .cloned()
+            .map(PlanItem::Install)
+            .collect();
+        items.extend(plan.removals.iter().cloned().map(PlanItem::Remove));
+        items.extend(plan.aur_updates.iter().cloned().map(PlanItem::AurUpdate));
+        if plan.repo_update_count > 0 {
+            items.push(PlanItem::RepoUpdates(plan.repo_update_count));
+        }
+        self.plan_items = items;
+    }
+
+    fn drifted(&self) -> Vec<&crate::core::dotfiles::DotfileAction> {
+        self.snapshot.plan.dotfile_drift().collect()
+    }
+
+    fn pane(&self) -> Pane {
+        PANES[self.focus]
+    }
+
+    fn pane_len(&self, pane: Pane) -> usize {
+        match pane {
+            Pane::Plan => self.plan_items.len(),
+            Pane::Dotfiles => self.drifted().len(),
+            Pane::Services => self.snapshot.plan.services.len(),
+            Pane::Adopt => self.snapshot.adopt_candidates.len(),
+        }
+    }
+
+    fn move_cursor(&mut self, down: bool) {
+        let len = self.pane_len(self.pane());
+        let cursor = &mut self.cursors[self.focus];
+        if len == 0 {
+            *cursor = 0;
+        } else if down {
+            *cursor = (*cursor + 1).min(len - 1);
+        } else {
+            *cursor = cursor.saturating_sub(1);
+        }
+    }
+
+    /// Selected plan items, or the one under the cursor when nothing is selected
+    fn selection(&self) -> Selection {
+        let indices: Vec<usize> = if self.selected.is_empty() {
+            vec![self.cursors[0]]
+        } else {
+            self.selected.iter().copied().collect()
+        };
+
+        let mut selection = Selection::default();
+        for item in indices.iter().filter_map(|&idx| self.plan_items.get(idx)) {
+            match item {
+                PlanItem::Install(name) => selection.installs.push(name.clone()),
+                PlanItem::Remove(name) => selection.removals.push(name.clone()),
+                PlanItem::AurUpdate(name) => selection.aur_updates.push(name.clone()),
+                PlanItem::RepoUpdates(_) => selection.repo_updates = true,
+            }
+        }
+        selection
+    }
+
+    fn handle_key(&mut self, code: KeyCode) -> Request {
+        self.message = None;
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Request::Quit,
+            KeyCode::Char('r') => return Request::Refresh,
+            KeyCode::Tab => self.focus = (self.focus + 1) % PANES.len(),
+            KeyCode::BackTab => self.focus = (self.focus + PANES.len() - 1) % PANES.len(),
+            KeyCode::Char(c @ '1'..='4') => self.focus = c as usize - '1' as usize,
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(true),
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(false),
+            KeyCode::Char(' ') if self.pane() == Pane::Plan && !self.plan_items.is_empty() => {
+                let cursor = self.cursors[0];
+                if !self.selected.remove(&cursor) {
+                    self.selected.insert(cursor);
+                }
+            }
+            KeyCode::Char('a') if self.pane() == Pane::Plan => {
+                if self.plan_items.is_empty() {
+                    self.message = Some("nothing to apply".to_string());
+                } else {
+                    return Request::Apply(self.selection());
+                }
+            }
+            KeyCode::Char('o') if self.pane() == Pane::Adopt => {
+                if let Some(name) = self.snapshot.adopt_candidates.get(self.cursors[3]) {
+                    return Request::Adopt(name.clone());
+                }
+            }
+            KeyCode::Char('d') if self.pane() == Pane::Dotfiles => {
+                if let Some(action) = self.drifted().get(self.cursors[1]) {
+                    return Request::Diff(action.mapping.clone());
+                }
+            }
+            _ => {}
+        }
+        Request::Nothing
+    }
+}
+
+/// Run the dashboard until the user quits
+pub fn run(snapshot: Snapshot, flags: &GlobalFlags) -> Result<()> {
+    if !crate::cli::ui::interaction_allowed() {
+        return Err(crate::cli::ui::InteractionError {
+            prompt: "the dashboard".to_string(),
+            block: crate::cli::ui::InteractionBlock::NotATerminal,
+        }
+        .into());
+    }
+    let mut app = App::new(snapshot);
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app, flags);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App, flags: &GlobalFlags) -> Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, app))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        let request = app.handle_key(key.code);
+        match request {
+            Request::Nothing => {}
+            Request::Quit => return Ok(()),
+            Request::Refresh => reload(app),
+            request => {
+                ratatui::restore();
+                perform(request, app, flags);
+                wait_for_enter();
+                *terminal = ratatui::init();
+                reload(app);
+            }
+        }
+    }
+}
+
+fn reload(app: &mut App) {
+    crate::core::package::invalidate_package_caches();
+    match Snapshot::load() {
+        Ok(snapshot) => app.replace_snapshot(snapshot),
+        Err(err) => app.message = Some(format!("refresh failed: {:#}", err)),
+    }
+}
+
+/// Run a request with the terminal in its normal mode
+fn perform(request: Request, app: &mut App, flags: &GlobalFlags) {
+    match request {
+        Request::Apply(selection) => {
+            crate::commands::apply::run_selection(&selection, flags, &mut app.snapshot.state);
+        }
+        Request::Adopt(name) => {
+            crate::commands::adopt::run(&crate::commands::adopt::AdoptOptions {
+                items: &[name],
+                from: None,
+                all: false,
+                yes: false,
+                to: None,
+                dry_run: false,
+                include_deps: false,
+                aur_section: false,
+                with_configs: false,
+                report: None,
+                orphans: false,
+                force: false,
+            })
+        }
+        Request::Diff(mapping) => {
+            crate::error::report("show dotfile diff", show_diff(&mapping, flags));
+        }
+        Request::Nothing | Request::Quit | Request::Refresh => {}
+    }
+}
+
+/// Show what applying a dotfile would change, destination first
+fn show_diff(mapping: &DotfileMapping, flags: &GlobalFlags) -> Result<()> {
+    use anyhow::Context;
+
+    let (source, destination) = crate::core::dotfiles::resolve_mapping(mapping)?;
+    let output = std::process::Command::new("diff")
+        .arg("-ruN")
+        .arg("--color=always")
+        .arg(&destination)
+        .arg(&source)
+        .logged()
+        .output()
+        .context("Failed to run diff")?;
+
+    let mut out = crate::cli::ui::Pager::new(flags);
+    out.write_all(&output.stdout).ok();
+    out.finish();
+    Ok(())
+}
+
+fn wait_for_enter() {
+    print!(
+        "\n  {} press Enter to return to the dashboard ",
+        crate::internal::color::blue("info:")
+    );
+    std::io::stdout().flush().ok();
+    crate::cli::ui::read_answer("the dashboard").ok();
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [main, footer] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [left, right] = Layout::horizontal([Constraint::Percentage(50); 2]).areas(main);
+    let [plan_area, dotfiles_area] = Layout::vertical([Constraint::Percentage(50); 2]).areas(left);
+    let [services_area, adopt_area] =
+        Layout::vertical([Constraint::Percentage(50); 2]).areas(right);
+
+    let plan_lines = app
+        .plan_items
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let mark = if app.selected.contains(&idx) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            let (text, color) = match item {
+                PlanItem::Install(name) => (format!("install {}", name), Color::Green),
+                PlanItem::Remove(name) => (format!("remove {}", name), Color::Red),
+                PlanItem::AurUpdate(name) => (format!("update {} (AUR)", name), Color::Yellow),
+                PlanItem::RepoUpdates(count) => {
+                    (format!("{} repository updates", count), Color::Yellow)
+                }
+            };
+            ListItem::new(format!("{} {}", mark, text)).style(Style::default().fg(color))
+        })
+        .collect();
+    draw_list(
+        frame,
+        app,
+        Pane::Plan,
+        plan_area,
+        "1 plan",
+        plan_lines,
+        if app.snapshot.plan.is_in_sync() {
+            "system is in sync"
+        } else {
+            "no package changes"
+        },
+    );
+
+    let dotfile_lines = app
+        .drifted()
+        .iter()
+        .map(|action| {
+            ListItem::new(format!(
+                "{} {} -> {}",
+                super::dotfile_label(&action.status),
+                action.mapping.source,
+                action.mapping.destination
+            ))
+        })
+        .collect();
+    draw_list(
+        frame,
+        app,
+        Pane::Dotfiles,
+        dotfiles_area,
+        "2 dotfiles",
+        dotfile_lines,
+        "all dotfiles up to date",
+    );
+
+    let service_lines = app
+        .snapshot
+        .plan
+        .services
+        .iter()
+        .map(|service| {
+            let ok = service.enabled && service.active;
+            ListItem::new(format!(
+                "{} {} ({}, {})",
+                if ok { "✓" } else { "✗" },
+                service.name,
+                if service.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                },
+                if service.active { "active" } else { "inactive" }
+            ))
+            .style(Style::default().fg(if ok { Color::Green } else { Color::Red }))
+        })
+        .collect();
+    draw_list(
+        frame,
+        app,
+        Pane::Services,
+        services_area,
+        "3 services",
+        service_lines,
+        "no services configured",
+    );
+
+    let adopt_lines = app
+        .snapshot
+        .adopt_candidates
+        .iter()
+        .map(|name| ListItem::new(name.as_str()))
+        .collect();
+    let adopt_title = format!("4 adopt ({} untracked)", app.snapshot.state.untracked.len());
+    draw_list(
+        frame,
+        app,
+        Pane::Adopt,
+        adopt_area,
+        &adopt_title,
+        adopt_lines,
+        "nothing to adopt",
+    );
+
+    let help = match &app.message {
+        Some(message) => message.clone(),
+        None => "tab/1-4 pane  j/k move  space select  a apply  d diff  o adopt  r refresh  q quit"
+            .to_string(),
+    };
+    frame.render_widget(
+        Paragraph::new(Line::from(help)).style(Style::default().add_modifier(Modifier::DIM)),
+        footer,
+    );
+}
+
+fn draw_list(
+    frame: &mut Frame,
+    app: &App,
+    pane: Pane,
+    area: Rect,
+    title: &str,
+    items: Vec<ListItem>,
+    empty: &str,
+) {
+    let focused = app.pane() == pane;
+    let block = Block::bordered()
+        .title(format!(" {} ({}) ", title, items.len()))
+        .border_style(if focused {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default().add_modifier(Modifier::DIM)
+        });
+
+    if items.is_empty() {
+        frame.render_widget(
+            Paragraph::new(empty.to_string())
+                .style(Style::default().add_modifier(Modifier::DIM))
+                .block(block),
+            area,
+        );
+        return;
+    }
+
+    let pane_index = PANES.iter().position(|p| *p == pane).unwrap_or(0);
+    let mut state = ListState::default();
+    if focused {
+        state.select(Some(app.cursors[pane_index]));
+    }
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::plan::Plan;
+    use crate::core::state::PackageState;
+
+    fn app() -> App {
+        App::new(Snapshot {
+            state: PackageState {
+                untracked: Vec::new(),
+                hidden: Vec::new(),
+                managed: Vec::new(),
+                pinned: Default::default(),
+                sources: Default::default(),
+                remove_hooks: Default::default(),
+            },
+            plan: Plan {
+                installs: vec!["htop".to_string()],
+                removals: vec!["nano".to_string()],
+                aur_updates: vec!["paru".to_string()],
+                repo_update_count: 3,
+                ..Plan::default()
+            },
+            adopt_candidates: vec!["btop".to_string()],
+        })
+    }
+
+    #[test]
+    fn test_apply_uses_cursor_without_selection() {
+        let mut app = app();
+        app.handle_key(KeyCode::Char('j'));
+        assert_eq!(
+            app.handle_key(KeyCode::Char('a')),
+            Request::Apply(Selection {
+                removals: vec!["nano".to_string()],
+                ..Selection::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_apply_uses_toggled_selection() {
+        let mut app = app();
+        app.handle_key(KeyCode::Char(' '));
+        for _ in 0..5 {
+            app.handle_key(KeyCode::Down);
+        }
+        app.handle_key(KeyCode::Char(' '));
+        assert_eq!(
+            app.handle_key(KeyCode::Char('a')),
+            Request::Apply(Selection {
+                installs: vec!["htop".to_string()],
+                repo_updates: true,
+                ..Selection::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_pane_keys_only_act_in_their_pane() {
+        let mut app = app();
+        assert_eq!(app.handle_key(KeyCode::Char('o')), Request::Nothing);
+        app.handle_key(KeyCode::Char('4'));
+        assert_eq!(
+            app.handle_key(KeyCode::Char('o')),
+            Request::Adopt("btop".to_string())
+        );
+        app.handle_key(KeyCode::BackTab);
+        assert_eq!(app.pane(), Pane::Services);
+    }
+}
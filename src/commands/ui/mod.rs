@@ -0,0 +1,156 @@
+//! Interactive dashboard over the plan API
+//!
+//! The dashboard itself lives behind the `tui` cargo feature. Without it, `owl ui`
+//! prints the same information as plain text.
+
+#[cfg(feature = "tui")]
+mod dashboard;
+
+use anyhow::{Context, Result};
+
+use crate::core::plan::{Plan, PlanOptions};
+use crate::core::state::PackageState;
+
+/// Everything the dashboard shows, gathered read-only
+pub(crate) struct Snapshot {
+    pub state: PackageState,
+    pub plan: Plan,
+    pub adopt_candidates: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn load() -> Result<Self> {
+        let config = crate::core::config::Config::load_all_relevant_config_files()
+            .context("Failed to load config")?;
+        let state = PackageState::load().context("Failed to load package state")?;
+        let plan = crate::core::plan::build_plan(&config, &state, &PlanOptions::default())?;
+        let adopt_candidates = crate::commands::adopt::discover_candidates(&state, &config)
+            .context("Failed to discover adoption candidates")?;
+        Ok(Self {
+            state,
+            plan,
+            adopt_candidates,
+        })
+    }
+}
+
+fn dotfile_label(status: &crate::core::dotfiles::DotfileStatus) -> &'static str {
+    match status {
+        crate::core::dotfiles::DotfileStatus::Create => "create",
+        crate::core::dotfiles::DotfileStatus::Update => "update",
+        crate::core::dotfiles::DotfileStatus::UpToDate => "up to date",
+    }
+}
+
+/// Run the ui command
+pub fn run(flags: &crate::cli::handler::GlobalFlags) {
+    let snapshot = match crate::internal::util::execute_with_progress(
+        Snapshot::load,
+        "Analyzing system configuration",
+    ) {
+        Ok(snapshot) => snapshot,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+
+    #[cfg(feature = "tui")]
+    crate::error::exit_on_error(dashboard::run(snapshot, flags));
+
+    #[cfg(not(feature = "tui"))]
+    print_summary(&snapshot, flags);
+}
+
+#[cfg(not(feature = "tui"))]
+fn print_summary(snapshot: &Snapshot, flags: &crate::cli::handler::GlobalFlags) {
+    use crate::internal::color;
+    use std::io::Write;
+
+    let plan = &snapshot.plan;
+    let mut out = crate::cli::ui::Pager::new(flags);
+
+    writeln!(
+        out,
+        "  {} this owl was built without the `tui` feature; showing a text summary",
+        color::blue("info:")
+    )
+    .ok();
+    writeln!(
+        out,
+        "  {} rebuild with `cargo install --path . --features tui` for the dashboard",
+        color::blue("info:")
+    )
+    .ok();
+
+    writeln!(out, "\n[{}]", color::blue("plan")).ok();
+    for name in &plan.installs {
+        writeln!(out, "  {} install {}", color::green("➔"), name).ok();
+    }
+    for name in &plan.removals {
+        writeln!(out, "  {} remove {}", color::red("➔"), name).ok();
+    }
+    for name in &plan.aur_updates {
+        writeln!(out, "  {} update {} (AUR)", color::yellow("➔"), name).ok();
+    }
+    if plan.repo_update_count > 0 {
+        writeln!(
+            out,
+            "  {} {} repository updates",
+            color::yellow("➔"),
+            plan.repo_update_count
+        )
+        .ok();
+    }
+    for name in &plan.held {
+        writeln!(out, "  {} held {} (pinned)", color::dim("➔"), name).ok();
+    }
+
+    writeln!(out, "\n[{}]", color::green("config")).ok();
+    for action in plan.dotfile_drift() {
+        writeln!(
+            out,
+            "  {} {} {} -> {}",
+            color::yellow("!"),
+            dotfile_label(&action.status),
+            action.mapping.source,
+            action.mapping.destination
+        )
+        .ok();
+    }
+
+    writeln!(out, "\n[{}]", color::blue("services")).ok();
+    for service in &plan.services {
+        writeln!(
+            out,
+            "  {} {} ({}, {})",
+            if service.enabled && service.active {
+                color::green("✓")
+            } else {
+                color::red("✗")
+            },
+            service.name,
+            if service.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            if service.active { "active" } else { "inactive" }
+        )
+        .ok();
+    }
+
+    writeln!(out, "\n[{}]", color::blue("adopt")).ok();
+    for name in &snapshot.adopt_candidates {
+        writeln!(out, "  {} {}", color::dim("·"), name).ok();
+    }
+    writeln!(
+        out,
+        "  {} {} untracked packages hidden from adopt",
+        color::blue("info:"),
+        snapshot.state.untracked.len()
+    )
+    .ok();
+
+    if plan.is_in_sync() {
+        writeln!(out, "\n  {} system is in sync", color::green("✓")).ok();
+    }
+    out.finish();
+}
@@ -0,0 +1,167 @@
+//! Make owl forget that it manages a package, leaving the config and the system alone
+
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashSet;
+
+use crate::core::config::Config;
+use crate::core::state::PackageState;
+use crate::internal::color;
+use crate::internal::util::glob_match;
+
+/// Run the untrack command
+pub fn run(
+    patterns: &[String],
+    all_missing: bool,
+    ignore_future: bool,
+    flags: &crate::cli::handler::GlobalFlags,
+) {
+    crate::error::exit_on_error(untrack(patterns, all_missing, ignore_future, flags.dry_run));
+}
+
+fn untrack(
+    patterns: &[String],
+    all_missing: bool,
+    ignore_future: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let mut state = PackageState::load().context("Failed to load package state")?;
+    let installed = if all_missing {
+        crate::core::pm::ParuPacman::new().list_installed()?
+    } else {
+        HashSet::new()
+    };
+    let (selected, unmatched) = select(&state.managed, patterns, all_missing, &installed);
+
+    println!("[{}]", color::blue("untrack"));
+    for pattern in &unmatched {
+        println!(
+            "  {} no managed package matches '{}'",
+            color::yellow("!"),
+            pattern
+        );
+    }
+    if selected.is_empty() {
+        if unmatched.is_empty() {
+            println!("  {} nothing to untrack", color::green("✓"));
+            return Ok(());
+        }
+        return Err(anyhow!(
+            "nothing to untrack; see `owl list` for managed packages"
+        ));
+    }
+
+    let verb = if dry_run {
+        "would untrack"
+    } else {
+        "untracked"
+    };
+    for package in &selected {
+        let ignored = if ignore_future {
+            " (ignored from now on)"
+        } else {
+            ""
+        };
+        println!("  {} {} {}{}", color::green("✓"), verb, package, ignored);
+    }
+
+    // Still declared packages are installed, so the next apply would manage them again
+    if let Ok(config) = Config::load_all_relevant_config_files() {
+        for package in selected.iter().filter(|p| config.packages.contains_key(*p)) {
+            let location = config
+                .provenance
+                .get(package)
+                .and_then(|provenance| {
+                    let path = provenance.path.as_ref()?;
+                    Some(format!(
+                        " ({}:{})",
+                        crate::internal::files::friendly_path(&path.display().to_string()),
+                        provenance.line
+                    ))
+                })
+                .unwrap_or_default();
+            println!(
+                "  {} {} is still in the config{}; the next apply tracks it again",
+                color::yellow("!"),
+                package,
+                location
+            );
+        }
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+    for package in selected {
+        if ignore_future {
+            state.ignore(package);
+        } else {
+            state.remove_managed(&package);
+        }
+    }
+    state.save().context("Failed to save package state")
+}
+
+/// Managed packages matching any pattern, plus the uninstalled ones with `all_missing`.
+/// Also returns the patterns that matched nothing.
+fn select(
+    managed: &[String],
+    patterns: &[String],
+    all_missing: bool,
+    installed: &HashSet<String>,
+) -> (Vec<String>, Vec<String>) {
+    let mut unmatched = Vec::new();
+    let mut selected: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let matches: Vec<&String> = managed
+            .iter()
+            .filter(|package| glob_match(pattern, package))
+            .collect();
+        if matches.is_empty() {
+            unmatched.push(pattern.clone());
+        }
+        selected.extend(matches.into_iter().cloned());
+    }
+    if all_missing {
+        selected.extend(
+            managed
+                .iter()
+                .filter(|package| !installed.contains(*package))
+                .cloned(),
+        );
+    }
+    selected.sort();
+    selected.dedup();
+    (selected, unmatched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(items: &[&str]) -> Vec<String> {
+        items.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_select_combines_patterns_and_missing() {
+        let managed = names(&["htop", "python-numpy", "python-requests", "vim"]);
+        let installed: HashSet<String> = names(&["htop", "python-numpy", "python-requests"])
+            .into_iter()
+            .collect();
+
+        let (selected, unmatched) = select(
+            &managed,
+            &names(&["python-*", "htop", "firefox"]),
+            true,
+            &installed,
+        );
+        assert_eq!(
+            selected,
+            names(&["htop", "python-numpy", "python-requests", "vim"])
+        );
+        assert_eq!(unmatched, names(&["firefox"]));
+
+        let (selected, _) = select(&managed, &[], true, &installed);
+        assert_eq!(selected, names(&["vim"]));
+    }
+}
@@ -0,0 +1,283 @@
+//! Check deployed dotfiles against the content and mode owl recorded when writing them
+
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::cli::context::Context;
+use crate::core::deployed::{DeployedFile, Drift};
+use crate::internal::color;
+
+/// A deployed file that failed verification
+#[derive(Debug, Serialize)]
+struct Failure {
+    /// The package whose `:config` deploys the file, when it is still configured
+    package: Option<String>,
+    path: PathBuf,
+    /// "missing", "modified", or "permissions"
+    problem: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actual_mode: Option<String>,
+    restored: bool,
+    /// Why `--restore` could not put the file back
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restore_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    checked: usize,
+    failures: Vec<Failure>,
+}
+
+/// Run the verify command; exits 1 when a file fails and was not restored
+pub fn run(json: bool, restore: bool, flags: &crate::cli::handler::GlobalFlags, ctx: &Context) {
+    if !restore || flags.dry_run {
+        crate::core::state::forbid_writes();
+    }
+    let report = match verify(restore, flags.dry_run, ctx) {
+        Ok(report) => report,
+        Err(err) => crate::error::exit_with_error(err),
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(out) => println!("{}", out),
+            Err(err) => crate::error::exit_with_error(err.into()),
+        }
+    } else {
+        print_report(&report, restore, flags.dry_run);
+    }
+    if report.failures.iter().any(|failure| !failure.restored) {
+        std::process::exit(1);
+    }
+}
+
+fn verify(restore: bool, dry_run: bool, ctx: &Context) -> Result<Report> {
+    let mut manifest = crate::core::deployed::load()?;
+    let packages = mapping_packages(ctx);
+
+    let mut failures = Vec::new();
+    for (path, file) in &manifest {
+        if let Some(drift) = crate::core::deployed::check(path, file)? {
+            failures.push(failure(path, file, drift, &packages));
+        }
+    }
+
+    if restore && !dry_run && !failures.is_empty() {
+        for failure in &mut failures {
+            let Some(file) = manifest.get(&failure.path).cloned() else {
+                continue;
+            };
+            match redeploy(&failure.path, &file) {
+                Ok(deployed) => {
+                    manifest.insert(failure.path.clone(), deployed);
+                    failure.restored = true;
+                }
+                Err(err) => failure.restore_error = Some(format!("{:#}", err)),
+            }
+        }
+        crate::core::deployed::save(&manifest)?;
+    }
+
+    Ok(Report {
+        checked: manifest.len(),
+        failures,
+    })
+}
+
+/// Configured mapping destinations to the package that declares them
+fn mapping_packages(ctx: &Context) -> HashMap<String, String> {
+    let Ok(config) = ctx.config() else {
+        return HashMap::new();
+    };
+    config
+        .packages
+        .iter()
+        .flat_map(|(name, package)| {
+            package.config.iter().map(move |cfg| {
+                (
+                    crate::core::dotfiles::parse_mapping(cfg).destination,
+                    name.clone(),
+                )
+            })
+        })
+        .collect()
+}
+
+fn failure(
+    path: &Path,
+    file: &DeployedFile,
+    drift: Drift,
+    packages: &HashMap<String, String>,
+) -> Failure {
+    let (problem, expected_mode, actual_mode) = match drift {
+        Drift::Missing => ("missing", None, None),
+        Drift::Modified => ("modified", None, None),
+        Drift::Permissions { expected, actual } => (
+            "permissions",
+            Some(format!("{:o}", expected)),
+            Some(format!("{:o}", actual)),
+        ),
+    };
+    Failure {
+        package: packages.get(&file.mapping).cloned(),
+        path: path.to_path_buf(),
+        problem,
+        expected_mode,
+        actual_mode,
+        restored: false,
+        restore_error: None,
+    }
+}
+
+/// Copy the file back from its dotfile source with the recorded mode
+fn redeploy(path: &Path, file: &DeployedFile) -> Result<DeployedFile> {
+    let data =
+        fs::read(&file.source).with_context(|| format!("Cannot restore {}", path.display()))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, data).with_context(|| format!("Failed to write {}", path.display()))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(file.mode))
+        .with_context(|| format!("Failed to set the mode of {}", path.display()))?;
+    crate::core::deployed::record(&file.mapping, &file.source, path)
+}
+
+/// Failures grouped by package, unconfigured ones last
+fn by_package(failures: &[Failure]) -> BTreeMap<(bool, String), Vec<&Failure>> {
+    let mut groups: BTreeMap<(bool, String), Vec<&Failure>> = BTreeMap::new();
+    for failure in failures {
+        let key = match &failure.package {
+            Some(package) => (false, package.clone()),
+            None => (true, "no longer configured".to_string()),
+        };
+        groups.entry(key).or_default().push(failure);
+    }
+    groups
+}
+
+fn print_report(report: &Report, restore: bool, dry_run: bool) {
+    println!("[{}]", color::blue("verify"));
+    if report.checked == 0 {
+        println!(
+            "  {} no deployed dotfiles recorded yet; `owl apply` or `owl dots` records them",
+            color::blue("info:")
+        );
+        return;
+    }
+
+    for ((_, package), failures) in by_package(&report.failures) {
+        println!("  {}", color::bold(&package));
+        for failure in failures {
+            let path = crate::internal::files::friendly_path(&failure.path.display().to_string());
+            let problem = match (&failure.expected_mode, &failure.actual_mode) {
+                (Some(expected), Some(actual)) => format!("mode {} -> {}", expected, actual),
+                _ => failure.problem.to_string(),
+            };
+            let outcome = if failure.restored {
+                format!(" {} restored", color::green("➔"))
+            } else if let Some(err) = &failure.restore_error {
+                format!(" {} {}", color::yellow("!"), err)
+            } else if restore && dry_run {
+                format!(" {} would restore", color::green("➔"))
+            } else {
+                String::new()
+            };
+            println!("    {} {} {}{}", color::red("✗"), problem, path, outcome);
+        }
+    }
+
+    let failed = report.failures.iter().filter(|f| !f.restored).count();
+    let restored = report.failures.len() - failed;
+    if report.failures.is_empty() {
+        println!(
+            "  {} {} deployed file{} intact",
+            color::green("✓"),
+            report.checked,
+            if report.checked == 1 { "" } else { "s" }
+        );
+    } else if failed == 0 {
+        println!(
+            "\n  {} restored {} of {} deployed files",
+            color::green("✓"),
+            restored,
+            report.checked
+        );
+    } else {
+        let hint = if restore {
+            ""
+        } else {
+            "; `owl verify --restore` redeploys them"
+        };
+        println!(
+            "\n  {} {} of {} deployed files failed{}",
+            color::red("✗"),
+            failed,
+            report.checked,
+            hint
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed(package: Option<&str>, path: &str) -> Failure {
+        Failure {
+            package: package.map(str::to_string),
+            path: PathBuf::from(path),
+            problem: "modified",
+            expected_mode: None,
+            actual_mode: None,
+            restored: false,
+            restore_error: None,
+        }
+    }
+
+    #[test]
+    fn test_by_package_puts_unconfigured_last() {
+        let failures = vec![
+            failed(None, "/h/.old"),
+            failed(Some("tmux"), "/h/.tmux.conf"),
+            failed(Some("htop"), "/h/.config/htop/htoprc"),
+            failed(Some("tmux"), "/h/.tmux/plugins"),
+        ];
+        let groups: Vec<(String, usize)> = by_package(&failures)
+            .into_iter()
+            .map(|((_, package), files)| (package, files.len()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                ("htop".to_string(), 1),
+                ("tmux".to_string(), 2),
+                ("no longer configured".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redeploy_restores_content_and_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, path) = (dir.path().join("src"), dir.path().join("deep/dst"));
+        fs::write(&source, "set -g mouse on\n").unwrap();
+        let mut file = crate::core::deployed::record("~/dst", &source, &source).unwrap();
+        file.mode = 0o600;
+
+        let deployed = redeploy(&path, &file).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "set -g mouse on\n");
+        assert_eq!(deployed.mode, 0o600);
+        assert_eq!(
+            crate::core::deployed::check(&path, &deployed).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use crate::internal::color;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_COMMIT: &str = env!("OWL_GIT_COMMIT");
+pub const BUILD_DATE: &str = env!("OWL_BUILD_DATE");
+
+const AUR_INFO_URL: &str = "https://aur.archlinux.org/rpc/v5/info";
+const AUR_PACKAGE_NAME: &str = "owl";
+const GITHUB_RELEASES_URL: &str = "https://api.github.com/repos/wobbat/owl/releases/latest";
+const UPDATE_CHECK_TIMEOUT_SECS: u64 = 5;
+
+/// Where to look for newer owl releases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum UpdateSource {
+    Aur,
+    Github,
+}
+
+#[derive(Debug, Deserialize)]
+struct AurInfoResponse {
+    #[serde(default)]
+    results: Vec<AurInfoPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AurInfoPackage {
+    #[serde(rename = "Version")]
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+}
+
+/// Print version and build information
+pub fn print_version() {
+    println!("owl {}", VERSION);
+    println!("  commit:  {}", GIT_COMMIT);
+    println!("  built:   {}", BUILD_DATE);
+    println!("  backend: {}", backend_description());
+}
+
+/// Run the version command, optionally checking for a newer release
+pub fn run(check: bool, source: UpdateSource) {
+    print_version();
+    if !check {
+        return;
+    }
+
+    println!();
+    match fetch_latest_version(source) {
+        Ok(latest) => match compare_versions(&latest, VERSION) {
+            Ordering::Greater => println!(
+                "  {} owl {} is available (installed: {})",
+                color::yellow("update:"),
+                color::bold(&latest),
+                VERSION
+            ),
+            _ => println!("  {} owl is up to date", color::green("➔")),
+        },
+        Err(err) => println!(
+            "  {} could not check for updates ({})",
+            color::blue("info:"),
+            color::dim(&err.to_string())
+        ),
+    }
+}
+
+fn backend_description() -> String {
+    match crate::core::pm::aur_helper_command() {
+        Some(helper) => format!("pacman + {}", helper),
+        None => "pacman (no AUR helper)".to_string(),
+    }
+}
+
+fn fetch_latest_version(source: UpdateSource) -> Result<String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(UPDATE_CHECK_TIMEOUT_SECS))
+        .build();
+
+    match source {
+        UpdateSource::Aur => {
+            let response: AurInfoResponse = agent
+                .get(AUR_INFO_URL)
+                .query("arg[]", AUR_PACKAGE_NAME)
+                .call()
+                .map_err(|e| anyhow!("AUR request failed: {}", e))?
+                .into_json()
+                .map_err(|e| anyhow!("Failed to parse AUR response: {}", e))?;
+            response
+                .results
+                .into_iter()
+                .next()
+                .map(|pkg| pkg.version)
+                .ok_or_else(|| anyhow!("{} not found in the AUR", AUR_PACKAGE_NAME))
+        }
+        UpdateSource::Github => {
+            let release: GithubRelease = agent
+                .get(GITHUB_RELEASES_URL)
+                .set("User-Agent", &format!("owl/{}", VERSION))
+                .call()
+                .map_err(|e| anyhow!("GitHub request failed: {}", e))?
+                .into_json()
+                .map_err(|e| anyhow!("Failed to parse GitHub response: {}", e))?;
+            Ok(release.tag_name)
+        }
+    }
+}
+
+/// Compare release versions like `v1.2.0` or AUR's `1.2.0-1` by their numeric components
+fn compare_versions(left: &str, right: &str) -> Ordering {
+    fn components(version: &str) -> Vec<u64> {
+        let version = version.trim().trim_start_matches('v');
+        let version = version.split_once('-').map_or(version, |(v, _)| v);
+        version
+            .split('.')
+            .map(|part| {
+                part.chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect::<String>()
+                    .parse()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    let (left, right) = (components(left), components(right));
+    let len = left.len().max(right.len());
+    (0..len)
+        .map(|idx| {
+            let l = left.get(idx).copied().unwrap_or(0);
+            let r = right.get(idx).copied().unwrap_or(0);
+            l.cmp(&r)
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("v0.2.0", "0.1.0"), Ordering::Greater);
+        assert_eq!(compare_versions("0.1.0-2", "0.1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.1", "0.1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.10.0", "0.9.3"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.0", "1.0.1"), Ordering::Less);
+    }
+}
@@ -0,0 +1,197 @@
+//! Explain why owl cares about a package
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::cli::context::Context;
+use crate::core::config::Config;
+use crate::internal::color;
+use crate::internal::constants;
+
+/// Run the why command
+pub fn run(package: &str, ctx: &Context) {
+    crate::error::exit_on_error(why(package, ctx));
+}
+
+fn why(package: &str, ctx: &Context) -> Result<()> {
+    let owl_root = crate::internal::files::owl_dir()?;
+    let config = ctx.config()?;
+    let state = ctx.state()?;
+    let info = crate::core::package::get_package_info(&[package.to_string()])?;
+    let info = info.get(package);
+
+    println!("[{}]", color::blue("why"));
+    println!("  {}", color::highlight(package));
+
+    let origin = origin_chain(config, &owl_root, package);
+    if origin.is_empty() {
+        println!("  {} not configured", color::yellow("!"));
+    }
+    for (idx, line) in origin.iter().enumerate() {
+        field(if idx == 0 { "declared:" } else { "via:" }, line);
+    }
+
+    let mut flags = Vec::new();
+    if state.is_managed(package) {
+        flags.push("managed");
+    }
+    if state.is_untracked(package) {
+        flags.push("untracked");
+    }
+    if state.is_hidden(package) {
+        flags.push("hidden");
+    }
+    if flags.is_empty() {
+        flags.push("not in state");
+    }
+    field("state:", &flags.join(", "));
+    if let Some(source) = state.source(package) {
+        let shown = Path::new(source)
+            .strip_prefix(&owl_root)
+            .map_or_else(|_| source.to_string(), |path| path.display().to_string());
+        field("adopted into:", &shown);
+    }
+
+    match info {
+        Some(info) => {
+            let from_repo = crate::core::pm::ParuPacman::new()
+                .batch_repo_available(&[package.to_string()])?
+                .contains(package);
+            field(
+                "installed:",
+                &format!(
+                    "{} from {}, {}",
+                    info.version,
+                    if from_repo { "the repos" } else { "the AUR" },
+                    if info.explicit {
+                        "explicitly"
+                    } else {
+                        "as a dependency"
+                    }
+                ),
+            );
+        }
+        None => field("installed:", "no"),
+    }
+
+    if let Some(declared) = config.packages.get(package) {
+        for cfg in &declared.config {
+            let mapping = crate::core::dotfiles::parse_mapping(cfg);
+            field(
+                "dotfile:",
+                &format!("{} -> {}", mapping.source, mapping.destination),
+            );
+        }
+        if let Some(service) = &declared.service {
+            field("service:", service);
+        }
+        let mut env: Vec<_> = declared.env_vars.iter().collect();
+        env.sort();
+        for (key, value) in env {
+            field("env:", &format!("{}={}", key, value));
+        }
+    } else if let Some(info) = info {
+        let required_by = if info.required_by.is_empty() {
+            "nothing".to_string()
+        } else {
+            info.required_by.join(", ")
+        };
+        field("required:", &required_by);
+    }
+    Ok(())
+}
+
+/// Print one aligned `label value` line
+fn field(label: &str, value: &str) {
+    println!("  {} {}", color::blue(&format!("{:<10}", label)), value);
+}
+
+/// Where `package` was declared, followed by the `@group` lines that pulled that file in,
+/// with paths shown relative to the owl directory
+pub fn origin_chain(config: &Config, owl_root: &Path, package: &str) -> Vec<String> {
+    let Some(provenance) = config.provenance.get(package) else {
+        return Vec::new();
+    };
+    let relative = |path: &Path| {
+        path.strip_prefix(owl_root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+
+    let mut chain = Vec::new();
+    let mut current = provenance;
+    let mut seen = HashSet::new();
+    loop {
+        let Some(path) = current.path.as_deref() else {
+            chain.push(format!("line {}", current.line));
+            break;
+        };
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let parent = path.parent().unwrap_or(path);
+        let kind = if parent == owl_root.join(constants::GROUPS_DIR) {
+            format!(" (group {})", stem)
+        } else if parent == owl_root.join(constants::HOSTS_DIR) {
+            format!(" (host {})", stem)
+        } else {
+            String::new()
+        };
+        chain.push(format!("{}:{}{}", relative(path), current.line, kind));
+
+        if parent != owl_root.join(constants::GROUPS_DIR) || !seen.insert(stem.clone()) {
+            break;
+        }
+        match config.group_provenance.get(&stem) {
+            Some(included) => current = included,
+            None => break,
+        }
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::Provenance;
+    use std::path::PathBuf;
+
+    fn at(path: &str, line: usize) -> Provenance {
+        Provenance {
+            path: Some(PathBuf::from(path)),
+            line,
+        }
+    }
+
+    #[test]
+    fn test_origin_chain_follows_groups() {
+        let mut config = Config::new();
+        config
+            .provenance
+            .insert("hyprland".to_string(), at("/o/groups/wm.owl", 4));
+        config
+            .provenance
+            .insert("htop".to_string(), at("/o/main.owl", 2));
+        config
+            .group_provenance
+            .insert("wm".to_string(), at("/o/groups/desktop.owl", 1));
+        config
+            .group_provenance
+            .insert("desktop".to_string(), at("/o/hosts/laptop.owl", 3));
+        let root = Path::new("/o");
+
+        assert_eq!(
+            origin_chain(&config, root, "hyprland"),
+            vec![
+                "groups/wm.owl:4 (group wm)",
+                "groups/desktop.owl:1 (group desktop)",
+                "hosts/laptop.owl:3 (host laptop)",
+            ]
+        );
+        assert_eq!(origin_chain(&config, root, "htop"), vec!["main.owl:2"]);
+        assert!(origin_chain(&config, root, "firefox").is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+//! Read-only package queries straight from pacman's database
+//!
+//! Built with the `alpm` feature, the reads in `core::system::PackageQueries` come
+//! from the libalpm database on disk instead of pacman's output: `local/*/desc` for
+//! the installed packages, and the `sync/*.db` archives for what the repos carry.
+//! Installs, removals, and everything else still go through pacman.
+
+use anyhow::{Context, Result, anyhow};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::core::pm::PackageInfo;
+
+/// A pacman database directory, `/var/lib/pacman` on a normal system
+pub struct AlpmDb {
+    root: PathBuf,
+    local: OnceLock<Vec<PackageInfo>>,
+}
+
+impl AlpmDb {
+    /// The database at `root`, when its local database can be read
+    pub fn open(root: &Path) -> Option<Self> {
+        std::fs::read_dir(root.join("local")).ok()?;
+        Some(Self {
+            root: root.to_path_buf(),
+            local: OnceLock::new(),
+        })
+    }
+
+    /// Every installed package, read once per handle
+    fn local(&self) -> Result<&[PackageInfo]> {
+        if let Some(packages) = self.local.get() {
+            return Ok(packages);
+        }
+        let packages = read_local(&self.root.join("local"))?;
+        Ok(self.local.get_or_init(|| packages))
+    }
+
+    /// The names of every package the sync databases carry
+    fn sync_names(&self) -> Result<HashSet<String>> {
+        let dir = self.root.join("sync");
+        let mut names = HashSet::new();
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return Ok(names);
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("db") {
+                names.extend(
+                    read_sync_names(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?,
+                );
+            }
+        }
+        Ok(names)
+    }
+}
+
+impl crate::core::system::PackageQueries for AlpmDb {
+    fn installed_versions(&self) -> Result<BTreeMap<String, String>> {
+        Ok(self
+            .local()?
+            .iter()
+            .map(|info| (info.name.clone(), info.version.clone()))
+            .collect())
+    }
+
+    fn explicit_packages(&self) -> Result<HashSet<String>> {
+        Ok(self
+            .local()?
+            .iter()
+            .filter(|info| info.explicit)
+            .map(|info| info.name.clone())
+            .collect())
+    }
+
+    fn foreign_versions(&self) -> Result<BTreeMap<String, String>> {
+        let synced = self.sync_names()?;
+        Ok(self
+            .local()?
+            .iter()
+            .filter(|info| !synced.contains(&info.name))
+            .map(|info| (info.name.clone(), info.version.clone()))
+            .collect())
+    }
+
+    fn package_info(&self, packages: &[String]) -> Result<HashMap<String, PackageInfo>> {
+        let wanted: HashSet<&String> = packages.iter().collect();
+        Ok(self
+            .local()?
+            .iter()
+            .filter(|info| wanted.contains(&info.name))
+            .map(|info| (info.name.clone(), info.clone()))
+            .collect())
+    }
+}
+
+/// Parse every `desc` under `local`, with "Required By" worked out as pacman does
+fn read_local(local: &Path) -> Result<Vec<PackageInfo>> {
+    let mut packages = Vec::new();
+    for entry in
+        std::fs::read_dir(local).with_context(|| format!("Failed to read {}", local.display()))?
+    {
+        let desc = entry?.path().join("desc");
+        // `ALPM_DB_VERSION` and half-written entries have no desc
+        let Ok(text) = std::fs::read_to_string(&desc) else {
+            continue;
+        };
+        packages.extend(parse_local_desc(&text));
+    }
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    fill_required_by(&mut packages);
+    Ok(packages)
+}
+
+/// One installed package from its `desc`, with the fields `pacman -Qi` shows
+fn parse_local_desc(desc: &str) -> Option<PackageInfo> {
+    let mut info = PackageInfo {
+        explicit: true,
+        ..PackageInfo::default()
+    };
+    let mut field = "";
+    for line in desc.lines() {
+        if line.len() > 2 && line.starts_with('%') && line.ends_with('%') {
+            field = &line[1..line.len() - 1];
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        match field {
+            "NAME" => info.name = line.to_string(),
+            "VERSION" => info.version = line.to_string(),
+            "DESC" => info.description = line.to_string(),
+            "SIZE" => info.installed_size = line.parse().unwrap_or(0),
+            "REASON" => info.explicit = line == "0",
+            "INSTALLDATE" => info.install_date = line.parse().map(format_date).unwrap_or_default(),
+            "PACKAGER" => info.packager = line.to_string(),
+            "DEPENDS" => info.depends_on.push(line.to_string()),
+            "PROVIDES" => info.provides.push(line.to_string()),
+            "GROUPS" => info.groups.push(line.to_string()),
+            "OPTDEPENDS" => info
+                .optional_deps
+                .extend(line.split(':').next().map(str::trim).map(str::to_string)),
+            _ => {}
+        }
+    }
+    (!info.name.is_empty()).then_some(info)
+}
+
+/// The name a dependency or provision such as `sh>=5` or `libfoo.so=1-64` is about
+fn bare_name(spec: &str) -> &str {
+    spec.split(['<', '>', '=']).next().unwrap_or(spec)
+}
+
+/// For each package, the installed packages that depend on its name or on something
+/// it provides, sorted as `pacman -Qi` lists them
+fn fill_required_by(packages: &mut [PackageInfo]) {
+    let mut dependents: HashMap<&str, Vec<String>> = HashMap::new();
+    for package in packages.iter() {
+        for dep in &package.depends_on {
+            dependents
+                .entry(bare_name(dep))
+                .or_default()
+                .push(package.name.clone());
+        }
+    }
+    let required: Vec<Vec<String>> = packages
+        .iter()
+        .map(|package| {
+            let mut names: Vec<String> = std::iter::once(package.name.as_str())
+                .chain(package.provides.iter().map(|spec| bare_name(spec)))
+                .flat_map(|name| dependents.get(name).into_iter().flatten().cloned())
+                .collect();
+            names.sort();
+            names.dedup();
+            names
+        })
+        .collect();
+    for (package, required_by) in packages.iter_mut().zip(required) {
+        package.required_by = required_by;
+    }
+}
+
+/// An install date as pacman prints it in the C locale, `%c` in local time
+fn format_date(secs: i64) -> String {
+    let time = secs as libc::time_t;
+    let mut buf = [0u8; 64];
+    // SAFETY: `tm` is written by localtime_r before strftime reads it, and strftime
+    // writes at most `buf.len()` bytes, returning how many
+    let len = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return String::new();
+        }
+        libc::strftime(buf.as_mut_ptr().cast(), buf.len(), c"%c".as_ptr(), &tm)
+    };
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// The package names in a sync database, gzip-compressed as repo-add writes them by
+/// default, or a plain tar
+fn read_sync_names(path: &Path) -> Result<HashSet<String>> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)?.read_to_end(&mut bytes)?;
+    let reader: Box<dyn Read> = match bytes.get(..4) {
+        Some([0x1f, 0x8b, ..]) => Box::new(flate2::read::GzDecoder::new(bytes.as_slice())),
+        Some([0x28, 0xb5, 0x2f, 0xfd]) => {
+            return Err(anyhow!("zstd-compressed sync databases are not supported"));
+        }
+        _ => Box::new(bytes.as_slice()),
+    };
+    let mut names = HashSet::new();
+    for entry in tar::Archive::new(reader).entries()? {
+        let entry = entry?;
+        let path = entry.path()?;
+        // Entries are `<name>-<pkgver>-<pkgrel>/` and the files in it
+        let Some(dir) = path.components().next() else {
+            continue;
+        };
+        let dir = dir.as_os_str().to_string_lossy();
+        let mut parts = dir.rsplitn(3, '-');
+        if let (Some(_), Some(_), Some(name)) = (parts.next(), parts.next(), parts.next()) {
+            names.insert(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::system::PackageQueries;
+
+    fn desc(dir: &Path, entry: &str, body: &str) {
+        std::fs::create_dir_all(dir.join(entry)).unwrap();
+        std::fs::write(dir.join(entry).join("desc"), body).unwrap();
+    }
+
+    fn sync_db(path: &Path, entries: &[&str]) {
+        let file = std::fs::File::create(path).unwrap();
+        let gz = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(gz);
+        for entry in entries {
+            let body = format!("%NAME%\n{}\n", entry);
+            let mut header = tar::Header::new_gnu();
+            header.set_size(body.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("{}/desc", entry), body.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    /// A database with what `CLI_QI` and `CLI_Q` describe
+    fn fixture(root: &Path) {
+        let local = root.join("local");
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::write(local.join("ALPM_DB_VERSION"), "9\n").unwrap();
+        desc(
+            &local,
+            "bash-5.2.037-1",
+            "%NAME%\nbash\n\n%VERSION%\n5.2.037-1\n\n%DESC%\nThe GNU Bourne Again shell\n\n\
+             %PACKAGER%\nArch Builder\n\n%SIZE%\n2097152\n\n%REASON%\n1\n\n\
+             %DEPENDS%\nglibc\nreadline>=7.0\n\n%OPTDEPENDS%\nbash-completion: for tab completion\n\n\
+             %PROVIDES%\nsh\n\n",
+        );
+        desc(
+            &local,
+            "glibc-2.41-1",
+            "%NAME%\nglibc\n\n%VERSION%\n2.41-1\n\n%DESC%\nGNU C Library\n\n\
+             %PACKAGER%\nArch Builder\n\n%SIZE%\n1024\n\n%REASON%\n1\n\n%GROUPS%\nbase\n\n",
+        );
+        desc(
+            &local,
+            "paru-2.0.4-1",
+            "%NAME%\nparu\n\n%VERSION%\n2.0.4-1\n\n%DESC%\nFeature packed AUR helper\n\n\
+             %PACKAGER%\nUnknown Packager\n\n%SIZE%\n3072\n\n%DEPENDS%\nglibc\nsh\n\n",
+        );
+        std::fs::create_dir_all(root.join("sync")).unwrap();
+        sync_db(
+            &root.join("sync/core.db"),
+            &["bash-5.2.037-1", "glibc-2.41-1", "linux-api-headers-6.10-1"],
+        );
+    }
+
+    const CLI_Q: &str = "bash 5.2.037-1\nglibc 2.41-1\nparu 2.0.4-1\n";
+
+    const CLI_QI: &str = "\
+Name            : bash
+Version         : 5.2.037-1
+Description     : The GNU Bourne Again shell
+Groups          : None
+Provides        : sh
+Depends On      : glibc  readline>=7.0
+Optional Deps   : bash-completion: for tab completion
+Required By     : paru
+Installed Size  : 2.00 MiB
+Packager        : Arch Builder
+Install Reason  : Installed as a dependency for another package
+
+Name            : glibc
+Version         : 2.41-1
+Description     : GNU C Library
+Groups          : base
+Provides        : None
+Depends On      : None
+Optional Deps   : None
+Required By     : bash  paru
+Installed Size  : 1024.00 B
+Packager        : Arch Builder
+Install Reason  : Installed as a dependency for another package
+
+Name            : paru
+Version         : 2.0.4-1
+Description     : Feature packed AUR helper
+Groups          : None
+Provides        : None
+Depends On      : glibc  sh
+Optional Deps   : None
+Required By     : None
+Installed Size  : 3.00 KiB
+Packager        : Unknown Packager
+Install Reason  : Explicitly installed
+";
+
+    #[test]
+    fn test_queries_match_what_pacman_reports_for_the_same_database() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        fixture(temp.path());
+        let db = AlpmDb::open(temp.path()).unwrap();
+        assert!(AlpmDb::open(&temp.path().join("missing")).is_none());
+
+        assert_eq!(
+            db.installed_versions().unwrap(),
+            crate::core::pm::parse_versions(CLI_Q)
+        );
+        assert_eq!(
+            db.explicit_packages().unwrap(),
+            HashSet::from(["paru".to_string()])
+        );
+        assert_eq!(
+            db.foreign_versions().unwrap(),
+            BTreeMap::from([("paru".to_string(), "2.0.4-1".to_string())])
+        );
+
+        let names: Vec<String> = ["bash", "glibc", "paru", "missing"]
+            .map(str::to_string)
+            .to_vec();
+        let cli: HashMap<String, PackageInfo> = crate::core::pm::parse_package_info(CLI_QI)
+            .into_iter()
+            .map(|info| (info.name.clone(), info))
+            .collect();
+        assert_eq!(db.package_info(&names).unwrap(), cli);
+    }
+}
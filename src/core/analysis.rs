@@ -0,0 +1,189 @@
+//! Comparisons between the system and the config that several commands share
+
+use std::collections::HashSet;
+
+use crate::core::config::Config;
+use crate::core::state::PackageState;
+
+/// Explicitly installed packages that are not managed, declared, untracked, or
+/// hidden: what `owl adopt` offers for review. Sorted by name.
+pub fn unmanaged_explicit(
+    explicit_installed: &HashSet<String>,
+    state: &PackageState,
+    config: &Config,
+) -> Vec<String> {
+    let mut candidates: Vec<String> = explicit_installed
+        .iter()
+        .filter(|pkg| !state.is_managed(pkg))
+        .filter(|pkg| !state.is_untracked(pkg))
+        .filter(|pkg| !state.is_hidden(pkg))
+        .filter(|pkg| !config.packages.contains_key(*pkg))
+        .cloned()
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// The line pointing at `owl adopt`; `None` when there is nothing to adopt or the
+/// `unmanaged_notice` setting turns it off
+pub fn unmanaged_notice(count: usize) -> Option<String> {
+    if count == 0 || !crate::core::settings::get().unmanaged_notice {
+        return None;
+    }
+    Some(format!(
+        "{} explicitly installed {} not managed — run `owl adopt` to review",
+        count,
+        if count == 1 {
+            "package is"
+        } else {
+            "packages are"
+        }
+    ))
+}
+
+/// Where state has drifted from the config and the system
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Drift {
+    /// Declared and managed, but no longer installed: removed behind owl's back.
+    /// The apply installs them again.
+    pub missing: Vec<String>,
+    /// Managed in state, but neither declared nor installed: stale entries to prune
+    pub stale: Vec<String>,
+}
+
+/// Compare `state.managed` and the config with the packages and groups `system` has
+pub fn drift(
+    state: &PackageState,
+    config: &Config,
+    system: &crate::core::system::SystemSnapshot,
+) -> Drift {
+    // A failed group lookup counts as installed: no drift is better than a wrong one
+    drift_with(state, config, |name| {
+        system.has_package_or_group(name).unwrap_or(true)
+    })
+}
+
+/// [`drift`] with the installed check supplied
+pub fn drift_with(
+    state: &PackageState,
+    config: &Config,
+    is_installed: impl Fn(&str) -> bool,
+) -> Drift {
+    let mut drift = Drift::default();
+    for name in state.managed.iter().filter(|name| !is_installed(name)) {
+        if config.packages.contains_key(name) {
+            drift.missing.push(name.clone());
+        } else {
+            drift.stale.push(name.clone());
+        }
+    }
+    drift.missing.sort();
+    drift.stale.sort();
+    drift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmanaged_explicit_filters_state_and_config() {
+        let mut state = PackageState {
+            untracked: Vec::new(),
+            hidden: Vec::new(),
+            managed: Vec::new(),
+            pinned: Default::default(),
+            sources: Default::default(),
+            remove_hooks: Default::default(),
+        };
+        state.add_managed("managed".to_string());
+        state.add_untracked("ignored".to_string());
+        state.add_hidden("hidden".to_string());
+
+        let mut config = Config::new();
+        config.packages.insert(
+            "in-config".to_string(),
+            crate::core::config::Package {
+                config: Vec::new(),
+                service: None,
+                env_vars: std::collections::HashMap::new(),
+                exact: false,
+                hooks: Default::default(),
+            },
+        );
+
+        let explicit_installed = HashSet::from([
+            "managed".to_string(),
+            "ignored".to_string(),
+            "hidden".to_string(),
+            "in-config".to_string(),
+            "candidate-b".to_string(),
+            "candidate-a".to_string(),
+        ]);
+
+        let candidates = unmanaged_explicit(&explicit_installed, &state, &config);
+        assert_eq!(
+            candidates,
+            vec!["candidate-a".to_string(), "candidate-b".to_string()]
+        );
+        assert_eq!(
+            unmanaged_notice(2).as_deref(),
+            Some("2 explicitly installed packages are not managed — run `owl adopt` to review")
+        );
+        assert_eq!(unmanaged_notice(0), None);
+    }
+
+    #[test]
+    fn test_drift_for_each_combination_of_config_state_and_installed() {
+        let mut state = PackageState {
+            untracked: Vec::new(),
+            hidden: Vec::new(),
+            managed: Vec::new(),
+            pinned: Default::default(),
+            sources: Default::default(),
+            remove_hooks: Default::default(),
+        };
+        let mut config = Config::new();
+        let mut installed = HashSet::new();
+        // Named after their membership: declared, managed, installed
+        for (name, declared, managed, is_installed) in [
+            ("d-m-i", true, true, true),
+            ("d-m", true, true, false),
+            ("d-i", true, false, true),
+            ("d", true, false, false),
+            ("m-i", false, true, true),
+            ("m", false, true, false),
+            ("i", false, false, true),
+            ("none", false, false, false),
+        ] {
+            if declared {
+                config.packages.insert(
+                    name.to_string(),
+                    crate::core::config::Package {
+                        config: Vec::new(),
+                        service: None,
+                        env_vars: std::collections::HashMap::new(),
+                        exact: false,
+                        hooks: Default::default(),
+                    },
+                );
+            }
+            if managed {
+                state.add_managed(name.to_string());
+            }
+            if is_installed {
+                installed.insert(name.to_string());
+            }
+        }
+
+        let drift = drift_with(&state, &config, |name| installed.contains(name));
+        assert_eq!(
+            drift,
+            Drift {
+                missing: vec!["d-m".to_string()],
+                stale: vec!["m".to_string()],
+            }
+        );
+        assert_eq!(drift_with(&state, &config, |_| true), Drift::default());
+    }
+}
@@ -0,0 +1,235 @@
+//! Scripts run around a whole apply
+//!
+//! Executables in `~/.owl/hooks` named after their stage, `pre-apply` or `post-apply`,
+//! or starting with it and a dash (`post-apply-10-notify`), run in lexical order:
+//! pre-apply ones before any change, post-apply ones once everything else is done.
+//! They are spawned through `core::hooks`, so they get its environment and exit-code
+//! contract, plus the plan's counts in further `OWL_*` variables.
+
+use anyhow::{Context, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::internal::color;
+
+const HOOKS_DIR: &str = "hooks";
+
+/// When a hook script runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    PreApply,
+    PostApply,
+}
+
+impl Stage {
+    /// The script name, and the prefix of the ones that share the stage
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PreApply => "pre-apply",
+            Self::PostApply => "post-apply",
+        }
+    }
+
+    fn matches(self, file_name: &str) -> bool {
+        file_name
+            .strip_prefix(self.name())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+    }
+}
+
+/// What the scripts are told about the run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HookEnv {
+    /// Packages the plan installs, repo and AUR
+    pub installs: usize,
+    /// Repo and AUR updates the plan runs
+    pub updates: usize,
+    pub removals: usize,
+    /// Whether the apply failed; only known to post-apply scripts
+    pub failed: Option<bool>,
+    /// The history id of the run
+    pub run_id: Option<String>,
+    /// Destinations of the dotfiles the run wrote; only known to post-apply scripts
+    pub changed_files: Vec<String>,
+}
+
+impl HookEnv {
+    /// `OWL_INSTALLS`, `OWL_UPDATES`, `OWL_REMOVALS`, and for post-apply `OWL_STATUS`
+    /// (`ok` or `failed`)
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("OWL_INSTALLS", self.installs.to_string()),
+            ("OWL_UPDATES", self.updates.to_string()),
+            ("OWL_REMOVALS", self.removals.to_string()),
+        ];
+        if let Some(failed) = self.failed {
+            vars.push((
+                "OWL_STATUS",
+                if failed { "failed" } else { "ok" }.to_string(),
+            ));
+        }
+        vars
+    }
+
+    /// What `core::hooks` tells every hook, for the `stage` scripts
+    fn context(&self, stage: Stage, dry_run: bool) -> crate::core::hooks::HookContext {
+        crate::core::hooks::HookContext {
+            phase: stage.name(),
+            package: None,
+            changed_files: self.changed_files.clone(),
+            dry_run,
+            run_id: self.run_id.clone(),
+        }
+    }
+}
+
+/// Run the `stage` scripts in `~/.owl/hooks`
+pub fn run(stage: Stage, env: &HookEnv, dry_run: bool) -> Result<()> {
+    run_in(
+        &crate::internal::files::owl_dir()?.join(HOOKS_DIR),
+        stage,
+        env,
+        dry_run,
+    )
+}
+
+/// The `stage` scripts in `dir` in lexical order; nothing when `dir` does not exist.
+/// Files that are not executable are noted and left out.
+pub fn discover(dir: &Path, stage: Stage) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut scripts = Vec::new();
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if !stage.matches(name) || !path.is_file() {
+            continue;
+        }
+        let executable = std::fs::metadata(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .permissions()
+            .mode()
+            & 0o111
+            != 0;
+        if executable {
+            scripts.push(path);
+        } else {
+            println!(
+                "  {}",
+                color::info(&format!("not executable, skipping: {}", path.display()))
+            );
+        }
+    }
+    scripts.sort();
+    Ok(scripts)
+}
+
+/// Run the `stage` scripts in `dir` with `env`, stopping at the first that fails. A
+/// dry run only notes them.
+fn run_in(dir: &Path, stage: Stage, env: &HookEnv, dry_run: bool) -> Result<()> {
+    let context = env.context(stage, dry_run);
+    for script in discover(dir, stage)? {
+        let display = crate::internal::files::friendly_path(&script.to_string_lossy());
+        if dry_run {
+            println!(
+                "  {}",
+                color::info(&format!(
+                    "dry run, not running {} hook {}",
+                    stage.name(),
+                    display
+                ))
+            );
+            continue;
+        }
+        println!("  {}", color::action(stage.name(), &display));
+        let mut command = std::process::Command::new(&script);
+        command.envs(env.vars());
+        crate::core::hooks::spawn(
+            command,
+            &format!("{} hook {}", stage.name(), display),
+            &context,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(dir: &Path, name: &str, body: &str, mode: u32) {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn test_discover_matches_the_stage_in_lexical_order() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        let dir = temp.path();
+        script(dir, "pre-apply-20-commit", "true", 0o755);
+        script(dir, "pre-apply", "true", 0o755);
+        script(dir, "pre-apply-10-snapshot", "true", 0o755);
+        script(dir, "pre-applyx", "true", 0o755);
+        script(dir, "pre-apply-30-off", "true", 0o644);
+        script(dir, "post-apply", "true", 0o755);
+
+        let names: Vec<String> = discover(dir, Stage::PreApply)
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            ["pre-apply", "pre-apply-10-snapshot", "pre-apply-20-commit"]
+        );
+        assert_eq!(discover(dir, Stage::PostApply).unwrap().len(), 1);
+        assert!(
+            discover(&dir.join("missing"), Stage::PreApply)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_run_passes_the_plan_and_stops_at_a_failure() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        let dir = temp.path();
+        let log = dir.join("log");
+        let record = format!(
+            "echo \"$OWL_PHASE $OWL_RUN_ID $OWL_INSTALLS $OWL_UPDATES $OWL_REMOVALS ${{OWL_STATUS:-}}\" >> {}",
+            log.display()
+        );
+        script(dir, "post-apply-1", &record, 0o755);
+        script(dir, "post-apply-2", "exit 3", 0o755);
+        script(dir, "post-apply-3", &record, 0o755);
+        let env = HookEnv {
+            installs: 2,
+            updates: 14,
+            removals: 1,
+            failed: Some(false),
+            run_id: Some("17-apply".to_string()),
+            changed_files: Vec::new(),
+        };
+
+        // A dry run runs none of them
+        run_in(dir, Stage::PostApply, &env, true).unwrap();
+        assert!(!log.exists());
+
+        let err = run_in(dir, Stage::PostApply, &env, false).unwrap_err();
+        assert!(err.to_string().contains("post-apply hook"));
+        assert!(err.to_string().contains("post-apply-2"));
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "post-apply 17-apply 2 14 1 ok\n"
+        );
+
+        // Without post-apply scripts, pre-apply has nothing to run
+        run_in(dir, Stage::PreApply, &HookEnv::default(), false).unwrap();
+    }
+}
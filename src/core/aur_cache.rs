@@ -0,0 +1,235 @@
+//! Remembered AUR update checks
+//!
+//! Asking the AUR takes seconds, so the answer is kept beside the state for
+//! `[aur] cache_minutes` and reused while the foreign packages and their versions
+//! are exactly what was checked. Installing, removing, or updating any of them
+//! changes that key and forces a fresh check. Read-only commands write the cache
+//! too: it only records what the AUR said, never what owl manages.
+//!
+//! A check asks the AUR RPC for all foreign packages in one info request and
+//! compares versions with `pm::vercmp`; only when the AUR cannot be reached does it
+//! fall back to the AUR helper's `-Qua`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::core::pm::{ParuPacman, PendingUpdate};
+
+const CACHE_FILE: &str = "aur-updates.json";
+
+static BYPASS: AtomicBool = AtomicBool::new(false);
+
+/// Ignore cached results for the rest of the process (`--refresh-aur`); fresh
+/// results are still stored
+pub fn bypass() {
+    BYPASS.store(true, Ordering::Relaxed);
+}
+
+/// One finished check and what it was run against
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AurCache {
+    /// Seconds since the Unix epoch
+    checked_at: u64,
+    /// Foreign packages and their installed versions at the time of the check
+    foreign: BTreeMap<String, String>,
+    updates: Vec<PendingUpdate>,
+}
+
+impl AurCache {
+    fn is_fresh_for(&self, foreign: &BTreeMap<String, String>, now: u64, ttl: u64) -> bool {
+        self.foreign == *foreign && now.saturating_sub(self.checked_at) < ttl
+    }
+}
+
+fn cache_path() -> Result<PathBuf> {
+    crate::core::state::PackageState::sibling_path(CACHE_FILE)
+}
+
+/// The stored check, or `None` when there is none or it cannot be parsed
+fn read(path: &Path) -> Option<AurCache> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content)
+        .map_err(|err| log::debug!("aur cache: ignoring {}: {}", path.display(), err))
+        .ok()
+}
+
+fn write(path: &Path, cache: &AurCache) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let content = serde_json::to_string_pretty(cache).context("Failed to serialize AUR cache")?;
+    std::fs::write(path, content + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Pending updates for the `foreign` packages (name to installed version): the
+/// cached answer while it is fresh, otherwise whatever `check` reports. Failed
+/// checks are not cached.
+pub fn pending_updates(
+    foreign: &BTreeMap<String, String>,
+    check: impl FnOnce() -> Result<Vec<PendingUpdate>>,
+) -> Result<Vec<PendingUpdate>> {
+    if foreign.is_empty() {
+        return Ok(Vec::new());
+    }
+    let path = cache_path()?;
+    let ttl = crate::core::settings::get().aur.cache_minutes * 60;
+    let now = crate::core::history::now();
+    if !BYPASS.load(Ordering::Relaxed)
+        && let Some(cache) = read(&path)
+        && cache.is_fresh_for(foreign, now, ttl)
+    {
+        log::debug!(
+            "aur cache: reusing the check from {}s ago",
+            now - cache.checked_at
+        );
+        return Ok(cache.updates);
+    }
+
+    let updates = check()?;
+    let cache = AurCache {
+        checked_at: now,
+        foreign: foreign.clone(),
+        updates,
+    };
+    if let Err(err) = write(&path, &cache) {
+        log::debug!("aur cache: {:#}", err);
+    }
+    Ok(cache.updates)
+}
+
+/// Foreign packages whose AUR version is newer than the installed one
+fn newer_versions(
+    installed: &BTreeMap<String, String>,
+    available: &HashMap<String, String>,
+) -> Vec<PendingUpdate> {
+    installed
+        .iter()
+        .filter_map(|(name, from)| {
+            let to = available.get(name)?;
+            crate::core::pm::vercmp(from, to)
+                .is_lt()
+                .then(|| PendingUpdate {
+                    name: name.clone(),
+                    from: from.clone(),
+                    to: to.clone(),
+                })
+        })
+        .collect()
+}
+
+/// Pending updates for the `foreign` packages from one AUR RPC info request
+pub fn rpc_updates(foreign: &BTreeMap<String, String>) -> Result<Vec<PendingUpdate>> {
+    let names: Vec<String> = foreign.keys().cloned().collect();
+    let available: HashMap<String, String> = crate::core::search::aur_info_batch(&names)?
+        .into_iter()
+        .map(|(name, info)| (name, info.version))
+        .collect();
+    Ok(newer_versions(foreign, &available))
+}
+
+/// The RPC check, or the AUR helper's when the RPC request fails
+fn check(foreign: &BTreeMap<String, String>) -> Result<Vec<PendingUpdate>> {
+    rpc_updates(foreign).or_else(|err| {
+        log::debug!(
+            "aur cache: RPC check failed, asking the AUR helper: {:#}",
+            err
+        );
+        ParuPacman::new().aur_updates()
+    })
+}
+
+/// Pending updates for the `foreign` packages, from the cache or a fresh check
+pub fn updates(foreign: &BTreeMap<String, String>) -> Result<Vec<PendingUpdate>> {
+    pending_updates(foreign, || check(foreign))
+}
+
+/// Names of the AUR packages with an update pending
+pub fn update_names(foreign: &BTreeMap<String, String>) -> Result<Vec<String>> {
+    Ok(updates(foreign)?
+        .into_iter()
+        .map(|update| update.name)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn versions(items: &[(&str, &str)]) -> BTreeMap<String, String> {
+        items
+            .iter()
+            .map(|(name, version)| (name.to_string(), version.to_string()))
+            .collect()
+    }
+
+    fn cache() -> AurCache {
+        AurCache {
+            checked_at: 1_000,
+            foreign: versions(&[("paru", "2.0.3-1"), ("spotify", "1.2.0-1")]),
+            updates: vec![PendingUpdate {
+                name: "paru".to_string(),
+                from: "2.0.3-1".to_string(),
+                to: "2.0.4-1".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_newer_versions_only_lists_newer_versions() {
+        let installed = versions(&[
+            ("paru", "2.0.3-1"),
+            ("yay-bin", "12.3.5-1"),
+            ("local-only", "1-1"),
+            ("devel-git", "r200.abc123-1"),
+            ("epoch", "1:1.0-1"),
+        ]);
+        let available: HashMap<String, String> = versions(&[
+            ("paru", "2.0.4-1"),
+            ("yay-bin", "12.3.5-1"),
+            ("devel-git", "r100.def456-1"),
+            ("epoch", "2.0-1"),
+        ])
+        .into_iter()
+        .collect();
+        assert_eq!(
+            newer_versions(&installed, &available),
+            vec![PendingUpdate {
+                name: "paru".to_string(),
+                from: "2.0.3-1".to_string(),
+                to: "2.0.4-1".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cache_round_trips_and_ignores_garbage() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("state").join(CACHE_FILE);
+        assert_eq!(read(&path), None);
+        write(&path, &cache()).unwrap();
+        assert_eq!(read(&path), Some(cache()));
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert_eq!(read(&path), None);
+    }
+
+    #[test]
+    fn test_cache_is_fresh_only_for_the_same_versions_within_ttl() {
+        let cache = cache();
+        let ttl = 15 * 60;
+        assert!(cache.is_fresh_for(&cache.foreign, 1_000 + ttl - 1, ttl));
+        assert!(!cache.is_fresh_for(&cache.foreign, 1_000 + ttl, ttl));
+        assert!(!cache.is_fresh_for(&cache.foreign, 1_000, 0));
+
+        let updated = versions(&[("paru", "2.0.4-1"), ("spotify", "1.2.0-1")]);
+        assert!(!cache.is_fresh_for(&updated, 1_001, ttl));
+        let removed = versions(&[("spotify", "1.2.0-1")]);
+        assert!(!cache.is_fresh_for(&removed, 1_001, ttl));
+    }
+}
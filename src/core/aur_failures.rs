@@ -0,0 +1,91 @@
+//! AUR builds that failed, kept for `owl apply --retry-failed`
+//!
+//! Each AUR phase drops the packages it attempted from the list and adds the ones that
+//! failed again, so the list is what the last attempt at each package left broken.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+const CACHE_FILE: &str = "aur-failed.json";
+
+fn cache_path() -> Result<PathBuf> {
+    crate::core::state::PackageState::sibling_path(CACHE_FILE)
+}
+
+fn read_from(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write_to(path: &Path, failed: &[String]) -> Result<()> {
+    if failed.is_empty() {
+        return match std::fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+            _ => Ok(()),
+        };
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let content =
+        serde_json::to_string_pretty(failed).context("Failed to serialize the failed builds")?;
+    std::fs::write(path, content + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// `previous` without the `attempted` packages, plus the `failed` ones, sorted
+fn merge(previous: Vec<String>, attempted: &[String], failed: &[String]) -> Vec<String> {
+    let mut merged: Vec<String> = previous
+        .into_iter()
+        .filter(|name| !attempted.contains(name))
+        .chain(failed.iter().cloned())
+        .collect();
+    merged.sort();
+    merged.dedup();
+    merged
+}
+
+/// The packages whose last AUR build failed, sorted; empty when none are recorded
+pub fn read() -> Vec<String> {
+    cache_path()
+        .map(|path| read_from(&path))
+        .unwrap_or_default()
+}
+
+/// Record the outcome of building `attempted`, of which `failed` did not build
+pub fn record(attempted: &[String], failed: &[String]) -> Result<()> {
+    let path = cache_path()?;
+    write_to(&path, &merge(read_from(&path), attempted, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_are_replaced_by_the_next_attempt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".state").join(CACHE_FILE);
+        let names =
+            |names: &[&str]| -> Vec<String> { names.iter().map(ToString::to_string).collect() };
+        assert!(read_from(&path).is_empty());
+
+        let failed = merge(Vec::new(), &names(&["a", "b", "c"]), &names(&["c", "a"]));
+        assert_eq!(failed, ["a", "c"]);
+        write_to(&path, &failed).unwrap();
+        assert_eq!(read_from(&path), failed);
+
+        // `c` built this time, `b` failed, and `a` was not attempted
+        let failed = merge(read_from(&path), &names(&["b", "c"]), &names(&["b"]));
+        assert_eq!(failed, ["a", "b"]);
+
+        write_to(&path, &[]).unwrap();
+        assert!(!path.exists());
+        write_to(&path, &[]).unwrap();
+    }
+}
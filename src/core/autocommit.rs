@@ -0,0 +1,176 @@
+//! Commit the config files owl edits on the user's behalf
+//!
+//! With `git_autocommit = true` in settings and the owl directory inside a git work
+//! tree, commands that rewrite config files hand the touched paths here. Only those
+//! paths are staged and committed; a commit is skipped with a warning when the index
+//! already holds something else, so owl never sweeps up the user's own staged work.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::core::git;
+use crate::internal::color;
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Skip auto-commits for the rest of the process (`--no-commit`)
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Committed,
+    /// The files already matched the last commit
+    Unchanged,
+    NotARepo,
+    /// Paths staged by someone else, relative to the work tree
+    UnrelatedStaged(Vec<String>),
+}
+
+/// `path` as shown in commit messages: relative to the owl directory when inside it
+pub fn describe(path: &Path) -> String {
+    let owl = crate::internal::files::owl_dir().unwrap_or_default();
+    path.strip_prefix(&owl)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Commit `files` with `message` when auto-commit is on. Failures are reported as
+/// warnings: the edit itself already succeeded.
+pub fn commit_config_change(files: &[PathBuf], message: &str) {
+    if files.is_empty()
+        || DISABLED.load(Ordering::Relaxed)
+        || !crate::core::settings::get().git_autocommit
+    {
+        return;
+    }
+    let owl = match crate::internal::files::owl_dir() {
+        Ok(owl) => owl,
+        Err(err) => return warn(&format!("{:#}", err)),
+    };
+    match commit(&owl, files, message) {
+        Ok(Outcome::Committed) => {
+            println!("  {} committed \"{}\"", color::green("✓"), message)
+        }
+        Ok(Outcome::Unchanged) => {}
+        Ok(Outcome::NotARepo) => {
+            log::debug!("autocommit: {} is not in a git repository", owl.display())
+        }
+        Ok(Outcome::UnrelatedStaged(paths)) => warn(&format!(
+            "other changes are staged ({}); commit them first or pass --no-commit",
+            paths.join(", ")
+        )),
+        Err(err) => warn(&format!("{:#}", err)),
+    }
+}
+
+fn warn(reason: &str) {
+    println!(
+        "  {} did not commit the config change: {}",
+        color::yellow("!"),
+        reason
+    );
+}
+
+fn commit(dir: &Path, files: &[PathBuf], message: &str) -> Result<Outcome> {
+    let Ok(top) = git::toplevel(dir) else {
+        return Ok(Outcome::NotARepo);
+    };
+    let top = std::fs::canonicalize(&top).unwrap_or(top);
+    let mut paths: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let file = std::fs::canonicalize(file).unwrap_or_else(|_| file.clone());
+            file.strip_prefix(&top)
+                .unwrap_or(&file)
+                .display()
+                .to_string()
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+
+    let unrelated: Vec<String> = git::staged_files(&top)?
+        .into_iter()
+        .filter(|staged| !paths.contains(staged))
+        .collect();
+    if !unrelated.is_empty() {
+        return Ok(Outcome::UnrelatedStaged(unrelated));
+    }
+    Ok(if git::commit_paths(&top, &paths, message)? {
+        Outcome::Committed
+    } else {
+        Outcome::Unchanged
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+
+    fn git(repo: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(args)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    #[test]
+    fn test_commit_stages_only_the_touched_files() {
+        let repo = tempfile::tempdir().unwrap();
+        let repo = repo.path();
+        git(repo, &["init", "--quiet"]);
+        git(repo, &["config", "user.name", "owl"]);
+        git(repo, &["config", "user.email", "owl@localhost"]);
+        fs::write(repo.join("main.owl"), "@packages\n").unwrap();
+        fs::write(repo.join("notes"), "draft\n").unwrap();
+        git(repo, &["add", "main.owl"]);
+        git(repo, &["commit", "--quiet", "-m", "init"]);
+
+        // An unrelated edit that is not staged stays out of the commit
+        fs::write(repo.join("main.owl"), "@packages\nhtop\n").unwrap();
+        fs::write(repo.join("notes"), "draft 2\n").unwrap();
+        let main = repo.join("main.owl");
+        let message = "owl adopt: add htop to main.owl";
+        assert_eq!(
+            commit(repo, std::slice::from_ref(&main), message).unwrap(),
+            Outcome::Committed
+        );
+        assert_eq!(git(repo, &["log", "-1", "--format=%s"]).trim(), message);
+        assert_eq!(
+            git(repo, &["show", "--name-only", "--format="]).trim(),
+            "main.owl"
+        );
+        assert_eq!(
+            commit(repo, std::slice::from_ref(&main), message).unwrap(),
+            Outcome::Unchanged
+        );
+
+        // Someone else's staged work blocks the commit
+        git(repo, &["add", "notes"]);
+        fs::write(&main, "@packages\nhtop\nbtop\n").unwrap();
+        assert_eq!(
+            commit(repo, &[main], message).unwrap(),
+            Outcome::UnrelatedStaged(vec!["notes".to_string()])
+        );
+        assert_eq!(git(repo, &["log", "--format=%s"]).lines().count(), 2);
+    }
+
+    #[test]
+    fn test_commit_outside_a_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.owl");
+        fs::write(&file, "@packages\n").unwrap();
+        assert_eq!(commit(dir.path(), &[file], "m").unwrap(), Outcome::NotARepo);
+    }
+}
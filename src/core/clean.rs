@@ -0,0 +1,414 @@
+//! Find and delete disk cruft: old snapshots and state backups, run logs beyond
+//! retention, surplus pacman cache files, and AUR helper build dirs for packages
+//! that are gone
+//!
+//! Every finder takes the directory it scans so the rules can be exercised on a
+//! fabricated tree; the callers pass the real locations.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Groups that `owl clean` reports and can be limited to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Backups,
+    Logs,
+    PackageCache,
+    AurBuilds,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::Backups,
+        Category::Logs,
+        Category::PackageCache,
+        Category::AurBuilds,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::Backups => "backups",
+            Category::Logs => "run logs",
+            Category::PackageCache => "pacman cache",
+            Category::AurBuilds => "AUR builds",
+        }
+    }
+}
+
+/// A file or directory that can go, with the bytes it frees
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub category: Category,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+impl Candidate {
+    fn new(category: Category, path: PathBuf) -> Self {
+        let size = disk_usage(&path);
+        Self {
+            category,
+            path,
+            size,
+        }
+    }
+}
+
+/// Bytes used by a file, or by everything under a directory; symlinks are not followed
+pub fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| disk_usage(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        paths.push(entry?.path());
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+}
+
+/// Snapshots beyond the newest `keep`, plus leftovers of interrupted creates
+pub fn old_snapshots(dir: &Path, keep: usize) -> Result<Vec<Candidate>> {
+    #[derive(Deserialize)]
+    struct Created {
+        created_at: u64,
+    }
+
+    let mut dated = Vec::new();
+    let mut candidates = Vec::new();
+    for path in entries(dir)? {
+        if file_name(&path).starts_with('.') {
+            candidates.push(Candidate::new(Category::Backups, path));
+            continue;
+        }
+        let created = fs::read_to_string(path.join("snapshot.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str::<Created>(&content).ok());
+        if let Some(created) = created {
+            dated.push((created.created_at, path));
+        }
+    }
+    dated.sort_by_key(|(created_at, _)| std::cmp::Reverse(*created_at));
+    candidates.extend(
+        dated
+            .into_iter()
+            .skip(keep)
+            .map(|(_, path)| Candidate::new(Category::Backups, path)),
+    );
+    Ok(candidates)
+}
+
+/// State files that `owl doctor --fix` moved aside as `<name>.corrupt`
+pub fn corrupt_state_files(dir: &Path) -> Result<Vec<Candidate>> {
+    Ok(entries(dir)?
+        .into_iter()
+        .filter(|path| file_name(path).ends_with(".corrupt"))
+        .map(|path| Candidate::new(Category::Backups, path))
+        .collect())
+}
+
+/// Run records in `dir` beyond the newest `keep`, and ones that no longer parse
+pub fn old_run_logs(dir: &Path, keep: usize) -> Result<Vec<Candidate>> {
+    #[derive(Deserialize)]
+    struct Started {
+        started_at: u64,
+    }
+
+    let mut dated = Vec::new();
+    let mut candidates = Vec::new();
+    for path in entries(dir)? {
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let started = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Started>(&content).ok());
+        match started {
+            Some(started) => dated.push((started.started_at, path)),
+            None => candidates.push(Candidate::new(Category::Logs, path)),
+        }
+    }
+    dated.sort_by(|a, b| b.cmp(a));
+    candidates.extend(
+        dated
+            .into_iter()
+            .skip(keep)
+            .map(|(_, path)| Candidate::new(Category::Logs, path)),
+    );
+    Ok(candidates)
+}
+
+/// Split `<name>-<pkgver>-<pkgrel>-<arch>.pkg.tar.*` into name, version, and arch
+fn parse_package_file(file: &str) -> Option<(&str, String, &str)> {
+    let (stem, _) = file.split_once(".pkg.tar")?;
+    let mut parts = stem.rsplitn(4, '-');
+    let arch = parts.next()?;
+    let pkgrel = parts.next()?;
+    let pkgver = parts.next()?;
+    let name = parts.next()?;
+    Some((name, format!("{}-{}", pkgver, pkgrel), arch))
+}
+
+/// Cached package files beyond the newest `keep` of each package, never the installed
+/// version. Signatures go with their package.
+pub fn stale_package_files(
+    dir: &Path,
+    keep: usize,
+    installed: &BTreeMap<String, String>,
+) -> Result<Vec<Candidate>> {
+    // (name, arch) to (modified, version, path) of each cached build
+    type Builds = Vec<(SystemTime, String, PathBuf)>;
+    let mut groups: HashMap<(String, String), Builds> = HashMap::new();
+    for path in entries(dir)? {
+        let file = file_name(&path);
+        if file.ends_with(".sig") {
+            continue;
+        }
+        let Some((name, version, arch)) = parse_package_file(file) else {
+            continue;
+        };
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        groups
+            .entry((name.to_string(), arch.to_string()))
+            .or_default()
+            .push((modified, version, path.clone()));
+    }
+
+    let mut candidates = Vec::new();
+    for ((name, _), mut files) in groups {
+        files.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| b.1.cmp(&a.1)));
+        for (_, version, path) in files.into_iter().skip(keep) {
+            if installed.get(&name) == Some(&version) {
+                continue;
+            }
+            let mut signature = path.clone().into_os_string();
+            signature.push(".sig");
+            let signature = PathBuf::from(signature);
+            candidates.push(Candidate::new(Category::PackageCache, path));
+            if signature.exists() {
+                candidates.push(Candidate::new(Category::PackageCache, signature));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(candidates)
+}
+
+/// AUR helper clone dirs none of whose packages are installed any more. Split packages
+/// are matched through the `pkgname` lines of `.SRCINFO`.
+pub fn orphaned_build_dirs(dir: &Path, installed: &HashSet<String>) -> Result<Vec<Candidate>> {
+    let mut candidates = Vec::new();
+    for path in entries(dir)? {
+        if !path.is_dir() || file_name(&path).starts_with('.') {
+            continue;
+        }
+        let mut names = vec![file_name(&path).to_string()];
+        if let Ok(srcinfo) = fs::read_to_string(path.join(".SRCINFO")) {
+            names.extend(srcinfo.lines().filter_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                (key.trim() == "pkgname").then(|| value.trim().to_string())
+            }));
+        }
+        if !names.iter().any(|name| installed.contains(name)) {
+            candidates.push(Candidate::new(Category::AurBuilds, path));
+        }
+    }
+    Ok(candidates)
+}
+
+/// What deleting a set of candidates achieved
+#[derive(Debug, Default)]
+pub struct Removal {
+    pub freed: u64,
+    /// Paths we were not allowed to delete, e.g. root-owned cache files
+    pub denied: Vec<PathBuf>,
+}
+
+/// Delete every candidate. Permission errors are collected for a privileged retry;
+/// anything else stops the run.
+pub fn remove(candidates: &[Candidate]) -> Result<Removal> {
+    let mut removal = Removal::default();
+    for candidate in candidates {
+        let result = if candidate.path.is_dir() {
+            fs::remove_dir_all(&candidate.path)
+        } else {
+            fs::remove_file(&candidate.path)
+        };
+        match result {
+            Ok(()) => removal.freed += candidate.size,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+                removal.denied.push(candidate.path.clone());
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to delete {}", candidate.path.display()));
+            }
+        }
+    }
+    Ok(removal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, bytes: usize) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![b'x'; bytes]).unwrap();
+    }
+
+    fn names(candidates: &[Candidate]) -> Vec<&str> {
+        candidates.iter().map(|c| file_name(&c.path)).collect()
+    }
+
+    #[test]
+    fn test_disk_usage_sums_a_tree() {
+        let dir = tempdir().unwrap();
+        write(&dir.path().join("a"), 10);
+        write(&dir.path().join("sub/b"), 20);
+        write(&dir.path().join("sub/deeper/c"), 30);
+        assert_eq!(disk_usage(dir.path()), 60);
+        assert_eq!(disk_usage(&dir.path().join("missing")), 0);
+    }
+
+    #[test]
+    fn test_old_snapshots_keep_the_newest() {
+        let dir = tempdir().unwrap();
+        for (name, created) in [("first", 100), ("second", 200), ("third", 300)] {
+            fs::create_dir_all(dir.path().join(name)).unwrap();
+            fs::write(
+                dir.path().join(name).join("snapshot.json"),
+                format!("{{\"created_at\": {}}}", created),
+            )
+            .unwrap();
+            write(&dir.path().join(name).join("files/abc"), 5);
+        }
+        fs::create_dir_all(dir.path().join(".fourth.partial")).unwrap();
+
+        let old = old_snapshots(dir.path(), 2).unwrap();
+        assert_eq!(names(&old), vec![".fourth.partial", "first"]);
+        assert!(old[1].size >= 5);
+    }
+
+    #[test]
+    fn test_old_run_logs_keep_the_newest_and_drop_unreadable() {
+        let dir = tempdir().unwrap();
+        for (id, started) in [("100-apply", 100), ("300-apply", 300), ("200-adopt", 200)] {
+            fs::write(
+                dir.path().join(format!("{}.json", id)),
+                format!("{{\"started_at\": {}}}", started),
+            )
+            .unwrap();
+        }
+        fs::write(dir.path().join("broken.json"), "{").unwrap();
+        fs::write(dir.path().join("notes.txt"), "kept").unwrap();
+
+        let old = old_run_logs(dir.path(), 2).unwrap();
+        assert_eq!(names(&old), vec!["broken.json", "100-apply.json"]);
+        assert!(old.iter().all(|c| c.category == Category::Logs));
+        assert!(
+            old_run_logs(&dir.path().join("missing"), 0)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_stale_package_files_keep_recent_and_installed() {
+        let dir = tempdir().unwrap();
+        // Written oldest first, so modification times follow the version order
+        for version in ["1.0-1", "1.1-1", "1.2-1", "1.3-1"] {
+            let file = format!("htop-{}-x86_64.pkg.tar.zst", version);
+            write(&dir.path().join(&file), 10);
+            write(&dir.path().join(format!("{}.sig", file)), 1);
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        write(&dir.path().join("lib32-foo-2.0-1-x86_64.pkg.tar.zst"), 10);
+        write(&dir.path().join("not-a-package.txt"), 10);
+
+        let installed = BTreeMap::from([("htop".to_string(), "1.0-1".to_string())]);
+        let stale = stale_package_files(dir.path(), 2, &installed).unwrap();
+        assert_eq!(
+            names(&stale),
+            vec![
+                "htop-1.1-1-x86_64.pkg.tar.zst",
+                "htop-1.1-1-x86_64.pkg.tar.zst.sig"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_package_file() {
+        assert_eq!(
+            parse_package_file("lib32-foo-1:2.0-3-x86_64.pkg.tar.zst"),
+            Some(("lib32-foo", "1:2.0-3".to_string(), "x86_64"))
+        );
+        assert_eq!(parse_package_file("readme.txt"), None);
+    }
+
+    #[test]
+    fn test_orphaned_build_dirs_match_split_packages() {
+        let dir = tempdir().unwrap();
+        write(&dir.path().join("gone/PKGBUILD"), 10);
+        write(&dir.path().join("installed/PKGBUILD"), 10);
+        write(&dir.path().join("split-base/.SRCINFO"), 0);
+        fs::write(
+            dir.path().join("split-base/.SRCINFO"),
+            "pkgbase = split-base\n\tpkgver = 1\npkgname = split-a\npkgname = split-b\n",
+        )
+        .unwrap();
+
+        let installed: HashSet<String> = ["installed", "split-b"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let orphaned = orphaned_build_dirs(dir.path(), &installed).unwrap();
+        assert_eq!(names(&orphaned), vec!["gone"]);
+    }
+
+    #[test]
+    fn test_remove_frees_the_reported_size() {
+        let dir = tempdir().unwrap();
+        write(&dir.path().join("old/a"), 10);
+        write(&dir.path().join("b.corrupt"), 5);
+        let mut candidates = corrupt_state_files(dir.path()).unwrap();
+        candidates.push(Candidate::new(Category::Backups, dir.path().join("old")));
+
+        let removal = remove(&candidates).unwrap();
+        assert_eq!(removal.freed, 15);
+        assert!(removal.denied.is_empty());
+        assert!(!dir.path().join("old").exists());
+        assert!(!dir.path().join("b.corrupt").exists());
+    }
+}
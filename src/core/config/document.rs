@@ -0,0 +1,394 @@
+//! Lossless view of a config file for targeted edits
+//!
+//! Unlike `Config`, a `Document` keeps every line, comment, and blank line, so owl can
+//! rewrite one entry without disturbing the rest of a hand-written file.
+
+use anyhow::{Context, Result};
+use std::ops::Range;
+use std::path::Path;
+
+/// The lines of a config file, exactly as written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    lines: Vec<String>,
+    trailing_newline: bool,
+}
+
+impl Document {
+    pub fn parse(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(ToString::to_string).collect(),
+            trailing_newline: content.ends_with('\n'),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        Ok(Self::parse(&content))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.render())
+            .with_context(|| format!("Failed to write config file: {}", path.display()))
+    }
+
+    /// The file content, byte-for-byte identical to the input when nothing was edited
+    pub fn render(&self) -> String {
+        let mut out = self.lines.join("\n");
+        if self.trailing_newline && !self.lines.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Line ranges (0-based) that declare `name`: an `@package` line together with its
+    /// `:` directives, or a single entry inside `@packages`
+    pub fn find_package(&self, name: &str) -> Vec<Range<usize>> {
+        let mut found = Vec::new();
+        let mut in_section = false;
+        let mut idx = 0;
+        while idx < self.lines.len() {
+            let line = self.lines[idx].trim();
+            if let Some(declared) = package_declaration(line) {
+                in_section = false;
+                let end = self.block_end(idx);
+                if declared == name {
+                    found.push(idx..end);
+                }
+                idx = end;
+                continue;
+            }
+            if matches!(line, "@packages" | "@pkgs" | "@aur") {
+                in_section = true;
+            } else if in_section && line == name {
+                found.push(idx..idx + 1);
+            }
+            idx += 1;
+        }
+        found
+    }
+
+    /// Remove every declaration of `name`, returning the removed lines with their 1-based
+    /// line numbers in the original file
+    pub fn remove_package(&mut self, name: &str) -> Vec<(usize, String)> {
+        let ranges = self.find_package(name);
+        let mut removed = Vec::new();
+        for range in ranges.iter().rev() {
+            for (offset, line) in self.lines.drain(range.clone()).enumerate().rev() {
+                removed.push((range.start + offset + 1, line));
+            }
+        }
+        removed.reverse();
+        removed
+    }
+
+    /// Pull in group `name` with an `@group` line after the existing ones, or above the
+    /// first entry when there are none. Returns false if the group is already there.
+    pub fn add_group(&mut self, name: &str) -> bool {
+        if self
+            .lines
+            .iter()
+            .any(|line| group_declaration(line) == Some(name))
+        {
+            return false;
+        }
+        let at = match self
+            .lines
+            .iter()
+            .rposition(|line| group_declaration(line).is_some())
+        {
+            Some(last) => last + 1,
+            None => {
+                let first = self
+                    .lines
+                    .iter()
+                    .position(|line| {
+                        let line = line.trim();
+                        !(line.is_empty() || line.starts_with('#'))
+                    })
+                    .unwrap_or(self.lines.len());
+                if first < self.lines.len() {
+                    self.lines.insert(first, String::new());
+                }
+                first
+            }
+        };
+        self.lines.insert(at, format!("@group {}", name));
+        if self.lines.len() == 1 {
+            self.trailing_newline = true;
+        }
+        true
+    }
+
+    /// Add `name` to the first `@packages` section, in case-insensitive alphabetical
+    /// order among its entries, or append a new section when the file has none. A new
+    /// entry goes above the comment lines directly over the entry that follows it, and
+    /// after the last entry, ahead of any comments that close the section.
+    pub fn insert_package(&mut self, name: &str) {
+        self.insert_into_section(&["@packages", "@pkgs"], name);
+    }
+
+    /// Add `name` to the first `@aur` section the same way, appending one when the
+    /// file has none
+    pub fn insert_aur_package(&mut self, name: &str) {
+        self.insert_into_section(&["@aur"], name);
+    }
+
+    /// Append an `@package` block for `name` with `directives` (e.g. `:config ...`)
+    /// under it, after a blank line
+    pub fn append_package_block(&mut self, name: &str, directives: &[String]) {
+        if self
+            .lines
+            .last()
+            .is_some_and(|line| !line.trim().is_empty())
+        {
+            self.lines.push(String::new());
+        }
+        self.lines.push(format!("@package {}", name));
+        self.lines.extend(directives.iter().cloned());
+        self.trailing_newline = true;
+    }
+
+    /// Sorted insert into the first section headed by one of `headers`; a new section
+    /// gets the first of them
+    fn insert_into_section(&mut self, headers: &[&str], name: &str) {
+        let Some(header) = self
+            .lines
+            .iter()
+            .position(|line| headers.contains(&line.trim()))
+        else {
+            if self
+                .lines
+                .last()
+                .is_some_and(|line| !line.trim().is_empty())
+            {
+                self.lines.push(String::new());
+            }
+            self.lines.push(headers[0].to_string());
+            self.lines.push(name.to_string());
+            self.trailing_newline = true;
+            return;
+        };
+        let end = self
+            .lines
+            .iter()
+            .skip(header + 1)
+            .position(|line| line.trim().starts_with('@'))
+            .map_or(self.lines.len(), |offset| header + 1 + offset);
+        let entries: Vec<usize> = (header + 1..end)
+            .filter(|&idx| {
+                let line = self.lines[idx].trim();
+                !(line.is_empty() || line.starts_with('#') || line.starts_with(':'))
+            })
+            .collect();
+
+        let key = name.to_lowercase();
+        let at = match entries
+            .iter()
+            .find(|&&idx| self.lines[idx].trim().to_lowercase() > key)
+        {
+            Some(&next) => {
+                let mut at = next;
+                while at > header + 1 && self.lines[at - 1].trim().starts_with('#') {
+                    at -= 1;
+                }
+                at
+            }
+            None => entries.last().map_or(header + 1, |last| last + 1),
+        };
+        self.lines.insert(at, name.to_string());
+    }
+
+    /// End (exclusive) of the `@package` block starting at `start`: the last `:` directive
+    /// before the next `@` line, so trailing comments and blank lines stay in place
+    fn block_end(&self, start: usize) -> usize {
+        let mut end = start + 1;
+        for (idx, line) in self.lines.iter().enumerate().skip(start + 1) {
+            let line = line.trim();
+            if line.starts_with(':') {
+                end = idx + 1;
+            } else if !(line.is_empty() || line.starts_with('#')) {
+                break;
+            }
+        }
+        end
+    }
+}
+
+fn package_declaration(line: &str) -> Option<&str> {
+    ["@package", "@pkg"].iter().find_map(|keyword| {
+        let rest = line.strip_prefix(keyword)?;
+        (rest.is_empty() || rest.starts_with(' ')).then(|| rest.trim())
+    })
+}
+
+fn group_declaration(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix("@group")?;
+    (rest.is_empty() || rest.starts_with(' ')).then(|| rest.trim())
+}
+
+/// Render removed lines as a unified diff against `path`
+pub fn removal_diff(path: &str, removed: &[(usize, String)]) -> Vec<String> {
+    let mut out = vec![format!("--- {}", path), format!("+++ {}", path)];
+    let mut idx = 0;
+    let mut shift = 0;
+    while idx < removed.len() {
+        let start = removed[idx].0;
+        let mut end = idx + 1;
+        while end < removed.len() && removed[end].0 == removed[end - 1].0 + 1 {
+            end += 1;
+        }
+        let count = end - idx;
+        out.push(format!(
+            "@@ -{},{} +{},0 @@",
+            start,
+            count,
+            start - 1 - shift
+        ));
+        out.extend(
+            removed[idx..end]
+                .iter()
+                .map(|(_, line)| format!("-{}", line)),
+        );
+        shift += count;
+        idx = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::Config;
+
+    const CONFIG: &str = "\
+# desktop
+@packages
+htop
+firefox
+git
+
+@package firefox
+:config firefox -> ~/.mozilla
+:service firefox-sync
+
+# editors
+@package nvim
+:config nvim -> ~/.config/nvim
+";
+
+    #[test]
+    fn test_render_is_lossless() {
+        assert_eq!(Document::parse(CONFIG).render(), CONFIG);
+        assert_eq!(Document::parse("a\nb").render(), "a\nb");
+    }
+
+    #[test]
+    fn test_find_package_blocks_and_section_entries() {
+        let doc = Document::parse(CONFIG);
+        assert_eq!(doc.find_package("firefox"), vec![3..4, 6..9]);
+        assert_eq!(doc.find_package("nvim"), vec![11..13]);
+        assert!(doc.find_package("fire").is_empty());
+    }
+
+    #[test]
+    fn test_remove_package_keeps_surrounding_lines() {
+        let mut doc = Document::parse(CONFIG);
+        let removed = doc.remove_package("firefox");
+        assert_eq!(
+            removed,
+            vec![
+                (4, "firefox".to_string()),
+                (7, "@package firefox".to_string()),
+                (8, ":config firefox -> ~/.mozilla".to_string()),
+                (9, ":service firefox-sync".to_string()),
+            ]
+        );
+        assert_eq!(
+            doc.render(),
+            "# desktop\n@packages\nhtop\ngit\n\n\n# editors\n@package nvim\n:config nvim -> ~/.config/nvim\n"
+        );
+        Config::parse(&doc.render()).unwrap();
+    }
+
+    #[test]
+    fn test_add_group_after_existing_groups_or_above_entries() {
+        let mut doc = Document::parse("# main\n@group base\n\n@packages\nhtop\n");
+        assert!(doc.add_group("stow"));
+        assert!(!doc.add_group("base"));
+        assert_eq!(
+            doc.render(),
+            "# main\n@group base\n@group stow\n\n@packages\nhtop\n"
+        );
+
+        let mut doc = Document::parse(CONFIG);
+        assert!(doc.add_group("stow"));
+        assert!(
+            doc.render()
+                .starts_with("# desktop\n@group stow\n\n@packages\n")
+        );
+        assert!(Config::parse(&doc.render()).unwrap().groups == vec!["stow"]);
+
+        let mut doc = Document::parse("");
+        assert!(doc.add_group("stow"));
+        assert_eq!(doc.render(), "@group stow\n");
+    }
+
+    #[test]
+    fn test_insert_package_sorted_within_first_section() {
+        let mut doc = Document::parse(
+            "@packages\nAlacritty\n# monitoring\nhtop\nzsh\n# end of tools\n\n@packages\nbase\n",
+        );
+        doc.insert_package("git");
+        doc.insert_package("btop");
+        doc.insert_package("zoxide");
+        assert_eq!(
+            doc.render(),
+            "@packages\nAlacritty\nbtop\ngit\n# monitoring\nhtop\nzoxide\nzsh\n# end of tools\n\n@packages\nbase\n"
+        );
+
+        let mut doc = Document::parse("@packages\nhtop\n# trailing note\n");
+        doc.insert_package("vim");
+        assert_eq!(doc.render(), "@packages\nhtop\nvim\n# trailing note\n");
+
+        let mut doc = Document::parse("@group base");
+        doc.insert_package("htop");
+        assert_eq!(doc.render(), "@group base\n\n@packages\nhtop\n");
+
+        let mut doc = Document::parse("@packages\nhtop\n");
+        doc.insert_aur_package("yay-bin");
+        doc.insert_aur_package("paru-bin");
+        assert_eq!(doc.render(), "@packages\nhtop\n\n@aur\nparu-bin\nyay-bin\n");
+        assert_eq!(doc.find_package("paru-bin"), vec![4..5]);
+
+        let mut doc = Document::parse("@packages\nhtop");
+        doc.append_package_block("kitty", &[":config kitty -> ~/.config/kitty".to_string()]);
+        assert_eq!(
+            doc.render(),
+            "@packages\nhtop\n\n@package kitty\n:config kitty -> ~/.config/kitty\n"
+        );
+        assert_eq!(doc.find_package("kitty"), vec![3..5]);
+    }
+
+    #[test]
+    fn test_removal_diff_hunks() {
+        let removed = vec![
+            (4, "firefox".to_string()),
+            (7, "@package firefox".to_string()),
+            (8, ":config firefox -> ~/.mozilla".to_string()),
+        ];
+        assert_eq!(
+            removal_diff("main.owl", &removed),
+            vec![
+                "--- main.owl",
+                "+++ main.owl",
+                "@@ -4,1 +3,0 @@",
+                "-firefox",
+                "@@ -7,2 +5,0 @@",
+                "-@package firefox",
+                "-:config firefox -> ~/.mozilla",
+            ]
+        );
+    }
+}
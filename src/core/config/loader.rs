@@ -1,30 +1,139 @@
-use anyhow::{Result, anyhow};
-use std::collections::HashSet;
-use std::env;
-use std::path::Path;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
 
 use super::Config;
 
+/// Size and modification time of a config file, `None` when it does not exist
+type Stamp = Option<(u64, SystemTime)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// How long before a load a file must have been modified for its stamp to be
+/// trusted. On filesystems with coarse timestamps, an edit right after the load can
+/// keep both the size and the modification time.
+const RACY_WINDOW: Duration = Duration::from_secs(1);
+
+/// A merged config and every file that was consulted to build it
+struct Loaded {
+    config: Config,
+    files: Vec<(PathBuf, Stamp)>,
+    /// When the files were stamped
+    loaded_at: SystemTime,
+}
+
+impl Loaded {
+    /// True when no file changed since the load. A file modified close to the load
+    /// is read again, since its stamp cannot tell a later edit apart.
+    fn is_current(&self) -> bool {
+        self.files.iter().all(|(path, seen)| {
+            let settled = seen.is_none_or(|(_, modified)| modified + RACY_WINDOW <= self.loaded_at);
+            settled && stamp(path) == *seen
+        })
+    }
+}
+
+// Loads for this process by config root and host name. An entry is reused until one
+// of its files, including ones that did not exist yet, changes on disk.
+static LOADED: OnceLock<Mutex<HashMap<(PathBuf, String), Loaded>>> = OnceLock::new();
+
+/// A load in progress: files are merged in priority order, earlier ones win
+struct Load {
+    loaded: Loaded,
+    known_groups: HashSet<String>,
+}
+
+impl Load {
+    /// Parse `path` into the result if it exists
+    fn merge_file(&mut self, path: &Path) -> Result<()> {
+        let seen = stamp(path);
+        self.loaded.files.push((path.to_path_buf(), seen));
+        if seen.is_none() {
+            log::debug!("no config at {}", path.display());
+            return Ok(());
+        }
+        let parsed = Config::parse_file(path)?;
+        self.loaded.config.merge(parsed, &mut self.known_groups);
+        Ok(())
+    }
+}
+
 impl Config {
     pub fn load_all_relevant_config_files() -> Result<Self> {
-        let home = env::var("HOME").map_err(|_| anyhow!("HOME environment variable not set"))?;
-        Self::load_all_relevant_config_files_from_path(
-            Path::new(&home).join(crate::internal::constants::OWL_DIR),
-        )
+        Self::load_all_relevant_config_files_from_path(crate::internal::files::owl_dir()?)
     }
 
     pub fn load_all_relevant_config_files_from_path<P: AsRef<Path>>(owl_root: P) -> Result<Self> {
-        let mut config = Config::new();
-        let owl_root = owl_root.as_ref();
+        let hostname = crate::internal::constants::get_host_name()?;
+        Self::load_for_host(owl_root, &hostname)
+    }
+
+    /// Load the config as the host named `hostname` would see it. Each file is read
+    /// and parsed once per process; later calls return the same result until a
+    /// file changes.
+    pub fn load_for_host<P: AsRef<Path>>(owl_root: P, hostname: &str) -> Result<Self> {
+        Self::with_loaded(owl_root.as_ref(), hostname, |loaded| loaded.config.clone())
+    }
+
+    /// The files the loader reads for this host, in load order: main.owl, the host
+    /// file, and the file of every group they pull in, whether or not it exists yet.
+    /// A config file anywhere else is never read.
+    pub fn loaded_files() -> Result<Vec<PathBuf>> {
+        let owl_root = crate::internal::files::owl_dir()?;
+        let hostname = crate::internal::constants::get_host_name()?;
+        Self::loaded_files_for_host(&owl_root, &hostname)
+    }
+
+    pub fn loaded_files_for_host(owl_root: &Path, hostname: &str) -> Result<Vec<PathBuf>> {
+        Self::with_loaded(owl_root, hostname, |loaded| {
+            loaded.files.iter().map(|(path, _)| path.clone()).collect()
+        })
+    }
+
+    /// Run `read` on the cached load for `owl_root` and `hostname`, loading first
+    /// when there is none or one of its files changed
+    fn with_loaded<T>(
+        owl_root: &Path,
+        hostname: &str,
+        read: impl FnOnce(&Loaded) -> T,
+    ) -> Result<T> {
+        let key = (owl_root.to_path_buf(), hostname.to_string());
+        let cache = LOADED.get_or_init(|| Mutex::new(HashMap::new()));
+        let mut cache = cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(loaded) = cache.get(&key).filter(|loaded| loaded.is_current()) {
+            log::debug!("config files unchanged since they were loaded; reusing them");
+            return Ok(read(loaded));
+        }
+
+        let loaded = Self::load_uncached(owl_root, hostname)?;
+        let result = read(&loaded);
+        cache.insert(key, loaded);
+        Ok(result)
+    }
+
+    fn load_uncached(owl_root: &Path, hostname: &str) -> Result<Loaded> {
+        let mut load = Load {
+            loaded: Loaded {
+                config: Config::new(),
+                files: Vec::new(),
+                loaded_at: SystemTime::now(),
+            },
+            known_groups: HashSet::new(),
+        };
 
         // Load in priority order: main (highest), hostname (medium), groups (lowest)
 
         // 1. Load main config (highest priority)
-        let main_config_path = owl_root.join(crate::internal::constants::MAIN_CONFIG_FILE);
-        Self::load_config_if_exists(&mut config, &main_config_path)?;
+        load.merge_file(&owl_root.join(crate::internal::constants::MAIN_CONFIG_FILE))?;
 
         // 2. Load host-specific config (medium priority)
-        let hostname = crate::internal::constants::get_host_name()?;
         let host_config_path = owl_root
             .join(crate::internal::constants::HOSTS_DIR)
             .join(format!(
@@ -32,54 +141,37 @@ impl Config {
                 hostname,
                 crate::internal::constants::OWL_EXT
             ));
-        Self::load_config_if_exists(&mut config, &host_config_path)?;
+        load.merge_file(&host_config_path)?;
 
         // 3. Load group configs (lowest priority)
         let groups_path = owl_root.join(crate::internal::constants::GROUPS_DIR);
-        if groups_path.exists() && groups_path.is_dir() {
-            let mut processed_groups = HashSet::new();
-            Self::load_groups_with_precedence(&groups_path, &mut config, &mut processed_groups)?;
-        }
+        Self::load_groups_with_precedence(&groups_path, &mut load)?;
 
-        Ok(config)
+        Ok(load.loaded)
     }
 
-    fn load_config_if_exists(config: &mut Config, path: &Path) -> Result<()> {
-        if path.exists() {
-            let loaded_config = Self::parse_file(path)?;
-            config.add_if_not_exists(loaded_config);
-        }
-        Ok(())
-    }
-
-    fn load_groups_with_precedence(
-        groups_path: &Path,
-        config: &mut Config,
-        processed_groups: &mut HashSet<String>,
-    ) -> Result<()> {
-        let mut groups_to_process: Vec<String> = config.groups.clone();
+    fn load_groups_with_precedence(groups_path: &Path, load: &mut Load) -> Result<()> {
+        let mut processed_groups = HashSet::new();
+        let mut groups_to_process: Vec<String> = load.loaded.config.groups.clone();
 
         while let Some(group_name) = groups_to_process.pop() {
-            if processed_groups.contains(&group_name) {
+            if !processed_groups.insert(group_name.clone()) {
                 continue;
             }
-            processed_groups.insert(group_name.clone());
 
             let group_file = groups_path.join(format!(
                 "{}{}",
                 group_name,
                 crate::internal::constants::OWL_EXT
             ));
-            if group_file.exists() {
-                let group_config = Self::parse_file(&group_file)?;
-                // Add any new groups found in this group file
-                for new_group in &group_config.groups {
-                    if !processed_groups.contains(new_group) {
-                        groups_to_process.push(new_group.clone());
-                    }
+            let known = load.loaded.config.groups.len();
+            log::debug!("loading group {}", group_name);
+            load.merge_file(&group_file)?;
+            // Queue the groups this file pulled in for the first time
+            for new_group in &load.loaded.config.groups[known..] {
+                if !processed_groups.contains(new_group) {
+                    groups_to_process.push(new_group.clone());
                 }
-                // Add packages from group config only if not already defined
-                config.add_if_not_exists(group_config);
             }
         }
 
@@ -87,15 +179,39 @@ impl Config {
     }
 
     // Adds packages/env vars from other config only if they don't already exist (respects precedence)
+    #[cfg(test)]
     pub(crate) fn add_if_not_exists(&mut self, other: Self) {
+        let mut known_groups = self.groups.iter().cloned().collect();
+        self.merge(other, &mut known_groups);
+    }
+
+    /// `add_if_not_exists` with the set of `self.groups` kept by the caller, so
+    /// merging many files stays linear
+    fn merge(&mut self, mut other: Self, known_groups: &mut HashSet<String>) {
         // Only add packages that don't already exist (higher priority configs win)
         for (name, package) in other.packages {
-            self.packages.entry(name).or_insert(package);
+            match self.packages.entry(name) {
+                std::collections::hash_map::Entry::Occupied(entry) => {
+                    log::debug!(
+                        "{} already defined by a higher-priority config; keeping it",
+                        entry.key()
+                    );
+                }
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    if let Some(provenance) = other.provenance.remove(entry.key()) {
+                        self.provenance.insert(entry.key().clone(), provenance);
+                    }
+                    entry.insert(package);
+                }
+            }
         }
 
         // Add groups (avoid duplicates)
         for group in other.groups {
-            if !self.groups.contains(&group) {
+            if known_groups.insert(group.clone()) {
+                if let Some(provenance) = other.group_provenance.remove(&group) {
+                    self.group_provenance.insert(group.clone(), provenance);
+                }
                 self.groups.push(group);
             }
         }
@@ -104,5 +220,139 @@ impl Config {
         for (key, value) in other.env_vars {
             self.env_vars.entry(key).or_insert(value);
         }
+
+        for name in other.protected {
+            if !self.protected.contains(&name) {
+                self.protected.push(name);
+            }
+        }
+
+        for name in other.aur_skip {
+            if !self.aur_skip.contains(&name) {
+                self.aur_skip.push(name);
+            }
+        }
+
+        // Like env vars, the first config to pin a package decides its version
+        for (name, version) in other.pins {
+            self.pins.entry(name).or_insert(version);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Move the modification time of the config files in `dir` and its `groups`
+    /// directory a minute back, out of the racy window
+    fn settle(dir: &Path) {
+        let earlier = SystemTime::now() - Duration::from_secs(60);
+        let files = std::fs::read_dir(dir)
+            .into_iter()
+            .chain(std::fs::read_dir(dir.join("groups")))
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file());
+        for file in files {
+            std::fs::File::options()
+                .write(true)
+                .open(file)
+                .and_then(|file| file.set_modified(earlier))
+                .unwrap();
+        }
+    }
+
+    /// How many files `load` parsed
+    fn parses(load: impl FnOnce()) -> usize {
+        crate::internal::logging::capture(load)
+            .iter()
+            .filter(|message| message.starts_with("parsing config"))
+            .count()
+    }
+
+    #[test]
+    fn test_load_is_reused_until_a_file_changes() {
+        let dir = tempdir().unwrap();
+        let groups = dir.path().join("groups");
+        std::fs::write(dir.path().join("main.owl"), "@packages\nhtop\n@group dev\n").unwrap();
+        settle(dir.path());
+
+        let load = || Config::load_for_host(dir.path(), "box").unwrap();
+        assert_eq!(parses(|| assert_eq!(load().packages.len(), 1)), 1);
+        assert_eq!(parses(|| assert_eq!(load().packages.len(), 1)), 0);
+
+        // A group file that did not exist at the first load is picked up once created
+        std::fs::create_dir(&groups).unwrap();
+        std::fs::write(groups.join("dev.owl"), "@packages\ngit\n").unwrap();
+        settle(dir.path());
+        assert_eq!(parses(|| assert!(load().packages.contains_key("git"))), 2);
+
+        std::fs::write(groups.join("dev.owl"), "@packages\ngit\nvim\n").unwrap();
+        let config = load();
+        assert!(config.packages.contains_key("vim"));
+        assert_eq!(config.provenance["vim"].line, 3);
+    }
+
+    #[test]
+    fn test_load_rereads_files_modified_close_to_it() {
+        let dir = tempdir().unwrap();
+        let main = dir.path().join("main.owl");
+        std::fs::write(&main, "@packages\nhtop\n").unwrap();
+        let load = || Config::load_for_host(dir.path(), "box").unwrap();
+        assert!(load().packages.contains_key("htop"));
+
+        // Same size, and on a coarse filesystem the same modification time
+        let modified = std::fs::metadata(&main).unwrap().modified().unwrap();
+        std::fs::write(&main, "@packages\nbtop\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&main)
+            .and_then(|file| file.set_modified(modified))
+            .unwrap();
+        assert!(load().packages.contains_key("btop"));
+    }
+
+    /// 200 files and 5,000 packages: the first load parses everything once, later
+    /// loads only look at file metadata
+    #[test]
+    fn test_load_of_a_large_config_parses_once() {
+        let dir = tempdir().unwrap();
+        let groups = dir.path().join("groups");
+        std::fs::create_dir(&groups).unwrap();
+        let mut main = String::new();
+        for group in 0..199 {
+            main.push_str(&format!("@group g{}\n", group));
+            let mut content = String::from("@env SHARED=1\n@packages\n");
+            for package in 0..25 {
+                content.push_str(&format!("pkg-{}-{}\n", group, package));
+            }
+            // Every group also pulls in the next one, as nested groups do
+            content.push_str(&format!("@group g{}\n", (group + 1) % 199));
+            std::fs::write(groups.join(format!("g{}.owl", group)), content).unwrap();
+        }
+        main.push_str("@packages\n");
+        for package in 0..25 {
+            main.push_str(&format!("main-{}\n", package));
+        }
+        std::fs::write(dir.path().join("main.owl"), main).unwrap();
+        settle(dir.path());
+
+        let mut cold = Config::new();
+        assert_eq!(
+            parses(|| cold = Config::load_for_host(dir.path(), "bench").unwrap()),
+            200
+        );
+        let mut warm = Config::new();
+        assert_eq!(
+            parses(|| warm = Config::load_for_host(dir.path(), "bench").unwrap()),
+            0
+        );
+
+        assert_eq!(cold.packages.len(), 5_000);
+        assert_eq!(cold.groups.len(), 199);
+        assert_eq!(warm.packages.len(), cold.packages.len());
     }
 }
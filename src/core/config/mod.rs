@@ -1,29 +1,116 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
+pub mod document;
 pub mod loader;
 pub mod parser;
 pub mod validator;
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct Package {
     pub config: Vec<String>,
     pub service: Option<String>,
     pub env_vars: HashMap<String, String>,
+    /// Marked `:exact`: only this package will do, not one that provides its name
+    pub exact: bool,
+    /// Commands run around its install and removal (`:pre_install`, `:post_remove`, ...)
+    pub hooks: crate::core::hooks::Hooks,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct Config {
     pub packages: HashMap<String, Package>,
     pub groups: Vec<String>,
     pub env_vars: HashMap<String, String>,
+    /// Packages from `@protected` sections, on top of [`DEFAULT_PROTECTED`]
+    pub protected: Vec<String>,
+    /// Packages from `@pin` sections, held back from updates: name to the version
+    /// to hold (`mesa=24.0.1`), or `None` to hold whatever is installed
+    pub pins: BTreeMap<String, Option<String>>,
+    /// AUR packages left out of AUR updates, from `@aur-skip` sections and `:noupdate`
+    pub aur_skip: Vec<String>,
+    /// Where each package was declared
+    #[serde(skip)]
+    pub provenance: HashMap<String, Provenance>,
+    /// Where each group was first pulled in with `@group`
+    #[serde(skip)]
+    pub group_provenance: HashMap<String, Provenance>,
+}
+
+/// The file and line that declared a package
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// `None` for config parsed from a string
+    pub path: Option<PathBuf>,
+    /// 1-based line of the `@package` line or the entry in `@packages`
+    pub line: usize,
+}
+
+/// A parse error pinned to a line of a config file
+#[derive(Debug, Clone)]
+pub struct ConfigDiagnostic {
+    pub path: Option<PathBuf>,
+    pub line: usize,
+    pub source_line: String,
+    pub message: String,
+}
+
+impl ConfigDiagnostic {
+    pub fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
 }
 
+impl std::fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(
+                f,
+                "{}: Line {}: {}",
+                path.display(),
+                self.line,
+                self.message
+            ),
+            None => write!(f, "Line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigDiagnostic {}
+
+/// Packages a removal never touches, whatever the config says
+pub const DEFAULT_PROTECTED: &[&str] = &[
+    "linux",
+    "linux-firmware",
+    "base",
+    "systemd",
+    "pacman",
+    "paru",
+];
+
 impl Config {
+    /// Whether removals must leave `package` installed: a built-in default or listed
+    /// under `@protected`
+    pub fn is_protected(&self, package: &str) -> bool {
+        DEFAULT_PROTECTED.contains(&package) || self.protected.iter().any(|name| name == package)
+    }
+
+    /// Whether AUR updates must leave `package` alone; it is still installed when missing
+    pub fn skips_aur_update(&self, package: &str) -> bool {
+        self.aur_skip.iter().any(|name| name == package)
+    }
+
     pub fn new() -> Self {
         Config {
             packages: HashMap::new(),
             groups: Vec::new(),
             env_vars: HashMap::new(),
+            protected: Vec::new(),
+            pins: BTreeMap::new(),
+            aur_skip: Vec::new(),
+            provenance: HashMap::new(),
+            group_provenance: HashMap::new(),
         }
     }
 }
@@ -44,6 +131,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_logs_decisions() {
+        let content =
+            "@package nvim\n:config nvim -> ~/.config/nvim\n@future thing\n@packages\nhtop\n";
+        let logs = crate::internal::logging::capture(|| {
+            Config::parse(content).unwrap();
+        });
+
+        for expected in [
+            "package nvim",
+            "nvim: dotfile nvim -> ~/.config/nvim",
+            "ignoring unknown directive '@future thing'",
+            "package htop (from @packages)",
+            "parsed 2 packages, 0 groups, 0 global env vars",
+        ] {
+            assert!(
+                logs.iter().any(|line| line == expected),
+                "missing log line '{}' in {:?}",
+                expected,
+                logs
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_records_provenance() {
+        let config =
+            Config::parse("# main\n@packages\nhtop\n\n@package nvim\n:config nvim\n").unwrap();
+        assert_eq!(
+            config.provenance["htop"],
+            Provenance {
+                path: None,
+                line: 3
+            }
+        );
+        assert_eq!(config.provenance["nvim"].line, 5);
+
+        let config = Config::parse(
+            "@package nvim
+
+@group dev
+",
+        )
+        .unwrap();
+        assert_eq!(config.group_provenance["dev"].line, 3);
+    }
+
     #[test]
     fn test_parse_packages_section() {
         let content = "@packages\npackage1\npackage2\npackage3";
@@ -56,6 +190,139 @@ mod tests {
         // keys serve as package names
     }
 
+    #[test]
+    fn test_parse_exact_qualifier() {
+        let content = "@packages\njdk-openjdk :exact\nhtop\n\n@package python\n:exact\n\n@package go :exact\n";
+        let config = Config::parse(content).unwrap();
+        assert!(config.packages["jdk-openjdk"].exact);
+        assert!(!config.packages["htop"].exact);
+        assert!(config.packages["python"].exact);
+        assert!(config.packages["go"].exact);
+        assert!(Config::parse("@packages\n:exact\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_hooks() {
+        let config = Config::parse(
+            "@package syncthing
+:pre_install mkdir -p ~/Sync
+:post_install systemctl --user enable syncthing
+:post_install systemctl --user start syncthing
+:pre_remove systemctl --user disable --now syncthing
+:post_remove rm -rf ~/.local/state/syncthing
+
+@packages
+htop
+",
+        )
+        .unwrap();
+        let hooks = &config.packages["syncthing"].hooks;
+        assert_eq!(hooks.pre_install, ["mkdir -p ~/Sync"]);
+        assert_eq!(
+            hooks.post_install,
+            [
+                "systemctl --user enable syncthing",
+                "systemctl --user start syncthing"
+            ]
+        );
+        assert_eq!(
+            hooks.pre_remove,
+            ["systemctl --user disable --now syncthing"]
+        );
+        assert_eq!(hooks.post_remove, ["rm -rf ~/.local/state/syncthing"]);
+        assert!(config.packages["htop"].hooks.is_empty());
+
+        assert!(Config::parse("@package syncthing\n:post_install\n").is_err());
+        assert!(Config::parse(":post_install true\n").is_err());
+        assert!(Config::parse("@packages\nhtop\n:pre_remove true\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_aur_section() {
+        let config = Config::parse("@aur\nparu-bin\n\n@packages\nhtop\n").unwrap();
+        assert!(config.packages.contains_key("paru-bin"));
+        assert!(config.packages.contains_key("htop"));
+    }
+
+    #[test]
+    fn test_parse_protected_section() {
+        let config = Config::parse(
+            "@protected
+grub
+linux-lts
+grub
+
+@packages
+htop
+",
+        )
+        .unwrap();
+        assert_eq!(config.protected, ["grub", "linux-lts"]);
+        assert_eq!(config.packages.len(), 1);
+        assert!(config.is_protected("grub"));
+        assert!(config.is_protected("linux"));
+        assert!(!config.is_protected("htop"));
+    }
+
+    #[test]
+    fn test_parse_aur_skip_section_and_noupdate() {
+        let config = Config::parse(
+            "@aur-skip
+neovim-git
+
+@aur
+neovim-git
+@package hyprland-git
+:noupdate
+@package paru-bin
+",
+        )
+        .unwrap();
+        assert_eq!(config.aur_skip, ["neovim-git", "hyprland-git"]);
+        assert_eq!(config.packages.len(), 3);
+        assert!(config.skips_aur_update("hyprland-git"));
+        assert!(!config.skips_aur_update("paru-bin"));
+        assert!(
+            Config::parse(
+                ":noupdate
+"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_pin_section() {
+        let config = Config::parse(
+            "@pin
+mesa=24.0.1
+linux = 6.9.1.arch1-1
+firefox
+
+@packages
+htop
+",
+        )
+        .unwrap();
+        assert_eq!(
+            config.pins,
+            BTreeMap::from([
+                ("firefox".to_string(), None),
+                ("linux".to_string(), Some("6.9.1.arch1-1".to_string())),
+                ("mesa".to_string(), Some("24.0.1".to_string())),
+            ])
+        );
+        assert_eq!(config.packages.len(), 1);
+        assert!(
+            Config::parse(
+                "@pin
+mesa=
+"
+            )
+            .is_err()
+        );
+    }
+
     #[test]
     fn test_parse_service_directive() {
         let content = "@package test-service\n:service test-service";
@@ -167,7 +434,10 @@ package2";
     fn test_package_directive_requires_name() {
         let content = "@package ";
         let err = Config::parse(content).unwrap_err();
-        assert!(err.to_string().contains("package directive requires a package name"));
+        assert!(
+            err.to_string()
+                .contains("package directive requires a package name")
+        );
     }
 
     #[test]
@@ -186,6 +456,8 @@ package2";
                 config: vec!["config1".to_string()],
                 service: None,
                 env_vars: std::collections::HashMap::new(),
+                exact: false,
+                hooks: Default::default(),
             },
         );
 
@@ -196,6 +468,8 @@ package2";
                 config: vec!["config2".to_string()],
                 service: Some("service2".to_string()),
                 env_vars: std::collections::HashMap::new(),
+                exact: false,
+                hooks: Default::default(),
             },
         );
 
@@ -217,6 +491,8 @@ package2";
                 config: Vec::new(),
                 service: None,
                 env_vars: std::collections::HashMap::new(),
+                exact: false,
+                hooks: Default::default(),
             },
         );
 
@@ -226,6 +502,8 @@ package2";
                 config: Vec::new(),
                 service: None,
                 env_vars: std::collections::HashMap::new(),
+                exact: false,
+                hooks: Default::default(),
             },
         );
 
@@ -2,19 +2,55 @@ use anyhow::{Context, Result, anyhow};
 use std::collections::HashMap;
 use std::path::Path;
 
-use super::{Config, Package};
+use super::{Config, ConfigDiagnostic, Package, Provenance};
+use crate::core::hooks::HookPoint;
+
+/// The list section that bare names on a line belong to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    None,
+    /// `@packages`, `@pkgs`, or `@aur`
+    Packages,
+    /// `@protected`: packages removals must never touch
+    Protected,
+    /// `@pin`: packages held back from updates, optionally at a version
+    Pin,
+    /// `@aur-skip`: AUR packages that AUR updates leave alone
+    AurSkip,
+}
+
+/// Split a trailing `:exact` qualifier off a package entry, e.g. `jdk-openjdk :exact`
+fn split_exact(entry: &str) -> (&str, bool) {
+    match entry.strip_suffix(":exact") {
+        Some(name) if name.ends_with(char::is_whitespace) => (name.trim_end(), true),
+        _ => (entry, false),
+    }
+}
 
 impl Config {
     pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        log::debug!("parsing config {}", path.as_ref().display());
         let content = std::fs::read_to_string(path.as_ref())
             .with_context(|| format!("Failed to read config file: {}", path.as_ref().display()))?;
-        Self::parse(&content)
+        let mut config =
+            Self::parse(&content).map_err(|err| match err.downcast::<ConfigDiagnostic>() {
+                Ok(diagnostic) => diagnostic.with_path(path.as_ref()).into(),
+                Err(err) => err,
+            })?;
+        for provenance in config
+            .provenance
+            .values_mut()
+            .chain(config.group_provenance.values_mut())
+        {
+            provenance.path = Some(path.as_ref().to_path_buf());
+        }
+        Ok(config)
     }
 
     pub fn parse(content: &str) -> Result<Self> {
         let mut config = Config::new();
         let mut current_package: Option<String> = None;
-        let mut in_packages_section = false;
+        let mut section = Section::None;
 
         for (idx, line) in content.lines().enumerate() {
             let line_number = idx + 1;
@@ -27,19 +63,31 @@ impl Config {
             Self::parse_line(
                 &mut config,
                 &mut current_package,
-                &mut in_packages_section,
+                &mut section,
                 trimmed,
                 line_number,
-            )?;
+            )
+            .map_err(|e| ConfigDiagnostic {
+                path: None,
+                line: line_number,
+                source_line: line.to_string(),
+                message: e.to_string(),
+            })?;
         }
 
+        log::debug!(
+            "parsed {} packages, {} groups, {} global env vars",
+            config.packages.len(),
+            config.groups.len(),
+            config.env_vars.len()
+        );
         Ok(config)
     }
 
     fn parse_line(
         config: &mut Config,
         current_package: &mut Option<String>,
-        in_packages_section: &mut bool,
+        section: &mut Section,
         line: &str,
         line_number: usize,
     ) -> Result<()> {
@@ -48,31 +96,48 @@ impl Config {
             || line == "@pkg"
             || line.starts_with("@pkg ")
         {
-            Self::parse_package_declaration(
-                config,
-                current_package,
-                in_packages_section,
-                line,
-                line_number,
-            )?;
-        } else if line == "@packages" || line == "@pkgs" {
-            Self::parse_packages_section(in_packages_section, current_package);
+            Self::parse_package_declaration(config, current_package, section, line, line_number)?;
+        } else if line == "@packages" || line == "@pkgs" || line == "@aur" {
+            Self::enter_section(section, Section::Packages, current_package);
+        } else if line == "@protected" {
+            Self::enter_section(section, Section::Protected, current_package);
+        } else if line == "@pin" {
+            Self::enter_section(section, Section::Pin, current_package);
+        } else if line == "@aur-skip" {
+            Self::enter_section(section, Section::AurSkip, current_package);
         } else if line == "@env" || line.starts_with("@env ") {
-            Self::parse_global_env_directive(config, line, line_number)?;
+            Self::parse_global_env_directive(config, line)?;
         } else if line == "@group" || line.starts_with("@group ") {
             Self::parse_group_declaration(config, current_package, line, line_number)?;
         } else if line == ":config" || line.starts_with(":config ") {
-            Self::parse_config_directive(config, current_package, line, ":config ", line_number)?;
+            Self::parse_config_directive(config, current_package, line, ":config ")?;
         } else if line == ":cfg" || line.starts_with(":cfg ") {
-            Self::parse_config_directive(config, current_package, line, ":cfg ", line_number)?;
+            Self::parse_config_directive(config, current_package, line, ":cfg ")?;
         } else if line == ":service" || line.starts_with(":service ") {
-            Self::parse_service_directive(config, current_package, line, line_number)?;
+            Self::parse_service_directive(config, current_package, line)?;
         } else if line == ":env" || line.starts_with(":env ") {
-            Self::parse_package_env_directive(config, current_package, line, line_number)?;
-        } else if !line.starts_with('@') && !line.starts_with(':') && *in_packages_section {
-            Self::parse_package_in_section(config, line);
+            Self::parse_package_env_directive(config, current_package, line)?;
+        } else if let Some(point) =
+            HookPoint::from_directive(line.split_whitespace().next().unwrap_or(line))
+        {
+            Self::parse_hook_directive(config, current_package, point, line)?;
+        } else if line == ":exact" {
+            Self::parse_exact_directive(config, current_package)?;
+        } else if line == ":noupdate" {
+            Self::parse_noupdate_directive(config, current_package)?;
+        } else if !line.starts_with('@') && !line.starts_with(':') && *section != Section::None {
+            match section {
+                Section::Packages => Self::parse_package_in_section(config, line, line_number),
+                Section::Protected => Self::parse_protected_in_section(config, line),
+                Section::Pin => Self::parse_pin_in_section(config, line)?,
+                Section::AurSkip => Self::skip_aur_update(config, line.trim()),
+                Section::None => {}
+            }
         } else if line.starts_with('@') || line.starts_with(':') {
             // Ignore unknown directives for forward compatibility.
+            log::debug!("ignoring unknown directive '{}'", line);
+        } else {
+            log::debug!("ignoring '{}' outside of a @packages section", line);
         }
 
         Ok(())
@@ -81,48 +146,60 @@ impl Config {
     fn parse_package_declaration(
         config: &mut Config,
         current_package: &mut Option<String>,
-        in_packages_section: &mut bool,
+        section: &mut Section,
         line: &str,
         line_number: usize,
     ) -> Result<()> {
-        *in_packages_section = false;
+        *section = Section::None;
         let name = if let Some(name) = line
             .strip_prefix("@package ")
             .or_else(|| line.strip_prefix("@package"))
         {
             name.trim()
-        } else if let Some(name) = line.strip_prefix("@pkg ").or_else(|| line.strip_prefix("@pkg"))
+        } else if let Some(name) = line
+            .strip_prefix("@pkg ")
+            .or_else(|| line.strip_prefix("@pkg"))
         {
             name.trim()
         } else {
             line.trim()
         };
 
+        let (name, exact) = split_exact(name);
         if name.is_empty() {
-            return Err(anyhow!(
-                "Line {}: package directive requires a package name",
-                line_number
-            ));
+            return Err(anyhow!("package directive requires a package name"));
         }
 
+        log::trace!("package {}", name);
         *current_package = Some(name.to_string());
+        config.provenance.insert(
+            name.to_string(),
+            Provenance {
+                path: None,
+                line: line_number,
+            },
+        );
         config.packages.insert(
             name.to_string(),
             Package {
                 config: Vec::new(),
                 service: None,
                 env_vars: HashMap::new(),
+                exact,
+                hooks: Default::default(),
             },
         );
 
         Ok(())
     }
 
-    fn parse_packages_section(
-        in_packages_section: &mut bool,
+    fn enter_section(
+        section: &mut Section,
+        entered: Section,
         current_package: &mut Option<String>,
     ) {
-        *in_packages_section = true;
+        log::trace!("entering {:?} section", entered);
+        *section = entered;
         *current_package = None;
     }
 
@@ -139,37 +216,82 @@ impl Config {
             .trim();
 
         if name.is_empty() {
-            return Err(anyhow!(
-                "Line {}: @group directive requires a group name",
-                line_number
-            ));
+            return Err(anyhow!("@group directive requires a group name"));
         }
 
+        log::trace!("group {}", name);
+        config
+            .group_provenance
+            .entry(name.to_string())
+            .or_insert(Provenance {
+                path: None,
+                line: line_number,
+            });
         config.groups.push(name.to_string());
         *current_package = None;
         Ok(())
     }
 
-    fn parse_package_in_section(config: &mut Config, line: &str) {
-        let package_name = line.trim();
+    fn parse_package_in_section(config: &mut Config, line: &str, line_number: usize) {
+        let (package_name, exact) = split_exact(line.trim());
         if !package_name.is_empty() && !package_name.starts_with('#') {
+            log::trace!("package {} (from @packages)", package_name);
+            config.provenance.insert(
+                package_name.to_string(),
+                Provenance {
+                    path: None,
+                    line: line_number,
+                },
+            );
             config.packages.insert(
                 package_name.to_string(),
                 Package {
                     config: Vec::new(),
                     service: None,
                     env_vars: HashMap::new(),
+                    exact,
+                    hooks: Default::default(),
                 },
             );
         }
     }
 
+    fn parse_protected_in_section(config: &mut Config, line: &str) {
+        let name = line.trim();
+        if !config.protected.iter().any(|protected| protected == name) {
+            log::trace!("protected {}", name);
+            config.protected.push(name.to_string());
+        }
+    }
+
+    fn skip_aur_update(config: &mut Config, name: &str) {
+        if !config.skips_aur_update(name) {
+            log::trace!("aur-skip {}", name);
+            config.aur_skip.push(name.to_string());
+        }
+    }
+
+    /// `name` holds the installed version, `name=version` a specific one
+    fn parse_pin_in_section(config: &mut Config, line: &str) -> Result<()> {
+        let (name, version) = match line.split_once('=') {
+            Some((name, version)) => (name.trim(), Some(version.trim())),
+            None => (line.trim(), None),
+        };
+        if name.is_empty() || version.is_some_and(str::is_empty) {
+            return Err(anyhow!("@pin entries are `name` or `name=version`"));
+        }
+        log::trace!("pin {} {}", name, version.unwrap_or("(installed)"));
+        config
+            .pins
+            .insert(name.to_string(), version.map(ToString::to_string));
+        Ok(())
+    }
+
     fn parse_config_directive(
         config: &mut Config,
         current_package: &Option<String>,
         line: &str,
         prefix: &str,
-        line_number: usize,
     ) -> Result<()> {
         let rest = line
             .strip_prefix(prefix)
@@ -178,27 +300,18 @@ impl Config {
             .trim();
 
         if rest.is_empty() {
-            return Err(anyhow!(
-                "Line {}: {} directive requires a value",
-                line_number,
-                prefix.trim()
-            ));
+            return Err(anyhow!("{} directive requires a value", prefix.trim()));
         }
 
         let Some(pkg_name) = current_package else {
             return Err(anyhow!(
-                "Line {}: {} directive found outside of a package context",
-                line_number,
+                "{} directive found outside of a package context",
                 prefix.trim()
             ));
         };
 
         let Some(package) = config.packages.get_mut(pkg_name) else {
-            return Err(anyhow!(
-                "Line {}: Package '{}' not found in config",
-                line_number,
-                pkg_name
-            ));
+            return Err(anyhow!("Package '{}' not found in config", pkg_name));
         };
 
         if let Some((source, sink)) = rest.split_once(" -> ") {
@@ -206,20 +319,16 @@ impl Config {
             let sink = sink.trim();
 
             if source.is_empty() {
-                return Err(anyhow!(
-                    "Line {}: Config source path cannot be empty",
-                    line_number
-                ));
+                return Err(anyhow!("Config source path cannot be empty"));
             }
             if sink.is_empty() {
-                return Err(anyhow!(
-                    "Line {}: Config destination path cannot be empty",
-                    line_number
-                ));
+                return Err(anyhow!("Config destination path cannot be empty"));
             }
 
+            log::trace!("{}: dotfile {} -> {}", pkg_name, source, sink);
             package.config.push(format!("{} -> {}", source, sink));
         } else {
+            log::trace!("{}: dotfile {}", pkg_name, rest);
             package.config.push(rest.to_string());
         }
 
@@ -230,7 +339,6 @@ impl Config {
         config: &mut Config,
         current_package: &Option<String>,
         line: &str,
-        line_number: usize,
     ) -> Result<()> {
         let service_part = line
             .strip_prefix(":service ")
@@ -243,28 +351,78 @@ impl Config {
             .trim();
 
         if service_name.is_empty() {
+            return Err(anyhow!(":service directive requires a service name"));
+        }
+
+        let Some(pkg_name) = current_package else {
             return Err(anyhow!(
-                "Line {}: :service directive requires a service name",
-                line_number
+                ":service directive found outside of a package context"
             ));
-        }
+        };
 
+        let Some(package) = config.packages.get_mut(pkg_name) else {
+            return Err(anyhow!("Package '{}' not found in config", pkg_name));
+        };
+
+        log::trace!("{}: service {}", pkg_name, service_name);
+        package.service = Some(service_name.to_string());
+        Ok(())
+    }
+
+    fn parse_exact_directive(config: &mut Config, current_package: &Option<String>) -> Result<()> {
         let Some(pkg_name) = current_package else {
             return Err(anyhow!(
-                "Line {}: :service directive found outside of a package context",
-                line_number
+                ":exact directive found outside of a package context"
             ));
         };
 
         let Some(package) = config.packages.get_mut(pkg_name) else {
+            return Err(anyhow!("Package '{}' not found in config", pkg_name));
+        };
+
+        log::trace!("{}: exact", pkg_name);
+        package.exact = true;
+        Ok(())
+    }
+
+    fn parse_noupdate_directive(
+        config: &mut Config,
+        current_package: &Option<String>,
+    ) -> Result<()> {
+        let Some(pkg_name) = current_package else {
             return Err(anyhow!(
-                "Line {}: Package '{}' not found in config",
-                line_number,
-                pkg_name
+                ":noupdate directive found outside of a package context"
             ));
         };
+        Self::skip_aur_update(config, pkg_name);
+        Ok(())
+    }
 
-        package.service = Some(service_name.to_string());
+    /// `:post_install <command>` and the like; each line adds one command
+    fn parse_hook_directive(
+        config: &mut Config,
+        current_package: &Option<String>,
+        point: HookPoint,
+        line: &str,
+    ) -> Result<()> {
+        let command = line[point.name().len() + 1..].trim();
+        if command.is_empty() {
+            return Err(anyhow!(":{} directive requires a command", point.name()));
+        }
+
+        let Some(pkg_name) = current_package else {
+            return Err(anyhow!(
+                ":{} directive found outside of a package context",
+                point.name()
+            ));
+        };
+
+        let Some(package) = config.packages.get_mut(pkg_name) else {
+            return Err(anyhow!("Package '{}' not found in config", pkg_name));
+        };
+
+        log::trace!("{}: {} hook {}", pkg_name, point.name(), command);
+        package.hooks.commands_mut(point).push(command.to_string());
         Ok(())
     }
 
@@ -272,7 +430,6 @@ impl Config {
         config: &mut Config,
         current_package: &Option<String>,
         line: &str,
-        line_number: usize,
     ) -> Result<()> {
         let env_part = line
             .strip_prefix(":env ")
@@ -281,8 +438,7 @@ impl Config {
 
         let Some((key, value)) = env_part.split_once('=') else {
             return Err(anyhow!(
-                "Line {}: :env directive must be in format 'KEY=value' (missing '=')",
-                line_number
+                ":env directive must be in format 'KEY=value' (missing '=')"
             ));
         };
 
@@ -290,36 +446,23 @@ impl Config {
         let value = value.trim();
 
         if key.is_empty() {
-            return Err(anyhow!(
-                "Line {}: Environment variable name cannot be empty",
-                line_number
-            ));
+            return Err(anyhow!("Environment variable name cannot be empty"));
         }
 
         let Some(pkg_name) = current_package else {
-            return Err(anyhow!(
-                "Line {}: :env directive found outside of a package context",
-                line_number
-            ));
+            return Err(anyhow!(":env directive found outside of a package context"));
         };
 
         let Some(package) = config.packages.get_mut(pkg_name) else {
-            return Err(anyhow!(
-                "Line {}: Package '{}' not found in config",
-                line_number,
-                pkg_name
-            ));
+            return Err(anyhow!("Package '{}' not found in config", pkg_name));
         };
 
+        log::trace!("{}: env {}", pkg_name, key);
         package.env_vars.insert(key.to_string(), value.to_string());
         Ok(())
     }
 
-    fn parse_global_env_directive(
-        config: &mut Config,
-        line: &str,
-        line_number: usize,
-    ) -> Result<()> {
+    fn parse_global_env_directive(config: &mut Config, line: &str) -> Result<()> {
         let env_part = line
             .strip_prefix("@env ")
             .or_else(|| line.strip_prefix("@env"))
@@ -327,8 +470,7 @@ impl Config {
 
         let Some((key, value)) = env_part.split_once('=') else {
             return Err(anyhow!(
-                "Line {}: @env directive must be in format 'KEY=value' (missing '=')",
-                line_number
+                "@env directive must be in format 'KEY=value' (missing '=')"
             ));
         };
 
@@ -336,12 +478,10 @@ impl Config {
         let value = value.trim();
 
         if key.is_empty() {
-            return Err(anyhow!(
-                "Line {}: Environment variable name cannot be empty",
-                line_number
-            ));
+            return Err(anyhow!("Environment variable name cannot be empty"));
         }
 
+        log::trace!("global env {}", key);
         config.env_vars.insert(key.to_string(), value.to_string());
         Ok(())
     }
@@ -16,14 +16,13 @@ pub fn run_configcheck(path: &str) -> Result<()> {
             );
             Ok(())
         }
-        Err(e) => Err(anyhow!("Failed to parse {}: {}", path, e)),
+        Err(e) => Err(e.context(format!("Failed to parse {}", path))),
     }
 }
 
 /// Validate and print the full config chain (main, hostname, groups)
 pub fn run_full_configcheck() -> Result<()> {
-    let home = std::env::var("HOME").map_err(|_| anyhow!("HOME environment variable not set"))?;
-    let owl_root = std::path::Path::new(&home).join(crate::internal::constants::OWL_DIR);
+    let owl_root = crate::internal::files::owl_dir()?;
     println!("Loading config from: {}", owl_root.display());
 
     // Check main config
@@ -111,7 +110,7 @@ pub fn run_full_configcheck() -> Result<()> {
 
             Ok(())
         }
-        Err(e) => Err(anyhow!("Failed to load full config: {}", e)),
+        Err(e) => Err(e.context("Failed to load full config")),
     }
 }
 
@@ -119,9 +118,7 @@ pub fn run_full_configcheck() -> Result<()> {
 pub fn run_confighost() -> Result<()> {
     let hostname =
         crate::internal::constants::get_host_name().unwrap_or_else(|_| "unknown".to_string());
-    let home = std::env::var("HOME").map_err(|_| anyhow!("HOME environment variable not set"))?;
-    let path = std::path::Path::new(&home)
-        .join(crate::internal::constants::OWL_DIR)
+    let path = crate::internal::files::owl_dir()?
         .join("hosts")
         .join(format!("{}.owl", hostname));
     println!(
@@ -0,0 +1,262 @@
+//! Manifest of the files owl deployed for dotfile mappings
+//!
+//! Every file an apply writes is recorded with its content hash, size, modification
+//! time, and mode, so `owl verify` can tell later edits and permission changes apart
+//! from what owl put there. The source's size and modification time are kept too,
+//! so planning can trust a pair whose metadata both still match without reading
+//! either file, the way git's index avoids rehashing the work tree.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const MANIFEST_FILE: &str = "deployed.json";
+/// Bumped whenever a record changes meaning; older manifests are dropped on load
+const FORMAT_VERSION: u32 = 2;
+
+/// What owl wrote to one destination file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeployedFile {
+    /// The mapping's destination as configured, e.g. `~/.config/nvim`
+    pub mapping: String,
+    /// The dotfile the content was copied from
+    pub source: PathBuf,
+    pub sha256: String,
+    pub size: u64,
+    /// Modification time after the write, in nanoseconds since the epoch
+    pub modified: u64,
+    /// Permission bits after the write
+    pub mode: u32,
+    /// Size of the source at the time of the copy
+    pub source_size: u64,
+    /// Modification time of the source at the time of the copy, in nanoseconds
+    pub source_modified: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestFile {
+    version: u32,
+    files: Manifest,
+}
+
+/// Deployed files keyed by their absolute destination path
+pub type Manifest = BTreeMap<PathBuf, DeployedFile>;
+
+/// How a deployed file no longer matches its record
+#[derive(Debug, Clone, PartialEq)]
+pub enum Drift {
+    Missing,
+    Modified,
+    Permissions { expected: u32, actual: u32 },
+}
+
+fn manifest_path() -> Result<PathBuf> {
+    crate::core::state::PackageState::sibling_path(MANIFEST_FILE)
+}
+
+/// Load the manifest; empty when nothing has been recorded yet or it was written
+/// in an older format
+pub fn load() -> Result<Manifest> {
+    let path = manifest_path()?;
+    if !path.exists() {
+        return Ok(Manifest::new());
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    if value.get("version").and_then(serde_json::Value::as_u64) != Some(FORMAT_VERSION.into()) {
+        log::debug!(
+            "deployed: {} has an older format; starting over",
+            path.display()
+        );
+        return Ok(Manifest::new());
+    }
+    let file: ManifestFile = serde_json::from_value(value)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(file.files)
+}
+
+pub fn save(manifest: &Manifest) -> Result<()> {
+    let path = manifest_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let file = ManifestFile {
+        version: FORMAT_VERSION,
+        files: manifest.clone(),
+    };
+    let content = serde_json::to_string_pretty(&file).context("Failed to serialize manifest")?;
+    fs::write(&path, content + "\n").with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn modified_nanos(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|age| age.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// Describe the file just written to `destination` from `source`
+pub fn record(mapping: &str, source: &Path, destination: &Path) -> Result<DeployedFile> {
+    let metadata = fs::metadata(destination)
+        .with_context(|| format!("Failed to stat {}", destination.display()))?;
+    let source_metadata =
+        fs::metadata(source).with_context(|| format!("Failed to stat {}", source.display()))?;
+    Ok(DeployedFile {
+        mapping: mapping.to_string(),
+        source: source.to_path_buf(),
+        sha256: crate::core::dotfiles::sha256_file(destination)?,
+        size: metadata.len(),
+        modified: modified_nanos(&metadata),
+        mode: metadata.permissions().mode() & 0o7777,
+        source_size: source_metadata.len(),
+        source_modified: modified_nanos(&source_metadata),
+    })
+}
+
+/// True when `destination` was deployed from `source` and neither has been touched
+/// since, judged by size and modification time alone
+pub fn untouched_since_deploy(manifest: &Manifest, source: &Path, destination: &Path) -> bool {
+    let Some(file) = manifest.get(destination) else {
+        return false;
+    };
+    let same = |path: &Path, size: u64, modified: u64| {
+        fs::metadata(path)
+            .is_ok_and(|metadata| metadata.len() == size && modified_nanos(&metadata) == modified)
+    };
+    file.source == source
+        && same(destination, file.size, file.modified)
+        && same(source, file.source_size, file.source_modified)
+}
+
+/// Whether anything at or below `destination` is recorded
+pub fn has_records(manifest: &Manifest, destination: &Path) -> bool {
+    manifest
+        .range(destination.to_path_buf()..)
+        .next()
+        .is_some_and(|(path, _)| path.starts_with(destination))
+}
+
+/// Drop the records of everything at or below `destination`
+pub fn forget(manifest: &mut Manifest, destination: &Path) {
+    manifest.retain(|path, _| !path.starts_with(destination));
+}
+
+/// Compare a destination with its record. An unchanged size and modification time
+/// count as unchanged content, so only touched files are hashed.
+pub fn check(destination: &Path, file: &DeployedFile) -> Result<Option<Drift>> {
+    let metadata = match fs::metadata(destination) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => return Ok(Some(Drift::Missing)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Some(Drift::Missing)),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to stat {}", destination.display()));
+        }
+    };
+    let untouched = metadata.len() == file.size && modified_nanos(&metadata) == file.modified;
+    if !untouched
+        && (metadata.len() != file.size
+            || crate::core::dotfiles::sha256_file(destination)? != file.sha256)
+    {
+        return Ok(Some(Drift::Modified));
+    }
+    let mode = metadata.permissions().mode() & 0o7777;
+    if mode != file.mode {
+        return Ok(Some(Drift::Permissions {
+            expected: file.mode,
+            actual: mode,
+        }));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_reports_each_kind_of_drift() {
+        let dir = tempdir().unwrap();
+        let (source, destination) = (dir.path().join("src"), dir.path().join("dst"));
+        fs::write(&source, "color=1\n").unwrap();
+        fs::write(&destination, "color=1\n").unwrap();
+        let file = record("~/dst", &source, &destination).unwrap();
+        assert_eq!(check(&destination, &file).unwrap(), None);
+
+        fs::write(&destination, "color=2\n").unwrap();
+        assert_eq!(check(&destination, &file).unwrap(), Some(Drift::Modified));
+
+        fs::write(&destination, "color=1\n").unwrap();
+        fs::set_permissions(&destination, fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(
+            check(&destination, &file).unwrap(),
+            Some(Drift::Permissions {
+                expected: file.mode,
+                actual: 0o600
+            })
+        );
+
+        fs::remove_file(&destination).unwrap();
+        assert_eq!(check(&destination, &file).unwrap(), Some(Drift::Missing));
+    }
+
+    #[test]
+    fn test_check_skips_hashing_when_size_and_mtime_match() {
+        let dir = tempdir().unwrap();
+        let destination = dir.path().join("dst");
+        fs::write(&destination, "aaaa").unwrap();
+        let file = record("~/dst", &destination, &destination).unwrap();
+
+        // Same size with the original mtime put back: taken on trust, not hashed
+        let modified = fs::metadata(&destination).unwrap().modified().unwrap();
+        fs::write(&destination, "bbbb").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&destination)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(check(&destination, &file).unwrap(), None);
+    }
+
+    #[test]
+    fn test_forget_drops_a_whole_mapping() {
+        let file = |mapping: &str| DeployedFile {
+            mapping: mapping.to_string(),
+            source: PathBuf::new(),
+            sha256: String::new(),
+            size: 0,
+            modified: 0,
+            mode: 0o644,
+            source_size: 0,
+            source_modified: 0,
+        };
+        let mut manifest = Manifest::from([
+            (
+                PathBuf::from("/h/.config/nvim/init.lua"),
+                file("~/.config/nvim"),
+            ),
+            (
+                PathBuf::from("/h/.config/nvim/lua/a.lua"),
+                file("~/.config/nvim"),
+            ),
+            (PathBuf::from("/h/.config/nvimrc"), file("~/.config/nvimrc")),
+        ]);
+        assert!(has_records(&manifest, Path::new("/h/.config/nvim")));
+        assert!(!has_records(&manifest, Path::new("/h/.config/nv")));
+        forget(&mut manifest, Path::new("/h/.config/nvim"));
+        assert_eq!(
+            manifest.keys().collect::<Vec<_>>(),
+            vec![Path::new("/h/.config/nvimrc")]
+        );
+    }
+}
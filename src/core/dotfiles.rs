@@ -5,11 +5,14 @@
 
 use anyhow::{Result, anyhow};
 use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::core::deployed::Manifest;
+
 /// Represents a dotfile mapping from source to destination
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DotfileMapping {
     pub source: String,
     pub destination: String,
@@ -31,10 +34,7 @@ pub struct DotfileAction {
 }
 
 fn owl_dotfiles_dir() -> Result<PathBuf> {
-    let home = std::env::var("HOME").map_err(|_| anyhow!("HOME environment variable not set"))?;
-    Ok(Path::new(&home)
-        .join(crate::internal::constants::OWL_DIR)
-        .join(crate::internal::constants::DOTFILES_DIR))
+    Ok(crate::internal::files::owl_dir()?.join(crate::internal::constants::DOTFILES_DIR))
 }
 
 fn expand_tilde(path: &str) -> String {
@@ -70,7 +70,35 @@ fn collect_files_recursively(root: &Path, rels: &mut Vec<PathBuf>, base: &Path)
     Ok(())
 }
 
-fn dirs_in_sync(src: &Path, dst: &Path) -> Result<bool> {
+/// Whether `dst` holds what `src` does. Pairs the manifest vouches for are not read.
+fn files_match(src: &Path, dst: &Path, manifest: &Manifest) -> Result<bool> {
+    files_match_with(src, dst, manifest, &mut sha256_file)
+}
+
+/// `files_match` with `hash` reading the content of the files it has to compare
+fn files_match_with(
+    src: &Path,
+    dst: &Path,
+    manifest: &Manifest,
+    hash: &mut impl FnMut(&Path) -> Result<String>,
+) -> Result<bool> {
+    if crate::core::deployed::untouched_since_deploy(manifest, src, dst) {
+        return Ok(true);
+    }
+    Ok(hash(src)? == hash(dst)?)
+}
+
+fn dirs_in_sync(src: &Path, dst: &Path, manifest: &Manifest) -> Result<bool> {
+    dirs_in_sync_with(src, dst, manifest, &mut sha256_file)
+}
+
+/// `dirs_in_sync` with `hash` reading the content of the files it has to compare
+fn dirs_in_sync_with(
+    src: &Path,
+    dst: &Path,
+    manifest: &Manifest,
+    hash: &mut impl FnMut(&Path) -> Result<String>,
+) -> Result<bool> {
     if !dst.exists() || !dst.is_dir() {
         return Ok(false);
     }
@@ -95,12 +123,13 @@ fn dirs_in_sync(src: &Path, dst: &Path) -> Result<bool> {
         if !d.exists() || !d.is_file() {
             return Ok(false);
         }
-        if sha256_file(&s)? != sha256_file(&d)? {
+        if !files_match_with(&s, &d, manifest, hash)? {
             return Ok(false);
         }
     }
 
     // Check if destination has no extra files (should be covered by count check, but being explicit)
+    let src_files: HashSet<PathBuf> = src_files.into_iter().collect();
     for rel in &dst_files {
         if !src_files.contains(rel) {
             return Ok(false);
@@ -110,7 +139,8 @@ fn dirs_in_sync(src: &Path, dst: &Path) -> Result<bool> {
     Ok(true)
 }
 
-fn sha256_file(path: &Path) -> Result<String> {
+/// Hex SHA-256 of a file's contents
+pub fn sha256_file(path: &Path) -> Result<String> {
     let data = fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
     let mut hasher = Sha256::new();
     hasher.update(&data);
@@ -125,7 +155,19 @@ fn ensure_parent_dir(dest: &Path) -> Result<()> {
     Ok(())
 }
 
-fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
+/// Total size in bytes of the regular files under `root`
+pub fn tree_size(root: &Path) -> Result<u64> {
+    let mut rels = Vec::new();
+    collect_files_recursively(root, &mut rels, root)?;
+    Ok(rels
+        .iter()
+        .filter_map(|rel| fs::metadata(root.join(rel)).ok())
+        .map(|metadata| metadata.len())
+        .sum())
+}
+
+/// Copy the regular files under `src` to `dst`, creating directories as needed
+pub fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
     if src == dst {
         return Ok(());
     }
@@ -157,44 +199,74 @@ fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
 
 /// Build dotfile mappings from config
 pub fn get_dotfile_mappings(config: &crate::core::config::Config) -> Vec<DotfileMapping> {
-    let mut mappings = Vec::new();
-    for pkg in config.packages.values() {
-        for cfg in &pkg.config {
-            // formats: "a -> b" or "b" (same source name)
-            if let Some((source, dest)) = cfg.split_once(" -> ") {
-                mappings.push(DotfileMapping {
-                    source: source.trim().to_string(),
-                    destination: dest.trim().to_string(),
-                });
-            } else {
-                mappings.push(DotfileMapping {
-                    source: cfg.clone(),
-                    destination: cfg.clone(),
-                });
-            }
+    config
+        .packages
+        .values()
+        .flat_map(|pkg| pkg.config.iter().map(|cfg| parse_mapping(cfg)))
+        .collect()
+}
+
+/// Parse a `:config` value: "a -> b", or "b" when source and destination share a name
+pub fn parse_mapping(cfg: &str) -> DotfileMapping {
+    if let Some((source, dest)) = cfg.split_once(" -> ") {
+        DotfileMapping {
+            source: source.trim().to_string(),
+            destination: dest.trim().to_string(),
+        }
+    } else {
+        DotfileMapping {
+            source: cfg.to_string(),
+            destination: cfg.to_string(),
         }
     }
-    mappings
+}
+
+/// Delete a mapping's deployed destination. Returns false if nothing was deployed.
+pub fn remove_deployed(mapping: &DotfileMapping) -> Result<bool> {
+    let (_, dst) = resolve_mapping(mapping)?;
+    let mut manifest = crate::core::deployed::load()?;
+    if manifest.keys().any(|path| path.starts_with(&dst)) {
+        crate::core::deployed::forget(&mut manifest, &dst);
+        crate::core::deployed::save(&manifest)?;
+    }
+    if dst.is_dir() {
+        fs::remove_dir_all(&dst)
+            .map_err(|e| anyhow!("Failed to remove {}: {}", dst.display(), e))?;
+    } else if dst.exists() {
+        fs::remove_file(&dst).map_err(|e| anyhow!("Failed to remove {}: {}", dst.display(), e))?;
+    } else {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// The deploy manifest, or an empty one when it cannot be read; comparisons then
+/// fall back to hashing
+pub fn load_manifest() -> Manifest {
+    crate::core::deployed::load().unwrap_or_else(|e| {
+        log::warn!("dotfiles: starting a new deploy manifest: {:#}", e);
+        Manifest::new()
+    })
 }
 
 /// Return true if any mapping requires action
 pub fn has_actionable_dotfiles(mappings: &[DotfileMapping]) -> Result<bool> {
+    let manifest = load_manifest();
     for m in mappings {
-        let src = owl_dotfiles_dir()?.join(&m.source);
-        let dst = expand_tilde(&m.destination);
-        let dst_path = Path::new(&dst);
+        let (src, dst) = resolve_mapping(m)?;
+        let dst_path = dst.as_path();
         if !src.exists() {
             continue;
         }
         if src.is_dir() {
-            if !dirs_in_sync(&src, dst_path)? {
+            if !dirs_in_sync(&src, dst_path, &manifest)? {
                 return Ok(true);
             }
         } else {
             if !dst_path.exists() {
                 return Ok(true);
             }
-            if sha256_file(&src)? != sha256_file(dst_path)? {
+            if !files_match(&src, dst_path, &manifest)? {
                 return Ok(true);
             }
         }
@@ -202,29 +274,88 @@ pub fn has_actionable_dotfiles(mappings: &[DotfileMapping]) -> Result<bool> {
     Ok(false)
 }
 
-/// Analyze and apply dotfiles
+/// Resolve a mapping to its absolute source and destination paths
+pub fn resolve_mapping(mapping: &DotfileMapping) -> Result<(PathBuf, PathBuf)> {
+    Ok((
+        owl_dotfiles_dir()?.join(&mapping.source),
+        PathBuf::from(expand_tilde(&mapping.destination)),
+    ))
+}
+
+/// Files currently deployed at a mapping's destination; empty when nothing is deployed
+pub fn deployed_files(mapping: &DotfileMapping) -> Result<Vec<PathBuf>> {
+    let (_, dst) = resolve_mapping(mapping)?;
+    if dst.is_dir() {
+        let mut rels = Vec::new();
+        collect_files_recursively(&dst, &mut rels, &dst)?;
+        rels.sort();
+        Ok(rels.into_iter().map(|rel| dst.join(rel)).collect())
+    } else if dst.is_file() {
+        Ok(vec![dst])
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Files that an apply of `mapping` would write or delete, as (source, destination)
+/// pairs. For directory mappings either side may be missing.
+pub fn differing_files(
+    mapping: &DotfileMapping,
+    manifest: &Manifest,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let (src, dst) = resolve_mapping(mapping)?;
+    if !src.is_dir() {
+        let differs = !dst.exists() || (src.exists() && !files_match(&src, &dst, manifest)?);
+        return Ok(if differs {
+            vec![(src, dst)]
+        } else {
+            Vec::new()
+        });
+    }
+
+    let mut rels = Vec::new();
+    collect_files_recursively(&src, &mut rels, &src)?;
+    if dst.is_dir() {
+        collect_files_recursively(&dst, &mut rels, &dst)?;
+    }
+    rels.sort();
+    rels.dedup();
+
+    let mut differing = Vec::new();
+    for rel in rels {
+        let (s, d) = (src.join(&rel), dst.join(&rel));
+        if !s.exists() || !d.exists() || !files_match(&s, &d, manifest)? {
+            differing.push((s, d));
+        }
+    }
+    Ok(differing)
+}
+
+/// Analyze and apply dotfiles. Mappings that are up to date are left alone.
 pub fn apply_dotfiles(mappings: &[DotfileMapping], dry_run: bool) -> Result<Vec<DotfileAction>> {
     let mut actions = Vec::new();
+    let mut manifest = load_manifest();
+    let mut recorded = false;
     for m in mappings {
-        let src = owl_dotfiles_dir()?.join(&m.source);
-        let dst = PathBuf::from(expand_tilde(&m.destination));
+        let (src, dst) = resolve_mapping(m)?;
         let status = if src.is_dir() {
             if !dst.exists() {
                 DotfileStatus::Create
-            } else if dirs_in_sync(&src, &dst)? {
+            } else if dirs_in_sync(&src, &dst, &manifest)? {
                 DotfileStatus::UpToDate
             } else {
                 DotfileStatus::Update
             }
         } else if !dst.exists() {
             DotfileStatus::Create
-        } else if sha256_file(&src)? == sha256_file(&dst)? {
+        } else if files_match(&src, &dst, &manifest)? {
             DotfileStatus::UpToDate
         } else {
             DotfileStatus::Update
         };
 
-        if !dry_run {
+        let write = status != DotfileStatus::UpToDate;
+        if !dry_run && write {
             if src.is_dir() {
                 // Remove destination directory if it exists, then copy entire source
                 if dst.exists() {
@@ -246,16 +377,64 @@ pub fn apply_dotfiles(mappings: &[DotfileMapping], dry_run: bool) -> Result<Vec<
                     .map_err(|e| anyhow!("Failed to write {}: {}", dst.display(), e))?;
             }
         }
+        if !dry_run && (write || !crate::core::deployed::has_records(&manifest, &dst)) {
+            record_deployed(&mut manifest, m, &src, &dst)?;
+            recorded = true;
+        }
 
         actions.push(DotfileAction {
             mapping: m.clone(),
             status,
         });
     }
+    if recorded {
+        crate::core::deployed::save(&manifest)?;
+    }
     Ok(actions)
 }
 
-pub fn print_actions(actions: &[DotfileAction], dry_run: bool) {
+/// Record in-sync mappings that the deploy manifest does not know yet, e.g. ones
+/// deployed before owl kept a manifest
+pub fn record_unrecorded(mappings: &[DotfileMapping]) -> Result<()> {
+    let mut manifest = crate::core::deployed::load()?;
+    let mut changed = false;
+    for m in mappings {
+        let (src, dst) = resolve_mapping(m)?;
+        if !src.exists() || crate::core::deployed::has_records(&manifest, &dst) {
+            continue;
+        }
+        if differing_files(m, &manifest)?.is_empty() {
+            record_deployed(&mut manifest, m, &src, &dst)?;
+            changed = true;
+        }
+    }
+    if changed {
+        crate::core::deployed::save(&manifest)?;
+    }
+    Ok(())
+}
+
+/// Replace a mapping's entries in the deploy manifest with the files now at `dst`
+fn record_deployed(
+    manifest: &mut crate::core::deployed::Manifest,
+    mapping: &DotfileMapping,
+    src: &Path,
+    dst: &Path,
+) -> Result<()> {
+    crate::core::deployed::forget(manifest, dst);
+    for file in deployed_files(mapping)? {
+        let source = match file.strip_prefix(dst) {
+            Ok(rel) if src.is_dir() => src.join(rel),
+            _ => src.to_path_buf(),
+        };
+        let deployed = crate::core::deployed::record(&mapping.destination, &source, &file)?;
+        manifest.insert(file, deployed);
+    }
+    Ok(())
+}
+
+/// Write one line per dotfile created or updated, and the totals when not a dry run
+pub fn print_actions(actions: &[DotfileAction], dry_run: bool, out: &mut impl std::io::Write) {
     let mut created = 0usize;
     let mut updated = 0usize;
     let mut up_to_date = 0usize;
@@ -263,21 +442,25 @@ pub fn print_actions(actions: &[DotfileAction], dry_run: bool) {
         match a.status {
             DotfileStatus::Create => {
                 created += 1;
-                println!(
+                writeln!(
+                    out,
                     "  {} create {} -> {}",
                     crate::internal::color::green("➔"),
                     a.mapping.source,
                     a.mapping.destination
-                );
+                )
+                .ok();
             }
             DotfileStatus::Update => {
                 updated += 1;
-                println!(
+                writeln!(
+                    out,
                     "  {} update {} -> {}",
                     crate::internal::color::green("➔"),
                     a.mapping.source,
                     a.mapping.destination
-                );
+                )
+                .ok();
             }
             DotfileStatus::UpToDate => {
                 up_to_date += 1;
@@ -285,12 +468,75 @@ pub fn print_actions(actions: &[DotfileAction], dry_run: bool) {
         }
     }
     if !dry_run {
-        println!(
+        writeln!(
+            out,
             "  {} Up to date: {} dotfiles ({} created, {} updated)",
             crate::internal::color::green("➔"),
             up_to_date,
             created,
             updated
-        );
+        )
+        .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Whether `dirs_in_sync` finds the trees in sync, and how many files it hashed
+    fn counted_sync(src: &Path, dst: &Path, manifest: &Manifest) -> (bool, usize) {
+        let mut hashed = 0;
+        let in_sync = dirs_in_sync_with(src, dst, manifest, &mut |path| {
+            hashed += 1;
+            sha256_file(path)
+        })
+        .unwrap();
+        (in_sync, hashed)
+    }
+
+    #[test]
+    fn test_tree_size_counts_nested_files() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("themes")).unwrap();
+        fs::write(dir.path().join("kitty.conf"), "font_size 11\n").unwrap();
+        fs::write(dir.path().join("themes/dark.conf"), "x").unwrap();
+        assert_eq!(tree_size(dir.path()).unwrap(), 14);
+    }
+
+    /// A 10,000 file tree: the first comparison hashes both sides of every file,
+    /// once the manifest has the pairs a repeat reads no content at all
+    #[test]
+    fn test_dirs_in_sync_trusts_the_manifest_for_untouched_files() {
+        let dir = tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        for d in 0..100 {
+            fs::create_dir_all(src.join(d.to_string())).unwrap();
+            for f in 0..100 {
+                fs::write(
+                    src.join(format!("{}/{}.conf", d, f)),
+                    format!("{} {}\n", d, f),
+                )
+                .unwrap();
+            }
+        }
+        copy_dir_all(&src, &dst).unwrap();
+
+        let mut manifest = Manifest::new();
+        assert_eq!(counted_sync(&src, &dst, &manifest), (true, 20_000));
+
+        let mut rels = Vec::new();
+        collect_files_recursively(&dst, &mut rels, &dst).unwrap();
+        for rel in rels {
+            let deployed =
+                crate::core::deployed::record("~/dst", &src.join(&rel), &dst.join(&rel)).unwrap();
+            manifest.insert(dst.join(rel), deployed);
+        }
+        assert_eq!(counted_sync(&src, &dst, &manifest), (true, 0));
+
+        // Changed metadata falls back to hashing just that pair
+        fs::write(src.join("7/7.conf"), "edited\n").unwrap();
+        assert_eq!(counted_sync(&src, &dst, &manifest), (false, 2));
     }
 }
@@ -1,19 +1,22 @@
 use anyhow::{Result, anyhow};
 use std::collections::HashMap;
 use std::fs;
+use std::path::PathBuf;
 
 use crate::internal::files::owl_dir;
 
 /// Get bash environment file path
-fn env_file_bash() -> Result<std::path::PathBuf> {
+fn env_file_bash() -> Result<PathBuf> {
     Ok(owl_dir()?.join(crate::internal::constants::ENV_BASH_FILE))
 }
 
 /// Get fish environment file path
-fn env_file_fish() -> Result<std::path::PathBuf> {
+fn env_file_fish() -> Result<PathBuf> {
     Ok(owl_dir()?.join(crate::internal::constants::ENV_FISH_FILE))
 }
 
+/// Every configured variable, sorted by name. Package `@env` entries override the
+/// global ones; between packages, the one whose name sorts last wins.
 pub fn collect_all_env_vars(config: &crate::core::config::Config) -> Vec<(String, String)> {
     let mut vars: HashMap<String, String> = HashMap::new();
     // Global first
@@ -21,7 +24,10 @@ pub fn collect_all_env_vars(config: &crate::core::config::Config) -> Vec<(String
         vars.insert(k.clone(), v.clone());
     }
     // Package-level, override globals
-    for pkg in config.packages.values() {
+    let mut packages: Vec<(&String, &crate::core::config::Package)> =
+        config.packages.iter().collect();
+    packages.sort_by(|a, b| a.0.cmp(b.0));
+    for (_, pkg) in packages {
         for (k, v) in &pkg.env_vars {
             vars.insert(k.clone(), v.clone());
         }
@@ -31,6 +37,100 @@ pub fn collect_all_env_vars(config: &crate::core::config::Config) -> Vec<(String
     sorted_environment_vars
 }
 
+/// The bash and fish env files an apply would write, with their contents.
+/// Empty when no env vars are configured, since apply then leaves the files alone.
+pub fn render_env_files(config: &crate::core::config::Config) -> Result<Vec<(PathBuf, String)>> {
+    let vars = collect_all_env_vars(config);
+    if vars.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut fish = String::new();
+    for (k, v) in &vars {
+        fish.push_str(&format!("set -x {} \"{}\"\n", k, v));
+    }
+    Ok(vec![
+        (env_file_bash()?, render_bash(&vars)),
+        (env_file_fish()?, fish),
+    ])
+}
+
+/// The `export` lines of the bash env file
+pub fn render_bash(vars: &[(String, String)]) -> String {
+    vars.iter()
+        .map(|(k, v)| format!("export {}=\"{}\"\n", k, v))
+        .collect()
+}
+
+/// The values a shell ends up with after sourcing the bash env file: each value is
+/// read as a double-quoted string, so `$NAME` and `${NAME}` expand to variables
+/// exported by earlier lines or else found through `lookup`, unset names expand to
+/// nothing, and a backslash escapes `$`, `"`, `\` and `` ` ``. Command substitution
+/// is left as written.
+pub fn resolve_env_vars(
+    vars: &[(String, String)],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Vec<(String, String)> {
+    let mut resolved: Vec<(String, String)> = Vec::new();
+    for (k, v) in vars {
+        let value = expand(v, |name| {
+            resolved
+                .iter()
+                .rev()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+                .or_else(|| lookup(name))
+        });
+        resolved.push((k.clone(), value));
+    }
+    resolved
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn expand(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.peek() {
+                Some(&next @ ('$' | '"' | '\\' | '`')) => {
+                    out.push(next);
+                    chars.next();
+                }
+                _ => out.push('\\'),
+            },
+            '$' if chars.peek() == Some(&'{') => {
+                let rest: String = chars.clone().skip(1).collect();
+                match rest.find('}') {
+                    Some(end) if end > 0 && rest[..end].chars().all(is_name_char) => {
+                        out.push_str(&lookup(&rest[..end]).unwrap_or_default());
+                        for _ in 0..end + 2 {
+                            chars.next();
+                        }
+                    }
+                    _ => out.push('$'),
+                }
+            }
+            '$' if chars
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_') =>
+            {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek().filter(|c| is_name_char(**c)) {
+                    name.push(c);
+                    chars.next();
+                }
+                out.push_str(&lookup(&name).unwrap_or_default());
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 pub fn apply_environment_variables(
     config: &crate::core::config::Config,
     dry_run: bool,
@@ -52,23 +152,10 @@ pub fn apply_environment_variables(
         return Ok(());
     }
 
-    // Write bash
-    let bash_path = env_file_bash()?;
-    let mut bash = String::new();
-    for (k, v) in &vars {
-        bash.push_str(&format!("export {}=\"{}\"\n", k, v));
-    }
-    fs::write(&bash_path, bash)
-        .map_err(|e| anyhow!("Failed to write {}: {}", bash_path.display(), e))?;
-
-    // Write fish
-    let fish_path = env_file_fish()?;
-    let mut fish = String::new();
-    for (k, v) in &vars {
-        fish.push_str(&format!("set -x {} \"{}\"\n", k, v));
+    for (path, content) in render_env_files(config)? {
+        fs::write(&path, content)
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
     }
-    fs::write(&fish_path, fish)
-        .map_err(|e| anyhow!("Failed to write {}: {}", fish_path.display(), e))?;
 
     println!(
         "  {} Environment exported (bash, fish)",
@@ -76,3 +163,38 @@ pub fn apply_environment_variables(
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_expands_like_the_sourced_env_file() {
+        let lookup = |name: &str| (name == "HOME").then(|| "/home/me".to_string());
+        let resolved = resolve_env_vars(
+            &vars(&[
+                ("A_BIN", "$HOME/bin"),
+                ("B_PATH", "${A_BIN}:$UNSET:x"),
+                ("C_PRICE", "\\$5 and \\\"quoted\\\" \\n"),
+                ("D_LITERAL", "costs $ 5"),
+            ]),
+            lookup,
+        );
+        assert_eq!(
+            resolved,
+            vars(&[
+                ("A_BIN", "/home/me/bin"),
+                ("B_PATH", "/home/me/bin::x"),
+                ("C_PRICE", "$5 and \"quoted\" \\n"),
+                ("D_LITERAL", "costs $ 5"),
+            ])
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Applying the config on other machines over SSH
+//!
+//! Each host gets the config first: `git pull --ff-only` when its owl directory is a
+//! git repository, otherwise an rsync of the local owl directory that leaves the
+//! host's own files alone: its `.state`, run lock, and generated env files. Then owl runs there with `--porcelain`, and the events come
+//! back over the ssh connection as they happen. A dry run changes nothing on the host:
+//! the pull is skipped and the rsync only lists what it would change.
+
+use anyhow::{Context, Result, anyhow};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::cli::porcelain::{Event, Summary};
+use crate::core::settings::FleetHost;
+use crate::internal::logging::LogCommand;
+
+/// Options every ssh connection uses: never prompt, and give up on dead hosts
+const SSH_OPTIONS: [&str; 4] = ["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"];
+
+/// Entries of the owl directory that belong to the machine they are on, which the
+/// rsync neither copies nor deletes
+const HOST_LOCAL: [&str; 5] = [
+    crate::internal::constants::STATE_DIR,
+    ".git",
+    crate::core::lock::LOCK_FILE,
+    crate::internal::constants::ENV_BASH_FILE,
+    crate::internal::constants::ENV_FISH_FILE,
+];
+
+/// The hosts named in `names` plus those carrying any of `tags`; all hosts when
+/// neither is given. Sorted by name.
+pub fn select<'a>(
+    hosts: &'a BTreeMap<String, FleetHost>,
+    names: &[String],
+    tags: &[String],
+) -> Result<Vec<(&'a str, &'a FleetHost)>> {
+    if hosts.is_empty() {
+        return Err(anyhow!(
+            "no fleet hosts are defined; add [fleet.hosts.<name>] tables to settings.toml"
+        ));
+    }
+    if let Some(unknown) = names.iter().find(|name| !hosts.contains_key(*name)) {
+        return Err(anyhow!(
+            "unknown fleet host '{}'; defined hosts: {}",
+            unknown,
+            hosts.keys().cloned().collect::<Vec<_>>().join(", ")
+        ));
+    }
+    let selected: Vec<(&str, &FleetHost)> = hosts
+        .iter()
+        .filter(|(name, host)| {
+            (names.is_empty() && tags.is_empty())
+                || names.contains(name)
+                || host.tags.iter().any(|tag| tags.contains(tag))
+        })
+        .map(|(name, host)| (name.as_str(), host))
+        .collect();
+    if selected.is_empty() {
+        return Err(anyhow!("no fleet host has the tag {}", tags.join(" or ")));
+    }
+    Ok(selected)
+}
+
+/// Quote an argument for the remote shell, which ssh hands the joined command to
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@".contains(c))
+    {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// `user@address`, or just the address
+fn destination(host: &FleetHost) -> String {
+    match &host.user {
+        Some(user) => format!("{}@{}", user, host.address),
+        None => host.address.clone(),
+    }
+}
+
+fn ssh(host: &FleetHost, remote: &[&str]) -> Command {
+    let mut command = Command::new("ssh");
+    command
+        .args(SSH_OPTIONS)
+        .arg(destination(host))
+        .arg("--")
+        .arg(
+            remote
+                .iter()
+                .map(|arg| shell_quote(arg))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+    command
+}
+
+/// The owl invocation run on the host
+fn remote_apply(host: &FleetHost, dry_run: bool) -> Vec<&str> {
+    let mut args = vec!["owl", "--non-interactive", "--porcelain"];
+    if dry_run {
+        args.push("--dry-run");
+    }
+    args.extend(["--config-dir", host.config_dir.as_str(), "apply"]);
+    args
+}
+
+/// How the config reached the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Synced {
+    /// `git pull` in the host's repository
+    Pulled,
+    /// rsync of the local owl directory
+    Copied,
+}
+
+/// Bring the host's owl directory up to date with `local`. A dry run skips the pull
+/// and runs rsync with `--dry-run`, so the host keeps the config it has.
+pub fn sync_config(host: &FleetHost, local: &Path, dry_run: bool) -> Result<Synced> {
+    let dir = host.config_dir.as_str();
+    let git_dir = format!("{}/.git", dir.trim_end_matches('/'));
+    let is_repo = ssh(host, &["test", "-d", &git_dir])
+        .stdin(Stdio::null())
+        .logged()
+        .status()
+        .context("Failed to run ssh")?;
+    // ssh itself exits with 255 when it cannot connect
+    if is_repo.code() == Some(255) {
+        return Err(anyhow!("could not connect to {}", destination(host)));
+    }
+    if is_repo.success() {
+        if dry_run {
+            return Ok(Synced::Pulled);
+        }
+        let output = ssh(host, &["git", "-C", dir, "pull", "--ff-only", "--quiet"])
+            .stdin(Stdio::null())
+            .logged()
+            .output()
+            .context("Failed to run ssh")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git pull failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        return Ok(Synced::Pulled);
+    }
+    let output = rsync(host, local, dry_run)
+        .stdin(Stdio::null())
+        .logged()
+        .output()
+        .context("Failed to run rsync")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "rsync failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(Synced::Copied)
+}
+
+/// The rsync that mirrors `local` into the host's owl directory, or only lists what
+/// it would change on a dry run
+fn rsync(host: &FleetHost, local: &Path, dry_run: bool) -> Command {
+    let mut command = Command::new("rsync");
+    command.args(["-a", "--delete"]);
+    command.args(HOST_LOCAL.iter().map(|name| format!("--exclude=/{}", name)));
+    if dry_run {
+        command.arg("--dry-run");
+    }
+    command
+        .arg("-e")
+        .arg(format!("ssh {}", SSH_OPTIONS.join(" ")))
+        .arg(format!("{}/", local.display()))
+        .arg(format!("{}:{}/", destination(host), host.config_dir));
+    command
+}
+
+/// The event on one line of a porcelain stream; other lines are skipped
+pub fn parse_event(line: &str) -> Option<Event> {
+    serde_json::from_str(line).ok()
+}
+
+/// Run apply on the host, passing each event to `on_event` as it arrives. Returns the
+/// final summary, or the error the run stopped on.
+pub fn apply(host: &FleetHost, dry_run: bool, on_event: impl FnMut(&Event)) -> Result<Summary> {
+    let summaries = follow(ssh(host, &remote_apply(host, dry_run)), "ssh", on_event)?;
+    Ok(summaries
+        .into_iter()
+        .last()
+        .expect("follow returns a summary"))
+}
+
+/// Run `command`, which prints porcelain events on stdout, passing each to `on_event`
+/// as it arrives. Returns the summary of every apply it ran, or the error it stopped
+/// on when it failed or never finished one.
+pub fn follow(
+    mut command: Command,
+    program: &str,
+    mut on_event: impl FnMut(&Event),
+) -> Result<Vec<Summary>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .logged()
+        .spawn()
+        .with_context(|| format!("Failed to run {}", program))?;
+    // Human output arrives on stderr; it is kept for the error message only
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let errors = std::thread::spawn(move || {
+        let mut text = String::new();
+        stderr.read_to_string(&mut text).ok();
+        text
+    });
+
+    let mut summaries = Vec::new();
+    let mut last_warning = None;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    for line in BufReader::new(stdout).lines() {
+        let Some(event) = parse_event(&line?) else {
+            continue;
+        };
+        on_event(&event);
+        match event {
+            Event::Warning { message } => last_warning = Some(message),
+            Event::Done { summary } => summaries.push(summary),
+            _ => {}
+        }
+    }
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to wait for {}", program))?;
+    let errors = errors.join().unwrap_or_default();
+    if status.success() && !summaries.is_empty() {
+        return Ok(summaries);
+    }
+    let last_line = errors.lines().map(str::trim).rfind(|line| !line.is_empty());
+    Err(anyhow!(
+        "{}",
+        last_warning
+            .or_else(|| last_line.map(String::from))
+            .unwrap_or_else(|| format!("owl exited with {}", status))
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(tags: &[&str]) -> FleetHost {
+        FleetHost {
+            address: "box.lan".to_string(),
+            user: None,
+            config_dir: ".owl".to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_rsync_leaves_host_files_alone() {
+        let command = rsync(&host(&[]), Path::new("/home/me/.owl"), false);
+        let args: Vec<String> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        for excluded in ["/.state", "/.git", "/.lock", "/env.sh", "/env.fish"] {
+            assert!(
+                args.contains(&format!("--exclude={}", excluded)),
+                "{} in {:?}",
+                excluded,
+                args
+            );
+        }
+    }
+
+    fn names(selected: Vec<(&str, &FleetHost)>) -> Vec<String> {
+        selected
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_select_by_name_and_tag() {
+        let hosts = BTreeMap::from([
+            ("htpc".to_string(), host(&["media"])),
+            ("laptop".to_string(), host(&[])),
+            ("nas".to_string(), host(&["media", "storage"])),
+        ]);
+        assert_eq!(
+            names(select(&hosts, &[], &[]).unwrap()),
+            ["htpc", "laptop", "nas"]
+        );
+        assert_eq!(
+            names(select(&hosts, &["laptop".to_string()], &["storage".to_string()]).unwrap()),
+            ["laptop", "nas"]
+        );
+        assert!(select(&hosts, &["desktop".to_string()], &[]).is_err());
+        assert!(select(&hosts, &[], &["gpu".to_string()]).is_err());
+        assert!(select(&BTreeMap::new(), &[], &[]).is_err());
+    }
+
+    #[test]
+    fn test_remote_command_quotes_and_passes_dry_run() {
+        let mut nas = host(&[]);
+        nas.user = Some("root".to_string());
+        nas.config_dir = "my owl".to_string();
+        assert_eq!(destination(&nas), "root@box.lan");
+        let command = ssh(&nas, &remote_apply(&nas, true));
+        let args: Vec<String> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            args.last().unwrap(),
+            "owl --non-interactive --porcelain --dry-run --config-dir 'my owl' apply"
+        );
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_rsync_only_lists_changes_on_a_dry_run() {
+        let args = |dry_run| -> Vec<String> {
+            rsync(&host(&[]), Path::new("/home/me/.owl"), dry_run)
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect()
+        };
+        assert!(!args(false).contains(&"--dry-run".to_string()));
+        let dry = args(true);
+        assert!(dry.contains(&"--dry-run".to_string()));
+        assert_eq!(dry[dry.len() - 2..], ["/home/me/.owl/", "box.lan:.owl/"]);
+    }
+
+    #[test]
+    fn test_parse_event_reads_the_porcelain_stream() {
+        assert_eq!(
+            parse_event(r#"{"event":"warning","message":"pacman failed"}"#),
+            Some(Event::Warning {
+                message: "pacman failed".to_string()
+            })
+        );
+        // An older owl without the package counts still parses
+        let done = parse_event(r#"{"event":"done","summary":{"ok":true,"to_install":2}}"#);
+        let Some(Event::Done { summary }) = done else {
+            panic!("expected done, got {:?}", done);
+        };
+        assert!(summary.ok);
+        assert_eq!(summary.to_install, 2);
+        assert_eq!(summary.installed, 0);
+        assert_eq!(parse_event("warning: not json"), None);
+        assert_eq!(parse_event(r#"{"event":"from_the_future"}"#), None);
+    }
+}
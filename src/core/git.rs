@@ -0,0 +1,297 @@
+//! Thin wrappers around the git CLI for syncing the owl directory
+
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use crate::internal::logging::LogCommand;
+
+/// Git failures that `error::present` turns into guidance
+#[derive(Debug)]
+pub enum GitError {
+    NotARepo(PathBuf),
+    Dirty { repo: PathBuf, files: Vec<String> },
+    Conflicted { repo: PathBuf, files: Vec<String> },
+    NotFastForward { repo: PathBuf, stderr: String },
+    PushRejected { repo: PathBuf, stderr: String },
+    CommandFailed { action: String, stderr: String },
+}
+
+impl std::fmt::Display for GitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let shown =
+            |path: &Path| crate::internal::files::friendly_path(&path.display().to_string());
+        match self {
+            GitError::NotARepo(dir) => write!(f, "{} is not a git repository", shown(dir)),
+            GitError::Dirty { repo, files } => write!(
+                f,
+                "{} has uncommitted changes: {}",
+                shown(repo),
+                files.join(", ")
+            ),
+            GitError::Conflicted { repo, files } => write!(
+                f,
+                "{} has unresolved merge conflicts: {}",
+                shown(repo),
+                files.join(", ")
+            ),
+            GitError::NotFastForward { repo, .. } => write!(
+                f,
+                "{} cannot be fast-forwarded; local and remote history have diverged",
+                shown(repo)
+            ),
+            GitError::PushRejected { repo, .. } => {
+                write!(f, "the remote rejected the push from {}", shown(repo))
+            }
+            GitError::CommandFailed { action, stderr } => {
+                write!(f, "{} failed: {}", action, stderr.trim())
+            }
+        }
+    }
+}
+
+impl std::error::Error for GitError {}
+
+/// One line of `git status --porcelain`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusEntry {
+    /// The two-letter XY code, e.g. ` M`, `??`, `UU`
+    pub code: String,
+    pub path: String,
+}
+
+impl StatusEntry {
+    /// Both sides modified the path and the merge has not been resolved
+    pub fn is_conflict(&self) -> bool {
+        matches!(
+            self.code.as_str(),
+            "DD" | "AU" | "UD" | "UA" | "DU" | "AA" | "UU"
+        )
+    }
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .logged()
+        .output()
+        .map_err(|e| anyhow!("Failed to run git: {}", e))
+}
+
+fn git_checked(repo: &Path, args: &[&str], action: &str) -> Result<String> {
+    let output = git(repo, args)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            action: action.to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `git clone` of `url` into `dest`, which must not exist yet
+pub fn clone(url: &str, dest: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .arg("clone")
+        .arg("--quiet")
+        .arg(url)
+        .arg(dest)
+        .logged()
+        .output()
+        .map_err(|e| anyhow!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            action: "git clone".to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// The URL of the `origin` remote, if there is one
+pub fn origin_url(repo: &Path) -> Result<Option<String>> {
+    let output = git(repo, &["remote", "get-url", "origin"])?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+/// The top of the work tree containing `dir`
+pub fn toplevel(dir: &Path) -> Result<PathBuf> {
+    let output = git(dir, &["rev-parse", "--show-toplevel"])?;
+    if !output.status.success() {
+        return Err(GitError::NotARepo(dir.to_path_buf()).into());
+    }
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+/// Changed, staged, and untracked paths in the work tree
+pub fn status(repo: &Path) -> Result<Vec<StatusEntry>> {
+    let out = git_checked(
+        repo,
+        &["status", "--porcelain", "--untracked-files=all"],
+        "git status",
+    )?;
+    Ok(parse_status(&out))
+}
+
+fn parse_status(output: &str) -> Vec<StatusEntry> {
+    output
+        .lines()
+        .filter(|line| line.len() > 3)
+        .map(|line| {
+            let path = &line[3..];
+            // Renames are reported as `old -> new`
+            let path = path.rsplit_once(" -> ").map_or(path, |(_, new)| new);
+            StatusEntry {
+                code: line[..2].to_string(),
+                path: path.trim_matches('"').to_string(),
+            }
+        })
+        .collect()
+}
+
+/// True while a merge is waiting to be concluded
+pub fn merge_in_progress(repo: &Path) -> Result<bool> {
+    let path = git_checked(
+        repo,
+        &["rev-parse", "--git-path", "MERGE_HEAD"],
+        "git rev-parse",
+    )?;
+    Ok(repo.join(path.trim()).exists())
+}
+
+/// The current commit, or `None` before the first commit
+pub fn head(repo: &Path) -> Result<Option<String>> {
+    let output = git(repo, &["rev-parse", "--verify", "--quiet", "HEAD"])?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+/// `git pull --ff-only`
+pub fn pull_ff_only(repo: &Path) -> Result<()> {
+    let output = git(repo, &["pull", "--ff-only", "--quiet"])?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    let lower = stderr.to_lowercase();
+    if lower.contains("not possible to fast-forward") || lower.contains("diverg") {
+        Err(GitError::NotFastForward {
+            repo: repo.to_path_buf(),
+            stderr,
+        }
+        .into())
+    } else {
+        Err(GitError::CommandFailed {
+            action: "git pull".to_string(),
+            stderr,
+        }
+        .into())
+    }
+}
+
+/// Paths changed between two commits, as (status letter, path)
+pub fn changed_files(repo: &Path, from: &str, to: &str) -> Result<Vec<(char, String)>> {
+    let range = format!("{}..{}", from, to);
+    let out = git_checked(repo, &["diff", "--name-status", &range], "git diff")?;
+    Ok(out
+        .lines()
+        .filter_map(|line| {
+            let (status, path) = line.split_once('\t')?;
+            let path = path.rsplit('\t').next().unwrap_or(path);
+            Some((status.chars().next()?, path.to_string()))
+        })
+        .collect())
+}
+
+/// The content of `path` at `rev`, or `None` when it did not exist there
+pub fn show_file(repo: &Path, rev: &str, path: &str) -> Result<Option<String>> {
+    let spec = format!("{}:{}", rev, path);
+    let output = git(repo, &["show", &spec])?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+/// Stage everything and commit it with `message`
+pub fn commit_all(repo: &Path, message: &str) -> Result<()> {
+    git_checked(repo, &["add", "-A"], "git add")?;
+    git_checked(repo, &["commit", "--quiet", "-m", message], "git commit")?;
+    Ok(())
+}
+
+/// Paths with changes in the index, relative to the top of the work tree
+pub fn staged_files(repo: &Path) -> Result<Vec<String>> {
+    let out = git_checked(repo, &["diff", "--cached", "--name-only"], "git diff")?;
+    Ok(out.lines().map(str::to_string).collect())
+}
+
+/// Stage `paths` and commit just them. False when they had nothing to commit.
+pub fn commit_paths(repo: &Path, paths: &[String], message: &str) -> Result<bool> {
+    let mut add = vec!["add", "--"];
+    add.extend(paths.iter().map(String::as_str));
+    git_checked(repo, &add, "git add")?;
+
+    let mut diff = vec!["diff", "--cached", "--quiet", "--"];
+    diff.extend(paths.iter().map(String::as_str));
+    if git(repo, &diff)?.status.success() {
+        return Ok(false);
+    }
+    let mut commit = vec!["commit", "--quiet", "-m", message, "--"];
+    commit.extend(paths.iter().map(String::as_str));
+    git_checked(repo, &commit, "git commit")?;
+    Ok(true)
+}
+
+/// `git push` to the configured upstream
+pub fn push(repo: &Path) -> Result<()> {
+    let output = git(repo, &["push", "--quiet"])?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if stderr.contains("[rejected]") || stderr.contains("non-fast-forward") {
+        Err(GitError::PushRejected {
+            repo: repo.to_path_buf(),
+            stderr,
+        }
+        .into())
+    } else {
+        Err(GitError::CommandFailed {
+            action: "git push".to_string(),
+            stderr,
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let output = " M main.owl\n?? groups/wm.owl\nUU hosts/laptop.owl\nR  old.owl -> new.owl\n";
+        let entries = parse_status(output);
+        assert_eq!(
+            entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(),
+            vec!["main.owl", "groups/wm.owl", "hosts/laptop.owl", "new.owl"]
+        );
+        assert_eq!(
+            entries.iter().filter(|e| e.is_conflict()).count(),
+            1,
+            "only UU is a conflict"
+        );
+    }
+}
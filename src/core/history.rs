@@ -0,0 +1,391 @@
+//! What owl did over time, kept next to the package state
+//!
+//! `last-apply.json` remembers the config as of the last successful apply. Every apply
+//! and adopt run also leaves a record under `history/`; records carry a schema version
+//! so older files stay readable as the format grows.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const RECORD_FILE: &str = "last-apply.json";
+const RUNS_DIR: &str = "history";
+
+/// Schema version written into new run records
+pub const RUN_RECORD_VERSION: u32 = 1;
+
+/// When the last apply finished and what the config files looked like then
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ApplyRecord {
+    /// Seconds since the Unix epoch
+    pub finished_at: u64,
+    /// SHA-256 of each config file, keyed by path
+    pub config_hashes: BTreeMap<String, String>,
+}
+
+impl ApplyRecord {
+    /// Snapshot the given config files as of now
+    pub fn capture(config_files: &[String]) -> Result<Self> {
+        let mut config_hashes = BTreeMap::new();
+        for file in config_files {
+            let hash = crate::core::dotfiles::sha256_file(Path::new(file))?;
+            config_hashes.insert(file.clone(), hash);
+        }
+        Ok(Self {
+            finished_at: now(),
+            config_hashes,
+        })
+    }
+
+    /// Config files that were added, removed, or edited since this record was taken
+    pub fn changed_files(&self, current: &BTreeMap<String, String>) -> Vec<String> {
+        let mut changed: Vec<String> = current
+            .iter()
+            .filter(|(path, hash)| self.config_hashes.get(*path) != Some(*hash))
+            .map(|(path, _)| path.clone())
+            .chain(
+                self.config_hashes
+                    .keys()
+                    .filter(|path| !current.contains_key(*path))
+                    .cloned(),
+            )
+            .collect();
+        changed.sort();
+        changed
+    }
+
+    /// Seconds between the record and now
+    pub fn age(&self) -> u64 {
+        now().saturating_sub(self.finished_at)
+    }
+}
+
+/// The command a run record describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunKind {
+    Apply,
+    Adopt,
+}
+
+impl RunKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            RunKind::Apply => "apply",
+            RunKind::Adopt => "adopt",
+        }
+    }
+}
+
+/// How a run ended. A run that exits early never writes its final record, so it
+/// stays `Incomplete`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Incomplete,
+}
+
+/// A package that a run installed (`from` is `None`), removed (`to` is `None`), or
+/// changed the version of
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageChange {
+    pub name: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// One apply or adopt run. Fields added after version 1 must have `#[serde(default)]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub version: u32,
+    pub id: String,
+    pub kind: RunKind,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    pub outcome: Outcome,
+    #[serde(default)]
+    pub packages: Vec<PackageChange>,
+    /// Destinations of the dotfiles the run wrote
+    #[serde(default)]
+    pub dotfiles: Vec<String>,
+    /// Packages an adopt run put under management
+    #[serde(default)]
+    pub adopted: Vec<String>,
+    /// Packages an adopt run marked as untracked
+    #[serde(default)]
+    pub ignored: Vec<String>,
+    /// Started by the `owl schedule` timer rather than by hand
+    #[serde(default)]
+    pub scheduled: bool,
+    /// Warnings and errors the run carried on past
+    #[serde(default)]
+    pub issues: Vec<crate::error::Issue>,
+}
+
+impl RunRecord {
+    /// A record for a run starting now, marked incomplete until `finish`
+    pub fn start(kind: RunKind) -> Self {
+        let started_at = now();
+        Self {
+            version: RUN_RECORD_VERSION,
+            id: format!("{}-{}", started_at, kind.label()),
+            kind,
+            started_at,
+            finished_at: None,
+            outcome: Outcome::Incomplete,
+            packages: Vec::new(),
+            dotfiles: Vec::new(),
+            adopted: Vec::new(),
+            ignored: Vec::new(),
+            scheduled: std::env::var_os(crate::core::schedule::SCHEDULED_ENV).is_some(),
+            issues: Vec::new(),
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.finished_at = Some(now());
+        self.outcome = Outcome::Success;
+    }
+
+    /// Whether the run installed, removed, upgraded, adopted, or ignored `package`
+    pub fn touches(&self, package: &str) -> bool {
+        self.packages.iter().any(|change| change.name == package)
+            || self.adopted.iter().any(|name| name == package)
+            || self.ignored.iter().any(|name| name == package)
+    }
+}
+
+/// Compare installed versions before and after a run
+pub fn package_changes(
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> Vec<PackageChange> {
+    let mut changes: Vec<PackageChange> = after
+        .iter()
+        .filter(|(name, version)| before.get(*name) != Some(*version))
+        .map(|(name, version)| PackageChange {
+            name: name.clone(),
+            from: before.get(name).cloned(),
+            to: Some(version.clone()),
+        })
+        .chain(
+            before
+                .iter()
+                .filter(|(name, _)| !after.contains_key(*name))
+                .map(|(name, version)| PackageChange {
+                    name: name.clone(),
+                    from: Some(version.clone()),
+                    to: None,
+                }),
+        )
+        .collect();
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes
+}
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// The record lives in the state directory, or beside the `--state-file` override
+fn record_path() -> Result<PathBuf> {
+    crate::core::state::PackageState::sibling_path(RECORD_FILE)
+}
+
+/// Run records live in the state directory, or beside the `--state-file` override
+pub fn runs_dir() -> Result<PathBuf> {
+    crate::core::state::PackageState::sibling_path(RUNS_DIR)
+}
+
+/// Write `record`, replacing an earlier version of the same run
+pub fn save_run(record: &RunRecord) -> Result<()> {
+    let dir = runs_dir()?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{}.json", record.id));
+    let content = serde_json::to_string_pretty(record).context("Failed to serialize run")?;
+    std::fs::write(&path, content + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Read a stored record of any known schema version. `None` for records written by a
+/// newer owl, which this one cannot interpret.
+fn parse_run(content: &str) -> Result<Option<RunRecord>> {
+    let value: serde_json::Value = serde_json::from_str(content)?;
+    let version = value
+        .get("version")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(1);
+    if version > u64::from(RUN_RECORD_VERSION) {
+        return Ok(None);
+    }
+    // Version 1 is the current layout; migrations for later versions go here
+    Ok(Some(serde_json::from_value(value)?))
+}
+
+/// Stored runs, newest first; unreadable records are skipped
+pub fn load_runs() -> Result<Vec<RunRecord>> {
+    let dir = runs_dir()?;
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut runs = Vec::new();
+    for entry in
+        std::fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| parse_run(&content));
+        match parsed {
+            Ok(Some(run)) => runs.push(run),
+            Ok(None) => log::debug!("history: {} is from a newer owl", path.display()),
+            Err(err) => log::debug!("history: skipping {}: {:#}", path.display(), err),
+        }
+    }
+    runs.sort_by(|a, b| {
+        b.started_at
+            .cmp(&a.started_at)
+            .then_with(|| b.id.cmp(&a.id))
+    });
+    Ok(runs)
+}
+
+/// The stored file of run `id`, as written
+pub fn run_file(id: &str) -> Result<Option<String>> {
+    if id.contains('/') || id.starts_with('.') {
+        return Ok(None);
+    }
+    let path = runs_dir()?.join(format!("{}.json", id));
+    if !path.exists() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(&path)
+        .map(Some)
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Delete all but the newest `keep` run records
+pub fn prune_runs(keep: usize) -> Result<()> {
+    let dir = runs_dir()?;
+    for run in load_runs()?.into_iter().skip(keep) {
+        let path = dir.join(format!("{}.json", run.id));
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Load the last apply record, if an apply has ever finished
+pub fn load_last() -> Result<Option<ApplyRecord>> {
+    let path = record_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let record = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(Some(record))
+}
+
+/// Store `record` as the last apply
+pub fn save_last(record: &ApplyRecord) -> Result<()> {
+    let path = record_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let content = serde_json::to_string_pretty(record).context("Failed to serialize record")?;
+    std::fs::write(&path, content + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    log::debug!("recorded apply in {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_changes() {
+        let versions = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(name, version)| (name.to_string(), version.to_string()))
+                .collect()
+        };
+        let before = versions(&[("htop", "3.3.0-1"), ("vim", "9.1-1"), ("git", "2.45-1")]);
+        let after = versions(&[("htop", "3.3.0-2"), ("git", "2.45-1"), ("tmux", "3.4-1")]);
+        let change = |name: &str, from: Option<&str>, to: Option<&str>| PackageChange {
+            name: name.to_string(),
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+        };
+        assert_eq!(
+            package_changes(&before, &after),
+            vec![
+                change("htop", Some("3.3.0-1"), Some("3.3.0-2")),
+                change("tmux", None, Some("3.4-1")),
+                change("vim", Some("9.1-1"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_run_reads_minimal_records_and_skips_newer_versions() {
+        let run = parse_run(
+            r#"{"version": 1, "id": "1-apply", "kind": "apply", "started_at": 1, "outcome": "incomplete"}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(run.kind, RunKind::Apply);
+        assert_eq!(run.outcome, Outcome::Incomplete);
+        assert!(run.packages.is_empty());
+
+        let mut record = RunRecord::start(RunKind::Adopt);
+        record.adopted.push("htop".to_string());
+        record.finish();
+        let parsed = parse_run(&serde_json::to_string(&record).unwrap()).unwrap();
+        assert_eq!(parsed, Some(record.clone()));
+        assert!(record.touches("htop"));
+
+        let future = r#"{"version": 99, "id": "x", "layout": "unknown"}"#;
+        assert_eq!(parse_run(future).unwrap(), None);
+    }
+
+    #[test]
+    fn test_changed_files_reports_edits_additions_and_removals() {
+        let record = ApplyRecord {
+            finished_at: 0,
+            config_hashes: [("main.owl", "a"), ("hosts/vm.owl", "b"), ("old.owl", "c")]
+                .into_iter()
+                .map(|(path, hash)| (path.to_string(), hash.to_string()))
+                .collect(),
+        };
+        let current = [
+            ("main.owl", "a"),
+            ("hosts/vm.owl", "B"),
+            ("groups/dev.owl", "d"),
+        ]
+        .into_iter()
+        .map(|(path, hash)| (path.to_string(), hash.to_string()))
+        .collect();
+
+        assert_eq!(
+            record.changed_files(&current),
+            vec!["groups/dev.owl", "hosts/vm.owl", "old.owl"]
+        );
+    }
+}
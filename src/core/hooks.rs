@@ -0,0 +1,314 @@
+//! Commands the config runs around installing and removing a package
+//!
+//! `:pre_install`, `:post_install`, `:pre_remove`, and `:post_remove` lines under an
+//! `@package` each add one command, run through `sh -c` in the order given. A package
+//! is only removed once it has left the config, so its remove hooks are remembered in
+//! state while it is still declared.
+//!
+//! Every hook owl runs, these and the apply hooks in `core::apply_hooks`, is spawned
+//! through [`spawn`], so all of them get the same contract:
+//!
+//! - `OWL_PHASE`: the hook point, such as `post_install` or `pre-apply`
+//! - `OWL_PACKAGE`: the package a package hook belongs to; empty for apply hooks
+//! - `OWL_CHANGED_FILES`: the files the run has written so far, one per line
+//! - `OWL_DRY_RUN`: `1` on a dry run, otherwise `0`; a dry run lists hooks instead of
+//!   running them, so hooks see `0`
+//! - `OWL_RUN_ID`: the id of the run in `owl history`; empty when none is recorded
+//! - `OWL_CONFIG_DIR`: the owl config directory, `~/.owl`
+//!
+//! A zero exit is success and any other exit fails the hook, except
+//! [`EXIT_RETRY_LATER`] (75, `EX_TEMPFAIL`): the hook is deferred with a warning and
+//! does not count as a failure.
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+use crate::internal::color;
+use crate::internal::logging::LogCommand;
+
+/// The exit code with which a hook asks to be retried later instead of failing
+pub const EXIT_RETRY_LATER: i32 = 75;
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookPoint {
+    PreInstall,
+    PostInstall,
+    PreRemove,
+    PostRemove,
+}
+
+impl HookPoint {
+    /// The name used in the config directive and in output, e.g. `post_install`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PreInstall => "pre_install",
+            Self::PostInstall => "post_install",
+            Self::PreRemove => "pre_remove",
+            Self::PostRemove => "post_remove",
+        }
+    }
+
+    /// The point a config directive such as `:post_install` names
+    pub fn from_directive(directive: &str) -> Option<Self> {
+        [
+            Self::PreInstall,
+            Self::PostInstall,
+            Self::PreRemove,
+            Self::PostRemove,
+        ]
+        .into_iter()
+        .find(|point| directive.strip_prefix(':') == Some(point.name()))
+    }
+}
+
+/// A package's hook commands
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pre_install: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub post_install: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pre_remove: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub post_remove: Vec<String>,
+}
+
+impl Hooks {
+    pub fn commands(&self, point: HookPoint) -> &[String] {
+        match point {
+            HookPoint::PreInstall => &self.pre_install,
+            HookPoint::PostInstall => &self.post_install,
+            HookPoint::PreRemove => &self.pre_remove,
+            HookPoint::PostRemove => &self.post_remove,
+        }
+    }
+
+    pub fn commands_mut(&mut self, point: HookPoint) -> &mut Vec<String> {
+        match point {
+            HookPoint::PreInstall => &mut self.pre_install,
+            HookPoint::PostInstall => &mut self.post_install,
+            HookPoint::PreRemove => &mut self.pre_remove,
+            HookPoint::PostRemove => &mut self.post_remove,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Just the remove hooks, which is what state keeps
+    pub fn remove_hooks(&self) -> Self {
+        Self {
+            pre_remove: self.pre_remove.clone(),
+            post_remove: self.post_remove.clone(),
+            ..Self::default()
+        }
+    }
+}
+
+/// What a hook is told about the run through its environment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HookContext {
+    /// The hook point, such as `post_install` or `pre-apply`
+    pub phase: &'static str,
+    /// The package a package hook belongs to
+    pub package: Option<String>,
+    /// Files the run has written so far
+    pub changed_files: Vec<String>,
+    pub dry_run: bool,
+    /// The history id of the run, when it is recorded
+    pub run_id: Option<String>,
+}
+
+impl HookContext {
+    /// The `OWL_*` variables of the contract, with an empty value for what is unknown
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        let config_dir = crate::internal::files::owl_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+        vec![
+            ("OWL_PHASE", self.phase.to_string()),
+            ("OWL_PACKAGE", self.package.clone().unwrap_or_default()),
+            ("OWL_CHANGED_FILES", self.changed_files.join("\n")),
+            (
+                "OWL_DRY_RUN",
+                if self.dry_run { "1" } else { "0" }.to_string(),
+            ),
+            ("OWL_RUN_ID", self.run_id.clone().unwrap_or_default()),
+            ("OWL_CONFIG_DIR", config_dir),
+        ]
+    }
+}
+
+/// How a hook that did not fail ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ran,
+    /// It exited with [`EXIT_RETRY_LATER`]
+    Deferred,
+}
+
+/// Run one hook, `command` with the variables of `context` added. `what` names it in
+/// messages, e.g. "post_install hook of syncthing". A deferred hook is warned about.
+pub fn spawn(mut command: Command, what: &str, context: &HookContext) -> Result<Outcome> {
+    let status = command
+        .envs(context.vars())
+        .logged()
+        .status()
+        .map_err(|e| anyhow!("Failed to run the {}: {}", what, e))?;
+    match status.code() {
+        Some(0) => Ok(Outcome::Ran),
+        Some(EXIT_RETRY_LATER) => {
+            crate::error::warn(&format!(
+                "the {} asked to be retried later (exit {}), deferring it",
+                what, EXIT_RETRY_LATER
+            ));
+            Ok(Outcome::Deferred)
+        }
+        _ => Err(anyhow!("The {} failed ({})", what, status)),
+    }
+}
+
+/// Run `package`'s hooks for `point` in order, stopping at the first that fails. A
+/// dry run only prints them as "would run".
+pub fn run(
+    package: &str,
+    point: HookPoint,
+    hooks: &Hooks,
+    dry_run: bool,
+    run_id: Option<&str>,
+) -> Result<()> {
+    let context = HookContext {
+        phase: point.name(),
+        package: Some(package.to_string()),
+        changed_files: Vec::new(),
+        dry_run,
+        run_id: run_id.map(str::to_string),
+    };
+    for command in hooks.commands(point) {
+        let label = format!("{} {}: {}", package, point.name(), command);
+        if dry_run {
+            println!("  {}", color::action("would run", &label));
+            continue;
+        }
+        println!("  {}", color::action("hook", &label));
+        let mut sh = Command::new("sh");
+        sh.arg("-c").arg(command);
+        let what = format!("{} hook of {}", point.name(), package);
+        spawn(sh, &what, &context).map_err(|e| anyhow!("{}: {}", e, command))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_stops_at_the_first_failing_hook() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        let marker = temp.path().join("ran");
+        let hooks = Hooks {
+            post_install: vec![
+                "true".to_string(),
+                "false".to_string(),
+                format!("touch {}", marker.display()),
+            ],
+            pre_remove: vec!["true".to_string()],
+            ..Hooks::default()
+        };
+
+        assert!(run("syncthing", HookPoint::PreRemove, &hooks, false, None).is_ok());
+        assert!(run("syncthing", HookPoint::PreInstall, &hooks, false, None).is_ok());
+        // A dry run runs nothing, so nothing fails
+        assert!(run("syncthing", HookPoint::PostInstall, &hooks, true, None).is_ok());
+        assert!(!marker.exists());
+
+        let err = run("syncthing", HookPoint::PostInstall, &hooks, false, None).unwrap_err();
+        assert!(err.to_string().contains("post_install hook of syncthing"));
+        assert!(err.to_string().ends_with(": false"));
+        assert!(!marker.exists());
+
+        assert_eq!(
+            HookPoint::from_directive(":post_remove"),
+            Some(HookPoint::PostRemove)
+        );
+        assert_eq!(HookPoint::from_directive("post_remove"), None);
+        assert_eq!(
+            hooks.remove_hooks().commands(HookPoint::PreRemove),
+            ["true"]
+        );
+        assert!(hooks.remove_hooks().post_install.is_empty());
+    }
+
+    #[test]
+    fn test_spawn_injects_the_contract_and_defers_on_75() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        let log = temp.path().join("env");
+        let hooks = Hooks {
+            post_install: vec![
+                format!(
+                    "printf '%s|%s|%s|%s|%s|%s' \"$OWL_PHASE\" \"$OWL_PACKAGE\" \"$OWL_CHANGED_FILES\" \"$OWL_DRY_RUN\" \"$OWL_RUN_ID\" \"$OWL_CONFIG_DIR\" > {}",
+                    log.display()
+                ),
+                "exit 75".to_string(),
+                format!("echo after >> {}", log.display()),
+            ],
+            ..Hooks::default()
+        };
+        assert!(
+            run(
+                "syncthing",
+                HookPoint::PostInstall,
+                &hooks,
+                false,
+                Some("17-apply")
+            )
+            .is_ok()
+        );
+        let config_dir = crate::internal::files::owl_dir().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            format!(
+                "post_install|syncthing||0|17-apply|{}after\n",
+                config_dir.display()
+            )
+        );
+
+        let context = HookContext {
+            phase: "post-apply",
+            package: None,
+            changed_files: vec!["~/.bashrc".to_string(), "~/.vimrc".to_string()],
+            dry_run: false,
+            run_id: None,
+        };
+        let mut echo = Command::new("sh");
+        echo.arg("-c").arg(format!(
+            "printf '%s' \"$OWL_CHANGED_FILES\" > {}",
+            log.display()
+        ));
+        assert_eq!(
+            spawn(echo, "post-apply hook", &context).unwrap(),
+            Outcome::Ran
+        );
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "~/.bashrc\n~/.vimrc"
+        );
+
+        let mut defer = Command::new("sh");
+        defer.arg("-c").arg("exit 75");
+        assert_eq!(
+            spawn(defer, "post-apply hook", &context).unwrap(),
+            Outcome::Deferred
+        );
+        let mut fail = Command::new("sh");
+        fail.arg("-c").arg("exit 74");
+        let err = spawn(fail, "post-apply hook", &context).unwrap_err();
+        assert!(err.to_string().starts_with("The post-apply hook failed"));
+    }
+}
@@ -0,0 +1,165 @@
+//! One apply or adopt at a time
+//!
+//! The lock is an advisory `flock` on `.lock` in the owl directory, so it goes away
+//! with the process that holds it, crashed or not. The holder writes its pid, start
+//! time, and command into the file for whoever finds the lock taken.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Seek, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+pub const LOCK_FILE: &str = ".lock";
+
+/// Who holds the lock, as written into the lock file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Holder {
+    pub pid: u32,
+    /// Epoch seconds
+    pub started_at: u64,
+    pub command: String,
+}
+
+impl Holder {
+    fn current(command: &str) -> Self {
+        Self {
+            pid: std::process::id(),
+            started_at: crate::core::history::now(),
+            command: command.to_string(),
+        }
+    }
+
+    /// e.g. `owl apply (pid 4242, started 3m ago)`
+    pub fn describe(&self) -> String {
+        format!(
+            "owl {} (pid {}, started {})",
+            self.command,
+            self.pid,
+            crate::commands::status::format_age(
+                crate::core::history::now().saturating_sub(self.started_at)
+            )
+        )
+    }
+}
+
+/// The lock was taken by another run
+#[derive(Debug)]
+pub struct LockHeld {
+    pub path: PathBuf,
+    /// `None` when the lock file could not be read, e.g. while the holder writes it
+    pub holder: Option<Holder>,
+}
+
+impl std::fmt::Display for LockHeld {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.holder {
+            Some(holder) => write!(f, "another run holds the lock: {}", holder.describe()),
+            None => write!(f, "another run holds the lock at {}", self.path.display()),
+        }
+    }
+}
+
+impl std::error::Error for LockHeld {}
+
+/// The held lock; released when dropped or when the process exits
+#[derive(Debug)]
+pub struct RunLock {
+    _file: File,
+}
+
+/// Take the lock for `command`. When another run holds it, wait for it with `wait`,
+/// otherwise fail with [`LockHeld`].
+pub fn acquire(command: &str, wait: bool) -> Result<RunLock> {
+    acquire_at(
+        &crate::internal::files::owl_dir()?.join(LOCK_FILE),
+        command,
+        wait,
+    )
+}
+
+fn acquire_at(path: &Path, command: &str, wait: bool) -> Result<RunLock> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    // Not truncated on open: the holder's details stay readable until we own the lock
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    if !flock(&file, libc::LOCK_EX | libc::LOCK_NB)? {
+        let held = LockHeld {
+            path: path.to_path_buf(),
+            holder: read_holder(path),
+        };
+        if !wait {
+            return Err(held.into());
+        }
+        println!(
+            "  {} waiting for {}",
+            crate::internal::color::blue("info:"),
+            held.holder
+                .as_ref()
+                .map_or_else(|| "another owl run".to_string(), Holder::describe)
+        );
+        flock(&file, libc::LOCK_EX)?;
+    }
+
+    let holder = serde_json::to_string(&Holder::current(command))?;
+    file.set_len(0)
+        .and_then(|()| file.rewind())
+        .and_then(|()| writeln!(file, "{}", holder))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    log::debug!("lock: {} taken for {}", path.display(), command);
+    Ok(RunLock { _file: file })
+}
+
+/// `flock` the file; false when `LOCK_NB` is set and another process holds it
+fn flock(file: &File, operation: libc::c_int) -> Result<bool> {
+    loop {
+        // SAFETY: the descriptor belongs to `file`, which outlives the call
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(true);
+        }
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EWOULDBLOCK) => return Ok(false),
+            _ => return Err(err).context("Failed to lock the owl directory"),
+        }
+    }
+}
+
+fn read_holder(path: &Path) -> Option<Holder> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(content.trim()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_lock_reports_the_holder_until_released() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        let path = temp.path().join(LOCK_FILE);
+
+        let lock = acquire_at(&path, "apply", false).unwrap();
+        let err = acquire_at(&path, "adopt", false).unwrap_err();
+        let held = err.downcast_ref::<LockHeld>().expect("a LockHeld error");
+        let holder = held.holder.as_ref().expect("the holder's details");
+        assert_eq!(holder.pid, std::process::id());
+        assert_eq!(holder.command, "apply");
+        assert!(err.to_string().contains("owl apply (pid "));
+
+        drop(lock);
+        let _lock = acquire_at(&path, "adopt", false).unwrap();
+        assert_eq!(read_holder(&path).unwrap().command, "adopt");
+    }
+}
@@ -1,8 +1,33 @@
+#[cfg(feature = "alpm")]
+pub mod alpm;
+pub mod analysis;
+pub mod apply_hooks;
+pub mod aur_cache;
+pub mod aur_failures;
+pub mod autocommit;
+pub mod clean;
 pub mod config;
+pub mod deployed;
 pub mod dotfiles;
 pub mod env;
+pub mod fleet;
+pub mod git;
+pub mod history;
+pub mod hooks;
+pub mod lock;
+pub mod news;
 pub mod package;
+pub mod pacman_hook;
+pub mod pacman_log;
+pub mod plan;
 pub mod pm;
+pub mod privilege;
+pub mod sandbox;
+pub mod schedule;
 pub mod search;
 pub mod services;
+pub mod settings;
+pub mod snapshot;
 pub mod state;
+pub mod status_cache;
+pub mod system;
@@ -0,0 +1,367 @@
+//! Arch Linux news, read before upgrades
+//!
+//! Some upgrades need manual steps that are only announced on the news feed. With
+//! `[news] check = true`, apply fetches the feed (kept for `[news] cache_minutes`)
+//! and holds back upgrades while items newer than the last acknowledged one are
+//! unread. The feed, the fetch time, and the acknowledgment share one file beside
+//! the state.
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub const NEWS_URL: &str = "https://archlinux.org/feeds/news/";
+const NEWS_FILE: &str = "news.json";
+const FETCH_TIMEOUT_SECS: u64 = 5;
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static ACKNOWLEDGED: AtomicBool = AtomicBool::new(false);
+
+/// Skip the news check for the rest of the process (`--offline`)
+pub fn go_offline() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+/// Treat unread news as read for this run (`--ack-news`)
+pub fn acknowledge_unread() {
+    ACKNOWLEDGED.store(true, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewsItem {
+    pub title: String,
+    pub link: String,
+    /// Seconds since the Unix epoch
+    pub published: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct NewsState {
+    /// Publication time of the newest acknowledged item
+    acknowledged: u64,
+    fetched_at: u64,
+    items: Vec<NewsItem>,
+}
+
+impl NewsState {
+    /// Items newer than the acknowledgment, oldest first
+    fn unread(&self) -> Vec<NewsItem> {
+        let mut unread: Vec<NewsItem> = self
+            .items
+            .iter()
+            .filter(|item| item.published > self.acknowledged)
+            .cloned()
+            .collect();
+        unread.sort_by_key(|item| item.published);
+        unread
+    }
+
+    fn acknowledge(&mut self) {
+        if let Some(newest) = self.items.iter().map(|item| item.published).max() {
+            self.acknowledged = self.acknowledged.max(newest);
+        }
+    }
+}
+
+fn news_path() -> Result<PathBuf> {
+    crate::core::state::PackageState::sibling_path(NEWS_FILE)
+}
+
+fn read(path: &Path) -> NewsState {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| {
+            serde_json::from_str(&content)
+                .map_err(|err| log::debug!("news: ignoring {}: {}", path.display(), err))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+fn write(path: &Path, news: &NewsState) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let content = serde_json::to_string_pretty(news).context("Failed to serialize news")?;
+    std::fs::write(path, content + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Replace the entities the feed uses with their characters
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The text of the first `<tag>` element in `xml`, unwrapped from CDATA
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    let text = xml[start..end].trim();
+    Some(
+        text.strip_prefix("<![CDATA[")
+            .and_then(|text| text.strip_suffix("]]>"))
+            .unwrap_or(text),
+    )
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Parse an RSS `pubDate` such as `Sat, 21 Jun 2025 12:00:00 +0000`
+fn parse_pub_date(date: &str) -> Option<u64> {
+    let date = date.split_once(',').map_or(date, |(_, rest)| rest);
+    let fields: Vec<&str> = date.split_whitespace().collect();
+    let [day, month, year, time, zone] = fields[..] else {
+        return None;
+    };
+    let month = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ]
+    .iter()
+    .position(|name| *name == month)? as i64
+        + 1;
+    let clock: Vec<i64> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let [hours, minutes, seconds] = clock[..] else {
+        return None;
+    };
+    let offset = match zone {
+        "GMT" | "UT" | "UTC" | "Z" => 0,
+        _ => {
+            let sign = if zone.starts_with('-') { -1 } else { 1 };
+            let digits = zone.trim_start_matches(['+', '-']);
+            let hhmm: i64 = digits.parse().ok()?;
+            sign * ((hhmm / 100) * 3600 + (hhmm % 100) * 60)
+        }
+    };
+    let days = days_from_civil(year.parse().ok()?, month, day.parse().ok()?);
+    u64::try_from(days * 86_400 + hours * 3600 + minutes * 60 + seconds - offset).ok()
+}
+
+/// The items of an RSS document; items without a parsable date are skipped
+pub fn parse_feed(xml: &str) -> Vec<NewsItem> {
+    xml.split("<item>")
+        .skip(1)
+        .filter_map(|item| {
+            let item = &item[..item.find("</item>")?];
+            Some(NewsItem {
+                title: unescape(element(item, "title")?),
+                link: unescape(element(item, "link").unwrap_or_default()),
+                published: parse_pub_date(element(item, "pubDate")?)?,
+            })
+        })
+        .collect()
+}
+
+fn fetch() -> Result<Vec<NewsItem>> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .build();
+    let body = agent
+        .get(NEWS_URL)
+        .call()
+        .map_err(|e| anyhow!("news request failed: {}", e))?
+        .into_string()
+        .map_err(|e| anyhow!("Failed to read the news feed: {}", e))?;
+    let items = parse_feed(&body);
+    if items.is_empty() {
+        return Err(anyhow!("the news feed had no items"));
+    }
+    Ok(items)
+}
+
+/// Unread news, fetching the feed with `fetch` when the cached copy is older than
+/// `ttl` seconds. The first fetch marks what is already out as read, so only news
+/// published once owl checks it holds upgrades back.
+fn unread(
+    news: &mut NewsState,
+    now: u64,
+    ttl: u64,
+    fetch: impl FnOnce() -> Result<Vec<NewsItem>>,
+) -> Result<Vec<NewsItem>> {
+    if news.items.is_empty() || now.saturating_sub(news.fetched_at) >= ttl {
+        let first = news.fetched_at == 0 && news.acknowledged == 0;
+        news.items = fetch()?;
+        news.fetched_at = now;
+        if first {
+            news.acknowledge();
+        }
+    }
+    Ok(news.unread())
+}
+
+/// Write `news` unless this is a dry run or a read-only command
+fn save(path: &Path, news: &NewsState, dry_run: bool) -> Result<()> {
+    if dry_run || crate::core::state::read_only() {
+        return Ok(());
+    }
+    write(path, news)
+}
+
+/// What the news says about upgrading now
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// Checking is off, skipped, or found nothing unread
+    Clear,
+    /// Unread items that were acknowledged with `--ack-news`
+    Acknowledged(Vec<NewsItem>),
+    /// Unread items that still need an acknowledgment
+    Unread(Vec<NewsItem>),
+}
+
+/// Check the news before upgrades. A feed that cannot be fetched is reported and
+/// does not hold anything back. A dry run, `--ack-news` included, writes nothing.
+pub fn check(dry_run: bool) -> Result<Verdict> {
+    if !crate::core::settings::get().news.check || OFFLINE.load(Ordering::Relaxed) {
+        return Ok(Verdict::Clear);
+    }
+    let path = news_path()?;
+    let mut news = read(&path);
+    let ttl = crate::core::settings::get().news.cache_minutes * 60;
+    let unread = unread(&mut news, crate::core::history::now(), ttl, fetch)?;
+    let verdict = if unread.is_empty() {
+        Verdict::Clear
+    } else if ACKNOWLEDGED.load(Ordering::Relaxed) {
+        news.acknowledge();
+        Verdict::Acknowledged(unread)
+    } else {
+        Verdict::Unread(unread)
+    };
+    save(&path, &news, dry_run)?;
+    Ok(verdict)
+}
+
+/// Mark every item in the cached feed as read
+pub fn acknowledge() -> Result<()> {
+    let path = news_path()?;
+    let mut news = read(&path);
+    news.acknowledge();
+    write(&path, &news)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0"><channel><title>Arch Linux: Recent news updates</title>
+<link>https://archlinux.org/news/</link>
+<item><title>Manual intervention for pacman 7.0 &amp; local repos</title>
+<link>https://archlinux.org/news/manual-intervention-for-pacman-700/</link>
+<description>&lt;p&gt;With the release of pacman 7.0.0...&lt;/p&gt;</description>
+<pubDate>Sat, 14 Sep 2024 08:30:15 +0000</pubDate></item>
+<item><title><![CDATA[The sshd service needs a restart]]></title>
+<link>https://archlinux.org/news/the-sshd-service-needs-restarting/</link>
+<pubDate>Mon, 01 Jul 2024 12:00:00 +0200</pubDate></item>
+<item><title>No date</title></item>
+</channel></rss>"#;
+
+    #[test]
+    fn test_parse_feed() {
+        let items = parse_feed(FEED);
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            items[0].title,
+            "Manual intervention for pacman 7.0 & local repos"
+        );
+        assert_eq!(
+            items[0].link,
+            "https://archlinux.org/news/manual-intervention-for-pacman-700/"
+        );
+        assert_eq!(items[0].published, 1_726_302_615);
+        assert_eq!(items[1].title, "The sshd service needs a restart");
+        assert_eq!(items[1].published, 1_719_828_000);
+        assert_eq!(
+            parse_pub_date("14 Sep 2024 08:30:15 GMT"),
+            Some(1_726_302_615)
+        );
+        assert_eq!(parse_pub_date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_acknowledgment_hides_read_items() {
+        let mut news = NewsState {
+            items: parse_feed(FEED),
+            ..Default::default()
+        };
+        let titles = |news: &NewsState| {
+            news.unread()
+                .into_iter()
+                .map(|item| item.title)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            titles(&news),
+            [
+                "The sshd service needs a restart",
+                "Manual intervention for pacman 7.0 & local repos"
+            ]
+        );
+        news.acknowledge();
+        assert!(news.unread().is_empty());
+
+        // A newer item shows up on the next fetch, and only it is unread
+        news.items.push(NewsItem {
+            title: "Valkey replaces Redis".to_string(),
+            link: String::new(),
+            published: news.acknowledged + 60,
+        });
+        assert_eq!(titles(&news), ["Valkey replaces Redis"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(NEWS_FILE);
+        write(&path, &news).unwrap();
+        assert_eq!(read(&path), news);
+        assert_eq!(read(&dir.path().join("missing.json")), NewsState::default());
+    }
+
+    #[test]
+    fn test_first_fetch_seeds_the_acknowledgment_and_dry_runs_keep_it() {
+        let mut news = NewsState::default();
+        let unread_now = unread(&mut news, 1_800_000_000, 3600, || Ok(parse_feed(FEED))).unwrap();
+        assert!(unread_now.is_empty());
+        assert_eq!(news.acknowledged, 1_726_302_615);
+
+        // Within the TTL the cached feed is used; after it, a newer item is unread
+        let unread_now = unread(&mut news, 1_800_000_060, 3600, || {
+            panic!("fetched within the TTL")
+        })
+        .unwrap();
+        assert!(unread_now.is_empty());
+        let newer = || {
+            let mut items = parse_feed(FEED);
+            items.push(NewsItem {
+                title: "Valkey replaces Redis".to_string(),
+                link: String::new(),
+                published: 1_790_000_000,
+            });
+            Ok(items)
+        };
+        let unread_now = unread(&mut news, 1_800_007_200, 3600, newer).unwrap();
+        assert_eq!(unread_now.len(), 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(NEWS_FILE);
+        save(&path, &news, true).unwrap();
+        assert!(!path.exists());
+        save(&path, &news, false).unwrap();
+        assert_eq!(read(&path), news);
+    }
+}
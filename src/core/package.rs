@@ -1,11 +1,12 @@
 //! Package management utilities
 
 use crate::core::config::Config;
-use crate::core::pm::{ParuPacman, SearchResult};
+use crate::core::pm::{PackageInfo, ParuPacman, SearchResult, SyncNames};
 use crate::core::state::PackageState;
+use crate::core::system::{Backend, PackageQueries, SystemSnapshot};
 use anyhow::Result;
-use std::collections::HashSet;
-use std::sync::OnceLock;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
 
 /// Package action types for planning installations and removals
 #[derive(Debug, Clone, PartialEq)]
@@ -14,31 +15,45 @@ pub enum PackageAction {
     Remove { name: String },
 }
 
-// Cache of installed packages for the current process run
-static INSTALLED_CACHE: OnceLock<HashSet<String>> = OnceLock::new();
-static PACKAGE_COUNT_CACHE: OnceLock<usize> = OnceLock::new();
+// Cache of installed packages and their versions for the current process run
+static INSTALLED_CACHE: Mutex<Option<BTreeMap<String, String>>> = Mutex::new(None);
+static PACKAGE_COUNT_CACHE: Mutex<Option<usize>> = Mutex::new(None);
 
-fn query_installed_packages() -> Result<HashSet<String>> {
-    ParuPacman::new().list_installed()
+fn query_installed_packages() -> Result<BTreeMap<String, String>> {
+    Backend::select().installed_versions()
 }
 
 /// Plan package actions by comparing desired config with installed packages
-pub fn plan_package_actions(config: &Config, state: &PackageState) -> Result<Vec<PackageAction>> {
-    let installed = get_installed_packages()?;
+pub fn plan_package_actions(
+    config: &Config,
+    state: &PackageState,
+    system: &SystemSnapshot,
+) -> Result<Vec<PackageAction>> {
+    let installed = system.installed();
     let desired: HashSet<String> = config.packages.keys().cloned().collect();
 
+    // Every name that is no installed package may be a group: look them up at once
+    let unresolved: Vec<String> = desired
+        .iter()
+        .filter(|name| !installed.contains(*name))
+        .cloned()
+        .collect();
+    let groups = ParuPacman::new().group_members(&unresolved)?;
+    // Members of declared groups are wanted too, so none of them is removed
+    let wanted: HashSet<&String> = desired.iter().chain(groups.values().flatten()).collect();
+
     let mut actions = Vec::new();
 
     for package in &desired {
-        if !is_package_or_group_installed(package)? {
+        if !system.has_package_or_group(package)? {
             actions.push(PackageAction::Install {
                 name: package.clone(),
             });
         }
     }
 
-    for package in &installed {
-        if !desired.contains(package) && state.is_managed(package) {
+    for package in installed {
+        if !wanted.contains(package) && state.is_managed(package) {
             actions.push(PackageAction::Remove {
                 name: package.clone(),
             });
@@ -48,16 +63,163 @@ pub fn plan_package_actions(config: &Config, state: &PackageState) -> Result<Vec
     Ok(actions)
 }
 
+/// Drop the removals of protected packages (`Config::is_protected`), returning the
+/// remaining actions and the names left installed
+pub fn split_protected(
+    actions: Vec<PackageAction>,
+    config: &Config,
+) -> (Vec<PackageAction>, Vec<String>) {
+    let mut protected = Vec::new();
+    let actions = actions
+        .into_iter()
+        .filter(|action| match action {
+            PackageAction::Remove { name } if config.is_protected(name) => {
+                protected.push(name.clone());
+                false
+            }
+            _ => true,
+        })
+        .collect();
+    protected.sort();
+    (actions, protected)
+}
+
+/// Packages updates must skip, sorted: pins made with `owl pin` and `@pin` entries
+pub fn held_packages(state: &PackageState, config: &Config) -> Vec<String> {
+    let mut held = state.pinned_names();
+    held.extend(config.pins.keys().cloned());
+    held.sort();
+    held.dedup();
+    held
+}
+
+/// The hooks of the declared packages that have any, by package
+pub fn package_hooks(config: &Config) -> BTreeMap<String, crate::core::hooks::Hooks> {
+    config
+        .packages
+        .iter()
+        .filter(|(_, package)| !package.hooks.is_empty())
+        .map(|(name, package)| (name.clone(), package.hooks.clone()))
+        .collect()
+}
+
+/// A configured package that is not installed by its own name, but provided by one
+/// that is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Satisfied {
+    pub name: String,
+    pub by: String,
+}
+
+/// Let installed packages stand in for configured names they provide: drops those
+/// installs, and the removal of every installed provider of them. Asks pacman for the provides only
+/// when there is an install that is not `:exact`.
+pub fn resolve_provides(
+    actions: Vec<PackageAction>,
+    config: &Config,
+) -> Result<(Vec<PackageAction>, Vec<Satisfied>)> {
+    let candidates = actions.iter().any(|action| match action {
+        PackageAction::Install { name } => !config.packages.get(name).is_some_and(|p| p.exact),
+        PackageAction::Remove { .. } => false,
+    });
+    if !candidates {
+        return Ok((actions, Vec::new()));
+    }
+    let provides = ParuPacman::new().installed_provides()?;
+    Ok(satisfy_with_provides(actions, config, &provides))
+}
+
+/// The installed packages providing each name, in package name order, from the
+/// `installed_provides` map. Versions are dropped, so `libfoo.so=1-64` counts as
+/// `libfoo.so`.
+pub fn providers(provides: &BTreeMap<String, Vec<String>>) -> HashMap<&str, Vec<&str>> {
+    let mut providers: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (package, provided) in provides {
+        for entry in provided {
+            providers
+                .entry(dependency_name(entry))
+                .or_default()
+                .push(package.as_str());
+        }
+    }
+    providers
+}
+
+/// `resolve_provides` with `provides` mapping installed packages to what they provide
+fn satisfy_with_provides(
+    actions: Vec<PackageAction>,
+    config: &Config,
+    provides: &BTreeMap<String, Vec<String>>,
+) -> (Vec<PackageAction>, Vec<Satisfied>) {
+    let providers = providers(provides);
+    let mut satisfied = Vec::new();
+    let mut kept = Vec::new();
+    for action in actions {
+        if let PackageAction::Install { name } = &action
+            && !config.packages.get(name).is_some_and(|p| p.exact)
+            && let Some(by) = providers.get(name.as_str())
+        {
+            satisfied.push(Satisfied {
+                name: name.clone(),
+                by: by[0].to_string(),
+            });
+            continue;
+        }
+        kept.push(action);
+    }
+    // Removing any provider could leave the configured name unprovided
+    let keep: HashSet<&str> = satisfied
+        .iter()
+        .flat_map(|s| &providers[s.name.as_str()])
+        .copied()
+        .collect();
+    kept.retain(|action| match action {
+        PackageAction::Remove { name } => !keep.contains(name.as_str()),
+        PackageAction::Install { .. } => true,
+    });
+    satisfied.sort_by(|a, b| a.name.cmp(&b.name));
+    (kept, satisfied)
+}
+
 /// Get list of all installed packages
 pub fn get_installed_packages() -> Result<HashSet<String>> {
-    if let Some(cached) = INSTALLED_CACHE.get() {
-        return Ok(cached.clone());
+    Ok(get_installed_versions()?.into_keys().collect())
+}
+
+/// Every installed package with its version, from one `pacman -Q` per run
+pub fn get_installed_versions() -> Result<BTreeMap<String, String>> {
+    if let Some(cached) = cached_installed() {
+        return Ok(cached);
     }
     let installed = query_installed_packages()?;
-    let _ = INSTALLED_CACHE.set(installed.clone());
+    store_installed(installed.clone());
     Ok(installed)
 }
 
+fn cached_installed() -> Option<BTreeMap<String, String>> {
+    INSTALLED_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+fn store_installed(installed: BTreeMap<String, String>) {
+    *INSTALLED_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(installed);
+}
+
+/// Forget cached pacman queries so the next lookup sees installs and removals
+/// made since, e.g. when a long-running session re-plans after applying
+pub fn invalidate_package_caches() {
+    *INSTALLED_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    *PACKAGE_COUNT_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+}
+
 /// Remove unmanaged packages
 pub fn remove_unmanaged_packages(packages: &[String], quiet: bool) -> Result<()> {
     if packages.is_empty() {
@@ -74,33 +236,164 @@ pub fn remove_unmanaged_packages(packages: &[String], quiet: bool) -> Result<()>
     ParuPacman::new().remove_packages(packages, quiet)
 }
 
+/// Packages installed as dependencies that nothing requires any more
+pub fn get_orphan_packages() -> Result<Vec<String>> {
+    ParuPacman::new().orphans()
+}
+
+/// What removing orphans needs from the package manager
+pub trait OrphanRemoval {
+    /// Packages installed as dependencies that nothing requires (`pacman -Qdtq`)
+    fn orphans(&self) -> Result<Vec<String>>;
+    /// Remove packages with the dependencies only they needed (`pacman -Rns`)
+    fn remove(&self, packages: &[String]) -> Result<()>;
+}
+
+impl OrphanRemoval for ParuPacman {
+    fn orphans(&self) -> Result<Vec<String>> {
+        ParuPacman::orphans(self)
+    }
+
+    fn remove(&self, packages: &[String]) -> Result<()> {
+        self.remove_packages(packages, true)
+    }
+}
+
+/// Query the orphans, leave out protected ones, let `confirm` pick which to remove,
+/// and remove those. Returns the packages removed.
+pub fn remove_orphans_with(
+    pm: &impl OrphanRemoval,
+    config: &Config,
+    confirm: impl FnOnce(&[String]) -> Result<Vec<String>>,
+) -> Result<Vec<String>> {
+    let orphans: Vec<String> = pm
+        .orphans()?
+        .into_iter()
+        .filter(|name| !config.is_protected(name))
+        .collect();
+    if orphans.is_empty() {
+        return Ok(Vec::new());
+    }
+    let selected = confirm(&orphans)?;
+    if !selected.is_empty() {
+        pm.remove(&selected)?;
+    }
+    Ok(selected)
+}
+
+/// Get pacman metadata for the installed packages among `packages`
+pub fn get_package_info(packages: &[String]) -> Result<HashMap<String, PackageInfo>> {
+    Backend::select().package_info(packages)
+}
+
+/// The installed packages that require each of the packages in `info`, from the
+/// "Required By" of a `pacman -Qi`; packages nothing requires are left out
+pub fn reverse_dependencies(info: &HashMap<String, PackageInfo>) -> BTreeMap<String, Vec<String>> {
+    info.iter()
+        .filter(|(_, info)| !info.required_by.is_empty())
+        .map(|(name, info)| (name.clone(), info.required_by.clone()))
+        .collect()
+}
+
+/// The removal candidates that a managed package staying installed requires, with
+/// those packages. Dependents that are being removed too do not count.
+pub fn required_by_managed(
+    reverse: &BTreeMap<String, Vec<String>>,
+    removing: &[String],
+    managed: &[String],
+) -> BTreeMap<String, Vec<String>> {
+    reverse
+        .iter()
+        .filter_map(|(name, dependents)| {
+            let kept: Vec<String> = dependents
+                .iter()
+                .filter(|dependent| managed.contains(dependent) && !removing.contains(dependent))
+                .cloned()
+                .collect();
+            (!kept.is_empty()).then(|| (name.clone(), kept))
+        })
+        .collect()
+}
+
+/// A dependency name without its version constraint, e.g. `glibc>=2.40` is `glibc`
+pub fn dependency_name(dependency: &str) -> &str {
+    dependency
+        .split(['<', '>', '='])
+        .next()
+        .unwrap_or(dependency)
+}
+
+/// Installed packages that `roots` pull in, directly or through other dependencies,
+/// with one `pacman -Qi` call per level. A root is only included when another root
+/// depends on it.
+pub fn dependency_closure(roots: &[String]) -> Result<HashSet<String>> {
+    dependency_closure_with(roots, get_package_info)
+}
+
+fn dependency_closure_with(
+    roots: &[String],
+    mut query: impl FnMut(&[String]) -> Result<HashMap<String, PackageInfo>>,
+) -> Result<HashSet<String>> {
+    let mut queried: HashSet<String> = roots.iter().cloned().collect();
+    let mut closure = HashSet::new();
+    let mut level: Vec<String> = roots.to_vec();
+    while !level.is_empty() {
+        let mut next = Vec::new();
+        for info in query(&level)?.into_values() {
+            for dep in &info.depends_on {
+                let name = dependency_name(dep);
+                closure.insert(name.to_string());
+                if queried.insert(name.to_string()) {
+                    next.push(name.to_string());
+                }
+            }
+        }
+        level = next;
+    }
+    Ok(closure)
+}
+
+/// Get sync db metadata for the repo packages among `packages`
+pub fn get_repo_package_info(packages: &[String]) -> Result<HashMap<String, PackageInfo>> {
+    ParuPacman::new().repo_package_info(packages)
+}
+
 /// Get the count of packages that can be upgraded
 pub fn get_package_count() -> Result<usize> {
-    if let Some(cached) = PACKAGE_COUNT_CACHE.get() {
-        return Ok(*cached);
+    let mut cache = PACKAGE_COUNT_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(cached) = *cache {
+        return Ok(cached);
     }
     let count = ParuPacman::new().upgrade_count()?;
-    let _ = PACKAGE_COUNT_CACHE.set(count);
+    *cache = Some(count);
     Ok(count)
 }
 
 /// Check if a package is installed
 pub fn is_package_installed(package_name: &str) -> Result<bool> {
-    if let Some(cached) = INSTALLED_CACHE.get() {
-        return Ok(cached.contains(package_name));
+    if let Some(cached) = cached_installed() {
+        return Ok(cached.contains_key(package_name));
     }
     let installed = query_installed_packages()?;
-    let contains = installed.contains(package_name);
-    let _ = INSTALLED_CACHE.set(installed.clone());
+    let contains = installed.contains_key(package_name);
+    store_installed(installed);
     Ok(contains)
 }
 
 /// Check if a package or group is effectively installed
 /// For regular packages, checks if the package is installed
 /// For groups, checks if all packages in the group are installed
+#[cfg(test)]
 pub fn is_package_or_group_installed(package_name: &str) -> Result<bool> {
+    package_or_group_in(&get_installed_packages()?, package_name)
+}
+
+/// Whether `package_name` is among `installed`, or is a group whose members all are
+pub fn package_or_group_in(installed: &HashSet<String>, package_name: &str) -> Result<bool> {
     // First check if it's a regular package (fastest check)
-    if is_package_installed(package_name)? {
+    if installed.contains(package_name) {
         return Ok(true);
     }
 
@@ -113,8 +406,6 @@ pub fn is_package_or_group_installed(package_name: &str) -> Result<bool> {
             return Ok(false);
         }
 
-        // Use the cached installed packages list for faster lookups
-        let installed = get_installed_packages()?;
         for pkg in group_packages {
             if !installed.contains(&pkg) {
                 return Ok(false);
@@ -127,6 +418,36 @@ pub fn is_package_or_group_installed(package_name: &str) -> Result<bool> {
     Ok(false)
 }
 
+/// Each group's member packages, by group name
+pub type GroupMembers = BTreeMap<String, Vec<String>>;
+
+/// `names` with each group in `groups` replaced by its members, for pacman: the
+/// members are then installed without its group selection prompt
+pub fn expand_groups(names: &[String], groups: &GroupMembers) -> Vec<String> {
+    let mut seen = HashSet::new();
+    names
+        .iter()
+        .flat_map(|name| {
+            groups
+                .get(name)
+                .map_or(std::slice::from_ref(name), Vec::as_slice)
+        })
+        .filter(|name| seen.insert(*name))
+        .cloned()
+        .collect()
+}
+
+/// `names` for display, with each group in `groups` shown as `base-devel (26 packages)`
+pub fn describe_groups(names: &[String], groups: &GroupMembers) -> Vec<String> {
+    names
+        .iter()
+        .map(|name| match groups.get(name) {
+            Some(members) => format!("{} ({} packages)", name, members.len()),
+            None => name.clone(),
+        })
+        .collect()
+}
+
 /// Determine if a package is available in official repositories
 #[cfg(test)]
 pub fn is_repo_package(package_name: &str) -> Result<bool, String> {
@@ -136,23 +457,118 @@ pub fn is_repo_package(package_name: &str) -> Result<bool, String> {
     Ok(set.contains(package_name))
 }
 
-/// Categorize packages into repo and AUR lists
-pub fn categorize_packages(packages: &[String]) -> Result<(Vec<String>, Vec<String>)> {
+/// Where names come from, as found by `categorize_packages`. Each list keeps the
+/// order the names were given in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Categorized {
+    /// Packages in a sync repo
+    pub repo: Vec<String>,
+    /// Package groups, which pacman installs from the repos
+    pub groups: Vec<String>,
+    /// Names no repo package has but one provides, e.g. `java-runtime`; pacman
+    /// installs a provider
+    pub provided: Vec<String>,
+    pub aur: Vec<String>,
+    /// Found neither in the repos nor in the AUR
+    pub unknown: Vec<String>,
+}
+
+impl Categorized {
+    /// Names for pacman and names for the AUR helper. Unknown names go to the
+    /// helper, which reports them properly.
+    pub fn install_sets(self) -> (Vec<String>, Vec<String>) {
+        let mut repo = self.repo;
+        repo.extend(self.groups);
+        repo.extend(self.provided);
+        let mut aur = self.aur;
+        aur.extend(self.unknown);
+        (repo, aur)
+    }
+}
+
+/// The batched lookups categorizing needs, one call each for any number of names
+pub trait PackageSources {
+    /// Names that sync repos have as packages, groups, or provides (one `pacman -Si`
+    /// over the sync dbs)
+    fn sync_lookup(&self, names: &[String]) -> Result<SyncNames>;
+    /// Names the AUR has a package for (one RPC info request)
+    fn aur_available(&self, names: &[String]) -> Result<HashSet<String>>;
+}
+
+impl PackageSources for ParuPacman {
+    fn sync_lookup(&self, names: &[String]) -> Result<SyncNames> {
+        self.batch_sync_lookup(names)
+    }
+
+    fn aur_available(&self, names: &[String]) -> Result<HashSet<String>> {
+        Ok(crate::core::search::aur_info_batch(names)?
+            .into_keys()
+            .collect())
+    }
+}
+
+/// Sources with the AUR lookup left out: every name the repos cannot place counts as
+/// an AUR package (`apply --no-network-check`)
+struct WithoutAur<S>(S);
+
+impl<S: PackageSources> PackageSources for WithoutAur<S> {
+    fn sync_lookup(&self, names: &[String]) -> Result<SyncNames> {
+        self.0.sync_lookup(names)
+    }
+
+    fn aur_available(&self, names: &[String]) -> Result<HashSet<String>> {
+        Ok(names.iter().cloned().collect())
+    }
+}
+
+/// Sort packages into repo packages, groups, names repo packages provide, AUR
+/// packages, and unknown names
+pub fn categorize_packages(packages: &[String]) -> Result<Categorized> {
+    categorize_with(&ParuPacman::new(), packages)
+}
+
+/// `categorize_packages` without asking the AUR, so nothing comes out unknown
+pub fn categorize_packages_offline(packages: &[String]) -> Result<Categorized> {
+    categorize_with(&WithoutAur(ParuPacman::new()), packages)
+}
+
+/// `categorize_packages` against any source, in at most two lookups: the sync dbs
+/// place every name they can, and the AUR only sees the leftovers. A package wins
+/// over a group of the same name, and a group over a provide. When the AUR cannot be
+/// reached, the leftovers count as AUR packages, as they would without the check.
+pub fn categorize_with(sources: &impl PackageSources, packages: &[String]) -> Result<Categorized> {
+    let mut categorized = Categorized::default();
     if packages.is_empty() {
-        return Ok((Vec::new(), Vec::new()));
+        return Ok(categorized);
     }
-    let available = ParuPacman::new().batch_repo_available(packages)?;
-    let repo_packages: Vec<String> = packages
-        .iter()
-        .filter(|p| available.contains(&**p))
-        .cloned()
-        .collect();
-    let aur_packages: Vec<String> = packages
-        .iter()
-        .filter(|p| !available.contains(&**p))
-        .cloned()
-        .collect();
-    Ok((repo_packages, aur_packages))
+    let sync = sources.sync_lookup(packages)?;
+    let mut rest = Vec::new();
+    for name in packages.iter().cloned() {
+        if sync.packages.contains(&name) {
+            categorized.repo.push(name);
+        } else if sync.groups.contains(&name) {
+            categorized.groups.push(name);
+        } else if sync.provided.contains(&name) {
+            categorized.provided.push(name);
+        } else {
+            rest.push(name);
+        }
+    }
+    if rest.is_empty() {
+        return Ok(categorized);
+    }
+
+    match sources.aur_available(&rest) {
+        Ok(aur) => {
+            (categorized.aur, categorized.unknown) =
+                rest.into_iter().partition(|p| aur.contains(p));
+        }
+        Err(err) => {
+            log::debug!("categorize: AUR lookup failed, assuming AUR: {:#}", err);
+            categorized.aur = rest;
+        }
+    }
+    Ok(categorized)
 }
 
 /// Search packages using the configured pacman/AUR backends
@@ -189,9 +605,175 @@ mod tests {
         let packages = vec!["bash".to_string(), "nonexistentpackage12345".to_string()];
         let result = categorize_packages(&packages);
         assert!(result.is_ok());
-        let (repo_packages, aur_packages) = result.unwrap();
-        assert!(repo_packages.contains(&"bash".to_string()));
-        assert!(aur_packages.contains(&"nonexistentpackage12345".to_string()));
+        let categorized = result.unwrap();
+        assert!(categorized.repo.contains(&"bash".to_string()));
+        // Offline the AUR lookup fails and the leftover counts as AUR
+        let missing = "nonexistentpackage12345".to_string();
+        assert!(categorized.unknown.contains(&missing) || categorized.aur.contains(&missing));
+    }
+
+    #[derive(Default)]
+    struct CountingSources {
+        calls: std::cell::Cell<usize>,
+    }
+
+    impl CountingSources {
+        fn with_prefix(names: &[String], prefix: &str) -> HashSet<String> {
+            names
+                .iter()
+                .filter(|name| name.starts_with(prefix))
+                .cloned()
+                .collect()
+        }
+    }
+
+    impl PackageSources for CountingSources {
+        fn sync_lookup(&self, names: &[String]) -> Result<SyncNames> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(SyncNames {
+                packages: Self::with_prefix(names, "repo-"),
+                groups: Self::with_prefix(names, "group-"),
+                provided: Self::with_prefix(names, "provided-"),
+            })
+        }
+
+        fn aur_available(&self, names: &[String]) -> Result<HashSet<String>> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(Self::with_prefix(names, "aur-"))
+        }
+    }
+
+    struct FixedSources {
+        repo: &'static [&'static str],
+        groups: &'static [&'static str],
+        provided: &'static [&'static str],
+        aur: &'static [&'static str],
+    }
+
+    impl FixedSources {
+        fn among(names: &[String], known: &[&str]) -> HashSet<String> {
+            names
+                .iter()
+                .filter(|name| known.contains(&name.as_str()))
+                .cloned()
+                .collect()
+        }
+    }
+
+    impl PackageSources for FixedSources {
+        fn sync_lookup(&self, names: &[String]) -> Result<SyncNames> {
+            Ok(SyncNames {
+                packages: Self::among(names, self.repo),
+                groups: Self::among(names, self.groups),
+                provided: Self::among(names, self.provided),
+            })
+        }
+
+        fn aur_available(&self, names: &[String]) -> Result<HashSet<String>> {
+            Ok(Self::among(names, self.aur))
+        }
+    }
+
+    #[test]
+    fn test_categorize_groups_that_are_no_package() {
+        let names: Vec<String> = ["xorg", "htop", "gnome", "paru-bin", "kde-applications"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        // `xorg` is a group and no package; `gnome` is both, and a package wins
+        let sources = FixedSources {
+            repo: &["htop", "gnome"],
+            groups: &["xorg", "gnome", "kde-applications"],
+            provided: &["xorg"],
+            aur: &["paru-bin", "xorg"],
+        };
+        let categorized = categorize_with(&sources, &names).unwrap();
+        assert_eq!(categorized.repo, ["htop", "gnome"]);
+        assert_eq!(categorized.groups, ["xorg", "kde-applications"]);
+        assert_eq!(categorized.aur, ["paru-bin"]);
+        assert!(categorized.unknown.is_empty());
+        let (repo, aur) = categorized.install_sets();
+        assert_eq!(repo, ["htop", "gnome", "xorg", "kde-applications"]);
+        assert_eq!(aur, ["paru-bin"]);
+    }
+
+    #[test]
+    fn test_categorize_without_aur_finds_nothing_unknown() {
+        let names: Vec<String> = ["htop", "paru-bin", "htpo"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let sources = FixedSources {
+            repo: &["htop"],
+            groups: &[],
+            provided: &[],
+            aur: &["paru-bin"],
+        };
+        let categorized = categorize_with(&sources, &names).unwrap();
+        assert_eq!(categorized.aur, ["paru-bin"]);
+        assert_eq!(categorized.unknown, ["htpo"]);
+
+        let categorized = categorize_with(&WithoutAur(sources), &names).unwrap();
+        assert_eq!(categorized.repo, ["htop"]);
+        assert_eq!(categorized.aur, ["paru-bin", "htpo"]);
+        assert!(categorized.unknown.is_empty());
+    }
+
+    #[test]
+    fn test_expand_and_describe_groups() {
+        let names =
+            |names: &[&str]| -> Vec<String> { names.iter().map(ToString::to_string).collect() };
+        let groups = BTreeMap::from([
+            ("base-devel".to_string(), names(&["gcc", "make", "patch"])),
+            ("xorg-apps".to_string(), names(&["xorg-xrandr", "make"])),
+        ]);
+        let installs = names(&["htop", "base-devel", "xorg-apps", "gcc"]);
+        assert_eq!(
+            expand_groups(&installs, &groups),
+            ["htop", "gcc", "make", "patch", "xorg-xrandr"]
+        );
+        assert_eq!(
+            describe_groups(&installs, &groups),
+            [
+                "htop",
+                "base-devel (3 packages)",
+                "xorg-apps (2 packages)",
+                "gcc"
+            ]
+        );
+        assert_eq!(expand_groups(&installs, &BTreeMap::new()), installs);
+    }
+
+    #[test]
+    fn test_categorize_runs_a_constant_number_of_lookups() {
+        for count in [5, 500] {
+            let names: Vec<String> = (0..count)
+                .map(|i| {
+                    let kind = ["repo", "group", "provided", "aur", "typo"][i % 5];
+                    format!("{}-{}", kind, i)
+                })
+                .collect();
+            let sources = CountingSources::default();
+            let categorized = categorize_with(&sources, &names).unwrap();
+            assert_eq!(sources.calls.get(), 2);
+            assert_eq!(categorized.repo.len(), count / 5);
+            assert_eq!(categorized.groups.len(), count / 5);
+            assert_eq!(categorized.provided.len(), count / 5);
+            assert_eq!(categorized.aur.len(), count / 5);
+            assert_eq!(
+                categorized.unknown,
+                names[4..].iter().step_by(5).cloned().collect::<Vec<_>>()
+            );
+        }
+
+        // Lookups stop once every name is placed
+        let sources = CountingSources::default();
+        let categorized = categorize_with(&sources, &["repo-a".to_string()]).unwrap();
+        assert_eq!(sources.calls.get(), 1);
+        assert_eq!(
+            categorized.install_sets(),
+            (vec!["repo-a".to_string()], vec![])
+        );
     }
 
     #[test]
@@ -236,4 +818,241 @@ mod tests {
         assert!(result.is_ok());
         // This might be true or false depending on the system, but shouldn't error
     }
+
+    #[test]
+    fn test_satisfy_with_provides() {
+        let config = Config::parse("@packages\njdk-openjdk\npython :exact\nhtop\n").unwrap();
+        let install = |name: &str| PackageAction::Install {
+            name: name.to_string(),
+        };
+        let provides = BTreeMap::from([
+            (
+                "jdk21-temurin".to_string(),
+                vec![
+                    "java-environment=21".to_string(),
+                    "jdk-openjdk=21.0.4".to_string(),
+                ],
+            ),
+            ("pypy3".to_string(), vec!["python".to_string()]),
+        ]);
+        let actions = vec![
+            install("jdk-openjdk"),
+            install("python"),
+            install("htop"),
+            PackageAction::Remove {
+                name: "jdk21-temurin".to_string(),
+            },
+            PackageAction::Remove {
+                name: "old".to_string(),
+            },
+        ];
+
+        let (kept, satisfied) = satisfy_with_provides(actions, &config, &provides);
+        assert_eq!(
+            satisfied,
+            vec![Satisfied {
+                name: "jdk-openjdk".to_string(),
+                by: "jdk21-temurin".to_string(),
+            }]
+        );
+        assert_eq!(
+            kept,
+            vec![
+                install("python"),
+                install("htop"),
+                PackageAction::Remove {
+                    name: "old".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_satisfy_with_provides_keeps_every_provider() {
+        let config = Config::parse(
+            "@packages
+java-runtime
+sh
+vi
+",
+        )
+        .unwrap();
+        let install = |name: &str| PackageAction::Install {
+            name: name.to_string(),
+        };
+        let remove = |name: &str| PackageAction::Remove {
+            name: name.to_string(),
+        };
+        let provides = BTreeMap::from([
+            (
+                "jre21-openjdk".to_string(),
+                vec!["java-runtime=21".to_string()],
+            ),
+            (
+                "jre17-openjdk".to_string(),
+                vec!["java-runtime=17".to_string()],
+            ),
+            ("bash".to_string(), vec!["sh".to_string()]),
+            ("nvi".to_string(), vec!["vim-minimal".to_string()]),
+        ]);
+        let actions = vec![
+            install("java-runtime"),
+            install("sh"),
+            install("vi"),
+            remove("jre17-openjdk"),
+            remove("jre21-openjdk"),
+            remove("bash"),
+            remove("nvi"),
+        ];
+
+        let (kept, satisfied) = satisfy_with_provides(actions, &config, &provides);
+        // Versioned and exact provides both count; the first provider by name is named
+        assert_eq!(
+            satisfied,
+            vec![
+                Satisfied {
+                    name: "java-runtime".to_string(),
+                    by: "jre17-openjdk".to_string(),
+                },
+                Satisfied {
+                    name: "sh".to_string(),
+                    by: "bash".to_string(),
+                },
+            ]
+        );
+        // Neither provider of java-runtime is removed, only what provides nothing declared
+        assert_eq!(kept, vec![install("vi"), remove("nvi")]);
+    }
+
+    #[test]
+    fn test_dependency_closure_follows_levels_once() {
+        let deps: HashMap<&str, &[&str]> = HashMap::from([
+            ("htop", &["ncurses", "libnl>=3"][..]),
+            ("ncurses", &["glibc"][..]),
+            ("libnl", &["glibc"][..]),
+            ("glibc", &[][..]),
+            ("git", &["htop"][..]),
+        ]);
+        let mut calls = Vec::new();
+        let closure = dependency_closure_with(&["git".to_string()], |level| {
+            calls.push(level.len());
+            Ok(level
+                .iter()
+                .filter_map(|name| deps.get(name.as_str()).map(|list| (name, list)))
+                .map(|(name, list)| {
+                    let info = PackageInfo {
+                        name: name.clone(),
+                        depends_on: list.iter().map(|dep| dep.to_string()).collect(),
+                        ..PackageInfo::default()
+                    };
+                    (name.clone(), info)
+                })
+                .collect())
+        })
+        .unwrap();
+        let mut closure: Vec<String> = closure.into_iter().collect();
+        closure.sort();
+        assert_eq!(closure, ["glibc", "htop", "libnl", "ncurses"]);
+        // git, htop, ncurses + libnl, glibc
+        assert_eq!(calls, [1, 1, 2, 1]);
+        assert_eq!(dependency_name("glibc>=2.40"), "glibc");
+        assert_eq!(dependency_name("libncursesw.so=6-64"), "libncursesw.so");
+    }
+
+    #[test]
+    fn test_remove_orphans_removes_what_was_confirmed() {
+        struct FakePm {
+            orphans: Vec<String>,
+            removed: std::cell::RefCell<Vec<String>>,
+        }
+        impl OrphanRemoval for FakePm {
+            fn orphans(&self) -> Result<Vec<String>> {
+                Ok(self.orphans.clone())
+            }
+            fn remove(&self, packages: &[String]) -> Result<()> {
+                self.removed.borrow_mut().extend_from_slice(packages);
+                Ok(())
+            }
+        }
+        let pm = FakePm {
+            orphans: ["libfoo", "systemd", "python-bar"]
+                .map(String::from)
+                .to_vec(),
+            removed: Default::default(),
+        };
+        let config = Config::new();
+
+        let removed = remove_orphans_with(&pm, &config, |orphans| {
+            assert_eq!(orphans, ["libfoo", "python-bar"]);
+            Ok(vec!["libfoo".to_string()])
+        })
+        .unwrap();
+        assert_eq!(removed, ["libfoo"]);
+        assert_eq!(*pm.removed.borrow(), ["libfoo"]);
+
+        // Declining removes nothing
+        assert!(
+            remove_orphans_with(&pm, &config, |_| Ok(Vec::new()))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(pm.removed.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_split_protected_keeps_installs() {
+        let config = Config::parse("@protected\ngrub\n").unwrap();
+        let action = |remove: bool, name: &str| {
+            let name = name.to_string();
+            if remove {
+                PackageAction::Remove { name }
+            } else {
+                PackageAction::Install { name }
+            }
+        };
+        let (actions, protected) = split_protected(
+            vec![
+                action(true, "nano"),
+                action(true, "linux"),
+                action(true, "grub"),
+                action(false, "base"),
+            ],
+            &config,
+        );
+        assert_eq!(actions, [action(true, "nano"), action(false, "base")]);
+        assert_eq!(protected, ["grub", "linux"]);
+    }
+
+    #[test]
+    fn test_required_by_managed_ignores_dependents_being_removed() {
+        let info: HashMap<String, PackageInfo> = [
+            ("libfoo", &["foo-gui", "foo-cli"][..]),
+            ("foo-cli", &["foo-gui"][..]),
+            ("nano", &[][..]),
+        ]
+        .into_iter()
+        .map(|(name, required_by)| {
+            let info = PackageInfo {
+                name: name.to_string(),
+                required_by: required_by.iter().map(ToString::to_string).collect(),
+                ..PackageInfo::default()
+            };
+            (name.to_string(), info)
+        })
+        .collect();
+        let reverse = reverse_dependencies(&info);
+        assert_eq!(reverse.keys().collect::<Vec<_>>(), ["foo-cli", "libfoo"]);
+
+        let removing = ["libfoo", "foo-cli", "nano"].map(String::from);
+        let managed = ["foo-gui", "foo-cli"].map(String::from);
+        let blocked = required_by_managed(&reverse, &removing, &managed);
+        assert_eq!(
+            blocked,
+            BTreeMap::from([
+                ("foo-cli".to_string(), vec!["foo-gui".to_string()]),
+                ("libfoo".to_string(), vec!["foo-gui".to_string()]),
+            ])
+        );
+        assert!(required_by_managed(&reverse, &removing, &[]).is_empty());
+    }
 }
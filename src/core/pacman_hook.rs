@@ -0,0 +1,219 @@
+//! A pacman hook that keeps owl's state current when packages change outside owl
+//!
+//! `owl integrate pacman-hook` installs [`HOOK_PATH`], which runs
+//! `owl state refresh --from-hook` after every transaction with the target names on
+//! stdin. The refresh reads pacman's local database directory directly instead of
+//! querying pacman or loading the config, so it adds next to nothing to `paru -Syu`.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::core::schedule::Invocation;
+use crate::core::state::PackageState;
+
+pub const HOOK_PATH: &str = "/etc/pacman.d/hooks/owl.hook";
+const MARKER: &str =
+    "# Generated by `owl integrate pacman-hook`; remove with `owl integrate --remove`";
+
+/// Quote an argument for the hook's `Exec =` line, which pacman splits like a shell
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"', '\\', '\'']) {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The hook for `invocation`, whose args are ignored in favor of the refresh
+pub fn render(invocation: &Invocation) -> String {
+    let exec = std::iter::once(invocation.exe.display().to_string())
+        .chain(invocation.globals.iter().cloned())
+        .chain(["state", "refresh", "--from-hook"].map(String::from))
+        .map(|arg| quote(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "{MARKER}
+[Trigger]
+Operation = Install
+Operation = Upgrade
+Operation = Remove
+Type = Package
+Target = *
+
+[Action]
+Description = Updating owl package state...
+When = PostTransaction
+Exec = {exec}
+NeedsTargets
+"
+    )
+}
+
+/// The content of the owl hook, when one is installed
+pub fn installed() -> Option<String> {
+    std::fs::read_to_string(HOOK_PATH)
+        .ok()
+        .filter(|content| content.starts_with(MARKER))
+}
+
+/// Package names from the hook's stdin, one per line
+pub fn parse_targets(input: &str) -> Vec<String> {
+    let mut targets: Vec<String> = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+/// Installed versions of `names`, from the entry names in pacman's `local/` directory.
+/// Entries are `<name>-<pkgver>-<pkgrel>` and neither version part contains a `-`,
+/// which is what tells `foo-1.0-1` apart from `foo-bar-1.0-1` without reading `desc`.
+pub fn installed_versions(local_db: &Path, names: &[String]) -> Result<BTreeMap<String, String>> {
+    let mut versions = BTreeMap::new();
+    for entry in std::fs::read_dir(local_db)? {
+        let entry = entry?.file_name();
+        let entry = entry.to_string_lossy();
+        for name in names {
+            if let Some(version) = entry
+                .strip_prefix(name.as_str())
+                .and_then(|rest| rest.strip_prefix('-'))
+                && version.matches('-').count() == 1
+            {
+                versions.insert(name.clone(), version.to_string());
+            }
+        }
+    }
+    Ok(versions)
+}
+
+/// What a refresh changed in the state
+#[derive(Debug, Default, PartialEq)]
+pub struct Refresh {
+    /// Managed packages that are no longer installed
+    pub forgotten: Vec<String>,
+    /// Pins moved to the version now installed: name, old, new
+    pub repinned: Vec<(String, String, String)>,
+    /// Pins of packages that are no longer installed
+    pub unpinned: Vec<String>,
+}
+
+impl Refresh {
+    pub fn is_empty(&self) -> bool {
+        self.forgotten.is_empty() && self.repinned.is_empty() && self.unpinned.is_empty()
+    }
+}
+
+/// Bring the managed list and the pins in `state` in line with `installed` for the
+/// `targets` of a transaction
+pub fn refresh(
+    state: &mut PackageState,
+    targets: &[String],
+    installed: &BTreeMap<String, String>,
+) -> Refresh {
+    let mut changes = Refresh::default();
+    for target in targets {
+        match installed.get(target) {
+            None => {
+                if state.is_managed(target) {
+                    state.remove_managed(target);
+                    changes.forgotten.push(target.clone());
+                }
+                if state.unpin(target).is_some() {
+                    changes.unpinned.push(target.clone());
+                }
+            }
+            Some(version) => {
+                if let Some(pinned) = state.pinned_version(target)
+                    && pinned != version
+                {
+                    changes
+                        .repinned
+                        .push((target.clone(), pinned.to_string(), version.clone()));
+                    state.pin(target.clone(), version.clone());
+                }
+            }
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_runs_the_refresh_with_targets() {
+        let invocation = Invocation {
+            exe: "/usr/bin/owl".into(),
+            globals: vec!["--config-dir".to_string(), "/home/me/my owl".to_string()],
+            args: vec!["apply".to_string()],
+        };
+        let hook = render(&invocation);
+        assert!(hook.starts_with(MARKER));
+        assert!(hook.contains(
+            "Exec = /usr/bin/owl --config-dir \"/home/me/my owl\" state refresh --from-hook\n"
+        ));
+        assert!(hook.contains("When = PostTransaction\n"));
+        assert!(hook.ends_with("NeedsTargets\n"));
+    }
+
+    #[test]
+    fn test_installed_versions_reads_entry_names() {
+        let db = tempfile::tempdir().unwrap();
+        for entry in ["foo-1.0-1", "foo-bar-2:3.1-2", "htop-3.3.0-1"] {
+            std::fs::create_dir(db.path().join(entry)).unwrap();
+        }
+        let names = parse_targets("foo\nfoo-bar\n\nvim\nfoo\n");
+        assert_eq!(names, ["foo", "foo-bar", "vim"]);
+        let versions = installed_versions(db.path(), &names).unwrap();
+        assert_eq!(
+            versions,
+            BTreeMap::from([
+                ("foo".to_string(), "1.0-1".to_string()),
+                ("foo-bar".to_string(), "2:3.1-2".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_refresh_forgets_removed_and_follows_upgraded_pins() {
+        let mut state = PackageState {
+            untracked: Vec::new(),
+            hidden: Vec::new(),
+            managed: vec!["htop".to_string(), "vim".to_string()],
+            pinned: BTreeMap::from([
+                ("firefox".to_string(), "128.0-1".to_string()),
+                ("vim".to_string(), "9.1-1".to_string()),
+            ]),
+            sources: BTreeMap::new(),
+            remove_hooks: BTreeMap::new(),
+        };
+        let targets = ["firefox", "htop", "vim", "zsh"].map(String::from);
+        let installed = BTreeMap::from([
+            ("firefox".to_string(), "129.0-1".to_string()),
+            ("htop".to_string(), "3.3.0-1".to_string()),
+            ("zsh".to_string(), "5.9-5".to_string()),
+        ]);
+        let changes = refresh(&mut state, &targets, &installed);
+        assert_eq!(
+            changes,
+            Refresh {
+                forgotten: vec!["vim".to_string()],
+                repinned: vec![(
+                    "firefox".to_string(),
+                    "128.0-1".to_string(),
+                    "129.0-1".to_string()
+                )],
+                unpinned: vec!["vim".to_string()],
+            }
+        );
+        assert_eq!(state.managed, ["htop"]);
+        assert_eq!(state.pinned_version("firefox"), Some("129.0-1"));
+        assert!(refresh(&mut state, &targets, &installed).is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+//! Read install history from pacman's log
+
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+
+const PACMAN_LOG: &str = "/var/log/pacman.log";
+
+/// The pacman transaction that last installed a package
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstallOrigin {
+    /// Date part of the log timestamp, e.g. `2024-05-01`
+    pub date: String,
+    /// The command that started the transaction, e.g. `pacman -S firefox`
+    pub command: String,
+}
+
+/// Where each of `packages` was last installed from, for the packages the log mentions
+pub fn install_origins(packages: &[String]) -> Result<HashMap<String, InstallOrigin>> {
+    let content = std::fs::read_to_string(PACMAN_LOG)
+        .with_context(|| format!("Failed to read {}", PACMAN_LOG))?;
+    let wanted: HashSet<&str> = packages.iter().map(String::as_str).collect();
+    Ok(parse_install_origins(&content, &wanted))
+}
+
+fn parse_install_origins(log: &str, wanted: &HashSet<&str>) -> HashMap<String, InstallOrigin> {
+    let mut origins = HashMap::new();
+    let mut command: Option<&str> = None;
+    for line in log.lines() {
+        // `[2024-05-01T10:00:00+0200] [ALPM] installed m4 (1.4.19-3)`
+        let Some((timestamp, rest)) = line
+            .strip_prefix('[')
+            .and_then(|line| line.split_once("] "))
+        else {
+            continue;
+        };
+        if let Some(running) = rest
+            .strip_prefix("[PACMAN] Running '")
+            .and_then(|running| running.strip_suffix('\''))
+        {
+            command = Some(running);
+        } else if let Some(installed) = rest.strip_prefix("[ALPM] installed ")
+            && let Some((name, _)) = installed.split_once(' ')
+            && wanted.contains(name)
+        {
+            origins.insert(
+                name.to_string(),
+                InstallOrigin {
+                    date: timestamp.split('T').next().unwrap_or(timestamp).to_string(),
+                    command: command.unwrap_or("unknown command").to_string(),
+                },
+            );
+        }
+    }
+    origins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_install_origins_uses_the_last_install() {
+        let log = "\
+[2024-05-01T10:00:00+0200] [PACMAN] Running 'pacman -S firefox'
+[2024-05-01T10:00:01+0200] [ALPM] transaction started
+[2024-05-01T10:00:02+0200] [ALPM] installed libfoo (1.0-1)
+[2024-05-01T10:00:02+0200] [ALPM] installed firefox (125.0-1)
+[2024-06-02T09:00:00+0200] [PACMAN] Running 'pacman -Rns libfoo'
+[2024-06-02T09:00:01+0200] [ALPM] removed libfoo (1.0-1)
+[2024-06-03T09:00:00+0200] [PACMAN] Running 'pacman -S --asdeps libfoo'
+[2024-06-03T09:00:01+0200] [ALPM] installed libfoo (1.1-1)
+";
+        let wanted: HashSet<&str> = ["libfoo", "missing"].into_iter().collect();
+        let origins = parse_install_origins(log, &wanted);
+        assert_eq!(origins.len(), 1);
+        assert_eq!(
+            origins["libfoo"],
+            InstallOrigin {
+                date: "2024-06-03".to_string(),
+                command: "pacman -S --asdeps libfoo".to_string(),
+            }
+        );
+    }
+}
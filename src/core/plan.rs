@@ -0,0 +1,240 @@
+//! Read-only view of what an apply would change
+//!
+//! Building a plan never touches the system or the state files, so it is safe to
+//! call from dashboards and status checks as often as needed.
+
+use anyhow::{Context, Result};
+
+use crate::core::config::Config;
+use crate::core::dotfiles::{DotfileAction, DotfileStatus};
+use crate::core::package::PackageAction;
+use crate::core::services::ServiceStatus;
+use crate::core::state::PackageState;
+use crate::core::system::SystemSnapshot;
+
+/// Which of the slower checks to run while planning
+#[derive(Debug, Clone)]
+pub struct PlanOptions {
+    /// Ask the AUR helper for outdated AUR packages (network access)
+    pub check_aur_updates: bool,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            check_aur_updates: true,
+        }
+    }
+}
+
+/// Pending changes between the config and the system
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    pub installs: Vec<String>,
+    pub removals: Vec<String>,
+    pub repo_update_count: usize,
+    pub aur_updates: Vec<String>,
+    /// Pinned packages that have an update available and will be held back
+    pub held: Vec<String>,
+    /// Outdated AUR packages the config keeps out of updates (`@aur-skip`)
+    pub aur_skipped: Vec<String>,
+    pub dotfiles: Vec<DotfileAction>,
+    pub services: Vec<ServiceStatus>,
+    /// Explicitly installed packages `owl adopt` would offer; not part of being in sync
+    pub unmanaged: usize,
+}
+
+impl Plan {
+    /// Dotfiles whose destination differs from the source
+    pub fn dotfile_drift(&self) -> impl Iterator<Item = &DotfileAction> {
+        self.dotfiles
+            .iter()
+            .filter(|action| action.status != DotfileStatus::UpToDate)
+    }
+
+    /// Services that are not both enabled and running
+    pub fn services_out_of_state(&self) -> impl Iterator<Item = &ServiceStatus> {
+        self.services
+            .iter()
+            .filter(|service| !(service.enabled && service.active))
+    }
+
+    /// True when applying would change nothing
+    pub fn is_in_sync(&self) -> bool {
+        self.installs.is_empty()
+            && self.removals.is_empty()
+            && self.repo_update_count == 0
+            && self.aur_updates.is_empty()
+            && self.dotfile_drift().next().is_none()
+            && self.services_out_of_state().next().is_none()
+    }
+}
+
+/// Package actions for `config` against `system`, and the ones left out
+pub struct PackageActions {
+    pub actions: Vec<PackageAction>,
+    /// Configured packages an installed package provides, left uninstalled
+    pub satisfied: Vec<crate::core::package::Satisfied>,
+    /// Managed packages that would be removed but are protected
+    pub protected: Vec<String>,
+}
+
+/// Installs and removals, after installed providers and protected packages are
+/// taken into account
+pub fn package_actions(
+    config: &Config,
+    state: &PackageState,
+    system: &SystemSnapshot,
+) -> Result<PackageActions> {
+    let actions = crate::core::package::plan_package_actions(config, state, system)
+        .context("Failed to plan package actions")?;
+    let (actions, satisfied) = crate::core::package::resolve_provides(actions, config)
+        .context("Failed to check what installed packages provide")?;
+    let (actions, protected) = crate::core::package::split_protected(actions, config);
+    Ok(PackageActions {
+        actions,
+        satisfied,
+        protected,
+    })
+}
+
+/// Fill in the updates: the `repo_update_count` pending repo updates and, with
+/// `options.check_aur_updates`, the outdated AUR packages. Pinned ones move to `held`
+/// and those the config skips to `aur_skipped`.
+pub fn add_updates(
+    plan: &mut Plan,
+    config: &Config,
+    state: &PackageState,
+    system: &SystemSnapshot,
+    repo_update_count: usize,
+    options: &PlanOptions,
+) -> Result<()> {
+    let pinned = crate::core::package::held_packages(state, config);
+    plan.repo_update_count = repo_update_count;
+    if !pinned.is_empty() {
+        // Pins need the names, not just the count
+        let holding: Vec<String> = crate::core::pm::ParuPacman::new()
+            .repo_updates()
+            .context("Failed to list repo updates")?
+            .into_iter()
+            .map(|update| update.name)
+            .filter(|name| pinned.contains(name))
+            .collect();
+        plan.repo_update_count = repo_update_count.saturating_sub(holding.len());
+        plan.held.extend(holding);
+    }
+
+    if options.check_aur_updates && crate::core::pm::aur_helper_command().is_some() {
+        let outdated = crate::core::aur_cache::update_names(system.foreign())
+            .context("Failed to check AUR updates")?;
+        for name in outdated {
+            if pinned.contains(&name) {
+                plan.held.push(name);
+            } else if config.skips_aur_update(&name) {
+                plan.aur_skipped.push(name);
+            } else {
+                plan.aur_updates.push(name);
+            }
+        }
+    }
+    plan.held.sort();
+    Ok(())
+}
+
+/// Compute the plan for `config` against the current system
+pub fn build_plan(config: &Config, state: &PackageState, options: &PlanOptions) -> Result<Plan> {
+    let system = SystemSnapshot::capture(&crate::core::system::Backend::select())
+        .context("Failed to query installed packages")?;
+    let mut plan = Plan::default();
+    for action in package_actions(config, state, &system)?.actions {
+        match action {
+            PackageAction::Install { name } => plan.installs.push(name),
+            PackageAction::Remove { name } => plan.removals.push(name),
+        }
+    }
+    plan.installs.sort();
+    plan.removals.sort();
+
+    let repo_update_count =
+        crate::core::package::get_package_count().context("Failed to get package count")?;
+    add_updates(
+        &mut plan,
+        config,
+        state,
+        &system,
+        repo_update_count,
+        options,
+    )?;
+
+    plan.unmanaged =
+        crate::core::analysis::unmanaged_explicit(system.explicit(), state, config).len();
+
+    let mappings = crate::core::dotfiles::get_dotfile_mappings(config);
+    plan.dotfiles = crate::core::dotfiles::apply_dotfiles(&mappings, true)
+        .context("Failed to analyze dotfiles")?;
+
+    plan.services = crate::core::services::get_configured_services(config)
+        .iter()
+        .map(|service| crate::core::services::query_service_status(service))
+        .collect();
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dotfiles::DotfileMapping;
+
+    fn dotfile(status: DotfileStatus) -> DotfileAction {
+        DotfileAction {
+            mapping: DotfileMapping {
+                source: "nvim".to_string(),
+                destination: "~/.config/nvim".to_string(),
+            },
+            status,
+        }
+    }
+
+    #[test]
+    fn test_plan_in_sync_ignores_up_to_date_items() {
+        let mut plan = Plan {
+            dotfiles: vec![dotfile(DotfileStatus::UpToDate)],
+            services: vec![ServiceStatus {
+                name: "sshd".to_string(),
+                enabled: true,
+                active: true,
+            }],
+            ..Plan::default()
+        };
+        assert!(plan.is_in_sync());
+
+        plan.dotfiles.push(dotfile(DotfileStatus::Update));
+        assert!(!plan.is_in_sync());
+        assert_eq!(plan.dotfile_drift().count(), 1);
+    }
+
+    #[test]
+    fn test_plan_services_out_of_state() {
+        let plan = Plan {
+            services: vec![
+                ServiceStatus {
+                    name: "sshd".to_string(),
+                    enabled: true,
+                    active: false,
+                },
+                ServiceStatus {
+                    name: "cronie".to_string(),
+                    enabled: true,
+                    active: true,
+                },
+            ],
+            ..Plan::default()
+        };
+        let names: Vec<&str> = plan
+            .services_out_of_state()
+            .map(|service| service.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["sshd"]);
+        assert!(!plan.is_in_sync());
+    }
+}
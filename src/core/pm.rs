@@ -1,11 +1,17 @@
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap};
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::AtomicU64;
 use std::sync::{Mutex, OnceLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use crate::internal::logging::LogCommand;
 
 pub use super::search::{PackageSource, SearchResult};
 
@@ -49,8 +55,238 @@ where
     Err(last_error.unwrap_or_else(|| anyhow!("Unknown error")))
 }
 
+/// Package manager failures that carry enough detail to explain them to the user
+#[derive(Debug)]
+pub enum PmError {
+    MissingAurHelper,
+    CommandFailed {
+        action: String,
+        stderr: String,
+    },
+    /// pacman's `db.lck` exists; stale when none of `holders` is running
+    DatabaseLocked {
+        path: PathBuf,
+        /// Running package managers, e.g. `pacman (pid 4242)`
+        holders: Vec<String>,
+        /// Seconds waited before giving up
+        waited: u64,
+    },
+}
+
+impl PmError {
+    /// A lock left behind by a package manager that is no longer running
+    pub fn is_stale_lock(&self) -> bool {
+        matches!(self, PmError::DatabaseLocked { holders, .. } if holders.is_empty())
+    }
+}
+
+impl std::fmt::Display for PmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PmError::MissingAurHelper => write!(
+                f,
+                "No AUR helper found. Install either 'paru' or 'yay' to manage AUR packages."
+            ),
+            PmError::CommandFailed { action, stderr } => {
+                write!(f, "{} failed: {}", action, stderr.trim())
+            }
+            PmError::DatabaseLocked { path, holders, .. } if holders.is_empty() => write!(
+                f,
+                "pacman's database is locked ({}) but no package manager is running",
+                path.display()
+            ),
+            PmError::DatabaseLocked {
+                holders, waited, ..
+            } => {
+                write!(f, "pacman's database is locked by {}", holders.join(", "))?;
+                if *waited > 0 {
+                    write!(f, " (waited {}s)", waited)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PmError {}
+
+/// pacman's lock file, present while a transaction runs
+const DB_LOCK_FILE: &str = "db.lck";
+
+/// Processes that take pacman's database lock, by their `/proc/<pid>/comm` name
+const LOCKING_PROCESSES: &[&str] = &["pacman", "paru", "yay", "pamac", "packagekitd"];
+
+const DB_LOCK_POLL_MS: u64 = 500;
+
+static DB_LOCK_WAIT_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// How long package operations wait for another package manager to release the
+/// database before failing (`--db-lock-wait`); 0, the default, fails right away
+pub fn set_db_lock_wait(secs: u64) {
+    DB_LOCK_WAIT_SECS.store(secs, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Make sure pacman's database is unlocked before changing packages, waiting up to
+/// the `--db-lock-wait` timeout while another package manager holds it. A lock no
+/// running process explains is reported as stale and left in place.
+pub fn wait_for_database() -> Result<()> {
+    wait_for_unlock(
+        &Path::new(crate::internal::constants::PACMAN_DB_DIR).join(DB_LOCK_FILE),
+        running_package_managers,
+        Duration::from_secs(DB_LOCK_WAIT_SECS.load(std::sync::atomic::Ordering::Relaxed)),
+        Duration::from_millis(DB_LOCK_POLL_MS),
+    )
+}
+
+fn wait_for_unlock(
+    path: &Path,
+    holders: impl Fn() -> Vec<String>,
+    wait: Duration,
+    poll: Duration,
+) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let locked = |holders: Vec<String>, waited: Duration| PmError::DatabaseLocked {
+        path: path.to_path_buf(),
+        holders,
+        waited: waited.as_secs(),
+    };
+    let running = holders();
+    if running.is_empty() || wait.is_zero() {
+        return Err(locked(running, Duration::ZERO).into());
+    }
+
+    let start = Instant::now();
+    let message = format!(
+        "Waiting up to {}s for {} to release pacman's database",
+        wait.as_secs(),
+        running.join(", ")
+    );
+    let mut frame = 0;
+    while path.exists() {
+        let running = holders();
+        if running.is_empty() || start.elapsed() >= wait {
+            crate::internal::util::spinner::clear_line();
+            return Err(locked(running, start.elapsed()).into());
+        }
+        crate::internal::util::spinner::print_frame(&message, frame);
+        frame += 1;
+        thread::sleep(poll);
+    }
+    crate::internal::util::spinner::clear_line();
+    Ok(())
+}
+
+/// Running processes that may hold pacman's lock, e.g. `paru (pid 4242)`
+fn running_package_managers() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut running: Vec<(u32, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let comm = std::fs::read_to_string(entry.path().join("comm")).ok()?;
+            let name = comm.trim();
+            LOCKING_PROCESSES
+                .contains(&name)
+                .then(|| (pid, name.to_string()))
+        })
+        .collect();
+    running.sort();
+    running
+        .into_iter()
+        .map(|(pid, name)| format!("{} (pid {})", name, pid))
+        .collect()
+}
+
+/// Package metadata, as reported by `pacman -Qi` (installed) or `pacman -Si` (sync db)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    /// Installed size in bytes
+    pub installed_size: u64,
+    pub depends_on: Vec<String>,
+    /// Names the package also answers to, possibly versioned, e.g. `java-environment=21`
+    pub provides: Vec<String>,
+    /// Package groups it belongs to, e.g. `base-devel`
+    pub groups: Vec<String>,
+    /// Names from "Optional Deps", without their descriptions
+    pub optional_deps: Vec<String>,
+    pub required_by: Vec<String>,
+    /// Installed explicitly rather than as a dependency
+    pub explicit: bool,
+    /// As printed by pacman; empty for sync db entries
+    pub install_date: String,
+    /// Sync db the package comes from; empty for `-Qi`
+    pub repository: String,
+    pub packager: String,
+}
+
+/// Names the sync dbs know, by what they name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncNames {
+    pub packages: HashSet<String>,
+    pub groups: HashSet<String>,
+    /// Names some package provides, versions dropped
+    pub provided: HashSet<String>,
+}
+
+impl SyncNames {
+    /// Everything the `pacman -Si` entries in `infos` name
+    fn from_info(infos: Vec<PackageInfo>) -> Self {
+        let mut names = Self::default();
+        for info in infos {
+            names.groups.extend(info.groups);
+            names.provided.extend(
+                info.provides
+                    .iter()
+                    .map(|entry| crate::core::package::dependency_name(entry).to_string()),
+            );
+            names.packages.insert(info.name);
+        }
+        names
+    }
+
+    /// The part of these names that `names` asks about
+    fn among(&self, names: &[String]) -> Self {
+        let among = |known: &HashSet<String>| -> HashSet<String> {
+            names
+                .iter()
+                .filter(|name| known.contains(*name))
+                .cloned()
+                .collect()
+        };
+        Self {
+            packages: among(&self.packages),
+            groups: among(&self.groups),
+            provided: among(&self.provided),
+        }
+    }
+}
+
+/// An installed package with a newer version available
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingUpdate {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+}
+
 pub struct ParuPacman;
 
+/// `--ignore a,b` for pacman and the AUR helpers, or nothing when no package is held
+fn ignore_args(held: &[String]) -> Vec<String> {
+    if held.is_empty() {
+        Vec::new()
+    } else {
+        vec!["--ignore".to_string(), held.join(",")]
+    }
+}
+
 impl ParuPacman {
     pub fn new() -> Self {
         Self
@@ -59,48 +295,221 @@ impl ParuPacman {
     pub fn list_installed(&self) -> Result<HashSet<String>> {
         let output = Command::new("pacman")
             .arg("-Qq")
+            .logged()
             .output()
             .map_err(|e| anyhow!("Failed to get installed packages: {}", e))?;
 
         if !output.status.success() {
-            return Err(anyhow!(
-                "Package manager failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            return Err(PmError::CommandFailed {
+                action: "Listing installed packages".to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+            .into());
+        }
+
+        Ok(parse_names(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Installed packages with their versions (`pacman -Q`)
+    pub fn installed_versions(&self) -> Result<BTreeMap<String, String>> {
+        let output = Command::new("pacman")
+            .arg("-Q")
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("Failed to get installed packages: {}", e))?;
+
+        if !output.status.success() {
+            return Err(PmError::CommandFailed {
+                action: "Listing installed packages".to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+            .into());
+        }
+
+        Ok(parse_versions(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Explicitly installed packages (`pacman -Qeq`)
+    pub fn explicit_packages(&self) -> Result<HashSet<String>> {
+        let output = Command::new("pacman")
+            .arg("-Qeq")
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("Failed to query explicit packages: {}", e))?;
+
+        if !output.status.success() {
+            return Err(PmError::CommandFailed {
+                action: "Listing explicit packages".to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+            .into());
         }
 
         Ok(String::from_utf8_lossy(&output.stdout)
             .lines()
             .map(str::trim)
-            .filter(|name| !name.is_empty())
+            .filter(|line| !line.is_empty())
             .map(ToOwned::to_owned)
             .collect())
     }
 
-    pub fn batch_repo_available(&self, packages: &[String]) -> Result<HashSet<String>> {
+    /// Query metadata for installed packages in one `pacman -Qi` call.
+    /// Packages that are not installed are simply absent from the result.
+    pub fn package_info(&self, packages: &[String]) -> Result<HashMap<String, PackageInfo>> {
         if packages.is_empty() {
-            return Ok(HashSet::new());
+            return Ok(HashMap::new());
         }
 
+        // pacman exits non-zero when some names are not installed but still
+        // prints the packages it found, so only a failed spawn is an error
         let output = Command::new("pacman")
+            .env("LC_ALL", "C")
+            .arg("-Qi")
+            .args(packages)
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("Failed to query package info: {}", e))?;
+
+        Ok(parse_package_info(&String::from_utf8_lossy(&output.stdout))
+            .into_iter()
+            .map(|info| (info.name.clone(), info))
+            .collect())
+    }
+
+    /// What each installed package provides besides its own name, from one `pacman -Qi`
+    /// call over the whole database; packages that provide nothing are left out
+    pub fn installed_provides(&self) -> Result<BTreeMap<String, Vec<String>>> {
+        let output = Command::new("pacman")
+            .env("LC_ALL", "C")
+            .arg("-Qi")
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("Failed to query package provides: {}", e))?;
+
+        if !output.status.success() {
+            return Err(PmError::CommandFailed {
+                action: "Listing package provides".to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+            .into());
+        }
+
+        Ok(parse_package_info(&String::from_utf8_lossy(&output.stdout))
+            .into_iter()
+            .filter(|info| !info.provides.is_empty())
+            .map(|info| (info.name, info.provides))
+            .collect())
+    }
+
+    /// Sync db metadata for packages available in the repos, via `pacman -Si`
+    pub fn repo_package_info(&self, packages: &[String]) -> Result<HashMap<String, PackageInfo>> {
+        if packages.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let output = Command::new("pacman")
+            .env("LC_ALL", "C")
             .arg("-Si")
             .args(packages)
+            .logged()
             .output()
-            .map_err(|e| anyhow!("Failed to check package info: {}", e))?;
+            .map_err(|e| anyhow!("Failed to query repo package info: {}", e))?;
 
-        Ok(String::from_utf8_lossy(&output.stdout)
-            .lines()
-            .filter_map(|line| line.strip_prefix("Name"))
-            .filter_map(|rest| rest.split_once(':'))
-            .map(|(_, value)| value.trim())
-            .filter(|value| !value.is_empty())
-            .map(ToOwned::to_owned)
+        Ok(parse_package_info(&String::from_utf8_lossy(&output.stdout))
+            .into_iter()
+            .map(|info| (info.name.clone(), info))
+            .collect())
+    }
+
+    /// The names among `packages` that a sync repo has, from the same listing as
+    /// [`batch_sync_lookup`](Self::batch_sync_lookup)
+    pub fn batch_repo_available(&self, packages: &[String]) -> Result<HashSet<String>> {
+        Ok(self.batch_sync_lookup(packages)?.packages)
+    }
+
+    /// The names among `names` that the sync dbs have as packages, as groups, and as
+    /// provides, checked in memory against one `pacman -Si` listing of every sync db,
+    /// taken once per run. `pacman -S` installs a provided name through its provider.
+    pub fn batch_sync_lookup(&self, names: &[String]) -> Result<SyncNames> {
+        if names.is_empty() {
+            return Ok(SyncNames::default());
+        }
+
+        let mut cache = SYNC_NAMES_CACHE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let sync = match cache.as_ref() {
+            Some(sync) => sync,
+            None => cache.insert(self.sync_names()?),
+        };
+        Ok(sync.among(names))
+    }
+
+    fn sync_names(&self) -> Result<SyncNames> {
+        let output = Command::new("pacman")
+            .env("LC_ALL", "C")
+            .arg("-Si")
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("Failed to query the sync databases: {}", e))?;
+
+        if !output.status.success() {
+            return Err(PmError::CommandFailed {
+                action: "Listing the sync databases".to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+            .into());
+        }
+
+        Ok(SyncNames::from_info(parse_package_info(
+            &String::from_utf8_lossy(&output.stdout),
+        )))
+    }
+
+    /// The members of each group among `names`, in one `pacman -Sg` call for the names
+    /// not looked up yet this run. Names that are no group are left out.
+    pub fn group_members(&self, names: &[String]) -> Result<crate::core::package::GroupMembers> {
+        let groups = GROUP_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+        let members = GROUP_PACKAGES_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+        let unknown: Vec<String> = {
+            let groups = groups.lock().unwrap();
+            names
+                .iter()
+                .filter(|name| !groups.contains_key(*name))
+                .cloned()
+                .collect()
+        };
+
+        if !unknown.is_empty() {
+            // Exits non-zero when any name is not a group, but still lists the others
+            let output = Command::new("pacman")
+                .arg("-Sg")
+                .args(&unknown)
+                .logged()
+                .output()
+                .map_err(|e| anyhow!("Failed to check package groups: {}", e))?;
+            let found = parse_group_members(&String::from_utf8_lossy(&output.stdout));
+            let mut groups = groups.lock().unwrap();
+            let mut members = members.lock().unwrap();
+            for name in unknown {
+                groups.insert(name.clone(), found.contains_key(&name));
+                if let Some(packages) = found.get(&name) {
+                    members.insert(name, packages.clone());
+                }
+            }
+        }
+
+        let members = members.lock().unwrap();
+        Ok(names
+            .iter()
+            .filter_map(|name| Some((name.clone(), members.get(name)?.clone())))
             .collect())
     }
 
     pub fn upgrade_count(&self) -> Result<usize> {
         let output = Command::new("pacman")
             .args(["-Qu", "-q"])
+            .logged()
             .output()
             .map_err(|e| anyhow!("Failed to run pacman -Qu: {}", e))?;
 
@@ -116,29 +525,163 @@ impl ParuPacman {
         }
     }
 
-    pub fn get_aur_updates(&self) -> Result<Vec<String>> {
+    /// Packages installed as dependencies that nothing depends on any more (`pacman -Qdtq`)
+    pub fn orphans(&self) -> Result<Vec<String>> {
+        let output = Command::new("pacman")
+            .args(["-Qdtq"])
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("Failed to run pacman -Qdtq: {}", e))?;
+
+        if output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.code() == Some(1) && stderr.trim().is_empty() {
+            Ok(Vec::new())
+        } else {
+            Err(anyhow!("pacman -Qdtq failed: {}", stderr))
+        }
+    }
+
+    /// Repo packages with pending updates, with their old and new versions
+    pub fn repo_updates(&self) -> Result<Vec<PendingUpdate>> {
+        let output = Command::new("pacman")
+            .arg("-Qu")
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("Failed to run pacman -Qu: {}", e))?;
+
+        if output.status.success() {
+            return Ok(parse_pending_updates(&String::from_utf8_lossy(
+                &output.stdout,
+            )));
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.code() == Some(1) && stderr.trim().is_empty() {
+            Ok(Vec::new())
+        } else {
+            Err(anyhow!("pacman -Qu failed: {}", stderr))
+        }
+    }
+
+    /// Repo updates against freshly synced databases, without root and without touching
+    /// the system databases: `checkupdates` when pacman-contrib is installed, otherwise
+    /// the same trick by hand with fakeroot and a private `--dbpath`. Without either the
+    /// current databases are used and the second value is false.
+    pub fn synced_repo_updates(&self) -> Result<(Vec<PendingUpdate>, bool)> {
+        if command_exists("checkupdates") {
+            let output = Command::new("checkupdates")
+                .logged()
+                .output()
+                .map_err(|e| anyhow!("Failed to run checkupdates: {}", e))?;
+            // checkupdates exits 2 when there is nothing to update
+            return match output.status.code() {
+                Some(0) => Ok((
+                    parse_pending_updates(&String::from_utf8_lossy(&output.stdout)),
+                    true,
+                )),
+                Some(2) => Ok((Vec::new(), true)),
+                _ => Err(anyhow!(
+                    "checkupdates failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )),
+            };
+        }
+        if !command_exists("fakeroot") {
+            log::debug!("neither checkupdates nor fakeroot found; using the current databases");
+            return Ok((self.repo_updates()?, false));
+        }
+
+        let db = private_db_dir()?;
+        let status = Command::new("fakeroot")
+            .args(["--", "pacman", "-Sy", "--logfile", "/dev/null", "--dbpath"])
+            .arg(&db)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .status()
+            .map_err(|e| anyhow!("Failed to run fakeroot pacman -Sy: {}", e))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "syncing a private copy of the package databases failed"
+            ));
+        }
+        let output = Command::new("pacman")
+            .arg("-Qu")
+            .arg("--dbpath")
+            .arg(&db)
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("Failed to run pacman -Qu: {}", e))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success() || (output.status.code() == Some(1) && stderr.trim().is_empty())
+        {
+            Ok((
+                parse_pending_updates(&String::from_utf8_lossy(&output.stdout)),
+                true,
+            ))
+        } else {
+            Err(anyhow!("pacman -Qu failed: {}", stderr))
+        }
+    }
+
+    /// Installed packages that are in no sync repo (`pacman -Qm`), name to version
+    pub fn foreign_versions(&self) -> Result<BTreeMap<String, String>> {
+        let output = Command::new("pacman")
+            .arg("-Qm")
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("Failed to run pacman -Qm: {}", e))?;
+        if output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| line.split_once(' '))
+                .map(|(name, version)| (name.to_string(), version.trim().to_string()))
+                .collect());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.code() == Some(1) && stderr.trim().is_empty() {
+            Ok(BTreeMap::new())
+        } else {
+            Err(anyhow!("pacman -Qm failed: {}", stderr))
+        }
+    }
+
+    /// AUR packages with pending updates, with their old and new versions
+    pub fn aur_updates(&self) -> Result<Vec<PendingUpdate>> {
         retry_command(
             || {
                 let aur_helper = require_aur_helper()?;
                 let output = Command::new(aur_helper)
-                    .args(["-Qua", "-q"])
+                    .arg("-Qua")
+                    .logged()
                     .output()
                     .map_err(|e| anyhow!("Failed to check AUR updates: {}", e))?;
 
                 if output.status.success() {
-                    return Ok(String::from_utf8_lossy(&output.stdout)
-                        .lines()
-                        .map(str::trim)
-                        .filter(|line| !line.is_empty())
-                        .map(|line| line.split_whitespace().next().unwrap_or(line).to_string())
-                        .collect());
+                    return Ok(parse_pending_updates(&String::from_utf8_lossy(
+                        &output.stdout,
+                    )));
                 }
 
                 let stderr = String::from_utf8_lossy(&output.stderr);
                 if output.status.code() == Some(1) && stderr.trim().is_empty() {
                     Ok(Vec::new())
                 } else {
-                    Err(anyhow!("AUR update check failed: {}", stderr))
+                    Err(PmError::CommandFailed {
+                        action: "AUR update check".to_string(),
+                        stderr: stderr.into_owned(),
+                    }
+                    .into())
                 }
             },
             3,
@@ -149,12 +692,15 @@ impl ParuPacman {
         self.install_repo_with_mode(packages, true)
     }
 
+    /// `pacman -S --needed` from the databases as last synced: never `-y`, which
+    /// without `-u` would leave a partial upgrade
     pub fn install_repo_with_mode(&self, packages: &[String], non_interactive: bool) -> Result<()> {
         if packages.is_empty() {
             return Ok(());
         }
+        wait_for_database()?;
 
-        let mut args = vec!["-S".to_string()];
+        let mut args = vec!["-S".to_string(), "--needed".to_string()];
         if non_interactive {
             args.push("--noconfirm".to_string());
         }
@@ -170,6 +716,29 @@ impl ParuPacman {
         ensure_success(outcome.status, "Repository install failed")
     }
 
+    /// Install package files, e.g. exact versions from the pacman cache (`pacman -U`)
+    pub fn install_files_with_mode(&self, files: &[PathBuf], non_interactive: bool) -> Result<()> {
+        if files.is_empty() {
+            return Ok(());
+        }
+        wait_for_database()?;
+
+        let mut args = vec!["-U".to_string()];
+        if non_interactive {
+            args.push("--noconfirm".to_string());
+        }
+        args.extend(files.iter().map(|file| file.display().to_string()));
+
+        let outcome = run_command(
+            "pacman",
+            &args,
+            mode_from_bool(non_interactive),
+            "Installing cached package versions",
+            CaptureMode::Spinner,
+        )?;
+        ensure_success(outcome.status, "Installing cached packages failed")
+    }
+
     pub fn install_aur(&self, packages: &[String]) -> Result<()> {
         self.install_aur_with_mode(packages, true)
     }
@@ -178,9 +747,15 @@ impl ParuPacman {
         if packages.is_empty() {
             return Ok(());
         }
+        wait_for_database()?;
 
         let aur_helper = require_aur_helper()?;
-        let mut args = vec!["--aur".to_string(), "-S".to_string()];
+        // `--needed`: retrying a failed update must not rebuild what is current
+        let mut args = vec![
+            "--aur".to_string(),
+            "-S".to_string(),
+            "--needed".to_string(),
+        ];
         if non_interactive {
             args.push("--noconfirm".to_string());
             args.push("--skipreview".to_string());
@@ -208,15 +783,18 @@ impl ParuPacman {
         ensure_success(status, "AUR install failed")
     }
 
-    pub fn update_repo(&self) -> Result<()> {
-        self.update_repo_with_mode(true)
+    pub fn update_repo(&self, held: &[String]) -> Result<()> {
+        self.update_repo_with_mode(true, held)
     }
 
-    pub fn update_repo_with_mode(&self, non_interactive: bool) -> Result<()> {
+    /// `pacman -Syu`, skipping the `held` (pinned) packages
+    pub fn update_repo_with_mode(&self, non_interactive: bool, held: &[String]) -> Result<()> {
+        wait_for_database()?;
         let mut args = vec!["-Syu".to_string()];
         if non_interactive {
             args.push("--noconfirm".to_string());
         }
+        args.extend(ignore_args(held));
 
         let outcome = run_command(
             "pacman",
@@ -233,38 +811,54 @@ impl ParuPacman {
             );
             Ok(())
         } else {
-            Err(anyhow!(
-                "Repository update failed (exit code: {:?})",
-                outcome.status.code()
-            ))
+            match outcome.stderr.filter(|stderr| !stderr.trim().is_empty()) {
+                Some(stderr) => Err(PmError::CommandFailed {
+                    action: "Repository update".to_string(),
+                    stderr,
+                }
+                .into()),
+                None => Err(anyhow!(
+                    "Repository update failed (exit code: {:?})",
+                    outcome.status.code()
+                )),
+            }
         }
     }
 
-    pub fn update_aur(&self, packages: &[String]) -> Result<()> {
-        self.update_aur_with_mode(packages, true)
+    pub fn update_aur(&self, packages: &[String], held: &[String]) -> Result<()> {
+        self.update_aur_with_mode(packages, true, held)
     }
 
-    pub fn update_aur_with_mode(&self, packages: &[String], non_interactive: bool) -> Result<()> {
+    /// Update the given AUR packages, telling the helper to skip the `held` (pinned) ones
+    pub fn update_aur_with_mode(
+        &self,
+        packages: &[String],
+        non_interactive: bool,
+        held: &[String],
+    ) -> Result<()> {
         if packages.is_empty() {
             return Ok(());
         }
+        wait_for_database()?;
 
         let aur_helper = require_aur_helper()?;
         let mut args = vec!["--aur".to_string(), "-Syu".to_string()];
         if non_interactive {
             args.push("--noconfirm".to_string());
         }
+        args.extend(ignore_args(held));
         args.extend(packages.iter().cloned());
 
         if non_interactive {
             let arg_refs: Vec<&str> = args.iter().map(String::as_str).collect();
             let result = retry_command(
                 || {
-                    let (status, stderr) = crate::internal::util::execute_command_with_stderr_capture(
-                        aur_helper,
-                        &arg_refs,
-                        "Updating AUR packages",
-                    )?;
+                    let (status, stderr) =
+                        crate::internal::util::execute_command_with_stderr_capture(
+                            aur_helper,
+                            &arg_refs,
+                            "Updating AUR packages",
+                        )?;
 
                     if status.success() {
                         Ok(())
@@ -285,14 +879,14 @@ impl ParuPacman {
                 }
                 Err(err) => {
                     let detail = err.to_string();
-                    if let Some((_, stderr)) = detail.split_once(": ") {
-                        if !stderr.trim().is_empty() {
-                            stderr
-                                .lines()
-                                .rev()
-                                .take(30)
-                                .for_each(|line| eprintln!("  {}", line));
-                        }
+                    if let Some((_, stderr)) = detail.split_once(": ")
+                        && !stderr.trim().is_empty()
+                    {
+                        stderr
+                            .lines()
+                            .rev()
+                            .take(30)
+                            .for_each(|line| eprintln!("  {}", line));
                     }
                     Err(anyhow!("AUR package update failed"))
                 }
@@ -321,6 +915,7 @@ impl ParuPacman {
         if packages.is_empty() {
             return Ok(());
         }
+        wait_for_database()?;
 
         let mut cmd = Command::new("pacman");
         cmd.arg("-Rns");
@@ -330,6 +925,7 @@ impl ParuPacman {
         cmd.args(packages);
 
         let status = cmd
+            .logged()
             .status()
             .map_err(|e| anyhow!("Failed to remove packages: {}", e))?;
 
@@ -345,74 +941,49 @@ impl ParuPacman {
         }
     }
 
+    /// Mark installed packages as explicitly installed (`pacman -D --asexplicit`)
+    pub fn mark_explicit(&self, packages: &[String]) -> Result<()> {
+        if packages.is_empty() {
+            return Ok(());
+        }
+        wait_for_database()?;
+
+        let mut args = vec![
+            "pacman".to_string(),
+            "-D".to_string(),
+            "--asexplicit".to_string(),
+        ];
+        args.extend(packages.iter().cloned());
+        let outcome = run_command(
+            "sudo",
+            &args,
+            CommandMode::Interactive,
+            "Marking packages as explicitly installed",
+            CaptureMode::Spinner,
+        )?;
+        ensure_success(outcome.status, "Marking packages as explicit failed")
+    }
+
     pub fn search_packages(&self, terms: &[String]) -> Result<Vec<SearchResult>> {
         super::search::search_packages(terms)
     }
 
     pub fn is_package_group(&self, package_name: &str) -> Result<bool> {
-        let cache = GROUP_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
-
-        {
-            let cache_guard = cache.lock().unwrap();
-            if let Some(&is_group) = cache_guard.get(package_name) {
-                return Ok(is_group);
-            }
-        }
-
-        let output = Command::new("pacman")
-            .args(["-Sg", package_name])
-            .output()
-            .map_err(|e| anyhow!("Failed to check if {} is a group: {}", package_name, e))?;
-
-        let is_group =
-            output.status.success() && !String::from_utf8_lossy(&output.stdout).trim().is_empty();
-
-        {
-            let mut cache_guard = cache.lock().unwrap();
-            cache_guard.insert(package_name.to_string(), is_group);
-        }
-
-        Ok(is_group)
+        Ok(self
+            .group_members(&[package_name.to_string()])?
+            .contains_key(package_name))
     }
 
     pub fn get_group_packages(&self, group_name: &str) -> Result<Vec<String>> {
-        let cache = GROUP_PACKAGES_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
-
-        {
-            let cache_guard = cache.lock().unwrap();
-            if let Some(packages) = cache_guard.get(group_name) {
-                return Ok(packages.clone());
-            }
-        }
-
-        let output = Command::new("pacman")
-            .args(["-Sg", group_name])
-            .output()
-            .map_err(|e| anyhow!("Failed to get packages for group {}: {}", group_name, e))?;
-
-        if !output.status.success() {
-            return Err(anyhow!("Failed to get packages for group {}", group_name));
-        }
-
-        let packages: Vec<String> = String::from_utf8_lossy(&output.stdout)
-            .lines()
-            .map(str::trim)
-            .filter(|line| !line.is_empty())
-            .filter_map(|line| line.split_once(' '))
-            .map(|(_, package)| package.trim())
-            .filter(|package| !package.is_empty())
-            .map(ToOwned::to_owned)
-            .collect();
-
-        {
-            let mut cache_guard = cache.lock().unwrap();
-            cache_guard.insert(group_name.to_string(), packages.clone());
-        }
-
-        Ok(packages)
+        self.group_members(&[group_name.to_string()])?
+            .remove(group_name)
+            .ok_or_else(|| anyhow!("Failed to get packages for group {}", group_name))
     }
 }
 
+/// Every package, group, and provided name in the sync dbs, for the rest of the run
+static SYNC_NAMES_CACHE: Mutex<Option<SyncNames>> = Mutex::new(None);
+/// Whether each name looked up is a group, for the rest of the run
 static GROUP_CACHE: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
 static GROUP_PACKAGES_CACHE: OnceLock<Mutex<HashMap<String, Vec<String>>>> = OnceLock::new();
 static AUR_HELPER: OnceLock<Option<String>> = OnceLock::new();
@@ -431,14 +1002,16 @@ enum CaptureMode {
 
 struct CommandOutcome {
     status: ExitStatus,
-    _stderr: Option<String>,
+    stderr: Option<String>,
 }
 
-fn command_exists(command: &str) -> bool {
+/// True when `command --version` runs successfully
+pub fn command_exists(command: &str) -> bool {
     Command::new(command)
         .arg("--version")
         .stdout(Stdio::null())
         .stderr(Stdio::null())
+        .logged()
         .status()
         .map(|status| status.success())
         .unwrap_or(false)
@@ -459,9 +1032,7 @@ pub fn aur_helper_command() -> Option<&'static str> {
 }
 
 fn require_aur_helper() -> Result<&'static str> {
-    aur_helper_command().ok_or_else(|| {
-        anyhow!("No AUR helper found. Install either 'paru' or 'yay' to manage AUR packages.")
-    })
+    aur_helper_command().ok_or_else(|| PmError::MissingAurHelper.into())
 }
 
 fn mode_from_bool(non_interactive: bool) -> CommandMode {
@@ -486,14 +1057,14 @@ fn run_command(
             status: crate::internal::util::execute_command_interactive(
                 command, &arg_refs, message,
             )?,
-            _stderr: None,
+            stderr: None,
         }),
         CommandMode::Managed => match capture {
             CaptureMode::Spinner => Ok(CommandOutcome {
                 status: crate::internal::util::execute_command_with_spinner(
                     command, &arg_refs, message,
                 )?,
-                _stderr: None,
+                stderr: None,
             }),
             CaptureMode::CaptureStderr => {
                 let (status, stderr) = crate::internal::util::execute_command_with_stderr_capture(
@@ -501,7 +1072,7 @@ fn run_command(
                 )?;
                 Ok(CommandOutcome {
                     status,
-                    _stderr: Some(stderr),
+                    stderr: Some(stderr),
                 })
             }
         },
@@ -515,3 +1086,608 @@ fn ensure_success(status: ExitStatus, failure_message: &str) -> Result<()> {
         Err(anyhow!("{}", failure_message))
     }
 }
+
+/// Parse `pacman -Qi` output (C locale) into one entry per package block
+pub fn parse_package_info(output: &str) -> Vec<PackageInfo> {
+    let list = |value: &str| -> Vec<String> {
+        if value == "None" {
+            Vec::new()
+        } else {
+            value.split_whitespace().map(ToString::to_string).collect()
+        }
+    };
+
+    // `lsof: show files opened by a process [installed]` names `lsof`
+    let optional = |value: &str| -> Option<String> {
+        let name = value.split(':').next()?.trim();
+        let name = name.strip_suffix("[installed]").unwrap_or(name).trim();
+        (!name.is_empty() && name != "None").then(|| name.to_string())
+    };
+
+    let mut packages = Vec::new();
+    let mut current: Option<PackageInfo> = None;
+    let mut in_optional = false;
+    for line in output.lines() {
+        // Continuation lines (e.g. optional deps) start with whitespace
+        if line.starts_with(' ') {
+            if in_optional && let Some(info) = current.as_mut() {
+                info.optional_deps.extend(optional(line));
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(" : ") else {
+            in_optional = false;
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        in_optional = key == "Optional Deps";
+        // `-Si` entries start with Repository, `-Qi` entries with Name
+        if key == "Repository" {
+            packages.extend(current.take());
+            current = Some(PackageInfo {
+                repository: value.to_string(),
+                ..PackageInfo::default()
+            });
+            continue;
+        }
+        if key == "Name" {
+            match current.as_mut().filter(|info| info.name.is_empty()) {
+                Some(info) => info.name = value.to_string(),
+                None => {
+                    packages.extend(current.take());
+                    current = Some(PackageInfo {
+                        name: value.to_string(),
+                        ..PackageInfo::default()
+                    });
+                }
+            }
+            continue;
+        }
+        let Some(info) = current.as_mut() else {
+            continue;
+        };
+        match key {
+            "Version" => info.version = value.to_string(),
+            "Description" => info.description = value.to_string(),
+            "Installed Size" => info.installed_size = parse_size(value).unwrap_or(0),
+            "Depends On" => info.depends_on = list(value),
+            "Provides" => info.provides = list(value),
+            "Groups" => info.groups = list(value),
+            "Optional Deps" => info.optional_deps = optional(value).into_iter().collect(),
+            "Required By" => info.required_by = list(value),
+            "Install Reason" => info.explicit = value == "Explicitly installed",
+            "Install Date" => info.install_date = value.to_string(),
+            "Packager" => info.packager = value.to_string(),
+            _ => {}
+        }
+    }
+    packages.extend(current);
+    packages
+}
+
+/// A database dir for this user in the temp dir, sharing the real local database, as
+/// `checkupdates` sets it up
+fn private_db_dir() -> Result<PathBuf> {
+    let user = std::env::var("USER").unwrap_or_else(|_| "owl".to_string());
+    let db = std::env::temp_dir().join(format!("owl-checkup-db-{}", user));
+    std::fs::create_dir_all(&db)
+        .map_err(|e| anyhow!("Failed to create {}: {}", db.display(), e))?;
+    let local = db.join("local");
+    if std::fs::symlink_metadata(&local).is_err() {
+        let real = std::path::Path::new(crate::internal::constants::PACMAN_DB_DIR).join("local");
+        std::os::unix::fs::symlink(&real, &local)
+            .map_err(|e| anyhow!("Failed to link {}: {}", local.display(), e))?;
+    }
+    Ok(db)
+}
+
+/// Compare two package versions as pacman's `vercmp` does: the epoch first, then the
+/// version, then the pkgrel when both have one
+pub fn vercmp(left: &str, right: &str) -> Ordering {
+    if left == right {
+        return Ordering::Equal;
+    }
+    let (left_epoch, left_version, left_rel) = split_evr(left);
+    let (right_epoch, right_version, right_rel) = split_evr(right);
+    rpmvercmp(left_epoch, right_epoch)
+        .then_with(|| rpmvercmp(left_version, right_version))
+        .then_with(|| match (left_rel, right_rel) {
+            (Some(left), Some(right)) => rpmvercmp(left, right),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Split `[epoch:]version[-pkgrel]`, with a missing or empty epoch read as 0
+fn split_evr(evr: &str) -> (&str, &str, Option<&str>) {
+    let digits = evr.bytes().take_while(u8::is_ascii_digit).count();
+    let (epoch, rest) = match evr[digits..].strip_prefix(':') {
+        Some(rest) if digits > 0 => (&evr[..digits], rest),
+        Some(rest) => ("0", rest),
+        None => ("0", evr),
+    };
+    match rest.rsplit_once('-') {
+        Some((version, rel)) => (epoch, version, Some(rel)),
+        None => (epoch, rest, None),
+    }
+}
+
+/// libalpm's `rpmvercmp`: alternating runs of digits and letters compared one by one,
+/// numbers by value, with separators only counting by their length. Of two versions
+/// that agree up to where one ends, the longer is newer unless it goes on with a
+/// letter (`1.0a` is older than `1.0`).
+fn rpmvercmp(left: &str, right: &str) -> Ordering {
+    if left == right {
+        return Ordering::Equal;
+    }
+    let (a, b) = (left.as_bytes(), right.as_bytes());
+    let (mut one, mut two) = (0, 0);
+    while one < a.len() && two < b.len() {
+        let (separator_one, separator_two) = (one, two);
+        while one < a.len() && !a[one].is_ascii_alphanumeric() {
+            one += 1;
+        }
+        while two < b.len() && !b[two].is_ascii_alphanumeric() {
+            two += 1;
+        }
+        if one == a.len() || two == b.len() {
+            break;
+        }
+        let separators = (one - separator_one).cmp(&(two - separator_two));
+        if separators.is_ne() {
+            return separators;
+        }
+
+        let numeric = a[one].is_ascii_digit();
+        let run_end = |bytes: &[u8], start: usize| {
+            start
+                + bytes[start..]
+                    .iter()
+                    .take_while(|c| {
+                        if numeric {
+                            c.is_ascii_digit()
+                        } else {
+                            c.is_ascii_alphabetic()
+                        }
+                    })
+                    .count()
+        };
+        let (end_one, end_two) = (run_end(a, one), run_end(b, two));
+        // A number is newer than letters in the same place
+        if end_two == two {
+            return if numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+        let (mut run_one, mut run_two) = (&a[one..end_one], &b[two..end_two]);
+        if numeric {
+            let zeros = |run: &[u8]| run.iter().take_while(|&&c| c == b'0').count();
+            (run_one, run_two) = (&run_one[zeros(run_one)..], &run_two[zeros(run_two)..]);
+            let lengths = run_one.len().cmp(&run_two.len());
+            if lengths.is_ne() {
+                return lengths;
+            }
+        }
+        let runs = run_one.cmp(run_two);
+        if runs.is_ne() {
+            return runs;
+        }
+        (one, two) = (end_one, end_two);
+    }
+
+    match (a.get(one), b.get(two)) {
+        (None, None) => Ordering::Equal,
+        (None, Some(c)) if !c.is_ascii_alphabetic() => Ordering::Less,
+        (Some(c), _) if c.is_ascii_alphabetic() => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+/// The package file for exactly `name` `version` in the pacman cache, if it was kept
+pub fn cached_package(name: &str, version: &str) -> Option<PathBuf> {
+    find_cached(name, |cached| cached == version)
+}
+
+/// A package file for `name` in the pacman cache at a version `pin` accepts
+pub fn cached_pinned_package(name: &str, pin: &str) -> Option<PathBuf> {
+    find_cached(name, |cached| pin_matches(pin, cached))
+}
+
+fn find_cached(name: &str, matches: impl Fn(&str) -> bool) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(crate::internal::constants::PACMAN_CACHE_DIR).ok()?;
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .and_then(|file| file.to_str())
+                .and_then(|file| cached_version(file, name))
+                .is_some_and(&matches)
+        })
+}
+
+/// The `<pkgver>-<pkgrel>` of a `<name>-<version>-<arch>.pkg.tar.*` file, but not
+/// of its signature
+fn cached_version<'a>(file: &'a str, name: &str) -> Option<&'a str> {
+    let rest = file.strip_prefix(name)?.strip_prefix('-')?;
+    if rest.ends_with(".sig") {
+        return None;
+    }
+    let (stem, _) = rest.split_once(".pkg.tar")?;
+    let (version, _arch) = stem.rsplit_once('-')?;
+    (version.matches('-').count() == 1).then_some(version)
+}
+
+/// Whether `version` is one a pin accepts: the same, or the same pkgver when the pin
+/// leaves out the pkgrel (`24.0.1` for `24.0.1-2`) or the epoch (`1:24.0.1-2`)
+pub fn pin_matches(pin: &str, version: &str) -> bool {
+    let version = if pin.contains(':') {
+        version
+    } else {
+        version
+            .split_once(':')
+            .map_or(version, |(_, version)| version)
+    };
+    version == pin
+        || version
+            .strip_prefix(pin)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .is_some_and(|pkgrel| !pkgrel.is_empty() && !pkgrel.contains('-'))
+}
+
+/// Parse one name per line, as `pacman -Qq` prints them
+fn parse_names(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Parse `pacman -Q` output, `name version` per line
+pub fn parse_versions(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, version) = line.trim().split_once(' ')?;
+            Some((name.to_string(), version.to_string()))
+        })
+        .collect()
+}
+
+/// Parse `pacman -Sg` output, `group member` per line, into each group's members
+fn parse_group_members(output: &str) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (group, member) in output
+        .lines()
+        .filter_map(|line| line.trim().split_once(' '))
+    {
+        let member = member.trim();
+        if !member.is_empty() {
+            groups
+                .entry(group.to_string())
+                .or_default()
+                .push(member.to_string());
+        }
+    }
+    groups
+}
+
+/// Parse `-Qu` style lines: `name old -> new`, possibly followed by `[ignored]`
+fn parse_pending_updates(output: &str) -> Vec<PendingUpdate> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?.to_string();
+            let from = fields.next().unwrap_or_default().to_string();
+            let to = match fields.next() {
+                Some("->") => fields.next().unwrap_or_default().to_string(),
+                _ => String::new(),
+            };
+            Some(PendingUpdate { name, from, to })
+        })
+        .collect()
+}
+
+/// Parse pacman sizes such as `1.50 MiB` into bytes
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, unit) = value.split_once(' ')?;
+    let number: f64 = number.parse().ok()?;
+    let factor = match unit.trim() {
+        "B" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * factor).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_version() {
+        let version = |file| cached_version(file, "htop");
+        assert_eq!(version("htop-3.3.0-1-x86_64.pkg.tar.zst"), Some("3.3.0-1"));
+        assert_eq!(version("htop-3.3.0-1-any.pkg.tar.xz"), Some("3.3.0-1"));
+        assert_eq!(
+            version("htop-1:3.3.0-2-x86_64.pkg.tar.zst"),
+            Some("1:3.3.0-2")
+        );
+        assert_eq!(version("htop-3.3.0-1-x86_64.pkg.tar.zst.sig"), None);
+        assert_eq!(version("htop-vim-3.3.0-1-x86_64.pkg.tar.zst"), None);
+        assert_eq!(version("htop.txt"), None);
+    }
+
+    #[test]
+    fn test_parse_group_members() {
+        let output = "base-devel autoconf\nbase-devel automake\nxorg-apps xorg-xrandr\n\n";
+        assert_eq!(
+            parse_group_members(output),
+            BTreeMap::from([
+                (
+                    "base-devel".to_string(),
+                    vec!["autoconf".to_string(), "automake".to_string()]
+                ),
+                ("xorg-apps".to_string(), vec!["xorg-xrandr".to_string()]),
+            ])
+        );
+        assert!(parse_group_members("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_listings_of_a_large_system() {
+        // About the size of the sync dbs, and of a well-stocked install
+        let sync: String = (0..15_000).map(|i| format!("pkg-{}\n", i)).collect();
+        let installed: String = (0..3_000)
+            .map(|i| format!("pkg-{} {}.0.{}-1\n", i * 5, i % 7, i))
+            .collect();
+
+        let start = Instant::now();
+        let names = parse_names(&sync);
+        let versions = parse_versions(&installed);
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        assert_eq!(names.len(), 15_000);
+        assert!(names.contains("pkg-14999"));
+        assert_eq!(versions.len(), 3_000);
+        assert_eq!(versions["pkg-10"].as_str(), "2.0.2-1");
+        assert!(versions.keys().all(|name| names.contains(name)));
+        assert!(parse_names("\n  \n").is_empty());
+        assert!(parse_versions("broken\n").is_empty());
+    }
+
+    #[test]
+    fn test_vercmp_matches_pacman() {
+        use Ordering::{Equal, Greater, Less};
+        // Cases from pacman's own vercmp tests
+        let cases = [
+            ("1.5.0", "1.5.0", Equal),
+            ("1.5.1", "1.5", Greater),
+            ("1.5.0-1", "1.5.0-2", Less),
+            ("1.5.0-2", "1.5.1-1", Less),
+            ("1.5-1", "1.5", Equal),
+            ("1.01", "1.1", Equal),
+            ("1.10", "1.9", Greater),
+            ("1.5b", "1.5", Less),
+            ("1.5b", "1.5.1", Less),
+            ("1.0a", "1.0alpha", Less),
+            ("1.0beta", "1.0rc", Less),
+            ("1.0rc", "1.0", Less),
+            ("1.5.a", "1.5", Greater),
+            ("1.5.1", "1.5.b", Greater),
+            ("2.0", "2_0", Equal),
+            ("2.0a", "2.0.a", Less),
+            ("2___a", "2_a", Greater),
+            // Epochs
+            ("0:1.0", "1.0", Equal),
+            (":1.0", "1.0", Equal),
+            ("1:1.0", "2.0", Greater),
+            ("1:1.0-1", "0:1.1-1", Greater),
+            ("1:1.0", "2:0.1", Less),
+            // VCS packages: revision counts and dated snapshots
+            ("r1234.abcdef0-1", "r1240.0123abc-1", Less),
+            ("0.9.r12.g1a2b3c4-1", "0.9-1", Greater),
+            ("20240101-1", "20231231-2", Greater),
+            ("1.2.3.r4.gabcdef-1", "1.2.4-1", Less),
+        ];
+        for (left, right, expected) in cases {
+            assert_eq!(vercmp(left, right), expected, "{} vs {}", left, right);
+            assert_eq!(
+                vercmp(right, left),
+                expected.reverse(),
+                "{} vs {}",
+                right,
+                left
+            );
+        }
+        assert_eq!(split_evr("2:1.0-3"), ("2", "1.0", Some("3")));
+        assert_eq!(split_evr("1.0.r5-a-1"), ("0", "1.0.r5-a", Some("1")));
+    }
+
+    #[test]
+    fn test_pin_matches() {
+        assert!(pin_matches("24.0.1", "24.0.1-2"));
+        assert!(pin_matches("24.0.1-2", "24.0.1-2"));
+        assert!(pin_matches("24.0.1", "1:24.0.1-2"));
+        assert!(pin_matches("1:24.0.1-2", "1:24.0.1-2"));
+        assert!(!pin_matches("24.0.1-1", "24.0.1-2"));
+        assert!(!pin_matches("24.0", "24.0.1-2"));
+        assert!(!pin_matches("24.0.1", "24.0.10-1"));
+        assert!(!pin_matches("1:24.0.1", "2:24.0.1-1"));
+    }
+
+    #[test]
+    fn test_ignore_args() {
+        assert!(ignore_args(&[]).is_empty());
+        assert_eq!(
+            ignore_args(&["firefox".to_string(), "linux".to_string()]),
+            vec!["--ignore", "firefox,linux"]
+        );
+    }
+
+    #[test]
+    fn test_parse_package_info() {
+        let output = "\
+Name            : htop
+Version         : 3.3.0-3
+Description     : Interactive process viewer
+Groups          : None
+Depends On      : libncursesw.so=6-64  libnl
+Optional Deps   : lsof: show files opened by a process
+                  strace: attach to a running process
+Required By     : None
+Installed Size  : 412.50 KiB
+Packager        : Jane Doe <jane@archlinux.org>
+Install Date    : Tue 04 Jun 2024 10:12:03 AM CEST
+Install Reason  : Explicitly installed
+
+Name            : glibc
+Version         : 2.40+r16-1
+Description     : GNU C Library
+Groups          : base-devel
+Provides        : libc.so=6-64  libm.so=6-64
+Depends On      : linux-api-headers>=4.10  tzdata  filesystem
+Required By     : bash  coreutils  htop
+Installed Size  : 48.25 MiB
+Install Reason  : Installed as a dependency for another package
+";
+        let packages = parse_package_info(output);
+        assert_eq!(packages.len(), 2);
+        assert_eq!(
+            packages[0],
+            PackageInfo {
+                name: "htop".to_string(),
+                version: "3.3.0-3".to_string(),
+                description: "Interactive process viewer".to_string(),
+                installed_size: 422_400,
+                depends_on: vec!["libncursesw.so=6-64".to_string(), "libnl".to_string()],
+                provides: Vec::new(),
+                groups: Vec::new(),
+                optional_deps: vec!["lsof".to_string(), "strace".to_string()],
+                required_by: Vec::new(),
+                explicit: true,
+                install_date: "Tue 04 Jun 2024 10:12:03 AM CEST".to_string(),
+                repository: String::new(),
+                packager: "Jane Doe <jane@archlinux.org>".to_string(),
+            }
+        );
+        assert!(!packages[1].explicit);
+        assert_eq!(packages[1].groups, vec!["base-devel"]);
+        assert_eq!(packages[1].required_by, vec!["bash", "coreutils", "htop"]);
+        assert_eq!(packages[1].installed_size, 50_593_792);
+        assert_eq!(packages[1].provides, vec!["libc.so=6-64", "libm.so=6-64"]);
+
+        // `-Si` puts Repository ahead of Name
+        let sync = "\
+Repository      : extra
+Name            : htop
+Version         : 3.3.0-3
+
+Repository      : core
+Name            : zlib
+";
+        let packages = parse_package_info(sync);
+        assert_eq!(packages.len(), 2);
+        assert_eq!(
+            (packages[0].name.as_str(), packages[0].repository.as_str()),
+            ("htop", "extra")
+        );
+        assert_eq!(
+            (packages[1].name.as_str(), packages[1].repository.as_str()),
+            ("zlib", "core")
+        );
+    }
+
+    #[test]
+    fn test_sync_names_place_packages_groups_and_provides() {
+        let sync = "\
+Repository      : core
+Name            : gcc
+Groups          : base-devel
+Provides        : gcc-multilib  libgcc.so=1-64
+
+Repository      : extra
+Name            : jdk21-openjdk
+Provides        : java-runtime=21  java-environment=21
+";
+        let names = SyncNames::from_info(parse_package_info(sync));
+        let asked: Vec<String> = ["gcc", "base-devel", "java-runtime", "libgcc.so", "htpo"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let among = names.among(&asked);
+        let set =
+            |items: &[&str]| -> HashSet<String> { items.iter().map(ToString::to_string).collect() };
+        assert_eq!(among.packages, set(&["gcc"]));
+        assert_eq!(among.groups, set(&["base-devel"]));
+        assert_eq!(among.provided, set(&["java-runtime", "libgcc.so"]));
+    }
+
+    #[test]
+    fn test_parse_pending_updates() {
+        let output = "linux 6.9.1-1 -> 6.9.2-1\nmesa 24.1.0-1 -> 24.1.1-1 [ignored]\n\nbare\n";
+        assert_eq!(
+            parse_pending_updates(output),
+            vec![
+                PendingUpdate {
+                    name: "linux".to_string(),
+                    from: "6.9.1-1".to_string(),
+                    to: "6.9.2-1".to_string(),
+                },
+                PendingUpdate {
+                    name: "mesa".to_string(),
+                    from: "24.1.0-1".to_string(),
+                    to: "24.1.1-1".to_string(),
+                },
+                PendingUpdate {
+                    name: "bare".to_string(),
+                    from: String::new(),
+                    to: String::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_size_units() {
+        assert_eq!(parse_size("0.00 B"), Some(0));
+        assert_eq!(parse_size("1.00 GiB"), Some(1 << 30));
+        assert_eq!(parse_size("12 parsecs"), None);
+    }
+
+    #[test]
+    fn test_database_lock_is_waited_for_or_reported() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        let path = temp.path().join(DB_LOCK_FILE);
+        let paru = || vec!["paru (pid 4242)".to_string()];
+        let poll = Duration::from_millis(10);
+
+        assert!(wait_for_unlock(&path, paru, Duration::ZERO, poll).is_ok());
+
+        std::fs::write(&path, "").unwrap();
+        let err = wait_for_unlock(&path, Vec::new, Duration::from_secs(60), poll).unwrap_err();
+        assert!(err.downcast_ref::<PmError>().unwrap().is_stale_lock());
+        assert!(path.exists(), "a stale lock is left for the user to remove");
+
+        let err = wait_for_unlock(&path, paru, Duration::ZERO, poll).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pacman's database is locked by paru (pid 4242)"
+        );
+
+        let releasing = path.clone();
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            std::fs::remove_file(releasing).unwrap();
+        });
+        assert!(wait_for_unlock(&path, paru, Duration::from_secs(10), poll).is_ok());
+        release.join().unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+//! Running things as root: directly when owl already is, through sudo otherwise
+
+use anyhow::{Context, Result, anyhow};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::internal::logging::LogCommand;
+
+/// Whether this process runs as root, judged by the owner of its `/proc` entry
+pub fn is_root() -> bool {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata("/proc/self").is_ok_and(|metadata| metadata.uid() == 0)
+}
+
+/// `args` as a command that runs as root, through sudo unless we already are root
+fn as_root(args: &[&str]) -> Command {
+    let (program, rest) = if is_root() {
+        (args[0], &args[1..])
+    } else {
+        ("sudo", args)
+    };
+    let mut command = Command::new(program);
+    command.args(rest);
+    command
+}
+
+/// Run a command as root, through sudo unless we already are root
+pub fn run(args: &[&str]) -> Result<()> {
+    let status = as_root(args)
+        .logged()
+        .status()
+        .with_context(|| format!("Failed to run {}", args[0]))?;
+    if !status.success() {
+        return Err(anyhow!("`{}` failed", args.join(" ")));
+    }
+    Ok(())
+}
+
+/// Like `run`, with `input` on the command's stdin
+fn run_with_input(args: &[&str], input: &str) -> Result<()> {
+    let mut child = as_root(args)
+        .stdin(Stdio::piped())
+        .logged()
+        .spawn()
+        .with_context(|| format!("Failed to run {}", args[0]))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .with_context(|| format!("Failed to pass input to {}", args[0]))?;
+    }
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to run {}", args[0]))?;
+    if !status.success() {
+        return Err(anyhow!("`{}` failed", args.join(" ")));
+    }
+    Ok(())
+}
+
+/// Write `content` to the root-owned `path`, creating its directory. The content
+/// reaches `install` on stdin, so it is never staged in a file another user could
+/// replace before it is copied.
+pub fn write_file(path: &Path, content: &str) -> Result<()> {
+    run_with_input(
+        &[
+            "install",
+            "-Dm644",
+            "/dev/stdin",
+            &path.display().to_string(),
+        ],
+        content,
+    )
+}
+
+/// Whether someone other than root can change `path` or the directory holding it,
+/// which makes it unfit to be run as root
+pub fn writable_by_others(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let unsafe_entry = |path: &Path| {
+        std::fs::metadata(path)
+            .is_ok_and(|metadata| metadata.uid() != 0 || metadata.mode() & 0o022 != 0)
+    };
+    unsafe_entry(path) || path.parent().is_some_and(unsafe_entry)
+}
+
+/// Delete the root-owned `path`; a missing file is not an error
+pub fn remove_file(path: &Path) -> Result<()> {
+    run(&["rm", "-f", "--", &path.display().to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_writable_by_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("owl");
+        std::fs::write(&exe, "").unwrap();
+        std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(writable_by_others(&exe));
+        assert!(!writable_by_others(Path::new("/")));
+    }
+}
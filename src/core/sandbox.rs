@@ -0,0 +1,171 @@
+//! Disposable environments for `owl test-apply`
+//!
+//! The owl directory is bind-mounted read-only and copied inside, so the run writes
+//! its state into the copy, and this owl binary is mounted in as `/usr/local/bin/owl`.
+//! Docker and podman containers run with `--rm` under a name that is force-removed
+//! again when the sandbox is dropped, in case the run was cut short; nspawn's
+//! `--ephemeral` throws its snapshot away by itself.
+
+use anyhow::Result;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::cli::porcelain::{Event, Summary};
+use crate::core::settings::SandboxRuntime;
+use crate::internal::logging::LogCommand;
+
+const CONFIG_MOUNT: &str = "/owl-config";
+const OWL_BINARY: &str = "/usr/local/bin/owl";
+
+/// Apply, then a dry run that should find nothing left to do
+const SCRIPT: &str = "set -e
+cp -a /owl-config /root/.owl
+owl --non-interactive --porcelain apply
+owl --non-interactive --porcelain --dry-run apply
+";
+
+pub struct Sandbox {
+    runtime: SandboxRuntime,
+    image: String,
+    /// Container name, unique to this process
+    name: String,
+    /// Whether a container may exist that needs removing
+    started: bool,
+}
+
+impl Sandbox {
+    pub fn new(runtime: SandboxRuntime, image: &str) -> Self {
+        Self {
+            runtime,
+            image: image.to_string(),
+            name: format!("owl-test-apply-{}", std::process::id()),
+            started: false,
+        }
+    }
+
+    /// The program that starts the environment
+    pub fn program(&self) -> &'static str {
+        match self.runtime {
+            SandboxRuntime::Docker => "docker",
+            SandboxRuntime::Podman => "podman",
+            SandboxRuntime::Nspawn => "systemd-nspawn",
+        }
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// What this host lacks to start the environment; empty when nothing is
+    pub fn missing(&self) -> Vec<String> {
+        let mut missing = Vec::new();
+        if !crate::core::pm::command_exists(self.program()) {
+            missing.push(format!("`{}` is not installed", self.program()));
+        }
+        if self.runtime == SandboxRuntime::Nspawn {
+            if !crate::core::privilege::is_root() {
+                missing.push("systemd-nspawn needs root; run owl test-apply with sudo".to_string());
+            }
+            if !Path::new(&self.image).join("usr").is_dir() {
+                missing.push(format!(
+                    "no root filesystem at '{}'; create one with `pacstrap -c <dir> base` and set [test_apply] image",
+                    self.image
+                ));
+            }
+        }
+        missing
+    }
+
+    /// Run apply and the follow-up dry run inside, passing each porcelain event to
+    /// `on_event`. Returns both summaries, or the error the run stopped on.
+    pub fn run(
+        &mut self,
+        owl_dir: &Path,
+        owl_binary: &Path,
+        on_event: impl FnMut(&Event),
+    ) -> Result<Vec<Summary>> {
+        self.started = true;
+        crate::core::fleet::follow(self.command(owl_dir, owl_binary), self.program(), on_event)
+    }
+
+    /// The command that runs the apply inside, with `owl_dir` as the config
+    fn command(&self, owl_dir: &Path, owl_binary: &Path) -> Command {
+        let mut command = Command::new(self.program());
+        match self.runtime {
+            SandboxRuntime::Docker | SandboxRuntime::Podman => {
+                command
+                    .args(["run", "--rm", "--name", &self.name])
+                    .arg("-v")
+                    .arg(format!("{}:{}:ro", owl_dir.display(), CONFIG_MOUNT))
+                    .arg("-v")
+                    .arg(format!("{}:{}:ro", owl_binary.display(), OWL_BINARY))
+                    .arg(&self.image);
+            }
+            SandboxRuntime::Nspawn => {
+                command
+                    .args(["--quiet", "--ephemeral", "--directory", &self.image])
+                    .arg(format!("--bind-ro={}:{}", owl_dir.display(), CONFIG_MOUNT))
+                    .arg(format!("--bind-ro={}:{}", owl_binary.display(), OWL_BINARY))
+                    .arg("--setenv=HOME=/root");
+            }
+        }
+        command.args(["/bin/sh", "-c", SCRIPT]);
+        command
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        if !self.started || self.runtime == SandboxRuntime::Nspawn {
+            return;
+        }
+        // Usually gone already through --rm; an error here only means it is
+        Command::new(self.program())
+            .args(["rm", "--force", &self.name])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .status()
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_command_mounts_config_and_binary_read_only() {
+        let owl = Path::new("/home/me/.owl");
+        let binary = Path::new("/usr/bin/owl");
+
+        let docker = Sandbox::new(SandboxRuntime::Podman, "archlinux:base");
+        let command = docker.command(owl, binary);
+        assert_eq!(command.get_program(), "podman");
+        let docker_args = args(&command);
+        assert_eq!(docker_args[..4], ["run", "--rm", "--name", &docker.name]);
+        assert!(docker_args.contains(&"/home/me/.owl:/owl-config:ro".to_string()));
+        assert!(docker_args.contains(&"/usr/bin/owl:/usr/local/bin/owl:ro".to_string()));
+        assert_eq!(docker_args[docker_args.len() - 4], "archlinux:base");
+        assert_eq!(docker_args.last().unwrap(), SCRIPT);
+
+        let nspawn = Sandbox::new(SandboxRuntime::Nspawn, "/var/lib/machines/arch");
+        let nspawn_args = args(&nspawn.command(owl, binary));
+        assert!(nspawn_args.contains(&"--ephemeral".to_string()));
+        assert!(nspawn_args.contains(&"--bind-ro=/home/me/.owl:/owl-config".to_string()));
+        assert!(
+            nspawn
+                .missing()
+                .iter()
+                .any(|reason| reason.contains("no root filesystem"))
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! systemd units that run owl on a timer to keep the machine converged
+//!
+//! `owl schedule enable` writes `owl-apply.service` and `owl-apply.timer`. Both start
+//! with [`MARKER`], which is how owl recognizes units it may rewrite: every apply
+//! regenerates marked units whose content no longer matches the settings.
+
+use anyhow::{Context, Result, anyhow};
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::internal::logging::LogCommand;
+
+pub const SERVICE: &str = "owl-apply.service";
+pub const TIMER: &str = "owl-apply.timer";
+/// Set in the service's environment, so run records know they were scheduled
+pub const SCHEDULED_ENV: &str = "OWL_SCHEDULED";
+const MARKER: &str = "# Generated by `owl schedule enable`; owl rewrites this file";
+
+/// Which systemd instance owns the units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// `systemctl --user`, running as the current user
+    User,
+    /// The system manager, running as root
+    System,
+}
+
+impl Scope {
+    pub fn unit_dir(self) -> Result<PathBuf> {
+        match self {
+            Scope::System => Ok(PathBuf::from("/etc/systemd/system")),
+            Scope::User => {
+                let config = match std::env::var("XDG_CONFIG_HOME") {
+                    Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+                    _ => PathBuf::from(
+                        std::env::var("HOME")
+                            .map_err(|_| anyhow!("HOME environment variable not set"))?,
+                    )
+                    .join(".config"),
+                };
+                Ok(config.join("systemd").join("user"))
+            }
+        }
+    }
+
+    /// `systemctl` with the scope's flag
+    pub fn systemctl(self) -> Command {
+        let mut command = Command::new("systemctl");
+        if self == Scope::User {
+            command.arg("--user");
+        }
+        command
+    }
+}
+
+/// Check an interval such as `30m`, `6h`, or `1d` and return it as a systemd time span
+pub fn parse_interval(interval: &str) -> Result<String> {
+    let interval = interval.trim();
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(interval.len());
+    let (number, unit) = interval.split_at(split);
+    let unit = match unit {
+        "m" | "min" => "min",
+        "h" => "h",
+        "d" => "d",
+        _ => "",
+    };
+    match number.parse::<u64>() {
+        Ok(count) if count > 0 && !unit.is_empty() => Ok(format!("{}{}", count, unit)),
+        _ => Err(anyhow!(
+            "invalid interval '{}'; use a number followed by m, h, or d, e.g. 6h",
+            interval
+        )),
+    }
+}
+
+/// How scheduled runs are invoked
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    pub exe: PathBuf,
+    /// Global options that keep the run on the same config and state
+    pub globals: Vec<String>,
+    /// The configured arguments, e.g. `--non-interactive apply`
+    pub args: Vec<String>,
+}
+
+impl Invocation {
+    /// This binary with the config, state, and settings of the current process
+    pub fn current() -> Result<Self> {
+        let mut globals = vec![
+            "--config-dir".to_string(),
+            crate::internal::files::owl_dir()?.display().to_string(),
+        ];
+        if let Some(state) = crate::core::state::state_file_override() {
+            globals.push("--state-file".to_string());
+            globals.push(state.display().to_string());
+        }
+        Ok(Self {
+            exe: std::env::current_exe().context("Failed to locate the owl binary")?,
+            globals,
+            args: crate::core::settings::get().schedule.args.clone(),
+        })
+    }
+
+    fn exec_start(&self) -> String {
+        std::iter::once(self.exe.display().to_string())
+            .chain(self.globals.iter().cloned())
+            .chain(self.args.iter().cloned())
+            .map(|arg| quote(&arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Quote an argument for an `ExecStart=` line, which splits on whitespace
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"', '\\', '\'', '$', '%', ';']) {
+        return arg.to_string();
+    }
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "$$")
+        .replace('%', "%%");
+    format!("\"{}\"", escaped)
+}
+
+/// The content of both units
+#[derive(Debug, Clone, PartialEq)]
+pub struct Units {
+    pub service: String,
+    pub timer: String,
+}
+
+/// Render the units for `invocation` every `interval`, a systemd time span
+pub fn render(invocation: &Invocation, interval: &str) -> Units {
+    let service = format!(
+        "{MARKER}
+[Unit]
+Description=Converge the system with the owl config
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=oneshot
+Environment={SCHEDULED_ENV}=1
+ExecStart={}
+",
+        invocation.exec_start()
+    );
+    let timer = format!(
+        "{MARKER}
+[Unit]
+Description=Run owl every {interval}
+
+[Timer]
+OnBootSec=15min
+OnUnitActiveSec={interval}
+
+[Install]
+WantedBy=timers.target
+"
+    );
+    Units { service, timer }
+}
+
+/// The interval of the owl-generated timer in `scope`, if there is one
+pub fn installed_interval(scope: Scope) -> Result<Option<String>> {
+    let path = scope.unit_dir()?.join(TIMER);
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    Ok(interval_from_timer(&content))
+}
+
+fn interval_from_timer(content: &str) -> Option<String> {
+    if !content.starts_with(MARKER) {
+        return None;
+    }
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("OnUnitActiveSec="))
+        .map(|interval| interval.trim().to_string())
+}
+
+/// Whether the units on disk in `scope` differ from `units`
+pub fn is_stale(scope: Scope, units: &Units) -> Result<bool> {
+    let dir = scope.unit_dir()?;
+    let current = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap_or_default();
+    Ok(current(SERVICE) != units.service || current(TIMER) != units.timer)
+}
+
+pub fn write(scope: Scope, units: &Units) -> Result<()> {
+    let dir = scope.unit_dir()?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    for (name, content) in [(SERVICE, &units.service), (TIMER, &units.timer)] {
+        let path = dir.join(name);
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    daemon_reload(scope)
+}
+
+/// Delete the units from `scope`; false when there were none
+pub fn remove(scope: Scope) -> Result<bool> {
+    let dir = scope.unit_dir()?;
+    let mut removed = false;
+    for name in [SERVICE, TIMER] {
+        let path = dir.join(name);
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            removed = true;
+        }
+    }
+    if removed {
+        daemon_reload(scope)?;
+    }
+    Ok(removed)
+}
+
+fn daemon_reload(scope: Scope) -> Result<()> {
+    systemctl(scope, &["daemon-reload"])
+}
+
+/// Run `systemctl` in `scope`, failing when it does
+pub fn systemctl(scope: Scope, args: &[&str]) -> Result<()> {
+    let status = scope
+        .systemctl()
+        .args(args)
+        .logged()
+        .status()
+        .map_err(|e| anyhow!("Failed to run systemctl: {}", e))?;
+    if !status.success() {
+        return Err(anyhow!("systemctl {} failed", args.join(" ")));
+    }
+    Ok(())
+}
+
+/// Rewrite owl-generated units whose content no longer matches the settings, keeping
+/// their interval. System units are only touched when running as root.
+pub fn refresh_stale() -> Result<()> {
+    for scope in [Scope::User, Scope::System] {
+        if scope == Scope::System && !crate::core::privilege::is_root() {
+            continue;
+        }
+        let Some(interval) = installed_interval(scope)? else {
+            continue;
+        };
+        let units = render(&Invocation::current()?, &interval);
+        if is_stale(scope, &units)? {
+            log::debug!("schedule: regenerating the {:?} units", scope);
+            write(scope, &units)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("6h").unwrap(), "6h");
+        assert_eq!(parse_interval("30m").unwrap(), "30min");
+        assert_eq!(parse_interval("1d").unwrap(), "1d");
+        for bad in ["", "h", "0h", "6", "6 weeks", "-1h"] {
+            assert!(parse_interval(bad).is_err(), "{:?} was accepted", bad);
+        }
+    }
+
+    #[test]
+    fn test_render_marks_units_and_quotes_arguments() {
+        let invocation = Invocation {
+            exe: PathBuf::from("/usr/bin/owl"),
+            globals: vec!["--config-dir".to_string(), "/home/me/my owl".to_string()],
+            args: vec!["--non-interactive".to_string(), "apply".to_string()],
+        };
+        let units = render(&invocation, "6h");
+        assert!(units.service.starts_with(MARKER));
+        assert!(units.service.contains(
+            "ExecStart=/usr/bin/owl --config-dir \"/home/me/my owl\" --non-interactive apply\n"
+        ));
+        assert!(units.service.contains("Environment=OWL_SCHEDULED=1\n"));
+        assert_eq!(interval_from_timer(&units.timer).as_deref(), Some("6h"));
+        // A hand-written timer is not ours to rewrite
+        assert_eq!(interval_from_timer("[Timer]\nOnUnitActiveSec=1h\n"), None);
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::{Result, anyhow};
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
@@ -9,8 +9,11 @@ use std::thread;
 use std::time::Duration;
 use tar::Archive;
 
+use crate::internal::logging::LogCommand;
+
 const PACMAN_SYNC_DIR: &str = "/var/lib/pacman/sync";
 const AUR_RPC_URL: &str = "https://aur.archlinux.org/rpc/v5/search";
+const AUR_INFO_URL: &str = "https://aur.archlinux.org/rpc/v5/info";
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PackageSource {
@@ -26,6 +29,9 @@ pub struct SearchResult {
     pub repo: String,
     pub description: String,
     pub installed: bool,
+    /// AUR votes and popularity; `None` for repo packages
+    pub votes: Option<u32>,
+    pub popularity: Option<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -50,6 +56,82 @@ struct AurPackage {
     version: String,
     #[serde(rename = "Description")]
     description: Option<String>,
+    #[serde(rename = "NumVotes", default)]
+    votes: u32,
+    #[serde(rename = "Popularity", default)]
+    popularity: f64,
+    #[serde(rename = "Maintainer", default)]
+    maintainer: Option<String>,
+    /// Unix time the package was flagged out of date
+    #[serde(rename = "OutOfDate", default)]
+    out_of_date: Option<u64>,
+    /// Only present in info responses
+    #[serde(rename = "Depends", default)]
+    depends: Vec<String>,
+}
+
+/// AUR metadata for a single package, from the RPC info endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct AurInfo {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    /// `None` for orphaned packages
+    pub maintainer: Option<String>,
+    /// Unix time the package was flagged out of date
+    pub out_of_date: Option<u64>,
+    pub votes: u32,
+    pub popularity: f64,
+    pub depends: Vec<String>,
+}
+
+/// Look up one package in the AUR; `None` when the AUR does not have it
+pub fn aur_info(name: &str) -> Result<Option<AurInfo>> {
+    Ok(aur_info_batch(&[name.to_string()])?.remove(name))
+}
+
+/// Look up several packages in one AUR request. Names the AUR does not know are absent.
+pub fn aur_info_batch(names: &[String]) -> Result<HashMap<String, AurInfo>> {
+    if names.is_empty() {
+        return Ok(HashMap::new());
+    }
+    retry_command(
+        || {
+            let mut request = ureq::get(AUR_INFO_URL);
+            for name in names {
+                request = request.query("arg[]", name);
+            }
+            let body = request
+                .call()
+                .map_err(|e| anyhow!("AUR info request failed: {}", e))?
+                .into_string()
+                .map_err(|e| anyhow!("Failed to read AUR info response: {}", e))?;
+            parse_info_response(&body, names)
+        },
+        3,
+    )
+}
+
+/// The packages among `names` in an RPC info response
+fn parse_info_response(body: &str, names: &[String]) -> Result<HashMap<String, AurInfo>> {
+    let payload: AurSearchResponse = serde_json::from_str(body)
+        .map_err(|e| anyhow!("Failed to parse AUR info response: {}", e))?;
+    Ok(payload
+        .results
+        .into_iter()
+        .filter(|pkg| names.contains(&pkg.name))
+        .map(|pkg| AurInfo {
+            name: pkg.name,
+            version: pkg.version,
+            description: pkg.description.unwrap_or_default(),
+            maintainer: pkg.maintainer,
+            out_of_date: pkg.out_of_date,
+            votes: pkg.votes,
+            popularity: pkg.popularity,
+            depends: pkg.depends,
+        })
+        .map(|info| (info.name.clone(), info))
+        .collect())
 }
 
 pub fn search_packages(terms: &[String]) -> Result<Vec<SearchResult>> {
@@ -57,19 +139,25 @@ pub fn search_packages(terms: &[String]) -> Result<Vec<SearchResult>> {
         return Ok(Vec::new());
     }
 
-    let normalized_terms: Vec<String> = terms
-        .iter()
-        .map(|term| term.trim().to_ascii_lowercase())
-        .filter(|term| !term.is_empty())
-        .collect();
+    let normalized_terms = normalize_terms(terms);
 
     if normalized_terms.is_empty() {
         return Ok(Vec::new());
     }
 
     let installed = installed_packages()?;
-    let mut results = search_repo_packages(&normalized_terms, &installed)?;
-    results.extend(search_aur_packages(&normalized_terms, &installed)?);
+    // The AUR request runs while the local sync databases are scanned
+    let (repo, aur) = thread::scope(|scope| {
+        let aur = scope.spawn(|| search_aur_packages(&normalized_terms, &installed));
+        let repo = search_repo_packages(&normalized_terms, &installed);
+        (
+            repo,
+            aur.join()
+                .unwrap_or_else(|_| Err(anyhow!("AUR search thread panicked"))),
+        )
+    });
+    let mut results = repo?;
+    results.extend(aur?);
     sort_results(&mut results, &normalized_terms);
     Ok(results)
 }
@@ -117,6 +205,7 @@ where
 fn installed_packages() -> Result<HashSet<String>> {
     let output = Command::new("pacman")
         .arg("-Qq")
+        .logged()
         .output()
         .map_err(|e| anyhow!("Failed to get installed packages: {}", e))?;
 
@@ -205,6 +294,8 @@ fn search_repo_archive(
             repo: record.repo,
             description: record.description,
             installed: installed.contains(&record.name),
+            votes: None,
+            popularity: None,
         });
     }
 
@@ -283,6 +374,8 @@ fn search_aur_packages(terms: &[String], installed: &HashSet<String>) -> Result<
                     source: PackageSource::Aur,
                     repo: "aur".to_string(),
                     description: pkg.description.unwrap_or_default(),
+                    votes: Some(pkg.votes),
+                    popularity: Some(pkg.popularity),
                 })
                 .collect())
         },
@@ -298,7 +391,17 @@ fn aur_search_seed(terms: &[String]) -> Option<String> {
     Some(seed.to_string())
 }
 
-fn matches_terms(name: &str, description: &str, terms: &[String]) -> bool {
+/// Lowercase, trimmed, non-empty search terms
+pub fn normalize_terms(terms: &[String]) -> Vec<String> {
+    terms
+        .iter()
+        .map(|term| term.trim().to_ascii_lowercase())
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+/// True when every (normalized) term appears in the name or description
+pub fn matches_terms(name: &str, description: &str, terms: &[String]) -> bool {
     let haystack = format!(
         "{} {}",
         name.to_ascii_lowercase(),
@@ -341,6 +444,25 @@ fn result_sort_key(result: &SearchResult, query: &str) -> (u8, u8, bool) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_info_response_keeps_the_names_asked_for() {
+        let body = r#"{"resultcount":2,"type":"multiinfo","version":5,"results":[
+            {"Name":"paru-bin","Version":"2.0.4-1","Description":"Feature packed AUR helper",
+             "NumVotes":150,"Popularity":4.2,"Maintainer":null,"Depends":["git","pacman"]},
+            {"Name":"yay","Version":"12.4.2-1","Description":null}]}"#;
+        let names = vec!["paru-bin".to_string(), "paru-typo".to_string()];
+        let infos = parse_info_response(body, &names).unwrap();
+        assert_eq!(infos.len(), 1);
+        let paru = &infos["paru-bin"];
+        assert_eq!(paru.version, "2.0.4-1");
+        assert_eq!(paru.maintainer, None);
+        assert_eq!(paru.depends, ["git", "pacman"]);
+
+        let empty = r#"{"resultcount":0,"type":"multiinfo","version":5,"results":[]}"#;
+        assert!(parse_info_response(empty, &names).unwrap().is_empty());
+        assert!(parse_info_response("<html>502</html>", &names).is_err());
+    }
+
     #[test]
     fn test_parse_sync_desc() {
         let desc = r#"%NAME%
@@ -360,6 +482,18 @@ The GNU Bourne Again shell
         assert_eq!(package.description, "The GNU Bourne Again shell");
     }
 
+    #[test]
+    fn test_aur_info_response_parses() {
+        let payload = r#"{"resultcount":1,"results":[{"Name":"paru","Version":"2.0.3-1",
+            "Description":"Feature packed AUR helper","Maintainer":"Morganamilo",
+            "NumVotes":1200,"Popularity":25.4,"OutOfDate":null,"Depends":["git","pacman"]}]}"#;
+        let response: AurSearchResponse = serde_json::from_str(payload).unwrap();
+        let pkg = &response.results[0];
+        assert_eq!(pkg.maintainer.as_deref(), Some("Morganamilo"));
+        assert_eq!(pkg.out_of_date, None);
+        assert_eq!(pkg.depends, vec!["git", "pacman"]);
+    }
+
     #[test]
     fn test_matches_terms() {
         assert!(matches_terms(
@@ -1,6 +1,8 @@
 use anyhow::{Result, anyhow};
 use std::process::Command;
 
+use crate::internal::logging::LogCommand;
+
 /// Result of service configuration operations
 #[derive(Debug)]
 pub struct ServiceResult {
@@ -10,6 +12,25 @@ pub struct ServiceResult {
     pub failed_services: Vec<String>,
 }
 
+/// Current systemd state of a configured service
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub enabled: bool,
+    pub active: bool,
+}
+
+impl ServiceStatus {
+    /// Short description such as `disabled, inactive`
+    pub fn describe(&self) -> String {
+        format!(
+            "{}, {}",
+            if self.enabled { "enabled" } else { "disabled" },
+            if self.active { "active" } else { "inactive" }
+        )
+    }
+}
+
 /// Ensure all specified services are configured (enabled and started)
 fn check_enabled(service: &str) -> Result<bool> {
     let status = Command::new("sudo")
@@ -17,6 +38,7 @@ fn check_enabled(service: &str) -> Result<bool> {
         .arg("is-enabled")
         .arg("--quiet")
         .arg(service)
+        .logged()
         .status()
         .map_err(|e| anyhow!("Failed to run systemctl is-enabled for {}: {}", service, e))?;
     Ok(status.success())
@@ -28,11 +50,45 @@ fn check_active(service: &str) -> Result<bool> {
         .arg("is-active")
         .arg("--quiet")
         .arg(service)
+        .logged()
         .status()
         .map_err(|e| anyhow!("Failed to run systemctl is-active for {}: {}", service, e))?;
     Ok(status.success())
 }
 
+/// Stop a service and disable it at boot
+pub fn disable_service(service: &str) -> Result<()> {
+    let status = Command::new("sudo")
+        .args(["systemctl", "disable", "--now", service])
+        .logged()
+        .status()
+        .map_err(|e| anyhow!("Failed to run systemctl disable for {}: {}", service, e))?;
+    if !status.success() {
+        return Err(anyhow!("systemctl disable --now {} failed", service));
+    }
+    Ok(())
+}
+
+/// Query a service's state without changing it. Runs without sudo; a service that
+/// cannot be queried is reported as disabled and inactive.
+pub fn query_service_status(service: &str) -> ServiceStatus {
+    let check = |verb: &str| {
+        Command::new("systemctl")
+            .args([verb, "--quiet", service])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .logged()
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    };
+    ServiceStatus {
+        name: service.to_string(),
+        enabled: check("is-enabled"),
+        active: check("is-active"),
+    }
+}
+
 pub fn ensure_services_configured(services: &[String]) -> Result<ServiceResult> {
     if services.is_empty() {
         return Ok(ServiceResult {
@@ -58,6 +114,7 @@ pub fn ensure_services_configured(services: &[String]) -> Result<ServiceResult>
                     .arg("systemctl")
                     .arg("enable")
                     .arg(service)
+                    .logged()
                     .status()
                 {
                     Ok(status) if status.success() => {
@@ -98,6 +155,7 @@ pub fn ensure_services_configured(services: &[String]) -> Result<ServiceResult>
                     .arg("systemctl")
                     .arg("start")
                     .arg(service)
+                    .logged()
                     .status()
                 {
                     Ok(status) if status.success() => {
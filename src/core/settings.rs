@@ -0,0 +1,358 @@
+//! User settings loaded from ~/.owl/settings.toml
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Behavior toggles that are not part of the declarative package config
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Page long output through $PAGER when stdout is a terminal
+    pub pager: bool,
+    /// Output color theme
+    pub colors: ColorSettings,
+    /// Retention for `owl clean`
+    pub clean: CleanSettings,
+    /// Retention for `owl history`
+    pub history: HistorySettings,
+    /// AUR update check caching
+    pub aur: AurSettings,
+    /// Commit the config files owl edits when the owl directory is a git repository
+    pub git_autocommit: bool,
+    /// Mention explicitly installed packages the config does not manage after apply
+    /// and in `owl status`
+    pub unmanaged_notice: bool,
+    /// Remove orphaned dependencies at the end of every apply, as `--clean-orphans` does
+    pub clean_orphans: bool,
+    /// Stop an apply at its first error, as `--fail-fast` does
+    pub fail_fast: bool,
+    /// Unattended runs from `owl schedule`
+    pub schedule: ScheduleSettings,
+    /// When an apply ends with a desktop notification
+    pub notify: NotifyWhen,
+    /// Machines `owl fleet` applies to over SSH
+    pub fleet: FleetSettings,
+    /// Arch news checks before upgrades
+    pub news: NewsSettings,
+    /// The disposable environment `owl test-apply` runs in
+    pub test_apply: TestApplySettings,
+    /// Dotfile directories `owl adopt --with-configs` copies
+    pub adopt: AdoptSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            pager: true,
+            colors: ColorSettings::default(),
+            clean: CleanSettings::default(),
+            history: HistorySettings::default(),
+            aur: AurSettings::default(),
+            git_autocommit: false,
+            unmanaged_notice: true,
+            clean_orphans: false,
+            fail_fast: false,
+            schedule: ScheduleSettings::default(),
+            notify: NotifyWhen::Off,
+            fleet: FleetSettings::default(),
+            news: NewsSettings::default(),
+            test_apply: TestApplySettings::default(),
+            adopt: AdoptSettings::default(),
+        }
+    }
+}
+
+/// The `[clean]` section: how much `owl clean` keeps
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CleanSettings {
+    /// Newest snapshots to keep
+    pub keep_snapshots: usize,
+    /// Newest cached versions of each package to keep, besides the installed one
+    pub keep_package_versions: usize,
+}
+
+impl Default for CleanSettings {
+    fn default() -> Self {
+        Self {
+            keep_snapshots: 5,
+            keep_package_versions: 3,
+        }
+    }
+}
+
+/// The `[history]` section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HistorySettings {
+    /// Newest run records to keep
+    pub keep_runs: usize,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self { keep_runs: 200 }
+    }
+}
+
+/// The `[adopt]` section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AdoptSettings {
+    /// Config directories larger than this many MiB are only copied after a yes
+    pub confirm_config_mib: u64,
+}
+
+impl Default for AdoptSettings {
+    fn default() -> Self {
+        Self {
+            confirm_config_mib: 10,
+        }
+    }
+}
+
+/// The `[aur]` section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AurSettings {
+    /// Minutes an AUR update check stays valid; 0 checks every time
+    pub cache_minutes: u64,
+}
+
+impl Default for AurSettings {
+    fn default() -> Self {
+        Self { cache_minutes: 15 }
+    }
+}
+
+/// The `[news]` section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct NewsSettings {
+    /// Hold back upgrades while Arch news items are unread
+    pub check: bool,
+    /// Minutes a fetched feed is reused
+    pub cache_minutes: u64,
+}
+
+impl Default for NewsSettings {
+    fn default() -> Self {
+        Self {
+            check: false,
+            cache_minutes: 60,
+        }
+    }
+}
+
+/// The `[test_apply]` section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TestApplySettings {
+    pub runtime: SandboxRuntime,
+    /// Image for docker and podman; for nspawn, the directory of an Arch root
+    /// filesystem, e.g. one made with `pacstrap`
+    pub image: String,
+}
+
+impl Default for TestApplySettings {
+    fn default() -> Self {
+        Self {
+            runtime: SandboxRuntime::Docker,
+            image: "archlinux:latest".to_string(),
+        }
+    }
+}
+
+/// What `owl test-apply` starts its disposable environment with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SandboxRuntime {
+    #[default]
+    Docker,
+    Podman,
+    /// `systemd-nspawn --ephemeral` on a root filesystem directory
+    Nspawn,
+}
+
+/// The `notify` setting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyWhen {
+    #[default]
+    Off,
+    /// When packages or dotfiles changed, or the run failed
+    OnChange,
+    /// When the run failed or warned
+    OnError,
+    Always,
+}
+
+/// The `[schedule]` section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScheduleSettings {
+    /// How often the timer fires unless `--interval` is given, e.g. `6h`
+    pub interval: String,
+    /// Arguments the scheduled service passes to owl
+    pub args: Vec<String>,
+}
+
+impl Default for ScheduleSettings {
+    fn default() -> Self {
+        Self {
+            interval: "6h".to_string(),
+            args: vec!["--non-interactive".to_string(), "apply".to_string()],
+        }
+    }
+}
+
+/// The `[fleet]` section
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FleetSettings {
+    /// `[fleet.hosts.<name>]` tables, by the name `--host` takes
+    pub hosts: BTreeMap<String, FleetHost>,
+}
+
+/// One machine of the fleet
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FleetHost {
+    /// Host name or address ssh connects to
+    pub address: String,
+    /// Login user; ssh's default when unset
+    #[serde(default)]
+    pub user: Option<String>,
+    /// The owl directory on the machine, relative to its home unless absolute
+    #[serde(default = "default_fleet_config_dir")]
+    pub config_dir: String,
+    /// Labels `--tag` selects hosts by
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_fleet_config_dir() -> String {
+    crate::internal::constants::OWL_DIR.to_string()
+}
+
+/// The `[colors]` section: a preset plus per-role overrides. Values are color names,
+/// 256-color numbers, or "none"; they are resolved by `internal::color`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ColorSettings {
+    pub preset: Option<String>,
+    pub error: Option<String>,
+    pub warn: Option<String>,
+    pub info: Option<String>,
+    pub success: Option<String>,
+    pub highlight: Option<String>,
+}
+
+impl Settings {
+    /// Parse settings from TOML content; missing keys keep their defaults
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content).context("Failed to parse settings")
+    }
+
+    /// Load settings from a file, returning defaults when it does not exist
+    pub fn load_from_path(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read settings file: {}", path.display()))?;
+        Self::parse(&content).with_context(|| path.display().to_string())
+    }
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Settings for this process, loaded once. An unreadable settings file is
+/// reported as a warning and the defaults are used instead.
+pub fn get() -> &'static Settings {
+    SETTINGS.get_or_init(|| {
+        let path = match crate::internal::files::owl_dir() {
+            Ok(owl) => owl.join(crate::internal::constants::SETTINGS_FILE),
+            Err(_) => return Settings::default(),
+        };
+        Settings::load_from_path(&path).unwrap_or_else(|err| {
+            // The theme is built from these settings, so this warning uses the default colors
+            eprintln!(
+                "  {} {:#}; using default settings",
+                crate::internal::color::colorize("!", crate::internal::color::Color::Yellow),
+                err
+            );
+            Settings::default()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings_defaults_and_overrides() {
+        assert_eq!(Settings::parse("").unwrap(), Settings::default());
+        assert!(!Settings::parse("pager = false\n").unwrap().pager);
+        assert!(
+            Settings::parse("git_autocommit = true\n")
+                .unwrap()
+                .git_autocommit
+        );
+        assert!(
+            !Settings::parse("unmanaged_notice = false\n")
+                .unwrap()
+                .unmanaged_notice
+        );
+        assert!(
+            Settings::parse("clean_orphans = true\n")
+                .unwrap()
+                .clean_orphans
+        );
+        assert!(Settings::parse("fail_fast = true\n").unwrap().fail_fast);
+        assert!(Settings::parse("pager = \"yes\"\n").is_err());
+        assert_eq!(
+            Settings::parse("notify = \"on-change\"\n").unwrap().notify,
+            NotifyWhen::OnChange
+        );
+
+        let settings = Settings::parse("[colors]\npreset = \"mono\"\nerror = \"red\"\n").unwrap();
+        assert_eq!(settings.colors.preset.as_deref(), Some("mono"));
+        assert_eq!(settings.colors.error.as_deref(), Some("red"));
+        assert!(settings.pager);
+
+        let settings = Settings::parse("[clean]\nkeep_snapshots = 1\n").unwrap();
+        assert_eq!(settings.clean.keep_snapshots, 1);
+        assert_eq!(settings.clean.keep_package_versions, 3);
+        let settings = Settings::parse("[history]\nkeep_runs = 10\n").unwrap();
+        assert_eq!(settings.history.keep_runs, 10);
+        let settings = Settings::parse("[adopt]\nconfirm_config_mib = 1\n").unwrap();
+        assert_eq!(settings.adopt.confirm_config_mib, 1);
+        let settings = Settings::parse("[aur]\ncache_minutes = 0\n").unwrap();
+        assert_eq!(settings.aur.cache_minutes, 0);
+        let settings = Settings::parse("[news]\ncheck = true\n").unwrap();
+        assert!(settings.news.check);
+        assert_eq!(settings.news.cache_minutes, 60);
+        let settings = Settings::parse("[schedule]\ninterval = \"1d\"\n").unwrap();
+        assert_eq!(settings.schedule.interval, "1d");
+        assert_eq!(settings.schedule.args, ScheduleSettings::default().args);
+
+        let settings = Settings::parse(
+            "[fleet.hosts.nas]\naddress = \"10.0.0.5\"\nuser = \"root\"\ntags = [\"media\"]\n",
+        )
+        .unwrap();
+        let nas = &settings.fleet.hosts["nas"];
+        assert_eq!(nas.address, "10.0.0.5");
+        assert_eq!(nas.user.as_deref(), Some("root"));
+        assert_eq!(nas.config_dir, ".owl");
+        assert_eq!(nas.tags, ["media"]);
+        assert!(Settings::parse("[fleet.hosts.nas]\nuser = \"root\"\n").is_err());
+
+        let settings = Settings::parse("[test_apply]\nruntime = \"podman\"\n").unwrap();
+        assert_eq!(settings.test_apply.runtime, SandboxRuntime::Podman);
+        assert_eq!(settings.test_apply.image, "archlinux:latest");
+    }
+}
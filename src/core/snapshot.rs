@@ -0,0 +1,300 @@
+//! Named snapshots of the managed surface: installed packages, package state, and the
+//! deployed dotfiles
+//!
+//! Each snapshot is a directory holding `snapshot.json` and a `files/` store with the
+//! content of every deployed dotfile, keyed by its SHA-256, so a restore can put the
+//! exact bytes back.
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::Config;
+use crate::core::state::PackageState;
+use crate::internal::constants;
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const FILES_DIR: &str = "files";
+
+/// What the system looked like when the snapshot was taken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    /// Every installed package, name to version
+    pub packages: BTreeMap<String, String>,
+    pub state: PackageState,
+    /// SHA-256 of each deployed dotfile, keyed by absolute path
+    pub dotfiles: BTreeMap<String, String>,
+}
+
+/// How a deployed dotfile differs from the snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DotfileChange {
+    Changed,
+    Missing,
+    Added,
+}
+
+/// Differences between a snapshot and the current system
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotDiff {
+    /// In the snapshot but no longer installed, with the snapshot version
+    pub missing: Vec<(String, String)>,
+    /// Installed since the snapshot, with the current version
+    pub added: Vec<(String, String)>,
+    /// Installed then and now at different versions: (name, then, now)
+    pub changed: Vec<(String, String, String)>,
+    pub dotfiles: Vec<(String, DotfileChange)>,
+    pub state_changed: bool,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty()
+            && self.added.is_empty()
+            && self.changed.is_empty()
+            && self.dotfiles.is_empty()
+            && !self.state_changed
+    }
+}
+
+/// Snapshots live in the state directory, or beside the `--state-file` override
+pub fn snapshots_dir() -> Result<PathBuf> {
+    PackageState::sibling_path(constants::SNAPSHOTS_DIR)
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "invalid snapshot name '{}'; use letters, digits, '-', '_' and '.'",
+            name
+        ))
+    }
+}
+
+/// Where the content of a dotfile with hash `hash` is kept in snapshot `name`
+pub fn stored_file(name: &str, hash: &str) -> Result<PathBuf> {
+    Ok(snapshots_dir()?.join(name).join(FILES_DIR).join(hash))
+}
+
+/// Hashes of the dotfiles deployed for `config` right now, plus any of `also` that exist
+pub fn deployed_hashes(config: &Config, also: &[String]) -> Result<BTreeMap<String, String>> {
+    let mut paths = Vec::new();
+    for mapping in crate::core::dotfiles::get_dotfile_mappings(config) {
+        paths.extend(crate::core::dotfiles::deployed_files(&mapping)?);
+    }
+    paths.extend(also.iter().map(PathBuf::from).filter(|path| path.is_file()));
+
+    let mut hashes = BTreeMap::new();
+    for path in paths {
+        let hash = crate::core::dotfiles::sha256_file(&path)?;
+        hashes.insert(path.display().to_string(), hash);
+    }
+    Ok(hashes)
+}
+
+/// Capture the current system as snapshot `name`
+pub fn create(name: &str) -> Result<Snapshot> {
+    validate_name(name)?;
+    let dir = snapshots_dir()?.join(name);
+    if dir.exists() {
+        return Err(anyhow!(
+            "a snapshot named '{}' already exists; pick another name",
+            name
+        ));
+    }
+
+    let config = Config::load_all_relevant_config_files().context("Failed to load config")?;
+    let snapshot = Snapshot {
+        name: name.to_string(),
+        created_at: crate::core::history::now(),
+        packages: crate::core::pm::ParuPacman::new().installed_versions()?,
+        state: PackageState::load().context("Failed to load package state")?,
+        dotfiles: deployed_hashes(&config, &[])?,
+    };
+
+    // Build the snapshot next to its final place so a failure never leaves half of one
+    let partial = dir.with_file_name(format!(".{}.partial", name));
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)
+            .with_context(|| format!("Failed to clear {}", partial.display()))?;
+    }
+    let files = partial.join(FILES_DIR);
+    std::fs::create_dir_all(&files)
+        .with_context(|| format!("Failed to create {}", files.display()))?;
+    for (path, hash) in &snapshot.dotfiles {
+        std::fs::copy(path, files.join(hash))
+            .with_context(|| format!("Failed to back up {}", path))?;
+    }
+    let content =
+        serde_json::to_string_pretty(&snapshot).context("Failed to serialize snapshot")?;
+    std::fs::write(partial.join(SNAPSHOT_FILE), content + "\n")
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+    std::fs::rename(&partial, &dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(snapshot)
+}
+
+/// Load snapshot `name`
+pub fn load(name: &str) -> Result<Snapshot> {
+    validate_name(name)?;
+    let path = snapshots_dir()?.join(name).join(SNAPSHOT_FILE);
+    if !path.exists() {
+        return Err(anyhow!(
+            "no snapshot named '{}'; see `owl snapshot list`",
+            name
+        ));
+    }
+    read(&path)
+}
+
+fn read(path: &Path) -> Result<Snapshot> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// All snapshots, oldest first
+pub fn list() -> Result<Vec<Snapshot>> {
+    let dir = snapshots_dir()?;
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in
+        std::fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let entry = entry?;
+        // Skips `.<name>.partial` leftovers of an interrupted create
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path().join(SNAPSHOT_FILE);
+        if path.is_file() {
+            snapshots.push(read(&path)?);
+        }
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.created_at);
+    Ok(snapshots)
+}
+
+/// Compare a snapshot with the current packages, dotfile hashes, and state
+pub fn compare(
+    snapshot: &Snapshot,
+    packages: &BTreeMap<String, String>,
+    dotfiles: &BTreeMap<String, String>,
+    state: &PackageState,
+) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+    for (name, then) in &snapshot.packages {
+        match packages.get(name) {
+            None => diff.missing.push((name.clone(), then.clone())),
+            Some(now) if now != then => {
+                diff.changed.push((name.clone(), then.clone(), now.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    diff.added = packages
+        .iter()
+        .filter(|(name, _)| !snapshot.packages.contains_key(*name))
+        .map(|(name, version)| (name.clone(), version.clone()))
+        .collect();
+
+    for (path, hash) in &snapshot.dotfiles {
+        match dotfiles.get(path) {
+            None => diff.dotfiles.push((path.clone(), DotfileChange::Missing)),
+            Some(now) if now != hash => diff.dotfiles.push((path.clone(), DotfileChange::Changed)),
+            Some(_) => {}
+        }
+    }
+    diff.dotfiles.extend(
+        dotfiles
+            .keys()
+            .filter(|path| !snapshot.dotfiles.contains_key(*path))
+            .map(|path| (path.clone(), DotfileChange::Added)),
+    );
+    diff.dotfiles.sort();
+
+    diff.state_changed = &snapshot.state != state;
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_reports_each_kind_of_change() {
+        let state = PackageState {
+            untracked: Vec::new(),
+            hidden: Vec::new(),
+            managed: vec!["htop".to_string()],
+            pinned: Default::default(),
+            sources: Default::default(),
+            remove_hooks: Default::default(),
+        };
+        let snapshot = Snapshot {
+            name: "before".to_string(),
+            created_at: 0,
+            packages: map(&[("htop", "3.3.0-1"), ("vim", "9.1-1"), ("git", "2.45-1")]),
+            state: state.clone(),
+            dotfiles: map(&[("/h/.vimrc", "aa"), ("/h/.gitconfig", "bb")]),
+        };
+
+        let diff = compare(
+            &snapshot,
+            &map(&[("htop", "3.3.0-2"), ("git", "2.45-1"), ("plasma", "6.0-1")]),
+            &map(&[("/h/.gitconfig", "cc"), ("/h/.bashrc", "dd")]),
+            &state,
+        );
+        assert_eq!(diff.missing, vec![("vim".to_string(), "9.1-1".to_string())]);
+        assert_eq!(
+            diff.added,
+            vec![("plasma".to_string(), "6.0-1".to_string())]
+        );
+        assert_eq!(
+            diff.changed,
+            vec![(
+                "htop".to_string(),
+                "3.3.0-1".to_string(),
+                "3.3.0-2".to_string()
+            )]
+        );
+        assert_eq!(
+            diff.dotfiles,
+            vec![
+                ("/h/.bashrc".to_string(), DotfileChange::Added),
+                ("/h/.gitconfig".to_string(), DotfileChange::Changed),
+                ("/h/.vimrc".to_string(), DotfileChange::Missing),
+            ]
+        );
+        assert!(!diff.state_changed);
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("pre-kde-switch").is_ok());
+        assert!(validate_name("2024.05_01").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("../escape").is_err());
+        assert!(validate_name(".hidden").is_err());
+    }
+}
@@ -3,9 +3,12 @@
 use crate::internal::constants;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Generic trait for state persistence operations
 trait StatePersistence<T> {
@@ -17,15 +20,27 @@ trait StatePersistence<T> {
 
     fn load(state_dir: &Path) -> Result<T> {
         let file_path = state_dir.join(Self::FILE_NAME);
+        if !file_path.exists() && read_only() {
+            return Ok(Self::DEFAULT_VALUE());
+        }
         if !file_path.exists() {
+            log::debug!("state: creating {} with defaults", file_path.display());
             let default = Self::DEFAULT_VALUE();
             Self::save(state_dir, &default)?;
             return Ok(default);
         }
 
-        let content = fs::read_to_string(&file_path)
-            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", Self::FILE_NAME, e))?;
-        Self::deserialize(&content)
+        let content = fs::read_to_string(&file_path).map_err(|e| StateError {
+            path: file_path.clone(),
+            message: format!("Failed to read {}: {}", Self::FILE_NAME, e),
+        })?;
+        Self::deserialize(&content).map_err(|e| {
+            StateError {
+                path: file_path,
+                message: e.to_string(),
+            }
+            .into()
+        })
     }
 
     fn save(state_dir: &Path, data: &T) -> Result<()> {
@@ -38,6 +53,21 @@ trait StatePersistence<T> {
     }
 }
 
+/// A state file that exists but could not be read or understood
+#[derive(Debug)]
+pub struct StateError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl std::fmt::Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for StateError {}
+
 /// Default system packages that should not be tracked
 fn default_untracked_packages() -> Vec<String> {
     vec![
@@ -73,11 +103,22 @@ fn default_untracked_packages() -> Vec<String> {
 }
 
 /// Package state information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PackageState {
     pub untracked: Vec<String>,
     pub hidden: Vec<String>,
     pub managed: Vec<String>,
+    /// Packages held at a version by `owl pin`, name to pinned version
+    #[serde(default)]
+    pub pinned: BTreeMap<String, String>,
+    /// The config file adopt wrote each managed package to, name to path; packages
+    /// managed before this was recorded, or seeded from the config, have no entry
+    #[serde(default)]
+    pub sources: BTreeMap<String, String>,
+    /// The `:pre_remove` and `:post_remove` hooks of managed packages, recorded while
+    /// they are declared so they still run once the config entry is gone
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub remove_hooks: HooksByPackage,
 }
 
 /// Specific implementation for untracked packages (JSON format)
@@ -136,14 +177,151 @@ impl StatePersistence<Vec<String>> for ManagedPackages {
     }
 }
 
+/// Specific implementation for managed package sources (JSON format)
+struct ManagedSources;
+
+impl StatePersistence<BTreeMap<String, String>> for ManagedSources {
+    const FILE_NAME: &'static str = "sources.json";
+    const DEFAULT_VALUE: fn() -> BTreeMap<String, String> = BTreeMap::new;
+
+    fn serialize(data: &BTreeMap<String, String>) -> Result<String> {
+        serde_json::to_string_pretty(data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize managed package sources: {}", e))
+    }
+
+    fn deserialize(content: &str) -> Result<BTreeMap<String, String>> {
+        serde_json::from_str(content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse managed package sources JSON: {}", e))
+    }
+}
+
+/// Specific implementation for remembered remove hooks (JSON format)
+struct RemoveHooks;
+
+type HooksByPackage = BTreeMap<String, crate::core::hooks::Hooks>;
+
+impl StatePersistence<HooksByPackage> for RemoveHooks {
+    const FILE_NAME: &'static str = "remove_hooks.json";
+    const DEFAULT_VALUE: fn() -> HooksByPackage = BTreeMap::new;
+
+    fn serialize(data: &HooksByPackage) -> Result<String> {
+        serde_json::to_string_pretty(data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize remove hooks: {}", e))
+    }
+
+    fn deserialize(content: &str) -> Result<HooksByPackage> {
+        serde_json::from_str(content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse remove hooks JSON: {}", e))
+    }
+}
+
+/// Specific implementation for pinned packages (JSON format)
+struct PinnedPackages;
+
+impl StatePersistence<BTreeMap<String, String>> for PinnedPackages {
+    const FILE_NAME: &'static str = "pinned.json";
+    const DEFAULT_VALUE: fn() -> BTreeMap<String, String> = BTreeMap::new;
+
+    fn serialize(data: &BTreeMap<String, String>) -> Result<String> {
+        serde_json::to_string_pretty(data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize pinned packages: {}", e))
+    }
+
+    fn deserialize(content: &str) -> Result<BTreeMap<String, String>> {
+        serde_json::from_str(content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse pinned packages JSON: {}", e))
+    }
+}
+
+static STATE_FILE_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Refuse to write state for the rest of the process. Read-only commands call this so
+/// that a stray save fails instead of quietly changing the system; loading still works
+/// and missing files are not created.
+pub fn forbid_writes() {
+    READ_ONLY.store(true, Ordering::Relaxed);
+}
+
+/// Whether `forbid_writes` was called, for files beside the state that skip their
+/// save instead of failing
+pub fn read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// Keep all state in the single JSON file `path` instead of ~/.owl/.state (`--state-file`)
+pub fn set_state_file(path: &Path) -> Result<()> {
+    let path = std::path::absolute(path)
+        .map_err(|e| anyhow::anyhow!("Invalid state file '{}': {}", path.display(), e))?;
+    log::debug!("state file overridden: {}", path.display());
+    STATE_FILE_OVERRIDE.set(path).ok();
+    Ok(())
+}
+
+/// The `--state-file` path, when state is kept in a single file
+pub fn state_file_override() -> Option<&'static Path> {
+    STATE_FILE_OVERRIDE.get().map(PathBuf::as_path)
+}
+
+impl PackageState {
+    fn load_file(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            let state = PackageState {
+                untracked: default_untracked_packages(),
+                hidden: Vec::new(),
+                managed: Vec::new(),
+                pinned: BTreeMap::new(),
+                sources: BTreeMap::new(),
+                remove_hooks: BTreeMap::new(),
+            };
+            if !read_only() {
+                log::debug!("state: creating {} with defaults", path.display());
+                state.save_file(path)?;
+            }
+            return Ok(state);
+        }
+        let content = fs::read_to_string(path).map_err(|e| StateError {
+            path: path.to_path_buf(),
+            message: format!("Failed to read state file: {}", e),
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            StateError {
+                path: path.to_path_buf(),
+                message: format!("Failed to parse state JSON: {}", e),
+            }
+            .into()
+        })
+    }
+
+    fn save_file(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| anyhow::anyhow!("Failed to create state directory: {}", e))?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize state: {}", e))?;
+        fs::write(path, content + "\n")
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
 // Some methods are part of the public API for future use (e.g., CLI commands for managing
 // hidden/untracked packages). They are tested but not yet used in the main application.
 #[allow(dead_code)]
 impl PackageState {
-    /// Load package state from ~/.owl/.state directory
+    /// Load package state from ~/.owl/.state directory, or the `--state-file` override
     pub fn load() -> Result<Self> {
+        if let Some(path) = STATE_FILE_OVERRIDE.get() {
+            let state = Self::load_file(path)?;
+            log::debug!(
+                "state: loaded {} managed from {}",
+                state.managed.len(),
+                path.display()
+            );
+            return Ok(state);
+        }
         let state_dir = Self::get_state_dir()?;
-        if !state_dir.exists() {
+        if !state_dir.exists() && !read_only() {
             fs::create_dir_all(&state_dir)
                 .map_err(|e| anyhow::anyhow!("Failed to create state directory: {}", e))?;
         }
@@ -152,16 +330,39 @@ impl PackageState {
         let untracked = UntrackedPackages::load(&state_dir)?;
         let hidden = HiddenPackages::load(&state_dir)?;
         let managed = ManagedPackages::load(&state_dir)?;
-
+        let pinned = PinnedPackages::load(&state_dir)?;
+        let sources = ManagedSources::load(&state_dir)?;
+        let remove_hooks = RemoveHooks::load(&state_dir)?;
+
+        log::debug!(
+            "state: loaded {} managed, {} untracked, {} hidden from {}",
+            managed.len(),
+            untracked.len(),
+            hidden.len(),
+            state_dir.display()
+        );
         Ok(PackageState {
             untracked,
             hidden,
             managed,
+            pinned,
+            sources,
+            remove_hooks,
         })
     }
 
     /// Save package state to disk
     pub fn save(&self) -> Result<()> {
+        if read_only() {
+            return Err(anyhow::anyhow!(
+                "refusing to save package state during a read-only command"
+            ));
+        }
+        if let Some(path) = STATE_FILE_OVERRIDE.get() {
+            self.save_file(path)?;
+            log::debug!("state: saved to {}", path.display());
+            return Ok(());
+        }
         let state_dir = Self::get_state_dir()?;
         if !state_dir.exists() {
             fs::create_dir_all(&state_dir)
@@ -172,6 +373,10 @@ impl PackageState {
         UntrackedPackages::save(&state_dir, &self.untracked)?;
         HiddenPackages::save(&state_dir, &self.hidden)?;
         ManagedPackages::save(&state_dir, &self.managed)?;
+        PinnedPackages::save(&state_dir, &self.pinned)?;
+        ManagedSources::save(&state_dir, &self.sources)?;
+        RemoveHooks::save(&state_dir, &self.remove_hooks)?;
+        log::debug!("state: saved to {}", state_dir.display());
         Ok(())
     }
 
@@ -193,6 +398,7 @@ impl PackageState {
     /// Add a package to the untracked list
     pub fn add_untracked(&mut self, package: String) {
         if !self.untracked.contains(&package) {
+            log::debug!("state: untracked += {}", package);
             self.untracked.push(package);
             self.untracked.sort();
         }
@@ -200,12 +406,41 @@ impl PackageState {
 
     /// Remove a package from the untracked list
     pub fn remove_untracked(&mut self, package: &str) {
+        if self.untracked.iter().any(|p| p == package) {
+            log::debug!("state: untracked -= {}", package);
+        }
         self.untracked.retain(|p| p != package);
     }
 
+    /// Stop offering a package for adoption: untracked, and no longer managed
+    pub fn ignore(&mut self, package: String) {
+        self.remove_managed(&package);
+        self.add_untracked(package);
+    }
+
+    /// Take `packages` off the untracked list; returns those that were on it and those
+    /// that were not. Asking again for the same names removes nothing more.
+    pub fn unignore(&mut self, packages: &[String]) -> (Vec<String>, Vec<String>) {
+        let (removed, not_found): (Vec<String>, Vec<String>) = packages
+            .iter()
+            .cloned()
+            .partition(|package| self.is_untracked(package));
+        for package in &removed {
+            self.remove_untracked(package);
+        }
+        (removed, not_found)
+    }
+
+    /// Take every package off the untracked list, returning them
+    pub fn unignore_all(&mut self) -> Vec<String> {
+        log::debug!("state: untracked cleared");
+        std::mem::take(&mut self.untracked)
+    }
+
     /// Add a package to the hidden list
     pub fn add_hidden(&mut self, package: String) {
         if !self.hidden.contains(&package) {
+            log::debug!("state: hidden += {}", package);
             self.hidden.push(package);
             self.hidden.sort();
         }
@@ -213,28 +448,137 @@ impl PackageState {
 
     /// Remove a package from the hidden list
     pub fn remove_hidden(&mut self, package: &str) {
+        if self.hidden.iter().any(|p| p == package) {
+            log::debug!("state: hidden -= {}", package);
+        }
         self.hidden.retain(|p| p != package);
     }
 
     /// Add a package to the managed list
     pub fn add_managed(&mut self, package: String) {
         if !self.managed.contains(&package) {
+            log::debug!("state: managed += {}", package);
             self.managed.push(package);
             self.managed.sort();
         }
     }
 
+    /// Add a package to the managed list, remembering the config file that declares it
+    pub fn add_managed_from(&mut self, package: String, source: &str) {
+        log::debug!("state: {} declared in {}", package, source);
+        self.sources.insert(package.clone(), source.to_string());
+        self.add_managed(package);
+    }
+
+    /// The config file adopt wrote `package` to, if that was recorded
+    pub fn source(&self, package: &str) -> Option<&str> {
+        self.sources.get(package).map(String::as_str)
+    }
+
     /// Remove a package from the managed list
     pub fn remove_managed(&mut self, package: &str) {
+        if self.managed.iter().any(|p| p == package) {
+            log::debug!("state: managed -= {}", package);
+        }
         self.managed.retain(|p| p != package);
+        self.sources.remove(package);
+        self.remove_hooks.remove(package);
+    }
+
+    /// Record the remove hooks of the managed packages `config` declares, so they are
+    /// known when the package is removed after leaving the config. Returns whether
+    /// anything changed.
+    pub fn record_remove_hooks(&mut self, config: &crate::core::config::Config) -> bool {
+        let mut changed = false;
+        for name in &self.managed {
+            let Some(package) = config.packages.get(name) else {
+                continue;
+            };
+            let hooks = package.hooks.remove_hooks();
+            if hooks.is_empty() {
+                changed |= self.remove_hooks.remove(name).is_some();
+            } else if self.remove_hooks.get(name) != Some(&hooks) {
+                log::debug!("state: recorded remove hooks of {}", name);
+                self.remove_hooks.insert(name.clone(), hooks);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// The version `package` is pinned at, if any
+    pub fn pinned_version(&self, package: &str) -> Option<&str> {
+        self.pinned.get(package).map(String::as_str)
+    }
+
+    /// Names of all pinned packages, sorted
+    pub fn pinned_names(&self) -> Vec<String> {
+        self.pinned.keys().cloned().collect()
+    }
+
+    /// Hold a package at `version`
+    pub fn pin(&mut self, package: String, version: String) {
+        log::debug!("state: pinned += {} {}", package, version);
+        self.pinned.insert(package, version);
+    }
+
+    /// Release a pin, returning the version it held
+    pub fn unpin(&mut self, package: &str) -> Option<String> {
+        let version = self.pinned.remove(package);
+        if version.is_some() {
+            log::debug!("state: pinned -= {}", package);
+        }
+        version
+    }
+
+    /// Where the state lives: the `--state-file` override, or the ~/.owl/.state directory
+    pub fn location() -> Result<PathBuf> {
+        match STATE_FILE_OVERRIDE.get() {
+            Some(path) => Ok(path.clone()),
+            None => Self::get_state_dir(),
+        }
+    }
+
+    /// Where a file or directory kept beside the state lives: `name` inside the state
+    /// directory, or `<stem>.<name>` next to the `--state-file` override
+    pub fn sibling_path(name: &str) -> Result<PathBuf> {
+        Ok(sibling_of(
+            &Self::location()?,
+            state_file_override().is_some(),
+            name,
+        ))
+    }
+
+    /// The directory the files beside the state live in
+    pub fn sibling_dir() -> Result<PathBuf> {
+        Ok(sibling_dir_of(
+            &Self::location()?,
+            state_file_override().is_some(),
+        ))
     }
 
     fn get_state_dir() -> Result<PathBuf> {
-        let home = std::env::var("HOME")
-            .map_err(|_| anyhow::anyhow!("HOME environment variable not set"))?;
-        Ok(PathBuf::from(home)
-            .join(constants::OWL_DIR)
-            .join(constants::STATE_DIR))
+        Ok(crate::internal::files::owl_dir()?.join(constants::STATE_DIR))
+    }
+}
+
+fn sibling_of(state: &Path, single_file: bool, name: &str) -> PathBuf {
+    if single_file {
+        let stem = state
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        state.with_file_name(format!("{}.{}", stem, name))
+    } else {
+        state.join(name)
+    }
+}
+
+fn sibling_dir_of(state: &Path, single_file: bool) -> PathBuf {
+    if single_file {
+        state.parent().map(Path::to_path_buf).unwrap_or_default()
+    } else {
+        state.to_path_buf()
     }
 }
 
@@ -274,5 +618,132 @@ mod tests {
         assert!(state.is_untracked("test-package"));
         state.remove_untracked("test-package");
         assert!(!state.is_untracked("test-package"));
+
+        state.add_managed("test-package".to_string());
+        state.ignore("test-package".to_string());
+        assert!(state.is_untracked("test-package"));
+        assert!(!state.is_managed("test-package"));
+    }
+
+    #[test]
+    fn test_unignore_is_idempotent() {
+        let mut state = PackageState {
+            untracked: vec!["htop".to_string(), "tmux".to_string(), "vim".to_string()],
+            hidden: Vec::new(),
+            managed: Vec::new(),
+            pinned: BTreeMap::new(),
+            sources: BTreeMap::new(),
+            remove_hooks: BTreeMap::new(),
+        };
+        let names = vec!["htop".to_string(), "nope".to_string()];
+
+        let (removed, not_found) = state.unignore(&names);
+        assert_eq!(removed, vec!["htop".to_string()]);
+        assert_eq!(not_found, vec!["nope".to_string()]);
+        assert_eq!(state.untracked, vec!["tmux".to_string(), "vim".to_string()]);
+
+        let (removed, not_found) = state.unignore(&names);
+        assert!(removed.is_empty());
+        assert_eq!(not_found, names);
+
+        assert_eq!(
+            state.unignore_all(),
+            vec!["tmux".to_string(), "vim".to_string()]
+        );
+        assert!(state.unignore_all().is_empty());
+        assert!(state.untracked.is_empty());
+    }
+
+    #[test]
+    fn test_sources_are_optional_and_round_trip() {
+        let _guard = TEST_MUTEX.lock().unwrap();
+        let temp_dir = setup_test_home();
+
+        // State written before sources were recorded
+        let state_dir = temp_dir.path().join(".owl").join(".state");
+        fs::create_dir_all(&state_dir).unwrap();
+        fs::write(state_dir.join("managed.json"), r#"["htop"]"#).unwrap();
+        let mut state = PackageState::load().expect("old state loads");
+        assert!(state.is_managed("htop"));
+        assert_eq!(state.source("htop"), None);
+
+        state.add_managed_from("vim".to_string(), "/home/me/.owl/groups/cli.owl");
+        state.save().unwrap();
+        let loaded = PackageState::load().unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(loaded.source("vim"), Some("/home/me/.owl/groups/cli.owl"));
+
+        let mut loaded = loaded;
+        loaded.remove_managed("vim");
+        assert_eq!(loaded.source("vim"), None);
+
+        let old: PackageState =
+            serde_json::from_str(r#"{"untracked": [], "hidden": [], "managed": ["htop"]}"#)
+                .expect("single-file state without sources parses");
+        assert!(old.sources.is_empty());
+    }
+
+    #[test]
+    fn test_pins_round_trip() {
+        let _guard = TEST_MUTEX.lock().unwrap();
+        let _temp_dir = setup_test_home();
+
+        let mut state = PackageState::load().expect("Failed to load package state");
+        state.pin("firefox".to_string(), "128.0-1".to_string());
+        state.save().expect("Failed to save package state");
+
+        let mut state = PackageState::load().expect("Failed to reload package state");
+        assert_eq!(state.pinned_version("firefox"), Some("128.0-1"));
+        assert_eq!(state.unpin("firefox").as_deref(), Some("128.0-1"));
+        assert_eq!(state.unpin("firefox"), None);
+    }
+
+    #[test]
+    fn test_remove_hooks_outlive_the_config_entry() {
+        let _guard = TEST_MUTEX.lock().unwrap();
+        let _temp_dir = setup_test_home();
+
+        let config = crate::core::config::Config::parse(
+            "@package syncthing\n:post_install systemctl --user enable syncthing\n:pre_remove systemctl --user disable syncthing\n\n@package htop\n",
+        )
+        .unwrap();
+        let mut state = PackageState::load().expect("Failed to load package state");
+        state.add_managed("syncthing".to_string());
+        state.add_managed("htop".to_string());
+        assert!(state.record_remove_hooks(&config));
+        assert!(!state.record_remove_hooks(&config));
+        state.save().expect("Failed to save package state");
+
+        // Gone from the config, the hooks recorded earlier are still there
+        let mut state = PackageState::load().expect("Failed to reload package state");
+        assert!(!state.record_remove_hooks(&crate::core::config::Config::new()));
+        let hooks = &state.remove_hooks["syncthing"];
+        assert_eq!(hooks.pre_remove, ["systemctl --user disable syncthing"]);
+        assert!(hooks.post_install.is_empty());
+        assert!(!state.remove_hooks.contains_key("htop"));
+
+        state.remove_managed("syncthing");
+        assert!(state.remove_hooks.is_empty());
+    }
+
+    #[test]
+    fn test_siblings_sit_in_the_state_dir_or_beside_the_state_file() {
+        let dir = Path::new("/home/me/.owl/.state");
+        assert_eq!(
+            sibling_of(dir, false, "news.json"),
+            Path::new("/home/me/.owl/.state/news.json")
+        );
+        assert_eq!(sibling_dir_of(dir, false), dir);
+
+        let file = Path::new("/tmp/owl/state.json");
+        assert_eq!(
+            sibling_of(file, true, "news.json"),
+            Path::new("/tmp/owl/state.news.json")
+        );
+        assert_eq!(
+            sibling_of(file, true, "history"),
+            Path::new("/tmp/owl/state.history")
+        );
+        assert_eq!(sibling_dir_of(file, true), Path::new("/tmp/owl"));
     }
 }
@@ -0,0 +1,80 @@
+//! The last status, kept for `owl prompt`
+//!
+//! A shell prompt cannot wait for pacman or a dotfile walk, so `owl status` and
+//! `owl prompt --refresh` store the counts they found beside the state and the
+//! prompt only reads them back.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const CACHE_FILE: &str = "status.json";
+
+/// The counts a status check found
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusSnapshot {
+    /// Seconds since the Unix epoch
+    pub taken_at: u64,
+    pub installs: usize,
+    pub removals: usize,
+    /// Repo and AUR updates together
+    pub updates: usize,
+    pub dotfile_drift: usize,
+}
+
+impl StatusSnapshot {
+    pub fn in_sync(&self) -> bool {
+        self.installs == 0 && self.removals == 0 && self.updates == 0 && self.dotfile_drift == 0
+    }
+}
+
+fn cache_path() -> Result<PathBuf> {
+    crate::core::state::PackageState::sibling_path(CACHE_FILE)
+}
+
+fn read_from(path: &Path) -> Option<StatusSnapshot> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn write_to(path: &Path, snapshot: &StatusSnapshot) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let content =
+        serde_json::to_string_pretty(snapshot).context("Failed to serialize the status")?;
+    std::fs::write(path, content + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The stored snapshot; `None` when no status was recorded yet
+pub fn read() -> Option<StatusSnapshot> {
+    read_from(&cache_path().ok()?)
+}
+
+pub fn write(snapshot: &StatusSnapshot) -> Result<()> {
+    write_to(&cache_path()?, snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".state").join(CACHE_FILE);
+        assert_eq!(read_from(&path), None);
+        let snapshot = StatusSnapshot {
+            taken_at: 1_700_000_000,
+            updates: 3,
+            dotfile_drift: 1,
+            ..Default::default()
+        };
+        write_to(&path, &snapshot).unwrap();
+        assert_eq!(read_from(&path), Some(snapshot.clone()));
+        assert!(!snapshot.in_sync());
+        assert!(StatusSnapshot::default().in_sync());
+    }
+}
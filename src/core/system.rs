@@ -0,0 +1,239 @@
+//! What the package database looked like at one point of a run
+//!
+//! Apply captures a snapshot once while analyzing and hands it to every phase, so
+//! pacman is asked for the installed, explicit, and foreign packages a single time.
+//! Only operations that change the database are followed by a `refresh`.
+
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::core::pm::{PackageInfo, ParuPacman};
+
+/// The local database queries a snapshot is built from
+pub trait PackageQueries {
+    /// Installed packages with their versions (`pacman -Q`)
+    fn installed_versions(&self) -> Result<BTreeMap<String, String>>;
+    /// Explicitly installed packages (`pacman -Qeq`)
+    fn explicit_packages(&self) -> Result<HashSet<String>>;
+    /// Installed packages that are in no sync repo (`pacman -Qm`)
+    fn foreign_versions(&self) -> Result<BTreeMap<String, String>>;
+    /// Metadata of the installed packages among `packages` (`pacman -Qi`)
+    fn package_info(&self, packages: &[String]) -> Result<HashMap<String, PackageInfo>>;
+}
+
+impl PackageQueries for ParuPacman {
+    fn installed_versions(&self) -> Result<BTreeMap<String, String>> {
+        ParuPacman::installed_versions(self)
+    }
+
+    fn explicit_packages(&self) -> Result<HashSet<String>> {
+        ParuPacman::explicit_packages(self)
+    }
+
+    fn foreign_versions(&self) -> Result<BTreeMap<String, String>> {
+        ParuPacman::foreign_versions(self)
+    }
+
+    fn package_info(&self, packages: &[String]) -> Result<HashMap<String, PackageInfo>> {
+        ParuPacman::package_info(self, packages)
+    }
+}
+
+/// Where the queries go: with the `alpm` feature, pacman's database read directly
+/// when it can be opened; otherwise pacman
+pub enum Backend {
+    Pacman(ParuPacman),
+    #[cfg(feature = "alpm")]
+    Alpm(crate::core::alpm::AlpmDb),
+}
+
+impl Backend {
+    pub fn select() -> Self {
+        #[cfg(feature = "alpm")]
+        if let Some(db) = crate::core::alpm::AlpmDb::open(std::path::Path::new(
+            crate::internal::constants::PACMAN_DB_DIR,
+        )) {
+            return Self::Alpm(db);
+        }
+        Self::Pacman(ParuPacman::new())
+    }
+
+    fn queries(&self) -> &dyn PackageQueries {
+        match self {
+            Self::Pacman(pm) => pm,
+            #[cfg(feature = "alpm")]
+            Self::Alpm(db) => db,
+        }
+    }
+}
+
+impl PackageQueries for Backend {
+    fn installed_versions(&self) -> Result<BTreeMap<String, String>> {
+        self.queries().installed_versions()
+    }
+
+    fn explicit_packages(&self) -> Result<HashSet<String>> {
+        self.queries().explicit_packages()
+    }
+
+    fn foreign_versions(&self) -> Result<BTreeMap<String, String>> {
+        self.queries().foreign_versions()
+    }
+
+    fn package_info(&self, packages: &[String]) -> Result<HashMap<String, PackageInfo>> {
+        self.queries().package_info(packages)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SystemSnapshot {
+    versions: BTreeMap<String, String>,
+    installed: HashSet<String>,
+    explicit: HashSet<String>,
+    foreign: BTreeMap<String, String>,
+}
+
+impl SystemSnapshot {
+    pub fn capture(pm: &impl PackageQueries) -> Result<Self> {
+        let versions = pm.installed_versions()?;
+        Ok(Self {
+            installed: versions.keys().cloned().collect(),
+            versions,
+            explicit: pm.explicit_packages()?,
+            foreign: pm.foreign_versions()?,
+        })
+    }
+
+    /// Read the database again after installs or removals
+    pub fn refresh(&mut self, pm: &impl PackageQueries) -> Result<()> {
+        *self = Self::capture(pm)?;
+        // Lookups outside the snapshot keep their own cache; keep them in step
+        crate::core::package::invalidate_package_caches();
+        Ok(())
+    }
+
+    pub fn installed(&self) -> &HashSet<String> {
+        &self.installed
+    }
+
+    /// Installed packages, name to version
+    pub fn versions(&self) -> &BTreeMap<String, String> {
+        &self.versions
+    }
+
+    pub fn explicit(&self) -> &HashSet<String> {
+        &self.explicit
+    }
+
+    /// Installed packages from outside the sync repos, name to version; only these
+    /// can have AUR updates pending
+    pub fn foreign(&self) -> &BTreeMap<String, String> {
+        &self.foreign
+    }
+
+    /// A regular package that is installed, or a group whose members all are
+    pub fn has_package_or_group(&self, name: &str) -> Result<bool> {
+        crate::core::package::package_or_group_in(&self.installed, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::{Config, Package};
+    use crate::core::package::PackageAction;
+    use crate::core::state::PackageState;
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    struct CountingPm {
+        installed: Cell<usize>,
+        explicit: Cell<usize>,
+        foreign: Cell<usize>,
+    }
+
+    impl CountingPm {
+        fn counts(&self) -> (usize, usize, usize) {
+            (
+                self.installed.get(),
+                self.explicit.get(),
+                self.foreign.get(),
+            )
+        }
+    }
+
+    impl PackageQueries for CountingPm {
+        fn installed_versions(&self) -> Result<BTreeMap<String, String>> {
+            self.installed.set(self.installed.get() + 1);
+            Ok(BTreeMap::from([
+                ("htop".to_string(), "3.3.0-1".to_string()),
+                ("old".to_string(), "1.0-1".to_string()),
+                ("paru".to_string(), "2.0.4-1".to_string()),
+            ]))
+        }
+
+        fn explicit_packages(&self) -> Result<HashSet<String>> {
+            self.explicit.set(self.explicit.get() + 1);
+            Ok(HashSet::from(["htop".to_string(), "paru".to_string()]))
+        }
+
+        fn foreign_versions(&self) -> Result<BTreeMap<String, String>> {
+            self.foreign.set(self.foreign.get() + 1);
+            Ok(BTreeMap::from([(
+                "paru".to_string(),
+                "2.0.4-1".to_string(),
+            )]))
+        }
+
+        fn package_info(&self, _: &[String]) -> Result<HashMap<String, PackageInfo>> {
+            Ok(HashMap::new())
+        }
+    }
+
+    #[test]
+    fn test_analysis_queries_the_database_once() {
+        let pm = CountingPm::default();
+        let mut system = SystemSnapshot::capture(&pm).unwrap();
+
+        let mut config = Config::new();
+        config.packages.insert(
+            "htop".to_string(),
+            Package {
+                config: Vec::new(),
+                service: None,
+                env_vars: HashMap::new(),
+                exact: false,
+                hooks: Default::default(),
+            },
+        );
+        let mut state = PackageState {
+            untracked: Vec::new(),
+            hidden: Vec::new(),
+            managed: vec!["old".to_string()],
+            pinned: BTreeMap::new(),
+            sources: BTreeMap::new(),
+            remove_hooks: BTreeMap::new(),
+        };
+
+        // The same lookups apply makes across its phases before installing
+        crate::commands::apply::analysis::seed_managed_with_desired_installed(
+            &config, &mut state, &system,
+        )
+        .unwrap();
+        let actions = crate::core::package::plan_package_actions(&config, &state, &system).unwrap();
+        assert!(state.is_managed("htop"));
+        assert_eq!(
+            actions,
+            vec![PackageAction::Remove {
+                name: "old".to_string()
+            }]
+        );
+        assert_eq!(system.foreign().len(), 1);
+        assert_eq!(system.explicit().len(), 2);
+        assert_eq!(system.versions()["htop"], "3.3.0-1");
+        assert_eq!(pm.counts(), (1, 1, 1));
+
+        system.refresh(&pm).unwrap();
+        assert_eq!(pm.counts(), (2, 2, 2));
+    }
+}
@@ -1,43 +1,690 @@
 //! Error handling utilities
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::process;
+use std::sync::Mutex;
+
+use crate::cli::ui::InteractionError;
+use crate::core::config::ConfigDiagnostic;
+use crate::core::git::GitError;
+use crate::core::lock::LockHeld;
+use crate::core::pm::PmError;
+use crate::core::state::StateError;
+use crate::internal::color;
+use crate::internal::files::friendly_path;
+
+/// User-facing view of an error: a short headline, an optional excerpt, and a hint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Presentation {
+    pub headline: String,
+    pub excerpt: Vec<String>,
+    pub hint: Option<String>,
+}
+
+impl Presentation {
+    /// Render the presentation for the terminal
+    pub fn render(&self) -> String {
+        let mut out = color::red(&self.headline);
+        for line in &self.excerpt {
+            out.push('\n');
+            out.push_str(&color::dim(line));
+        }
+        if let Some(hint) = &self.hint {
+            out.push('\n');
+            out.push_str(&format!("{} {}", color::yellow("hint:"), hint));
+        }
+        out
+    }
+}
+
+/// Map an error chain to a presentation, recognizing owl's structured error types.
+/// Errors of unknown type render as the plain `anyhow` chain.
+pub fn present(error: &anyhow::Error) -> Presentation {
+    let mut contexts = Vec::new();
+    for cause in error.chain() {
+        if let Some(diagnostic) = cause.downcast_ref::<ConfigDiagnostic>() {
+            return present_config(contexts, diagnostic);
+        }
+        if let Some(pm_error) = cause.downcast_ref::<PmError>() {
+            return present_pm(contexts, pm_error);
+        }
+        if let Some(state_error) = cause.downcast_ref::<StateError>() {
+            return present_state(contexts, state_error);
+        }
+        if let Some(git_error) = cause.downcast_ref::<GitError>() {
+            return present_git(contexts, git_error);
+        }
+        if let Some(interaction) = cause.downcast_ref::<InteractionError>() {
+            return present_interaction(contexts, interaction);
+        }
+        if let Some(held) = cause.downcast_ref::<LockHeld>() {
+            return Presentation {
+                headline: headline(contexts, &held.to_string()),
+                excerpt: Vec::new(),
+                hint: Some(
+                    "wait for it to finish, or pass `--wait` to start once it has".to_string(),
+                ),
+            };
+        }
+        contexts.push(cause.to_string());
+    }
+
+    Presentation {
+        headline: format!("{:#}", error),
+        excerpt: Vec::new(),
+        hint: None,
+    }
+}
+
+fn headline(mut contexts: Vec<String>, message: &str) -> String {
+    contexts.push(message.to_string());
+    contexts.join(": ")
+}
+
+fn present_config(contexts: Vec<String>, diagnostic: &ConfigDiagnostic) -> Presentation {
+    let gutter = " ".repeat(diagnostic.line.to_string().len());
+    let location = match &diagnostic.path {
+        Some(path) => format!(
+            "{}:{}",
+            friendly_path(&path.display().to_string()),
+            diagnostic.line
+        ),
+        None => format!("line {}", diagnostic.line),
+    };
+    let hint = match &diagnostic.path {
+        Some(path) => format!(
+            "fix the line above, then run `owl config-check {}`",
+            friendly_path(&path.display().to_string())
+        ),
+        None => "fix the line above, then run `owl config-check`".to_string(),
+    };
+
+    Presentation {
+        headline: headline(contexts, &diagnostic.message),
+        excerpt: vec![
+            format!("{} --> {}", gutter, location),
+            format!("{} |", gutter),
+            format!(
+                "{} | {}",
+                diagnostic.line,
+                diagnostic.source_line.trim_end()
+            ),
+            format!("{} |", gutter),
+        ],
+        hint: Some(hint),
+    }
+}
+
+fn present_pm(contexts: Vec<String>, pm_error: &PmError) -> Presentation {
+    match pm_error {
+        PmError::MissingAurHelper => Presentation {
+            headline: headline(contexts, "No AUR helper found"),
+            excerpt: Vec::new(),
+            hint: Some(
+                "install `paru` or `yay`, or move AUR packages out of your config".to_string(),
+            ),
+        },
+        PmError::CommandFailed { action, stderr } => {
+            let lines: Vec<&str> = stderr
+                .lines()
+                .map(str::trim_end)
+                .filter(|line| !line.trim().is_empty())
+                .collect();
+            let excerpt = lines
+                .iter()
+                .skip(lines.len().saturating_sub(5))
+                .map(|line| format!("  | {}", line))
+                .collect();
+            Presentation {
+                headline: headline(contexts, &format!("{} failed", action)),
+                excerpt,
+                hint: pm_hint(stderr),
+            }
+        }
+        PmError::DatabaseLocked { path, .. } => Presentation {
+            headline: headline(contexts, &pm_error.to_string()),
+            excerpt: Vec::new(),
+            hint: Some(if pm_error.is_stale_lock() {
+                format!(
+                    "the lock was left by a run that crashed or was killed; make sure nothing is updating, then remove it with `sudo rm {}`",
+                    path.display()
+                )
+            } else {
+                "wait for it to finish, or pass `--db-lock-wait <SECS>` to wait for it".to_string()
+            }),
+        },
+    }
+}
+
+/// Suggest a fix for well-known pacman/AUR helper failures
+fn pm_hint(stderr: &str) -> Option<String> {
+    let lower = stderr.to_lowercase();
+    let hint = if lower.contains("unable to lock database") {
+        "another package manager is running; wait for it to finish, or remove /var/lib/pacman/db.lck if no pacman process is left"
+    } else if lower.contains("signature")
+        || lower.contains("keyring")
+        || lower.contains("unknown trust")
+    {
+        "refresh the keyring with `sudo pacman -Sy archlinux-keyring` or `sudo pacman-key --refresh-keys`"
+    } else if lower.contains("could not resolve host") || lower.contains("failed retrieving file") {
+        "check your network connection and /etc/pacman.d/mirrorlist"
+    } else if lower.contains("target not found") {
+        "check the package name; `owl add <term>` searches the repos and the AUR"
+    } else if lower.contains("conflicting files") || lower.contains("exists in filesystem") {
+        "another package or a manual install owns those files; remove them or resolve the conflict with pacman"
+    } else {
+        return None;
+    };
+    Some(hint.to_string())
+}
+
+fn present_state(contexts: Vec<String>, state_error: &StateError) -> Presentation {
+    let path = friendly_path(&state_error.path.display().to_string());
+    Presentation {
+        headline: headline(
+            contexts,
+            &format!("State file is unreadable: {}", state_error.message),
+        ),
+        excerpt: vec![format!(" --> {}", path)],
+        hint: Some(format!(
+            "inspect {} or move it aside; owl recreates missing state files with defaults",
+            path
+        )),
+    }
+}
+
+fn present_git(contexts: Vec<String>, git_error: &GitError) -> Presentation {
+    let excerpt = |stderr: &str| -> Vec<String> {
+        stderr
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.trim().is_empty())
+            .take(5)
+            .map(|line| format!("  | {}", line))
+            .collect()
+    };
+    let (excerpt, hint) = match git_error {
+        GitError::NotARepo(_) => (
+            Vec::new(),
+            Some(
+                "run `owl init --git`, then add a remote with `git remote add origin <url>`"
+                    .to_string(),
+            ),
+        ),
+        GitError::Dirty { .. } => (
+            Vec::new(),
+            Some(
+                "commit and push them with `owl sync --push`, or set them aside with `git stash`"
+                    .to_string(),
+            ),
+        ),
+        GitError::Conflicted { repo, .. } => (
+            Vec::new(),
+            Some(format!(
+                "resolve the conflicts in {} and commit, then run `owl sync` again",
+                friendly_path(&repo.display().to_string())
+            )),
+        ),
+        GitError::NotFastForward { repo, stderr } => (
+            excerpt(stderr),
+            Some(format!(
+                "run `git -C {} pull --rebase` (or merge by hand), then `owl sync` again",
+                friendly_path(&repo.display().to_string())
+            )),
+        ),
+        GitError::PushRejected { repo, stderr } => (
+            excerpt(stderr),
+            Some(format!(
+                "the remote has commits you do not; run `git -C {} pull --rebase`, then `owl sync --push` again",
+                friendly_path(&repo.display().to_string())
+            )),
+        ),
+        GitError::CommandFailed { stderr, .. } => (excerpt(stderr), None),
+    };
+    let message = match git_error {
+        GitError::CommandFailed { action, .. } => format!("{} failed", action),
+        other => other.to_string(),
+    };
+    Presentation {
+        headline: headline(contexts, &message),
+        excerpt,
+        hint,
+    }
+}
+
+fn present_interaction(contexts: Vec<String>, interaction: &InteractionError) -> Presentation {
+    Presentation {
+        headline: headline(contexts, &interaction.to_string()),
+        excerpt: Vec::new(),
+        hint: Some(
+            "run owl from a terminal, or pass `--answers-file <path>` with one answer per line"
+                .to_string(),
+        ),
+    }
+}
+
+/// Render an error for the terminal
+pub fn render_error(error: &anyhow::Error) -> String {
+    present(error).render()
+}
 
 /// Print an error message and exit with code 1
 pub fn exit_with_error(error: anyhow::Error) -> ! {
-    eprintln!("{}", crate::internal::color::red(&error.to_string()));
-    process::exit(1);
+    exit_with_code(error, 1)
 }
 
-/// Handle a Result by printing the error (with operation context) but not exiting
-/// Returns true if there was an error
-pub fn handle_error_with_context(operation: &str, result: Result<()>) -> bool {
-    if let Err(e) = result {
-        eprintln!(
-            "{}",
-            crate::internal::color::red(&format!("Failed to {}: {}", operation, e))
-        );
+/// Print an error message and exit with `code`
+pub fn exit_with_code(error: anyhow::Error, code: i32) -> ! {
+    eprintln!("{}", render_error(&error));
+    stopped(&error);
+    process::exit(code);
+}
+
+/// Close the porcelain stream, and notify, for a run that is about to exit on `error`
+fn stopped(error: &anyhow::Error) {
+    use crate::cli::porcelain;
+    let message = format!("{:#}", error);
+    porcelain::warning(&message);
+    let summary = porcelain::Summary {
+        warnings: porcelain::warnings(),
+        ..Default::default()
+    };
+    crate::cli::notify::finished(&summary, Some(&message));
+    porcelain::emit(porcelain::Event::Done { summary });
+}
+
+/// How a reported problem counts toward the run's outcome: errors fail the run,
+/// warnings only show up in its summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem a run reported and carried on past
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Issue {
+    pub severity: Severity,
+    /// The porcelain phase the run was in, if it had started one
+    #[serde(default)]
+    pub phase: Option<String>,
+    /// What owl was trying to do, e.g. "check the Arch news"
+    #[serde(default)]
+    pub context: Option<String>,
+    /// The full error chain, or the warning text
+    pub message: String,
+}
+
+/// Collects the problems of one run. Each is printed the moment it is reported and
+/// kept until `drain` hands the list to the run's summary.
+#[derive(Debug, Default)]
+pub struct Reporter {
+    phase: Option<String>,
+    issues: Vec<Issue>,
+}
+
+impl Reporter {
+    /// Attribute the problems reported from here on to `phase`
+    pub fn enter(&mut self, phase: &str) {
+        self.phase = Some(phase.to_string());
+    }
+
+    /// Print and record the error in `result`, under `operation` when given.
+    /// Returns true if there was an error.
+    pub fn result(
+        &mut self,
+        severity: Severity,
+        operation: Option<&str>,
+        result: Result<()>,
+    ) -> bool {
+        let Err(error) = result else {
+            return false;
+        };
+        let error = match operation {
+            Some(operation) => error.context(format!("Failed to {}", operation)),
+            None => error,
+        };
+        eprintln!("{}", render_error(&error));
+        self.record(severity, operation, format!("{:#}", error));
         true
-    } else {
-        false
+    }
+
+    /// Print and record a warning that is not an error value
+    pub fn warning(&mut self, message: &str) {
+        eprintln!("  {}", color::warn(message));
+        self.record(Severity::Warning, None, message.to_string());
+    }
+
+    fn record(&mut self, severity: Severity, operation: Option<&str>, message: String) {
+        crate::cli::porcelain::warning(&message);
+        self.issues.push(Issue {
+            severity,
+            phase: self.phase.clone(),
+            context: operation.map(str::to_string),
+            message,
+        });
+    }
+
+    /// Whether an error, not just a warning, has been reported since the last `drain`
+    pub fn errors_reported(&self) -> bool {
+        failed(&self.issues)
+    }
+
+    /// Hand over everything reported so far, leaving the reporter empty
+    pub fn drain(&mut self) -> Vec<Issue> {
+        std::mem::take(&mut self.issues)
     }
 }
 
-/// Handle a Result by printing the error (without context) but not exiting
-/// Returns true if there was an error
-pub fn handle_error(result: Result<()>) -> bool {
-    if let Err(e) = result {
-        eprintln!("{}", crate::internal::color::red(&e.to_string()));
-        true
-    } else {
-        false
+/// The reporter of the current run; owl runs one command per process
+static REPORTER: Mutex<Reporter> = Mutex::new(Reporter {
+    phase: None,
+    issues: Vec::new(),
+});
+
+fn reporter() -> std::sync::MutexGuard<'static, Reporter> {
+    REPORTER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Attribute the problems reported from here on to `phase`
+pub fn enter_phase(phase: &str) {
+    reporter().enter(phase);
+}
+
+/// Report the error in `result` with the operation that failed, and carry on.
+/// Returns true if there was an error.
+pub fn report(operation: &str, result: Result<()>) -> bool {
+    reporter().result(Severity::Error, Some(operation), result)
+}
+
+/// Report the error in `result`, which already says what failed, and carry on.
+/// Returns true if there was an error.
+pub fn report_error(result: Result<()>) -> bool {
+    reporter().result(Severity::Error, None, result)
+}
+
+/// Like `report`, for a failed check that should not fail the run
+pub fn report_warning(operation: &str, result: Result<()>) -> bool {
+    reporter().result(Severity::Warning, Some(operation), result)
+}
+
+/// Report a warning message, and carry on
+pub fn warn(message: &str) {
+    reporter().warning(message);
+}
+
+/// Whether an error, not just a warning, has been reported since the last `drain`
+pub fn errors_reported() -> bool {
+    reporter().errors_reported()
+}
+
+/// Everything reported in this run so far, for its summary
+pub fn drain() -> Vec<Issue> {
+    reporter().drain()
+}
+
+/// Whether any of `issues` should fail the run
+pub fn failed(issues: &[Issue]) -> bool {
+    issues.iter().any(|issue| issue.severity == Severity::Error)
+}
+
+/// The "completed with N warnings" block that ends a run; empty without issues
+pub fn completion_lines(issues: &[Issue]) -> Vec<String> {
+    if issues.is_empty() {
+        return Vec::new();
+    }
+    let count =
+        |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    let warnings = issues.len() - errors;
+    let mut counts = Vec::new();
+    if errors > 0 {
+        counts.push(count(errors, "error", "errors"));
+    }
+    if warnings > 0 {
+        counts.push(count(warnings, "warning", "warnings"));
+    }
+    let mut lines = vec![format!("completed with {}:", counts.join(" and "))];
+    for issue in issues {
+        let line = format!(
+            "{}{}",
+            issue
+                .phase
+                .as_deref()
+                .map(|phase| format!("[{}] ", phase))
+                .unwrap_or_default(),
+            issue.message
+        );
+        lines.push(format!(
+            "  {}",
+            match issue.severity {
+                Severity::Error => color::err(&line),
+                Severity::Warning => color::warn(&line),
+            }
+        ));
     }
+    lines
 }
 
 /// Handle a Result by printing the error and exiting if failed
 pub fn exit_on_error(result: Result<()>) {
     if let Err(e) = result {
-        eprintln!("{}", crate::internal::color::red(&e.to_string()));
+        eprintln!("{}", render_error(&e));
+        stopped(&e);
         process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    fn strip_ansi(s: &str) -> String {
+        let mut out = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                for c in chars.by_ref() {
+                    if c == 'm' {
+                        break;
+                    }
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_render_config_diagnostic() {
+        let err = crate::core::config::Config::parse("@packages\nhtop\n\n@package \n")
+            .unwrap_err()
+            .context("Failed to load config");
+
+        assert_eq!(
+            strip_ansi(&render_error(&err)),
+            "Failed to load config: package directive requires a package name
+  --> line 4
+  |
+4 | @package
+  |
+hint: fix the line above, then run `owl config-check`"
+        );
+    }
+
+    #[test]
+    fn test_render_config_diagnostic_with_path() {
+        let temp = tempfile::tempdir().expect("failed to create temp dir");
+        let path = temp.path().join("main.owl");
+        std::fs::write(&path, ":service sshd\n").expect("failed to write config");
+
+        let err = crate::core::config::Config::parse_file(&path).unwrap_err();
+        let rendered = strip_ansi(&render_error(&err));
+        assert!(rendered.starts_with(":service directive found outside of a package context\n"));
+        assert!(rendered.contains(&format!("--> {}:1", path.display())));
+        assert!(rendered.contains("1 | :service sshd"));
+    }
+
+    #[test]
+    fn test_render_pm_lock_error() {
+        let err = anyhow::Error::from(PmError::CommandFailed {
+            action: "Repository update".to_string(),
+            stderr: ":: Synchronizing package databases...\nerror: failed to init transaction (unable to lock database)\n".to_string(),
+        });
+
+        assert_eq!(
+            strip_ansi(&render_error(&err)),
+            "Repository update failed
+  | :: Synchronizing package databases...
+  | error: failed to init transaction (unable to lock database)
+hint: another package manager is running; wait for it to finish, or remove /var/lib/pacman/db.lck if no pacman process is left"
+        );
+    }
+
+    #[test]
+    fn test_render_database_locked() {
+        let path = std::path::PathBuf::from("/var/lib/pacman/db.lck");
+        let stale = anyhow::Error::from(PmError::DatabaseLocked {
+            path: path.clone(),
+            holders: Vec::new(),
+            waited: 0,
+        });
+        assert_eq!(
+            strip_ansi(&render_error(&stale)),
+            "pacman's database is locked (/var/lib/pacman/db.lck) but no package manager is running
+hint: the lock was left by a run that crashed or was killed; make sure nothing is updating, then remove it with `sudo rm /var/lib/pacman/db.lck`"
+        );
+
+        let held = anyhow::Error::from(PmError::DatabaseLocked {
+            path,
+            holders: vec!["paru (pid 4242)".to_string()],
+            waited: 60,
+        });
+        let presentation = present(&held);
+        assert_eq!(
+            presentation.headline,
+            "pacman's database is locked by paru (pid 4242) (waited 60s)"
+        );
+        assert!(presentation.hint.unwrap().contains("--db-lock-wait"));
+    }
+
+    #[test]
+    fn test_render_missing_aur_helper() {
+        let err = anyhow::Error::from(PmError::MissingAurHelper).context("Failed to install");
+        let presentation = present(&err);
+        assert_eq!(
+            presentation.headline,
+            "Failed to install: No AUR helper found"
+        );
+        assert!(presentation.hint.unwrap().contains("paru"));
+    }
+
+    #[test]
+    fn test_render_state_error() {
+        let err = anyhow::Error::from(StateError {
+            path: std::path::PathBuf::from("/tmp/state/managed.json"),
+            message: "Failed to parse managed packages JSON: EOF".to_string(),
+        });
+
+        assert_eq!(
+            strip_ansi(&render_error(&err)),
+            "State file is unreadable: Failed to parse managed packages JSON: EOF
+ --> /tmp/state/managed.json
+hint: inspect /tmp/state/managed.json or move it aside; owl recreates missing state files with defaults"
+        );
+    }
+
+    #[test]
+    fn test_render_interaction_error() {
+        let err = anyhow::Error::from(InteractionError {
+            prompt: "the removal confirmation".to_string(),
+            block: crate::cli::ui::InteractionBlock::NotATerminal,
+        });
+
+        assert_eq!(
+            strip_ansi(&render_error(&err)),
+            "the removal confirmation needs an answer, but stdin/stdout is not a terminal
+hint: run owl from a terminal, or pass `--answers-file <path>` with one answer per line"
+        );
+    }
+
+    #[test]
+    fn test_reporter_collects_issues_by_phase() {
+        let mut reporter = Reporter::default();
+        assert!(!reporter.result(Severity::Error, Some("save package state"), Ok(())));
+        reporter.enter("packages");
+        assert!(reporter.result(
+            Severity::Warning,
+            Some("check the Arch news"),
+            Err(anyhow!("could not resolve host"))
+        ));
+        assert!(reporter.result(Severity::Error, None, Err(PmError::MissingAurHelper.into())));
+        reporter.enter("dotfiles");
+        reporter.warning("not found in the repos or the AUR: nope");
+
+        let issues = reporter.drain();
+        assert!(reporter.drain().is_empty());
+        assert_eq!(
+            issues
+                .iter()
+                .map(|issue| (
+                    issue.severity,
+                    issue.phase.as_deref(),
+                    issue.context.as_deref(),
+                    issue.message.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Severity::Warning,
+                    Some("packages"),
+                    Some("check the Arch news"),
+                    "Failed to check the Arch news: could not resolve host"
+                ),
+                (
+                    Severity::Error,
+                    Some("packages"),
+                    None,
+                    "No AUR helper found. Install either 'paru' or 'yay' to manage AUR packages."
+                ),
+                (
+                    Severity::Warning,
+                    Some("dotfiles"),
+                    None,
+                    "not found in the repos or the AUR: nope"
+                ),
+            ]
+        );
+        assert!(failed(&issues));
+        assert!(!failed(&issues[2..]));
+
+        let lines: Vec<String> = completion_lines(&issues)
+            .iter()
+            .map(|line| strip_ansi(line))
+            .collect();
+        assert_eq!(lines[0], "completed with 1 error and 2 warnings:");
+        assert!(
+            lines[1].ends_with("[packages] Failed to check the Arch news: could not resolve host")
+        );
+        assert_eq!(lines.len(), 4);
+        assert!(completion_lines(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_render_unknown_error_keeps_chain() {
+        let err = Err::<(), _>(anyhow!("invalid entry"))
+            .context("Failed to load config")
+            .unwrap_err();
+        assert_eq!(
+            strip_ansi(&render_error(&err)),
+            "Failed to load config: invalid entry"
+        );
+    }
+}
@@ -72,15 +72,155 @@ pub fn colorize(s: &str, color: Color) -> String {
     format!("\x1b[{}m{}\x1b[0m", color.ansi_code(), s)
 }
 
-// Convenience functions for backward compatibility
+/// Semantic output roles that the `[colors]` settings section can restyle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Error,
+    Warn,
+    Info,
+    Success,
+    Highlight,
+}
+
+impl Role {
+    pub const ALL: [Role; 5] = [
+        Role::Error,
+        Role::Warn,
+        Role::Info,
+        Role::Success,
+        Role::Highlight,
+    ];
+
+    fn key(self) -> &'static str {
+        match self {
+            Role::Error => "error",
+            Role::Warn => "warn",
+            Role::Info => "info",
+            Role::Success => "success",
+            Role::Highlight => "highlight",
+        }
+    }
+}
+
+/// ANSI codes for each role; `None` prints the text unstyled
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    codes: [Option<String>; 5],
+}
+
+impl Theme {
+    /// Look up a built-in preset: `default`, `high-contrast`, or `mono`
+    pub fn preset(name: &str) -> Option<Self> {
+        let codes: [Option<&str>; 5] = match name {
+            "default" => [Some("31"), Some("33"), Some("34"), Some("32"), Some("1;36")],
+            // Orange/blue instead of red/green, which read the same to many colorblind users
+            "high-contrast" => [
+                Some("1;38;5;208"),
+                Some("1;33"),
+                Some("1;36"),
+                Some("1;38;5;33"),
+                Some("1;4"),
+            ],
+            "mono" => [Some("1"), Some("1"), None, None, Some("4")],
+            _ => return None,
+        };
+        Some(Self {
+            codes: codes.map(|code| code.map(str::to_string)),
+        })
+    }
+
+    /// Build the theme described by the `[colors]` settings. Unknown presets and color
+    /// names are reported in the returned warnings and fall back to the preset's color.
+    pub fn from_settings(settings: &crate::core::settings::ColorSettings) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let preset = settings.preset.as_deref().unwrap_or("default");
+        let mut theme = Self::preset(preset).unwrap_or_else(|| {
+            warnings.push(format!("unknown color preset '{}'", preset));
+            Self::default()
+        });
+
+        for role in Role::ALL {
+            let value = match role {
+                Role::Error => &settings.error,
+                Role::Warn => &settings.warn,
+                Role::Info => &settings.info,
+                Role::Success => &settings.success,
+                Role::Highlight => &settings.highlight,
+            };
+            let Some(value) = value else { continue };
+            match parse_color(value) {
+                Some(code) => theme.codes[role as usize] = code,
+                None => warnings.push(format!("unknown color '{}' for {}", value, role.key())),
+            }
+        }
+        (theme, warnings)
+    }
+
+    /// Style `s` for `role`; `bold` adds weight on top of the role's color
+    pub fn paint(&self, role: Role, s: &str, bold: bool) -> String {
+        match (&self.codes[role as usize], bold) {
+            (Some(code), true) if !code.starts_with("1;") && code != "1" => {
+                format!("\x1b[1;{}m{}\x1b[0m", code, s)
+            }
+            (Some(code), _) => format!("\x1b[{}m{}\x1b[0m", code, s),
+            (None, _) => s.to_string(),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::preset("default").expect("default preset exists")
+    }
+}
+
+/// Parse a color name (`red`, `bright-blue`), a 256-color number, or `none`.
+/// Returns `Some(None)` for `none` and `None` for anything unrecognized.
+fn parse_color(value: &str) -> Option<Option<String>> {
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    let value = value.trim().to_lowercase();
+    if value == "none" {
+        return Some(None);
+    }
+    if let Ok(number) = value.parse::<u8>() {
+        return Some(Some(format!("38;5;{}", number)));
+    }
+    let (base, name) = match value.strip_prefix("bright-") {
+        Some(name) => (90, name),
+        None => (30, value.as_str()),
+    };
+    let index = NAMES.iter().position(|candidate| *candidate == name)?;
+    Some(Some((base + index).to_string()))
+}
+
+static THEME: std::sync::OnceLock<Theme> = std::sync::OnceLock::new();
+
+/// The theme from settings, resolved once per process
+pub fn theme() -> &'static Theme {
+    // Warnings are printed after initialization since printing them uses the theme
+    let mut warnings = Vec::new();
+    let theme = THEME.get_or_init(|| {
+        let (theme, problems) = Theme::from_settings(&crate::core::settings::get().colors);
+        warnings = problems;
+        theme
+    });
+    for warning in warnings {
+        eprintln!("  {} {}; using the preset color", yellow("!"), warning);
+    }
+    theme
+}
+
+// Convenience functions for backward compatibility. The semantic ones follow the theme.
 pub fn red(s: &str) -> String {
-    colorize(s, Color::Red)
+    theme().paint(Role::Error, s, false)
 }
 pub fn green(s: &str) -> String {
-    colorize(s, Color::Green)
+    theme().paint(Role::Success, s, false)
 }
 pub fn yellow(s: &str) -> String {
-    colorize(s, Color::Yellow)
+    theme().paint(Role::Warn, s, false)
 }
 pub fn orange(s: &str) -> String {
     colorize(s, Color::Orange)
@@ -92,7 +232,7 @@ pub fn system_purple(s: &str) -> String {
     colorize(s, Color::SystemPurple)
 }
 pub fn blue(s: &str) -> String {
-    colorize(s, Color::Blue)
+    theme().paint(Role::Info, s, false)
 }
 pub fn magenta(s: &str) -> String {
     colorize(s, Color::Magenta)
@@ -140,13 +280,13 @@ pub fn dim(s: &str) -> String {
     colorize(s, Color::Dim)
 }
 pub fn highlight(s: &str) -> String {
-    colorize(s, Color::Highlight)
+    theme().paint(Role::Highlight, s, false)
 }
 pub fn success(s: &str) -> String {
-    colorize(s, Color::Success)
+    theme().paint(Role::Success, s, true)
 }
 pub fn warning(s: &str) -> String {
-    colorize(s, Color::Warning)
+    theme().paint(Role::Warn, s, true)
 }
 pub fn repository(s: &str) -> String {
     colorize(s, Color::Repository)
@@ -154,3 +294,250 @@ pub fn repository(s: &str) -> String {
 pub fn description(s: &str) -> String {
     colorize(s, Color::Description)
 }
+
+/// The markers in front of status lines: Unicode symbols, or ASCII stand-ins for
+/// terminals that cannot show them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbols {
+    Unicode,
+    Ascii,
+}
+
+impl Symbols {
+    fn ok(self) -> &'static str {
+        match self {
+            Symbols::Unicode => "✓",
+            Symbols::Ascii => "[ok]",
+        }
+    }
+
+    fn warn(self) -> &'static str {
+        match self {
+            Symbols::Unicode => "!",
+            Symbols::Ascii => "[!]",
+        }
+    }
+
+    fn err(self) -> &'static str {
+        match self {
+            Symbols::Unicode => "✗",
+            Symbols::Ascii => "[x]",
+        }
+    }
+
+    fn action(self) -> &'static str {
+        match self {
+            Symbols::Unicode => "➔",
+            Symbols::Ascii => "->",
+        }
+    }
+
+    /// Unicode when the first of `LC_ALL`, `LC_CTYPE`, and `LANG` that is set names a
+    /// UTF-8 locale
+    fn from_locale(var: impl Fn(&str) -> Option<String>) -> Self {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .filter_map(var)
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        if locale.contains("utf-8") || locale.contains("utf8") {
+            Symbols::Unicode
+        } else {
+            Symbols::Ascii
+        }
+    }
+}
+
+static FORCE_ASCII: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+static SYMBOLS: std::sync::OnceLock<Symbols> = std::sync::OnceLock::new();
+
+/// Use the ASCII markers whatever the locale says, for `--ascii`
+pub fn force_ascii() {
+    FORCE_ASCII.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// The markers for this process, decided on first use
+pub fn symbols() -> Symbols {
+    *SYMBOLS.get_or_init(|| {
+        if FORCE_ASCII.load(std::sync::atomic::Ordering::Relaxed) {
+            Symbols::Ascii
+        } else {
+            Symbols::from_locale(|name| std::env::var(name).ok())
+        }
+    })
+}
+
+/// A status line for `symbols`, painted with the theme
+fn marked(symbols: Symbols, role: Role, marker: fn(Symbols) -> &'static str, msg: &str) -> String {
+    format!("{} {}", theme().paint(role, marker(symbols), false), msg)
+}
+
+fn ok_with(symbols: Symbols, msg: &str) -> String {
+    marked(symbols, Role::Success, Symbols::ok, msg)
+}
+
+fn warn_with(symbols: Symbols, msg: &str) -> String {
+    marked(symbols, Role::Warn, Symbols::warn, msg)
+}
+
+fn err_with(symbols: Symbols, msg: &str) -> String {
+    marked(symbols, Role::Error, Symbols::err, msg)
+}
+
+fn action_with(symbols: Symbols, verb: &str, subject: &str) -> String {
+    marked(
+        symbols,
+        Role::Success,
+        Symbols::action,
+        &format!("{} {}", verb, highlight(subject)),
+    )
+}
+
+/// `✓ msg`: something finished as asked
+pub fn ok(msg: &str) -> String {
+    ok_with(symbols(), msg)
+}
+
+/// `! msg`: something needs attention but did not stop the run
+pub fn warn(msg: &str) -> String {
+    warn_with(symbols(), msg)
+}
+
+/// `✗ msg`: something failed
+pub fn err(msg: &str) -> String {
+    err_with(symbols(), msg)
+}
+
+/// `info: msg`
+pub fn info(msg: &str) -> String {
+    format!("{} {}", blue("info:"), msg)
+}
+
+/// `➔ verb subject`: a change that is about to happen, or would on a dry run
+pub fn action(verb: &str, subject: &str) -> String {
+    action_with(symbols(), verb, subject)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::settings::Settings;
+
+    /// The `[colors]` example from the readme
+    fn readme_example() -> String {
+        let readme = include_str!("../../readme.md");
+        let start = readme.find("```toml\n").expect("readme has a toml example") + 8;
+        let end = start + readme[start..].find("```").expect("toml example is closed");
+        readme[start..end].to_string()
+    }
+
+    #[test]
+    fn test_readme_theme_renders_each_role() {
+        let settings = Settings::parse(&readme_example()).expect("readme example parses");
+        let (theme, warnings) = Theme::from_settings(&settings.colors);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+
+        let rendered: Vec<String> = Role::ALL
+            .iter()
+            .map(|role| theme.paint(*role, "x", false))
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "\x1b[95mx\x1b[0m",
+                "\x1b[1;33mx\x1b[0m",
+                "\x1b[1;36mx\x1b[0m",
+                "\x1b[38;5;33mx\x1b[0m",
+                "x",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_colors_warn_and_fall_back() {
+        let settings = Settings::parse("[colors]\npreset = \"neon\"\nerror = \"reddish\"\n")
+            .unwrap()
+            .colors;
+        let (theme, warnings) = Theme::from_settings(&settings);
+        assert_eq!(theme, Theme::default());
+        assert_eq!(
+            warnings,
+            vec![
+                "unknown color preset 'neon'".to_string(),
+                "unknown color 'reddish' for error".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mono_preset_and_bold() {
+        let mono = Theme::preset("mono").unwrap();
+        assert_eq!(mono.paint(Role::Info, "x", true), "x");
+        assert_eq!(mono.paint(Role::Error, "x", true), "\x1b[1mx\x1b[0m");
+        assert_eq!(
+            Theme::default().paint(Role::Success, "x", true),
+            "\x1b[1;32mx\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_semantic_helpers_in_both_symbol_modes() {
+        let unicode = [
+            ok_with(Symbols::Unicode, "done"),
+            warn_with(Symbols::Unicode, "careful"),
+            err_with(Symbols::Unicode, "broken"),
+            action_with(Symbols::Unicode, "install", "htop"),
+        ];
+        assert_eq!(
+            unicode,
+            [
+                "\x1b[32m✓\x1b[0m done",
+                "\x1b[33m!\x1b[0m careful",
+                "\x1b[31m✗\x1b[0m broken",
+                "\x1b[32m➔\x1b[0m install \x1b[1;36mhtop\x1b[0m",
+            ]
+        );
+        let ascii = [
+            ok_with(Symbols::Ascii, "done"),
+            warn_with(Symbols::Ascii, "careful"),
+            err_with(Symbols::Ascii, "broken"),
+            action_with(Symbols::Ascii, "install", "htop"),
+        ];
+        assert_eq!(
+            ascii,
+            [
+                "\x1b[32m[ok]\x1b[0m done",
+                "\x1b[33m[!]\x1b[0m careful",
+                "\x1b[31m[x]\x1b[0m broken",
+                "\x1b[32m->\x1b[0m install \x1b[1;36mhtop\x1b[0m",
+            ]
+        );
+        assert_eq!(info("note"), "\x1b[34minfo:\x1b[0m note");
+    }
+
+    #[test]
+    fn test_symbols_follow_the_locale() {
+        let env = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                pairs
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(
+            Symbols::from_locale(env(&[("LANG", "en_US.UTF-8")])),
+            Symbols::Unicode
+        );
+        assert_eq!(
+            Symbols::from_locale(env(&[("LC_ALL", "C"), ("LANG", "en_US.UTF-8")])),
+            Symbols::Ascii
+        );
+        assert_eq!(
+            Symbols::from_locale(env(&[("LC_ALL", ""), ("LC_CTYPE", "de_DE.utf8")])),
+            Symbols::Unicode
+        );
+        assert_eq!(Symbols::from_locale(env(&[])), Symbols::Ascii);
+    }
+}
@@ -11,22 +11,43 @@ pub const EDIT_TYPE_CONFIG: &str = "config";
 // Default editor
 pub const DEFAULT_EDITOR: &str = "vim";
 
+// Default pager, used when $PAGER is unset
+pub const DEFAULT_PAGER: &str = "less -FRX";
+
 // Directory paths
 pub const OWL_DIR: &str = ".owl";
 pub const DOTFILES_DIR: &str = "dotfiles";
 pub const HOSTS_DIR: &str = "hosts";
 pub const GROUPS_DIR: &str = "groups";
 pub const OWL_EXT: &str = ".owl";
+// Directory levels below the owl directory searched for config files
+pub const MAX_CONFIG_DEPTH: usize = 8;
 
 // Config filenames
 pub const MAIN_CONFIG_FILE: &str = "main.owl";
 
+// Settings filename under ~/.owl
+pub const SETTINGS_FILE: &str = "settings.toml";
+
 // Environment filenames under ~/.owl
 pub const ENV_BASH_FILE: &str = "env.sh";
 pub const ENV_FISH_FILE: &str = "env.fish";
 
 // State management paths
 pub const STATE_DIR: &str = ".state";
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+// pacman's database directory, holding `local/` and `sync/`
+pub const PACMAN_DB_DIR: &str = "/var/lib/pacman";
+
+// Where pacman keeps downloaded package files
+pub const PACMAN_CACHE_DIR: &str = "/var/cache/pacman/pkg";
+
+// pacman's keyring, empty until `pacman-key --init`
+pub const PACMAN_GNUPG_DIR: &str = "/etc/pacman.d/gnupg";
+
+// Where AUR package repositories are cloned from
+pub const AUR_GIT_URL: &str = "https://aur.archlinux.org";
 
 // Host name will be read from system
 pub fn get_host_name() -> Result<String> {
@@ -35,5 +56,8 @@ pub fn get_host_name() -> Result<String> {
         .map_err(|e| anyhow!("Failed to read hostname: {}", e))
 }
 
+// Exit code when another apply or adopt holds the lock
+pub const EXIT_LOCKED: i32 = 75;
+
 // Timing constants
 pub const SPINNER_DELAY_MS: u64 = 120;
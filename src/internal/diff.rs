@@ -0,0 +1,174 @@
+//! Line-based unified diffs for previews
+
+/// Unchanged lines shown around each change
+const CONTEXT: usize = 3;
+
+/// Largest LCS table we build; bigger inputs are reported without a line-level diff
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Keep,
+    Remove,
+    Add,
+}
+
+/// One step of the edit script, with the positions in `old` and `new` before the step
+#[derive(Debug, Clone, Copy)]
+struct Step {
+    op: Op,
+    old: usize,
+    new: usize,
+}
+
+/// Unified diff from `old` to `new` with `--- old_label` / `+++ new_label` headers.
+/// Returns no lines when the contents are identical.
+pub fn unified_diff(old_label: &str, new_label: &str, old: &str, new: &str) -> Vec<String> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    if a == b {
+        return Vec::new();
+    }
+
+    let mut out = vec![format!("--- {}", old_label), format!("+++ {}", new_label)];
+    if (a.len() + 1).saturating_mul(b.len() + 1) > MAX_CELLS {
+        out.push(format!(
+            "@@ {} -> {} lines, too large to diff line by line @@",
+            a.len(),
+            b.len()
+        ));
+        return out;
+    }
+
+    let steps = edit_script(&a, &b);
+    let changes: Vec<usize> = steps
+        .iter()
+        .enumerate()
+        .filter(|(_, step)| step.op != Op::Keep)
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let mut group_start = 0;
+    while group_start < changes.len() {
+        let mut group_end = group_start;
+        while group_end + 1 < changes.len()
+            && changes[group_end + 1] - changes[group_end] <= 2 * CONTEXT + 1
+        {
+            group_end += 1;
+        }
+
+        let first = changes[group_start].saturating_sub(CONTEXT);
+        let last = (changes[group_end] + CONTEXT + 1).min(steps.len());
+        let hunk = &steps[first..last];
+        let old_count = hunk.iter().filter(|step| step.op != Op::Add).count();
+        let new_count = hunk.iter().filter(|step| step.op != Op::Remove).count();
+        // An empty side is numbered by the line it follows
+        let old_start = hunk[0].old + usize::from(old_count > 0);
+        let new_start = hunk[0].new + usize::from(new_count > 0);
+        out.push(format!(
+            "@@ -{},{} +{},{} @@",
+            old_start, old_count, new_start, new_count
+        ));
+        for step in hunk {
+            out.push(match step.op {
+                Op::Keep => format!(" {}", a[step.old]),
+                Op::Remove => format!("-{}", a[step.old]),
+                Op::Add => format!("+{}", b[step.new]),
+            });
+        }
+        group_start = group_end + 1;
+    }
+    out
+}
+
+/// Color a line of `unified_diff` output for the terminal
+pub fn colorize(line: &str) -> String {
+    use crate::internal::color;
+    if line.starts_with("---") || line.starts_with("+++") {
+        color::bold(line)
+    } else if line.starts_with("@@") {
+        color::blue(line)
+    } else if line.starts_with('+') {
+        color::green(line)
+    } else if line.starts_with('-') {
+        color::red(line)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Shortest edit script via a longest-common-subsequence table
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Step> {
+    let width = b.len() + 1;
+    let mut lcs = vec![0usize; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut steps = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let op = if i < a.len() && j < b.len() && a[i] == b[j] {
+            Op::Keep
+        } else if j == b.len()
+            || (i < a.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            Op::Remove
+        } else {
+            Op::Add
+        };
+        steps.push(Step { op, old: i, new: j });
+        if op != Op::Add {
+            i += 1;
+        }
+        if op != Op::Remove {
+            j += 1;
+        }
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        assert_eq!(
+            unified_diff("old", "new", old, new),
+            vec![
+                "--- old",
+                "+++ new",
+                "@@ -1,5 +1,5 @@",
+                " a",
+                "-b",
+                "+B",
+                " c",
+                " d",
+                " e",
+                "@@ -9,3 +9,4 @@",
+                " i",
+                " j",
+                " k",
+                "+l",
+            ]
+        );
+        assert!(unified_diff("old", "new", old, old).is_empty());
+    }
+
+    #[test]
+    fn test_unified_diff_from_empty() {
+        assert_eq!(
+            unified_diff("/dev/null", "new", "", "x\n"),
+            vec!["--- /dev/null", "+++ new", "@@ -0,0 +1,1 @@", "+x"]
+        );
+    }
+}
@@ -1,49 +1,142 @@
 //! File operations utilities
 
 use anyhow::{Result, anyhow};
+use std::collections::HashSet;
 use std::env;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::OnceLock;
+use std::time::SystemTime;
 
 use crate::internal::constants;
+use crate::internal::logging::LogCommand;
 
-/// Get the owl root directory (~/.owl)
+static OWL_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use `dir` instead of ~/.owl for the rest of the process (`--config-dir`)
+pub fn set_owl_dir(dir: &Path) -> Result<()> {
+    let dir = std::path::absolute(dir)
+        .map_err(|e| anyhow!("Invalid config directory '{}': {}", dir.display(), e))?;
+    log::debug!("config dir overridden: {}", dir.display());
+    OWL_DIR_OVERRIDE.set(dir).ok();
+    Ok(())
+}
+
+/// Get the owl root directory (~/.owl, or the `--config-dir` override)
 pub fn owl_dir() -> Result<PathBuf> {
+    if let Some(dir) = OWL_DIR_OVERRIDE.get() {
+        return Ok(dir.clone());
+    }
     let home = env::var("HOME").map_err(|_| anyhow!("HOME environment variable not set"))?;
     Ok(PathBuf::from(home).join(constants::OWL_DIR))
 }
 
-/// Scan a directory for .owl files and add them to the files vector
-pub fn scan_directory_for_owl_files(directory: &Path, files: &mut Vec<String>) {
-    if let Ok(entries) = std::fs::read_dir(directory) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().is_some_and(|ext| ext == "owl")
-                && let Some(path_str) = path.to_str()
-            {
-                files.push(path_str.to_string());
-            }
-        }
+/// Shorten a path for display by replacing the home directory with `~`
+pub fn friendly_path(path: &str) -> String {
+    match env::var("HOME") {
+        Ok(home) => tilde_home(path, &home),
+        Err(_) => path.to_string(),
+    }
+}
+
+/// `path` with a leading `home` directory written as `~`; a `home` of `/` or one that
+/// only appears later in the path leaves it as is
+fn tilde_home(path: &str, home: &str) -> String {
+    let home = home.trim_end_matches('/');
+    if home.is_empty() {
+        return path.to_string();
+    }
+    match path.strip_prefix(home) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+        _ => path.to_string(),
     }
 }
 
 /// Open a file in the user's preferred editor
 pub fn open_editor(path: &str) -> Result<()> {
-    let editor = env::var("EDITOR").unwrap_or_else(|_| constants::DEFAULT_EDITOR.to_string());
+    open_editor_at(path, None)
+}
+
+/// Open a file in the user's preferred editor, at `line` when the editor supports it
+pub fn open_editor_at(path: &str, line: Option<usize>) -> Result<()> {
+    let editor = preferred_editor()?;
+    let (program, args) = editor_invocation(&editor, path, line);
 
-    Command::new(&editor)
-        .arg(path)
+    Command::new(&program)
+        .args(&args)
+        .logged()
         .status()
-        .map_err(|e| anyhow!("Failed to open editor '{}': {}", editor, e))
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                anyhow!(
+                    "Editor '{}' not found; set $VISUAL or $EDITOR to an installed editor",
+                    program
+                )
+            } else {
+                anyhow!("Failed to open editor '{}': {}", program, e)
+            }
+        })
         .and_then(|status| {
             if status.success() {
                 Ok(())
             } else {
-                Err(anyhow!("Editor '{}' exited with error", editor))
+                Err(anyhow!("Editor '{}' exited with error", program))
             }
         })
 }
 
+/// $VISUAL, then $EDITOR, then the first of the fallback editors found on PATH
+fn preferred_editor() -> Result<String> {
+    for var in ["VISUAL", "EDITOR"] {
+        if let Ok(editor) = env::var(var)
+            && !editor.trim().is_empty()
+        {
+            return Ok(editor);
+        }
+    }
+    let path = env::var_os("PATH").unwrap_or_default();
+    [constants::DEFAULT_EDITOR, "nano", "vi"]
+        .into_iter()
+        .find(|editor| env::split_paths(&path).any(|dir| dir.join(editor).is_file()))
+        .map(ToString::to_string)
+        .ok_or_else(|| anyhow!("No editor found; set $VISUAL or $EDITOR"))
+}
+
+/// Split an editor setting such as `code --wait` into a program and its arguments,
+/// then add the file, with a jump to `line` in the syntax the editor understands
+fn editor_invocation(editor: &str, path: &str, line: Option<usize>) -> (String, Vec<String>) {
+    let mut words = editor.split_whitespace().map(ToString::to_string);
+    let program = words
+        .next()
+        .unwrap_or_else(|| constants::DEFAULT_EDITOR.to_string());
+    let mut args: Vec<String> = words.collect();
+    let name = Path::new(&program)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    match line {
+        Some(line)
+            if matches!(
+                name.as_str(),
+                "vi" | "vim" | "nvim" | "nano" | "emacs" | "emacsclient" | "micro" | "kak" | "joe"
+            ) =>
+        {
+            args.push(format!("+{}", line));
+            args.push(path.to_string());
+        }
+        Some(line) if matches!(name.as_str(), "hx" | "helix" | "subl") => {
+            args.push(format!("{}:{}", path, line));
+        }
+        Some(line) if matches!(name.as_str(), "code" | "codium") => {
+            args.push("--goto".to_string());
+            args.push(format!("{}:{}", path, line));
+        }
+        _ => args.push(path.to_string()),
+    }
+    (program, args)
+}
+
 /// Find a config file in the standard locations
 pub fn find_config_file(arg: &str) -> Result<String> {
     let base_dir = owl_dir()?;
@@ -78,25 +171,97 @@ pub fn get_dotfile_path(filename: &str) -> Result<String> {
         .ok_or_else(|| anyhow!("Invalid path encoding"))
 }
 
-/// Get all config files from the owl directory (main, hosts, and groups)
-pub fn get_all_config_files() -> Result<Vec<String>> {
-    let owl = owl_dir()?;
-    let mut files = Vec::new();
+/// A config file found under the owl directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigFileEntry {
+    pub path: PathBuf,
+    /// The path below the owl directory, e.g. `hosts/laptop.owl`
+    pub relative: PathBuf,
+    /// Last modification; `None` when the filesystem does not report it
+    pub modified: Option<SystemTime>,
+}
 
-    // Check main config
-    let main_config = owl.join(constants::MAIN_CONFIG_FILE);
-    if main_config.exists()
-        && let Some(path_str) = main_config.to_str()
-    {
-        files.push(path_str.to_string());
-    }
+/// Every `.owl` file under the owl directory: main.owl first, then the rest sorted by
+/// relative path so the order is the same on every filesystem
+pub fn config_file_entries() -> Result<Vec<ConfigFileEntry>> {
+    walk_config_files(&owl_dir()?, constants::MAX_CONFIG_DEPTH)
+}
 
-    // Scan hosts directory
-    scan_directory_for_owl_files(&owl.join(constants::HOSTS_DIR), &mut files);
+/// Walk `root` for `.owl` files, at most `max_depth` directories down. Hidden
+/// directories (`.git`, `.state`) and the dotfiles directory are skipped, and a
+/// symlinked directory is entered only once, so links back up the tree end the walk.
+pub fn walk_config_files(root: &Path, max_depth: usize) -> Result<Vec<ConfigFileEntry>> {
+    let mut entries = Vec::new();
+    let mut visited = HashSet::new();
+    walk_dir(root, root, max_depth, &mut visited, &mut entries)?;
+    entries.sort_by(|a, b| {
+        let main =
+            |entry: &ConfigFileEntry| entry.relative != Path::new(constants::MAIN_CONFIG_FILE);
+        (main(a), &a.relative).cmp(&(main(b), &b.relative))
+    });
+    Ok(entries)
+}
 
-    // Scan groups directory
-    scan_directory_for_owl_files(&owl.join(constants::GROUPS_DIR), &mut files);
+fn walk_dir(
+    root: &Path,
+    dir: &Path,
+    depth_left: usize,
+    visited: &mut HashSet<PathBuf>,
+    entries: &mut Vec<ConfigFileEntry>,
+) -> Result<()> {
+    let Ok(canonical) = dir.canonicalize() else {
+        return Ok(());
+    };
+    if !visited.insert(canonical) {
+        log::debug!("config walk: {} was already visited", dir.display());
+        return Ok(());
+    }
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in read.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        // Follows symlinks, so a link to a directory is walked like one
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            let skipped = name.starts_with('.')
+                || (dir == root && name == constants::DOTFILES_DIR)
+                || depth_left == 0;
+            if !skipped {
+                walk_dir(root, &path, depth_left - 1, visited, entries)?;
+            }
+        } else if name.ends_with(constants::OWL_EXT) {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            entries.push(ConfigFileEntry {
+                modified: metadata.modified().ok(),
+                path,
+                relative,
+            });
+        }
+    }
+    Ok(())
+}
 
+/// Get all config files from the owl directory, main.owl first
+pub fn get_all_config_files() -> Result<Vec<String>> {
+    let entries = config_file_entries()?;
+    let files: Vec<String> = entries
+        .iter()
+        .filter_map(|entry| entry.path.to_str().map(ToString::to_string))
+        .collect();
+    log::debug!(
+        "found {} config files: {}",
+        files.len(),
+        entries
+            .iter()
+            .map(|entry| entry.relative.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
     Ok(files)
 }
 
@@ -108,6 +273,38 @@ pub fn get_main_config_path() -> Result<String> {
         .ok_or_else(|| anyhow!("Invalid path encoding"))
 }
 
+/// Replace `path` with `content` through a temporary file in the same directory, so an
+/// interrupted write leaves the old file whole. An existing file's permissions carry over.
+pub fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    use std::io::Write;
+
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Not a file path: {}", path.display()))?;
+    let temp = dir.join(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let write = || -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(content.as_bytes())?;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    };
+    write().map_err(|e| {
+        std::fs::remove_file(&temp).ok();
+        anyhow!("Failed to write '{}': {}", path.display(), e)
+    })
+}
+
 /// Result of adding a package to a config file
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AddPackageResult {
@@ -160,8 +357,7 @@ pub fn add_package_to_file(package_name: &str, file_path: &str) -> Result<AddPac
     }
 
     let new_content = lines.join("\n") + "\n";
-    std::fs::write(path, new_content)
-        .map_err(|e| anyhow!("Failed to write config file '{}': {}", file_path, e))?;
+    write_atomic(path, &new_content)?;
 
     Ok(AddPackageResult::Added)
 }
@@ -172,3 +368,119 @@ fn config_contains_package(package_name: &str, content: &str) -> bool {
     }
     content.lines().any(|line| line.trim() == package_name)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tilde_home_only_replaces_a_leading_home() {
+        assert_eq!(
+            tilde_home("/home/me/.owl/main.owl", "/home/me"),
+            "~/.owl/main.owl"
+        );
+        assert_eq!(tilde_home("/home/me", "/home/me/"), "~");
+        assert_eq!(
+            tilde_home("/mnt/backup/home/me/x", "/home/me"),
+            "/mnt/backup/home/me/x"
+        );
+        assert_eq!(tilde_home("/home/meg/x", "/home/me"), "/home/meg/x");
+        assert_eq!(tilde_home("/etc/pacman.conf", "/"), "/etc/pacman.conf");
+        assert_eq!(tilde_home("/etc/pacman.conf", ""), "/etc/pacman.conf");
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_and_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("main.owl");
+        write_atomic(&path, "@packages\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        write_atomic(&path, "@packages\nhtop\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "@packages\nhtop\n");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_editor_invocation_jumps_to_line() {
+        let args = |editor: &str, line| editor_invocation(editor, "main.owl", line);
+        assert_eq!(
+            args("nvim", Some(12)),
+            (
+                "nvim".to_string(),
+                vec!["+12".to_string(), "main.owl".to_string()]
+            )
+        );
+        assert_eq!(
+            args("/usr/bin/code --wait", Some(3)),
+            (
+                "/usr/bin/code".to_string(),
+                vec![
+                    "--wait".to_string(),
+                    "--goto".to_string(),
+                    "main.owl:3".to_string()
+                ]
+            )
+        );
+        assert_eq!(
+            args("gedit", Some(3)),
+            ("gedit".to_string(), vec!["main.owl".to_string()])
+        );
+        assert_eq!(
+            args("vim", None),
+            ("vim".to_string(), vec!["main.owl".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_walk_config_files_is_sorted_and_survives_symlink_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in [
+            "main.owl",
+            "a.owl",
+            "hosts/laptop.owl",
+            "modules/b/deep.owl",
+            "modules/a.owl",
+            "modules/notes.txt",
+            ".git/hooks/x.owl",
+            "dotfiles/app/theme.owl",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "@packages\n").unwrap();
+        }
+        // A link back to the root must not loop forever
+        std::os::unix::fs::symlink(root, root.join("modules/loop")).unwrap();
+
+        let relative = |max_depth| -> Vec<String> {
+            walk_config_files(root, max_depth)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.relative.display().to_string())
+                .collect()
+        };
+        assert_eq!(
+            relative(constants::MAX_CONFIG_DEPTH),
+            [
+                "main.owl",
+                "a.owl",
+                "hosts/laptop.owl",
+                "modules/a.owl",
+                "modules/b/deep.owl",
+            ]
+        );
+        assert_eq!(
+            relative(1),
+            ["main.owl", "a.owl", "hosts/laptop.owl", "modules/a.owl"]
+        );
+
+        let entries = walk_config_files(root, 1).unwrap();
+        assert_eq!(entries[0].path, root.join("main.owl"));
+        assert!(entries[0].modified.is_some());
+    }
+}
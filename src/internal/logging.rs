@@ -0,0 +1,115 @@
+//! Debug logging to stderr, controlled by `OWL_LOG` or `-vv`
+
+use std::process::Command;
+
+/// Environment variable holding an env_logger filter such as `debug` or `owl::core::pm=trace`
+const LOG_ENV: &str = "OWL_LOG";
+
+/// Install the logger. `OWL_LOG` wins; otherwise `-vv` enables owl's debug output.
+/// Without either, logging stays off.
+pub fn init(verbosity: u8) {
+    let filter = match std::env::var(LOG_ENV) {
+        Ok(filter) if !filter.trim().is_empty() => filter,
+        _ if verbosity >= 2 => "owl=debug".to_string(),
+        _ => return,
+    };
+
+    env_logger::Builder::new()
+        .parse_filters(&filter)
+        .format_timestamp(None)
+        .init();
+}
+
+/// Log a command's argv at debug level before it runs
+pub trait LogCommand {
+    fn logged(&mut self) -> &mut Self;
+}
+
+impl LogCommand for Command {
+    fn logged(&mut self) -> &mut Self {
+        log::debug!(target: "owl::exec", "run: {}", describe_command(self));
+        self
+    }
+}
+
+/// Render a command as a shell-like line, quoting arguments that need it
+fn describe_command(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:+@,".contains(c))
+            {
+                arg.into_owned()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Collect log messages emitted on the current thread, for tests
+#[cfg(test)]
+pub fn capture<F: FnOnce()>(operation: F) -> Vec<String> {
+    use std::cell::RefCell;
+    use std::sync::Once;
+
+    thread_local! {
+        static RECORDS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    }
+
+    struct CaptureLogger;
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            RECORDS.with(|records| {
+                if let Some(records) = records.borrow_mut().as_mut() {
+                    records.push(record.args().to_string());
+                }
+            });
+        }
+
+        fn flush(&self) {}
+    }
+
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        log::set_logger(&CaptureLogger).expect("logger already installed");
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+
+    RECORDS.with(|records| *records.borrow_mut() = Some(Vec::new()));
+    operation();
+    RECORDS.with(|records| records.borrow_mut().take().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_command_quotes_arguments() {
+        let mut command = Command::new("pacman");
+        command.args(["-S", "--needed", "my pkg", "it's"]);
+        assert_eq!(
+            describe_command(&command),
+            r"pacman -S --needed 'my pkg' 'it'\''s'"
+        );
+    }
+
+    #[test]
+    fn test_logged_command_is_captured() {
+        let logs = capture(|| {
+            Command::new("pacman").arg("-Qq").logged();
+        });
+        assert_eq!(logs, vec!["run: pacman -Qq".to_string()]);
+    }
+}
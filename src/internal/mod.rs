@@ -1,4 +1,6 @@
 pub mod color;
 pub mod constants;
+pub mod diff;
 pub mod files;
+pub mod logging;
 pub mod util;
@@ -5,6 +5,8 @@ use std::sync::{Arc, Mutex, mpsc};
 use std::thread;
 use std::time::Duration;
 
+use crate::internal::logging::LogCommand;
+
 /// Spinner display functionality
 pub mod spinner {
     use super::*;
@@ -76,6 +78,7 @@ pub mod command {
                 .stderr(Stdio::piped());
 
             let mut child = cmd
+                .logged()
                 .spawn()
                 .map_err(|e| anyhow!("Failed to spawn {}: {}", command, e))?;
 
@@ -266,6 +269,7 @@ pub fn execute_command_interactive(
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
+        .logged()
         .status()
         .map_err(|e| anyhow!("Failed to run {}: {}", command, e))
 }
@@ -524,6 +528,35 @@ where
     });
 }
 
+/// Shell-style match supporting `*` (any run) and `?` (one character)
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 /// Extract package name from common paru/pacman output patterns
 fn extract_package_name(line: &str) -> Option<String> {
     // Try parentheses pattern first
@@ -564,4 +597,16 @@ mod tests {
         );
         assert_eq!(result.unwrap(), 42);
     }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("htop", "htop"));
+        assert!(!glob_match("htop", "htop2"));
+        assert!(glob_match("python-*", "python-requests"));
+        assert!(glob_match("*-git", "neovim-git"));
+        assert!(glob_match("lib*32*", "lib32-mesa"));
+        assert!(glob_match("?top", "htop"));
+        assert!(!glob_match("python-*", "python"));
+        assert!(glob_match("*", ""));
+    }
 }
@@ -0,0 +1,366 @@
+//! Runs the owl binary entirely inside a temp directory using --config-dir and --state-file
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Fake pacman that reports `htop` as the only (explicitly) installed package
+const FAKE_PACMAN: &str = "#!/bin/sh
+case \"$1\" in
+    -Qq|-Qeq) echo htop ;;
+    -Q) echo 'htop 3.3.0-1' ;;
+    -Qu) exit 1 ;;
+esac
+exit 0
+";
+
+fn owl(sandbox: &Path, home: &Path, args: &[&str]) -> Output {
+    let path = format!(
+        "{}:{}",
+        sandbox.join("bin").display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    Command::new(env!("CARGO_BIN_EXE_owl"))
+        .arg("--config-dir")
+        .arg(sandbox.join("owl"))
+        .arg("--state-file")
+        .arg(sandbox.join("state.json"))
+        .args(args)
+        .env("HOME", home)
+        .env("PATH", path)
+        .env_remove("OWL_LOG")
+        .current_dir(sandbox)
+        .output()
+        .expect("failed to run owl")
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "owl failed\nstdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_adopt_and_dry_run_stay_in_sandbox() {
+    let sandbox = tempfile::tempdir().expect("failed to create sandbox");
+    let home = tempfile::tempdir().expect("failed to create fake home");
+    let sandbox = sandbox.path();
+
+    fs::create_dir_all(sandbox.join("bin")).unwrap();
+    let pacman = sandbox.join("bin").join("pacman");
+    fs::write(&pacman, FAKE_PACMAN).unwrap();
+    fs::set_permissions(&pacman, fs::Permissions::from_mode(0o755)).unwrap();
+
+    fs::create_dir_all(sandbox.join("owl")).unwrap();
+    fs::write(sandbox.join("owl").join("main.owl"), "@packages\n").unwrap();
+    // Adopt htop, then write it to the only config file
+    fs::write(sandbox.join("answers"), "a\n0\n").unwrap();
+
+    let adopt = owl(
+        sandbox,
+        home.path(),
+        &["--answers-file", "answers", "adopt", "htop"],
+    );
+    assert_success(&adopt);
+
+    let config = fs::read_to_string(sandbox.join("owl").join("main.owl")).unwrap();
+    assert!(config.lines().any(|line| line == "htop"), "{}", config);
+    let state: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(sandbox.join("state.json")).unwrap()).unwrap();
+    assert_eq!(state["managed"], serde_json::json!(["htop"]));
+
+    let apply = owl(sandbox, home.path(), &["--dry-run", "apply"]);
+    assert_success(&apply);
+
+    let untouched: Vec<_> = fs::read_dir(home.path()).unwrap().collect();
+    assert!(untouched.is_empty(), "owl wrote to HOME: {:?}", untouched);
+}
+
+#[test]
+fn test_adopt_yes_writes_to_the_named_file_without_prompts() {
+    let sandbox = tempfile::tempdir().expect("failed to create sandbox");
+    let home = tempfile::tempdir().expect("failed to create fake home");
+    let sandbox = sandbox.path();
+
+    fs::create_dir_all(sandbox.join("bin")).unwrap();
+    let pacman = sandbox.join("bin").join("pacman");
+    fs::write(&pacman, FAKE_PACMAN).unwrap();
+    fs::set_permissions(&pacman, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::create_dir_all(sandbox.join("owl")).unwrap();
+    fs::write(sandbox.join("owl").join("main.owl"), "@packages\n").unwrap();
+
+    // Without --yes there is nobody to answer the prompts
+    let prompted = owl(sandbox, home.path(), &["adopt", "htop"]);
+    assert!(!prompted.status.success());
+    assert!(String::from_utf8_lossy(&prompted.stderr).contains("--yes"));
+    assert!(!sandbox.join("state.json").exists());
+
+    let adopt = owl(
+        sandbox,
+        home.path(),
+        &["adopt", "--yes", "--to", "groups/cli.owl", "htop"],
+    );
+    assert_success(&adopt);
+    let stdout = String::from_utf8_lossy(&adopt.stdout);
+    assert!(stdout.contains("Adopted 1 package(s): htop"), "{}", stdout);
+
+    let group = fs::read_to_string(sandbox.join("owl/groups/cli.owl")).unwrap();
+    assert_eq!(group, "@packages\nhtop\n");
+
+    // main.owl pulls the new group in, so htop stays declared and managed
+    let apply = owl(sandbox, home.path(), &["--dry-run", "apply"]);
+    assert_success(&apply);
+    let stdout = String::from_utf8_lossy(&apply.stdout);
+    assert!(
+        !stdout.to_lowercase().contains("would remove"),
+        "{}",
+        stdout
+    );
+
+    // A file the config loader never reads is refused
+    let unread = owl(
+        sandbox,
+        home.path(),
+        &["adopt", "--yes", "--to", "notes/cli.owl", "htop"],
+    );
+    assert!(!unread.status.success());
+    assert!(!sandbox.join("owl/notes/cli.owl").exists());
+}
+
+#[test]
+fn test_remove_edits_config_and_state() {
+    let sandbox = tempfile::tempdir().expect("failed to create sandbox");
+    let home = tempfile::tempdir().expect("failed to create fake home");
+    let sandbox = sandbox.path();
+
+    fs::create_dir_all(sandbox.join("bin")).unwrap();
+    let pacman = sandbox.join("bin").join("pacman");
+    fs::write(&pacman, FAKE_PACMAN).unwrap();
+    fs::set_permissions(&pacman, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let main = sandbox.join("owl").join("main.owl");
+    fs::create_dir_all(sandbox.join("owl")).unwrap();
+    fs::write(&main, "# tools\n@packages\nhtop\ngit\n").unwrap();
+    fs::write(
+        sandbox.join("state.json"),
+        r#"{"untracked": [], "hidden": [], "managed": ["htop"]}"#,
+    )
+    .unwrap();
+
+    let dry_run = owl(sandbox, home.path(), &["--dry-run", "remove", "htop"]);
+    assert_success(&dry_run);
+    assert!(String::from_utf8_lossy(&dry_run.stdout).contains("-htop"));
+    assert_eq!(
+        fs::read_to_string(&main).unwrap(),
+        "# tools\n@packages\nhtop\ngit\n"
+    );
+
+    let remove = owl(
+        sandbox,
+        home.path(),
+        &["remove", "htop", "--keep-installed"],
+    );
+    assert_success(&remove);
+    assert_eq!(
+        fs::read_to_string(&main).unwrap(),
+        "# tools\n@packages\ngit\n"
+    );
+    let state: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(sandbox.join("state.json")).unwrap()).unwrap();
+    assert_eq!(state["managed"], serde_json::json!([]));
+}
+
+fn git(dir: &Path, home: &Path, args: &[&str]) {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .env("HOME", home)
+        .output()
+        .expect("failed to run git");
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_sync_pushes_and_pulls_config() {
+    let sandbox = tempfile::tempdir().expect("failed to create sandbox");
+    let home = tempfile::tempdir().expect("failed to create fake home");
+    let (sandbox, home) = (sandbox.path(), home.path());
+    fs::write(
+        home.join(".gitconfig"),
+        "[user]\n\tname = Owl Test\n\temail = owl@example.org\n[init]\n\tdefaultBranch = main\n",
+    )
+    .unwrap();
+
+    git(sandbox, home, &["init", "--quiet", "--bare", "remote.git"]);
+    git(sandbox, home, &["clone", "--quiet", "remote.git", "owl"]);
+    fs::write(sandbox.join("owl").join("main.owl"), "@packages\nhtop\n").unwrap();
+
+    let push = owl(sandbox, home, &["sync", "--push"]);
+    assert_success(&push);
+    let log = Command::new("git")
+        .args(["-C", "remote.git", "log", "--format=%s"])
+        .current_dir(sandbox)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&log.stdout), "owl: add htop\n");
+
+    // Another machine adds a group file
+    git(sandbox, home, &["clone", "--quiet", "remote.git", "other"]);
+    fs::create_dir_all(sandbox.join("other").join("groups")).unwrap();
+    fs::write(
+        sandbox.join("other").join("groups").join("wm.owl"),
+        "@packages\nsway\n",
+    )
+    .unwrap();
+    git(&sandbox.join("other"), home, &["add", "-A"]);
+    git(
+        &sandbox.join("other"),
+        home,
+        &["commit", "--quiet", "-m", "wm"],
+    );
+    git(&sandbox.join("other"), home, &["push", "--quiet"]);
+
+    // Local edits stop the pull before anything changes
+    fs::write(
+        sandbox.join("owl").join("main.owl"),
+        "@packages\nhtop\ngit\n",
+    )
+    .unwrap();
+    let dirty = owl(sandbox, home, &["sync"]);
+    assert!(!dirty.status.success());
+    assert!(String::from_utf8_lossy(&dirty.stderr).contains("uncommitted changes"));
+    assert!(!sandbox.join("owl").join("groups").exists());
+
+    git(
+        &sandbox.join("owl"),
+        home,
+        &["checkout", "--quiet", "--", "main.owl"],
+    );
+    let pull = owl(sandbox, home, &["sync"]);
+    assert_success(&pull);
+    assert!(String::from_utf8_lossy(&pull.stdout).contains("groups/wm.owl"));
+    assert!(sandbox.join("owl").join("groups").join("wm.owl").exists());
+}
+
+#[test]
+fn test_help_and_usage_errors_load_nothing() {
+    let sandbox = tempfile::tempdir().expect("failed to create sandbox");
+    let home = tempfile::tempdir().expect("failed to create fake home");
+    let sandbox = sandbox.path();
+
+    // Tripwires: a pacman that leaves a mark, and settings that warn when read
+    fs::create_dir_all(sandbox.join("bin")).unwrap();
+    let pacman = sandbox.join("bin").join("pacman");
+    fs::write(&pacman, "#!/bin/sh\ntouch \"$(dirname \"$0\")/ran\"\n").unwrap();
+    fs::set_permissions(&pacman, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::create_dir_all(sandbox.join("owl")).unwrap();
+    fs::write(sandbox.join("owl").join("settings.toml"), "pager = [\n").unwrap();
+    fs::write(sandbox.join("owl").join("main.owl"), "@bogus\n").unwrap();
+
+    let help = owl(sandbox, home.path(), &["--help"]);
+    assert_success(&help);
+    assert!(String::from_utf8_lossy(&help.stdout).contains("Usage"));
+    assert!(
+        help.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&help.stderr)
+    );
+
+    let typo = owl(sandbox, home.path(), &["lsit"]);
+    assert!(!typo.status.success());
+    let stderr = String::from_utf8_lossy(&typo.stderr);
+    assert!(stderr.contains("unrecognized subcommand"), "{}", stderr);
+    assert!(!stderr.contains("settings"), "{}", stderr);
+
+    assert!(!sandbox.join("bin").join("ran").exists(), "pacman was run");
+    assert!(!sandbox.join("state.json").exists());
+    let untouched: Vec<_> = fs::read_dir(home.path()).unwrap().collect();
+    assert!(untouched.is_empty(), "owl wrote to HOME: {:?}", untouched);
+}
+
+#[test]
+fn test_porcelain_dry_run_streams_json_events() {
+    let sandbox = tempfile::tempdir().expect("failed to create sandbox");
+    let home = tempfile::tempdir().expect("failed to create fake home");
+    let sandbox = sandbox.path();
+
+    fs::create_dir_all(sandbox.join("bin")).unwrap();
+    let pacman = sandbox.join("bin").join("pacman");
+    fs::write(&pacman, FAKE_PACMAN).unwrap();
+    fs::set_permissions(&pacman, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::create_dir_all(sandbox.join("owl")).unwrap();
+    fs::write(sandbox.join("owl").join("main.owl"), "@packages\nhtop\n").unwrap();
+
+    let output = owl(sandbox, home.path(), &["--porcelain", "--dry-run", "apply"]);
+    assert_success(&output);
+    let events: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("stdout holds only JSON lines"))
+        .collect();
+    assert_eq!(
+        events.first(),
+        Some(&serde_json::json!({"event": "start", "schema": 1, "command": "apply"}))
+    );
+    let phases: Vec<&str> = events
+        .iter()
+        .filter(|event| event["event"] == "phase_start")
+        .filter_map(|event| event["phase"].as_str())
+        .collect();
+    assert_eq!(phases, vec!["analysis", "packages", "dotfiles", "system"]);
+    let done = events.last().unwrap();
+    assert_eq!(done["event"], "done");
+    assert_eq!(done["summary"]["dry_run"], true);
+    assert_eq!(done["summary"]["to_install"], 0);
+    // People still get the usual report, on stderr
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Dry run mode"));
+
+    let dotfiles_only = owl(
+        sandbox,
+        home.path(),
+        &["--porcelain", "--dry-run", "apply", "--dotfiles-only"],
+    );
+    assert_success(&dotfiles_only);
+    let phases: Vec<String> = String::from_utf8_lossy(&dotfiles_only.stdout)
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|event| event["event"] == "phase_start")
+        .filter_map(|event| event["phase"].as_str().map(ToString::to_string))
+        .collect();
+    assert_eq!(phases, vec!["analysis", "dotfiles"]);
+
+    // A dry run with --plan prints the plan and stops there
+    let plan = owl(sandbox, home.path(), &["--dry-run", "apply", "--plan"]);
+    assert_success(&plan);
+    let stdout = String::from_utf8_lossy(&plan.stdout);
+    assert!(stdout.contains("nothing to do"), "{}", stdout);
+    assert!(!stdout.contains("[config]"), "{}", stdout);
+
+    let both = owl(
+        sandbox,
+        home.path(),
+        &["apply", "--packages-only", "--dotfiles-only"],
+    );
+    assert!(!both.status.success());
+    assert!(String::from_utf8_lossy(&both.stderr).contains("cannot be used with"));
+
+    // --only refuses names the config does not declare before doing anything
+    let only = owl(
+        sandbox,
+        home.path(),
+        &["--dry-run", "apply", "--only", "htop", "nope"],
+    );
+    assert!(!only.status.success());
+    let stderr = String::from_utf8_lossy(&only.stderr);
+    assert!(stderr.contains("does not declare: nope"), "{}", stderr);
+    assert!(!String::from_utf8_lossy(&only.stdout).contains("[config]"));
+}